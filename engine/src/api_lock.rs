@@ -0,0 +1,402 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An optional lockfile listing every item in the generated Rust, along
+//! with its signature. Once it exists, any change to the generated API,
+//! for instance because the headers changed upstream, fails the build
+//! until the lockfile is updated, so that a bindings crate can't change
+//! its own API without anybody noticing.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{Attribute, ForeignItem, ImplItem, Item, Meta, NestedMeta, TraitItem, Type};
+
+/// Set this environment variable to update the lockfile given to
+/// [`crate::Builder::api_lockfile`], rather than failing the build
+/// if the API has changed.
+pub(crate) const UPDATE_LOCKFILE_VAR: &str = "AUTOCXX_UPDATE_LOCK";
+
+const LOCKFILE_HEADER: &str = "# The API generated by autocxx. Don't edit this file by hand:
+# instead, build with AUTOCXX_UPDATE_LOCK=1 set to accept changes to the API.
+";
+
+/// How the generated API differs from that recorded in the lockfile given
+/// to [`crate::Builder::api_lockfile`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "build")))]
+pub struct ApiLockMismatch {
+    /// The lockfile.
+    pub lockfile: PathBuf,
+    /// Items in the lockfile which are no longer generated, or no longer
+    /// with the same signature.
+    pub removed: Vec<String>,
+    /// Items now generated which aren't in the lockfile, including those
+    /// whose signature has changed.
+    pub added: Vec<String>,
+}
+
+impl Display for ApiLockMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The generated API differs from that in {}. If this is expected, build with {}=1 set to update it.",
+            self.lockfile.display(),
+            UPDATE_LOCKFILE_VAR
+        )?;
+        write!(f, "\n--- {}\n+++ generated", self.lockfile.display())?;
+        for line in &self.removed {
+            write!(f, "\n-{}", line)?;
+        }
+        for line in &self.added {
+            write!(f, "\n+{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists each item in the generated Rust, one per line, as its path
+/// followed by its signature. Documentation isn't included, and neither
+/// are `#[doc(hidden)]` items.
+pub(crate) fn describe_api<'a>(rs: impl Iterator<Item = &'a TokenStream>) -> BTreeSet<String> {
+    let mut api = BTreeSet::new();
+    for rs in rs {
+        // e.g. in parse-only mode there's nothing to describe.
+        if let Ok(file) = syn::parse2::<syn::File>(rs.clone()) {
+            describe_items(&file.items, "", &mut api);
+        }
+    }
+    api
+}
+
+fn describe_items(items: &[Item], path: &str, api: &mut BTreeSet<String>) {
+    for item in items {
+        let mut item = item.clone();
+        if strip_item_docs(&mut item) {
+            continue;
+        }
+        match &mut item {
+            Item::Mod(m) => {
+                if let Some((_, items)) = &m.content {
+                    describe_items(items, &join(path, &m.ident.to_string()), api);
+                }
+            }
+            Item::Impl(imp) => {
+                let self_path = join(path, &type_name(&imp.self_ty));
+                if let Some((bang, trait_path, _)) = &imp.trait_ {
+                    record(api, &self_path, quote! { impl #bang #trait_path });
+                }
+                for impl_item in &mut imp.items {
+                    if strip_impl_item_docs(impl_item) {
+                        continue;
+                    }
+                    match impl_item {
+                        ImplItem::Method(m) => {
+                            let (attrs, vis, sig) = (&m.attrs, &m.vis, &m.sig);
+                            record(
+                                api,
+                                &join(&self_path, &sig.ident.to_string()),
+                                quote! { #(#attrs)* #vis #sig },
+                            )
+                        }
+                        ImplItem::Const(c) => {
+                            let (vis, ident, ty) = (&c.vis, &c.ident, &c.ty);
+                            record(
+                                api,
+                                &join(&self_path, &ident.to_string()),
+                                quote! { #vis const #ident: #ty },
+                            )
+                        }
+                        ImplItem::Type(t) => record(
+                            api,
+                            &join(&self_path, &t.ident.to_string()),
+                            t.to_token_stream(),
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+            Item::ForeignMod(fm) => {
+                for foreign_item in &mut fm.items {
+                    let (ident, attrs) = match foreign_item {
+                        ForeignItem::Fn(f) => (f.sig.ident.clone(), &mut f.attrs),
+                        ForeignItem::Type(t) => (t.ident.clone(), &mut t.attrs),
+                        ForeignItem::Static(s) => (s.ident.clone(), &mut s.attrs),
+                        _ => continue,
+                    };
+                    if !strip_docs(attrs) {
+                        record(
+                            api,
+                            &join(path, &ident.to_string()),
+                            foreign_item.to_token_stream(),
+                        )
+                    }
+                }
+            }
+            Item::Fn(f) => {
+                let (attrs, vis, sig) = (&f.attrs, &f.vis, &f.sig);
+                record(
+                    api,
+                    &join(path, &sig.ident.to_string()),
+                    quote! { #(#attrs)* #vis #sig },
+                )
+            }
+            Item::Struct(s) => {
+                for field in s.fields.iter_mut() {
+                    strip_docs(&mut field.attrs);
+                }
+                record(api, &join(path, &s.ident.to_string()), s.to_token_stream())
+            }
+            Item::Enum(e) => {
+                for variant in e.variants.iter_mut() {
+                    strip_docs(&mut variant.attrs);
+                }
+                record(api, &join(path, &e.ident.to_string()), e.to_token_stream())
+            }
+            Item::Trait(t) => {
+                for trait_item in &mut t.items {
+                    if let TraitItem::Method(m) = trait_item {
+                        strip_docs(&mut m.attrs);
+                        m.default = None;
+                        m.semi_token = Some(Default::default());
+                    }
+                }
+                record(api, &join(path, &t.ident.to_string()), t.to_token_stream())
+            }
+            Item::Type(t) => record(api, &join(path, &t.ident.to_string()), t.to_token_stream()),
+            Item::Const(c) => {
+                let (vis, ident, ty) = (&c.vis, &c.ident, &c.ty);
+                record(
+                    api,
+                    &join(path, &ident.to_string()),
+                    quote! { #vis const #ident: #ty },
+                )
+            }
+            Item::Use(u) => record(api, path, u.to_token_stream()),
+            _ => {}
+        }
+    }
+}
+
+/// As [`strip_docs`], for any item which has attributes.
+fn strip_item_docs(item: &mut Item) -> bool {
+    let attrs = match item {
+        Item::Mod(m) => &mut m.attrs,
+        Item::Impl(imp) => &mut imp.attrs,
+        Item::ForeignMod(fm) => &mut fm.attrs,
+        Item::Fn(f) => &mut f.attrs,
+        Item::Struct(s) => &mut s.attrs,
+        Item::Enum(e) => &mut e.attrs,
+        Item::Trait(t) => &mut t.attrs,
+        Item::Type(t) => &mut t.attrs,
+        Item::Const(c) => &mut c.attrs,
+        Item::Use(u) => &mut u.attrs,
+        _ => return false,
+    };
+    strip_docs(attrs)
+}
+
+fn strip_impl_item_docs(impl_item: &mut ImplItem) -> bool {
+    let attrs = match impl_item {
+        ImplItem::Method(m) => &mut m.attrs,
+        ImplItem::Const(c) => &mut c.attrs,
+        ImplItem::Type(t) => &mut t.attrs,
+        _ => return false,
+    };
+    strip_docs(attrs)
+}
+
+fn record(api: &mut BTreeSet<String>, path: &str, signature: TokenStream) {
+    api.insert(format!("{} {}", path, signature));
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}::{}", path, name)
+    }
+}
+
+/// The name used for the items within `impl Foo`.
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(typ) if typ.qself.is_none() => typ
+            .path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::"),
+        _ => ty.to_token_stream().to_string().replace(' ', ""),
+    }
+}
+
+/// Removes documentation, which doesn't form part of the API, and returns
+/// whether the item was `#[doc(hidden)]`.
+fn strip_docs(attrs: &mut Vec<Attribute>) -> bool {
+    let is_hidden = attrs.iter().any(|attr| {
+        matches!(attr.parse_meta(), Ok(Meta::List(list)) if list.path.is_ident("doc")
+            && list.nested.iter().any(|nested| matches!(nested, NestedMeta::Meta(Meta::Path(p)) if p.is_ident("hidden"))))
+    });
+    attrs.retain(|attr| !attr.path.is_ident("doc"));
+    is_hidden
+}
+
+/// Checks the generated API against that in `lockfile`. Changes to items
+/// matching any of the `allowed` patterns are accepted; otherwise, a
+/// mismatch is returned unless `update` is set. The lockfile is written
+/// whenever it doesn't exist yet, or the changes were accepted.
+pub(crate) fn check_api_lock(
+    lockfile: &Path,
+    api: &BTreeSet<String>,
+    allowed: &[String],
+    update: bool,
+) -> std::io::Result<Option<ApiLockMismatch>> {
+    let locked: Option<BTreeSet<String>> = if update {
+        None
+    } else {
+        match std::fs::read_to_string(lockfile) {
+            Ok(content) => Some(
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| line.to_string())
+                    .collect(),
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        }
+    };
+    if let Some(locked) = &locked {
+        if locked == api {
+            return Ok(None);
+        }
+        let is_disallowed = |line: &&String| !is_allowed(line, allowed);
+        let removed: Vec<_> = locked
+            .difference(api)
+            .filter(is_disallowed)
+            .cloned()
+            .collect();
+        let added: Vec<_> = api
+            .difference(locked)
+            .filter(is_disallowed)
+            .cloned()
+            .collect();
+        if !removed.is_empty() || !added.is_empty() {
+            return Ok(Some(ApiLockMismatch {
+                lockfile: lockfile.to_path_buf(),
+                removed,
+                added,
+            }));
+        }
+    }
+    let content: String = std::iter::once(LOCKFILE_HEADER.to_string())
+        .chain(api.iter().map(|line| format!("{}\n", line)))
+        .collect();
+    std::fs::write(lockfile, content)?;
+    Ok(None)
+}
+
+/// Whether the path of the item described by `line` matches any of the
+/// patterns, each of which is either a path such as `ffi::Goat::bleat`, or
+/// ends with `*` to match everything beginning with it, e.g. `ffi::Goat::*`.
+fn is_allowed(line: &str, allowed: &[String]) -> bool {
+    let path = line.split(' ').next().unwrap_or_default();
+    allowed
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == pattern,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_api_lock, describe_api, is_allowed};
+    use quote::quote;
+
+    #[test]
+    fn test_describe_api() {
+        let rs = quote! {
+            mod ffi {
+                /// A goat.
+                pub struct Goat {
+                    /// Its horns.
+                    pub horns: u32,
+                }
+                impl Goat {
+                    /// Bleats.
+                    pub fn bleat(&self) -> u32 {
+                        3
+                    }
+                    #[doc(hidden)]
+                    pub fn secret(&self) {}
+                }
+                pub use cxxbridge::feed;
+            }
+        };
+        let api: Vec<_> = describe_api(std::iter::once(&rs)).into_iter().collect();
+        assert_eq!(
+            api,
+            vec![
+                "ffi pub use cxxbridge :: feed ;",
+                "ffi::Goat pub struct Goat { pub horns : u32 , }",
+                "ffi::Goat::bleat pub fn bleat (& self) -> u32",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let allowed = vec!["ffi::Goat::*".to_string(), "ffi::feed".to_string()];
+        assert!(is_allowed("ffi::Goat::bleat pub fn bleat()", &allowed));
+        assert!(is_allowed("ffi::feed pub fn feed()", &allowed));
+        assert!(!is_allowed("ffi::feeder pub fn feeder()", &allowed));
+        assert!(!is_allowed("ffi::Goat pub struct Goat", &allowed));
+    }
+
+    #[test]
+    fn test_check_api_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let lockfile = dir.path().join("autocxx.lock");
+        let api = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect();
+        let original = api(&["ffi::a pub fn a ()", "ffi::b pub fn b ()"]);
+        // The lockfile is created the first time.
+        assert!(check_api_lock(&lockfile, &original, &[], false)
+            .unwrap()
+            .is_none());
+        assert!(check_api_lock(&lockfile, &original, &[], false)
+            .unwrap()
+            .is_none());
+        let changed = api(&["ffi::a pub fn a (x : u32)", "ffi::b pub fn b ()"]);
+        let mismatch = check_api_lock(&lockfile, &changed, &[], false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(mismatch.removed, vec!["ffi::a pub fn a ()"]);
+        assert_eq!(mismatch.added, vec!["ffi::a pub fn a (x : u32)"]);
+        // Allowed changes are accepted, and recorded.
+        assert!(
+            check_api_lock(&lockfile, &changed, &["ffi::a".to_string()], false)
+                .unwrap()
+                .is_none()
+        );
+        assert!(check_api_lock(&lockfile, &changed, &[], false)
+            .unwrap()
+            .is_none());
+        assert!(check_api_lock(&lockfile, &original, &[], true)
+            .unwrap()
+            .is_none());
+        assert!(check_api_lock(&lockfile, &original, &[], false)
+            .unwrap()
+            .is_none());
+    }
+}
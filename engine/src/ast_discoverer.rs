@@ -10,15 +10,15 @@ use std::collections::HashSet;
 
 use autocxx_parser::{
     directives::{EXTERN_RUST_FUN, EXTERN_RUST_TYPE},
-    RustFun, RustPath,
+    RustEnum, RustFun, RustPath,
 };
 use itertools::Itertools;
 use proc_macro2::Ident;
 use syn::{
     punctuated::Punctuated, Attribute, Binding, Expr, ExprAssign, ExprAssignOp, ExprAwait,
     ExprBinary, ExprBox, ExprBreak, ExprCast, ExprField, ExprGroup, ExprLet, ExprParen,
-    ExprReference, ExprTry, ExprType, ExprUnary, ImplItem, Item, ItemEnum, ItemStruct, Pat, PatBox,
-    PatReference, PatSlice, PatTuple, Path, ReturnType, Stmt, TraitItem, Type, TypeArray,
+    ExprReference, ExprTry, ExprType, ExprUnary, Fields, ImplItem, Item, ItemEnum, ItemStruct, Pat,
+    PatBox, PatReference, PatSlice, PatTuple, Path, ReturnType, Stmt, TraitItem, Type, TypeArray,
     TypeGroup, TypeParamBound, TypeParen, TypePtr, TypeReference, TypeSlice,
 };
 
@@ -27,6 +27,7 @@ pub(super) struct Discoveries {
     pub(super) cpp_list: HashSet<String>,
     pub(super) extern_rust_funs: Vec<RustFun>,
     pub(super) extern_rust_types: Vec<RustPath>,
+    pub(super) extern_rust_enums: Vec<RustEnum>,
 }
 
 impl Discoveries {
@@ -42,12 +43,14 @@ impl Discoveries {
         self.cpp_list.is_empty()
             && self.extern_rust_funs.is_empty()
             && self.extern_rust_types.is_empty()
+            && self.extern_rust_enums.is_empty()
     }
 
     pub(crate) fn extend(&mut self, other: Self) {
         self.cpp_list.extend(other.cpp_list);
         self.extern_rust_funs.extend(other.extern_rust_funs);
         self.extern_rust_types.extend(other.extern_rust_types);
+        self.extern_rust_enums.extend(other.extern_rust_enums);
     }
 }
 
@@ -80,10 +83,15 @@ impl<'b> PerModDiscoveries<'b> {
                         }
                     }
                 }
-                if Self::has_attr(&fun.attrs, EXTERN_RUST_FUN) {
+                if let Some(attr) = Self::find_attr(&fun.attrs, EXTERN_RUST_FUN) {
+                    // #[extern_rust_function(expected)] or
+                    // #[extern_rust_function(factory)]
+                    let modifier = attr.parse_args::<Ident>().ok();
                     self.discoveries.extern_rust_funs.push(RustFun {
                         path: self.deeper_path(&fun.sig.ident),
                         sig: fun.sig.clone(),
+                        returns_expected: matches!(&modifier, Some(id) if id == "expected"),
+                        is_factory: matches!(&modifier, Some(id) if id == "factory"),
                     });
                 }
             }
@@ -109,6 +117,23 @@ impl<'b> PerModDiscoveries<'b> {
                     self.search_trait_item(item)
                 }
             }
+            // Fieldless enums can be shared with C++ as enum classes,
+            // rather than being opaque.
+            Item::Enum(en)
+                if Self::has_attr(&en.attrs, EXTERN_RUST_TYPE)
+                    && en.generics.params.is_empty()
+                    && en.variants.iter().all(|v| matches!(v.fields, Fields::Unit)) =>
+            {
+                let mut definition = en.clone();
+                definition.attrs.retain(|attr| attr.path.is_ident("doc"));
+                for variant in definition.variants.iter_mut() {
+                    variant.attrs.retain(|attr| attr.path.is_ident("doc"));
+                }
+                self.discoveries.extern_rust_enums.push(RustEnum {
+                    path: self.deeper_path(&en.ident),
+                    definition,
+                });
+            }
             Item::Struct(ItemStruct { ident, attrs, .. })
             | Item::Enum(ItemEnum { ident, attrs, .. })
                 if Self::has_attr(attrs, EXTERN_RUST_TYPE) =>
@@ -394,7 +419,11 @@ impl<'b> PerModDiscoveries<'b> {
     }
 
     fn has_attr(attrs: &[Attribute], attr_name: &str) -> bool {
-        attrs.iter().any(|attr| {
+        Self::find_attr(attrs, attr_name).is_some()
+    }
+
+    fn find_attr<'a>(attrs: &'a [Attribute], attr_name: &str) -> Option<&'a Attribute> {
+        attrs.iter().find(|attr| {
             attr.path
                 .segments
                 .last()
@@ -516,12 +545,29 @@ mod tests {
     fn test_extern_rust_fun() {
         let mut discoveries = Discoveries::default();
         let itm = parse_quote! {
-            #[autocxx::extern_rust::extern_rust_fun]
+            #[autocxx::extern_rust::extern_rust_function]
             fn bar(a: cxx::UniquePtr<ffi::xxx>) {
             }
         };
         discoveries.search_item(&itm, None);
-        assert!(discoveries.extern_rust_funs.get(0).unwrap().sig.ident == "bar");
+        assert!(discoveries.extern_rust_funs.first().unwrap().sig.ident == "bar");
+        assert!(!discoveries.extern_rust_funs[0].returns_expected);
+        let itm = parse_quote! {
+            #[autocxx::extern_rust::extern_rust_function(expected)]
+            fn baz() -> Result<u32, String> {
+            }
+        };
+        discoveries.search_item(&itm, None);
+        assert!(discoveries.extern_rust_funs[1].returns_expected);
+        assert!(!discoveries.extern_rust_funs[1].is_factory);
+        let itm = parse_quote! {
+            #[autocxx::extern_rust::extern_rust_function(factory)]
+            fn new_bar() -> Bar {
+            }
+        };
+        discoveries.search_item(&itm, None);
+        assert!(discoveries.extern_rust_funs[2].is_factory);
+        assert!(!discoveries.extern_rust_funs[2].returns_expected);
     }
 
     #[test]
@@ -537,10 +583,43 @@ mod tests {
         assert!(
             discoveries
                 .extern_rust_types
-                .get(0)
+                .first()
                 .unwrap()
                 .get_final_ident()
                 == "Bar"
         );
     }
+
+    #[test]
+    fn test_extern_rust_enum() {
+        let mut discoveries = Discoveries::default();
+        let itm = parse_quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            enum Status {
+                Ready,
+                Busy = 4,
+            }
+        };
+        discoveries.search_item(&itm, None);
+        assert!(discoveries.extern_rust_types.is_empty());
+        let en = discoveries.extern_rust_enums.first().unwrap();
+        assert!(en.path.get_final_ident() == "Status");
+        assert!(en.definition.attrs.is_empty());
+        assert_eq!(en.definition.variants.len(), 2);
+        let itm = parse_quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            enum Shape {
+                Square(u32),
+            }
+        };
+        discoveries.search_item(&itm, None);
+        assert!(
+            discoveries
+                .extern_rust_types
+                .first()
+                .unwrap()
+                .get_final_ident()
+                == "Shape"
+        );
+    }
 }
@@ -0,0 +1,436 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A cache of the bindings generated by bindgen, so that we can avoid
+//! rerunning libclang when only the directives within an `include_cpp!`
+//! change, or when nothing has changed at all.
+//!
+//! Bindings are keyed by the header we pass to bindgen and the clang
+//! arguments, but not the allowlist. Instead, we note the allowlist with
+//! which they were generated: if a later request's allowlist is a subset,
+//! the bindings can be reused, since our analysis discards any APIs which
+//! aren't needed. If not, we regenerate bindings for the union of the two
+//! allowlists, so that toggling directives back and forth doesn't miss the
+//! cache. Bindings are discarded if any header file which bindgen included
+//! has since changed.
+//!
+//! Bindings are always cached in memory, which helps if several
+//! `include_cpp!` sections use the same headers. They can also be persisted
+//! to a directory (see [`crate::CppCodegenOptions::bindings_cache_dir`]), so
+//! that later runs of `autocxx-gen` or a build script, for instance in CI
+//! with a warm cache, needn't load libclang at all. Each file in that
+//! directory holds the bindings for one key, in a simple line-based format
+//! which begins with the version of autocxx which wrote it.
+//!
+//! Alongside the bindings we keep the source locations which libclang
+//! reported for the same headers, since finding those also means loading
+//! libclang.
+//!
+//! Only bindgen's output is cached, not our analysis of it. Almost every
+//! analysis phase depends on the directives as a whole: which items are
+//! POD, which are blocked or renamed, which overloads clash, and what the
+//! garbage collector keeps from the allowlist. So a change to any directive
+//! can change the analysis of items it doesn't mention, and there is no
+//! smaller delta to recompute. Parsing and analysis are cheap compared with
+//! running libclang anyway.
+//!
+//! Set `AUTOCXX_DISABLE_BINDINGS_CACHE` to disable the cache.
+
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use once_cell::sync::OnceCell;
+use tempfile::NamedTempFile;
+
+use crate::source_locations::{SourceLocation, SourceLocations};
+
+/// Identifies the format of persisted bindings. Bindings written by other
+/// versions of autocxx, which may use a different bindgen, are ignored.
+const FORMAT_HEADER: &str = concat!("autocxx bindings cache 3 ", env!("CARGO_PKG_VERSION"));
+
+/// The allowlist passed to bindgen, or `None` if bindgen was asked to
+/// generate everything.
+pub(crate) type BindgenAllowlist = Option<BTreeSet<String>>;
+
+struct CachedBindings {
+    allowlist: BindgenAllowlist,
+    /// Each header file included by bindgen, and a hash of its content.
+    dependencies: Vec<(String, u64)>,
+    /// Shared with callers, since bindings can be very large.
+    bindings: Arc<str>,
+    source_locations: Arc<SourceLocations>,
+}
+
+impl CachedBindings {
+    fn is_stale(&self) -> bool {
+        self.dependencies
+            .iter()
+            .any(|(filename, hash)| hash_file(filename) != Some(*hash))
+    }
+}
+
+pub(crate) enum CacheLookup {
+    /// Bindings which can be used for the requested allowlist, along with
+    /// the header files which bindgen included when generating them.
+    Hit {
+        bindings: Arc<str>,
+        source_locations: Arc<SourceLocations>,
+        dependencies: Vec<String>,
+    },
+    /// Bindgen must be run, with this allowlist.
+    Miss { allowlist: BindgenAllowlist },
+}
+
+fn cache() -> &'static Mutex<HashMap<u64, CachedBindings>> {
+    static CACHE: OnceCell<Mutex<HashMap<u64, CachedBindings>>> = OnceCell::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn is_enabled() -> bool {
+    std::env::var_os("AUTOCXX_DISABLE_BINDINGS_CACHE").is_none()
+}
+
+/// Computes the key under which bindings are cached. This is a hash
+/// (64-bit FNV-1a) which is specified here so that it's stable across
+/// Rust versions, and therefore suitable for persistent caches.
+pub(crate) fn cache_key(header: &str, clang_args: impl Iterator<Item = String>) -> u64 {
+    let hash = fnv1a(FNV_OFFSET_BASIS, header.as_bytes());
+    clang_args.fold(hash, |hash, arg| {
+        // Separate each argument so that "-a", "-b" differs from "-a-b".
+        fnv1a(fnv1a(hash, &[0]), arg.as_bytes())
+    })
+}
+
+/// Finds bindings suitable for the requested allowlist, or if there are
+/// none, the allowlist with which bindgen should be run.
+pub(crate) fn lookup(
+    key: u64,
+    requested: BindgenAllowlist,
+    persist_dir: Option<&Path>,
+) -> CacheLookup {
+    if !is_enabled() {
+        return CacheLookup::Miss {
+            allowlist: requested,
+        };
+    }
+    let mut cache = cache().lock().unwrap();
+    if let Entry::Vacant(entry) = cache.entry(key) {
+        if let Some(cached) = persist_dir.and_then(|dir| load(dir, key)) {
+            entry.insert(cached);
+        }
+    }
+    if cache.get(&key).is_some_and(CachedBindings::is_stale) {
+        cache.remove(&key);
+    }
+    let cached = cache.get(&key);
+    match (cached, requested) {
+        (None, requested) => CacheLookup::Miss {
+            allowlist: requested,
+        },
+        (Some(cached), requested) if covers(&cached.allowlist, &requested) => CacheLookup::Hit {
+            bindings: cached.bindings.clone(),
+            source_locations: cached.source_locations.clone(),
+            dependencies: cached
+                .dependencies
+                .iter()
+                .map(|(filename, _)| filename.clone())
+                .collect(),
+        },
+        (
+            Some(CachedBindings {
+                allowlist: None, ..
+            }),
+            _,
+        )
+        | (_, None) => CacheLookup::Miss { allowlist: None },
+        (
+            Some(CachedBindings {
+                allowlist: Some(cached),
+                ..
+            }),
+            Some(requested),
+        ) => CacheLookup::Miss {
+            allowlist: Some(cached.union(&requested).cloned().collect()),
+        },
+    }
+}
+
+/// Records the bindings which bindgen generated for an allowlist.
+pub(crate) fn store(
+    key: u64,
+    allowlist: BindgenAllowlist,
+    dependencies: Vec<String>,
+    bindings: Arc<str>,
+    source_locations: Arc<SourceLocations>,
+    persist_dir: Option<&Path>,
+) {
+    if !is_enabled() {
+        return;
+    }
+    let dependencies: Option<Vec<_>> = dependencies
+        .into_iter()
+        .map(|filename| hash_file(&filename).map(|hash| (filename, hash)))
+        .collect();
+    // If we can't read a header, we can't tell whether it changes, so
+    // it's not safe to cache these bindings.
+    if let Some(dependencies) = dependencies {
+        let cached = CachedBindings {
+            allowlist,
+            dependencies,
+            bindings,
+            source_locations,
+        };
+        if let Some(dir) = persist_dir {
+            if let Err(err) = save(dir, key, &cached) {
+                log::warn!("Unable to persist bindings to {}: {}", dir.display(), err);
+            }
+        }
+        cache().lock().unwrap().insert(key, cached);
+    }
+}
+
+fn cache_file(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{:016x}.bindings", key))
+}
+
+fn save(dir: &Path, key: u64, cached: &CachedBindings) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    // Write to a temporary file first so that concurrent builds never see
+    // a partially written file.
+    let mut file = NamedTempFile::new_in(dir)?;
+    writeln!(file, "{}", FORMAT_HEADER)?;
+    match &cached.allowlist {
+        None => writeln!(file, "allowlist all")?,
+        Some(items) => {
+            writeln!(file, "allowlist {}", items.len())?;
+            for item in items {
+                writeln!(file, "{}", item)?;
+            }
+        }
+    }
+    writeln!(file, "dependencies {}", cached.dependencies.len())?;
+    for (filename, hash) in &cached.dependencies {
+        writeln!(file, "{:016x} {}", hash, filename)?;
+    }
+    let source_locations = &cached.source_locations;
+    writeln!(file, "namespaces {}", source_locations.namespaces.len())?;
+    for namespace in &source_locations.namespaces {
+        writeln!(file, "{}", namespace)?;
+    }
+    let locations: Vec<_> = source_locations
+        .items
+        .iter()
+        .flat_map(|(name, locations)| locations.iter().map(move |location| (name, location)))
+        .collect();
+    writeln!(file, "locations {}", locations.len())?;
+    for (name, location) in locations {
+        writeln!(file, "{}\t{}\t{}", location.line, name, location.file)?;
+    }
+    writeln!(
+        file,
+        "statics {}",
+        source_locations.template_static_methods.len()
+    )?;
+    for method in &source_locations.template_static_methods {
+        writeln!(file, "{}", method)?;
+    }
+    file.write_all(cached.bindings.as_bytes())?;
+    file.persist(cache_file(dir, key))
+        .map_err(|err| err.error)?;
+    Ok(())
+}
+
+/// Reads persisted bindings, returning `None` if there are none or if
+/// they were written in some other format.
+fn load(dir: &Path, key: u64) -> Option<CachedBindings> {
+    let content = std::fs::read_to_string(cache_file(dir, key)).ok()?;
+    let mut rest = content.as_str();
+    let mut next_line = || {
+        let (line, remainder) = rest.split_once('\n')?;
+        rest = remainder;
+        Some(line)
+    };
+    if next_line()? != FORMAT_HEADER {
+        return None;
+    }
+    let allowlist = match next_line()?.strip_prefix("allowlist ")? {
+        "all" => None,
+        count => Some(
+            (0..count.parse::<usize>().ok()?)
+                .map(|_| next_line().map(str::to_string))
+                .collect::<Option<_>>()?,
+        ),
+    };
+    let count: usize = next_line()?.strip_prefix("dependencies ")?.parse().ok()?;
+    let dependencies = (0..count)
+        .map(|_| {
+            let (hash, filename) = next_line()?.split_once(' ')?;
+            Some((filename.to_string(), u64::from_str_radix(hash, 16).ok()?))
+        })
+        .collect::<Option<_>>()?;
+    let count: usize = next_line()?.strip_prefix("namespaces ")?.parse().ok()?;
+    let namespaces = (0..count)
+        .map(|_| next_line().map(str::to_string))
+        .collect::<Option<_>>()?;
+    let count: usize = next_line()?.strip_prefix("locations ")?.parse().ok()?;
+    let mut items: HashMap<String, Vec<SourceLocation>> = HashMap::new();
+    for _ in 0..count {
+        let mut fields = next_line()?.splitn(3, '\t');
+        let line = fields.next()?.parse().ok()?;
+        let name = fields.next()?;
+        let file = fields.next()?.to_string();
+        // Overloads were written in order, so are read back in order.
+        items
+            .entry(name.to_string())
+            .or_default()
+            .push(SourceLocation { file, line });
+    }
+    let count: usize = next_line()?.strip_prefix("statics ")?.parse().ok()?;
+    let template_static_methods = (0..count)
+        .map(|_| next_line().map(str::to_string))
+        .collect::<Option<_>>()?;
+    Some(CachedBindings {
+        allowlist,
+        dependencies,
+        bindings: rest.into(),
+        source_locations: Arc::new(SourceLocations {
+            items,
+            namespaces,
+            template_static_methods,
+        }),
+    })
+}
+
+fn covers(cached: &BindgenAllowlist, requested: &BindgenAllowlist) -> bool {
+    match (cached, requested) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(cached), Some(requested)) => requested.is_subset(cached),
+    }
+}
+
+fn hash_file(filename: &str) -> Option<u64> {
+    let content = std::fs::read(filename).ok()?;
+    Some(fnv1a(FNV_OFFSET_BASIS, &content))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(items: &[&str]) -> BindgenAllowlist {
+        Some(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    #[test]
+    fn test_bindings_cache() {
+        let mut header = tempfile::NamedTempFile::new().unwrap();
+        write!(header, "void a(); void b();").unwrap();
+        let filename = header.path().to_str().unwrap().to_string();
+        let key = cache_key(&filename, std::iter::empty());
+        assert!(matches!(
+            lookup(key, allowlist(&["a"]), None),
+            CacheLookup::Miss { allowlist } if allowlist == self::allowlist(&["a"])
+        ));
+        store(
+            key,
+            allowlist(&["a"]),
+            vec![filename.clone()],
+            "a".into(),
+            Arc::default(),
+            None,
+        );
+        assert!(matches!(
+            lookup(key, allowlist(&["a"]), None),
+            CacheLookup::Hit { bindings, dependencies, .. } if &*bindings == "a" && dependencies == vec![filename.clone()]
+        ));
+        // Adding a directive means bindgen must generate both.
+        assert!(matches!(
+            lookup(key, allowlist(&["b"]), None),
+            CacheLookup::Miss { allowlist } if allowlist == self::allowlist(&["a", "b"])
+        ));
+        store(
+            key,
+            allowlist(&["a", "b"]),
+            vec![filename],
+            "ab".into(),
+            Arc::default(),
+            None,
+        );
+        assert!(matches!(
+            lookup(key, allowlist(&["b"]), None),
+            CacheLookup::Hit { bindings, .. } if &*bindings == "ab"
+        ));
+        // Changing the header invalidates the bindings.
+        write!(header, "void c();").unwrap();
+        assert!(matches!(
+            lookup(key, allowlist(&["b"]), None),
+            CacheLookup::Miss { allowlist } if allowlist == self::allowlist(&["b"])
+        ));
+    }
+
+    #[test]
+    fn test_persisted_bindings() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut header = tempfile::NamedTempFile::new().unwrap();
+        write!(header, "void a();").unwrap();
+        let filename = header.path().to_str().unwrap().to_string();
+        let key = cache_key(&filename, ["-DFOO".to_string()].into_iter());
+        let bindings: Arc<str> = "mod root {\n}\n".into();
+        let location = |line| SourceLocation {
+            file: filename.clone(),
+            line,
+        };
+        let source_locations = Arc::new(SourceLocations {
+            items: [
+                ("a".to_string(), vec![location(1)]),
+                ("ns::b".to_string(), vec![location(2), location(3)]),
+            ]
+            .into_iter()
+            .collect(),
+            namespaces: ["ns".to_string()].into_iter().collect(),
+            template_static_methods: ["ns::b".to_string()].into_iter().collect(),
+        });
+        store(
+            key,
+            allowlist(&["a", "b"]),
+            vec![filename.clone()],
+            bindings.clone(),
+            source_locations.clone(),
+            Some(dir.path()),
+        );
+        let loaded = load(dir.path(), key).unwrap();
+        assert!(loaded.allowlist == allowlist(&["a", "b"]));
+        assert_eq!(loaded.dependencies.len(), 1);
+        assert_eq!(loaded.dependencies[0].0, filename);
+        assert_eq!(&*loaded.bindings, &*bindings);
+        assert_eq!(loaded.source_locations, source_locations);
+        assert!(!loaded.is_stale());
+        store(
+            key,
+            None,
+            Vec::new(),
+            bindings,
+            Arc::default(),
+            Some(dir.path()),
+        );
+        assert!(load(dir.path(), key).unwrap().allowlist.is_none());
+    }
+}
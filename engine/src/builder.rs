@@ -6,10 +6,17 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use autocxx_parser::file_locations::FileLocationStrategy;
+use autocxx_parser::{file_locations::FileLocationStrategy, is_supported_cpp_standard};
 use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{ext::IdentExt, parse_quote, AttrStyle, Item};
 
-use crate::{strip_system_headers, CppCodegenOptions, ParseError, RebuildDependencyRecorder};
+use crate::api_lock::{check_api_lock, describe_api, ApiLockMismatch, UPDATE_LOCKFILE_VAR};
+use crate::early_typecheck::{typecheck, GeneratedCppError, GeneratedFile};
+use crate::{
+    strip_system_headers, CppCodegenOptions, CppOutputLayout, ParseError,
+    RebuildDependencyRecorder, ShimVisibility, TypeMapper, WarningSuppression,
+};
 use std::ffi::OsString;
 use std::io::Write;
 use std::marker::PhantomData;
@@ -33,6 +40,23 @@ pub enum BuilderError {
     NoIncludeCxxMacrosFound,
     /// Unable to create one of the directories to which we need to write
     UnableToCreateDirectory(std::io::Error, PathBuf),
+    /// The compiler failed to create a precompiled header.
+    UnableToPrecompileHeader(std::io::Error, PathBuf),
+    /// The compiler failed to build the code generated for an
+    /// `include_cpp!` which chose its own C++ standard.
+    UnableToCompileBlock(cc::Error),
+    /// The C++ standard requested using [`Builder::cpp_standard`] isn't
+    /// one which autocxx supports.
+    UnsupportedCppStandard(String),
+    /// The compiler failed to build the generated code in
+    /// [`Builder::build_header_only`].
+    UnableToCompileHeaderOnly(cc::Error),
+    /// The generated C++ didn't compile, as found by
+    /// [`Builder::early_typecheck`].
+    GeneratedCppInvalid(Vec<GeneratedCppError>),
+    /// The generated API differs from that recorded in the lockfile given
+    /// to [`Builder::api_lockfile`].
+    ApiChanged(ApiLockMismatch),
 }
 
 impl Display for BuilderError {
@@ -43,6 +67,17 @@ impl Display for BuilderError {
             BuilderError::FileWriteFail(ee, pb) => write!(f, "Unable to write to {}: {}", pb.to_string_lossy(), ee)?,
             BuilderError::NoIncludeCxxMacrosFound => write!(f, "No include_cpp! macro found")?,
             BuilderError::UnableToCreateDirectory(ee, pb) => write!(f, "Unable to create directory {}: {}", pb.to_string_lossy(), ee)?,
+            BuilderError::UnableToPrecompileHeader(ee, pb) => write!(f, "Unable to precompile header {}: {}", pb.to_string_lossy(), ee)?,
+            BuilderError::UnableToCompileBlock(ee) => write!(f, "Unable to compile the code generated for an include_cpp! with its own cpp_standard!: {}", ee)?,
+            BuilderError::UnsupportedCppStandard(std) => write!(f, "Unsupported C++ standard {}; autocxx requires C++14 or later, e.g. \"c++17\" or \"gnu++17\"", std)?,
+            BuilderError::UnableToCompileHeaderOnly(ee) => write!(f, "Unable to compile the code generated for header-only bindings: {}", ee)?,
+            BuilderError::GeneratedCppInvalid(errors) => {
+                write!(f, "The C++ generated by autocxx doesn't compile:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+            }
+            BuilderError::ApiChanged(mismatch) => write!(f, "{}", mismatch)?,
         }
         Ok(())
     }
@@ -51,6 +86,15 @@ impl Display for BuilderError {
 #[cfg_attr(feature = "nightly", doc(cfg(feature = "build")))]
 pub type BuilderBuild = cc::Build;
 
+/// The header we generate, including the header requested by the user,
+/// which is compiled into a precompiled header.
+const PCH_HEADER_NAME: &str = "autocxx_pch.h";
+
+/// The single file which includes all the generated C++, for
+/// [`Builder::build_header_only`], and the name of the library built
+/// from it.
+const HEADER_ONLY_NAME: &str = "autocxx_header_only";
+
 /// For test purposes only, a [`cc::Build`] and lists of Rust and C++
 /// files generated.
 #[cfg_attr(feature = "nightly", doc(cfg(feature = "build")))]
@@ -81,9 +125,18 @@ pub struct Builder<'a, BuilderContext> {
     rs_file: PathBuf,
     autocxx_incs: Vec<OsString>,
     extra_clang_args: Vec<String>,
+    clang_target: Option<String>,
     dependency_recorder: Option<Box<dyn RebuildDependencyRecorder>>,
     custom_gendir: Option<PathBuf>,
     auto_allowlist: bool,
+    precompiled_header: Option<String>,
+    cpp_standard: Option<String>,
+    rs_module_tree: bool,
+    header_only: bool,
+    early_typecheck: bool,
+    cross_language_lto: bool,
+    api_lockfile: Option<PathBuf>,
+    allowed_api_changes: Vec<String>,
     cpp_codegen_options: CppCodegenOptions<'a>,
     // This member is to ensure that this type is parameterized
     // by a BuilderContext. The goal is to balance three needs:
@@ -95,7 +148,7 @@ pub struct Builder<'a, BuilderContext> {
     ctx: PhantomData<BuilderContext>,
 }
 
-impl<CTX: BuilderContext> Builder<'_, CTX> {
+impl<'a, CTX: BuilderContext> Builder<'a, CTX> {
     #[doc(hidden)]
     pub fn new(
         rs_file: impl AsRef<Path>,
@@ -109,9 +162,18 @@ impl<CTX: BuilderContext> Builder<'_, CTX> {
                 .map(|s| s.as_ref().to_os_string())
                 .collect(),
             extra_clang_args: Vec::new(),
+            clang_target: None,
             dependency_recorder: CTX::get_dependency_recorder(),
             custom_gendir: None,
             auto_allowlist: false,
+            precompiled_header: None,
+            cpp_standard: None,
+            rs_module_tree: false,
+            header_only: false,
+            early_typecheck: false,
+            cross_language_lto: false,
+            api_lockfile: None,
+            allowed_api_changes: Vec::new(),
             cpp_codegen_options: CppCodegenOptions::default(),
             ctx: PhantomData,
         }
@@ -123,6 +185,15 @@ impl<CTX: BuilderContext> Builder<'_, CTX> {
         self
     }
 
+    /// The target for which to generate bindings, as a clang target
+    /// triple, so that the layouts baked into POD types are those of that
+    /// target. Within a build script there's no need: bindgen uses cargo's
+    /// `TARGET`. A `--target` given in [`Builder::extra_clang_args`] wins.
+    pub fn clang_target(mut self, clang_target: &str) -> Self {
+        self.clang_target = Some(clang_target.to_string());
+        self
+    }
+
     /// Where to generate the code.
     pub fn custom_gendir(mut self, custom_gendir: PathBuf) -> Self {
         self.custom_gendir = Some(custom_gendir);
@@ -148,9 +219,10 @@ impl<CTX: BuilderContext> Builder<'_, CTX> {
     /// * You're using multiple FFI mods
     /// * You've got usages scattered across files beyond that with the
     ///   `include_cpp` invocation
-    /// * You're using `use` statements to rename mods or items. If this
-    /// proves to be a promising or helpful direction, autocxx would be happy
-    /// to accept pull requests to remove some of these limitations.
+    /// * You're using `use` statements to rename mods or items.
+    ///
+    /// If this proves to be a promising or helpful direction, autocxx would
+    /// be happy to accept pull requests to remove some of these limitations.
     pub fn auto_allowlist(mut self, do_it: bool) -> Self {
         self.auto_allowlist = do_it;
         self
@@ -180,15 +252,210 @@ impl<CTX: BuilderContext> Builder<'_, CTX> {
         self
     }
 
+    /// Whether to emit `#line` directives in the generated C++, such that
+    /// compiler errors point to the original header declaring the API.
+    pub fn emit_line_directives(mut self, do_it: bool) -> Self {
+        self.cpp_codegen_options.emit_line_directives = do_it;
+        self
+    }
+
+    /// How to lay out the generated C++ into files: a single file per
+    /// `include_cpp!`, or several which can be built in parallel.
+    /// See [`CppOutputLayout`].
+    pub fn cpp_output_layout(mut self, output_layout: CppOutputLayout) -> Self {
+        self.cpp_codegen_options.output_layout = output_layout;
+        self
+    }
+
+    /// Which compiler warnings to suppress within the generated C++.
+    /// See [`WarningSuppression`].
+    pub fn warning_suppression(mut self, warning_suppression: WarningSuppression) -> Self {
+        self.cpp_codegen_options.warning_suppression = warning_suppression;
+        self
+    }
+
+    /// The visibility of the C++ shim functions generated by autocxx,
+    /// for instance to avoid exporting them from a shared library.
+    /// See [`ShimVisibility`].
+    pub fn shim_visibility(mut self, shim_visibility: ShimVisibility) -> Self {
+        self.cpp_codegen_options.shim_visibility = shim_visibility;
+        self
+    }
+
+    /// Persist the output of bindgen into this directory, so that later
+    /// builds with unchanged headers needn't run libclang at all, for
+    /// instance in CI with a warm cache. See
+    /// [`CppCodegenOptions::bindings_cache_dir`].
+    pub fn bindings_cache_dir(mut self, dir: PathBuf) -> Self {
+        self.cpp_codegen_options.bindings_cache_dir = Some(dir);
+        self
+    }
+
+    /// Whether to additionally generate a plain C header for the Rust
+    /// functions exposed using `extern_rust_function`, alongside the
+    /// C++ headers. See [`CppCodegenOptions::c_header`].
+    pub fn c_header(mut self, do_it: bool) -> Self {
+        self.cpp_codegen_options.c_header = do_it;
+        self
+    }
+
+    /// Whether to write each `mod` within the generated Rust to a file of
+    /// its own, rather than generating one enormous `mod` for each
+    /// `include_cpp!`. IDEs such as rust-analyzer cope much better with
+    /// this, for instance to go to the definition of a generated item.
+    /// The files are named using a hash of the path to each `mod`, so
+    /// the names are stable between builds.
+    pub fn rs_module_tree(mut self, do_it: bool) -> Self {
+        self.rs_module_tree = do_it;
+        self
+    }
+
+    /// Represent some C++ types by existing Rust types, as decided by the
+    /// given [`TypeMapper`]. This is like using `extern_cpp_type!` for each
+    /// such type, but allows a crate to supply mappings for types it knows
+    /// about, wherever they turn up. May be called more than once; the
+    /// first mapper to map a given type wins.
+    pub fn type_mapper(mut self, mapper: impl TypeMapper + 'a) -> Self {
+        self.cpp_codegen_options.type_mappers.push(Box::new(mapper));
+        self
+    }
+
+    /// Compile the given header (typically a large SDK header) into a
+    /// precompiled header, and use that in all the C++ which is built using
+    /// the returned [`cc::Build`]. The header is named as it would be in an
+    /// `#include` directive.
+    ///
+    /// The precompiled header isn't cached: it's rebuilt each time the
+    /// build script runs, so it only saves time when several translation
+    /// units are built with it: your own C++ files added to the returned
+    /// [`cc::Build`], or the generated C++ sharded using
+    /// [`Builder::cpp_output_layout`].
+    ///
+    /// The precompiled header is built using the compiler and flags of the
+    /// [`cc::Build`] as it stands when returned to you, plus any
+    /// [`Builder::extra_clang_args`]. The compiler will ignore (or reject)
+    /// the precompiled header if any flags you add later change its meaning,
+    /// e.g. a different `-std`, so pass such flags as `extra_clang_args`.
+    /// Precompiled headers are supported for GCC and clang; with other
+    /// compilers, or if the compiler can't be determined, this does nothing.
+    pub fn use_precompiled_header(mut self, header: impl AsRef<str>) -> Self {
+        self.precompiled_header = Some(header.as_ref().to_string());
+        self
+    }
+
+    /// Select the C++ standard, e.g. `"c++17"` or `"gnu++20"`, both for
+    /// bindgen's parsing of your headers and for the returned
+    /// [`cc::Build`]. This is better than passing `-std` as one of the
+    /// [`Builder::extra_clang_args`] since it's checked against the
+    /// standards autocxx supports (C++14 and later), and the two can't
+    /// disagree. An `include_cpp!` can override this using
+    /// `cpp_standard!`.
+    pub fn cpp_standard(mut self, standard: impl AsRef<str>) -> Self {
+        self.cpp_standard = Some(standard.as_ref().to_string());
+        self
+    }
+
+    /// Check that the generated C++ compiles, using a quick syntax-only
+    /// compile of each generated file before returning the [`cc::Build`].
+    /// Any errors are reported as [`BuilderError::GeneratedCppInvalid`],
+    /// naming the C++ API for which the offending code was generated,
+    /// rather than as compiler errors about generated files once you
+    /// come to build them. As with [`Builder::use_precompiled_header`],
+    /// this uses the compiler and flags of the [`cc::Build`] plus any
+    /// [`Builder::extra_clang_args`]. If the compiler can't be
+    /// determined, this does nothing.
+    pub fn early_typecheck(mut self, do_it: bool) -> Self {
+        self.early_typecheck = do_it;
+        self
+    }
+
+    /// Prepare the generated C++ for cross-language link-time optimization,
+    /// so that the shims between Rust and C++ can be inlined into their
+    /// callers. If the compiler is clang, the returned [`cc::Build`] is
+    /// given `-flto=thin`, so that the generated C++ is compiled to LLVM
+    /// bitcode. The C++ generated by cxx and the shims generated by autocxx
+    /// are separate translation units, so it's the linker, not the
+    /// compiler, which optimizes them together, along with the Rust code
+    /// which calls them. Other compilers are left alone, with a warning.
+    /// You must also build your Rust code with
+    /// `-Clinker-plugin-lto`, and link using a linker which understands
+    /// LLVM bitcode such as `lld`, e.g. by setting `RUSTFLAGS` to
+    /// `-Clinker-plugin-lto -Clinker=clang -Clink-arg=-fuse-ld=lld`.
+    /// The version of LLVM used by clang must be compatible with that used
+    /// by rustc.
+    pub fn cross_language_lto(mut self, do_it: bool) -> Self {
+        self.cross_language_lto = do_it;
+        self
+    }
+
+    /// Record the generated API in the given lockfile, conventionally
+    /// `autocxx.lock` alongside `Cargo.toml`: one line per generated item,
+    /// giving its path and signature. The lockfile is written by the first
+    /// build. After that, if the API changes, for instance because the
+    /// headers changed upstream, the build fails with
+    /// [`BuilderError::ApiChanged`], showing the difference. Set the
+    /// `AUTOCXX_UPDATE_LOCK` environment variable to accept the changes
+    /// and update the lockfile. Check the lockfile into source control so
+    /// that changes to your API show up in review.
+    pub fn api_lockfile(mut self, lockfile: impl AsRef<Path>) -> Self {
+        self.api_lockfile = Some(lockfile.as_ref().to_path_buf());
+        self
+    }
+
+    /// Accept changes to the generated items matching `pattern` without
+    /// failing the build, even though they differ from the lockfile given
+    /// to [`Builder::api_lockfile`], which is then updated. The pattern is
+    /// the path of an item, such as `ffi::Goat::bleat`, or ends with `*`
+    /// to match every path beginning with it, such as `ffi::Goat::*`.
+    /// May be called more than once.
+    pub fn allow_api_changes(mut self, pattern: impl AsRef<str>) -> Self {
+        self.allowed_api_changes.push(pattern.as_ref().to_string());
+        self
+    }
+
     /// Build autocxx C++ files and return a cc::Build you can use to build
     /// more from a build.rs file.
     pub fn build(self) -> Result<BuilderBuild, BuilderError> {
         self.build_listing_files().map(|r| r.0)
     }
 
+    /// For bindings to header-only libraries, where you have no C++ of your
+    /// own to build: generate the bindings, and compile all the generated
+    /// C++ as a single translation unit with minimal flags, so there's no
+    /// [`cc::Build`] to configure and compile. Cargo is told to link the
+    /// result, and to rerun the build script if the `.rs` file or any of
+    /// the headers change.
+    pub fn build_header_only(mut self) -> Result<(), BuilderError> {
+        if let Some(recorder) = &self.dependency_recorder {
+            recorder.record_header_file_dependency(&self.rs_file.to_string_lossy());
+        }
+        self.header_only = true;
+        let BuilderSuccess(mut builder, _, _) = self.build_listing_files()?;
+        builder
+            .warnings(false)
+            .try_compile(HEADER_ONLY_NAME)
+            .map_err(BuilderError::UnableToCompileHeaderOnly)
+    }
+
     /// For use in tests only, this does the build and returns additional information
     /// about the files generated which can subsequently be examined for correctness.
-    pub fn build_listing_files(self) -> Result<BuilderSuccess, BuilderError> {
+    pub fn build_listing_files(mut self) -> Result<BuilderSuccess, BuilderError> {
+        if let (Some(recorder), Some(lockfile)) = (&self.dependency_recorder, &self.api_lockfile) {
+            recorder.record_header_file_dependency(&lockfile.to_string_lossy());
+        }
+        let std_flag = match &self.cpp_standard {
+            Some(standard) if !is_supported_cpp_standard(standard) => {
+                return Err(BuilderError::UnsupportedCppStandard(standard.clone()))
+            }
+            Some(standard) => Some(format!("-std={}", standard)),
+            None => None,
+        };
+        // Later arguments win, so this overrides autocxx's default standard.
+        self.extra_clang_args.extend(std_flag.clone());
+        if let Some(clang_target) = &self.clang_target {
+            self.extra_clang_args
+                .insert(0, format!("--target={}", clang_target));
+        }
         let clang_args = &self
             .extra_clang_args
             .iter()
@@ -230,35 +497,159 @@ impl<CTX: BuilderContext> Builder<'_, CTX> {
             )
             .map_err(BuilderError::ParseError)?;
         let mut counter = 0;
-        let mut builder = cc::Build::new();
-        builder.cpp(true);
+        let mut base_builder = cc::Build::new();
+        base_builder.cpp(true);
+        base_builder.includes(parsed_file.include_dirs());
+        if self.cross_language_lto {
+            enable_lto(&mut base_builder);
+        }
+        let mut builder = base_builder.clone();
+        if let Some(std_flag) = &std_flag {
+            builder.flag_if_supported(std_flag);
+        }
         let mut generated_rs = Vec::new();
         let mut generated_cpp = Vec::new();
-        builder.includes(parsed_file.include_dirs());
+        // For header-only builds, the generated .cxx files are all included
+        // into one file instead of being built separately.
+        let mut header_only_files = Vec::new();
+        let mut typecheck_errors: Vec<GeneratedCppError> = Vec::new();
+        if let Some(header) = &self.precompiled_header {
+            let pch_header = write_to_file(
+                &incdir,
+                PCH_HEADER_NAME,
+                format!("#include \"{}\"\n", header).as_bytes(),
+            )?;
+            if precompile_header(&builder, &pch_header, &self.extra_clang_args)? {
+                builder.flag("-include").flag(&pch_header.to_string_lossy());
+            }
+        }
         for include_cpp in parsed_file.get_cpp_buildables() {
             let generated_code = include_cpp
                 .generate_h_and_cxx(&self.cpp_codegen_options)
                 .map_err(BuilderError::InvalidCxx)?;
+            // An include_cpp! which chose its own C++ standard has its
+            // generated code built separately, since cc applies the same
+            // flags to every file.
+            let own_standard = include_cpp.cpp_standard().is_some();
+            let mut block_builder = include_cpp.cpp_standard().map(|standard| {
+                let mut block_builder = base_builder.clone();
+                block_builder.flag_if_supported(&format!("-std={}", standard));
+                block_builder
+            });
+            let builder = block_builder.as_mut().unwrap_or(&mut builder);
+            // Each generated file may include those generated after it, so
+            // they're checked once all have been written.
+            let mut typecheck_cxx_paths = Vec::new();
+            let mut typecheck_files = Vec::new();
             for filepair in generated_code.0 {
-                let fname = format!("gen{}.cxx", counter);
+                let mut gen_files = Vec::new();
+                let file_number = counter;
+                let fname = format!("gen{}.cxx", file_number);
                 counter += 1;
+                let mut gen_cxx_paths = Vec::new();
                 if let Some(implementation) = &filepair.implementation {
-                    let gen_cxx_path = write_to_file(&cxxdir, &fname, implementation)?;
-                    builder.file(&gen_cxx_path);
+                    gen_cxx_paths.push(write_to_file(&cxxdir, &fname, implementation)?);
+                    gen_files.push(implementation);
+                }
+                for (shard, implementation) in
+                    filepair.additional_implementations.iter().enumerate()
+                {
+                    let fname = format!("gen{}_{}.cxx", file_number, shard + 1);
+                    gen_cxx_paths.push(write_to_file(&cxxdir, &fname, implementation)?);
+                    gen_files.push(implementation);
+                }
+                let header_path = write_to_file(&incdir, &filepair.header_name, &filepair.header)?;
+                if self.early_typecheck {
+                    typecheck_cxx_paths.extend(gen_cxx_paths.iter().cloned());
+                    typecheck_files.extend(
+                        gen_cxx_paths
+                            .iter()
+                            .zip(gen_files)
+                            .chain(std::iter::once((&header_path, &filepair.header)))
+                            .map(|(path, content)| GeneratedFile {
+                                path: path.clone(),
+                                content: content.clone(),
+                                shim_origins: filepair.shim_origins.clone(),
+                            }),
+                    );
+                }
+                for gen_cxx_path in gen_cxx_paths {
+                    if self.header_only && !own_standard {
+                        header_only_files.push(gen_cxx_path.clone());
+                    } else {
+                        builder.file(&gen_cxx_path);
+                    }
                     generated_cpp.push(gen_cxx_path);
                 }
-                write_to_file(&incdir, &filepair.header_name, &filepair.header)?;
-                generated_cpp.push(incdir.join(filepair.header_name));
+                generated_cpp.push(header_path);
+            }
+            for gen_cxx_path in &typecheck_cxx_paths {
+                match typecheck(
+                    builder,
+                    &self.extra_clang_args,
+                    gen_cxx_path,
+                    &typecheck_files,
+                ) {
+                    Ok(errors) => {
+                        // Errors within a header are reported by each file
+                        // which includes it.
+                        for error in errors.into_iter().flatten() {
+                            if !typecheck_errors
+                                .iter()
+                                .any(|e| e.location == error.location && e.message == error.message)
+                            {
+                                typecheck_errors.push(error);
+                            }
+                        }
+                    }
+                    Err(e) => log::warn!("Unable to check the generated C++: {}", e),
+                }
             }
+            if !typecheck_errors.is_empty() {
+                return Err(BuilderError::GeneratedCppInvalid(typecheck_errors));
+            }
+            if let Some(block_builder) = block_builder {
+                block_builder
+                    .try_compile(&format!("autocxx_block{}", counter))
+                    .map_err(BuilderError::UnableToCompileBlock)?;
+            }
+        }
+
+        if !header_only_files.is_empty() {
+            let includes: String = header_only_files
+                .iter()
+                .map(|path| format!("#include \"{}\"\n", path.to_string_lossy()))
+                .collect();
+            let path = write_to_file(
+                &cxxdir,
+                &format!("{}.cxx", HEADER_ONLY_NAME),
+                includes.as_bytes(),
+            )?;
+            builder.file(&path);
+            generated_cpp.push(path);
         }
 
+        let mut all_rs = Vec::new();
         for include_cpp in parsed_file.get_rs_buildables() {
             let rs = include_cpp.generate_rs();
-            generated_rs.push(write_rs_to_file(
-                &rsdir,
-                &include_cpp.config.get_rs_filename(),
-                rs,
-            )?);
+            let filename = include_cpp.config.get_rs_filename();
+            if self.api_lockfile.is_some() {
+                all_rs.push(rs.clone());
+            }
+            generated_rs.push(if self.rs_module_tree {
+                write_rs_module_tree(&rsdir, &filename, rs)?
+            } else {
+                write_rs_to_file(&rsdir, &filename, rs)?
+            });
+        }
+        if let Some(lockfile) = &self.api_lockfile {
+            let api = describe_api(all_rs.iter());
+            let update = std::env::var_os(UPDATE_LOCKFILE_VAR).is_some();
+            match check_api_lock(lockfile, &api, &self.allowed_api_changes, update) {
+                Ok(None) => {}
+                Ok(Some(mismatch)) => return Err(BuilderError::ApiChanged(mismatch)),
+                Err(e) => return Err(BuilderError::FileWriteFail(e, lockfile.clone())),
+            }
         }
         if counter == 0 {
             Err(BuilderError::NoIncludeCxxMacrosFound)
@@ -286,18 +677,92 @@ fn ensure_created(dir: &Path) -> Result<(), BuilderError> {
         .map_err(|e| BuilderError::UnableToCreateDirectory(e, dir.to_path_buf()))
 }
 
+/// The include directories given to the builder, followed by any in the
+/// `AUTOCXX_INC` environment variable (a list separated like `PATH`), and
+/// then `extra_path`.
 fn build_autocxx_inc<I, T>(paths: I, extra_path: &Path) -> Vec<PathBuf>
 where
     I: IntoIterator<Item = T>,
     T: AsRef<OsStr>,
 {
-    paths
+    let env_paths = std::env::var_os("AUTOCXX_INC")
+        .map(|paths| std::env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let paths: Vec<PathBuf> = paths
         .into_iter()
         .map(|p| PathBuf::from(p.as_ref()))
+        .chain(env_paths)
+        .collect();
+    for path in &paths {
+        if !path.is_dir() {
+            log::warn!(
+                "Include directory {} doesn't exist, or isn't a directory",
+                path.display()
+            );
+        }
+    }
+    paths
+        .into_iter()
         .chain(std::iter::once(extra_path.to_path_buf()))
         .collect()
 }
 
+/// Compiles `header` into a precompiled header alongside it, where GCC and
+/// clang find it whenever `header` is included using `-include`. Returns
+/// whether it was possible to do so.
+fn precompile_header(
+    builder: &cc::Build,
+    header: &Path,
+    extra_args: &[String],
+) -> Result<bool, BuilderError> {
+    let compiler = match builder.try_get_compiler() {
+        Ok(compiler) => compiler,
+        Err(e) => {
+            log::warn!("Not using a precompiled header: {}", e);
+            return Ok(false);
+        }
+    };
+    let extension = if compiler.is_like_clang() {
+        ".pch"
+    } else if compiler.is_like_gnu() {
+        ".gch"
+    } else {
+        log::warn!("Not using a precompiled header: unsupported compiler");
+        return Ok(false);
+    };
+    let mut output = header.as_os_str().to_os_string();
+    output.push(extension);
+    let status = compiler
+        .to_command()
+        .args(extra_args)
+        .args(["-x", "c++-header"])
+        .arg(header)
+        .arg("-o")
+        .arg(&output)
+        .status()
+        .map_err(|e| BuilderError::UnableToPrecompileHeader(e, header.to_path_buf()))?;
+    if status.success() {
+        Ok(true)
+    } else {
+        Err(BuilderError::UnableToPrecompileHeader(
+            io::Error::other(format!("compiler failed: {}", status)),
+            header.to_path_buf(),
+        ))
+    }
+}
+
+/// Asks the compiler to emit LLVM bitcode suitable for cross-language LTO,
+/// which only clang can do.
+fn enable_lto(builder: &mut cc::Build) {
+    match builder.try_get_compiler() {
+        Ok(compiler) if compiler.is_like_clang() => {
+            builder.flag("-flto=thin");
+        }
+        Ok(_) => log::warn!("Not enabling cross-language LTO: the C++ compiler isn't clang"),
+        Err(e) => log::warn!("Not enabling cross-language LTO: {}", e),
+    }
+}
+
 fn write_to_file(dir: &Path, filename: &str, content: &[u8]) -> Result<PathBuf, BuilderError> {
     let path = dir.join(filename);
     try_write_to_file(&path, content).map_err(|e| BuilderError::FileWriteFail(e, path.clone()))?;
@@ -317,8 +782,105 @@ fn write_rs_to_file(
     write_to_file(dir, filename, content.to_string().as_bytes())
 }
 
+/// Writes the generated Rust to `filename`, except that each inline `mod`
+/// is moved to a file of its own within a directory alongside, and
+/// referred to using `#[path]`.
+fn write_rs_module_tree(
+    dir: &Path,
+    filename: &str,
+    content: TokenStream,
+) -> Result<PathBuf, BuilderError> {
+    let mut file: syn::File = match syn::parse2(content.clone()) {
+        Ok(file) => file,
+        Err(_) => return write_rs_to_file(dir, filename, content), // e.g. parse-only mode
+    };
+    let mod_dir = dir.join(filename.trim_end_matches(".rs"));
+    // Clear out files for any mods which no longer exist.
+    let _ = std::fs::remove_dir_all(&mod_dir);
+    ensure_created(&mod_dir)?;
+    split_out_mods(&mut file.items, &mod_dir, "")?;
+    write_rs_to_file(dir, filename, file.into_token_stream())
+}
+
+fn split_out_mods(items: &mut [Item], mod_dir: &Path, parent: &str) -> Result<(), BuilderError> {
+    for item in items {
+        let m = match item {
+            Item::Mod(m) if m.content.is_some() => m,
+            _ => continue,
+        };
+        // Attribute macros such as #[cxx::bridge] can't be applied to
+        // mods in other files, so leave those mods, and everything in
+        // them, where they are.
+        if !m.attrs.iter().all(|attr| {
+            ["doc", "allow", "warn", "deny", "cfg"]
+                .iter()
+                .any(|builtin| attr.path.is_ident(builtin))
+        }) {
+            continue;
+        }
+        let path = format!("{}::{}", parent, m.ident);
+        let (_, mut mod_items) = m.content.take().unwrap();
+        split_out_mods(&mut mod_items, mod_dir, &path)?;
+        // 64-bit FNV-1a, which is specified here so that file names are
+        // stable across Rust versions.
+        let hash = path.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001b3)
+        });
+        let mod_filename = format!("{}_{:016x}.rs", m.ident.unraw(), hash);
+        let mod_content = quote! { #(#mod_items)* };
+        let mod_path = write_rs_to_file(mod_dir, &mod_filename, mod_content)?;
+        let mod_path = mod_path.to_string_lossy();
+        for attr in &mut m.attrs {
+            attr.style = AttrStyle::Outer;
+        }
+        m.attrs.push(parse_quote! { #[path = #mod_path] });
+        m.semi = Some(Default::default());
+    }
+    Ok(())
+}
+
 fn rust_version_check() {
     if !version_check::is_min_version("1.54.0").unwrap_or(false) {
         panic!("Rust 1.54 or later is required.")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::enable_lto;
+
+    fn build_with_compiler(compiler: &str) -> cc::Build {
+        let mut builder = cc::Build::new();
+        builder
+            .cpp(true)
+            .compiler(compiler)
+            .target("x86_64-unknown-linux-gnu")
+            .host("x86_64-unknown-linux-gnu")
+            .opt_level(2)
+            .debug(false)
+            .cargo_metadata(false);
+        builder
+    }
+
+    fn has_lto_flag(builder: &cc::Build) -> bool {
+        builder
+            .get_compiler()
+            .args()
+            .iter()
+            .any(|arg| arg == "-flto=thin")
+    }
+
+    #[test]
+    fn test_enable_lto_clang() {
+        let mut builder = build_with_compiler("clang++");
+        enable_lto(&mut builder);
+        assert!(has_lto_flag(&builder));
+    }
+
+    #[test]
+    fn test_enable_lto_gcc() {
+        let mut builder = build_with_compiler("g++");
+        enable_lto(&mut builder);
+        assert!(!has_lto_flag(&builder));
+    }
+}
@@ -19,10 +19,17 @@ use crate::conversion::{
     error_reporter::{convert_apis, convert_item_apis},
     ConvertError,
 };
+use autocxx_parser::IncludeCppConfig;
 use std::collections::HashSet;
 
 /// Spot types with pure virtual functions and mark them abstract.
-pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPrePhase2> {
+/// Types which the user has declared `instantiable!` are never marked
+/// abstract, and nor are their subclasses unless they have their own
+/// pure virtual functions.
+pub(crate) fn mark_types_abstract(
+    mut apis: ApiVec<FnPrePhase2>,
+    config: &IncludeCppConfig,
+) -> ApiVec<FnPrePhase2> {
     let mut abstract_types: HashSet<_> = apis
         .iter()
         .filter_map(|api| match &api {
@@ -41,6 +48,7 @@ pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPre
             } => Some(self_ty_name.clone()),
             _ => None,
         })
+        .filter(|self_ty_name| !config.is_instantiable(&self_ty_name.to_cpp_name()))
         .collect();
 
     // Spot any derived classes (recursively). Also, any types which have a base
@@ -58,20 +66,17 @@ pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPre
                         analysis:
                             PodAndConstructorAnalysis {
                                 pod:
-                                    PodAnalysis {
-                                        bases,
+                                    pod @ PodAnalysis {
                                         kind: TypeKind::Pod | TypeKind::NonPod,
-                                        castable_bases,
-                                        field_deps,
-                                        field_info,
-                                        is_generic,
+                                        ..
                                     },
                                 constructors,
                             },
                         name,
                         details,
-                    } if abstract_types.contains(&name.name)
-                        || !abstract_types.is_disjoint(&bases) =>
+                    } if (abstract_types.contains(&name.name)
+                        || !abstract_types.is_disjoint(&pod.bases))
+                        && !config.is_instantiable(&name.name.to_cpp_name()) =>
                     {
                         abstract_types.insert(name.name.clone());
                         // Recurse in case there are further dependent types
@@ -79,12 +84,8 @@ pub(crate) fn mark_types_abstract(mut apis: ApiVec<FnPrePhase2>) -> ApiVec<FnPre
                         Api::Struct {
                             analysis: PodAndConstructorAnalysis {
                                 pod: PodAnalysis {
-                                    bases,
                                     kind: TypeKind::Abstract,
-                                    castable_bases,
-                                    field_deps,
-                                    field_info,
-                                    is_generic,
+                                    ..pod
                                 },
                                 constructors,
                             },
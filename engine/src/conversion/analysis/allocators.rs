@@ -88,6 +88,10 @@ fn create_alloc_and_free(ty_name: QualifiedName) -> impl Iterator<Item = Api<Pod
                     synthetic_cpp: Some((cpp_function_body, CppFunctionKind::Function)),
                     add_to_trait: Some(synthesis),
                     is_deleted: false,
+                    internal_linkage: false,
+                    source_location: None,
+                    deprecation: None,
+                    reexported_from: None,
                     provenance: Provenance::SynthesizedOther,
                 }),
                 analysis: (),
@@ -114,6 +114,10 @@ fn create_cast(from: &QualifiedName, to: &QualifiedName, mutable: CastMutability
             }),
             synthetic_cpp: Some((CppFunctionBody::Cast, CppFunctionKind::Function)),
             is_deleted: false,
+            internal_linkage: false,
+            source_location: None,
+            deprecation: None,
+            reexported_from: None,
             provenance: Provenance::SynthesizedOther,
         }),
         analysis: (),
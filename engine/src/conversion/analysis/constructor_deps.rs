@@ -66,6 +66,7 @@ fn decorate_struct(
             pod,
             constructor_and_allocator_deps,
             constructors: fn_struct.constructors,
+            trait_impls: Vec::new(),
         },
     })))
 }
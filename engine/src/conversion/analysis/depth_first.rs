@@ -33,14 +33,14 @@ impl<'a, T: HasDependencies + Debug> Iterator for DepthFirstIter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let first_candidate = self.queue.get(0).map(|api| api.name());
+        let first_candidate = self.queue.front().map(|api| api.name());
         while let Some(candidate) = self.queue.pop_front() {
             if !candidate.deps().any(|d| self.yet_to_do.contains(&d)) {
                 self.yet_to_do.remove(candidate.name());
                 return Some(candidate);
             }
             self.queue.push_back(candidate);
-            if self.queue.get(0).map(|api| api.name()) == first_candidate {
+            if self.queue.front().map(|api| api.name()) == first_candidate {
                 panic!(
                     "Failed to find a candidate; there must be a circular dependency. Queue is {}",
                     self.queue
@@ -87,7 +87,7 @@ mod test {
             QualifiedName::new_from_cpp_name("c"),
             vec![QualifiedName::new_from_cpp_name("a")],
         );
-        let api_list = vec![a, b, c];
+        let api_list = [a, b, c];
         let mut it = depth_first(api_list.iter());
         assert_eq!(it.next().unwrap().0, QualifiedName::new_from_cpp_name("a"));
         assert_eq!(it.next().unwrap().0, QualifiedName::new_from_cpp_name("c"));
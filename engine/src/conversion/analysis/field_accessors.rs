@@ -0,0 +1,119 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use itertools::Itertools;
+use quote::quote;
+use syn::{ext::IdentExt, parse_quote, FnArg};
+
+use crate::{
+    conversion::{
+        api::{Api, ApiName, CppVisibility, FuncToConvert, Provenance, References, Virtualness},
+        apivec::ApiVec,
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::{
+    fun::function_wrapper::{CppFunctionBody, CppFunctionKind},
+    pod::{OpaqueField, PodPhase},
+};
+
+/// A POD struct may contain fields which we represent as opaque blobs,
+/// because they can't be represented in Rust. Add C++ functions to get
+/// at each such field, as the type which autocxx generates for it.
+/// (We don't do this for arrays.)
+pub(crate) fn add_field_accessors(apis: ApiVec<PodPhase>) -> ApiVec<PodPhase> {
+    apis.into_iter()
+        .flat_map(|api| {
+            let mut resultant_apis = match api {
+                Api::Struct {
+                    ref name,
+                    details: _,
+                    ref analysis,
+                } => analysis
+                    .opaque_fields
+                    .iter()
+                    .filter(|opaque_field| opaque_field.array_len.is_none())
+                    .flat_map(|opaque_field| {
+                        [false, true]
+                            .into_iter()
+                            .map(|mutable| create_accessor(&name.name, opaque_field, mutable))
+                    })
+                    .collect_vec(),
+                _ => Vec::new(),
+            };
+            resultant_apis.push(api);
+            resultant_apis.into_iter()
+        })
+        .collect()
+}
+
+fn create_accessor(
+    owner: &QualifiedName,
+    opaque_field: &OpaqueField,
+    mutable: bool,
+) -> Api<PodPhase> {
+    let field = opaque_field.ident.unraw();
+    let (mutability, suffix) = if mutable {
+        (quote! { mut }, "_mut")
+    } else {
+        (quote! { const }, "")
+    };
+    let ident = make_ident(format!(
+        "{}_{}{}_autocxx_accessor",
+        owner.get_final_item(),
+        field,
+        suffix
+    ));
+    let owner_typ = owner.to_type_path();
+    let field_typ = opaque_field.ty.to_type_path();
+    let fnarg: FnArg = parse_quote! {
+        this: * #mutability #owner_typ
+    };
+    let doc = format!(
+        "Returns the `{}` field, which can't be represented directly in Rust.",
+        field
+    );
+    Api::Function {
+        name: ApiName::new_with_cpp_name(
+            owner.get_namespace(),
+            ident.clone(),
+            Some(format!("{}{}", field, suffix)),
+        ),
+        name_for_gc: None,
+        fun: Box::new(FuncToConvert {
+            ident,
+            doc_attr: Some(parse_quote! { #[doc = #doc] }),
+            inputs: [fnarg].into_iter().collect(),
+            output: parse_quote! {
+                -> * #mutability #field_typ
+            },
+            vis: parse_quote! { pub },
+            virtualness: Virtualness::None,
+            cpp_vis: CppVisibility::Public,
+            special_member: None,
+            unused_template_param: false,
+            references: References::new_with_this_and_return_as_reference(),
+            original_name: None,
+            self_ty: Some(owner.clone()),
+            synthesized_this_type: None,
+            add_to_trait: None,
+            synthetic_cpp: Some((
+                CppFunctionBody::FieldAccess(opaque_field.ident.clone()),
+                CppFunctionKind::Function,
+            )),
+            is_deleted: false,
+            internal_linkage: false,
+            source_location: None,
+            deprecation: None,
+            reexported_from: None,
+            provenance: Provenance::SynthesizedOther,
+        }),
+        analysis: (),
+    }
+}
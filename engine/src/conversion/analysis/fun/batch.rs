@@ -0,0 +1,106 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::IncludeCppConfig;
+use itertools::Itertools;
+use syn::{Ident, ReturnType, Type};
+
+use crate::{
+    known_types::known_types,
+    types::{make_ident, Namespace, QualifiedName},
+};
+
+use super::{ArgumentAnalysis, FnKind, MethodKind};
+
+/// A batched form of a function, requested using `batch!`, which takes a
+/// slice of argument structs and calls the function for each one from C++.
+#[derive(Clone)]
+pub(crate) struct BatchDetails {
+    /// What the C++ calls for each element: the name of the method for
+    /// methods, or else the fully qualified name of the function.
+    pub(crate) cpp_call: String,
+    /// The Rust name of the batched form, e.g. `draw_point_batch`.
+    pub(crate) rust_name: Ident,
+    /// The struct holding the arguments of each call, e.g.
+    /// `Canvas_draw_point_args`.
+    pub(crate) args_struct: Ident,
+    /// The C++ function which loops over the argument structs.
+    pub(crate) helper: Ident,
+}
+
+/// Works out the batched form of this function, if one was requested using
+/// `batch!`. Only functions which return nothing, and whose parameters
+/// (other than any receiver) are all numbers or `bool`s, can be batched,
+/// since only those can be laid out identically by Rust and C++ without
+/// further help.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn analyze_batch(
+    config: &IncludeCppConfig,
+    ns: &Namespace,
+    kind: &FnKind,
+    cpp_name: &str,
+    rust_name: &str,
+    cxxbridge_name: &Ident,
+    param_details: &[ArgumentAnalysis],
+    ret_type: &ReturnType,
+) -> Option<BatchDetails> {
+    let (qualified_cpp_name, args_struct) = match kind {
+        FnKind::Function => (
+            ns.iter()
+                .chain(std::iter::once(&cpp_name.to_string()))
+                .join("::"),
+            format!("{}_args", rust_name),
+        ),
+        FnKind::Method { impl_for, .. } => (
+            format!("{}::{}", impl_for.to_cpp_name(), cpp_name),
+            format!("{}_{}_args", impl_for.get_final_item(), rust_name),
+        ),
+        FnKind::TraitMethod { .. } => return None,
+    };
+    if !config.is_batched(&qualified_cpp_name) {
+        return None;
+    }
+    let cpp_call = match kind {
+        FnKind::Function => qualified_cpp_name.clone(),
+        FnKind::Method {
+            method_kind: MethodKind::Normal(_) | MethodKind::Virtual(_) | MethodKind::PureVirtual(_),
+            ..
+        } => cpp_name.to_string(),
+        _ => {
+            log::warn!(
+                "batch!(\"{}\") was given, but only functions and non-static methods can be batched",
+                qualified_cpp_name
+            );
+            return None;
+        }
+    };
+    let mut params = param_details
+        .iter()
+        .filter(|pd| pd.self_type.is_none())
+        .peekable();
+    let params_are_scalar = params.peek().is_some()
+        && params.all(|pd| {
+            !pd.conversion.cpp_work_needed()
+                && !pd.conversion.rust_work_needed()
+                && matches!(&pd.conversion.unwrapped_type, Type::Path(typ)
+                    if known_types().is_scalar(&QualifiedName::from_type_path(typ)))
+        });
+    if !matches!(ret_type, ReturnType::Default) || !params_are_scalar {
+        log::warn!(
+            "batch!(\"{}\") was given, but only functions returning nothing, which take at least one parameter and whose parameters are all numbers or bools, can be batched",
+            qualified_cpp_name
+        );
+        return None;
+    }
+    Some(BatchDetails {
+        cpp_call,
+        rust_name: make_ident(format!("{}_batch", rust_name)),
+        args_struct: make_ident(args_struct),
+        helper: make_ident(format!("{}_autocxx_batch", cxxbridge_name)),
+    })
+}
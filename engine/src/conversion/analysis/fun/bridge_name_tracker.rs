@@ -10,6 +10,21 @@ use crate::types::Namespace;
 use itertools::Itertools;
 use std::collections::HashMap;
 
+/// Names in the cxx::bridge mod longer than this are replaced by a shorter
+/// name incorporating a hash of the original. Such names form the basis of
+/// the symbol names of the shims generated by both cxx and autocxx, and
+/// very long symbols (e.g. for functions on deeply nested template
+/// instantiations) cause trouble for some toolchains, notably MSVC.
+const MAX_BRIDGE_NAME_LEN: usize = 200;
+
+/// How much of the original name to retain when shortening it, to keep
+/// shortened names vaguely recognizable.
+const SHORTENED_NAME_PREFIX_LEN: usize = 64;
+
+/// The first `mangling_version!` in which overlong names are shortened.
+/// Earlier versions leave them alone, so that their symbols don't change.
+const SHORTENING_MANGLING_VERSION: u32 = 2;
+
 /// Type to generate unique names for entries in the [cxx::bridge]
 /// mod which is flat.
 ///
@@ -46,14 +61,20 @@ use std::collections::HashMap;
 ///    #[rust_name] attribute and instead generate a 'use A = B;'
 ///    declaration in the mod which we generate for the output
 ///    namespace.
-#[derive(Default)]
 pub(crate) struct BridgeNameTracker {
+    mangling_version: u32,
     next_cxx_bridge_name_for_prefix: HashMap<String, usize>,
+    unshortened_names: HashMap<String, String>,
 }
 
 impl BridgeNameTracker {
-    pub(crate) fn new() -> Self {
-        Self::default()
+    /// Names are generated according to the given `mangling_version!`.
+    pub(crate) fn new(mangling_version: u32) -> Self {
+        Self {
+            mangling_version,
+            next_cxx_bridge_name_for_prefix: HashMap::new(),
+            unshortened_names: HashMap::new(),
+        }
     }
 
     /// Figure out the least confusing unique name for this function in the
@@ -70,11 +91,35 @@ impl BridgeNameTracker {
     /// may even allow us to remove whole impl blocks. So we may wish to try
     /// harder to find better names in future instead of always prepending
     /// the namespace.
+    /// From mangling version 2, overly long names are shortened: see
+    /// [`Self::get_unshortened_name`].
     pub(crate) fn get_unique_cxx_bridge_name(
         &mut self,
         type_name: Option<&str>,
         found_name: &str,
         ns: &Namespace,
+    ) -> String {
+        let name = self.get_unique_unshortened_cxx_bridge_name(type_name, found_name, ns);
+        if self.mangling_version < SHORTENING_MANGLING_VERSION || name.len() <= MAX_BRIDGE_NAME_LEN
+        {
+            return name;
+        }
+        let shortened = shorten_name(&name);
+        self.unshortened_names.insert(shortened.clone(), name);
+        shortened
+    }
+
+    /// If this name was shortened by [`Self::get_unique_cxx_bridge_name`],
+    /// returns the name it would otherwise have had.
+    pub(crate) fn get_unshortened_name(&self, name: &str) -> Option<&str> {
+        self.unshortened_names.get(name).map(String::as_str)
+    }
+
+    fn get_unique_unshortened_cxx_bridge_name(
+        &mut self,
+        type_name: Option<&str>,
+        found_name: &str,
+        ns: &Namespace,
     ) -> String {
         let found_name = if found_name == "new" {
             "new_autocxx"
@@ -111,15 +156,29 @@ impl BridgeNameTracker {
     }
 }
 
+/// Shortens a name by truncating it and appending a hash of the whole
+/// name. The hash (64-bit FNV-1a) is specified here rather than relying
+/// on the standard library, so that the resulting names are stable
+/// across Rust and autocxx versions.
+fn shorten_name(name: &str) -> String {
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    // Avoid generating a double underscore, which cxx won't accept.
+    let prefix = name[..SHORTENED_NAME_PREFIX_LEN].trim_end_matches('_');
+    format!("{}_autocxx_hash{:016x}", prefix, hash)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::types::Namespace;
+    use autocxx_parser::CURRENT_MANGLING_VERSION;
 
-    use super::BridgeNameTracker;
+    use super::{BridgeNameTracker, MAX_BRIDGE_NAME_LEN};
 
     #[test]
     fn test() {
-        let mut bnt = BridgeNameTracker::new();
+        let mut bnt = BridgeNameTracker::new(CURRENT_MANGLING_VERSION);
         let ns_root = Namespace::new();
         let ns_a = Namespace::from_user_input("A");
         let ns_b = Namespace::from_user_input("B");
@@ -155,4 +214,37 @@ mod tests {
             "A_B_ty_do"
         );
     }
+
+    #[test]
+    fn test_long_names() {
+        let mut bnt = BridgeNameTracker::new(CURRENT_MANGLING_VERSION);
+        let ns_root = Namespace::new();
+        let long_name = "a".repeat(MAX_BRIDGE_NAME_LEN + 1);
+        let shortened = bnt.get_unique_cxx_bridge_name(None, &long_name, &ns_root);
+        assert!(shortened.len() < MAX_BRIDGE_NAME_LEN);
+        assert!(shortened.starts_with("aaaa"));
+        assert_eq!(
+            bnt.get_unshortened_name(&shortened),
+            Some(long_name.as_str())
+        );
+        // Shortening must be stable.
+        let mut bnt2 = BridgeNameTracker::new(CURRENT_MANGLING_VERSION);
+        assert_eq!(
+            bnt2.get_unique_cxx_bridge_name(None, &long_name, &ns_root),
+            shortened
+        );
+        assert_eq!(bnt.get_unshortened_name("a"), None);
+    }
+
+    #[test]
+    fn test_long_names_unshortened_in_mangling_version_1() {
+        let mut bnt = BridgeNameTracker::new(1);
+        let ns_root = Namespace::new();
+        let long_name = "a".repeat(MAX_BRIDGE_NAME_LEN + 1);
+        assert_eq!(
+            bnt.get_unique_cxx_bridge_name(None, &long_name, &ns_root),
+            long_name
+        );
+        assert_eq!(bnt.get_unshortened_name(&long_name), None);
+    }
 }
@@ -7,10 +7,12 @@
 // except according to those terms.
 
 use crate::{
-    conversion::api::SubclassName,
+    conversion::api::{SourceLocation, SubclassName},
+    known_types::known_types,
     types::{Namespace, QualifiedName},
 };
-use syn::{parse_quote, Ident, Type};
+use autocxx_parser::StringReturns;
+use syn::{parse_quote, Ident, Type, TypeReference};
 
 #[derive(Clone, Debug)]
 pub(crate) enum CppConversionType {
@@ -19,6 +21,8 @@ pub(crate) enum CppConversionType {
     FromPtrToValue,
     FromValueToUniquePtr,
     FromPtrToMove,
+    FromOwningPtrToUniquePtr,
+    FromUniquePtrToOwningPtr,
 }
 
 impl CppConversionType {
@@ -46,6 +50,10 @@ pub(crate) enum RustConversionType {
     FromPinMoveRefToPtr,
     FromTypeToPtr,
     FromValueParamToPtr,
+    FromCxxStringToStr,
+    FromCxxStringToCow,
+    FromUniquePtrToString,
+    FromUniquePtrToStringLossy,
 }
 
 impl RustConversionType {
@@ -66,6 +74,7 @@ impl RustConversionType {
 /// * C++ wrapper function converts `std::unique_ptr<std::string>` to just
 ///   `std::string`
 /// * Finally, the actual C++ API receives a `std::string` by value.
+///
 /// The implementation here is distributed across this file, and
 /// `function_wrapper_rs` and `function_wrapper_cpp`.
 #[derive(Clone)]
@@ -92,20 +101,73 @@ impl TypeConversionPolicy {
         }
     }
 
+    /// Releases ownership of an object into a raw pointer parameter, as
+    /// requested using `consumes!`. `ty` is the pointee.
+    pub(crate) fn new_unique_ptr_to_owning_ptr(ty: Type) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::FromUniquePtrToOwningPtr,
+            rust_conversion: RustConversionType::None,
+        }
+    }
+
+    /// Takes ownership of the object to which a returned raw pointer
+    /// points, as requested using `returns_owned!`. `ty` is the pointee.
+    pub(crate) fn new_owning_ptr_to_unique_ptr(ty: Type) -> Self {
+        TypeConversionPolicy {
+            unwrapped_type: ty,
+            cpp_conversion: CppConversionType::FromOwningPtrToUniquePtr,
+            rust_conversion: RustConversionType::None,
+        }
+    }
+
+    /// Present a returned `&CxxString`, or a `std::string` returned by
+    /// value, as a native Rust string, as requested using `string_returns!`.
+    /// Other return types are left alone.
+    pub(crate) fn convert_string_return(&mut self, string_returns: StringReturns) {
+        let lossy = matches!(string_returns, StringReturns::Cow);
+        self.rust_conversion = match (&self.unwrapped_type, &self.cpp_conversion) {
+            (
+                Type::Reference(TypeReference {
+                    mutability: None,
+                    elem,
+                    ..
+                }),
+                CppConversionType::None,
+            ) if is_cxx_string(elem) => {
+                if lossy {
+                    RustConversionType::FromCxxStringToCow
+                } else {
+                    RustConversionType::FromCxxStringToStr
+                }
+            }
+            (ty, CppConversionType::FromValueToUniquePtr) if is_cxx_string(ty) => {
+                if lossy {
+                    RustConversionType::FromUniquePtrToStringLossy
+                } else {
+                    RustConversionType::FromUniquePtrToString
+                }
+            }
+            _ => return,
+        };
+    }
+
     pub(crate) fn cpp_work_needed(&self) -> bool {
         !matches!(self.cpp_conversion, CppConversionType::None)
     }
 
     pub(crate) fn unconverted_rust_type(&self) -> Type {
         match self.cpp_conversion {
-            CppConversionType::FromValueToUniquePtr => self.make_unique_ptr_type(),
+            CppConversionType::FromValueToUniquePtr
+            | CppConversionType::FromOwningPtrToUniquePtr => self.make_unique_ptr_type(),
             _ => self.unwrapped_type.clone(),
         }
     }
 
     pub(crate) fn converted_rust_type(&self) -> Type {
         match self.cpp_conversion {
-            CppConversionType::FromUniquePtrToValue => self.make_unique_ptr_type(),
+            CppConversionType::FromUniquePtrToValue
+            | CppConversionType::FromUniquePtrToOwningPtr => self.make_unique_ptr_type(),
             CppConversionType::FromPtrToValue => {
                 let innerty = &self.unwrapped_type;
                 parse_quote! {
@@ -146,17 +208,35 @@ impl TypeConversionPolicy {
     }
 }
 
+fn is_cxx_string(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => known_types().convertible_from_strs(&QualifiedName::from_type_path(p)),
+        _ => false,
+    }
+}
+
 #[derive(Clone)]
 pub(crate) enum CppFunctionBody {
     FunctionCall(Namespace, Ident),
+    /// Calls a non-const method of the given type through a `const_cast`
+    /// of a const receiver, as requested using `treat_as_const!`.
+    ConstCastMethodCall(QualifiedName, Ident),
+    /// Calls a function or method, passing those arguments for which a
+    /// type is given through a `const_cast` to a non-const reference to
+    /// that type, for variants requested using `shared_ref_variant!`.
+    FunctionCallCastingArgs(Namespace, Ident, Vec<Option<Type>>),
     StaticMethodCall(Namespace, Ident, Ident),
     PlacementNew(Namespace, Ident),
     MakeUnique,
     ConstructSuperclass(String),
     Cast,
+    FieldAccess(Ident),
     Destructor(Namespace, Ident),
     AllocUninitialized(QualifiedName),
     FreeUninitialized(QualifiedName),
+    /// Calls the given method of the object to which the argument points,
+    /// to add or release a reference as requested using `refcounted!`.
+    RefCount(String),
 }
 
 #[derive(Clone)]
@@ -178,4 +258,5 @@ pub(crate) struct CppFunction {
     pub(crate) kind: CppFunctionKind,
     pub(crate) pass_obs_field: bool,
     pub(crate) qualification: Option<QualifiedName>,
+    pub(crate) source_location: Option<SourceLocation>,
 }
@@ -213,10 +213,17 @@ pub(super) fn find_constructors_present(
             let fields_items_found: Vec<_> = field_info
                 .iter()
                 .filter_map(|field_info| match field_info.type_kind {
-                    TypeKind::Regular | TypeKind::SubclassHolder(_) => match field_info.ty {
-                        Type::Path(ref qn) => get_items_found(&QualifiedName::from_type_path(qn)),
-                        _ => None,
-                    },
+                    TypeKind::Regular | TypeKind::SubclassHolder(_) => {
+                        // An array has the same special members as its elements.
+                        let mut ty = &field_info.ty;
+                        while let Type::Array(arr) = ty {
+                            ty = &arr.elem;
+                        }
+                        match ty {
+                            Type::Path(qn) => get_items_found(&QualifiedName::from_type_path(qn)),
+                            _ => None,
+                        }
+                    }
                     // TODO: https://github.com/google/autocxx/issues/865 Figure out how to
                     // differentiate between pointers and references coming from C++. Pointers
                     // have a default constructor.
@@ -602,12 +609,12 @@ fn find_explicit_items(apis: &ApiVec<FnPrePhase1>) -> HashMap<ExplicitType, Expl
                 fun,
                 ..
             } => match method_kind {
-                MethodKind::Constructor { is_default: true } => {
-                    Some(ExplicitKind::DefaultConstructor)
-                }
-                MethodKind::Constructor { is_default: false } => {
-                    Some(ExplicitKind::OtherConstructor)
-                }
+                MethodKind::Constructor {
+                    is_default: true, ..
+                } => Some(ExplicitKind::DefaultConstructor),
+                MethodKind::Constructor {
+                    is_default: false, ..
+                } => Some(ExplicitKind::OtherConstructor),
                 _ => None,
             }
             .map_or((), |explicit_kind| {
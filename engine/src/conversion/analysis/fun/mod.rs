@@ -6,11 +6,15 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+pub(crate) mod batch;
 mod bridge_name_tracker;
 pub(crate) mod function_wrapper;
 mod implicit_constructors;
 mod overload_tracker;
+mod properties;
+mod snake_case;
 mod subclass;
+pub(crate) mod thin_call;
 
 use crate::{
     conversion::{
@@ -19,28 +23,31 @@ use crate::{
             type_converter::{self, add_analysis, TypeConversionContext, TypeConverter},
         },
         api::{
-            ApiName, CastMutability, CppVisibility, FuncToConvert, NullPhase, Provenance,
-            References, SpecialMemberKind, SubclassName, TraitImplSignature, TraitSynthesis,
-            UnsafetyNeeded, Virtualness,
+            ApiName, CastMutability, CppVisibility, Deprecation, FuncToConvert, NullPhase,
+            Provenance, References, SpecialMemberKind, SubclassName, TraitImplSignature,
+            TraitSynthesis, UnsafetyNeeded, Virtualness,
         },
         apivec::ApiVec,
+        codegen_cpp::type_to_cpp::type_to_cpp,
         convert_error::ConvertErrorWithContext,
         convert_error::ErrorContext,
+        doc_attr::append_doc_attr,
         error_reporter::{convert_apis, report_any_error},
     },
     known_types::known_types,
-    types::validate_ident_ok_for_rust,
+    target_cfg::{cfg_attr, excluded_for_target},
+    types::{is_rust_keyword, validate_ident_ok_for_rust},
 };
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
+use autocxx_parser::{DeprecationPolicy, IncludeCppConfig, UnsafePolicy};
 use function_wrapper::{CppFunction, CppFunctionBody, TypeConversionPolicy};
 use itertools::Itertools;
 use proc_macro2::Span;
 use quote::quote;
 use syn::{
-    parse_quote, punctuated::Punctuated, token::Comma, FnArg, Ident, Pat, ReturnType, Type,
-    TypePtr, Visibility,
+    parse_quote, punctuated::Punctuated, token::Comma, Attribute, FnArg, Ident, Pat, ReturnType,
+    Type, TypePtr, Visibility,
 };
 
 use crate::{
@@ -52,19 +59,25 @@ use crate::{
 };
 
 use self::{
+    batch::{analyze_batch, BatchDetails},
     bridge_name_tracker::BridgeNameTracker,
     function_wrapper::RustConversionType,
     implicit_constructors::{find_constructors_present, ItemsFound},
     overload_tracker::OverloadTracker,
+    properties::{find_properties, MethodShape, PropertyAccessor, PropertyMethodKey},
+    snake_case::{find_snake_case_collisions, to_snake_case, SnakeCaseMethodKey},
     subclass::{
         create_subclass_constructor, create_subclass_fn_wrapper, create_subclass_function,
-        create_subclass_trait_item,
+        create_subclass_protected_fn_wrapper, create_subclass_trait_item,
     },
+    thin_call::{analyze_thin_call, ThinCallDetails},
 };
 
 use super::{
     pod::{PodAnalysis, PodPhase},
+    reserved_names::{choose_helper_name, CONSTRUCTOR, MAKE_UNIQUE},
     tdef::TypedefAnalysis,
+    trait_impls::TraitImplDetails,
     type_converter::Annotated,
 };
 
@@ -77,7 +90,13 @@ pub(crate) enum ReceiverMutability {
 #[derive(Clone, Debug)]
 pub(crate) enum MethodKind {
     Normal(ReceiverMutability),
-    Constructor { is_default: bool },
+    /// A constructor. For POD types, which are trivially relocatable, the
+    /// Rust `new` returns the constructed value directly (`by_value`) rather
+    /// than a `moveit` constructor.
+    Constructor {
+        is_default: bool,
+        by_value: bool,
+    },
     MakeUnique,
     Static,
     Virtual(ReceiverMutability),
@@ -92,6 +111,8 @@ pub(crate) enum TraitMethodKind {
     Destructor,
     Alloc,
     Dealloc,
+    AddRef,
+    Release,
 }
 
 #[derive(Clone)]
@@ -147,6 +168,9 @@ pub(crate) struct FnAnalysis {
     /// (from the perspective of Rust and C++) things are in different
     /// namespaces/mods.
     pub(crate) cxxbridge_name: Ident,
+    /// If the cxxbridge name was shortened because it was too long,
+    /// the name it would otherwise have had.
+    pub(crate) unshortened_cxxbridge_name: Option<String>,
     /// ... so record also the name under which we wish to expose it in Rust.
     pub(crate) rust_name: String,
     pub(crate) rust_rename_strategy: RustRenameStrategy,
@@ -169,6 +193,19 @@ pub(crate) struct FnAnalysis {
     pub(crate) externally_callable: bool,
     /// Whether we need to generate a Rust-side calling function
     pub(crate) rust_wrapper_needed: bool,
+    /// A `#[cfg]` attribute requested using the `cfg!` directive.
+    pub(crate) cfg: Option<Attribute>,
+    /// A `#[deprecated]` attribute, if the C++ function is deprecated and
+    /// `deprecation_policy!(warn)` asked us to say so.
+    pub(crate) deprecated: Option<Attribute>,
+    /// A batched form of this function, requested using `batch!`.
+    pub(crate) batch: Option<BatchDetails>,
+    /// Whether this is called through a thin `extern "C"` wrapper instead
+    /// of through cxx, as requested using `thin_call!`.
+    pub(crate) thin_call: Option<ThinCallDetails>,
+    /// The type, and the name within it, under which this free function
+    /// should also be available, as requested using `associate_fn!`.
+    pub(crate) associated_fn: Option<(QualifiedName, Ident)>,
 }
 
 #[derive(Clone)]
@@ -228,6 +265,9 @@ pub(crate) struct PodAndDepAnalysis {
     pub(crate) pod: PodAnalysis,
     pub(crate) constructor_and_allocator_deps: Vec<QualifiedName>,
     pub(crate) constructors: PublicConstructors,
+    /// Traits to implement by delegating to C++ methods, requested using
+    /// `impl_trait!`.
+    pub(crate) trait_impls: Vec<TraitImplDetails>,
 }
 
 /// Analysis phase after we've finished analyzing functions and determined
@@ -277,6 +317,9 @@ pub(crate) struct FnAnalyzer<'a> {
     nested_type_name_map: HashMap<QualifiedName, String>,
     generic_types: HashSet<QualifiedName>,
     existing_superclass_trait_api_names: HashSet<QualifiedName>,
+    snake_case_collisions: HashSet<SnakeCaseMethodKey>,
+    property_accessors: HashMap<PropertyMethodKey, PropertyAccessor>,
+    helper_names: HashMap<(QualifiedName, &'static str), String>,
 }
 
 impl<'a> FnAnalyzer<'a> {
@@ -285,11 +328,12 @@ impl<'a> FnAnalyzer<'a> {
         unsafe_policy: UnsafePolicy,
         config: &'a IncludeCppConfig,
     ) -> ApiVec<FnPrePhase2> {
+        let cpp_method_names = Self::build_cpp_method_names(&apis);
         let mut me = Self {
             unsafe_policy,
             extra_apis: ApiVec::new(),
             type_converter: TypeConverter::new(config, &apis),
-            bridge_name_tracker: BridgeNameTracker::new(),
+            bridge_name_tracker: BridgeNameTracker::new(config.get_mangling_version()),
             config,
             overload_trackers_by_mod: HashMap::new(),
             pod_safe_types: Self::build_pod_safe_type_set(&apis),
@@ -297,6 +341,9 @@ impl<'a> FnAnalyzer<'a> {
             nested_type_name_map: Self::build_nested_type_map(&apis),
             generic_types: Self::build_generic_type_set(&apis),
             existing_superclass_trait_api_names: HashSet::new(),
+            snake_case_collisions: Self::build_snake_case_collisions(&cpp_method_names, config),
+            property_accessors: Self::build_property_accessors(&apis, config),
+            helper_names: Self::build_helper_names(&cpp_method_names, config),
         };
         let mut results = ApiVec::new();
         convert_apis(
@@ -310,7 +357,30 @@ impl<'a> FnAnalyzer<'a> {
         let mut results = me.add_constructors_present(results);
         me.add_make_uniques(&mut results);
         results.extend(me.extra_apis.into_iter().map(add_analysis));
+        // Everything later phases need from the original bindgen signatures
+        // is now in the analysis, so drop them rather than carrying them
+        // through to codegen.
         results
+            .into_iter()
+            .map(|api| match api {
+                Api::Function {
+                    name,
+                    name_for_gc,
+                    mut fun,
+                    analysis,
+                } => {
+                    fun.inputs = Punctuated::new();
+                    fun.output = ReturnType::Default;
+                    Api::Function {
+                        name,
+                        name_for_gc,
+                        fun,
+                        analysis,
+                    }
+                }
+                _ => api,
+            })
+            .collect()
     }
 
     fn build_pod_safe_type_set(apis: &ApiVec<PodPhase>) -> HashSet<QualifiedName> {
@@ -358,6 +428,127 @@ impl<'a> FnAnalyzer<'a> {
             .collect()
     }
 
+    /// Finds the original C++ names of the methods of each type.
+    fn build_cpp_method_names(apis: &ApiVec<PodPhase>) -> HashMap<QualifiedName, HashSet<String>> {
+        let mut method_names: HashMap<QualifiedName, HashSet<String>> = HashMap::new();
+        for api in apis.iter() {
+            let (name, fun) = match api {
+                Api::Function { name, fun, .. } if fun.special_member.is_none() => (name, fun),
+                _ => continue,
+            };
+            let self_ty = fun.self_ty.clone().or_else(|| {
+                fun.inputs.iter().find_map(|input| match input {
+                    FnArg::Typed(pt) => match (pt.pat.as_ref(), pt.ty.as_ref()) {
+                        (Pat::Ident(pp), Type::Ptr(TypePtr { elem, .. })) if pp.ident == "this" => {
+                            match elem.as_ref() {
+                                Type::Path(typ) => Some(QualifiedName::from_type_path(typ)),
+                                _ => None,
+                            }
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                })
+            });
+            if let Some(self_ty) = self_ty {
+                let cpp_name = name
+                    .cpp_name_if_present()
+                    .cloned()
+                    .unwrap_or_else(|| fun.ident.to_string());
+                method_names.entry(self_ty).or_default().insert(cpp_name);
+            }
+        }
+        method_names
+    }
+
+    /// If the user asked for `snake_case_methods!`, finds the methods
+    /// which we can't rename because they'd clash with another method.
+    fn build_snake_case_collisions(
+        cpp_method_names: &HashMap<QualifiedName, HashSet<String>>,
+        config: &IncludeCppConfig,
+    ) -> HashSet<SnakeCaseMethodKey> {
+        if !config.snake_case_methods() {
+            return HashSet::new();
+        }
+        find_snake_case_collisions(cpp_method_names.iter().flat_map(|(self_ty, names)| {
+            names.iter().map(|cpp_name| {
+                (
+                    self_ty.get_namespace().clone(),
+                    self_ty.get_final_item().to_string(),
+                    cpp_name.clone(),
+                )
+            })
+        }))
+    }
+
+    /// If the user asked for `property_accessors!`, finds the getter/setter
+    /// pairs which we should rename.
+    fn build_property_accessors(
+        apis: &ApiVec<PodPhase>,
+        config: &IncludeCppConfig,
+    ) -> HashMap<PropertyMethodKey, PropertyAccessor> {
+        if !config.property_accessors() {
+            return HashMap::new();
+        }
+        find_properties(apis.iter().filter_map(|api| {
+            let (name, fun) = match api {
+                Api::Function { name, fun, .. }
+                    if fun.special_member.is_none()
+                        && matches!(fun.virtualness, Virtualness::None)
+                        && fun.synthetic_cpp.is_none() =>
+                {
+                    (name, fun)
+                }
+                _ => return None,
+            };
+            let self_ty = fun.inputs.iter().find_map(|input| match input {
+                FnArg::Typed(pt) => match (pt.pat.as_ref(), pt.ty.as_ref()) {
+                    (Pat::Ident(pp), Type::Ptr(TypePtr { elem, .. })) if pp.ident == "this" => {
+                        match elem.as_ref() {
+                            Type::Path(typ) => Some(QualifiedName::from_type_path(typ)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })?;
+            Some(MethodShape {
+                ns: self_ty.get_namespace().clone(),
+                type_name: self_ty.get_final_item().to_string(),
+                cpp_name: name
+                    .cpp_name_if_present()
+                    .cloned()
+                    .unwrap_or_else(|| fun.ident.to_string()),
+                param_count: fun.inputs.len() - 1,
+                returns_value: !matches!(fun.output, ReturnType::Default),
+            })
+        }))
+    }
+
+    /// Names the helpers we generate for each type which has methods, such
+    /// that none clashes with a method. (Methods keep their names.)
+    fn build_helper_names(
+        cpp_method_names: &HashMap<QualifiedName, HashSet<String>>,
+        config: &IncludeCppConfig,
+    ) -> HashMap<(QualifiedName, &'static str), String> {
+        let mut helper_names = HashMap::new();
+        for (self_ty, method_names) in cpp_method_names {
+            let is_taken = |name: &str| {
+                method_names.iter().any(|method_name| {
+                    method_name == name
+                        || (config.snake_case_methods() && to_snake_case(method_name) == name)
+                })
+            };
+            for helper in [CONSTRUCTOR, MAKE_UNIQUE] {
+                let qualified_helper = format!("{}::{}", self_ty.to_cpp_name(), helper);
+                let name = choose_helper_name(config, &qualified_helper, helper, is_taken);
+                helper_names.insert((self_ty.clone(), helper), name);
+            }
+        }
+        helper_names
+    }
+
     /// Builds a mapping from a qualified type name to the last 'nest'
     /// of its name, if it has multiple elements.
     fn build_nested_type_map(apis: &ApiVec<PodPhase>) -> HashMap<QualifiedName, String> {
@@ -426,7 +617,9 @@ impl<'a> FnAnalyzer<'a> {
                     TraitMethodKind::CopyConstructor
                     | TraitMethodKind::MoveConstructor
                     | TraitMethodKind::Alloc
-                    | TraitMethodKind::Dealloc,
+                    | TraitMethodKind::Dealloc
+                    | TraitMethodKind::AddRef
+                    | TraitMethodKind::Release,
                 ..
             } => UnsafetyNeeded::Always,
             FnKind::TraitMethod { .. } => match unsafest_param {
@@ -539,7 +732,7 @@ impl<'a> FnAnalyzer<'a> {
     fn analyze_foreign_fn_and_subclasses(
         &mut self,
         name: ApiName,
-        fun: Box<FuncToConvert>,
+        mut fun: Box<FuncToConvert>,
     ) -> Result<Box<dyn Iterator<Item = Api<FnPrePhase1>>>, ConvertErrorWithContext> {
         let (analysis, name) =
             self.analyze_foreign_fn(name, &fun, TypeConversionSophistication::Regular, None);
@@ -628,6 +821,73 @@ impl<'a> FnAnalyzer<'a> {
             }
         }
 
+        // Protected methods can't be called from outside the class, but our
+        // subclasses can make them accessible, so that Rust subclass
+        // implementations can call them via their C++ peer.
+        if let FnKind::Method {
+            impl_for: sup,
+            method_kind: MethodKind::Normal(..),
+        } = &analysis.kind
+        {
+            if fun.cpp_vis == CppVisibility::Protected && analysis.ignore_reason.is_ok() {
+                for sub in self.subclasses_by_superclass(sup) {
+                    let (protected_fun, protected_fun_name) = create_subclass_protected_fn_wrapper(
+                        &sub,
+                        &fun,
+                        &analysis.rust_name,
+                        name.cpp_name(),
+                    );
+                    self.analyze_and_add(
+                        protected_fun_name,
+                        protected_fun,
+                        &mut results,
+                        TypeConversionSophistication::Regular,
+                    );
+                }
+            }
+        }
+
+        // The user may have asked for a variant which accepts shared
+        // references in place of non-const references.
+        if analysis.ignore_reason.is_ok() {
+            if let Some((variant_fun, variant_name)) =
+                self.create_shared_ref_variant(&fun, &analysis, &name)
+            {
+                let (variant_analysis, variant_name) = self.analyze_foreign_fn(
+                    variant_name,
+                    &variant_fun,
+                    TypeConversionSophistication::Regular,
+                    Some(format!("{}_shared", analysis.rust_name)),
+                );
+                results.push(Api::Function {
+                    fun: variant_fun,
+                    analysis: variant_analysis,
+                    name: variant_name,
+                    name_for_gc: None,
+                });
+            }
+        }
+
+        // Point each half of a getter/setter pair at the other.
+        if let FnKind::Method {
+            impl_for,
+            method_kind: MethodKind::Normal(..),
+        } = &analysis.kind
+        {
+            if let Some(accessor) = self.property_accessor(
+                impl_for.get_namespace(),
+                impl_for.get_final_item(),
+                &name.cpp_name(),
+            ) {
+                if accessor.rust_name == analysis.rust_name {
+                    fun.doc_attr = Some(append_doc_attr(
+                        fun.doc_attr.as_ref(),
+                        &accessor.cross_link_doc(),
+                    ));
+                }
+            }
+        }
+
         results.push(Api::Function {
             fun,
             analysis,
@@ -687,7 +947,8 @@ impl<'a> FnAnalyzer<'a> {
     ///   to [cxx::UniquePtr]
     /// * We'll need a Rust wrapper if we've got a C++ wrapper and it's a method.
     /// * We may need wrappers if names conflict.
-    /// etc.
+    /// * etc.
+    ///
     /// The other major thing we do here is figure out naming for the function.
     /// This depends on overloads, and what other functions are floating around.
     /// The output of this analysis phase is used by both Rust and C++ codegen.
@@ -706,18 +967,36 @@ impl<'a> FnAnalyzer<'a> {
         // and it would be nice to have some idea of the function name
         // for diagnostics whilst we do that.
         let initial_rust_name = fun.ident.to_string();
-        let diagnostic_display_name = cpp_name.as_ref().unwrap_or(&initial_rust_name);
+        let diagnostic_display_name = cpp_name
+            .clone()
+            .unwrap_or_else(|| initial_rust_name.clone());
+
+        // If the user has promised that a non-const method is logically
+        // const, pretend that it's const. We'll need to call it through a
+        // const_cast.
+        let const_cast_inputs = self.const_cast_receiver_inputs(fun, &diagnostic_display_name);
+        let const_cast_receiver = const_cast_inputs.is_some();
+
+        // A variant requested using `shared_ref_variant!` takes shared
+        // references, and needs to const_cast them for C++.
+        let const_cast_args = match &fun.provenance {
+            Provenance::SynthesizedSharedRefVariant(params) => {
+                Some(const_cast_arg_types(&fun.inputs, params))
+            }
+            _ => None,
+        };
 
         // Now let's analyze all the parameters.
         // See if any have annotations which our fork of bindgen has craftily inserted...
-        let (param_details, bads): (Vec<_>, Vec<_>) = fun
-            .inputs
+        let (param_details, bads): (Vec<_>, Vec<_>) = const_cast_inputs
+            .as_ref()
+            .unwrap_or(&fun.inputs)
             .iter()
             .map(|i| {
                 self.convert_fn_arg(
                     i,
                     ns,
-                    diagnostic_display_name,
+                    &diagnostic_display_name,
                     &fun.synthesized_this_type,
                     &fun.references,
                     true,
@@ -743,19 +1022,23 @@ impl<'a> FnAnalyzer<'a> {
         // Work out naming, part one.
         // bindgen may have mangled the name either because it's invalid Rust
         // syntax (e.g. a keyword like 'async') or it's an overload.
-        // If the former, we respect that mangling. If the latter, we don't,
-        // because we'll add our own overload counting mangling later.
+        // If the former, we use the original name, which will become a raw
+        // identifier (e.g. `r#async`) when we output it. If the latter, we
+        // don't respect bindgen's mangling, because we'll add our own
+        // overload counting mangling later.
         // Cases:
-        //   function, IRN=foo,    CN=<none>                    output: foo    case 1
-        //   function, IRN=move_,  CN=move   (keyword problem)  output: move_  case 2
-        //   function, IRN=foo1,   CN=foo    (overload)         output: foo    case 3
-        //   method,   IRN=A_foo,  CN=foo                       output: foo    case 4
-        //   method,   IRN=A_move, CN=move   (keyword problem)  output: move_  case 5
-        //   method,   IRN=A_foo1, CN=foo    (overload)         output: foo    case 6
+        //   function, IRN=foo,    CN=<none>                    output: foo     case 1
+        //   function, IRN=move_,  CN=move   (keyword problem)  output: r#move  case 2
+        //   function, IRN=foo1,   CN=foo    (overload)         output: foo     case 3
+        //   method,   IRN=A_foo,  CN=foo                       output: foo     case 4
+        //   method,   IRN=A_move, CN=move   (keyword problem)  output: r#move  case 5
+        //   method,   IRN=A_foo1, CN=foo    (overload)         output: foo     case 6
         let ideal_rust_name = match &cpp_name {
             None => initial_rust_name, // case 1
             Some(cpp_name) => {
-                if initial_rust_name.ends_with('_') {
+                if is_rust_keyword(cpp_name) {
+                    cpp_name.to_string() // cases 2, 5
+                } else if initial_rust_name.ends_with('_') {
                     initial_rust_name // case 2
                 } else if validate_ident_ok_for_rust(cpp_name).is_err() {
                     format!("{}_", cpp_name) // case 5
@@ -814,8 +1097,9 @@ impl<'a> FnAnalyzer<'a> {
                 if let Some(constructor_suffix) = rust_name.strip_prefix(nested_type_ident) {
                     rust_name = format!("new{}", constructor_suffix);
                 }
-                rust_name = predetermined_rust_name
-                    .unwrap_or_else(|| self.get_overload_name(ns, type_ident, rust_name));
+                rust_name = predetermined_rust_name.unwrap_or_else(|| {
+                    self.get_constructor_name(ns, &self_ty, type_ident, None, rust_name)
+                });
                 let error_context = error_context_for_method(&self_ty, &rust_name);
 
                 // If this is 'None', then something weird is going on. We'll check for that
@@ -867,10 +1151,14 @@ impl<'a> FnAnalyzer<'a> {
                         rust_name,
                     )
                 } else {
+                    let by_value = self.pod_safe_types.contains(&self_ty);
                     (
                         FnKind::Method {
                             impl_for: self_ty,
-                            method_kind: MethodKind::Constructor { is_default: false },
+                            method_kind: MethodKind::Constructor {
+                                is_default: false,
+                                by_value,
+                            },
                         },
                         error_context,
                         rust_name,
@@ -911,7 +1199,11 @@ impl<'a> FnAnalyzer<'a> {
                         .strip_prefix(nested_type_ident)
                         .or_else(|| rust_name.strip_prefix("new"))
                         .unwrap();
-                    rust_name = format!("make_unique{}", constructor_suffix);
+                    rust_name = format!(
+                        "{}{}",
+                        self.get_helper_name(&self_ty, MAKE_UNIQUE),
+                        constructor_suffix
+                    );
                     // Strip off the 'this' arg.
                     params = params.into_iter().skip(1).collect();
                     param_details.remove(0);
@@ -928,12 +1220,17 @@ impl<'a> FnAnalyzer<'a> {
                     // fn make_unique(...args) -> UniquePtr<Type>
                     // If there are multiple constructors, bindgen generates
                     // new, new1, new2 etc. and we'll keep those suffixes.
-                    rust_name = format!("new{}", constructor_suffix);
+                    rust_name = format!(
+                        "{}{}",
+                        self.get_helper_name(&self_ty, CONSTRUCTOR),
+                        constructor_suffix
+                    );
                     MethodKind::Constructor {
                         is_default: matches!(
                             fun.special_member,
                             Some(SpecialMemberKind::DefaultConstructor)
                         ),
+                        by_value: self.pod_safe_types.contains(&self_ty),
                     }
                 } else if is_static_method {
                     MethodKind::Static
@@ -947,8 +1244,23 @@ impl<'a> FnAnalyzer<'a> {
                     }
                 };
                 // Disambiguate overloads.
-                let rust_name = predetermined_rust_name
-                    .unwrap_or_else(|| self.get_overload_name(ns, type_ident, rust_name));
+                let rust_name = predetermined_rust_name.unwrap_or_else(|| match method_kind {
+                    MethodKind::Constructor { .. } | MethodKind::MakeUnique => {
+                        self.get_constructor_name(ns, &self_ty, type_ident, Some(fun), rust_name)
+                    }
+                    MethodKind::Normal(_) => {
+                        let rust_name = self
+                            .property_accessor(ns, type_ident, &rust_name)
+                            .map(|accessor| accessor.rust_name.clone())
+                            .unwrap_or_else(|| self.snake_case_name(ns, type_ident, rust_name));
+                        self.get_overload_name(ns, type_ident, rust_name)
+                    }
+                    MethodKind::Static => {
+                        let rust_name = self.snake_case_name(ns, type_ident, rust_name);
+                        self.get_overload_name(ns, type_ident, rust_name)
+                    }
+                    _ => self.get_overload_name(ns, type_ident, rust_name),
+                });
                 let error_context = error_context_for_method(&self_ty, &rust_name);
                 (
                     FnKind::Method {
@@ -962,7 +1274,8 @@ impl<'a> FnAnalyzer<'a> {
         } else {
             // Not a method.
             // What shall we call this function? It may be overloaded.
-            let rust_name = self.get_function_overload_name(ns, ideal_rust_name);
+            let rust_name = predetermined_rust_name
+                .unwrap_or_else(|| self.get_function_overload_name(ns, ideal_rust_name));
             (
                 FnKind::Function,
                 ErrorContext::Item(make_ident(&rust_name)),
@@ -1071,6 +1384,36 @@ impl<'a> FnAnalyzer<'a> {
             _ => {}
         }
 
+        // The user may have promised, using `consumes!`, that some raw
+        // pointer parameters take ownership of their objects. If so, we
+        // accept a UniquePtr instead, and release it into the call.
+        let qualified_cpp_name =
+            qualified_cpp_name(&kind, ns, cpp_name.as_ref().unwrap_or(&rust_name));
+        if let Some(qualified_cpp_name) = &qualified_cpp_name {
+            for arg in self.config.get_consumed_args(qualified_cpp_name) {
+                // Parameters are numbered from 1, not counting any receiver.
+                let pd = arg.checked_sub(1).and_then(|index| {
+                    param_details
+                        .iter_mut()
+                        .filter(|pd| pd.self_type.is_none())
+                        .nth(index)
+                });
+                let pointee = pd
+                    .as_ref()
+                    .and_then(|pd| owned_pointee(&pd.conversion.unwrapped_type));
+                match (pd, pointee) {
+                    (Some(pd), Some(pointee)) => {
+                        pd.conversion = TypeConversionPolicy::new_unique_ptr_to_owning_ptr(pointee);
+                        pd.requires_unsafe = UnsafetyNeeded::None;
+                    }
+                    _ => set_ignore_reason(ConvertError::ConsumesNonPointer(
+                        qualified_cpp_name.clone(),
+                        arg,
+                    )),
+                }
+            }
+        }
+
         let requires_unsafe = self.should_be_unsafe(&param_details, &kind);
 
         // Now we can add context to the error, check for a variety of error
@@ -1085,7 +1428,30 @@ impl<'a> FnAnalyzer<'a> {
             CppVisibility::Protected => false,
             CppVisibility::Public => true,
         };
-        if matches!(
+        // cfg! directives name methods as Type::method.
+        let cfg_name = match kind {
+            FnKind::Method { ref impl_for, .. } | FnKind::TraitMethod { ref impl_for, .. } => {
+                format!("{}::{}", impl_for.to_cpp_name(), diagnostic_display_name)
+            }
+            FnKind::Function => ns
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(diagnostic_display_name.as_str()))
+                .join("::"),
+        };
+        let cfg = cfg_attr(self.config, &cfg_name);
+        let deprecated = match (&fun.deprecation, self.config.deprecation_policy()) {
+            (Some(Deprecation(Some(note))), DeprecationPolicy::Warn) => {
+                Some(parse_quote! { #[deprecated(note = #note)] })
+            }
+            (Some(Deprecation(None)), DeprecationPolicy::Warn) => {
+                Some(parse_quote! { #[deprecated] })
+            }
+            _ => None,
+        };
+        if excluded_for_target(self.config, &cfg_name) {
+            set_ignore_reason(ConvertError::ExcludedByCfg(cfg_name))
+        } else if matches!(
             fun.special_member,
             Some(SpecialMemberKind::AssignmentOperator)
         ) {
@@ -1094,6 +1460,12 @@ impl<'a> FnAnalyzer<'a> {
             set_ignore_reason(ConvertError::RValueReturn)
         } else if fun.is_deleted {
             set_ignore_reason(ConvertError::Deleted)
+        } else if fun.deprecation.is_some()
+            && self.config.deprecation_policy() == DeprecationPolicy::Skip
+        {
+            set_ignore_reason(ConvertError::Deprecated)
+        } else if self.is_blocked_constructor(&kind, fun) {
+            set_ignore_reason(ConvertError::BlockedConstructor)
         } else if !fun.references.rvalue_ref_params.is_empty()
             && !matches!(
                 kind,
@@ -1127,6 +1499,19 @@ impl<'a> FnAnalyzer<'a> {
                 } if !known_types().is_cxx_acceptable_receiver(impl_for) => {
                     set_ignore_reason(ConvertError::UnsupportedReceiver);
                 }
+                FnKind::Method { ref impl_for, .. } | FnKind::TraitMethod { ref impl_for, .. }
+                    if self
+                        .config
+                        .get_extern_cpp_type(&impl_for.to_cpp_name())
+                        .is_some() =>
+                {
+                    set_ignore_reason(ConvertError::MethodOfExternCppType);
+                }
+                FnKind::Method { ref impl_for, .. } | FnKind::TraitMethod { ref impl_for, .. }
+                    if self.config.is_opaque_only(&impl_for.to_cpp_name()) =>
+                {
+                    set_ignore_reason(ConvertError::MethodOfOpaqueOnlyType);
+                }
                 FnKind::Method { ref impl_for, .. } if !self.is_on_allowlist(impl_for) => {
                     // Bindgen will output methods for types which have been encountered
                     // virally as arguments on other allowlisted types. But we don't want
@@ -1146,15 +1531,24 @@ impl<'a> FnAnalyzer<'a> {
         // The name we use within the cxx::bridge mod may be different
         // from both the C++ name and the Rust name, because it's a flat
         // namespace so we might need to prepend some stuff to make it unique.
+        // We also avoid using raw identifiers within the cxx::bridge mod.
         let cxxbridge_name = self.get_cxx_bridge_name(
             match kind {
                 FnKind::Method { ref impl_for, .. } => Some(impl_for.get_final_item()),
                 FnKind::Function => None,
                 FnKind::TraitMethod { ref impl_for, .. } => Some(impl_for.get_final_item()),
             },
-            &rust_name,
+            &if is_rust_keyword(&rust_name) {
+                format!("{}_", rust_name)
+            } else {
+                rust_name.clone()
+            },
             ns,
         );
+        let unshortened_cxxbridge_name = self
+            .bridge_name_tracker
+            .get_unshortened_name(&cxxbridge_name)
+            .map(str::to_string);
         if cxxbridge_name != rust_name && cpp_name.is_none() {
             cpp_name = Some(rust_name.clone());
         }
@@ -1189,6 +1583,24 @@ impl<'a> FnAnalyzer<'a> {
         let mut deps = params_deps;
         deps.extend(return_analysis.deps.drain());
 
+        let associated_fn = match kind {
+            FnKind::Function => {
+                let qualified_cpp_name = ns
+                    .iter()
+                    .chain(std::iter::once(cpp_name.as_ref().unwrap_or(&rust_name)))
+                    .join("::");
+                self.config
+                    .get_associated_fn(&qualified_cpp_name)
+                    .map(|(ty, name)| (QualifiedName::new_from_cpp_name(ty), make_ident(name)))
+            }
+            _ => None,
+        };
+        // If the type isn't available, the function will be ignored rather
+        // than generating an associated function which can't compile.
+        if let Some((ty, _)) = &associated_fn {
+            deps.insert(ty.clone());
+        }
+
         let num_input_references = param_details.iter().filter(|pd| pd.was_reference).count();
         if num_input_references != 1 && return_analysis.was_reference {
             // cxx only allows functions to return a reference if they take exactly
@@ -1196,23 +1608,71 @@ impl<'a> FnAnalyzer<'a> {
             set_ignore_reason(ConvertError::NotOneInputReference(rust_name.clone()));
         }
         let mut ret_type = return_analysis.rt;
-        let ret_type_conversion = return_analysis.conversion;
+        let mut ret_type_conversion = return_analysis.conversion;
+        if let (Some(string_returns), Some(conversion)) = (
+            self.config.get_string_returns(),
+            ret_type_conversion.as_mut(),
+        ) {
+            // Trait methods have signatures fixed by the trait, and methods
+            // synthesized for subclasses must match the subclass traits.
+            if !matches!(kind, FnKind::TraitMethod { .. }) && fun.synthesized_this_type.is_none() {
+                conversion.convert_string_return(string_returns);
+            }
+        }
+
+        // If the user has promised, using `returns_owned!`, that a returned
+        // raw pointer owns its object, hand that ownership to a UniquePtr.
+        if let Some(qualified_cpp_name) = qualified_cpp_name {
+            if self.config.returns_owned(&qualified_cpp_name) {
+                let pointee = match &ret_type {
+                    ReturnType::Type(_, ty) => owned_pointee(ty),
+                    ReturnType::Default => None,
+                };
+                match pointee {
+                    Some(pointee) => {
+                        ret_type = parse_quote! { -> #pointee };
+                        ret_type_conversion =
+                            Some(TypeConversionPolicy::new_owning_ptr_to_unique_ptr(pointee));
+                    }
+                    None => {
+                        set_ignore_reason(ConvertError::ReturnsOwnedNonPointer(qualified_cpp_name))
+                    }
+                }
+            }
+        }
 
         // Do we need to convert either parameters or return type?
         let param_conversion_needed = param_details.iter().any(|b| b.conversion.cpp_work_needed());
         let ret_type_conversion_needed = ret_type_conversion
             .as_ref()
-            .map_or(false, |x| x.cpp_work_needed());
+            .is_some_and(|x| x.cpp_work_needed());
+        // If the return type is converted in Rust, the cxx::bridge function
+        // can't have the name of the Rust wrapper, else cxx would generate
+        // a clashing method.
+        let ret_type_rust_conversion_needed = ret_type_conversion
+            .as_ref()
+            .is_some_and(|x| x.rust_work_needed());
         // See https://github.com/dtolnay/cxx/issues/878 for the reason for this next line.
         let effective_cpp_name = cpp_name.as_ref().unwrap_or(&rust_name);
         let cpp_name_incompatible_with_cxx =
             validate_ident_ok_for_rust(effective_cpp_name).is_err();
+        let thin_call = analyze_thin_call(
+            self.config,
+            &kind,
+            effective_cpp_name,
+            &cxxbridge_name,
+            &param_details,
+            &ret_type,
+            &ret_type_conversion,
+        );
         // If possible, we'll put knowledge of the C++ API directly into the cxx::bridge
         // mod. However, there are various circumstances where cxx can't work with the existing
         // C++ API and we need to create a C++ wrapper function which is more cxx-compliant.
         // That wrapper function is included in the cxx::bridge, and calls through to the
         // original function.
         let wrapper_function_needed = match kind {
+            // The thin wrapper replaces any cxx wrapper.
+            _ if thin_call.is_some() => false,
             FnKind::Method {
                 method_kind:
                     MethodKind::Static
@@ -1231,21 +1691,36 @@ impl<'a> FnAnalyzer<'a> {
             FnKind::Method { .. } if cxxbridge_name != rust_name => true,
             _ if param_conversion_needed => true,
             _ if ret_type_conversion_needed => true,
+            _ if ret_type_rust_conversion_needed => true,
             _ if cpp_name_incompatible_with_cxx => true,
             _ if fun.synthetic_cpp.is_some() => true,
+            _ if fun.internal_linkage => true,
+            _ if const_cast_receiver => true,
+            _ if const_cast_args.is_some() => true,
             _ => false,
         };
 
+        let batch = analyze_batch(
+            self.config,
+            ns,
+            &kind,
+            effective_cpp_name,
+            &rust_name,
+            &cxxbridge_name,
+            &param_details,
+            &ret_type,
+        );
+
         let cpp_wrapper = if wrapper_function_needed {
             // Generate a new layer of C++ code to wrap/unwrap parameters
             // and return values into/out of std::unique_ptrs.
-            let cpp_construction_ident = make_ident(&effective_cpp_name);
+            let cpp_construction_ident = make_ident(effective_cpp_name);
             let joiner = if cxxbridge_name.to_string().ends_with('_') {
                 ""
             } else {
                 "_"
             };
-            cxxbridge_name = make_ident(&format!("{}{}autocxx_wrapper", cxxbridge_name, joiner));
+            cxxbridge_name = make_ident(format!("{}{}autocxx_wrapper", cxxbridge_name, joiner));
             let (payload, cpp_function_kind) = match fun.synthetic_cpp.as_ref().cloned() {
                 Some((payload, cpp_function_kind)) => (payload, cpp_function_kind),
                 None => match kind {
@@ -1286,12 +1761,38 @@ impl<'a> FnAnalyzer<'a> {
                         ),
                         CppFunctionKind::Function,
                     ),
+                    FnKind::Method { ref impl_for, .. } if const_cast_receiver => (
+                        CppFunctionBody::ConstCastMethodCall(
+                            impl_for.clone(),
+                            cpp_construction_ident,
+                        ),
+                        CppFunctionKind::Method,
+                    ),
+                    FnKind::Method { .. } if const_cast_args.is_some() => (
+                        CppFunctionBody::FunctionCallCastingArgs(
+                            ns.clone(),
+                            cpp_construction_ident,
+                            const_cast_args.unwrap(),
+                        ),
+                        CppFunctionKind::Method,
+                    ),
                     FnKind::Method { .. } => (
                         CppFunctionBody::FunctionCall(ns.clone(), cpp_construction_ident),
                         CppFunctionKind::Method,
                     ),
+                    FnKind::Function if const_cast_args.is_some() => (
+                        CppFunctionBody::FunctionCallCastingArgs(
+                            fun.reexported_from.as_ref().unwrap_or(ns).clone(),
+                            cpp_construction_ident,
+                            const_cast_args.unwrap(),
+                        ),
+                        CppFunctionKind::Function,
+                    ),
                     _ => (
-                        CppFunctionBody::FunctionCall(ns.clone(), cpp_construction_ident),
+                        CppFunctionBody::FunctionCall(
+                            fun.reexported_from.as_ref().unwrap_or(ns).clone(),
+                            cpp_construction_ident,
+                        ),
                         CppFunctionKind::Function,
                     ),
                 },
@@ -1337,6 +1838,7 @@ impl<'a> FnAnalyzer<'a> {
                 kind: cpp_function_kind,
                 pass_obs_field: false,
                 qualification: None,
+                source_location: fun.source_location.clone(),
             })
         } else {
             None
@@ -1350,6 +1852,7 @@ impl<'a> FnAnalyzer<'a> {
 
         let rust_wrapper_needed = match kind {
             FnKind::TraitMethod { .. } => true,
+            _ if ret_type_rust_conversion_needed => true,
             FnKind::Method { .. } => any_param_needs_rust_conversion || cxxbridge_name != rust_name,
             _ => any_param_needs_rust_conversion,
         };
@@ -1368,6 +1871,7 @@ impl<'a> FnAnalyzer<'a> {
 
         let analysis = FnAnalysis {
             cxxbridge_name: cxxbridge_name.clone(),
+            unshortened_cxxbridge_name,
             rust_name: rust_name.clone(),
             rust_rename_strategy,
             params,
@@ -1382,6 +1886,11 @@ impl<'a> FnAnalyzer<'a> {
             ignore_reason,
             externally_callable,
             rust_wrapper_needed,
+            cfg,
+            deprecated,
+            batch,
+            thin_call,
+            associated_fn,
         };
         let name = ApiName::new_with_cpp_name(ns, cxxbridge_name, cpp_name);
         (analysis, name)
@@ -1432,6 +1941,104 @@ impl<'a> FnAnalyzer<'a> {
         overload_tracker.get_method_real_name(type_ident, rust_name)
     }
 
+    /// The name of a helper (e.g. `make_unique`) generated for this type.
+    /// That's the name chosen by the user using `helper_name!`, if any, but
+    /// may have been changed to avoid clashing with a method.
+    fn get_helper_name(&self, self_ty: &QualifiedName, helper: &'static str) -> String {
+        self.helper_names
+            .get(&(self_ty.clone(), helper))
+            .cloned()
+            .unwrap_or_else(|| {
+                let qualified_helper = format!("{}::{}", self_ty.to_cpp_name(), helper);
+                self.config
+                    .get_helper_name(&qualified_helper)
+                    .unwrap_or(helper)
+                    .to_string()
+            })
+    }
+
+    /// Converts a method name to `snake_case` if the user asked for that
+    /// using `snake_case_methods!`, and it's safe to do so. The original
+    /// name is retained as the C++ name, so cxx still calls the right
+    /// method.
+    fn snake_case_name(&self, ns: &Namespace, type_ident: &str, rust_name: String) -> String {
+        if !self.config.snake_case_methods() {
+            return rust_name;
+        }
+        let snake_name = to_snake_case(&rust_name);
+        let key = (ns.clone(), type_ident.to_string(), snake_name);
+        if is_rust_keyword(&key.2) || self.snake_case_collisions.contains(&key) {
+            rust_name
+        } else {
+            key.2
+        }
+    }
+
+    /// Finds out whether a method is half of a getter/setter pair which
+    /// the user asked us to rename using `property_accessors!`.
+    fn property_accessor(
+        &self,
+        ns: &Namespace,
+        type_ident: &str,
+        cpp_name: &str,
+    ) -> Option<&PropertyAccessor> {
+        self.property_accessors
+            .get(&(ns.clone(), type_ident.to_string(), cpp_name.to_string()))
+    }
+
+    /// Names a constructor (or its `make_unique` equivalent). Usually
+    /// constructors are named in the order we encounter them, but if the
+    /// user has asked for a particular constructor to be canonical using
+    /// `canonical_constructor!`, that one gets the unsuffixed name and the
+    /// others are numbered. `fun` is `None` for copy and move constructors,
+    /// which can never be canonical.
+    fn get_constructor_name(
+        &mut self,
+        ns: &Namespace,
+        self_ty: &QualifiedName,
+        type_ident: &str,
+        fun: Option<&FuncToConvert>,
+        rust_name: String,
+    ) -> String {
+        let cpp_name = self_ty.to_cpp_name();
+        if !self.config.has_canonical_constructor(&cpp_name) {
+            return self.get_overload_name(ns, type_ident, rust_name);
+        }
+        let is_canonical = fun
+            .and_then(constructor_signature)
+            .map(|params| self.config.is_canonical_constructor(&cpp_name, &params))
+            .unwrap_or_default();
+        if is_canonical {
+            return rust_name;
+        }
+        let overload_tracker = self.overload_trackers_by_mod.entry(ns.clone()).or_default();
+        overload_tracker.reserve_method_name(type_ident, &rust_name);
+        overload_tracker.get_method_real_name(type_ident, rust_name)
+    }
+
+    /// Whether the user has blocked this constructor using
+    /// `block_constructors!` with its signature.
+    fn is_blocked_constructor(&self, kind: &FnKind, fun: &FuncToConvert) -> bool {
+        let self_ty = match kind {
+            FnKind::Method {
+                impl_for,
+                method_kind: MethodKind::Constructor { .. } | MethodKind::MakeUnique,
+            }
+            | FnKind::TraitMethod {
+                impl_for,
+                kind: TraitMethodKind::CopyConstructor | TraitMethodKind::MoveConstructor,
+                ..
+            } => impl_for,
+            _ => return false,
+        };
+        constructor_signature(fun)
+            .map(|params| {
+                self.config
+                    .is_constructor_blocked(&self_ty.to_cpp_name(), &params)
+            })
+            .unwrap_or_default()
+    }
+
     /// Determine if this synthetic function should actually result in the implementation
     /// of a trait, rather than a function/method.
     fn trait_creation_details_for_synthetic_function(
@@ -1495,25 +2102,44 @@ impl<'a> FnAnalyzer<'a> {
                     rust_name,
                 ))
             }
-            TraitSynthesis::AllocUninitialized(ty) => self.generate_alloc_or_deallocate(
+            TraitSynthesis::AllocUninitialized(ty) => self.generate_unsafe_trait_method(
                 ideal_rust_name,
                 ty,
+                parse_quote! { autocxx::moveit::MakeCppStorage },
                 "allocate_uninitialized_cpp_storage",
                 TraitMethodKind::Alloc,
             ),
-            TraitSynthesis::FreeUninitialized(ty) => self.generate_alloc_or_deallocate(
+            TraitSynthesis::FreeUninitialized(ty) => self.generate_unsafe_trait_method(
                 ideal_rust_name,
                 ty,
+                parse_quote! { autocxx::moveit::MakeCppStorage },
                 "free_uninitialized_cpp_storage",
                 TraitMethodKind::Dealloc,
             ),
+            TraitSynthesis::AddRef(ty) => self.generate_unsafe_trait_method(
+                ideal_rust_name,
+                ty,
+                parse_quote! { autocxx::RefCounted },
+                "add_ref",
+                TraitMethodKind::AddRef,
+            ),
+            TraitSynthesis::Release(ty) => self.generate_unsafe_trait_method(
+                ideal_rust_name,
+                ty,
+                parse_quote! { autocxx::RefCounted },
+                "release",
+                TraitMethodKind::Release,
+            ),
         })
     }
 
-    fn generate_alloc_or_deallocate(
+    /// Generates a method of an unsafe trait, such as `MakeCppStorage`,
+    /// which takes and returns raw pointers.
+    fn generate_unsafe_trait_method(
         &mut self,
         ideal_rust_name: &str,
         ty: &QualifiedName,
+        trait_signature: Type,
         method_name: &str,
         kind: TraitMethodKind,
     ) -> Option<(FnKind, ErrorContext, String)> {
@@ -1526,7 +2152,7 @@ impl<'a> FnAnalyzer<'a> {
                 details: Box::new(TraitMethodDetails {
                     trt: TraitImplSignature {
                         ty: Type::Path(typ),
-                        trait_signature: parse_quote! { autocxx::moveit::MakeCppStorage },
+                        trait_signature,
                         unsafety: Some(parse_quote! { unsafe }),
                     },
                     avoid_self: false,
@@ -1541,6 +2167,99 @@ impl<'a> FnAnalyzer<'a> {
         ))
     }
 
+    /// If the user has asked, using `treat_as_const!`, for this non-const
+    /// method to have a `&self` receiver, returns its inputs with the `this`
+    /// parameter made const.
+    fn const_cast_receiver_inputs(
+        &self,
+        fun: &FuncToConvert,
+        method_name: &str,
+    ) -> Option<Punctuated<FnArg, Comma>> {
+        let mut inputs = fun.inputs.clone();
+        let this_type = inputs.iter_mut().find_map(|input| match input {
+            FnArg::Typed(pt) if matches!(pt.pat.as_ref(), syn::Pat::Ident(pp) if pp.ident == "this") => {
+                Some(&mut pt.ty)
+            }
+            _ => None,
+        })?;
+        let elem = match this_type.as_ref() {
+            Type::Ptr(TypePtr {
+                elem,
+                mutability: Some(_),
+                ..
+            }) => match elem.as_ref() {
+                Type::Path(typ) => typ.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let qualified_name = format!(
+            "{}::{}",
+            QualifiedName::from_type_path(&elem).to_cpp_name(),
+            method_name
+        );
+        if !self.config.is_treated_as_const(&qualified_name) {
+            return None;
+        }
+        *this_type = parse_quote! { *const #elem };
+        Some(inputs)
+    }
+
+    /// If the user has asked, using `shared_ref_variant!`, for a variant of
+    /// this function which takes shared references, returns it: a copy of
+    /// the function whose non-const reference parameters are made const.
+    fn create_shared_ref_variant(
+        &self,
+        fun: &FuncToConvert,
+        analysis: &FnAnalysis,
+        name: &ApiName,
+    ) -> Option<(Box<FuncToConvert>, ApiName)> {
+        let cpp_name = match &analysis.kind {
+            FnKind::Function => name.qualified_cpp_name(),
+            FnKind::Method {
+                impl_for,
+                method_kind: MethodKind::Normal(_),
+            } => format!("{}::{}", impl_for.to_cpp_name(), name.cpp_name()),
+            _ => return None,
+        };
+        if !self.config.wants_shared_ref_variant(&cpp_name) {
+            return None;
+        }
+        let mut variant = fun.clone();
+        let mut made_const = Vec::new();
+        for input in variant.inputs.iter_mut() {
+            if let FnArg::Typed(pt) = input {
+                let ident = match pt.pat.as_ref() {
+                    syn::Pat::Ident(pp) if pp.ident != "this" => &pp.ident,
+                    _ => continue,
+                };
+                if !fun.references.ref_params.contains(ident) {
+                    continue;
+                }
+                let elem = match pt.ty.as_ref() {
+                    Type::Ptr(TypePtr {
+                        elem,
+                        mutability: Some(_),
+                        ..
+                    }) => elem.clone(),
+                    _ => continue,
+                };
+                made_const.push(ident.clone());
+                pt.ty = parse_quote! { *const #elem };
+            }
+        }
+        if made_const.is_empty() {
+            return None;
+        }
+        variant.provenance = Provenance::SynthesizedSharedRefVariant(made_const);
+        let variant_name = ApiName::new_with_cpp_name(
+            name.name.get_namespace(),
+            fun.ident.clone(),
+            Some(name.cpp_name()),
+        );
+        Some((Box::new(variant), variant_name))
+    }
+
     fn get_function_overload_name(&mut self, ns: &Namespace, ideal_rust_name: String) -> String {
         let overload_tracker = self.overload_trackers_by_mod.entry(ns.clone()).or_default();
         overload_tracker.get_function_real_name(ideal_rust_name)
@@ -1938,6 +2657,10 @@ impl<'a> FnAnalyzer<'a> {
                         is_deleted: false,
                         add_to_trait: None,
                         synthetic_cpp: None,
+                        internal_linkage: false,
+                        source_location: None,
+                        deprecation: None,
+                        reexported_from: None,
                         provenance: Provenance::SynthesizedOther,
                     }),
                 )
@@ -1949,6 +2672,59 @@ impl<'a> FnAnalyzer<'a> {
     }
 }
 
+/// The name by which the user refers to a function or method in
+/// directives such as `returns_owned!`, e.g. `ns::Factory::make`.
+fn qualified_cpp_name(kind: &FnKind, ns: &Namespace, cpp_name: &str) -> Option<String> {
+    let owner = match kind {
+        FnKind::Function => ns.iter().join("::"),
+        FnKind::Method { impl_for, .. } => impl_for.to_cpp_name(),
+        FnKind::TraitMethod { .. } => return None,
+    };
+    Some(
+        [owner.as_str(), cpp_name]
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .join("::"),
+    )
+}
+
+/// The type to which a non-const raw pointer points, if it's a type which
+/// may be held in a `UniquePtr`, so that the pointer may own its object.
+fn owned_pointee(ty: &Type) -> Option<Type> {
+    match ty {
+        Type::Ptr(TypePtr {
+            elem,
+            mutability: Some(_),
+            ..
+        }) => match elem.as_ref() {
+            Type::Path(typ) if !known_types().is_scalar(&QualifiedName::from_type_path(typ)) => {
+                Some(elem.as_ref().clone())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// For each of a function's inputs, the type to which it must be
+/// const_cast when calling C++, if it's one of the given parameters.
+fn const_cast_arg_types(inputs: &Punctuated<FnArg, Comma>, params: &[Ident]) -> Vec<Option<Type>> {
+    inputs
+        .iter()
+        .map(|input| match input {
+            FnArg::Typed(pt) => match (pt.pat.as_ref(), pt.ty.as_ref()) {
+                (syn::Pat::Ident(pp), Type::Ptr(TypePtr { elem, .. }))
+                    if params.contains(&pp.ident) =>
+                {
+                    Some(elem.as_ref().clone())
+                }
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
 fn error_context_for_method(self_ty: &QualifiedName, rust_name: &str) -> ErrorContext {
     ErrorContext::Method {
         self_ty: self_ty.get_final_ident(),
@@ -1991,6 +2767,22 @@ impl Api<FnPhase> {
                     ..
                 },
                 ..
+            } | Api::Function {
+                analysis: FnAnalysis {
+                    batch: Some(..),
+                    ignore_reason: Ok(_),
+                    externally_callable: true,
+                    ..
+                },
+                ..
+            } | Api::Function {
+                analysis: FnAnalysis {
+                    thin_call: Some(..),
+                    ignore_reason: Ok(_),
+                    externally_callable: true,
+                    ..
+                },
+                ..
             } | Api::StringConstructor { .. }
                 | Api::ConcreteType { .. }
                 | Api::CType { .. }
@@ -2006,6 +2798,47 @@ impl Api<FnPhase> {
                     },
                     ..
                 }
+        ) || matches!(
+            &self,
+            Api::Struct {
+                analysis: PodAndDepAnalysis {
+                    pod: PodAnalysis {
+                        layout_override: Some(_),
+                        ..
+                    },
+                    ..
+                },
+                ..
+            } | Api::ExternCppType {
+                layout: Some(_),
+                ..
+            } | Api::ExternCppType {
+                cpp_shims: Some(_),
+                ..
+            }
+        ) || self.supports_vector_push()
+    }
+
+    /// Whether this is a type which can be pushed onto a `CxxVector` by
+    /// moving it, for which we generate a C++ helper.
+    pub(crate) fn supports_vector_push(&self) -> bool {
+        matches!(
+            &self,
+            Api::Struct {
+                details,
+                analysis: PodAndDepAnalysis {
+                    pod: PodAnalysis {
+                        kind: TypeKind::Pod | TypeKind::NonPod,
+                        ..
+                    },
+                    constructors: PublicConstructors {
+                        move_constructor: true,
+                        destructor: true,
+                    },
+                    ..
+                },
+                ..
+            } if details.item.generics.params.is_empty()
         )
     }
 
@@ -2020,3 +2853,34 @@ impl Api<FnPhase> {
         }
     }
 }
+
+/// Describes the parameters of a constructor as a comma-separated list of
+/// C++ types, for matching against `block_constructors!` and
+/// `canonical_constructor!` directives. Returns `None` if any of the
+/// parameter types can't be described.
+fn constructor_signature(fun: &FuncToConvert) -> Option<String> {
+    let params: Option<Vec<String>> = fun
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(pt) => Some(pt),
+            FnArg::Receiver(_) => None,
+        })
+        .filter_map(|pt| match pt.pat.as_ref() {
+            Pat::Ident(pi) if pi.ident == "this" => None,
+            Pat::Ident(pi) => Some((&pi.ident, pt.ty.as_ref())),
+            _ => None,
+        })
+        .map(|(id, ty)| {
+            let cpp_type = type_to_cpp(ty, &HashMap::new()).ok()?;
+            Some(if fun.references.rvalue_ref_params.contains(id) {
+                format!("{}&&", cpp_type.strip_suffix('*')?)
+            } else if fun.references.ref_params.contains(id) {
+                format!("{}&", cpp_type.strip_suffix('*')?)
+            } else {
+                cpp_type
+            })
+        })
+        .collect();
+    params.map(|params| params.join(","))
+}
@@ -35,6 +35,21 @@ impl OverloadTracker {
         self.get_name(Some(type_name), found_name)
     }
 
+    /// Ensures that no method of this type is given exactly this name,
+    /// so that it can be used for something else. Subsequent methods
+    /// will be given suffixed names.
+    pub(crate) fn reserve_method_name(&mut self, type_name: &str, found_name: &str) {
+        let offset = self
+            .offset_by_type_and_name
+            .entry(type_name.to_string())
+            .or_default()
+            .entry(found_name.to_string())
+            .or_default();
+        if *offset == 0 {
+            *offset = 1;
+        }
+    }
+
     fn get_name(&mut self, type_name: Option<&str>, cpp_method_name: String) -> String {
         let registry = match type_name {
             Some(type_name) => self
@@ -74,4 +89,14 @@ mod tests {
         assert_eq!(ot.get_method_real_name("Ty2", "bob".into()), "bob");
         assert_eq!(ot.get_method_real_name("Ty2", "bob".into()), "bob1");
     }
+
+    #[test]
+    fn test_reserved_method_name() {
+        let mut ot = OverloadTracker::default();
+        ot.reserve_method_name("Ty1", "bob");
+        assert_eq!(ot.get_method_real_name("Ty1", "bob".into()), "bob1");
+        ot.reserve_method_name("Ty1", "bob");
+        assert_eq!(ot.get_method_real_name("Ty1", "bob".into()), "bob2");
+        assert_eq!(ot.get_method_real_name("Ty2", "bob".into()), "bob");
+    }
 }
@@ -0,0 +1,263 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    conversion::analysis::reserved_names::{CONSTRUCTOR, MAKE_UNIQUE},
+    types::{is_rust_keyword, Namespace},
+};
+
+use super::snake_case::to_snake_case;
+
+/// Identifies a method by the namespace and name of its type, and its
+/// original C++ name.
+pub(crate) type PropertyMethodKey = (Namespace, String, String);
+
+/// The shape of an instance method, which is all we need to know to
+/// spot getters and setters.
+pub(crate) struct MethodShape {
+    pub(crate) ns: Namespace,
+    pub(crate) type_name: String,
+    pub(crate) cpp_name: String,
+    /// Parameters, not counting `this`.
+    pub(crate) param_count: usize,
+    pub(crate) returns_value: bool,
+}
+
+/// One half of a getter/setter pair found by `property_accessors!`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PropertyAccessor {
+    pub(crate) rust_name: String,
+    pub(crate) is_getter: bool,
+    /// The Rust name of the other half of the pair.
+    pub(crate) partner_rust_name: String,
+}
+
+impl PropertyAccessor {
+    /// Documentation linking to the other half of the pair.
+    pub(crate) fn cross_link_doc(&self) -> String {
+        let what = if self.is_getter { "sets" } else { "returns" };
+        format!(
+            "See also [`{}`](Self::{}), which {} the same property.",
+            self.partner_rust_name, self.partner_rust_name, what
+        )
+    }
+}
+
+/// Splits a name such as `get_width`, `getWidth` or `SetWidth` into
+/// whether it's a getter, and the name of the property (`width`).
+fn split_accessor_name(cpp_name: &str) -> Option<(bool, String)> {
+    let snake_name = to_snake_case(cpp_name);
+    let (is_getter, property) = if let Some(property) = snake_name.strip_prefix("get_") {
+        (true, property)
+    } else {
+        (false, snake_name.strip_prefix("set_")?)
+    };
+    if property.is_empty()
+        || property.starts_with(|c: char| c.is_ascii_digit() || c == '_')
+        || is_rust_keyword(property)
+    {
+        return None;
+    }
+    Some((is_getter, property.to_string()))
+}
+
+/// Finds getter/setter pairs among the instance methods of each type,
+/// e.g. `getWidth() const` and `setWidth(int)`, and names them `width`
+/// and `set_width`. A getter takes no parameters and returns something;
+/// a setter takes one parameter and returns nothing. Overloaded methods
+/// are never considered, and neither are pairs whose new names would
+/// clash with another method or one of our helpers, in which case a warning is logged. Each
+/// pair found is logged, so that the user can see what's been renamed.
+pub(crate) fn find_properties(
+    methods: impl Iterator<Item = MethodShape>,
+) -> HashMap<PropertyMethodKey, PropertyAccessor> {
+    let mut methods_by_type: HashMap<(Namespace, String), Vec<MethodShape>> = HashMap::new();
+    for method in methods {
+        methods_by_type
+            .entry((method.ns.clone(), method.type_name.clone()))
+            .or_default()
+            .push(method);
+    }
+    let mut accessors = HashMap::new();
+    for methods in methods_by_type.into_values() {
+        let mut overloads: HashMap<&str, usize> = HashMap::new();
+        for method in &methods {
+            *overloads.entry(&method.cpp_name).or_default() += 1;
+        }
+        let mut getters = BTreeMap::new();
+        let mut setters = BTreeMap::new();
+        for method in &methods {
+            if overloads[method.cpp_name.as_str()] > 1 {
+                continue;
+            }
+            match split_accessor_name(&method.cpp_name) {
+                Some((true, property)) if method.param_count == 0 && method.returns_value => {
+                    getters.insert(property, method);
+                }
+                Some((false, property)) if method.param_count == 1 && !method.returns_value => {
+                    setters.insert(property, method);
+                }
+                _ => {}
+            }
+        }
+        for (property, getter) in getters {
+            let setter = match setters.get(&property) {
+                Some(setter) => setter,
+                None => continue,
+            };
+            let setter_rust_name = format!("set_{}", property);
+            let is_taken = |name: &str, by_other_than: &str| {
+                name == CONSTRUCTOR
+                    || name == MAKE_UNIQUE
+                    || methods.iter().any(|method| {
+                        method.cpp_name != by_other_than
+                            && (method.cpp_name == name || to_snake_case(&method.cpp_name) == name)
+                    })
+            };
+            if is_taken(&property, &getter.cpp_name)
+                || is_taken(&setter_rust_name, &setter.cpp_name)
+            {
+                log::warn!(
+                    "Not treating {} and {} of {} as a property, since another method is already called {} or {}",
+                    getter.cpp_name,
+                    setter.cpp_name,
+                    getter.type_name,
+                    property,
+                    setter_rust_name
+                );
+                continue;
+            }
+            log::info!(
+                "Exposing {}::{} and {}::{} as the property {} (with {} and {})",
+                getter.type_name,
+                getter.cpp_name,
+                setter.type_name,
+                setter.cpp_name,
+                property,
+                property,
+                setter_rust_name
+            );
+            accessors.insert(
+                (
+                    getter.ns.clone(),
+                    getter.type_name.clone(),
+                    getter.cpp_name.clone(),
+                ),
+                PropertyAccessor {
+                    rust_name: property.clone(),
+                    is_getter: true,
+                    partner_rust_name: setter_rust_name.clone(),
+                },
+            );
+            accessors.insert(
+                (
+                    setter.ns.clone(),
+                    setter.type_name.clone(),
+                    setter.cpp_name.clone(),
+                ),
+                PropertyAccessor {
+                    rust_name: setter_rust_name,
+                    is_getter: false,
+                    partner_rust_name: property,
+                },
+            );
+        }
+    }
+    accessors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_properties, split_accessor_name, MethodShape, PropertyAccessor, PropertyMethodKey,
+    };
+    use crate::types::Namespace;
+    use std::collections::HashMap;
+
+    fn method(cpp_name: &str, param_count: usize, returns_value: bool) -> MethodShape {
+        MethodShape {
+            ns: Namespace::new(),
+            type_name: "Widget".to_string(),
+            cpp_name: cpp_name.to_string(),
+            param_count,
+            returns_value,
+        }
+    }
+
+    fn rust_name(
+        accessors: &HashMap<PropertyMethodKey, PropertyAccessor>,
+        cpp_name: &str,
+    ) -> Option<String> {
+        accessors
+            .get(&(Namespace::new(), "Widget".to_string(), cpp_name.to_string()))
+            .map(|accessor| accessor.rust_name.clone())
+    }
+
+    #[test]
+    fn test_split_accessor_name() {
+        assert_eq!(
+            split_accessor_name("getWidth"),
+            Some((true, "width".to_string()))
+        );
+        assert_eq!(
+            split_accessor_name("SetHTTPStatus"),
+            Some((false, "http_status".to_string()))
+        );
+        assert_eq!(
+            split_accessor_name("get_size"),
+            Some((true, "size".to_string()))
+        );
+        assert_eq!(split_accessor_name("getaway"), None);
+        assert_eq!(split_accessor_name("get"), None);
+        assert_eq!(split_accessor_name("getType"), None);
+    }
+
+    #[test]
+    fn test_find_properties() {
+        let accessors = find_properties(
+            [
+                method("getWidth", 0, true),
+                method("setWidth", 1, false),
+                method("GetHeight", 0, true),
+                method("SetHeight", 2, false),
+                method("getDepth", 0, true),
+                method("setDepth", 1, false),
+                method("depth", 0, true),
+                method("getName", 0, true),
+                method("setName", 1, false),
+                method("setName", 2, false),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(rust_name(&accessors, "getWidth").as_deref(), Some("width"));
+        assert_eq!(
+            rust_name(&accessors, "setWidth").as_deref(),
+            Some("set_width")
+        );
+        assert_eq!(
+            accessors
+                .get(&(
+                    Namespace::new(),
+                    "Widget".to_string(),
+                    "getWidth".to_string()
+                ))
+                .unwrap()
+                .partner_rust_name,
+            "set_width"
+        );
+        // Not a setter.
+        assert_eq!(rust_name(&accessors, "GetHeight"), None);
+        // Would clash.
+        assert_eq!(rust_name(&accessors, "getDepth"), None);
+        // Overloaded.
+        assert_eq!(rust_name(&accessors, "getName"), None);
+        assert_eq!(accessors.len(), 2);
+    }
+}
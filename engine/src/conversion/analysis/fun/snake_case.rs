@@ -0,0 +1,103 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::types::Namespace;
+
+/// Identifies a method by the namespace and name of its type, and its
+/// proposed `snake_case` name.
+pub(crate) type SnakeCaseMethodKey = (Namespace, String, String);
+
+/// Converts a C++ method name such as `GetHTTPStatus` or `getValue`
+/// into Rust style, e.g. `get_http_status` or `get_value`.
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+    for (idx, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev = idx.checked_sub(1).map(|prev| chars[prev]);
+            let next = chars.get(idx + 1);
+            let starts_word = match prev {
+                Some(prev) if prev.is_lowercase() || prev.is_ascii_digit() => true,
+                // The last capital of an acronym starts the next word.
+                Some(prev) if prev.is_uppercase() => next.is_some_and(|next| next.is_lowercase()),
+                _ => false,
+            };
+            if starts_word {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(*c);
+        }
+    }
+    snake
+}
+
+/// Given the original C++ names of all the methods of each type, finds
+/// those which can't be converted to `snake_case` because some other
+/// method would end up with the same name (e.g. `GetValue` and
+/// `get_value`). Overloads share a C++ name, so don't count as a
+/// collision.
+pub(crate) fn find_snake_case_collisions(
+    methods: impl Iterator<Item = (Namespace, String, String)>,
+) -> HashSet<SnakeCaseMethodKey> {
+    let mut originals: HashMap<SnakeCaseMethodKey, BTreeSet<String>> = HashMap::new();
+    for (ns, type_name, cpp_name) in methods {
+        originals
+            .entry((ns, type_name, to_snake_case(&cpp_name)))
+            .or_default()
+            .insert(cpp_name);
+    }
+    originals
+        .into_iter()
+        .filter(|(_, cpp_names)| cpp_names.len() > 1)
+        .map(|(key, cpp_names)| {
+            log::warn!(
+                "Not converting methods {} of {} to snake_case, since they would all be called {}",
+                cpp_names.into_iter().collect::<Vec<_>>().join(", "),
+                key.1,
+                key.2
+            );
+            key
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_snake_case_collisions, to_snake_case};
+    use crate::types::Namespace;
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("GetValue"), "get_value");
+        assert_eq!(to_snake_case("getValue"), "get_value");
+        assert_eq!(to_snake_case("GetHTTPStatus"), "get_http_status");
+        assert_eq!(to_snake_case("Get2DPoint"), "get2_d_point");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+    }
+
+    #[test]
+    fn test_collisions() {
+        let ns = Namespace::new();
+        let methods = [
+            ("Widget", "GetValue"),
+            ("Widget", "get_value"),
+            ("Widget", "SetValue"),
+            ("Widget", "SetValue"),
+            ("Gadget", "GetValue"),
+        ]
+        .into_iter()
+        .map(|(ty, method)| (ns.clone(), ty.to_string(), method.to_string()));
+        let collisions = find_snake_case_collisions(methods);
+        assert_eq!(collisions.len(), 1);
+        assert!(collisions.contains(&(ns, "Widget".to_string(), "get_value".to_string())));
+    }
+}
@@ -68,10 +68,55 @@ pub(super) fn create_subclass_fn_wrapper(
         add_to_trait: fun.add_to_trait.clone(),
         is_deleted: fun.is_deleted,
         synthetic_cpp: None,
+        internal_linkage: false,
+        source_location: fun.source_location.clone(),
+        deprecation: fun.deprecation.clone(),
+        reexported_from: None,
         provenance: Provenance::SynthesizedOther,
     })
 }
 
+/// Creates a method on the C++ subclass which exposes a protected method
+/// of its superclass. The C++ subclass will make the superclass method
+/// public with a `using` declaration, so we can call it directly.
+pub(super) fn create_subclass_protected_fn_wrapper(
+    sub: &SubclassName,
+    fun: &FuncToConvert,
+    rust_name: &str,
+    cpp_name: String,
+) -> (Box<FuncToConvert>, ApiName) {
+    let self_ty = Some(sub.cpp());
+    let api_name = ApiName::new_with_cpp_name(
+        &Namespace::new(),
+        make_ident(format!("{}_{}", sub.cpp().get_final_item(), rust_name)),
+        Some(cpp_name),
+    );
+    let fun = Box::new(FuncToConvert {
+        synthesized_this_type: self_ty.clone(),
+        self_ty,
+        ident: make_ident(rust_name),
+        doc_attr: fun.doc_attr.clone(),
+        inputs: fun.inputs.clone(),
+        output: fun.output.clone(),
+        vis: fun.vis.clone(),
+        virtualness: Virtualness::None,
+        cpp_vis: CppVisibility::Public,
+        special_member: None,
+        unused_template_param: fun.unused_template_param,
+        original_name: None,
+        references: fun.references.clone(),
+        add_to_trait: None,
+        is_deleted: fun.is_deleted,
+        synthetic_cpp: None,
+        internal_linkage: false,
+        source_location: fun.source_location.clone(),
+        deprecation: fun.deprecation.clone(),
+        reexported_from: None,
+        provenance: Provenance::SynthesizedSubclassProtectedAccess(sub.clone()),
+    });
+    (fun, api_name)
+}
+
 pub(super) fn create_subclass_trait_item(
     name: ApiName,
     analysis: &FnAnalysis,
@@ -146,6 +191,7 @@ pub(super) fn create_subclass_function(
                 kind,
                 pass_obs_field: true,
                 qualification: Some(cpp),
+                source_location: None,
             },
             superclass: superclass.clone(),
             receiver_mutability: receiver_mutability.clone(),
@@ -190,6 +236,7 @@ pub(super) fn create_subclass_constructor(
         pass_obs_field: false,
         qualification: Some(cpp.clone()),
         original_cpp_name: cpp.to_cpp_name(),
+        source_location: fun.source_location.clone(),
     };
     let subclass_constructor_details = Box::new(SubclassConstructorDetails {
         subclass: sub.clone(),
@@ -202,7 +249,7 @@ pub(super) fn create_subclass_constructor(
     let mut existing_params = fun.inputs.clone();
     if let Some(FnArg::Typed(PatType { ty, .. })) = existing_params.first_mut() {
         if let Type::Ptr(TypePtr { elem, .. }) = &mut **ty {
-            *elem = Box::new(Type::Path(sub.cpp().to_type_path()));
+            **elem = Type::Path(sub.cpp().to_type_path());
         } else {
             panic!("Unexpected self type parameter when creating subclass constructor");
         }
@@ -236,6 +283,10 @@ pub(super) fn create_subclass_constructor(
         add_to_trait: None,
         is_deleted: fun.is_deleted,
         synthetic_cpp: None,
+        internal_linkage: false,
+        source_location: fun.source_location.clone(),
+        deprecation: fun.deprecation.clone(),
+        reexported_from: None,
         provenance: Provenance::SynthesizedSubclassConstructor(subclass_constructor_details),
     });
     let subclass_constructor_name = ApiName::new_with_cpp_name(
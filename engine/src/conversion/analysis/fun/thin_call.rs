@@ -0,0 +1,79 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::{ExceptionPolicy, IncludeCppConfig};
+use syn::{Ident, ReturnType, Type};
+
+use crate::{
+    known_types::known_types,
+    types::{make_ident, QualifiedName},
+};
+
+use super::{
+    function_wrapper::TypeConversionPolicy, ArgumentAnalysis, FnKind, MethodKind,
+    ReceiverMutability,
+};
+
+/// A getter which is called through a plain `extern "C"` function rather
+/// than through cxx, requested using `thin_call!`.
+#[derive(Clone)]
+pub(crate) struct ThinCallDetails {
+    /// The name of the method to call in C++.
+    pub(crate) cpp_call: String,
+    /// The `extern "C"` function which calls it.
+    pub(crate) helper: Ident,
+    /// Whether C++ exceptions may unwind through that function.
+    pub(crate) exception_policy: ExceptionPolicy,
+}
+
+/// Works out whether this method should be called through a thin
+/// `extern "C"` wrapper, as requested using `thin_call!`. Only const
+/// methods which take no parameters and return a number or `bool` are
+/// eligible, since only those need no help from cxx to cross the boundary.
+pub(super) fn analyze_thin_call(
+    config: &IncludeCppConfig,
+    kind: &FnKind,
+    cpp_name: &str,
+    cxxbridge_name: &Ident,
+    param_details: &[ArgumentAnalysis],
+    ret_type: &ReturnType,
+    ret_conversion: &Option<TypeConversionPolicy>,
+) -> Option<ThinCallDetails> {
+    let (impl_for, method_kind) = match kind {
+        FnKind::Method {
+            impl_for,
+            method_kind,
+            ..
+        } => (impl_for, method_kind),
+        _ => return None,
+    };
+    let qualified_cpp_name = format!("{}::{}", impl_for.to_cpp_name(), cpp_name);
+    if !config.is_thin_call(&qualified_cpp_name) {
+        return None;
+    }
+    let is_const_getter = matches!(method_kind, MethodKind::Normal(ReceiverMutability::Const))
+        && matches!(param_details, [pd] if pd.self_type.is_some());
+    let returns_scalar = matches!(ret_type, ReturnType::Type(_, ty)
+            if matches!(ty.as_ref(), Type::Path(typ)
+                if known_types().is_scalar(&QualifiedName::from_type_path(typ))))
+        && ret_conversion
+            .as_ref()
+            .is_none_or(|conv| !conv.cpp_work_needed() && !conv.rust_work_needed());
+    if !is_const_getter || !returns_scalar {
+        log::warn!(
+            "thin_call!(\"{}\") was given, but only const methods which take no parameters and return a number or bool can be called thinly",
+            qualified_cpp_name
+        );
+        return None;
+    }
+    Some(ThinCallDetails {
+        cpp_call: cpp_name.to_string(),
+        helper: make_ident(format!("{}_autocxx_thin", cxxbridge_name)),
+        exception_policy: config.exception_policy(),
+    })
+}
@@ -13,12 +13,16 @@ pub(crate) mod constructor_deps;
 pub(crate) mod ctypes;
 pub(crate) mod deps;
 mod depth_first;
+pub(crate) mod field_accessors;
 pub(crate) mod fun;
 pub(crate) mod gc;
 mod name_check;
 pub(crate) mod pod; // hey, that rhymes
+pub(crate) mod refcounting;
 pub(crate) mod remove_ignored;
+pub(crate) mod reserved_names;
 pub(crate) mod tdef;
-mod type_converter;
+pub(crate) mod trait_impls;
+pub(crate) mod type_converter;
 
 pub(crate) use name_check::check_names;
@@ -6,18 +6,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::conversion::analysis::type_converter::function_pointer_signature;
 use crate::conversion::apivec::ApiVec;
 use crate::{conversion::ConvertError, known_types::known_types};
 use crate::{
     conversion::{
         analysis::tdef::TypedefPhase,
-        api::{Api, TypedefKind},
+        api::{Api, Layout, TypedefKind},
     },
     types::{Namespace, QualifiedName},
 };
 use autocxx_parser::IncludeCppConfig;
-use std::collections::HashMap;
-use syn::{ItemStruct, Type};
+use std::collections::{HashMap, HashSet};
+use syn::{Expr, Field, Ident, ItemStruct, Lit, Type};
 
 #[derive(Clone)]
 enum PodState {
@@ -31,6 +32,11 @@ enum PodState {
 struct StructDetails {
     state: PodState,
     dependent_structs: Vec<QualifiedName>,
+    opaque_fields: Vec<OpaqueField>,
+    // The name and type of every field, for diagnostics.
+    fields: Vec<(String, QualifiedName)>,
+    // Any reason this struct can't be POD other than its fields.
+    own_problem: Option<String>,
 }
 
 impl StructDetails {
@@ -38,10 +44,26 @@ impl StructDetails {
         StructDetails {
             state,
             dependent_structs: Vec::new(),
+            opaque_fields: Vec::new(),
+            fields: Vec::new(),
+            own_problem: None,
         }
     }
 }
 
+/// A field which would prevent its struct from being POD, but whose size
+/// and alignment we know. If the struct is nevertheless made POD, the
+/// field is represented in Rust as an opaque blob of the same layout.
+#[derive(Clone)]
+pub(crate) struct OpaqueField {
+    pub(crate) ident: Ident,
+    /// The type of the field, or of its elements if it's an array.
+    pub(crate) ty: QualifiedName,
+    pub(crate) array_len: Option<usize>,
+    /// The layout of the whole field.
+    pub(crate) layout: Layout,
+}
+
 /// Type which is able to check whether it's safe to make a type
 /// fully representable by cxx. For instance if it is a struct containing
 /// a struct containing a std::string, the answer is no, because that
@@ -51,6 +73,9 @@ impl StructDetails {
 pub struct ByValueChecker {
     // Mapping from type name to whether it is safe to be POD
     results: HashMap<QualifiedName, StructDetails>,
+    // Layouts of the structs we know about, so that we can represent
+    // fields of those types opaquely.
+    layouts: HashMap<QualifiedName, Layout>,
 }
 
 impl ByValueChecker {
@@ -64,7 +89,10 @@ impl ByValueChecker {
             };
             results.insert(tn.clone(), StructDetails::new(safety));
         }
-        ByValueChecker { results }
+        ByValueChecker {
+            results,
+            layouts: HashMap::new(),
+        }
     }
 
     /// Scan APIs to work out which are by-value safe. Constructs a [ByValueChecker]
@@ -74,6 +102,15 @@ impl ByValueChecker {
         config: &IncludeCppConfig,
     ) -> Result<ByValueChecker, ConvertError> {
         let mut byvalue_checker = ByValueChecker::new();
+        for api in apis.iter() {
+            if let Api::Struct { details, .. } = api {
+                if let Some(layout) = &details.layout {
+                    byvalue_checker
+                        .layouts
+                        .insert(api.name().clone(), layout.clone());
+                }
+            }
+        }
         for blocklisted in config.get_blocklist() {
             let tn = QualifiedName::new_from_cpp_name(blocklisted);
             let safety = PodState::UnsafeToBePod(format!("type {} is on the blocklist", &tn));
@@ -87,6 +124,13 @@ impl ByValueChecker {
                     let name = api.name();
                     let typedef_type = match analysis.kind {
                         TypedefKind::Type(ref type_item) => match type_item.ty.as_ref() {
+                            // Function pointers are safe to hold by value.
+                            Type::Path(typ) if function_pointer_signature(typ).is_some() => {
+                                byvalue_checker
+                                    .results
+                                    .insert(name.clone(), StructDetails::new(PodState::IsPod));
+                                continue;
+                            }
                             Type::Path(typ) => {
                                 let target_tn = QualifiedName::from_type_path(typ);
                                 known_types().consider_substitution(&target_tn)
@@ -110,7 +154,9 @@ impl ByValueChecker {
                 Api::Struct { details, .. } => {
                     byvalue_checker.ingest_struct(&details.item, api.name().get_namespace())
                 }
-                Api::Enum { .. } => {
+                // Types given using extern_cpp_type! are ABI-identical to a
+                // Rust type, which cxx requires to be trivial.
+                Api::Enum { .. } | Api::ExternCppType { .. } => {
                     byvalue_checker
                         .results
                         .insert(api.name().clone(), StructDetails::new(PodState::IsPod));
@@ -118,6 +164,17 @@ impl ByValueChecker {
                 _ => {}
             }
         }
+        let problems: Vec<_> = config
+            .get_pod_recursive_requests()
+            .iter()
+            .flat_map(|ty| byvalue_checker.find_pod_problems(&QualifiedName::new_from_cpp_name(ty)))
+            .collect();
+        if !problems.is_empty() {
+            return Err(ConvertError::UnsafePodType(format!(
+                "Some types reachable from those requested using generate_pod_recursive! can't be POD:\n{}",
+                problems.join("\n")
+            )));
+        }
         let pod_requests = config
             .get_pod_requests()
             .iter()
@@ -133,37 +190,140 @@ impl ByValueChecker {
         // For this struct, work out whether it _could_ be safe as a POD.
         let tyname = QualifiedName::new(ns, def.ident.clone());
         let mut field_safety_problem = PodState::SafeToBePod;
-        let fieldlist = Self::get_field_types(def);
-        for ty_id in &fieldlist {
-            match self.results.get(ty_id) {
-                None => {
-                    field_safety_problem = PodState::UnsafeToBePod(format!(
-                        "Type {} could not be POD because its dependent type {} isn't known",
-                        tyname, ty_id
-                    ));
-                    break;
-                }
-                Some(deets) => {
-                    if let PodState::UnsafeToBePod(reason) = &deets.state {
-                        let new_reason = format!("Type {} could not be POD because its dependent type {} isn't safe to be POD. Because: {}", tyname, ty_id, reason);
-                        field_safety_problem = PodState::UnsafeToBePod(new_reason);
+        let mut fieldlist = Vec::new();
+        let mut opaque_fields = Vec::new();
+        let field_types = Self::get_field_types(def);
+        let fields = field_types
+            .iter()
+            .map(|(field, ty_id, _)| {
+                let field_name = field
+                    .ident
+                    .as_ref()
+                    .map(|id| id.to_string())
+                    .unwrap_or_default();
+                (field_name, ty_id.clone())
+            })
+            .collect();
+        for (field, ty_id, array_len) in field_types {
+            let problem = match self.results.get(&ty_id) {
+                None => Some(format!(
+                    "Type {} could not be POD because its dependent type {} isn't known",
+                    tyname, ty_id
+                )),
+                Some(deets) => match &deets.state {
+                    PodState::UnsafeToBePod(reason) => Some(format!("Type {} could not be POD because its dependent type {} isn't safe to be POD. Because: {}", tyname, ty_id, reason)),
+                    _ => None,
+                },
+            };
+            match problem {
+                None => fieldlist.push(ty_id),
+                Some(reason) => match self.opaque_field(field, ty_id, array_len) {
+                    Some(opaque_field) => opaque_fields.push(opaque_field),
+                    None => {
+                        field_safety_problem = PodState::UnsafeToBePod(reason);
                         break;
                     }
-                }
+                },
             }
         }
-        if Self::has_vtable(def) {
+        let own_problem = if Self::has_vtable(def) {
             let reason = format!(
                 "Type {} could not be POD because it has virtual functions.",
                 tyname
             );
-            field_safety_problem = PodState::UnsafeToBePod(reason);
-        }
+            field_safety_problem = PodState::UnsafeToBePod(reason.clone());
+            Some(reason)
+        } else {
+            None
+        };
         let mut my_details = StructDetails::new(field_safety_problem);
         my_details.dependent_structs = fieldlist;
+        my_details.opaque_fields = opaque_fields;
+        my_details.fields = fields;
+        my_details.own_problem = own_problem;
         self.results.insert(tyname, my_details);
     }
 
+    /// Lists every reason why the given type, or any type reachable through
+    /// its fields, can't be entirely POD, including fields which would have
+    /// to be represented opaquely. Each is prefixed by the path of fields
+    /// leading to the problem.
+    fn find_pod_problems(&self, ty_id: &QualifiedName) -> Vec<String> {
+        let mut problems = Vec::new();
+        self.find_pod_problems_within(
+            ty_id,
+            ty_id.to_cpp_name(),
+            &mut HashSet::new(),
+            &mut problems,
+        );
+        problems
+    }
+
+    fn find_pod_problems_within(
+        &self,
+        ty_id: &QualifiedName,
+        path: String,
+        visited: &mut HashSet<QualifiedName>,
+        problems: &mut Vec<String>,
+    ) {
+        if !visited.insert(ty_id.clone()) {
+            return;
+        }
+        let deets = match self.results.get(ty_id) {
+            None => {
+                problems.push(format!("{}: type {} isn't known", path, ty_id));
+                return;
+            }
+            Some(deets) => deets,
+        };
+        if let PodState::IsAlias(target) = &deets.state {
+            return self.find_pod_problems_within(target, path, visited, problems);
+        }
+        if let Some(own_problem) = &deets.own_problem {
+            problems.push(format!("{}: {}", path, own_problem));
+        }
+        let problems_before = problems.len();
+        for (field_name, field_ty) in &deets.fields {
+            self.find_pod_problems_within(
+                field_ty,
+                format!("{}.{}", path, field_name),
+                visited,
+                problems,
+            );
+        }
+        // If none of the fields were to blame, this type itself is.
+        if let PodState::UnsafeToBePod(reason) = &deets.state {
+            if deets.own_problem.is_none() && problems.len() == problems_before {
+                problems.push(format!("{}: {}", path, reason));
+            }
+        }
+    }
+
+    /// If a field can't be POD, but it's a plain (not base class) field
+    /// whose layout we know, describe how to represent it opaquely.
+    fn opaque_field(
+        &self,
+        field: &Field,
+        ty: QualifiedName,
+        array_len: Option<Option<usize>>,
+    ) -> Option<OpaqueField> {
+        let ident = field
+            .ident
+            .as_ref()
+            .filter(|id| !id.to_string().starts_with("_base"))?;
+        let element_layout = self.layouts.get(&ty)?;
+        let layout = Layout {
+            size: element_layout.size * array_len.unwrap_or(Some(1))?,
+            ..element_layout.clone()
+        };
+        Some(OpaqueField {
+            ident: ident.clone(),
+            ty,
+            array_len: array_len.flatten(),
+            layout,
+        })
+    }
+
     fn ingest_nonpod_type(&mut self, tyname: QualifiedName) {
         let new_reason = format!("Type {} is a typedef to a complex type", tyname);
         self.results.insert(
@@ -220,19 +380,51 @@ impl ByValueChecker {
             self.results.get(ty_id),
             Some(StructDetails {
                 state: PodState::IsPod,
-                dependent_structs: _,
+                ..
             })
         )
     }
 
-    fn get_field_types(def: &ItemStruct) -> Vec<QualifiedName> {
+    /// The fields of a POD type which must be represented opaquely
+    /// because they themselves can't be POD.
+    pub(crate) fn get_opaque_fields(&self, ty_id: &QualifiedName) -> &[OpaqueField] {
+        match self.results.get(ty_id) {
+            Some(StructDetails {
+                state: PodState::IsPod,
+                opaque_fields,
+                ..
+            }) => opaque_fields,
+            _ => &[],
+        }
+    }
+
+    /// Returns each field whose type might affect POD-ness, along with
+    /// that type. If the field is an array, also returns its total number
+    /// of elements, if known.
+    fn get_field_types(def: &ItemStruct) -> Vec<(&Field, QualifiedName, Option<Option<usize>>)> {
         let mut results = Vec::new();
         for f in &def.fields {
-            let fty = &f.ty;
-            if let Type::Path(p) = fty {
-                results.push(QualifiedName::from_type_path(p));
+            let mut fty = &f.ty;
+            let mut array_len = None;
+            // An array is safe to be POD if its elements are.
+            while let Type::Array(arr) = fty {
+                let len = match &arr.len {
+                    Expr::Lit(lit) => match &lit.lit {
+                        Lit::Int(len) => len.base10_parse::<usize>().ok(),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                array_len = Some(array_len.unwrap_or(Some(1)).zip(len).map(|(a, b)| a * b));
+                fty = &arr.elem;
+            }
+            match fty {
+                // Function pointers are always safe to be POD.
+                Type::Path(p) if function_pointer_signature(p).is_some() => {}
+                Type::Path(p) => results.push((f, QualifiedName::from_type_path(p), array_len)),
+                _ => {}
             }
-            // TODO handle anything else which bindgen might spit out, e.g. arrays?
+            // TODO handle anything else which bindgen might spit out?
         }
         results
     }
@@ -250,6 +442,7 @@ impl ByValueChecker {
 #[cfg(test)]
 mod tests {
     use super::ByValueChecker;
+    use crate::conversion::api::Layout;
     use crate::types::{Namespace, QualifiedName};
     use syn::{parse_quote, Ident, ItemStruct};
 
@@ -316,6 +509,81 @@ mod tests {
         assert!(bvc.is_pod(&t_id));
     }
 
+    #[test]
+    fn test_with_opaque_field() {
+        let mut bvc = ByValueChecker::new();
+        let t: ItemStruct = parse_quote! {
+            struct Inner {
+                a: CxxString,
+            }
+        };
+        bvc.ingest_struct(&t, &Namespace::new());
+        bvc.layouts.insert(
+            ty_from_ident(&t.ident),
+            Layout {
+                size: 8,
+                align: 4,
+                packed: false,
+            },
+        );
+        let t: ItemStruct = parse_quote! {
+            struct Bar {
+                a: Inner,
+                b: [[Inner; 2usize]; 3usize],
+                c: i64,
+            }
+        };
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&t, &Namespace::new());
+        bvc.satisfy_requests(vec![t_id.clone()]).unwrap();
+        assert!(bvc.is_pod(&t_id));
+        let opaque_fields: Vec<_> = bvc
+            .get_opaque_fields(&t_id)
+            .iter()
+            .map(|f| (f.ident.to_string(), f.array_len, f.layout.size))
+            .collect();
+        assert_eq!(
+            opaque_fields,
+            vec![("a".to_string(), None, 8), ("b".to_string(), Some(6), 48)]
+        );
+    }
+
+    #[test]
+    fn test_find_pod_problems() {
+        let mut bvc = ByValueChecker::new();
+        let t: ItemStruct = parse_quote! {
+            struct Inner {
+                a: std::string,
+                b: u32,
+            }
+        };
+        bvc.ingest_struct(&t, &Namespace::new());
+        let t: ItemStruct = parse_quote! {
+            struct Virtual {
+                vtable_: *const u8,
+            }
+        };
+        bvc.ingest_struct(&t, &Namespace::new());
+        let t: ItemStruct = parse_quote! {
+            struct Bar {
+                inner: Inner,
+                virt: Virtual,
+                c: i64,
+                d: Unknown,
+            }
+        };
+        let t_id = ty_from_ident(&t.ident);
+        bvc.ingest_struct(&t, &Namespace::new());
+        assert_eq!(
+            bvc.find_pod_problems(&t_id),
+            vec![
+                "Bar.inner.a: type std::string is not safe for POD",
+                "Bar.virt: Type Virtual could not be POD because it has virtual functions.",
+                "Bar.d: type Unknown isn't known",
+            ]
+        );
+    }
+
     #[test]
     fn test_with_cxxstring() {
         let mut bvc = ByValueChecker::new();
@@ -10,9 +10,10 @@ mod byvalue_checker;
 
 use std::collections::{HashMap, HashSet};
 
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, LayoutOverride};
 use byvalue_checker::ByValueChecker;
-use syn::{ItemEnum, ItemStruct, Type, Visibility};
+pub(crate) use byvalue_checker::OpaqueField;
+use syn::{parse_quote, Ident, ItemEnum, ItemStruct, Type, Visibility};
 
 use crate::{
     conversion::{
@@ -24,7 +25,7 @@ use crate::{
         parse::BindgenSemanticAttributes,
         ConvertError,
     },
-    types::{Namespace, QualifiedName},
+    types::{make_ident, Namespace, QualifiedName},
 };
 
 use super::tdef::{TypedefAnalysis, TypedefPhase};
@@ -44,7 +45,24 @@ pub(crate) struct PodAnalysis {
     pub(crate) castable_bases: HashSet<QualifiedName>,
     pub(crate) field_deps: HashSet<QualifiedName>,
     pub(crate) field_info: Vec<FieldInfo>,
+    /// Fields of a POD type which are represented by opaque blobs,
+    /// because their types can't be POD.
+    pub(crate) opaque_fields: Vec<OpaqueField>,
     pub(crate) is_generic: bool,
+    /// The packing or alignment asserted by the user using `layout!`.
+    pub(crate) layout_override: Option<LayoutOverride>,
+}
+
+impl OpaqueField {
+    /// The name of the Rust type representing this field within
+    /// the given struct.
+    pub(crate) fn blob_ident(&self, owner: &QualifiedName) -> Ident {
+        make_ident(format!(
+            "{}_{}_AutocxxOpaque",
+            owner.get_final_item(),
+            self.ident
+        ))
+    }
 }
 
 pub(crate) struct PodPhase;
@@ -143,14 +161,26 @@ fn analyze_struct(
     let bases = get_bases(&details.item);
     let mut field_deps = HashSet::new();
     let mut field_info = Vec::new();
+    let opaque_fields = byvalue_checker.get_opaque_fields(&name.name).to_vec();
     let field_conversion_errors = get_struct_field_types(
         type_converter,
         name.name.get_namespace(),
         &details.item,
+        &opaque_fields,
         &mut field_deps,
         &mut field_info,
         extra_apis,
     );
+    for field in details.item.fields.iter_mut() {
+        if let Some(opaque_field) = opaque_fields
+            .iter()
+            .find(|opaque_field| field.ident.as_ref() == Some(&opaque_field.ident))
+        {
+            let blob = opaque_field.blob_ident(&name.name);
+            field.ty = parse_quote! { #blob };
+            field.vis = Visibility::Inherited;
+        }
+    }
     let type_kind = if byvalue_checker.is_pod(&name.name) {
         // It's POD so any errors encountered parsing its fields are important.
         // Let's not allow anything to be POD if it's got rvalue reference fields.
@@ -175,6 +205,7 @@ fn analyze_struct(
         .cloned()
         .collect();
     let is_generic = !details.item.generics.params.is_empty();
+    let layout_override = config.get_layout_override(&name.name.to_cpp_name());
     Ok(Box::new(std::iter::once(Api::Struct {
         name,
         details,
@@ -184,7 +215,9 @@ fn analyze_struct(
             castable_bases,
             field_deps,
             field_info,
+            opaque_fields,
             is_generic,
+            layout_override,
         },
     })))
 }
@@ -193,15 +226,33 @@ fn get_struct_field_types(
     type_converter: &mut TypeConverter,
     ns: &Namespace,
     s: &ItemStruct,
+    opaque_fields: &[OpaqueField],
     field_deps: &mut HashSet<QualifiedName>,
     field_info: &mut Vec<FieldInfo>,
     extra_apis: &mut ApiVec<NullPhase>,
 ) -> Vec<ConvertError> {
     let mut convert_errors = Vec::new();
     for f in &s.fields {
-        let annotated =
-            type_converter.convert_type(f.ty.clone(), ns, &TypeConversionContext::CxxInnerType);
+        let annotated = type_converter.convert_type(
+            f.ty.clone(),
+            ns,
+            &TypeConversionContext::WithinStructFieldOrTypedef,
+        );
+        let is_opaque = opaque_fields
+            .iter()
+            .any(|opaque_field| f.ident.as_ref() == Some(&opaque_field.ident));
         match annotated {
+            // Fields represented opaquely needn't be representable in
+            // Rust, nor generated, but we'd still like to know about them
+            // for the purposes of working out implicit constructors.
+            Ok(r) if is_opaque => field_info.push(FieldInfo {
+                ty: r.ty,
+                type_kind: r.kind,
+            }),
+            Err(_) if is_opaque => field_info.push(FieldInfo {
+                ty: f.ty.clone(),
+                type_kind: type_converter::TypeKind::Regular,
+            }),
             Ok(mut r) => {
                 extra_apis.append(&mut r.extra_apis);
                 // Skip base classes represented as fields. Anything which wants to include bases can chain
@@ -0,0 +1,148 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Code to create functions to add and release references to objects
+//! of reference counted classes.
+
+use std::collections::{HashMap, HashSet};
+
+use autocxx_parser::{IncludeCppConfig, RefCounting};
+use syn::{parse_quote, punctuated::Punctuated, token::Comma, FnArg, ReturnType};
+
+use crate::{
+    conversion::{
+        api::{Api, ApiName, CppVisibility, FuncToConvert, Provenance, References, TraitSynthesis},
+        apivec::ApiVec,
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::{
+    fun::function_wrapper::{CppFunctionBody, CppFunctionKind},
+    pod::PodPhase,
+};
+
+/// For each type which is, or derives from, a base class given using
+/// `refcounted!`, creates functions which will implement
+/// `autocxx::RefCounted` by calling the base class's methods.
+pub(crate) fn add_ref_counting(
+    apis: ApiVec<PodPhase>,
+    config: &IncludeCppConfig,
+) -> ApiVec<PodPhase> {
+    if config.get_ref_countings().is_empty() {
+        return apis;
+    }
+    let bases_by_type: HashMap<QualifiedName, HashSet<QualifiedName>> = apis
+        .iter()
+        .filter_map(|api| match api {
+            Api::Struct { name, analysis, .. } => Some((name.name.clone(), analysis.bases.clone())),
+            _ => None,
+        })
+        .collect();
+    let mut results = ApiVec::new();
+    for api in apis.into_iter() {
+        if let Api::Struct { name, .. } = &api {
+            if let Some(ref_counting) = find_ref_counting(&name.name, &bases_by_type, config) {
+                results.extend(create_add_ref_and_release(&name.name, ref_counting));
+            }
+        }
+        results.push(api);
+    }
+    results
+}
+
+/// Finds the `refcounted!` directive for the type itself, or for any of
+/// its bases, direct or indirect.
+fn find_ref_counting<'a>(
+    ty: &QualifiedName,
+    bases_by_type: &HashMap<QualifiedName, HashSet<QualifiedName>>,
+    config: &'a IncludeCppConfig,
+) -> Option<&'a RefCounting> {
+    let mut to_visit = vec![ty.clone()];
+    let mut visited = HashSet::new();
+    while let Some(ty) = to_visit.pop() {
+        let ref_counting = config
+            .get_ref_countings()
+            .iter()
+            .find(|ref_counting| QualifiedName::new_from_cpp_name(&ref_counting.base) == ty);
+        if ref_counting.is_some() {
+            return ref_counting;
+        }
+        if let Some(bases) = bases_by_type.get(&ty) {
+            to_visit.extend(
+                bases
+                    .iter()
+                    .filter(|base| !visited.contains(*base))
+                    .cloned(),
+            );
+        }
+        visited.insert(ty);
+    }
+    None
+}
+
+fn create_add_ref_and_release(
+    ty_name: &QualifiedName,
+    ref_counting: &RefCounting,
+) -> impl Iterator<Item = Api<PodPhase>> {
+    let typ = ty_name.to_type_path();
+    let inputs: Punctuated<FnArg, Comma> = parse_quote! {
+        arg0: *mut #typ
+    };
+    [
+        (
+            TraitSynthesis::AddRef(ty_name.clone()),
+            get_name(ty_name, "add_ref"),
+            ref_counting.addref.clone(),
+        ),
+        (
+            TraitSynthesis::Release(ty_name.clone()),
+            get_name(ty_name, "release"),
+            ref_counting.release.clone(),
+        ),
+    ]
+    .into_iter()
+    .map(move |(synthesis, name, method)| {
+        let ident = name.get_final_ident();
+        let api_name = ApiName::new_from_qualified_name(name);
+        Api::Function {
+            name: api_name,
+            name_for_gc: None,
+            fun: Box::new(FuncToConvert {
+                ident,
+                doc_attr: None,
+                inputs: inputs.clone(),
+                output: ReturnType::Default,
+                vis: parse_quote! { pub },
+                virtualness: crate::conversion::api::Virtualness::None,
+                cpp_vis: CppVisibility::Public,
+                special_member: None,
+                unused_template_param: false,
+                references: References::default(),
+                original_name: None,
+                self_ty: None,
+                synthesized_this_type: None,
+                synthetic_cpp: Some((CppFunctionBody::RefCount(method), CppFunctionKind::Function)),
+                add_to_trait: Some(synthesis),
+                is_deleted: false,
+                internal_linkage: false,
+                source_location: None,
+                deprecation: None,
+                reexported_from: None,
+                provenance: Provenance::SynthesizedOther,
+            }),
+            analysis: (),
+        }
+    })
+}
+
+fn get_name(ty_name: &QualifiedName, label: &str) -> QualifiedName {
+    let name = format!("{}_{}", ty_name.get_final_item(), label);
+    let name_id = make_ident(name);
+    QualifiedName::new(ty_name.get_namespace(), name_id)
+}
@@ -0,0 +1,78 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::IncludeCppConfig;
+
+/// The Rust names of the helpers which we generate alongside the C++
+/// APIs, and which a C++ item might therefore also want. Each helper is
+/// identified (e.g. in `helper_name!`) by its default name, qualified by
+/// the type for per-type helpers, e.g. `ns::Widget::make_unique`.
+pub(crate) const MAKE_STRING: &str = "make_string";
+pub(crate) const CONSTRUCTOR: &str = "new";
+pub(crate) const MAKE_UNIQUE: &str = "make_unique";
+
+/// Picks the Rust name of a generated helper. That's its default name
+/// unless the user chose another using `helper_name!`. If `is_taken` says
+/// that a C++ item already has the name, the C++ item keeps it, and the
+/// helper is instead given a suffixed name, with a warning.
+pub(crate) fn choose_helper_name(
+    config: &IncludeCppConfig,
+    qualified_helper: &str,
+    default_name: &str,
+    is_taken: impl Fn(&str) -> bool,
+) -> String {
+    let preferred_name = config
+        .get_helper_name(qualified_helper)
+        .unwrap_or(default_name);
+    if !is_taken(preferred_name) {
+        return preferred_name.to_string();
+    }
+    let name = (0..)
+        .map(|count| match count {
+            0 => format!("{}_autocxx", preferred_name),
+            _ => format!("{}_autocxx{}", preferred_name, count),
+        })
+        .find(|name| !is_taken(name))
+        .unwrap();
+    log::warn!(
+        "Generated helper {} would clash with a C++ item called {}, so it has been called {}. Use helper_name!(\"{}\", \"...\") to choose another name.",
+        qualified_helper,
+        preferred_name,
+        name,
+        qualified_helper
+    );
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::choose_helper_name;
+    use autocxx_parser::IncludeCppConfig;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_choose_helper_name() {
+        let config: IncludeCppConfig = parse_quote! {
+            helper_name!("Widget::make_unique", "create")
+        };
+        let taken = ["make_string", "make_string_autocxx", "build"];
+        let is_taken = |name: &str| taken.contains(&name);
+        assert_eq!(
+            choose_helper_name(&config, "Gadget::make_unique", "make_unique", is_taken),
+            "make_unique"
+        );
+        assert_eq!(
+            choose_helper_name(&config, "Widget::make_unique", "make_unique", is_taken),
+            "create"
+        );
+        assert_eq!(
+            choose_helper_name(&config, "make_string", "make_string", is_taken),
+            "make_string_autocxx1"
+        );
+    }
+}
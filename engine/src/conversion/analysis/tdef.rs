@@ -13,7 +13,9 @@ use syn::ItemType;
 
 use crate::{
     conversion::{
-        analysis::type_converter::{add_analysis, Annotated, TypeConversionContext, TypeConverter},
+        analysis::type_converter::{
+            add_analysis, is_alias_template, Annotated, TypeConversionContext, TypeConverter,
+        },
         api::{AnalysisPhase, Api, ApiName, NullPhase, TypedefKind},
         apivec::ApiVec,
         convert_error::{ConvertErrorWithContext, ErrorContext},
@@ -88,12 +90,34 @@ fn get_replacement_typedef(
     let mut converted_type = ity.clone();
     let metadata = BindgenSemanticAttributes::new_retaining_others(&mut converted_type.attrs);
     metadata.check_for_fatal_attrs(&ity.ident)?;
+    if is_alias_template(&ity) {
+        // Its target mentions its type parameters, so can't be converted
+        // until we know what they are. That's done by the type converter
+        // wherever an instantiation of it is used.
+        return Ok(Api::Typedef {
+            name,
+            item: TypedefKind::Type(ity),
+            old_tyname,
+            analysis: TypedefAnalysis {
+                kind: TypedefKind::Type(converted_type),
+                deps: HashSet::new(),
+            },
+        });
+    }
     let type_conversion_results = type_converter.convert_type(
         (*ity.ty).clone(),
         name.name.get_namespace(),
-        &TypeConversionContext::CxxInnerType,
+        &TypeConversionContext::WithinStructFieldOrTypedef,
     );
     match type_conversion_results {
+        Err(err) if can_treat_as_opaque(&name, &err) => {
+            log::info!(
+                "Treating typedef {} as an opaque type because its target couldn't be converted: {}",
+                name.name.to_cpp_name(),
+                err
+            );
+            Ok(Api::ForwardDeclaration { name })
+        }
         Err(err) => Err(ConvertErrorWithContext(
             err,
             Some(ErrorContext::Item(name.name.get_final_ident())),
@@ -102,7 +126,7 @@ fn get_replacement_typedef(
             ty: syn::Type::Path(ref typ),
             ..
         }) if QualifiedName::from_type_path(typ) == name.name => Err(ConvertErrorWithContext(
-            ConvertError::InfinitelyRecursiveTypedef(name.name.clone()),
+            ConvertError::InfinitelyRecursiveTypedef(vec![name.name.clone(), name.name.clone()]),
             Some(ErrorContext::Item(name.name.get_final_ident())),
         )),
         Ok(mut final_type) => {
@@ -120,3 +144,20 @@ fn get_replacement_typedef(
         }
     }
 }
+
+/// Whether, having failed to convert the target of a typedef for this
+/// reason, we can instead represent the typedef as an opaque type. That
+/// way, APIs which use it by reference or pointer can still be generated,
+/// rather than ignoring everything which refers to it.
+fn can_treat_as_opaque(name: &ApiName, err: &ConvertError) -> bool {
+    name.name.validate_ok_for_cxx().is_ok()
+        && matches!(
+            err,
+            ConvertError::UnknownDependentType(_)
+                | ConvertError::TemplatedTypeContainingNonPathArg(_)
+                | ConvertError::UnsupportedType(_)
+                | ConvertError::UnsupportedBuiltInType(_)
+                | ConvertError::UnusedTemplateParam
+                | ConvertError::TooManyUnderscores
+        )
+}
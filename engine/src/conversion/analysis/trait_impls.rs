@@ -0,0 +1,251 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+
+use autocxx_parser::{IncludeCppConfig, TraitImpl};
+use quote::ToTokens;
+use syn::{Ident, ReturnType, Type};
+
+use crate::{
+    conversion::{api::Api, apivec::ApiVec, ConvertError},
+    types::{make_ident, QualifiedName},
+};
+
+use super::fun::{FnAnalysis, FnKind, FnPhase, MethodKind, ReceiverMutability};
+
+/// A standard Rust trait which can be implemented for a generated type
+/// using `impl_trait!`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SupportedTrait {
+    Write,
+    Read,
+    Display,
+    /// Implemented for `&T`, iterating over the elements of a container
+    /// which offers indexed access.
+    IntoIterator,
+}
+
+/// What we require of a C++ method in order to implement a given trait
+/// method using it.
+#[derive(Clone, Copy)]
+enum ExpectedSignature {
+    /// Takes a pointer to some bytes and their length, and returns how
+    /// many were used, e.g. `size_t write(const uint8_t*, size_t)`.
+    Bytes { mutable: bool },
+    /// Takes no parameters and returns nothing.
+    Nothing,
+    /// A const method which takes no parameters and returns a string.
+    StringGetter,
+    /// A const method which takes no parameters and returns a `size_t`.
+    Len,
+    /// A const method which takes a `size_t` index and returns a const
+    /// reference.
+    Element,
+}
+
+impl SupportedTrait {
+    fn from_path(path: &str) -> Option<Self> {
+        match path.trim().trim_start_matches("::") {
+            "std::io::Write" => Some(Self::Write),
+            "std::io::Read" => Some(Self::Read),
+            "std::fmt::Display" => Some(Self::Display),
+            "IntoIterator" | "std::iter::IntoIterator" => Some(Self::IntoIterator),
+            _ => None,
+        }
+    }
+
+    /// The required methods of the trait, or for `IntoIterator`, the
+    /// methods needed to iterate.
+    fn methods(&self) -> &'static [(&'static str, ExpectedSignature)] {
+        match self {
+            Self::Write => &[
+                ("write", ExpectedSignature::Bytes { mutable: false }),
+                ("flush", ExpectedSignature::Nothing),
+            ],
+            Self::Read => &[("read", ExpectedSignature::Bytes { mutable: true })],
+            Self::Display => &[("fmt", ExpectedSignature::StringGetter)],
+            Self::IntoIterator => &[
+                ("len", ExpectedSignature::Len),
+                ("get", ExpectedSignature::Element),
+            ],
+        }
+    }
+}
+
+/// A trait to implement for a type, and the methods of the type to
+/// which each of the trait's methods delegates.
+#[derive(Clone)]
+pub(crate) struct TraitImplDetails {
+    pub(crate) trt: SupportedTrait,
+    /// The Rust name of the method to call for each method of the trait.
+    pub(crate) methods: HashMap<&'static str, Ident>,
+    /// For `IntoIterator`, the type of the elements.
+    pub(crate) element_type: Option<Type>,
+}
+
+/// Checks each `impl_trait!` directive against the methods we're going to
+/// generate, and records the traits to implement on each type. Unlike most
+/// problems, a directive which can't be obeyed is fatal, since the user
+/// explicitly asked for it.
+pub(crate) fn analyze_trait_impls(
+    apis: ApiVec<FnPhase>,
+    config: &IncludeCppConfig,
+) -> Result<ApiVec<FnPhase>, ConvertError> {
+    if config.get_trait_impls().is_empty() {
+        return Ok(apis);
+    }
+    let mut trait_impls_by_type: HashMap<QualifiedName, Vec<TraitImplDetails>> = HashMap::new();
+    for trait_impl in config.get_trait_impls() {
+        let ty = QualifiedName::new_from_cpp_name(&trait_impl.ty);
+        let details = analyze_trait_impl(&apis, &ty, trait_impl).map_err(|problem| {
+            ConvertError::InvalidTraitImpl(
+                trait_impl.ty.clone(),
+                trait_impl.trait_path.clone(),
+                problem,
+            )
+        })?;
+        trait_impls_by_type.entry(ty).or_default().push(details);
+    }
+    let mut results = ApiVec::new();
+    for mut api in apis.into_iter() {
+        if let Api::Struct { name, analysis, .. } = &mut api {
+            if let Some(details) = trait_impls_by_type.remove(&name.name) {
+                analysis.trait_impls = details;
+            }
+        }
+        results.push(api);
+    }
+    if let Some(ty) = trait_impls_by_type.keys().next() {
+        let trait_impl = config
+            .get_trait_impls()
+            .iter()
+            .find(|trait_impl| QualifiedName::new_from_cpp_name(&trait_impl.ty) == *ty)
+            .unwrap();
+        return Err(ConvertError::InvalidTraitImpl(
+            trait_impl.ty.clone(),
+            trait_impl.trait_path.clone(),
+            "the type isn't generated as a struct or class".into(),
+        ));
+    }
+    Ok(results)
+}
+
+fn analyze_trait_impl(
+    apis: &ApiVec<FnPhase>,
+    ty: &QualifiedName,
+    trait_impl: &TraitImpl,
+) -> Result<TraitImplDetails, String> {
+    let trt = SupportedTrait::from_path(&trait_impl.trait_path).ok_or_else(|| {
+        "only std::io::Write, std::io::Read, std::fmt::Display and IntoIterator are supported"
+            .to_string()
+    })?;
+    if let Some((unknown, _)) = trait_impl
+        .methods
+        .iter()
+        .find(|(method, _)| !trt.methods().iter().any(|(name, _)| method == name))
+    {
+        return Err(format!("{} isn't needed to implement this trait", unknown));
+    }
+    let mut methods = HashMap::new();
+    let mut element_type = None;
+    for (method, expected) in trt.methods() {
+        let cpp_method = trait_impl
+            .methods
+            .iter()
+            .find(|(name, _)| name == method)
+            .map(|(_, cpp_method)| cpp_method)
+            .ok_or_else(|| format!("no method was given for {}", method))?;
+        let analysis = apis
+            .iter()
+            .filter_map(|api| match api {
+                Api::Function { name, analysis, .. } => Some((name, analysis)),
+                _ => None,
+            })
+            .filter(|(name, analysis)| {
+                matches!(&analysis.kind, FnKind::Method { impl_for, .. }
+                    if impl_for == ty
+                        && format!("{}::{}", impl_for.to_cpp_name(), name.cpp_name()) == *cpp_method)
+            })
+            .map(|(_, analysis)| analysis)
+            .find(|analysis| signature_matches(*expected, analysis))
+            .ok_or_else(|| {
+                format!(
+                    "{} isn't a generated method of {} whose signature is suitable for {}",
+                    cpp_method, trait_impl.ty, method
+                )
+            })?;
+        if let ExpectedSignature::Element = expected {
+            element_type = referent(&analysis.ret_type).cloned();
+        }
+        methods.insert(*method, make_ident(&analysis.rust_name));
+    }
+    Ok(TraitImplDetails {
+        trt,
+        methods,
+        element_type,
+    })
+}
+
+/// The type to which a method returns a const reference, if it does.
+fn referent(ret_type: &ReturnType) -> Option<&Type> {
+    match ret_type {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Reference(reference) if reference.mutability.is_none() => {
+                Some(reference.elem.as_ref())
+            }
+            _ => None,
+        },
+        ReturnType::Default => None,
+    }
+}
+
+fn signature_matches(expected: ExpectedSignature, analysis: &FnAnalysis) -> bool {
+    let receiver = match &analysis.kind {
+        FnKind::Method {
+            method_kind: MethodKind::Normal(receiver),
+            ..
+        } => receiver,
+        _ => return false,
+    };
+    let params: Vec<_> = analysis
+        .param_details
+        .iter()
+        .filter(|pd| pd.self_type.is_none())
+        .map(|pd| pd.conversion.unwrapped_type.to_token_stream().to_string())
+        .collect();
+    let ret = match &analysis.ret_type {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some(ty.to_token_stream().to_string()),
+    };
+    match expected {
+        ExpectedSignature::Bytes { mutable } => {
+            let ptr = if mutable { "* mut u8" } else { "* const u8" };
+            params == [ptr, "usize"] && ret.as_deref() == Some("usize")
+        }
+        ExpectedSignature::Nothing => params.is_empty() && ret.is_none(),
+        ExpectedSignature::StringGetter => {
+            // Any of the forms in which autocxx may return a string.
+            params.is_empty()
+                && matches!(receiver, ReceiverMutability::Const)
+                && ret.is_some_and(|ret| {
+                    ret.contains("String") || ret.split_whitespace().any(|token| token == "str")
+                })
+        }
+        ExpectedSignature::Len => {
+            params.is_empty()
+                && matches!(receiver, ReceiverMutability::Const)
+                && ret.as_deref() == Some("usize")
+        }
+        ExpectedSignature::Element => {
+            params == ["usize"]
+                && matches!(receiver, ReceiverMutability::Const)
+                && referent(&analysis.ret_type).is_some()
+        }
+    }
+}
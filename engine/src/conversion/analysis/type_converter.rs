@@ -16,18 +16,58 @@ use crate::{
     known_types::{known_types, CxxGenericType},
     types::{make_ident, Namespace, QualifiedName},
 };
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, RustPath};
 use itertools::Itertools;
 use proc_macro2::Ident;
 use quote::ToTokens;
 use std::collections::{HashMap, HashSet};
 use syn::{
-    parse_quote, punctuated::Punctuated, GenericArgument, PathArguments, PathSegment, Type,
-    TypePath, TypePtr,
+    parse_quote, punctuated::Punctuated, GenericArgument, Item, ItemType, PathArguments,
+    PathSegment, ReturnType, Type, TypeBareFn, TypePath, TypePtr,
 };
 
 use super::tdef::TypedefAnalysis;
 
+/// A custom conversion from C++ types to Rust types, for crates which
+/// know how to represent some third-party C++ types better than autocxx
+/// does. Register one using `Builder::type_mapper`.
+///
+/// Each C++ type for which this returns a [`TypeMapping`] is treated as
+/// though it had been named in an `extern_cpp_type!` directive: wherever
+/// it's used, the generated bindings use the given Rust type instead, and
+/// autocxx generates nothing else for it. Mappers are consulted in the
+/// order they were registered, and only for types which aren't already
+/// named in `extern_cpp_type!`.
+pub trait TypeMapper {
+    /// How to represent the given C++ type, e.g. `"fixed::Q16"`, or `None`
+    /// if this mapper doesn't know about it.
+    fn map_type(&self, cpp_name: &str) -> Option<TypeMapping>;
+}
+
+/// The Rust type to use for some C++ type, as decided by a [`TypeMapper`].
+pub struct TypeMapping {
+    /// The Rust type, which must implement `cxx::ExternType` with the C++
+    /// name as its `Id` and `cxx::kind::Trivial` as its `Kind`.
+    pub rust_type: RustPath,
+    /// C++ to include in the generated header, e.g. helper functions or
+    /// static assertions about the C++ type.
+    pub cpp_shims: Option<String>,
+    /// Rust items to include in the mod generated by `include_cpp!`, e.g.
+    /// conversions to and from the Rust type.
+    pub rs_shims: Vec<Item>,
+}
+
+impl TypeMapping {
+    /// Represents the C++ type by this Rust type, with no shims.
+    pub fn new(rust_type: RustPath) -> Self {
+        Self {
+            rust_type,
+            cpp_shims: None,
+            rs_shims: Vec::new(),
+        }
+    }
+}
+
 /// Certain kinds of type may require special handling by callers.
 #[derive(Debug)]
 pub(crate) enum TypeKind {
@@ -81,7 +121,13 @@ impl<T> Annotated<T> {
 /// from [TypeConverter] _might_ be used in the [cxx::bridge].
 pub(crate) enum TypeConversionContext {
     CxxInnerType,
-    CxxOuterType { convert_ptrs_to_references: bool },
+    CxxOuterType {
+        convert_ptrs_to_references: bool,
+    },
+    /// The type of a struct field or the target of a typedef. These are
+    /// only ever used in the bindgen-generated Rust code, so we can permit
+    /// some types (such as function pointers and arrays) which cxx can't handle.
+    WithinStructFieldOrTypedef,
 }
 
 impl TypeConversionContext {
@@ -95,7 +141,16 @@ impl TypeConversionContext {
         )
     }
     fn allow_instantiation_of_forward_declaration(&self) -> bool {
-        matches!(self, TypeConversionContext::CxxInnerType)
+        matches!(
+            self,
+            TypeConversionContext::CxxInnerType | TypeConversionContext::WithinStructFieldOrTypedef
+        )
+    }
+    fn allow_function_pointers(&self) -> bool {
+        matches!(self, TypeConversionContext::WithinStructFieldOrTypedef)
+    }
+    fn allow_arrays(&self) -> bool {
+        matches!(self, TypeConversionContext::WithinStructFieldOrTypedef)
     }
 }
 
@@ -110,6 +165,8 @@ impl TypeConversionContext {
 pub(crate) struct TypeConverter<'a> {
     types_found: HashSet<QualifiedName>,
     typedefs: HashMap<QualifiedName, Type>,
+    /// Alias templates, e.g. `template<class T> using Ptr = std::unique_ptr<T>`.
+    alias_templates: HashMap<QualifiedName, ItemType>,
     concrete_templates: HashMap<String, QualifiedName>,
     forward_declarations: HashSet<QualifiedName>,
     config: &'a IncludeCppConfig,
@@ -123,8 +180,9 @@ impl<'a> TypeConverter<'a> {
         Self {
             types_found: find_types(apis),
             typedefs: Self::find_typedefs(apis),
+            alias_templates: Self::find_alias_templates(apis),
             concrete_templates: Self::find_concrete_templates(apis),
-            forward_declarations: Self::find_incomplete_types(apis),
+            forward_declarations: Self::find_incomplete_types(config, apis),
             config,
         }
     }
@@ -145,6 +203,9 @@ impl<'a> TypeConverter<'a> {
         ctx: &TypeConversionContext,
     ) -> Result<Annotated<Type>, ConvertError> {
         let result = match ty {
+            Type::Path(p) if function_pointer_signature(&p).is_some() => {
+                self.convert_function_pointer(p, ns, ctx)?
+            }
             Type::Path(p) => {
                 let newp = self.convert_type_path(p, ns, ctx)?;
                 if let Type::Path(newpp) = &newp.ty {
@@ -200,6 +261,18 @@ impl<'a> TypeConverter<'a> {
                     TypeKind::Pointer,
                 )
             }
+            Type::Array(mut arr) if ctx.allow_arrays() => {
+                // An array has the same characteristics (e.g. whether it
+                // can be POD) as its elements, so report the element's kind.
+                let innerty = self.convert_boxed_type(arr.elem, ns, ctx)?;
+                arr.elem = innerty.ty;
+                Annotated::new(
+                    Type::Array(arr),
+                    innerty.types_encountered,
+                    innerty.extra_apis,
+                    innerty.kind,
+                )
+            }
             _ => return Err(ConvertError::UnknownType(ty.to_token_stream().to_string())),
         };
         Ok(result)
@@ -230,7 +303,7 @@ impl<'a> TypeConverter<'a> {
                             let i = make_ident(s);
                             parse_quote! { #i }
                         })
-                        .chain(typ.path.segments.into_iter())
+                        .chain(typ.path.segments)
                         .collect();
                 }
             }
@@ -238,9 +311,16 @@ impl<'a> TypeConverter<'a> {
 
         let original_tn = QualifiedName::from_type_path(&typ);
         original_tn.validate_ok_for_cxx()?;
-        if self.config.is_on_blocklist(&original_tn.to_cpp_name()) {
+        if self.config.is_on_blocklist(&original_tn.to_cpp_name())
+            || self
+                .config
+                .is_in_blocked_namespace(&original_tn.to_cpp_name())
+        {
             return Err(ConvertError::Blocked(original_tn));
         }
+        if let Some(instantiated) = self.instantiate_alias_template(&typ, &original_tn) {
+            return self.convert_type_path(instantiated, ns, ctx);
+        }
         let mut deps = HashSet::new();
 
         // Now convert this type itself.
@@ -248,6 +328,19 @@ impl<'a> TypeConverter<'a> {
         // First let's see if this is a typedef.
         let (typ, tn) = match self.resolve_typedef(&original_tn)? {
             None => (typ, original_tn),
+            Some(Type::Path(resolved_tp)) if function_pointer_signature(resolved_tp).is_some() => {
+                // Keep referring to the typedef, which has its own
+                // dependencies on the types in the function signature.
+                if !ctx.allow_function_pointers() {
+                    return Err(ConvertError::FunctionPointer);
+                }
+                return Ok(Annotated::new(
+                    Type::Path(typ),
+                    deps,
+                    ApiVec::new(),
+                    TypeKind::Pointer,
+                ));
+            }
             Some(Type::Path(resolved_tp)) => {
                 let resolved_tn = QualifiedName::from_type_path(resolved_tp);
                 deps.insert(resolved_tn.clone());
@@ -261,6 +354,11 @@ impl<'a> TypeConverter<'a> {
                     TypeKind::Pointer,
                 ))
             }
+            Some(Type::Array(arr)) if !ctx.allow_arrays() => {
+                return Err(ConvertError::UnsupportedType(
+                    arr.to_token_stream().to_string(),
+                ))
+            }
             Some(other) => {
                 return Ok(Annotated::new(
                     other.clone(),
@@ -322,6 +420,44 @@ impl<'a> TypeConverter<'a> {
         Ok(Annotated::new(Type::Path(typ), deps, extra_apis, kind))
     }
 
+    /// Function pointers can't be represented by cxx, but they're fine in
+    /// the bindgen-generated Rust code, e.g. as struct fields. We keep the
+    /// type as-is, but record the types in its signature as dependencies.
+    fn convert_function_pointer(
+        &mut self,
+        typ: TypePath,
+        ns: &Namespace,
+        ctx: &TypeConversionContext,
+    ) -> Result<Annotated<Type>, ConvertError> {
+        if !ctx.allow_function_pointers() {
+            return Err(ConvertError::FunctionPointer);
+        }
+        let sig = function_pointer_signature(&typ).unwrap();
+        let output = match &sig.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ty) => Some(ty.as_ref().clone()),
+        };
+        let signature_types: Vec<Type> = sig
+            .inputs
+            .iter()
+            .map(|arg| arg.ty.clone())
+            .chain(output)
+            .collect();
+        let mut deps = HashSet::new();
+        let mut extra_apis = ApiVec::new();
+        for ty in signature_types {
+            let mut annotated = self.convert_type(ty, ns, ctx)?;
+            deps.extend(annotated.types_encountered.drain());
+            extra_apis.append(&mut annotated.extra_apis);
+        }
+        Ok(Annotated::new(
+            Type::Path(typ),
+            deps,
+            extra_apis,
+            TypeKind::Pointer,
+        ))
+    }
+
     fn get_generic_args(typ: &mut TypePath) -> Option<&mut PathSegment> {
         match typ.path.segments.last_mut() {
             Some(s) if !s.arguments.is_empty() => Some(s),
@@ -360,8 +496,58 @@ impl<'a> TypeConverter<'a> {
         ))
     }
 
+    /// If this is an instantiation of an alias template, e.g. `Ptr<Goat>`,
+    /// whose target is a generic type understood by cxx, e.g.
+    /// `std::unique_ptr<T>`, returns that target instantiated in its place,
+    /// e.g. `std::unique_ptr<Goat>`. Other alias templates, such as
+    /// `template<class T> using Vec = std::vector<T, MyAlloc<T>>`, are
+    /// treated like any other template which cxx doesn't understand: we
+    /// instantiate a concrete type, which on the C++ side is a typedef to
+    /// the alias as written, e.g. `Vec<int>`.
+    fn instantiate_alias_template(&self, typ: &TypePath, tn: &QualifiedName) -> Option<TypePath> {
+        let alias = self.alias_templates.get(tn)?;
+        let args = match &typ.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) => ab
+                .args
+                .iter()
+                .filter_map(|arg| match arg {
+                    GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => return None,
+        };
+        let params: Vec<_> = alias.generics.type_params().map(|tp| &tp.ident).collect();
+        if params.len() != args.len() {
+            return None;
+        }
+        let target = match alias.ty.as_ref() {
+            Type::Path(target) => target,
+            _ => return None,
+        };
+        let target_tn = QualifiedName::from_type_path(target);
+        let target_args = match &target.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(ab) => ab.args.len(),
+            _ => 0,
+        };
+        if known_types().cxx_generic_behavior(&target_tn) == CxxGenericType::Not || target_args != 1
+        {
+            return None;
+        }
+        let substitutions: HashMap<_, _> = params.into_iter().zip(args).collect();
+        let mut instantiated = target.clone();
+        substitute_type_params_in_path(&mut instantiated, &substitutions);
+        log::info!(
+            "Treating {} as {}",
+            typ.to_token_stream(),
+            instantiated.to_token_stream()
+        );
+        Some(instantiated)
+    }
+
     fn resolve_typedef<'b>(&'b self, tn: &QualifiedName) -> Result<Option<&'b Type>, ConvertError> {
-        let mut encountered = HashSet::new();
+        // The chain of typedefs followed so far, for diagnostics.
+        let mut chain = vec![tn.clone()];
         let mut tn = tn.clone();
         let mut previous_typ = None;
         loop {
@@ -370,10 +556,11 @@ impl<'a> TypeConverter<'a> {
                 Some(Type::Path(typ)) => {
                     previous_typ = r;
                     let new_tn = QualifiedName::from_type_path(typ);
-                    if encountered.contains(&new_tn) {
-                        return Err(ConvertError::InfinitelyRecursiveTypedef(tn.clone()));
+                    let is_cycle = chain.contains(&new_tn);
+                    chain.push(new_tn.clone());
+                    if is_cycle {
+                        return Err(ConvertError::InfinitelyRecursiveTypedef(chain));
                     }
-                    encountered.insert(new_tn.clone());
                     tn = new_tn;
                 }
                 None => return Ok(previous_typ),
@@ -535,6 +722,20 @@ impl<'a> TypeConverter<'a> {
             .collect()
     }
 
+    fn find_alias_templates<A: AnalysisPhase>(
+        apis: &ApiVec<A>,
+    ) -> HashMap<QualifiedName, ItemType> {
+        apis.iter()
+            .filter_map(|api| match &api {
+                Api::Typedef {
+                    item: TypedefKind::Type(ity),
+                    ..
+                } if is_alias_template(ity) => Some((api.name().clone(), ity.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn find_concrete_templates<A: AnalysisPhase>(
         apis: &ApiVec<A>,
     ) -> HashMap<String, QualifiedName> {
@@ -548,10 +749,21 @@ impl<'a> TypeConverter<'a> {
             .collect()
     }
 
-    fn find_incomplete_types<A: AnalysisPhase>(apis: &ApiVec<A>) -> HashSet<QualifiedName> {
+    /// Finds forward declarations which we shouldn't allow inside
+    /// templated types. Types declared with `extern_cpp_opaque_type!` are
+    /// exempt: the user has promised that they'll be complete wherever
+    /// the C++ compiler needs them to be.
+    fn find_incomplete_types<A: AnalysisPhase>(
+        config: &IncludeCppConfig,
+        apis: &ApiVec<A>,
+    ) -> HashSet<QualifiedName> {
         apis.iter()
             .filter_map(|api| match api {
-                Api::ForwardDeclaration { .. } => Some(api.name()),
+                Api::ForwardDeclaration { .. }
+                    if !config.is_opaque_type(&api.name().to_cpp_name()) =>
+                {
+                    Some(api.name())
+                }
                 _ => None,
             })
             .cloned()
@@ -579,6 +791,71 @@ pub(crate) fn add_analysis<A: AnalysisPhase>(api: UnanalyzedApi) -> Api<A> {
         _ => panic!("Function analysis created an unexpected type of extra API"),
     }
 }
+/// If this type is a function pointer, as represented by bindgen (that is,
+/// `Option<unsafe extern "C" fn(..)>`), returns its signature.
+pub(crate) fn function_pointer_signature(typ: &TypePath) -> Option<&TypeBareFn> {
+    let mut segments = typ.path.segments.iter().rev();
+    let last_seg = segments.next()?;
+    if last_seg.ident != "Option" || segments.next()?.ident != "option" {
+        return None;
+    }
+    match &last_seg.arguments {
+        PathArguments::AngleBracketed(ab) if ab.args.len() == 1 => match ab.args.first() {
+            Some(GenericArgument::Type(Type::BareFn(sig))) => Some(sig),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether this typedef is really an alias template, such as
+/// `template<class T> using Ptr = std::unique_ptr<T>`. Its target can only
+/// be understood once we know what it's instantiated with.
+pub(crate) fn is_alias_template(ity: &ItemType) -> bool {
+    ity.generics.type_params().next().is_some()
+}
+
+/// Replaces the type parameters of an alias template with the types given.
+fn substitute_type_params(ty: &mut Type, substitutions: &HashMap<&Ident, &Type>) {
+    match ty {
+        Type::Path(typ) => {
+            if typ.qself.is_none() && typ.path.segments.len() == 1 {
+                let seg = typ.path.segments.first().unwrap();
+                if seg.arguments.is_empty() {
+                    if let Some(substitute) = substitutions.get(&seg.ident) {
+                        *ty = (*substitute).clone();
+                        return;
+                    }
+                }
+            }
+            substitute_type_params_in_path(typ, substitutions);
+        }
+        Type::Ptr(ptr) => substitute_type_params(&mut ptr.elem, substitutions),
+        Type::Reference(reference) => substitute_type_params(&mut reference.elem, substitutions),
+        Type::Array(arr) => substitute_type_params(&mut arr.elem, substitutions),
+        Type::Slice(slice) => substitute_type_params(&mut slice.elem, substitutions),
+        Type::Paren(paren) => substitute_type_params(&mut paren.elem, substitutions),
+        Type::Group(group) => substitute_type_params(&mut group.elem, substitutions),
+        Type::Tuple(tuple) => tuple
+            .elems
+            .iter_mut()
+            .for_each(|elem| substitute_type_params(elem, substitutions)),
+        _ => {}
+    }
+}
+
+fn substitute_type_params_in_path(typ: &mut TypePath, substitutions: &HashMap<&Ident, &Type>) {
+    for seg in typ.path.segments.iter_mut() {
+        if let PathArguments::AngleBracketed(ab) = &mut seg.arguments {
+            for arg in ab.args.iter_mut() {
+                if let GenericArgument::Type(ty) = arg {
+                    substitute_type_params(ty, substitutions);
+                }
+            }
+        }
+    }
+}
+
 pub(crate) trait TypedefTarget {
     fn get_target(&self) -> Option<&Type>;
 }
@@ -592,6 +869,9 @@ impl TypedefTarget for () {
 impl TypedefTarget for TypedefAnalysis {
     fn get_target(&self) -> Option<&Type> {
         match self.kind {
+            // Alias templates are handled separately, since their targets
+            // mention their type parameters.
+            TypedefKind::Type(ref ty) if is_alias_template(ty) => None,
             TypedefKind::Type(ref ty) => Some(&ty.ty),
             TypedefKind::Use(_) => None,
         }
@@ -607,6 +887,7 @@ pub(crate) fn find_types<A: AnalysisPhase>(apis: &ApiVec<A>) -> HashSet<Qualifie
             | Api::Enum { .. }
             | Api::Struct { .. }
             | Api::Subclass { .. }
+            | Api::ExternCppType { .. }
             | Api::RustType { .. } => Some(api.name()),
             Api::StringConstructor { .. }
             | Api::Function { .. }
@@ -8,6 +8,7 @@
 
 use std::collections::HashSet;
 
+pub(crate) use crate::source_locations::SourceLocation;
 use crate::types::{make_ident, Namespace, QualifiedName};
 use autocxx_parser::RustPath;
 use itertools::Itertools;
@@ -16,8 +17,8 @@ use syn::{
     parse::Parse,
     punctuated::Punctuated,
     token::{Comma, Unsafe},
-    Attribute, FnArg, Ident, ItemConst, ItemEnum, ItemStruct, ItemType, ItemUse, LitBool, LitInt,
-    Pat, ReturnType, Signature, Type, Visibility,
+    Attribute, FnArg, Ident, Item, ItemConst, ItemEnum, ItemStruct, ItemType, ItemUse, LitBool,
+    LitInt, Pat, ReturnType, Signature, Type, Visibility,
 };
 
 use super::{
@@ -82,6 +83,10 @@ impl Parse for Layout {
     }
 }
 
+/// The message given for a C++ function marked `[[deprecated]]`, if any.
+#[derive(Clone)]
+pub(crate) struct Deprecation(pub(crate) Option<String>);
+
 #[derive(Clone)]
 pub(crate) enum Virtualness {
     None,
@@ -106,6 +111,8 @@ pub(crate) enum TraitSynthesis {
     },
     AllocUninitialized(QualifiedName),
     FreeUninitialized(QualifiedName),
+    AddRef(QualifiedName),
+    Release(QualifiedName),
 }
 
 /// Details of a subclass constructor.
@@ -208,6 +215,13 @@ pub(crate) enum Provenance {
     SynthesizedOther,
     SynthesizedMakeUnique,
     SynthesizedSubclassConstructor(Box<SubclassConstructorDetails>),
+    /// A protected method of a superclass, which the subclass makes
+    /// accessible so that its Rust implementation can call it.
+    SynthesizedSubclassProtectedAccess(SubclassName),
+    /// A variant of a function which takes shared references in place of
+    /// the given non-const reference parameters, requested using
+    /// `shared_ref_variant!`.
+    SynthesizedSharedRefVariant(Vec<Ident>),
 }
 
 /// A C++ function for which we need to generate bindings, but haven't
@@ -247,6 +261,17 @@ pub(crate) struct FuncToConvert {
     /// C++ and instead we're synthesizing it.
     pub(crate) synthetic_cpp: Option<(CppFunctionBody, CppFunctionKind)>,
     pub(crate) is_deleted: bool,
+    /// Whether this function is in an anonymous namespace, and so can only
+    /// be called through a shim in our generated C++.
+    pub(crate) internal_linkage: bool,
+    /// Where this function was declared, if known.
+    pub(crate) source_location: Option<SourceLocation>,
+    /// Whether this function is marked `[[deprecated]]`.
+    pub(crate) deprecation: Option<Deprecation>,
+    /// If this function was brought into its namespace by a `using`
+    /// declaration, the namespace it was declared in. Any shim calls it
+    /// there, so is the same as the shim for the original function.
+    pub(crate) reexported_from: Option<Namespace>,
 }
 
 /// Layers of analysis which may be applied to decorate each API.
@@ -274,10 +299,29 @@ pub(crate) enum TypedefKind {
 
 /// Name information for an API. This includes the name by
 /// which we know it in Rust, and its C++ name, which may differ.
-#[derive(Clone, Hash, PartialEq, Eq)]
+/// It also records where the API was declared, if known, for
+/// diagnostics; that isn't part of the API's identity, so is
+/// ignored when comparing names.
+#[derive(Clone)]
 pub(crate) struct ApiName {
     pub(crate) name: QualifiedName,
     cpp_name: Option<String>,
+    source_location: Option<SourceLocation>,
+}
+
+impl PartialEq for ApiName {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.cpp_name == other.cpp_name
+    }
+}
+
+impl Eq for ApiName {}
+
+impl std::hash::Hash for ApiName {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.cpp_name.hash(state);
+    }
 }
 
 impl ApiName {
@@ -289,6 +333,7 @@ impl ApiName {
         Self {
             name: QualifiedName::new(ns, id),
             cpp_name,
+            source_location: None,
         }
     }
 
@@ -296,9 +341,21 @@ impl ApiName {
         Self {
             name,
             cpp_name: None,
+            source_location: None,
         }
     }
 
+    /// Records where this API was declared in the original headers.
+    #[must_use]
+    pub(crate) fn with_source_location(mut self, source_location: Option<SourceLocation>) -> Self {
+        self.source_location = source_location;
+        self
+    }
+
+    pub(crate) fn source_location(&self) -> Option<&SourceLocation> {
+        self.source_location.as_ref()
+    }
+
     pub(crate) fn new_in_root_namespace(id: Ident) -> Self {
         Self::new(&Namespace::new(), id)
     }
@@ -366,6 +423,16 @@ impl SubclassName {
     pub(crate) fn remove_ownership(&self) -> Ident {
         self.with_suffix("_remove_ownership")
     }
+    /// Generate the name for the Rust function called from the C++
+    /// subclass destructor.
+    pub(crate) fn destroyed(&self) -> Ident {
+        self.with_suffix("_destroyed")
+    }
+    /// Generate the name for the trait implemented by Rust types which
+    /// plug into a generic subclass.
+    pub(crate) fn generic_methods_trait(&self) -> Ident {
+        self.with_suffix("_methods")
+    }
     fn with_suffix(&self, suffix: &str) -> Ident {
         make_ident(format!("{}{}", self.0.name.get_final_item(), suffix))
     }
@@ -456,6 +523,16 @@ pub(crate) enum Api<T: AnalysisPhase> {
         details: Box<StructDetails>,
         analysis: T::StructAnalysis,
     },
+    /// A C++ type which the user has declared, using `extern_cpp_type!`
+    /// or a [`TypeMapper`](crate::TypeMapper), to be ABI-identical to an
+    /// existing Rust type.
+    ExternCppType {
+        name: ApiName,
+        rust_path: RustPath,
+        layout: Option<Layout>,
+        cpp_shims: Option<String>,
+        rs_shims: Vec<Item>,
+    },
     /// A variable-length C integer type (e.g. int, unsigned long).
     CType {
         name: ApiName,
@@ -477,6 +554,8 @@ pub(crate) enum Api<T: AnalysisPhase> {
         name: ApiName,
         sig: Signature,
         path: RustPath,
+        /// Whether C++ should be able to call this via a factory function.
+        is_factory: bool,
     },
     /// Some function for the extern "Rust" block.
     RustSubclassFn {
@@ -527,6 +606,7 @@ impl<T: AnalysisPhase> Api<T> {
             Api::Typedef { name, .. } => name,
             Api::Enum { name, .. } => name,
             Api::Struct { name, .. } => name,
+            Api::ExternCppType { name, .. } => name,
             Api::CType { name, .. } => name,
             Api::IgnoredItem { name, .. } => name,
             Api::RustType { name, .. } => name,
@@ -98,7 +98,7 @@ impl<P: AnalysisPhase> ApiVec<P> {
     }
 
     pub(crate) fn extend(&mut self, it: impl Iterator<Item = Api<P>>) {
-        // Could be optimized in future
+        self.apis.reserve(it.size_hint().0);
         for api in it {
             self.push(api)
         }
@@ -112,6 +112,16 @@ impl<P: AnalysisPhase> ApiVec<P> {
         self.apis.into_iter()
     }
 
+    pub(crate) fn len(&self) -> usize {
+        self.apis.len()
+    }
+
+    /// Since each `Api` is large, avoid reallocating the whole list as it
+    /// grows when we know roughly how many to expect.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.apis.reserve(additional)
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.apis.is_empty()
     }
@@ -138,11 +148,25 @@ impl<P: AnalysisPhase> Default for ApiVec<P> {
 
 impl<P: AnalysisPhase> FromIterator<Api<P>> for ApiVec<P> {
     fn from_iter<I: IntoIterator<Item = Api<P>>>(iter: I) -> Self {
-        let mut this = ApiVec::new();
-        for i in iter {
-            // Could be optimized in future
-            this.push(i);
-        }
-        this
+        // Collecting straight into a `Vec` lets the standard library reuse
+        // the allocation of the source when an `ApiVec` is mapped into
+        // another of the same phase, rather than holding both at once.
+        let mut apis: Vec<_> = iter.into_iter().collect();
+        let mut names = HashSet::with_capacity(apis.len());
+        apis.retain(|api| {
+            let name = api.name();
+            if names.insert(name.clone()) {
+                true
+            } else if api.discard_duplicates() {
+                log::info!("Discarding duplicate API for {}", name);
+                false
+            } else {
+                panic!(
+                    "Already have an API with that name: {}. API was {:?}",
+                    name, api
+                );
+            }
+        });
+        Self { apis, names }
     }
 }
@@ -0,0 +1,242 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A plain C header for the Rust functions given using
+//! `extern_rust_function`, for consumers which can't use the C++ API
+//! generated by cxx (e.g. C code, or other binding generators such as
+//! SWIG). Only functions whose parameters and return types are all
+//! primitives, or pointers to primitives, can be represented in C. For
+//! each, we generate a `#[no_mangle] extern "C"` Rust wrapper, named
+//! after the `include_cpp!` mod since C has no namespaces. That also
+//! avoids clashing with the C++ function which cxx declares.
+
+use autocxx_parser::{IncludeCppConfig, RustPath};
+use itertools::Itertools;
+use quote::quote;
+use syn::{parse_quote, FnArg, ItemFn, ReturnType, Signature, Type};
+
+use crate::{conversion::unwind::extern_abi, types::make_ident, CppFilePair};
+
+/// A Rust function as seen from C.
+struct CSignature {
+    name: String,
+    params: Vec<String>,
+    ret: String,
+}
+
+impl CSignature {
+    fn new(config: &IncludeCppConfig, sig: &Signature) -> Option<Self> {
+        if sig.asyncness.is_some() || sig.variadic.is_some() || !sig.generics.params.is_empty() {
+            return None;
+        }
+        let params = sig
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                FnArg::Typed(pt) => c_type(&pt.ty),
+                FnArg::Receiver(_) => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let ret = match &sig.output {
+            ReturnType::Default => "void".into(),
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Tuple(tup) if tup.elems.is_empty() => "void".into(),
+                ty => c_type(ty)?,
+            },
+        };
+        Some(Self {
+            name: format!("{}_{}", config.get_mod_name(), sig.ident),
+            params,
+            ret,
+        })
+    }
+
+    fn declaration(&self) -> String {
+        let params = if self.params.is_empty() {
+            "void".into()
+        } else {
+            self.params
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| format!("{} arg{}", ty, i))
+                .join(", ")
+        };
+        format!("{} {}({});", self.ret, self.name, params)
+    }
+}
+
+/// The C equivalent of a Rust type, if there is one.
+fn c_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(typ) if typ.qself.is_none() => {
+            let c_type = match typ.path.get_ident()?.to_string().as_str() {
+                "bool" => "bool",
+                "i8" => "int8_t",
+                "i16" => "int16_t",
+                "i32" => "int32_t",
+                "i64" => "int64_t",
+                "u8" => "uint8_t",
+                "u16" => "uint16_t",
+                "u32" => "uint32_t",
+                "u64" => "uint64_t",
+                "isize" => "intptr_t",
+                "usize" => "uintptr_t",
+                "f32" => "float",
+                "f64" => "double",
+                _ => return None,
+            };
+            Some(c_type.into())
+        }
+        Type::Ptr(ptr) => {
+            let pointee = c_type(&ptr.elem)?;
+            Some(match ptr.const_token {
+                Some(_) => format!("const {}*", pointee),
+                None => format!("{}*", pointee),
+            })
+        }
+        Type::Paren(paren) => c_type(&paren.elem),
+        Type::Group(group) => c_type(&group.elem),
+        _ => None,
+    }
+}
+
+/// Generates the `extern "C"` wrapper for a Rust function, if it can be
+/// represented in C. The wrapper lives in the `include_cpp!` mod, whose
+/// parent contains the function itself.
+pub(crate) fn generate_c_wrapper(
+    config: &IncludeCppConfig,
+    path: &RustPath,
+    sig: &Signature,
+) -> Option<ItemFn> {
+    let c_sig = CSignature::new(config, sig)?;
+    let c_name = make_ident(&c_sig.name);
+    let (params, args): (Vec<_>, Vec<_>) = sig
+        .inputs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, arg)| match arg {
+            FnArg::Typed(pt) => {
+                let arg = make_ident(format!("arg{}", i));
+                let ty = &pt.ty;
+                Some((quote! { #arg: #ty }, arg))
+            }
+            FnArg::Receiver(_) => None,
+        })
+        .unzip();
+    let ret = &sig.output;
+    let call = quote! { super::#path(#(#args),*) };
+    let body = match sig.unsafety {
+        Some(_) => quote! { unsafe { #call } },
+        None => call,
+    };
+    let unsafety = &sig.unsafety;
+    let abi = extern_abi(config.exception_policy());
+    Some(parse_quote! {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub #unsafety #abi fn #c_name(#(#params),*) #ret {
+            #body
+        }
+    })
+}
+
+/// Generates a C header declaring the wrappers for each Rust function
+/// which can be represented in C, or `None` if there are no such functions.
+pub(crate) fn generate_c_header(
+    config: &IncludeCppConfig,
+    header_name: String,
+) -> Option<CppFilePair> {
+    let declarations: Vec<_> = config
+        .extern_rust_funs
+        .iter()
+        .filter_map(|fun| {
+            let c_sig = CSignature::new(config, &fun.sig);
+            if c_sig.is_none() {
+                log::info!(
+                    "Rust function {} isn't C-compatible, so is omitted from the C header",
+                    fun.sig.ident
+                );
+            }
+            c_sig
+        })
+        .map(|c_sig| c_sig.declaration())
+        .collect();
+    if declarations.is_empty() {
+        return None;
+    }
+    let guard = format!(
+        "__{}__",
+        header_name
+            .to_uppercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    let header = format!(
+        "#ifndef {}\n#define {}\n\n#include <stdbool.h>\n#include <stdint.h>\n\n#ifdef __cplusplus\nextern \"C\" {{\n#endif\n\n{}\n\n#ifdef __cplusplus\n}}\n#endif\n\n#endif // {}\n",
+        guard,
+        guard,
+        declarations.join("\n"),
+        guard
+    );
+    Some(CppFilePair {
+        header: header.into_bytes(),
+        implementation: None,
+        additional_implementations: Vec::new(),
+        header_name,
+        shim_origins: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_c_wrapper, CSignature};
+    use autocxx_parser::{IncludeCppConfig, RustPath};
+    use quote::ToTokens;
+    use syn::{parse_quote, Signature};
+
+    #[test]
+    fn test_c_signature() {
+        let config: IncludeCppConfig = parse_quote! {
+            name!(mylib)
+        };
+        let declaration = |sig: Signature| CSignature::new(&config, &sig).map(|s| s.declaration());
+        assert_eq!(
+            declaration(parse_quote! { fn add(a: u32, b: *const f64) -> i64 }).as_deref(),
+            Some("int64_t mylib_add(uint32_t arg0, const double* arg1);")
+        );
+        assert_eq!(
+            declaration(parse_quote! { fn reset() }).as_deref(),
+            Some("void mylib_reset(void);")
+        );
+        assert_eq!(
+            declaration(parse_quote! { fn name(data: &[u8]) -> String }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_c_wrapper_abi() {
+        let path: RustPath = parse_quote! { reset };
+        let sig: Signature = parse_quote! { fn reset() };
+        let abi = |config: IncludeCppConfig| {
+            generate_c_wrapper(&config, &path, &sig)
+                .unwrap()
+                .sig
+                .abi
+                .to_token_stream()
+                .to_string()
+        };
+        assert_eq!(abi(parse_quote! { name!(mylib) }), "extern \"C\"");
+        assert_eq!(
+            abi(parse_quote! {
+                name!(mylib)
+                exception_policy!(propagate)
+            }),
+            "extern \"C-unwind\""
+        );
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use indoc::formatdoc;
+use itertools::Itertools;
+use quote::ToTokens;
+
+use crate::conversion::{
+    analysis::fun::{batch::BatchDetails, ArgumentAnalysis, FnKind, ReceiverMutability},
+    ConvertError,
+};
+
+use super::{
+    type_to_cpp::{namespaced_name_using_original_name_map, type_to_cpp, CppNameMap},
+    AdditionalFunction, Header,
+};
+
+/// Generates the C++ side of a batched function, requested using `batch!`.
+/// Rust passes the argument structs as a slice of bytes; we declare a
+/// struct with the same layout and call the original function once for
+/// each element. The Rust side is generated by `codegen_rs::batch`.
+pub(super) fn generate_batch_helper(
+    batch: &BatchDetails,
+    kind: &FnKind,
+    param_details: &[ArgumentAnalysis],
+    original_name_map: &CppNameMap,
+) -> Result<AdditionalFunction, ConvertError> {
+    let helper = &batch.helper;
+    let args_struct = format!("{}_args", helper);
+    let mut receiver = None;
+    let mut fields = Vec::new();
+    let mut args = Vec::new();
+    for pd in param_details {
+        match &pd.self_type {
+            Some((_, mutability)) => receiver = Some(mutability),
+            None => {
+                let name = pd.name.to_token_stream().to_string();
+                let ty = type_to_cpp(&pd.conversion.unwrapped_type, original_name_map)?;
+                fields.push(format!("  {} {};", ty, name));
+                args.push(format!("a.{}", name));
+            }
+        }
+    }
+    let args = args.join(", ");
+    let (this_param, call) = match (kind, receiver) {
+        (FnKind::Method { impl_for, .. }, Some(mutability)) => {
+            let constness = match mutability {
+                ReceiverMutability::Const => "const ",
+                ReceiverMutability::Mutable => "",
+            };
+            (
+                format!(
+                    "{}{}& self, ",
+                    constness,
+                    namespaced_name_using_original_name_map(impl_for, original_name_map)
+                ),
+                format!("self.{}({})", batch.cpp_call, args),
+            )
+        }
+        _ => (String::new(), format!("{}({})", batch.cpp_call, args)),
+    };
+    let signature = format!(
+        "void {}({}rust::Slice<const std::uint8_t> args)",
+        helper, this_param
+    );
+    let definition = formatdoc! {"
+        {} {{
+          auto begin = reinterpret_cast<const {}*>(args.data());
+          auto end = begin + args.size() / sizeof({});
+          for (auto it = begin; it != end; ++it) {{
+            const {}& a = *it;
+            {};
+          }}
+        }}",
+        signature,
+        args_struct,
+        args_struct,
+        args_struct,
+        call
+    };
+    Ok(AdditionalFunction {
+        type_definition: Some(format!(
+            "struct {} {{\n{}\n}};",
+            args_struct,
+            fields.iter().join("\n")
+        )),
+        declaration: Some(format!("{};", signature)),
+        definition: Some(definition),
+        headers: vec![Header::System("cstdint"), Header::CxxH],
+        cpp_headers: Vec::new(),
+    })
+}
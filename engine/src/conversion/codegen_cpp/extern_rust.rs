@@ -0,0 +1,381 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::{RustFun, RustPath};
+use indoc::indoc;
+use itertools::Itertools;
+use syn::{FnArg, GenericArgument, Ident, PathArguments, ReturnType, Type};
+
+use crate::conversion::ConvertError;
+
+use super::{
+    type_to_cpp::{rust_type_to_cpp, unsupported_type},
+    AdditionalFunction, Header,
+};
+
+/// `autocxx::Expected<T>`, returned by the wrappers generated for
+/// `#[extern_rust_function(expected)]`. It holds either the value returned
+/// by the Rust function, or the message of the error it returned instead.
+pub(super) static EXPECTED_PRELUDE: &str = indoc! {"
+    #include <memory>
+    #include <stdexcept>
+    #include <string>
+    #include <utility>
+    #ifndef AUTOCXX_EXPECTED_PRELUDE
+    #define AUTOCXX_EXPECTED_PRELUDE
+    namespace autocxx {
+    // Either the value returned by a Rust function, or the message of
+    // the error which it returned instead.
+    template <typename T> class Expected {
+    public:
+      Expected(T value) : value_(std::make_unique<T>(std::move(value))) {}
+      static Expected from_error(std::string message) {
+        Expected e;
+        e.error_ = std::move(message);
+        return e;
+      }
+      bool has_value() const { return value_ != nullptr; }
+      explicit operator bool() const { return has_value(); }
+      // Throws std::logic_error if this holds an error.
+      T &value() {
+        check();
+        return *value_;
+      }
+      const T &value() const {
+        check();
+        return *value_;
+      }
+      const std::string &error() const { return error_; }
+
+    private:
+      Expected() = default;
+      void check() const {
+        if (!value_) {
+          throw std::logic_error(error_);
+        }
+      }
+      std::unique_ptr<T> value_;
+      std::string error_;
+    };
+    template <> class Expected<void> {
+    public:
+      Expected() : has_value_(true) {}
+      static Expected from_error(std::string message) {
+        Expected e;
+        e.has_value_ = false;
+        e.error_ = std::move(message);
+        return e;
+      }
+      bool has_value() const { return has_value_; }
+      explicit operator bool() const { return has_value(); }
+      // Throws std::logic_error if this holds an error.
+      void value() const {
+        if (!has_value_) {
+          throw std::logic_error(error_);
+        }
+      }
+      const std::string &error() const { return error_; }
+
+    private:
+      bool has_value_;
+      std::string error_;
+    };
+    } // namespace autocxx
+    #endif // AUTOCXX_EXPECTED_PRELUDE
+"};
+
+/// Generates `<function>_expected`, which calls a Rust function returning a
+/// `Result` and converts any `rust::Error` thrown by cxx into an
+/// `autocxx::Expected`. The wrapper is declared in our header using the
+/// C++ types which cxx uses for the function, but can only be defined
+/// alongside `cxxgen.h`, which declares the Rust function itself.
+pub(super) fn generate_expected_wrapper(
+    fun: &RustFun,
+    ns: Option<&str>,
+) -> Result<AdditionalFunction, ConvertError> {
+    let name = &fun.sig.ident;
+    let ok_type = result_ok_type(&fun.sig.output).ok_or_else(|| {
+        ConvertError::UnsupportedType(format!(
+            "{} is marked as 'expected' but does not return a Result",
+            name
+        ))
+    })?;
+    let ret_type = format!("autocxx::Expected<{}>", expected_value_type(ok_type)?);
+    let (param_decls, args) = params_to_cpp(fun)?;
+    let call = format!("{}({})", name, args);
+    let success = if is_unit(ok_type) {
+        format!("{};\n    return {}();", call, ret_type)
+    } else {
+        format!("return {}({});", ret_type, call)
+    };
+    let signature = format!("{} {}_expected({})", ret_type, name, param_decls);
+    Ok(AdditionalFunction {
+        type_definition: None,
+        declaration: Some(in_namespace(ns, format!("{};", signature))),
+        definition: Some(in_namespace(ns, format!(
+            "{} {{\n  try {{\n    {}\n  }} catch (const rust::Error &e) {{\n    return {}::from_error(e.what());\n  }}\n}}",
+            signature, success, ret_type
+        ))),
+        headers: vec![Header::CxxH, Header::ExpectedPrelude],
+        cpp_headers: vec![Header::CxxgenH],
+    })
+}
+
+/// Returns the type which a `#[extern_rust_function(factory)]` creates,
+/// and whether it's already returned in a `Box`.
+pub(crate) fn factory_product(ret_type: &ReturnType) -> Option<(&Type, bool)> {
+    let ty = match ret_type {
+        ReturnType::Type(_, ty) => ty.as_ref(),
+        ReturnType::Default => return None,
+    };
+    let typ = match ty {
+        Type::Path(typ) => typ,
+        _ => return None,
+    };
+    let last_seg = typ.path.segments.last()?;
+    if last_seg.ident != "Box" {
+        return Some((ty, false));
+    }
+    match &last_seg.arguments {
+        PathArguments::AngleBracketed(ab) => match ab.args.first()? {
+            GenericArgument::Type(ty) => Some((ty, true)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The name of the function which boxes the result of a factory function
+/// returning its type by value, since cxx can't do that itself.
+pub(crate) fn boxing_wrapper_name(fun: &Ident) -> String {
+    format!("{}_autocxx_boxed", fun)
+}
+
+/// Returns the type created by a factory function, as cxx refers to it in
+/// C++, the name of the Rust function which returns it in a `Box`, and the
+/// name of the C++ factory function which we generate to call that.
+fn factory_details(
+    fun: &RustFun,
+    types: &[RustPath],
+) -> Result<(String, String, String), ConvertError> {
+    let name = &fun.sig.ident;
+    let product = factory_product(&fun.sig.output).and_then(|(ty, boxed)| match ty {
+        Type::Path(typ) if typ.qself.is_none() => {
+            let id = &typ.path.segments.last()?.ident;
+            types
+                .iter()
+                .any(|rt| rt.get_final_ident() == id)
+                .then(|| (id.to_string(), boxed))
+        }
+        _ => None,
+    });
+    let (product, boxed) = product.ok_or_else(|| {
+        ConvertError::UnsupportedType(format!(
+            "{} is marked as a 'factory' but does not return a Rust type exposed to C++, or a Box of one",
+            name
+        ))
+    })?;
+    let rust_fun = if boxed {
+        name.to_string()
+    } else {
+        boxing_wrapper_name(name)
+    };
+    let factory = format!("make_{}", product);
+    Ok((product, rust_fun, factory))
+}
+
+/// Generates `make_<type>`, which lets C++ create instances of a Rust type
+/// by calling a `#[extern_rust_function(factory)]`. It returns the new
+/// instance in a `rust::Box`, since C++ can't hold a Rust type by value.
+pub(super) fn generate_factory(
+    fun: &RustFun,
+    ns: Option<&str>,
+    types: &[RustPath],
+) -> Result<AdditionalFunction, ConvertError> {
+    let (product, rust_fun, factory) = factory_details(fun, types)?;
+    let (param_decls, args) = params_to_cpp(fun)?;
+    let signature = format!("rust::Box<{}> {}({})", product, factory, param_decls);
+    // As for extern_rust_class!, cxx defines the type in cxxgen.h, so we
+    // need to declare it first.
+    Ok(AdditionalFunction {
+        type_definition: None,
+        declaration: Some(in_namespace(
+            ns,
+            format!("struct {};\n{};", product, signature),
+        )),
+        definition: Some(in_namespace(
+            ns,
+            format!("{} {{\n  return {}({});\n}}", signature, rust_fun, args),
+        )),
+        headers: vec![Header::CxxH],
+        cpp_headers: vec![Header::CxxgenH],
+    })
+}
+
+/// Generates the class requested using `extern_rust_class!`, which groups
+/// the Rust functions exposed to C++ as static member functions, and the
+/// Rust types as member type aliases, so that they can match the
+/// conventions of an existing C++ API. Each member function forwards to
+/// the free function declared by cxx.
+pub(super) fn generate_extern_rust_class(
+    class: &str,
+    ns: Option<&str>,
+    funs: &[RustFun],
+    types: &[RustPath],
+) -> Result<AdditionalFunction, ConvertError> {
+    let qualifier = match ns {
+        Some(ns) => format!("::{}::", ns),
+        None => "::".into(),
+    };
+    // cxx defines these types in cxxgen.h, which includes our header,
+    // so we need to declare them before we can refer to them.
+    let mut declarations: Vec<String> = types
+        .iter()
+        .map(|ty| format!("struct {};", ty.get_final_ident()))
+        .collect();
+    let mut members: Vec<String> = types
+        .iter()
+        .map(|ty| {
+            let id = ty.get_final_ident();
+            format!("  using {} = {}{};", id, qualifier, id)
+        })
+        .collect();
+    let mut definitions = Vec::new();
+    let mut headers = vec![Header::CxxH];
+    for fun in funs {
+        let mut forwarders = Vec::new();
+        if fun.is_factory {
+            // A factory returning its type by value can only be called
+            // via the factory function which we generate for it.
+            let (product, rust_fun, factory) = factory_details(fun, types)?;
+            if fun.sig.ident == rust_fun {
+                forwarders.push((rust_fun, ret_type_to_cpp(fun)?));
+            }
+            forwarders.push((factory, format!("rust::Box<{}>", product)));
+        } else {
+            forwarders.push((fun.sig.ident.to_string(), ret_type_to_cpp(fun)?));
+        }
+        if fun.returns_expected {
+            let ok_type = result_ok_type(&fun.sig.output).ok_or_else(|| {
+                ConvertError::UnsupportedType(format!(
+                    "{} is marked as 'expected' but does not return a Result",
+                    fun.sig.ident
+                ))
+            })?;
+            forwarders.push((
+                format!("{}_expected", fun.sig.ident),
+                format!("autocxx::Expected<{}>", expected_value_type(ok_type)?),
+            ));
+            headers.push(Header::ExpectedPrelude);
+        }
+        let (param_decls, args) = params_to_cpp(fun)?;
+        for (name, ret_type) in forwarders {
+            members.push(format!("  static {} {}({});", ret_type, name, param_decls));
+            definitions.push(format!(
+                "{} {}::{}({}) {{\n  return {}{}({});\n}}",
+                ret_type, class, name, param_decls, qualifier, name, args
+            ));
+        }
+    }
+    declarations.push(format!("struct {} {{\n{}\n}};", class, members.join("\n")));
+    Ok(AdditionalFunction {
+        type_definition: None,
+        declaration: Some(in_namespace(ns, declarations.join("\n"))),
+        definition: Some(in_namespace(ns, definitions.join("\n"))),
+        headers,
+        cpp_headers: vec![Header::CxxgenH],
+    })
+}
+
+/// Returns the C++ parameter list which cxx declares for a Rust function,
+/// and the arguments with which to forward those parameters to it.
+fn params_to_cpp(fun: &RustFun) -> Result<(String, String), ConvertError> {
+    let params = fun
+        .sig
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match arg {
+            FnArg::Typed(pt) => Ok((format!("arg{}", i), pt.ty.as_ref())),
+            FnArg::Receiver(_) => Err(ConvertError::UnsupportedReceiver),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let param_decls = params
+        .iter()
+        .map(|(arg_name, ty)| Ok(format!("{} {}", rust_type_to_cpp(ty)?, arg_name)))
+        .collect::<Result<Vec<_>, ConvertError>>()?
+        .join(", ");
+    // References and slices are cheap to copy; anything else we move.
+    let args = params
+        .iter()
+        .map(|(arg_name, ty)| match ty {
+            Type::Path(_) => format!("std::move({})", arg_name),
+            _ => arg_name.clone(),
+        })
+        .join(", ");
+    Ok((param_decls, args))
+}
+
+/// Returns the C++ return type which cxx declares for a Rust function.
+/// If the function returns a `Result`, cxx instead throws any error.
+fn ret_type_to_cpp(fun: &RustFun) -> Result<String, ConvertError> {
+    match (&fun.sig.output, result_ok_type(&fun.sig.output)) {
+        (_, Some(ok_type)) => rust_type_to_cpp(ok_type),
+        (ReturnType::Type(_, ty), None) => rust_type_to_cpp(ty),
+        (ReturnType::Default, None) => Ok("void".into()),
+    }
+}
+
+/// The type held by an `autocxx::Expected`, which can't be a reference.
+fn expected_value_type(ok_type: &Type) -> Result<String, ConvertError> {
+    match ok_type {
+        Type::Reference(_) => Err(unsupported_type(ok_type)),
+        _ => rust_type_to_cpp(ok_type),
+    }
+}
+
+/// Returns `T` if this returns `Result<T, E>`.
+fn result_ok_type(ret_type: &ReturnType) -> Option<&Type> {
+    match ret_type {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(typ) => {
+                let last_seg = typ.path.segments.last()?;
+                if last_seg.ident != "Result" {
+                    return None;
+                }
+                match &last_seg.arguments {
+                    PathArguments::AngleBracketed(ab) => match ab.args.first()? {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            _ => None,
+        },
+        ReturnType::Default => None,
+    }
+}
+
+fn is_unit(ty: &Type) -> bool {
+    matches!(ty, Type::Tuple(tup) if tup.elems.is_empty())
+}
+
+/// Wraps some C++ in the given namespace, if any.
+fn in_namespace(ns: Option<&str>, code: String) -> String {
+    match ns {
+        None => code,
+        Some(ns) => {
+            let (opening, closing): (String, String) = ns
+                .split("::")
+                .map(|segment| (format!("namespace {} {{\n", segment), "}\n"))
+                .unzip();
+            format!("{}{}\n{}", opening, code, closing)
+        }
+    }
+}
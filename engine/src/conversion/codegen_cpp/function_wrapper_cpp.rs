@@ -11,6 +11,8 @@ use crate::conversion::{
     ConvertError,
 };
 
+use syn::Type;
+
 use super::type_to_cpp::{type_to_cpp, CppNameMap};
 
 impl TypeConversionPolicy {
@@ -19,8 +21,11 @@ impl TypeConversionPolicy {
         cpp_name_map: &CppNameMap,
     ) -> Result<String, ConvertError> {
         match self.cpp_conversion {
-            CppConversionType::FromUniquePtrToValue => self.unique_ptr_wrapped_type(cpp_name_map),
-            CppConversionType::FromPtrToValue => {
+            CppConversionType::FromUniquePtrToValue
+            | CppConversionType::FromUniquePtrToOwningPtr => {
+                self.unique_ptr_wrapped_type(cpp_name_map)
+            }
+            CppConversionType::FromPtrToValue | CppConversionType::FromOwningPtrToUniquePtr => {
                 Ok(format!("{}*", self.unwrapped_type_as_string(cpp_name_map)?))
             }
             _ => self.unwrapped_type_as_string(cpp_name_map),
@@ -29,11 +34,27 @@ impl TypeConversionPolicy {
 
     pub(super) fn converted_type(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         match self.cpp_conversion {
-            CppConversionType::FromValueToUniquePtr => self.unique_ptr_wrapped_type(cpp_name_map),
+            CppConversionType::FromValueToUniquePtr
+            | CppConversionType::FromOwningPtrToUniquePtr => {
+                self.unique_ptr_wrapped_type(cpp_name_map)
+            }
             _ => self.unwrapped_type_as_string(cpp_name_map),
         }
     }
 
+    fn is_reference_or_pointer(&self) -> bool {
+        match &self.unwrapped_type {
+            Type::Reference(_) | Type::Ptr(_) => true,
+            Type::Path(typ) => typ
+                .path
+                .segments
+                .last()
+                .map(|seg| seg.ident == "Pin")
+                .unwrap_or_default(),
+            _ => false,
+        }
+    }
+
     fn unwrapped_type_as_string(&self, cpp_name_map: &CppNameMap) -> Result<String, ConvertError> {
         type_to_cpp(&self.unwrapped_type, cpp_name_map)
     }
@@ -58,7 +79,10 @@ impl TypeConversionPolicy {
         // make RVO less effective
         Ok(match self.cpp_conversion {
             CppConversionType::None => {
-                if is_return {
+                // A reference must be passed on as it is: moving from a
+                // `T&` would give something which can't bind to another
+                // `T&`, e.g. when C++ passes it on to a Rust subclass.
+                if is_return || self.is_reference_or_pointer() {
                     var_name.to_string()
                 } else {
                     format!("std::move({})", var_name)
@@ -67,11 +91,24 @@ impl TypeConversionPolicy {
             CppConversionType::FromUniquePtrToValue | CppConversionType::FromPtrToMove => {
                 format!("std::move(*{})", var_name)
             }
-            CppConversionType::FromValueToUniquePtr => format!(
-                "std::make_unique<{}>({})",
-                self.unconverted_type(cpp_name_map)?,
-                var_name
-            ),
+            // Rather than std::make_unique, which would have to move
+            // the value into place, use new directly. If the value is
+            // a prvalue (e.g. the result of a constructor or function call)
+            // then, from C++17, it's constructed in place inside the
+            // unique_ptr. That means we can also return types which are
+            // neither copyable nor movable.
+            CppConversionType::FromValueToUniquePtr => {
+                let ty = self.unconverted_type(cpp_name_map)?;
+                format!("std::unique_ptr<{}>(new {}({}))", ty, ty, var_name)
+            }
+            CppConversionType::FromUniquePtrToOwningPtr => format!("{}.release()", var_name),
+            CppConversionType::FromOwningPtrToUniquePtr => {
+                format!(
+                    "{}({})",
+                    self.unique_ptr_wrapped_type(cpp_name_map)?,
+                    var_name
+                )
+            }
             CppConversionType::FromPtrToValue => {
                 let dereference = format!("*{}", var_name);
                 if is_return {
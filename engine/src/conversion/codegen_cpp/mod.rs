@@ -6,18 +6,25 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod batch;
+pub(crate) mod extern_rust;
 mod function_wrapper_cpp;
 mod new_and_delete_prelude;
+mod qt_signals;
+mod thin_call;
 pub(crate) mod type_to_cpp;
+pub(crate) mod vector_push;
 
 use crate::{
     conversion::analysis::fun::{function_wrapper::CppFunctionKind, FnAnalysis},
     types::{make_ident, QualifiedName},
-    CppCodegenOptions, CppFilePair,
+    CppCodegenOptions, CppFilePair, CppOutputLayout, ShimVisibility, WarningSuppression,
 };
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, LayoutOverride};
+use indoc::indoc;
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
+use syn::ext::IdentExt;
 use type_to_cpp::{original_name_map_from_apis, type_to_cpp, CppNameMap};
 
 use self::type_to_cpp::{
@@ -25,24 +32,60 @@ use self::type_to_cpp::{
 };
 
 use super::{
-    analysis::{
-        fun::{
-            function_wrapper::{CppFunction, CppFunctionBody},
-            FnPhase, PodAndDepAnalysis,
-        },
-        pod::PodAnalysis,
+    analysis::fun::{
+        function_wrapper::{CppFunction, CppFunctionBody},
+        FnKind, FnPhase, PodAndDepAnalysis,
     },
-    api::{Api, Provenance, SubclassName, TypeKind},
+    api::{Api, ApiName, Layout, Provenance, SubclassName, TypeKind},
     apivec::ApiVec,
+    size_report::ApiSizes,
     ConvertError,
 };
 
+/// Placeholder emitted after any code attributed to an original header
+/// using `#line`. It's replaced by a `#line` directive returning to the
+/// real location once we know the final line numbers.
+const LINE_RESTORE_MARKER: &str = "// autocxx: restore #line";
+
+/// Suppresses the warnings which our generated code is known to trigger:
+/// unused parameters (C4100) and calls to deprecated functions (C4996).
+const TARGETED_WARNING_PROLOGUE: &str = indoc! {"
+    #if defined(__GNUC__) || defined(__clang__)
+    #pragma GCC diagnostic push
+    #pragma GCC diagnostic ignored \"-Wunused-parameter\"
+    #pragma GCC diagnostic ignored \"-Wdeprecated-declarations\"
+    #elif defined(_MSC_VER)
+    #pragma warning(push)
+    #pragma warning(disable: 4100 4996)
+    #endif
+"};
+
+const TARGETED_WARNING_EPILOGUE: &str = indoc! {"
+    #if defined(__GNUC__) || defined(__clang__)
+    #pragma GCC diagnostic pop
+    #elif defined(_MSC_VER)
+    #pragma warning(pop)
+    #endif
+"};
+
+/// Defines the annotation used for [`ShimVisibility::Hidden`].
+const HIDDEN_VISIBILITY_PRELUDE: &str = indoc! {"
+    #ifndef AUTOCXX_HIDDEN
+    #if defined(__GNUC__) || defined(__clang__)
+    #define AUTOCXX_HIDDEN __attribute__((visibility(\"hidden\")))
+    #else
+    #define AUTOCXX_HIDDEN
+    #endif
+    #endif
+"};
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
 enum Header {
     System(&'static str),
     CxxH,
     CxxgenH,
     NewDeletePrelude,
+    ExpectedPrelude,
 }
 
 impl Header {
@@ -62,6 +105,7 @@ impl Header {
                 format!("#include \"{}cxxgen.h\"", prefix)
             }
             Header::NewDeletePrelude => new_and_delete_prelude::NEW_AND_DELETE_PRELUDE.to_string(),
+            Header::ExpectedPrelude => extern_rust::EXPECTED_PRELUDE.to_string(),
         }
     }
 
@@ -92,6 +136,13 @@ struct AdditionalFunction {
 /// need to be built and included in linking procedures.
 pub(crate) struct CppCodeGenerator<'a> {
     additional_functions: Vec<AdditionalFunction>,
+    /// Shim names which were shortened, and their original names.
+    shortened_names: Vec<(String, String)>,
+    /// Each shim generated for a function, and the C++ API it serves.
+    shim_origins: Vec<(String, String)>,
+    /// The first shim generated with each distinct signature and body,
+    /// keyed by its code with the name removed.
+    shims_by_content: HashMap<(Option<String>, Option<String>), String>,
     inclusions: String,
     original_name_map: CppNameMap,
     config: &'a IncludeCppConfig,
@@ -109,6 +160,7 @@ impl<'a> CppCodeGenerator<'a> {
         apis: &ApiVec<FnPhase>,
         config: &'a IncludeCppConfig,
         cpp_codegen_options: &CppCodegenOptions,
+        api_sizes: Option<&mut ApiSizes>,
     ) -> Result<Option<CppFilePair>, ConvertError> {
         let mut gen = CppCodeGenerator::new(
             inclusions,
@@ -118,7 +170,43 @@ impl<'a> CppCodeGenerator<'a> {
         );
         // The 'filter' on the following line is designed to ensure we don't accidentally
         // end up out of sync with needs_cpp_codegen
-        gen.add_needs(apis.iter().filter(|api| api.needs_cpp_codegen()))?;
+        gen.add_needs(
+            apis.iter().filter(|api| api.needs_cpp_codegen()),
+            protected_methods_by_subclass(apis),
+            api_sizes,
+        )?;
+        for instantiable_type in config.get_instantiable_types() {
+            gen.generate_instantiable_assertion(instantiable_type);
+        }
+        let extern_rust_ns = config.get_extern_rust_namespace();
+        for fun in config
+            .extern_rust_funs
+            .iter()
+            .filter(|fun| fun.returns_expected)
+        {
+            gen.additional_functions
+                .push(extern_rust::generate_expected_wrapper(fun, extern_rust_ns)?);
+        }
+        for fun in config.extern_rust_funs.iter().filter(|fun| fun.is_factory) {
+            gen.additional_functions.push(extern_rust::generate_factory(
+                fun,
+                extern_rust_ns,
+                &config.rust_types,
+            )?);
+        }
+        if let Some(class) = config.get_extern_rust_class() {
+            gen.additional_functions
+                .push(extern_rust::generate_extern_rust_class(
+                    class,
+                    extern_rust_ns,
+                    &config.extern_rust_funs,
+                    &config.rust_types,
+                )?);
+        }
+        for signal in config.get_qt_signals() {
+            gen.additional_functions
+                .push(qt_signals::generate_connect_helper(signal));
+        }
         Ok(gen.generate())
     }
 
@@ -130,6 +218,9 @@ impl<'a> CppCodeGenerator<'a> {
     ) -> Self {
         CppCodeGenerator {
             additional_functions: Vec::new(),
+            shortened_names: Vec::new(),
+            shim_origins: Vec::new(),
+            shims_by_content: HashMap::new(),
             inclusions,
             original_name_map,
             config,
@@ -141,36 +232,83 @@ impl<'a> CppCodeGenerator<'a> {
     fn add_needs<'b>(
         &mut self,
         apis: impl Iterator<Item = &'a Api<FnPhase>>,
+        mut protected_methods_by_subclass: HashMap<SubclassName, Vec<String>>,
+        mut api_sizes: Option<&mut ApiSizes>,
     ) -> Result<(), ConvertError> {
         let mut constructors_by_subclass: HashMap<SubclassName, Vec<&CppFunction>> = HashMap::new();
         let mut methods_by_subclass: HashMap<SubclassName, Vec<SubclassFunction>> = HashMap::new();
         let mut deferred_apis = Vec::new();
         for api in apis {
+            let functions_before = self.additional_functions.len();
             match &api {
                 Api::StringConstructor { .. } => self.generate_string_constructor(),
                 Api::Function {
                     analysis:
                         FnAnalysis {
-                            cpp_wrapper: Some(cpp_wrapper),
+                            cpp_wrapper,
+                            unshortened_cxxbridge_name,
                             ignore_reason: Ok(_),
                             externally_callable: true,
+                            kind,
+                            param_details,
+                            batch,
+                            thin_call,
+                            ret_type,
                             ..
                         },
                     fun,
+                    name,
                     ..
                 } => {
-                    if let Provenance::SynthesizedSubclassConstructor(details) = &fun.provenance {
-                        constructors_by_subclass
-                            .entry(details.subclass.clone())
-                            .or_default()
-                            .push(&details.cpp_impl);
+                    if let Some(cpp_wrapper) = cpp_wrapper {
+                        self.shim_origins.push((
+                            cpp_wrapper.wrapper_function_name.to_string(),
+                            shim_origin(kind, name),
+                        ));
+                        if let Some(unshortened_name) = unshortened_cxxbridge_name {
+                            self.shortened_names.push((
+                                cpp_wrapper.wrapper_function_name.to_string(),
+                                unshortened_name.clone(),
+                            ));
+                        }
+                        if let Provenance::SynthesizedSubclassConstructor(details) = &fun.provenance
+                        {
+                            constructors_by_subclass
+                                .entry(details.subclass.clone())
+                                .or_default()
+                                .push(&details.cpp_impl);
+                        }
+                        self.generate_cpp_function(cpp_wrapper)?;
+                    }
+                    if let Some(batch) = batch {
+                        let helper = batch::generate_batch_helper(
+                            batch,
+                            kind,
+                            param_details,
+                            &self.original_name_map,
+                        )?;
+                        self.additional_functions
+                            .push(self.with_shim_visibility(helper));
+                    }
+                    if let (Some(thin_call), FnKind::Method { impl_for, .. }) = (thin_call, kind) {
+                        self.additional_functions
+                            .push(thin_call::generate_thin_call_helper(
+                                thin_call,
+                                impl_for,
+                                ret_type,
+                                &self.original_name_map,
+                            )?);
                     }
-                    self.generate_cpp_function(cpp_wrapper)?
                 }
-                Api::ConcreteType { rs_definition, .. } => self.generate_typedef(
-                    api.name(),
-                    type_to_cpp(rs_definition, &self.original_name_map)?,
-                ),
+                Api::ConcreteType { rs_definition, .. } => {
+                    // Spell instantiations named using concrete! as the
+                    // user did.
+                    let definition = match self.config.get_concrete(&api.name().to_cpp_name()) {
+                        Some(cpp_type) => cpp_type.to_string(),
+                        None => type_to_cpp(rs_definition, &self.original_name_map)?,
+                    };
+                    self.generate_typedef(api.name(), definition)
+                }
                 Api::CType { typename, .. } => self.generate_ctype_typedef(typename),
                 Api::Subclass { .. } => deferred_apis.push(api),
                 Api::RustSubclassFn {
@@ -186,77 +324,214 @@ impl<'a> CppCodeGenerator<'a> {
                 }
                 Api::Struct {
                     name,
-                    analysis:
-                        PodAndDepAnalysis {
-                            pod:
-                                PodAnalysis {
-                                    kind: TypeKind::Pod,
-                                    ..
-                                },
-                            ..
-                        },
+                    analysis: PodAndDepAnalysis { pod, .. },
                     ..
                 } => {
-                    self.generate_pod_assertion(name.qualified_cpp_name());
+                    if matches!(pod.kind, TypeKind::Pod) {
+                        if self.config.is_force_pod_unsafe(&name.name.to_cpp_name()) {
+                            self.generate_relocatable_specialization(name.qualified_cpp_name());
+                        }
+                        self.generate_pod_assertion(name.qualified_cpp_name());
+                    }
+                    if let Some(layout_override) = pod.layout_override {
+                        self.generate_layout_assertion(name, layout_override);
+                    }
+                    if api.supports_vector_push() && !self.config.exclude_impls {
+                        let helper = vector_push::generate_vector_push_helper(
+                            &name.name,
+                            &name.qualified_cpp_name(),
+                        );
+                        self.additional_functions
+                            .push(self.with_shim_visibility(helper));
+                    }
+                }
+                Api::ExternCppType {
+                    name,
+                    layout,
+                    cpp_shims,
+                    ..
+                } => {
+                    if let Some(layout) = layout {
+                        self.generate_extern_cpp_type_assertion(name, layout);
+                    }
+                    if let Some(cpp_shims) = cpp_shims {
+                        self.additional_functions.push(AdditionalFunction {
+                            type_definition: None,
+                            declaration: Some(cpp_shims.clone()),
+                            definition: None,
+                            headers: Vec::new(),
+                            cpp_headers: Vec::new(),
+                        });
+                    }
                 }
                 _ => panic!("Should have filtered on needs_cpp_codegen"),
             }
+            if let Some(api_sizes) = api_sizes.as_deref_mut() {
+                api_sizes.record_cpp(api.name(), self.bytes_generated_since(functions_before));
+            }
         }
 
         for api in deferred_apis.into_iter() {
+            let api_name = api.name().clone();
+            let functions_before = self.additional_functions.len();
             match api {
                 Api::Subclass { name, superclass } => self.generate_subclass(
                     superclass,
                     name,
                     constructors_by_subclass.remove(name).unwrap_or_default(),
                     methods_by_subclass.remove(name).unwrap_or_default(),
+                    protected_methods_by_subclass
+                        .remove(name)
+                        .unwrap_or_default(),
                 )?,
                 _ => panic!("Unexpected deferred API"),
             }
+            if let Some(api_sizes) = api_sizes.as_deref_mut() {
+                api_sizes.record_cpp(&api_name, self.bytes_generated_since(functions_before));
+            }
         }
         Ok(())
     }
 
+    /// The amount of C++ in the functions added since there were this many.
+    fn bytes_generated_since(&self, functions_before: usize) -> usize {
+        self.additional_functions[functions_before..]
+            .iter()
+            .flat_map(|fun| [&fun.type_definition, &fun.declaration, &fun.definition])
+            .flatten()
+            .map(String::len)
+            .sum()
+    }
+
+    /// The annotation with which to declare shims, followed by a space if
+    /// there is one.
+    fn shim_visibility_annotation(&self) -> String {
+        match &self.cpp_codegen_options.shim_visibility {
+            ShimVisibility::Default => String::new(),
+            ShimVisibility::Hidden => "AUTOCXX_HIDDEN ".into(),
+            ShimVisibility::Annotation(annotation) => format!("{} ", annotation),
+        }
+    }
+
+    fn with_shim_visibility(&self, mut fun: AdditionalFunction) -> AdditionalFunction {
+        fun.declaration = fun
+            .declaration
+            .map(|declaration| format!("{}{}", self.shim_visibility_annotation(), declaration));
+        fun
+    }
+
+    /// A comment listing any shim names which were shortened, so that
+    /// the resulting symbols can be related back to the functions concerned.
+    fn shortened_names_table(&self) -> String {
+        if self.shortened_names.is_empty() {
+            return String::new();
+        }
+        let entries = self
+            .shortened_names
+            .iter()
+            .map(|(shortened, original)| format!("//   {}\n//     was: {}\n", shortened, original))
+            .join("");
+        format!("// Shortened function names:\n{}\n", entries)
+    }
+
     fn generate(&self) -> Option<CppFilePair> {
-        if self.additional_functions.is_empty() {
+        if self.additional_functions.is_empty() && self.config.get_prelude().is_empty() {
             None
         } else {
             let headers = self.collect_headers(|additional_need| &additional_need.headers);
             let cpp_headers = self.collect_headers(|additional_need| &additional_need.cpp_headers);
             let type_definitions = self.concat_additional_items(|x| x.type_definition.as_ref());
             let declarations = self.concat_additional_items(|x| x.declaration.as_ref());
+            let (warning_prologue, warning_epilogue) = self.warning_suppression();
+            let visibility_prelude =
+                if self.cpp_codegen_options.shim_visibility == ShimVisibility::Hidden {
+                    HIDDEN_VISIBILITY_PRELUDE
+                } else {
+                    ""
+                };
             let declarations = format!(
-                "#ifndef __AUTOCXXGEN_H__\n#define __AUTOCXXGEN_H__\n\n{}\n{}\n{}\n{}#endif // __AUTOCXXGEN_H__\n",
-                headers, self.inclusions, type_definitions, declarations
+                "#ifndef __AUTOCXXGEN_H__\n#define __AUTOCXXGEN_H__\n\n{}{}{}\n{}\n{}{}{}\n{}{}#endif // __AUTOCXXGEN_H__\n",
+                self.shortened_names_table(), self.config.get_prelude(), headers, self.inclusions, visibility_prelude, warning_prologue, type_definitions, declarations, warning_epilogue
             );
-            log::info!("Additional C++ decls:\n{}", declarations);
             let header_name = self
                 .cpp_codegen_options
                 .header_namer
                 .name_header(self.config.get_mod_name().to_string());
-            let implementation = if self
+            let declarations = restore_line_numbers(declarations, &header_name);
+            log::info!("Additional C++ decls:\n{}", declarations);
+            let mut implementations = if self
                 .additional_functions
                 .iter()
                 .any(|x| x.definition.is_some())
             {
-                let definitions = self.concat_additional_items(|x| x.definition.as_ref());
-                let definitions = format!(
-                    "#include \"{}\"\n{}\n{}",
-                    header_name, cpp_headers, definitions
-                );
-                log::info!("Additional C++ defs:\n{}", definitions);
-                Some(definitions.into_bytes())
+                self.lay_out_definitions()
+                    .into_iter()
+                    .map(|definitions| {
+                        let definitions = format!(
+                            "#include \"{}\"\n{}\n{}{}{}",
+                            header_name,
+                            cpp_headers,
+                            warning_prologue,
+                            definitions,
+                            warning_epilogue
+                        );
+                        log::info!("Additional C++ defs:\n{}", definitions);
+                        definitions.into_bytes()
+                    })
+                    .collect()
             } else {
-                None
-            };
+                Vec::new()
+            }
+            .into_iter();
             Some(CppFilePair {
                 header: declarations.into_bytes(),
-                implementation,
+                implementation: implementations.next(),
+                additional_implementations: implementations.collect(),
                 header_name,
+                shim_origins: self.shim_origins.clone(),
             })
         }
     }
 
+    /// C++ to go before and after the code we generate, to suppress any
+    /// warnings it would otherwise trigger.
+    fn warning_suppression(&self) -> (&str, &str) {
+        match &self.cpp_codegen_options.warning_suppression {
+            WarningSuppression::Targeted => (TARGETED_WARNING_PROLOGUE, TARGETED_WARNING_EPILOGUE),
+            WarningSuppression::Off => ("", ""),
+            WarningSuppression::Custom { prologue, epilogue } => (prologue, epilogue),
+        }
+    }
+
+    /// Returns the content of each file of function definitions, according
+    /// to the requested [`CppOutputLayout`]. When sharding, each definition
+    /// is assigned to a shard using a hash (64-bit FNV-1a) of its content,
+    /// which is specified here so that the assignment is stable across Rust
+    /// versions.
+    fn lay_out_definitions(&self) -> Vec<String> {
+        let shard_count = match self.cpp_codegen_options.output_layout {
+            CppOutputLayout::Unity => {
+                return vec![self.concat_additional_items(|x| x.definition.as_ref())]
+            }
+            CppOutputLayout::Sharded(shard_count) => shard_count.max(1),
+        };
+        let mut shards = vec![Vec::new(); shard_count];
+        for definition in self
+            .additional_functions
+            .iter()
+            .filter_map(|x| x.definition.as_ref())
+        {
+            let hash = definition.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+                (hash ^ b as u64).wrapping_mul(0x100000001b3)
+            });
+            shards[(hash % shard_count as u64) as usize].push(definition);
+        }
+        shards
+            .into_iter()
+            .map(|definitions| definitions.into_iter().join("\n") + "\n")
+            .collect()
+    }
+
     fn collect_headers<F>(&self, filter: F) -> String
     where
         F: Fn(&AdditionalFunction) -> &[Header],
@@ -304,6 +579,79 @@ impl<'a> CppCodeGenerator<'a> {
         })
     }
 
+    fn generate_relocatable_specialization(&mut self, name: String) {
+        // The user has used force_pod_unsafe! to promise that this type may
+        // be moved around with memcpy, even though C++ doesn't consider it
+        // trivial (typically because of a destructor which only logs).
+        // Tell cxx, and our own assertion, the same. Rust will still call
+        // the destructor from the generated Drop impl.
+        let type_definition = Some(format!(
+            "namespace rust {{ template<> struct IsRelocatable<{}> : std::true_type {{}}; }}",
+            name
+        ));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition,
+            declaration: None,
+            definition: None,
+            headers: vec![Header::CxxH],
+            cpp_headers: Vec::new(),
+        })
+    }
+
+    fn generate_layout_assertion(&mut self, name: &ApiName, layout_override: LayoutOverride) {
+        // The user has told us the packing or alignment of this type using
+        // layout!, and we've told Rust the same. Check that C++ agrees.
+        let cpp_name = name.qualified_cpp_name();
+        let directive_name = name.name.to_cpp_name();
+        let (condition, directive) = match layout_override {
+            LayoutOverride::Packed(n) => (
+                format!("alignof({}) <= {}", cpp_name, n),
+                format!("packed = {}", n),
+            ),
+            LayoutOverride::Align(n) => (
+                format!("alignof({}) == {}", cpp_name, n),
+                format!("align = {}", n),
+            ),
+        };
+        let declaration = Some(format!("static_assert({}, \"type {} was given layout!(\\\"{}\\\", {}) in autocxx, which doesn't match its alignment in C++\");", condition, cpp_name, directive_name, directive));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration,
+            definition: None,
+            headers: vec![Header::CxxH],
+            cpp_headers: Vec::new(),
+        })
+    }
+
+    fn generate_extern_cpp_type_assertion(&mut self, name: &ApiName, layout: &Layout) {
+        // The Rust type given using extern_cpp_type! is checked against the
+        // same layout in the generated Rust.
+        let cpp_name = name.qualified_cpp_name();
+        let declaration = Some(format!("static_assert(sizeof({}) == {} && alignof({}) == {}, \"type {} was given using extern_cpp_type! in autocxx, which expected it to have size {} and alignment {}\");", cpp_name, layout.size, cpp_name, layout.align, cpp_name, layout.size, layout.align));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration,
+            definition: None,
+            headers: vec![Header::CxxH],
+            cpp_headers: Vec::new(),
+        })
+    }
+
+    fn generate_instantiable_assertion(&mut self, name: &str) {
+        // The user has told us to generate constructors for this type even
+        // though we thought it was abstract. If they're wrong, the C++
+        // compiler would complain about our constructor wrappers, but
+        // confusingly; this explains what's going on.
+        let declaration = Some(format!("static_assert(!std::is_abstract<{}>::value, \"type {} was marked as instantiable! in autocxx, but is abstract\");", name, name));
+        self.additional_functions.push(AdditionalFunction {
+            type_definition: None,
+            declaration,
+            definition: None,
+            headers: vec![Header::System("type_traits")],
+            cpp_headers: Vec::new(),
+        })
+    }
+
     fn generate_string_constructor(&mut self) {
         let makestring_name = self.config.get_makestring_name();
         let declaration = Some(format!("inline std::unique_ptr<std::string> {}(::rust::Str str) {{ return std::make_unique<std::string>(std::string(str)); }}", makestring_name));
@@ -321,17 +669,46 @@ impl<'a> CppCodeGenerator<'a> {
     }
 
     fn generate_cpp_function(&mut self, details: &CppFunction) -> Result<(), ConvertError> {
-        self.additional_functions
-            .push(self.generate_cpp_function_inner(
-                details,
-                false,
-                ConversionDirection::RustCallsCpp,
-                false,
-                None,
-            )?);
+        let mut fun = self.generate_cpp_function_inner(
+            details,
+            false,
+            ConversionDirection::RustCallsCpp,
+            false,
+            None,
+        )?;
+        if details.qualification.is_none() {
+            self.deduplicate_shim(&details.wrapper_function_name.unraw().to_string(), &mut fun);
+        }
+        self.additional_functions.push(fun);
         Ok(())
     }
 
+    /// Shims for different APIs (e.g. for functions re-exported by `using`,
+    /// or for typedefs of the same template instantiation) may be identical
+    /// apart from their names. In
+    /// that case, rather than emitting the whole shim again, we emit a
+    /// reference to the first such shim under the new name.
+    fn deduplicate_shim(&mut self, name: &str, fun: &mut AdditionalFunction) {
+        let name_in_signature = format!(" {}(", name);
+        let without_name = |code: &Option<String>| {
+            code.as_ref()
+                .map(|code| code.replacen(&name_in_signature, " (", 1))
+        };
+        let content = (
+            without_name(&fun.declaration),
+            without_name(&fun.definition),
+        );
+        match self.shims_by_content.get(&content) {
+            Some(existing) => {
+                fun.declaration = Some(format!("constexpr auto& {} = {};", name, existing));
+                fun.definition = None;
+            }
+            None => {
+                self.shims_by_content.insert(content, name.to_string());
+            }
+        }
+    }
+
     fn generate_cpp_function_inner(
         &self,
         details: &CppFunction,
@@ -356,7 +733,7 @@ impl<'a> CppCodeGenerator<'a> {
             );
         let name = match force_name {
             Some(n) => n.to_string(),
-            None => details.wrapper_function_name.to_string(),
+            None => details.wrapper_function_name.unraw().to_string(),
         };
         let get_arg_name = |counter: usize| -> String {
             if is_a_method && counter == 0 {
@@ -411,7 +788,16 @@ impl<'a> CppCodeGenerator<'a> {
             CppFunctionKind::ConstMethod => " const",
             _ => "",
         };
-        let declaration = format!("{} {}({}){}", ret_type, name, args, constness);
+        // Only shims which cxx calls are affected; not the methods of
+        // subclasses.
+        let visibility = if details.qualification.is_none()
+            && matches!(conversion_direction, ConversionDirection::RustCallsCpp)
+        {
+            self.shim_visibility_annotation()
+        } else {
+            String::new()
+        };
+        let declaration = format!("{}{} {}({}){}", visibility, ret_type, name, args, constness);
         let qualification = if let Some(qualification) = &details.qualification {
             format!("{}::", qualification.to_cpp_name())
         } else {
@@ -437,7 +823,19 @@ impl<'a> CppCodeGenerator<'a> {
                 ),
             })
             .collect();
-        let mut arg_list = arg_list?.into_iter();
+        let mut arg_list = arg_list?;
+        if let CppFunctionBody::FunctionCallCastingArgs(_, _, casts) = &details.payload {
+            for (arg, cast) in arg_list.iter_mut().zip(casts) {
+                if let Some(ty) = cast {
+                    *arg = format!(
+                        "const_cast<{}&>({})",
+                        type_to_cpp(ty, &self.original_name_map)?,
+                        arg
+                    );
+                }
+            }
+        }
+        let mut arg_list = arg_list.into_iter();
         let receiver = if is_a_method { arg_list.next() } else { None };
         if matches!(&details.payload, CppFunctionBody::ConstructSuperclass(_)) {
             arg_list.next();
@@ -455,6 +853,11 @@ impl<'a> CppCodeGenerator<'a> {
             CppFunctionBody::MakeUnique | CppFunctionBody::Cast => {
                 (arg_list, "".to_string(), false)
             }
+            CppFunctionBody::FieldAccess(field) => (
+                format!("{}.{}", arg_list, field.unraw()),
+                "".to_string(),
+                false,
+            ),
             CppFunctionBody::PlacementNew(ns, id) => {
                 let ty_id = QualifiedName::new(ns, id.clone());
                 let ty_id = self.namespaced_name(&ty_id);
@@ -469,9 +872,10 @@ impl<'a> CppCodeGenerator<'a> {
                 let ty_id = final_ident_using_original_name_map(&ty_id, &self.original_name_map);
                 (format!("{}->~{}()", arg_list, ty_id), "".to_string(), false)
             }
-            CppFunctionBody::FunctionCall(ns, id) => match receiver {
+            CppFunctionBody::FunctionCall(ns, id)
+            | CppFunctionBody::FunctionCallCastingArgs(ns, id, _) => match receiver {
                 Some(receiver) => (
-                    format!("{}.{}({})", receiver, id, arg_list),
+                    format!("{}.{}({})", receiver, id.unraw(), arg_list),
                     "".to_string(),
                     false,
                 ),
@@ -479,7 +883,7 @@ impl<'a> CppCodeGenerator<'a> {
                     let underlying_function_call = ns
                         .into_iter()
                         .cloned()
-                        .chain(std::iter::once(id.to_string()))
+                        .chain(std::iter::once(id.unraw().to_string()))
                         .join("::");
                     (
                         format!("{}({})", underlying_function_call, arg_list),
@@ -488,11 +892,26 @@ impl<'a> CppCodeGenerator<'a> {
                     )
                 }
             },
+            CppFunctionBody::ConstCastMethodCall(ty, id) => (
+                format!(
+                    "const_cast<{}&>({}).{}({})",
+                    self.namespaced_name(ty),
+                    receiver.unwrap(),
+                    id.unraw(),
+                    arg_list
+                ),
+                "".to_string(),
+                false,
+            ),
             CppFunctionBody::StaticMethodCall(ns, ty_id, fn_id) => {
                 let underlying_function_call = ns
                     .into_iter()
                     .cloned()
-                    .chain([ty_id.to_string(), fn_id.to_string()].iter().cloned())
+                    .chain(
+                        [ty_id.to_string(), fn_id.unraw().to_string()]
+                            .iter()
+                            .cloned(),
+                    )
                     .join("::");
                 (
                     format!("{}({})", underlying_function_call, arg_list),
@@ -514,6 +933,9 @@ impl<'a> CppCodeGenerator<'a> {
                 "".to_string(),
                 true,
             ),
+            CppFunctionBody::RefCount(method) => {
+                (format!("{}->{}()", arg_list, method), "".to_string(), false)
+            }
         };
         if let Some(ret) = &details.return_conversion {
             underlying_function_call = format!(
@@ -558,10 +980,15 @@ impl<'a> CppCodeGenerator<'a> {
                 )),
             )
         } else {
-            (
-                Some(format!("inline {} {}", declaration, definition_after_sig)),
-                None,
-            )
+            let declaration = format!("inline {} {}", declaration, definition_after_sig);
+            let declaration = match &details.source_location {
+                Some(loc) if self.cpp_codegen_options.emit_line_directives => format!(
+                    "#line {} \"{}\"\n{}\n{}",
+                    loc.line, loc.file, declaration, LINE_RESTORE_MARKER
+                ),
+                _ => declaration,
+            };
+            (Some(declaration), None)
         };
         let mut headers = vec![Header::System("memory")];
         if need_allocators {
@@ -602,6 +1029,7 @@ impl<'a> CppCodeGenerator<'a> {
         subclass: &SubclassName,
         constructors: Vec<&CppFunction>,
         methods: Vec<SubclassFunction>,
+        protected_methods: Vec<String>,
     ) -> Result<(), ConvertError> {
         let holder = subclass.holder();
         self.additional_functions.push(AdditionalFunction {
@@ -648,15 +1076,27 @@ impl<'a> CppCodeGenerator<'a> {
                 self.additional_functions.push(super_fn_impl);
             }
         }
+        // Make any protected methods which we expose to Rust public.
+        for protected_method in protected_methods.into_iter().sorted().dedup() {
+            method_decls.push(format!(
+                "using {}::{};",
+                superclass.to_cpp_name(),
+                protected_method
+            ));
+        }
         // In future, for each superclass..
+        // The superclass may be in a namespace, so refer to it by its fully
+        // qualified name, though the cast methods are named by its final
+        // component only.
         let super_name = superclass.get_final_item();
+        let qualified_super_name = superclass.to_cpp_name();
         method_decls.push(format!(
             "const {}& As_{}() const {{ return *this; }}",
-            super_name, super_name,
+            qualified_super_name, super_name,
         ));
         method_decls.push(format!(
             "{}& As_{}_mut() {{ return *this; }}",
-            super_name, super_name
+            qualified_super_name, super_name
         ));
         // And now constructors
         let mut constructor_decls: Vec<String> = Vec::new();
@@ -674,16 +1114,20 @@ impl<'a> CppCodeGenerator<'a> {
         }
         self.additional_functions.push(AdditionalFunction {
             type_definition: Some(format!(
-                "class {} : {}\n{{\npublic:\n{}\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
+                "class {} : {}\n{{\npublic:\n{}\n~{}();\n{}\nvoid {}() const;\nprivate:rust::Box<{}> obs;\nvoid really_remove_ownership();\n\n}};",
                 subclass.cpp(),
                 superclass.to_cpp_name(),
                 constructor_decls.join("\n"),
+                subclass.cpp().get_final_item(),
                 method_decls.join("\n"),
                 subclass.cpp_remove_ownership(),
                 holder
             )),
             definition: Some(format!(
-                "void {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\nvoid {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                "{}::~{}() {{\n{}(*obs);\n}}\nvoid {}::{}() const {{\nconst_cast<{}*>(this)->really_remove_ownership();\n}}\nvoid {}::really_remove_ownership() {{\nauto new_obs = {}(std::move(obs));\nobs = std::move(new_obs);\n}}\n",
+                subclass.cpp(),
+                subclass.cpp().get_final_item(),
+                subclass.destroyed(),
                 subclass.cpp(),
                 subclass.cpp_remove_ownership(),
                 subclass.cpp(),
@@ -697,3 +1141,65 @@ impl<'a> CppCodeGenerator<'a> {
         Ok(())
     }
 }
+
+/// Finds the protected superclass methods which each subclass needs to
+/// make accessible to Rust. Such methods are called directly by cxx, so
+/// they may not otherwise require any C++ codegen.
+fn protected_methods_by_subclass(apis: &ApiVec<FnPhase>) -> HashMap<SubclassName, Vec<String>> {
+    let mut protected_methods: HashMap<SubclassName, Vec<String>> = HashMap::new();
+    for api in apis.iter() {
+        if let Api::Function {
+            fun,
+            name,
+            analysis:
+                FnAnalysis {
+                    ignore_reason: Ok(_),
+                    ..
+                },
+            ..
+        } = api
+        {
+            if let Provenance::SynthesizedSubclassProtectedAccess(subclass) = &fun.provenance {
+                protected_methods
+                    .entry(subclass.clone())
+                    .or_default()
+                    .push(name.cpp_name());
+            }
+        }
+    }
+    protected_methods
+}
+
+/// The C++ API for which a function's shim was generated, e.g. `Foo::bar`.
+fn shim_origin(kind: &FnKind, name: &ApiName) -> String {
+    match kind {
+        FnKind::Method { impl_for, .. } | FnKind::TraitMethod { impl_for, .. } => {
+            format!("{}::{}", impl_for.to_cpp_name(), name.cpp_name())
+        }
+        FnKind::Function => {
+            QualifiedName::new(name.name.get_namespace(), make_ident(name.cpp_name())).to_cpp_name()
+        }
+    }
+}
+
+/// Replace each [`LINE_RESTORE_MARKER`] with a `#line` directive pointing
+/// back to the real line within the file we're generating.
+fn restore_line_numbers(content: String, filename: &str) -> String {
+    if !content.contains(LINE_RESTORE_MARKER) {
+        return content;
+    }
+    content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            if line == LINE_RESTORE_MARKER {
+                // #line gives the number of the line which follows it;
+                // lines are numbered from 1.
+                format!("#line {} \"{}\"", idx + 2, filename)
+            } else {
+                line.to_string()
+            }
+        })
+        .join("\n")
+        + "\n"
+}
@@ -0,0 +1,62 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::QtSignal;
+use indoc::formatdoc;
+use itertools::Itertools;
+
+use super::{AdditionalFunction, Header};
+
+/// Generates the C++ side of the method which connects a Qt signal to a
+/// Rust closure. The closure is boxed within a Rust type, declared by cxx
+/// in `cxxgen.h`, which has a `call` method taking the signal's
+/// parameters. We connect the signal to a lambda which calls that. Qt
+/// requires the lambda to be copyable, so it shares ownership of the box;
+/// the closure is dropped once Qt has destroyed the last copy, i.e. when
+/// the connection is broken.
+pub(super) fn generate_connect_helper(signal: &QtSignal) -> AdditionalFunction {
+    let helper = signal.mangled_name();
+    let handler = format!("::{}_Handler", helper);
+    let class = format!("::{}", signal.class);
+    let signature = format!(
+        "bool {}({}& sender, rust::Box<{}> handler)",
+        helper, class, handler
+    );
+    let params = signal
+        .cpp_params
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| format!("{} arg{}", ty, i))
+        .join(", ");
+    let args = (0..signal.cpp_params.len())
+        .map(|i| format!("arg{}", i))
+        .join(", ");
+    let definition = formatdoc! {"
+        {} {{
+          auto shared_handler = std::make_shared<rust::Box<{}>>(std::move(handler));
+          return static_cast<bool>(QObject::connect(
+              &sender, static_cast<void ({}::*)({})>(&{}::{}),
+              [shared_handler]({}) {{ (*shared_handler)->call({}); }}));
+        }}",
+        signature,
+        handler,
+        class,
+        signal.cpp_params.join(", "),
+        class,
+        signal.name,
+        params,
+        args
+    };
+    AdditionalFunction {
+        type_definition: None,
+        declaration: Some(format!("struct {}_Handler;\n{};", helper, signature)),
+        definition: Some(definition),
+        headers: vec![Header::System("memory"), Header::CxxH],
+        cpp_headers: vec![Header::CxxgenH],
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use syn::ReturnType;
+
+use crate::{
+    conversion::{
+        analysis::fun::thin_call::ThinCallDetails, unwind::cpp_exception_spec, ConvertError,
+    },
+    types::QualifiedName,
+};
+
+use super::{
+    type_to_cpp::{namespaced_name_using_original_name_map, type_to_cpp, CppNameMap},
+    AdditionalFunction,
+};
+
+/// Generates the plain `extern "C"` function through which Rust calls a
+/// getter requested using `thin_call!`. Getters are usually defined in
+/// headers, so the compiler can inline the getter into this function,
+/// leaving a single call from Rust. The Rust side is generated by
+/// `codegen_rs::thin_call`.
+pub(super) fn generate_thin_call_helper(
+    thin_call: &ThinCallDetails,
+    impl_for: &QualifiedName,
+    ret_type: &ReturnType,
+    original_name_map: &CppNameMap,
+) -> Result<AdditionalFunction, ConvertError> {
+    let ret_type = match ret_type {
+        ReturnType::Type(_, ty) => type_to_cpp(ty, original_name_map)?,
+        ReturnType::Default => "void".to_string(),
+    };
+    let class = namespaced_name_using_original_name_map(impl_for, original_name_map);
+    let signature = format!(
+        "{} {}(const void* self){}",
+        ret_type,
+        thin_call.helper,
+        cpp_exception_spec(thin_call.exception_policy)
+    );
+    Ok(AdditionalFunction {
+        type_definition: None,
+        declaration: Some(format!("extern \"C\" {};", signature)),
+        definition: Some(format!(
+            "extern \"C\" {} {{\n  return static_cast<const {}*>(self)->{}();\n}}",
+            signature, class, thin_call.cpp_call
+        )),
+        headers: Vec::new(),
+        cpp_headers: Vec::new(),
+    })
+}
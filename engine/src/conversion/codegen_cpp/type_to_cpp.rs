@@ -8,6 +8,7 @@
 
 use crate::{
     conversion::{apivec::ApiVec, AnalysisPhase, ConvertError},
+    known_types::known_types,
     types::QualifiedName,
 };
 use itertools::Itertools;
@@ -135,3 +136,60 @@ fn get_mut_string(mutability: &Option<Token![mut]>) -> &'static str {
         Some(_) => "",
     }
 }
+
+/// Returns the C++ type which cxx uses for a type in the signature of a
+/// Rust function. Within the `cxx::bridge`, such types are referred to by
+/// the final segment of their path, so that's all we consider here.
+pub(crate) fn rust_type_to_cpp(ty: &Type) -> Result<String, ConvertError> {
+    match ty {
+        Type::Reference(r) => {
+            let constness = get_mut_string(&r.mutability);
+            match r.elem.as_ref() {
+                Type::Slice(slice) => Ok(format!(
+                    "rust::Slice<{}{}>",
+                    constness,
+                    rust_type_to_cpp(&slice.elem)?
+                )),
+                Type::Path(typ) if typ.path.is_ident("str") => Ok("rust::Str".into()),
+                elem => Ok(format!("{}{}&", constness, rust_type_to_cpp(elem)?)),
+            }
+        }
+        Type::Path(typ) if typ.qself.is_none() => {
+            let last_seg = typ.path.segments.last().unwrap();
+            let generic_args = match &last_seg.arguments {
+                syn::PathArguments::None => Vec::new(),
+                syn::PathArguments::AngleBracketed(ab) => ab
+                    .args
+                    .iter()
+                    .map(|arg| match arg {
+                        syn::GenericArgument::Type(ty) => rust_type_to_cpp(ty),
+                        _ => Err(unsupported_type(ty)),
+                    })
+                    .collect::<Result<_, _>>()?,
+                syn::PathArguments::Parenthesized(_) => return Err(unsupported_type(ty)),
+            };
+            let id = last_seg.ident.to_string();
+            let cpp_name = match id.as_str() {
+                "Vec" => "rust::Vec".into(),
+                "Box" => "rust::Box".into(),
+                "UniquePtr" => "std::unique_ptr".into(),
+                "usize" => "size_t".into(),
+                "isize" => "rust::isize".into(),
+                _ => known_types()
+                    .special_cpp_name(&QualifiedName::new_from_cpp_name(&id))
+                    .unwrap_or(id),
+            };
+            if generic_args.is_empty() {
+                Ok(cpp_name)
+            } else {
+                Ok(format!("{}<{}>", cpp_name, generic_args.join(", ")))
+            }
+        }
+        Type::Tuple(tup) if tup.elems.is_empty() => Ok("void".into()),
+        _ => Err(unsupported_type(ty)),
+    }
+}
+
+pub(crate) fn unsupported_type(ty: &Type) -> ConvertError {
+    ConvertError::UnsupportedType(ty.to_token_stream().to_string())
+}
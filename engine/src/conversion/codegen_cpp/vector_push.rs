@@ -0,0 +1,42 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use itertools::Itertools;
+
+use crate::types::QualifiedName;
+
+use super::{AdditionalFunction, Header};
+
+/// The name of the helper which pushes a value of the given type onto a
+/// `std::vector` by moving it.
+pub(crate) fn vector_push_helper_name(name: &QualifiedName) -> String {
+    format!("{}_autocxx_vector_push", name.segment_iter().join("_"))
+}
+
+/// Generates a helper which moves a value onto the end of a `std::vector`,
+/// so that movable types which aren't trivial can be pushed onto a
+/// `CxxVector` from Rust. The Rust side is generated by
+/// `codegen_rs::vector_push`.
+pub(super) fn generate_vector_push_helper(
+    name: &QualifiedName,
+    cpp_name: &str,
+) -> AdditionalFunction {
+    let declaration = format!(
+        "inline void {}(std::vector<{}>& vec, {}& value) {{ vec.push_back(std::move(value)); }}",
+        vector_push_helper_name(name),
+        cpp_name,
+        cpp_name
+    );
+    AdditionalFunction {
+        type_definition: None,
+        declaration: Some(declaration),
+        definition: None,
+        headers: vec![Header::System("vector"), Header::System("utility")],
+        cpp_headers: Vec::new(),
+    }
+}
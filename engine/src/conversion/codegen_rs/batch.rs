@@ -0,0 +1,124 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{parse::Parser, parse_quote, Field};
+
+use crate::conversion::{
+    analysis::fun::{batch::BatchDetails, ArgumentAnalysis, FnKind, ReceiverMutability},
+    api::UnsafetyNeeded,
+};
+
+use super::{RsCodegenResult, Use};
+
+/// Generates the Rust side of a batched function, requested using `batch!`:
+/// a `#[repr(C)]` struct holding the arguments of each call, and a function
+/// taking a slice of them. The slice is passed to C++ as bytes, since cxx
+/// doesn't know about the struct; the C++ side, generated by
+/// `codegen_cpp::batch`, declares a struct with the same layout.
+pub(super) fn generate_batch(
+    batch: &BatchDetails,
+    kind: &FnKind,
+    param_details: &[ArgumentAnalysis],
+    unsafety: &UnsafetyNeeded,
+) -> RsCodegenResult {
+    let BatchDetails {
+        rust_name,
+        args_struct,
+        helper,
+        ..
+    } = batch;
+    let mut receiver = None;
+    let mut fields: Vec<Field> = Vec::new();
+    for pd in param_details {
+        match &pd.self_type {
+            Some((_, mutability)) => receiver = Some(mutability),
+            None => {
+                let name = &pd.name;
+                let ty = &pd.conversion.unwrapped_type;
+                fields.push(
+                    Field::parse_named
+                        .parse2(quote! { pub #name: #ty })
+                        .unwrap(),
+                );
+            }
+        }
+    }
+    let (this_param, this_bridge_param, this_arg) = match (kind, receiver) {
+        (FnKind::Method { impl_for, .. }, Some(mutability)) => {
+            let id = impl_for.get_final_ident();
+            match mutability {
+                ReceiverMutability::Const => {
+                    (quote! { &self, }, quote! { this: &#id, }, quote! { self, })
+                }
+                ReceiverMutability::Mutable => (
+                    quote! { self: ::std::pin::Pin<&mut Self>, },
+                    quote! { this: ::std::pin::Pin<&mut #id>, },
+                    quote! { self, },
+                ),
+            }
+        }
+        _ => (quote! {}, quote! {}, quote! {}),
+    };
+    let wrapper_unsafety = unsafety.wrapper_token();
+    let bridge_unsafety = unsafety.bridge_token();
+    let mut call = quote! {
+        cxxbridge::#helper(#this_arg bytes)
+    };
+    if matches!(unsafety, UnsafetyNeeded::JustBridge) {
+        call = quote! { unsafe { #call } };
+    }
+    let body = quote! {
+        // SAFETY: the structs are `#[repr(C)]` and contain only numbers
+        // and bools, so they may be viewed as bytes.
+        let bytes = unsafe {
+            ::std::slice::from_raw_parts(
+                args.as_ptr() as *const u8,
+                ::std::mem::size_of_val(args),
+            )
+        };
+        #call
+    };
+    let mut bindgen_mod_items = vec![parse_quote! {
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        #[allow(non_camel_case_types)]
+        pub struct #args_struct {
+            #(#fields,)*
+        }
+    }];
+    bindgen_mod_items.push(match kind {
+        FnKind::Method { impl_for, .. } => {
+            let id = impl_for.get_final_ident();
+            parse_quote! {
+                impl #id {
+                    pub #wrapper_unsafety fn #rust_name(#this_param args: &[#args_struct]) {
+                        #body
+                    }
+                }
+            }
+        }
+        _ => parse_quote! {
+            pub #wrapper_unsafety fn #rust_name(args: &[#args_struct]) {
+                #body
+            }
+        },
+    });
+    let mut materializations = vec![Use::SpecificNameFromBindgen(args_struct.clone())];
+    if matches!(kind, FnKind::Function) {
+        materializations.push(Use::SpecificNameFromBindgen(rust_name.clone()));
+    }
+    RsCodegenResult {
+        extern_c_mod_items: vec![parse_quote! {
+            #bridge_unsafety fn #helper(#this_bridge_param args: &[u8]);
+        }],
+        bindgen_mod_items,
+        materializations,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{
+    ext::IdentExt, parse_quote, FnArg, GenericArgument, Item, PathArguments, ReturnType, Type,
+};
+
+use crate::{
+    conversion::{
+        analysis::fun::ReceiverMutability,
+        api::{SubclassName, SuperclassMethod},
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::RsCodeGenerator;
+
+/// Generates the items requested using `channel_observer!`, which let
+/// Rust code receive the callbacks made to a C++ observer interface as
+/// messages on a `std::sync::mpsc` channel. These are:
+/// * a `<superclass>Event` enum, with a variant per callback;
+/// * a `<superclass>EventSender` type, which implements the
+///   `<subclass>_methods` trait of a generic subclass by sending each
+///   callback's arguments down the channel;
+/// * `<subclass>::channel()`, to create the whole lot at once.
+///
+/// Only callbacks which return nothing, and whose arguments don't borrow
+/// anything, can be forwarded. Of the others, those which aren't pure
+/// virtual keep calling the superclass implementation, and those which
+/// are panic if they're ever called.
+pub(super) fn generate_channel_observer(
+    sub: &SubclassName,
+    superclass: &QualifiedName,
+    methods: Option<&Vec<SuperclassMethod>>,
+) -> Vec<Item> {
+    let id = sub.id();
+    let cpp_id = sub.cpp().get_final_ident();
+    let trait_name = sub.generic_methods_trait();
+    let event_name = make_ident(format!("{}Event", superclass.get_final_item()));
+    let sender_name = make_ident(format!("{}EventSender", superclass.get_final_item()));
+    let mut variants = Vec::new();
+    let mut method_impls = Vec::new();
+    for method in methods.into_iter().flatten() {
+        let method_id = &method.name;
+        let param_tys: Vec<Type> = method
+            .params
+            .iter()
+            .skip(1)
+            .filter_map(|arg| match arg {
+                FnArg::Typed(pt) => Some((*pt.ty).clone()),
+                FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let forwardable =
+            matches!(method.ret_type, ReturnType::Default) && param_tys.iter().all(is_owned);
+        if !forwardable && !method.is_pure_virtual {
+            continue;
+        }
+        let param_names: Vec<_> = RsCodeGenerator::args_from_sig(&method.params).collect();
+        let (receiver, peer_type) = match method.receiver_mutability {
+            ReceiverMutability::Const => (quote!(&self), quote!(&#cpp_id)),
+            ReceiverMutability::Mutable => {
+                (quote!(&mut self), quote!(::std::pin::Pin<&mut #cpp_id>))
+            }
+        };
+        let mut params = method.params.clone();
+        *(params.iter_mut().next().unwrap()) = parse_quote!(#receiver);
+        params.insert(1, parse_quote!(_peer: #peer_type));
+        let ret_type = &method.ret_type;
+        let unsafe_token = method.requires_unsafe.wrapper_token();
+        if forwardable {
+            let variant = make_ident(to_camel_case(&method_id.unraw().to_string()));
+            let variant_doc = format!("`{}` was called.", method_id);
+            let (fields, event) = if param_tys.is_empty() {
+                (None, quote!(#event_name::#variant))
+            } else {
+                (
+                    Some(quote!((#(#param_tys),*))),
+                    quote!(#event_name::#variant(#(#param_names),*)),
+                )
+            };
+            variants.push(quote! {
+                #[doc = #variant_doc]
+                #variant #fields
+            });
+            method_impls.push(quote! {
+                #unsafe_token fn #method_id(#params) {
+                    // Nobody's listening any more, which is their business.
+                    let _ = self.sender.send(#event);
+                }
+            });
+        } else {
+            let unforwardable_msg = format!(
+                "{}::{} can't be forwarded to a channel because it returns a value or borrows its arguments",
+                sender_name, method_id
+            );
+            method_impls.push(quote! {
+                #unsafe_token fn #method_id(#params) #ret_type {
+                    panic!(#unforwardable_msg)
+                }
+            });
+        }
+    }
+    let event_doc = format!(
+        "A call made by C++ to a virtual method of `{}`, sent by [`{}`].",
+        superclass.to_cpp_name(),
+        sender_name
+    );
+    let sender_doc = format!(
+        "An implementation of [`{}`] which sends each call it receives to a channel as a [`{}`].",
+        trait_name, event_name
+    );
+    vec![
+        parse_quote! {
+            #[doc = #event_doc]
+            pub enum #event_name {
+                #(#variants),*
+            }
+        },
+        parse_quote! {
+            #[doc = #sender_doc]
+            pub struct #sender_name {
+                sender: ::std::sync::mpsc::Sender<#event_name>,
+            }
+        },
+        parse_quote! {
+            impl #sender_name {
+                /// Creates an implementation which sends events using
+                /// `sender`, so that several observers can share a channel.
+                pub fn new(sender: ::std::sync::mpsc::Sender<#event_name>) -> Self {
+                    Self { sender }
+                }
+            }
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            #[allow(unused_variables)]
+            impl #trait_name for #sender_name {
+                #(#method_impls)*
+            }
+        },
+        parse_quote! {
+            impl #id {
+                /// Creates a subclass instance which sends each call it
+                /// receives to a new channel, along with the receiving end
+                /// of that channel. Use the [`autocxx::subclass::CppSubclass`]
+                /// constructors to create the C++ peer.
+                pub fn channel() -> (Self, ::std::sync::mpsc::Receiver<#event_name>) {
+                    let (sender, receiver) = ::std::sync::mpsc::channel();
+                    (Self::new(#sender_name::new(sender)), receiver)
+                }
+            }
+        },
+    ]
+}
+
+/// Whether a value of this type can outlive the call in which it was
+/// passed, i.e. whether it borrows nothing.
+fn is_owned(ty: &Type) -> bool {
+    match ty {
+        Type::Path(typ) => typ.path.segments.iter().all(|seg| match &seg.arguments {
+            PathArguments::AngleBracketed(args) => args.args.iter().all(|arg| match arg {
+                GenericArgument::Type(ty) => is_owned(ty),
+                GenericArgument::Lifetime(_) => false,
+                _ => true,
+            }),
+            PathArguments::Parenthesized(_) => false,
+            PathArguments::None => true,
+        }),
+        Type::Ptr(_) => true,
+        Type::Array(arr) => is_owned(&arr.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().all(is_owned),
+        Type::Paren(paren) => is_owned(&paren.elem),
+        Type::Group(group) => is_owned(&group.elem),
+        _ => false,
+    }
+}
+
+/// Turns a method name such as `on_goat_fed` or `onGoatFed` into a
+/// variant name such as `OnGoatFed`.
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
@@ -0,0 +1,70 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::RustEnum;
+use quote::quote;
+use syn::{parse_quote, Attribute, Item};
+
+/// Generates a cxx shared enum mirroring a fieldless Rust enum marked with
+/// `#[extern_rust_type]`, such that C++ gets a matching `enum class`.
+/// Returns the item for the cxx bridge, plus items for the output mod which
+/// expose the shared enum and convert between it and the original Rust
+/// enum. Converting from the shared enum is fallible, since C++ may pass
+/// any value of the underlying integer type.
+pub(super) fn generate_extern_rust_enum(
+    e: &RustEnum,
+    ns_attr: Option<Attribute>,
+) -> (Item, Vec<Item>) {
+    let def = &e.definition;
+    let id = &def.ident;
+    let path = &e.path;
+    let attrs = &def.attrs;
+    let variants = def.variants.iter().map(|v| {
+        let variant_attrs = &v.attrs;
+        let variant_id = &v.ident;
+        let discriminant = v.discriminant.as_ref().map(|(eq, expr)| quote!(#eq #expr));
+        quote! {
+            #(#variant_attrs)*
+            #variant_id #discriminant
+        }
+    });
+    let variant_ids: Vec<_> = def.variants.iter().map(|v| &v.ident).collect();
+    let bridge_item = parse_quote! {
+        #(#attrs)*
+        #ns_attr
+        enum #id {
+            #(#variants),*
+        }
+    };
+    let global_items = vec![
+        parse_quote! {
+            pub use cxxbridge::#id;
+        },
+        parse_quote! {
+            impl From<super::#path> for #id {
+                fn from(val: super::#path) -> Self {
+                    match val {
+                        #(super::#path::#variant_ids => Self::#variant_ids,)*
+                    }
+                }
+            }
+        },
+        parse_quote! {
+            impl ::std::convert::TryFrom<#id> for super::#path {
+                type Error = #id;
+                fn try_from(val: #id) -> Result<Self, Self::Error> {
+                    match val {
+                        #(#id::#variant_ids => Ok(Self::#variant_ids),)*
+                        _ => Err(val),
+                    }
+                }
+            }
+        },
+    ];
+    (bridge_item, global_items)
+}
@@ -0,0 +1,46 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use syn::{parse_quote, FnArg, Item, Signature};
+
+use crate::{
+    conversion::codegen_cpp::extern_rust::{boxing_wrapper_name, factory_product},
+    types::make_ident,
+};
+
+/// cxx can't return a Rust type by value to C++, so for a
+/// `#[extern_rust_function(factory)]` which does so, generates a function
+/// which returns it in a `Box` instead. This is what's declared to cxx,
+/// and what's called by the factory function generated by
+/// `codegen_cpp::extern_rust`. Returns `None` if the factory already
+/// returns a `Box`. `sig` is as declared within the cxx::bridge.
+pub(super) fn generate_boxing_wrapper(sig: &Signature) -> Option<(Item, Signature)> {
+    let product = match factory_product(&sig.output) {
+        Some((product, false)) => product.clone(),
+        _ => return None,
+    };
+    let fun = &sig.ident;
+    let mut wrapper_sig = sig.clone();
+    wrapper_sig.ident = make_ident(boxing_wrapper_name(fun));
+    wrapper_sig.output = parse_quote! { -> Box<#product> };
+    let mut args = Vec::new();
+    for (i, arg) in wrapper_sig.inputs.iter_mut().enumerate() {
+        if let FnArg::Typed(pt) = arg {
+            let arg_name = make_ident(format!("arg{}", i));
+            pt.pat = parse_quote! { #arg_name };
+            args.push(arg_name);
+        }
+    }
+    let wrapper = parse_quote! {
+        #[doc(hidden)]
+        #wrapper_sig {
+            Box::new(#fun(#(#args),*))
+        }
+    };
+    Some((wrapper, wrapper_sig))
+}
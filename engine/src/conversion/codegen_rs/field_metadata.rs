@@ -0,0 +1,56 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{ext::IdentExt, parse_quote, ItemImpl, ItemStruct};
+
+/// Generates an implementation of `autocxx::FieldMetadataProvider` for a
+/// POD struct, describing the name, offset and size of each of its fields.
+/// The struct must be POD, since only then does the layout of the Rust
+/// struct generated by bindgen precisely match the C++ layout.
+pub(crate) fn generate_field_metadata(s: &ItemStruct) -> Option<ItemImpl> {
+    if !s.generics.params.is_empty() {
+        return None;
+    }
+    let id = &s.ident;
+    let fields = s
+        .fields
+        .iter()
+        .filter_map(|f| f.ident.as_ref())
+        // Skip base classes and bindgen's padding fields.
+        .filter(|field_id| {
+            let field_name = field_id.to_string();
+            !field_name.starts_with("_base") && !field_name.starts_with("_bindgen")
+        })
+        .map(|field_id| {
+            let field_name = field_id.unraw().to_string();
+            quote! {
+                autocxx::FieldMetadata {
+                    name: #field_name,
+                    offset: ::std::ptr::addr_of!((*base).#field_id) as usize - base as usize,
+                    size: size_of_pointee(::std::ptr::addr_of!((*base).#field_id)),
+                }
+            }
+        });
+    Some(parse_quote! {
+        impl autocxx::FieldMetadataProvider for #id {
+            fn field_metadata() -> ::std::vec::Vec<autocxx::FieldMetadata> {
+                fn size_of_pointee<T>(_: *const T) -> usize {
+                    ::std::mem::size_of::<T>()
+                }
+                let instance = ::std::mem::MaybeUninit::<Self>::uninit();
+                let base = instance.as_ptr();
+                // Safety: we only calculate the addresses of fields,
+                // and never read from the uninitialized instance.
+                unsafe {
+                    ::std::vec![#(#fields),*]
+                }
+            }
+        }
+    })
+}
@@ -17,14 +17,16 @@ use syn::{
 };
 
 use super::{
+    batch::generate_batch,
+    thin_call::generate_thin_call,
     unqualify::{unqualify_params, unqualify_ret_type},
     ImplBlockDetails, RsCodegenResult, TraitImplBlockDetails, Use,
 };
 use crate::{
     conversion::{
         analysis::fun::{
-            ArgumentAnalysis, FnAnalysis, FnKind, MethodKind, RustRenameStrategy,
-            TraitMethodDetails,
+            function_wrapper::TypeConversionPolicy, ArgumentAnalysis, FnAnalysis, FnKind,
+            MethodKind, RustRenameStrategy, TraitMethodDetails,
         },
         api::UnsafetyNeeded,
         codegen_rs::lifetime::add_lifetime_to_all_params,
@@ -95,6 +97,20 @@ pub(super) fn gen_function(
     let vis = analysis.vis;
     let kind = analysis.kind;
     let doc_attr = fun.doc_attr;
+    let cfg = analysis.cfg;
+    let deprecated = analysis.deprecated;
+
+    if let (Some(thin_call), FnKind::Method { impl_for, .. }) = (&analysis.thin_call, &kind) {
+        return generate_thin_call(
+            thin_call,
+            impl_for,
+            rust_name,
+            &ret_type,
+            &analysis.requires_unsafe,
+            &doc_attr,
+            &cfg,
+        );
+    }
 
     let mut cpp_name_attr = Vec::new();
     let mut impl_entry = None;
@@ -106,11 +122,14 @@ pub(super) fn gen_function(
     };
     let fn_generator = FnGenerator {
         param_details: &param_details,
+        ret_conversion: &analysis.ret_conversion,
         cxxbridge_name: &cxxbridge_name,
         rust_name,
         unsafety: &analysis.requires_unsafe,
         always_unsafe_due_to_trait_definition,
         doc_attr: &doc_attr,
+        cfg: &cfg,
+        deprecated: &deprecated,
     };
     // In rare occasions, we might need to give an explicit lifetime.
     let (lifetime_tokens, params, ret_type) =
@@ -120,11 +139,15 @@ pub(super) fn gen_function(
         match kind {
             FnKind::Method {
                 ref impl_for,
-                method_kind: MethodKind::Constructor { .. },
+                method_kind: MethodKind::Constructor { by_value, .. },
                 ..
             } => {
                 // Constructor.
-                impl_entry = Some(fn_generator.generate_constructor_impl(impl_for));
+                impl_entry = Some(if by_value {
+                    fn_generator.generate_by_value_constructor_impl(impl_for)
+                } else {
+                    fn_generator.generate_constructor_impl(impl_for)
+                });
             }
             FnKind::Method {
                 ref impl_for,
@@ -151,6 +174,25 @@ pub(super) fn gen_function(
         }
     }
 
+    if let Some((impl_for, associated_name)) = &analysis.associated_fn {
+        // Requested using `associate_fn!`. The type may be in another
+        // namespace, so refer to it by its full path.
+        let associated_name = associated_name.to_string();
+        let generator = FnGenerator {
+            rust_name: &associated_name,
+            ..fn_generator
+        };
+        let item = generator
+            .generate_method_impl(false, impl_for, &ret_type)
+            .item;
+        let ty = impl_for.to_type_path();
+        bindgen_mod_items.push(parse_quote! {
+            impl #ty {
+                #item
+            }
+        });
+    }
+
     let materialization = match kind {
         FnKind::Method { .. } | FnKind::TraitMethod { .. } => None,
         FnKind::Function => match analysis.rust_rename_strategy {
@@ -195,31 +237,53 @@ pub(super) fn gen_function(
     };
     // At last, actually generate the cxx::bridge entry.
     let bridge_unsafety = analysis.requires_unsafe.bridge_token();
+    // If there's a Rust wrapper, that's what gets marked deprecated: otherwise
+    // cxx passes the attribute through to the function it generates.
+    let bridge_deprecated = if analysis.rust_wrapper_needed {
+        None
+    } else {
+        deprecated.as_ref()
+    };
     let extern_c_mod_item = ForeignItem::Fn(parse_quote!(
         #(#namespace_attr)*
         #(#cpp_name_attr)*
         #doc_attr
+        #bridge_deprecated
         #vis #bridge_unsafety fn #cxxbridge_name #lifetime_tokens ( #params ) #ret_type;
     ));
-    RsCodegenResult {
+    let mut result = RsCodegenResult {
         extern_c_mod_items: vec![extern_c_mod_item],
         bindgen_mod_items,
         impl_entry,
         trait_impl_entry,
         materializations: materialization.into_iter().collect(),
         ..Default::default()
+    };
+    if let Some(batch) = analysis.batch {
+        let mut batch = generate_batch(&batch, &kind, &param_details, &analysis.requires_unsafe);
+        result
+            .extern_c_mod_items
+            .append(&mut batch.extern_c_mod_items);
+        result
+            .bindgen_mod_items
+            .append(&mut batch.bindgen_mod_items);
+        result.materializations.append(&mut batch.materializations);
     }
+    result
 }
 
 /// Knows how to generate a given function.
 #[derive(Clone)]
 struct FnGenerator<'a> {
     param_details: &'a [ArgumentAnalysis],
+    ret_conversion: &'a Option<TypeConversionPolicy>,
     cxxbridge_name: &'a Ident,
     rust_name: &'a str,
     unsafety: &'a UnsafetyNeeded,
     always_unsafe_due_to_trait_definition: bool,
     doc_attr: &'a Option<Attribute>,
+    cfg: &'a Option<Attribute>,
+    deprecated: &'a Option<Attribute>,
 }
 
 impl<'a> FnGenerator<'a> {
@@ -246,7 +310,7 @@ impl<'a> FnGenerator<'a> {
                 .conversion
                 .rust_conversion(wrapper_arg_name, wrap_unsafe_calls);
             arg_list.push(actual_arg);
-            local_variables.extend(local_variable.into_iter());
+            local_variables.extend(local_variable);
         }
         (wrapper_params, local_variables, arg_list)
     }
@@ -261,16 +325,21 @@ impl<'a> FnGenerator<'a> {
         let (wrapper_params, local_variables, arg_list) = self.generate_arg_lists(avoid_self);
         let (lifetime_tokens, wrapper_params, ret_type) =
             add_explicit_lifetime_if_necessary(self.param_details, wrapper_params, ret_type);
+        let ret_type = self.wrapper_ret_type(&ret_type);
         let rust_name = make_ident(self.rust_name);
         let unsafety = self.unsafety.wrapper_token();
         let doc_attr = self.doc_attr;
+        let cfg = self.cfg;
+        let deprecated = self.deprecated;
         let cxxbridge_name = self.cxxbridge_name;
-        let call_body = self.wrap_call_with_unsafe(quote! {
+        let call_body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         Box::new(ImplBlockDetails {
             item: ImplItem::Method(parse_quote! {
                 #doc_attr
+                #cfg
+                #deprecated
                 pub #unsafety fn #rust_name #lifetime_tokens ( #wrapper_params ) #ret_type {
                     #(#local_variables),*
                     #call_body
@@ -293,14 +362,15 @@ impl<'a> FnGenerator<'a> {
         }
         let (lifetime_tokens, wrapper_params, ret_type) =
             add_explicit_lifetime_if_necessary(self.param_details, wrapper_params, ret_type);
+        let ret_type = self.wrapper_ret_type(&ret_type);
         let doc_attr = self.doc_attr;
         let unsafety = self.unsafety.wrapper_token();
         let cxxbridge_name = self.cxxbridge_name;
         let key = details.trt.clone();
         let method_name = &details.method_name;
-        let call_body = self.wrap_call_with_unsafe(quote! {
+        let call_body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         let item = parse_quote! {
             #doc_attr
             #unsafety fn #method_name #lifetime_tokens ( #wrapper_params ) #ret_type {
@@ -311,6 +381,20 @@ impl<'a> FnGenerator<'a> {
         Box::new(TraitImplBlockDetails { item, key })
     }
 
+    fn wrapper_ret_type(&self, ret_type: &ReturnType) -> ReturnType {
+        match self.ret_conversion {
+            Some(conversion) => conversion.rust_wrapper_return_type(ret_type),
+            None => ret_type.clone(),
+        }
+    }
+
+    fn convert_return(&self, call: TokenStream) -> TokenStream {
+        match self.ret_conversion {
+            Some(conversion) => conversion.rust_return_conversion(call),
+            None => call,
+        }
+    }
+
     fn should_wrap_unsafe_calls(&self) -> bool {
         matches!(self.unsafety, UnsafetyNeeded::JustBridge)
             || self.always_unsafe_due_to_trait_definition
@@ -338,7 +422,7 @@ impl<'a> FnGenerator<'a> {
         let mut wrapper_params: Punctuated<FnArg, Comma> =
             wrapper_params.into_iter().skip(1).collect();
         let ptr_arg_name = &arg_list[0];
-        let rust_name = make_ident(&self.rust_name);
+        let rust_name = make_ident(self.rust_name);
         let any_references = self.param_details.iter().any(|pd| pd.was_reference);
         let (lifetime_param, lifetime_addition) = if any_references {
             add_lifetime_to_all_params(&mut wrapper_params);
@@ -356,10 +440,14 @@ impl<'a> FnGenerator<'a> {
         };
         let body = self.wrap_call_with_unsafe(body);
         let doc_attr = self.doc_attr;
+        let cfg = self.cfg;
+        let deprecated = self.deprecated;
         let unsafety = self.unsafety.wrapper_token();
         Box::new(ImplBlockDetails {
             item: ImplItem::Method(parse_quote! {
                 #doc_attr
+                #cfg
+                #deprecated
                 pub #unsafety fn #rust_name #lifetime_param ( #wrapper_params ) -> impl autocxx::moveit::new::New<Output=Self> #lifetime_addition {
                     #body
                 }
@@ -368,18 +456,58 @@ impl<'a> FnGenerator<'a> {
         })
     }
 
+    /// Generate a 'impl Type { methods-go-here }' item which is a constructor
+    /// returning the new object by value, for trivially relocatable types.
+    fn generate_by_value_constructor_impl(
+        &self,
+        impl_block_type_name: &QualifiedName,
+    ) -> Box<ImplBlockDetails> {
+        let (wrapper_params, local_variables, arg_list) = self.generate_arg_lists(true);
+        let wrapper_params: Punctuated<FnArg, Comma> = wrapper_params.into_iter().skip(1).collect();
+        let ptr_arg_name = &arg_list[0];
+        let rust_name = make_ident(self.rust_name);
+        let cxxbridge_name = self.cxxbridge_name;
+        let body = self.wrap_call_with_unsafe(quote! {
+            cxxbridge::#cxxbridge_name(#(#arg_list),* );
+            autocxx_space.assume_init()
+        });
+        let doc_attr = self.doc_attr;
+        let cfg = self.cfg;
+        let deprecated = self.deprecated;
+        let unsafety = self.unsafety.wrapper_token();
+        Box::new(ImplBlockDetails {
+            item: ImplItem::Method(parse_quote! {
+                #doc_attr
+                #cfg
+                #deprecated
+                pub #unsafety fn #rust_name ( #wrapper_params ) -> Self {
+                    #(#local_variables),*
+                    let mut autocxx_space = ::std::mem::MaybeUninit::<Self>::uninit();
+                    let #ptr_arg_name = autocxx_space.as_mut_ptr();
+                    #body
+                }
+            }),
+            ty: impl_block_type_name.get_final_ident(),
+        })
+    }
+
     /// Generate a function call wrapper
     fn generate_function_impl(&self, ret_type: &ReturnType) -> Item {
         let (wrapper_params, local_variables, arg_list) = self.generate_arg_lists(false);
         let rust_name = make_ident(self.rust_name);
         let doc_attr = self.doc_attr;
+        let cfg = self.cfg;
+        let deprecated = self.deprecated;
         let unsafety = self.unsafety.wrapper_token();
         let cxxbridge_name = self.cxxbridge_name;
-        let body = self.wrap_call_with_unsafe(quote! {
+        let ret_type = self.wrapper_ret_type(ret_type);
+        let body = self.wrap_call_with_unsafe(self.convert_return(quote! {
             cxxbridge::#cxxbridge_name ( #(#arg_list),* )
-        });
+        }));
         Item::Fn(parse_quote! {
             #doc_attr
+            #cfg
+            #deprecated
             pub #unsafety fn #rust_name ( #wrapper_params ) #ret_type {
                 #(#local_variables),*
                 #body
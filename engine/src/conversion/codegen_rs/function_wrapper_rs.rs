@@ -7,7 +7,7 @@
 // except according to those terms.
 
 use proc_macro2::TokenStream;
-use syn::{Pat, Type, TypePtr};
+use syn::{Pat, ReturnType, Type, TypePtr};
 
 use crate::{
     conversion::analysis::fun::function_wrapper::{RustConversionType, TypeConversionPolicy},
@@ -29,7 +29,7 @@ impl TypeConversionPolicy {
             RustConversionType::FromStr => parse_quote! { impl ToCppString },
             RustConversionType::FromPinMaybeUninitToPtr => {
                 let ty = match &self.unwrapped_type {
-                    Type::Ptr(TypePtr { elem, .. }) => &*elem,
+                    Type::Ptr(TypePtr { elem, .. }) => elem,
                     _ => panic!("Not a ptr"),
                 };
                 parse_quote! {
@@ -38,7 +38,7 @@ impl TypeConversionPolicy {
             }
             RustConversionType::FromPinMoveRefToPtr => {
                 let ty = match &self.unwrapped_type {
-                    Type::Ptr(TypePtr { elem, .. }) => &*elem,
+                    Type::Ptr(TypePtr { elem, .. }) => elem,
                     _ => panic!("Not a ptr"),
                 };
                 parse_quote! {
@@ -47,7 +47,7 @@ impl TypeConversionPolicy {
             }
             RustConversionType::FromTypeToPtr => {
                 let ty = match &self.unwrapped_type {
-                    Type::Ptr(TypePtr { elem, .. }) => &*elem,
+                    Type::Ptr(TypePtr { elem, .. }) => elem,
                     _ => panic!("Not a ptr"),
                 };
                 parse_quote! { &mut #ty }
@@ -56,6 +56,12 @@ impl TypeConversionPolicy {
                 let ty = &self.unwrapped_type;
                 parse_quote! { impl autocxx::ValueParam<#ty> }
             }
+            RustConversionType::FromCxxStringToStr
+            | RustConversionType::FromCxxStringToCow
+            | RustConversionType::FromUniquePtrToString
+            | RustConversionType::FromUniquePtrToStringLossy => {
+                panic!("Return type conversion used for a parameter")
+            }
         }
     }
 
@@ -134,6 +140,57 @@ impl TypeConversionPolicy {
                     },
                 )
             }
+            RustConversionType::FromCxxStringToStr
+            | RustConversionType::FromCxxStringToCow
+            | RustConversionType::FromUniquePtrToString
+            | RustConversionType::FromUniquePtrToStringLossy => {
+                panic!("Return type conversion used for a parameter")
+            }
+        }
+    }
+
+    /// The return type of the Rust wrapper function, given that of the
+    /// cxx::bridge function it calls, which may have been given an
+    /// explicit lifetime.
+    pub(super) fn rust_wrapper_return_type(&self, ret_type: &ReturnType) -> ReturnType {
+        let lifetime = match ret_type {
+            ReturnType::Type(_, ty) => match ty.as_ref() {
+                Type::Reference(tyr) => tyr.lifetime.clone(),
+                _ => None,
+            },
+            ReturnType::Default => None,
+        };
+        match self.rust_conversion {
+            RustConversionType::FromCxxStringToStr => parse_quote! { -> & #lifetime str },
+            RustConversionType::FromCxxStringToCow => {
+                let lifetime = lifetime.unwrap_or_else(|| parse_quote! { '_ });
+                parse_quote! { -> ::std::borrow::Cow< #lifetime, str> }
+            }
+            RustConversionType::FromUniquePtrToString
+            | RustConversionType::FromUniquePtrToStringLossy => {
+                parse_quote! { -> ::std::string::String }
+            }
+            _ => ret_type.clone(),
+        }
+    }
+
+    /// Converts the value returned by a call to the cxx::bridge function
+    /// into that returned by the Rust wrapper function.
+    pub(super) fn rust_return_conversion(&self, call: TokenStream) -> TokenStream {
+        match self.rust_conversion {
+            RustConversionType::FromCxxStringToStr => quote! {
+                #call .to_str().expect("C++ string was not valid UTF-8")
+            },
+            RustConversionType::FromCxxStringToCow => quote! {
+                #call .to_string_lossy()
+            },
+            RustConversionType::FromUniquePtrToString => quote! {
+                #call .to_str().expect("C++ string was not valid UTF-8").to_owned()
+            },
+            RustConversionType::FromUniquePtrToStringLossy => quote! {
+                #call .to_string_lossy().into_owned()
+            },
+            _ => call,
         }
     }
 }
@@ -0,0 +1,149 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{parse_quote, Item};
+
+use crate::{
+    conversion::{
+        analysis::fun::ReceiverMutability,
+        api::{SubclassName, SuperclassMethod},
+    },
+    types::{Namespace, QualifiedName},
+};
+
+use super::{has_destroyed_hook, RsCodeGenerator};
+
+/// Generates the Rust struct for a subclass requested using
+/// `subclass!(..., generic)`. Rather than being written by the user, this
+/// struct holds a boxed `<subclass>_methods` trait object and forwards each
+/// virtual method to it, so that any number of Rust types can implement the
+/// superclass whilst sharing one C++ subclass. Each method of the trait is
+/// given the C++ peer, so that implementations can call back into C++ (and
+/// non-pure methods default to calling the superclass implementation).
+pub(super) fn generate_generic_subclass(
+    sub: &SubclassName,
+    superclass: &QualifiedName,
+    methods: Option<&Vec<SuperclassMethod>>,
+) -> Vec<Item> {
+    let id = sub.id();
+    let cpp_id = sub.cpp().get_final_ident();
+    let trait_name = sub.generic_methods_trait();
+    let mut trait_items = Vec::new();
+    let mut forwarding_impls = Vec::new();
+    for method in methods.into_iter().flatten() {
+        let id = &method.name;
+        let param_names: Vec<_> = RsCodeGenerator::args_from_sig(&method.params).collect();
+        let (receiver, peer_type, peer_call) = match method.receiver_mutability {
+            ReceiverMutability::Const => {
+                (quote!(&self), quote!(&#cpp_id), quote!(self.cpp_peer.get()))
+            }
+            ReceiverMutability::Mutable => (
+                quote!(&mut self),
+                quote!(::std::pin::Pin<&mut #cpp_id>),
+                quote!(self.cpp_peer.pin_mut()),
+            ),
+        };
+        let mut params = method.params.clone();
+        *(params.iter_mut().next().unwrap()) = parse_quote!(#receiver);
+        let mut trait_params = params.clone();
+        trait_params.insert(1, parse_quote!(peer: #peer_type));
+        let ret_type = &method.ret_type;
+        let unsafe_token = method.requires_unsafe.wrapper_token();
+        if method.is_pure_virtual {
+            trait_items.push(quote! {
+                #unsafe_token fn #id(#trait_params) #ret_type;
+            });
+        } else {
+            let super_id = SubclassName::get_super_fn_name(&Namespace::new(), &id.to_string())
+                .get_final_ident();
+            trait_items.push(quote! {
+                #unsafe_token fn #id(#trait_params) #ret_type {
+                    peer.#super_id(#(#param_names),*)
+                }
+            });
+        }
+        forwarding_impls.push(quote! {
+            #unsafe_token fn #id(#params) #ret_type {
+                self.inner.#id(#peer_call, #(#param_names),*)
+            }
+        });
+    }
+    if has_destroyed_hook(methods) {
+        trait_items.push(quote! {
+            /// Called when the C++ peer is destroyed by C++. See
+            /// the `destroyed` method of the superclass's `_methods` trait.
+            fn destroyed(&mut self) {}
+        });
+        forwarding_impls.push(quote! {
+            fn destroyed(&mut self) {
+                self.inner.destroyed()
+            }
+        });
+    }
+    let trait_doc = format!(
+        "Implement this to provide an implementation of the C++ class `{}` which can be plugged into [`{}`].",
+        superclass.to_cpp_name(),
+        id
+    );
+    let struct_doc = format!(
+        "A Rust subclass of the C++ class `{}` which forwards each virtual method to any implementation of [`{}`].",
+        superclass.to_cpp_name(),
+        trait_name
+    );
+    let mut items: Vec<Item> = vec![
+        parse_quote! {
+            #[doc = #trait_doc]
+            #[allow(non_snake_case)]
+            #[allow(non_camel_case_types)]
+            pub trait #trait_name {
+                #(#trait_items)*
+            }
+        },
+        parse_quote! {
+            #[doc = #struct_doc]
+            pub struct #id {
+                cpp_peer: autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id>,
+                inner: Box<dyn #trait_name>,
+            }
+        },
+        parse_quote! {
+            impl #id {
+                /// Creates a new subclass instance which forwards to `inner`.
+                /// Use the [`autocxx::subclass::CppSubclass`] constructors to
+                /// create the C++ peer.
+                pub fn new(inner: impl #trait_name + 'static) -> Self {
+                    Self {
+                        cpp_peer: Default::default(),
+                        inner: Box::new(inner),
+                    }
+                }
+            }
+        },
+        parse_quote! {
+            impl autocxx::subclass::CppSubclass<#cpp_id> for #id {
+                fn peer_holder_mut(&mut self) -> &mut autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id> {
+                    &mut self.cpp_peer
+                }
+                fn peer_holder(&self) -> &autocxx::subclass::CppSubclassCppPeerHolder<#cpp_id> {
+                    &self.cpp_peer
+                }
+            }
+        },
+    ];
+    if methods.is_some() {
+        let methods_trait = SubclassName::get_methods_trait_name(superclass).to_type_path();
+        items.push(parse_quote! {
+            #[allow(non_snake_case)]
+            impl #methods_trait for #id {
+                #(#forwarding_impls)*
+            }
+        });
+    }
+    items
+}
@@ -0,0 +1,47 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::LayoutOverride;
+use proc_macro2::Literal;
+use syn::{parse_quote, ItemStruct};
+
+use crate::conversion::api::Layout;
+
+/// Replaces whatever `#[repr]` bindgen gave a POD struct with one
+/// matching the layout the user asserted using `layout!`.
+pub(super) fn apply_layout_override_to_pod(s: &mut ItemStruct, layout_override: LayoutOverride) {
+    s.attrs.retain(|attr| !attr.path.is_ident("repr"));
+    s.attrs.push(match layout_override {
+        LayoutOverride::Packed(n) => {
+            let n = Literal::usize_unsuffixed(n);
+            parse_quote! { #[repr(C, packed(#n))] }
+        }
+        LayoutOverride::Align(n) => {
+            let n = Literal::usize_unsuffixed(n);
+            parse_quote! { #[repr(C, align(#n))] }
+        }
+    });
+}
+
+/// Adjusts the layout of a non-POD type to match the layout the user
+/// asserted using `layout!`. Such types are opaque, so only their size
+/// and alignment matter.
+pub(super) fn apply_layout_override_to_non_pod(
+    layout: Layout,
+    layout_override: LayoutOverride,
+) -> Layout {
+    let align = match layout_override {
+        LayoutOverride::Packed(n) => layout.align.min(n),
+        LayoutOverride::Align(n) => n,
+    };
+    Layout {
+        size: layout.size,
+        align,
+        packed: false,
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::IncludeCppConfig;
+use syn::{parse_quote, Expr, Item};
+
+use crate::types::{make_ident, QualifiedName};
+
+/// Generates `initialize` and `shutdown` functions which call the C++
+/// functions listed in `init_function!` and `shutdown_function!`
+/// directives, if there are any.
+pub(super) fn generate_lifecycle_functions(config: &IncludeCppConfig) -> Vec<Item> {
+    if config.get_init_functions().is_empty() && config.get_shutdown_functions().is_empty() {
+        return Vec::new();
+    }
+    let init_calls = config
+        .get_init_functions()
+        .iter()
+        .map(String::as_str)
+        .map(call_function);
+    // Shut down in the reverse order, as C++ does for static destructors.
+    let shutdown_calls = config
+        .get_shutdown_functions()
+        .iter()
+        .rev()
+        .map(String::as_str)
+        .map(call_function);
+    vec![
+        parse_quote! {
+            /// Calls each of the C++ functions listed in `init_function!`
+            /// directives, in order. Only the first call has any effect.
+            pub fn initialize() {
+                static INITIALIZED: ::std::sync::Once = ::std::sync::Once::new();
+                INITIALIZED.call_once(|| {
+                    #[allow(unused_unsafe)]
+                    unsafe {
+                        #(#init_calls;)*
+                    }
+                });
+            }
+        },
+        parse_quote! {
+            /// Calls each of the C++ functions listed in `shutdown_function!`
+            /// directives, in the reverse order to that in which they were
+            /// listed. Only the first call has any effect.
+            pub fn shutdown() {
+                static SHUT_DOWN: ::std::sync::Once = ::std::sync::Once::new();
+                SHUT_DOWN.call_once(|| {
+                    #[allow(unused_unsafe)]
+                    unsafe {
+                        #(#shutdown_calls;)*
+                    }
+                });
+            }
+        },
+    ]
+}
+
+fn call_function(cpp_name: &str) -> Expr {
+    let name = QualifiedName::new_from_cpp_name(cpp_name);
+    let path = name.get_namespace().iter().map(make_ident);
+    let id = make_ident(name.get_final_item());
+    parse_quote! {
+        self:: #(#path::)* #id()
+    }
+}
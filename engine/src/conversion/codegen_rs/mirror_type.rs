@@ -0,0 +1,66 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::RustPath;
+use syn::{parse_quote, Item, ItemStruct};
+
+/// Generates `From` and `PartialEq` implementations connecting a POD struct
+/// and a Rust type with the same fields, requested using `mirror_type!`.
+/// The POD struct can only be built from the Rust type if bindgen gave
+/// it no hidden fields, i.e. base classes or padding.
+pub(crate) fn generate_mirror_type_impls(s: &ItemStruct, mirror: &RustPath) -> Vec<Item> {
+    if !s.generics.params.is_empty() {
+        return Vec::new();
+    }
+    let id = &s.ident;
+    let all_fields: Vec<_> = s.fields.iter().filter_map(|f| f.ident.as_ref()).collect();
+    let fields: Vec<_> = all_fields
+        .iter()
+        .filter(|field_id| {
+            let field_name = field_id.to_string();
+            !field_name.starts_with("_base") && !field_name.starts_with("_bindgen")
+        })
+        .collect();
+    let mut items: Vec<Item> = vec![
+        parse_quote! {
+            impl From<#id> for #mirror {
+                fn from(other: #id) -> Self {
+                    Self {
+                        #(#fields: other.#fields.into(),)*
+                    }
+                }
+            }
+        },
+        parse_quote! {
+            impl PartialEq<#mirror> for #id {
+                fn eq(&self, other: &#mirror) -> bool {
+                    true #(&& self.#fields == other.#fields)*
+                }
+            }
+        },
+        parse_quote! {
+            impl PartialEq<#id> for #mirror {
+                fn eq(&self, other: &#id) -> bool {
+                    other == self
+                }
+            }
+        },
+    ];
+    if fields.len() == all_fields.len() {
+        items.push(parse_quote! {
+            impl From<#mirror> for #id {
+                fn from(other: #mirror) -> Self {
+                    Self {
+                        #(#fields: other.#fields.into(),)*
+                    }
+                }
+            }
+        });
+    }
+    items
+}
@@ -0,0 +1,132 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{ext::IdentExt, parse_quote, FnArg, Ident, Item, Type};
+
+use crate::{
+    conversion::{
+        analysis::fun::ReceiverMutability,
+        api::{SubclassName, SuperclassMethod},
+    },
+    types::{make_ident, Namespace, QualifiedName},
+};
+
+use super::RsCodeGenerator;
+
+/// Generates a `Mock<superclass>` type which implements the
+/// `<superclass>_methods` trait (and the `<superclass>_supers` trait
+/// if there is one). Each call is recorded by name, and the behavior of
+/// each method can be programmed by `expect_<method>`. Nothing is
+/// generated on the C++ side.
+pub(super) fn generate_mock(
+    name: &QualifiedName,
+    methods: &[SuperclassMethod],
+    methods_trait: &Ident,
+    supers_trait: Option<&Ident>,
+) -> (Ident, Vec<Item>) {
+    let mock_name = make_ident(format!("Mock{}", name.get_final_item()));
+    let mut fields = Vec::new();
+    let mut expectations = Vec::new();
+    let mut method_impls = Vec::new();
+    let mut super_impls = Vec::new();
+    for method in methods {
+        let id = &method.name;
+        let id_str = id.unraw().to_string();
+        let handler = make_ident(format!("{}_handler", id_str));
+        let expect = make_ident(format!("expect_{}", id_str));
+        let param_tys: Vec<Type> = method
+            .params
+            .iter()
+            .skip(1)
+            .filter_map(|arg| match arg {
+                FnArg::Typed(pt) => Some((*pt.ty).clone()),
+                FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let param_names: Vec<_> = RsCodeGenerator::args_from_sig(&method.params).collect();
+        let mut params = method.params.clone();
+        *(params.iter_mut().next().unwrap()) = match method.receiver_mutability {
+            ReceiverMutability::Const => parse_quote!(&self),
+            ReceiverMutability::Mutable => parse_quote!(&mut self),
+        };
+        let ret_type = &method.ret_type;
+        let unsafe_token = method.requires_unsafe.wrapper_token();
+        let unprogrammed_msg = format!(
+            "{}::{} called but no behavior was set using {}::{}",
+            mock_name, id, mock_name, expect
+        );
+        fields.push(quote! {
+            #handler: ::std::cell::RefCell<Option<Box<dyn FnMut(#(#param_tys),*) #ret_type>>>
+        });
+        expectations.push(quote! {
+            pub fn #expect(&mut self, f: impl FnMut(#(#param_tys),*) #ret_type + 'static) -> &mut Self {
+                *self.#handler.get_mut() = Some(Box::new(f));
+                self
+            }
+        });
+        method_impls.push(quote! {
+            #unsafe_token fn #id(#params) #ret_type {
+                self.calls.borrow_mut().push(#id_str);
+                let mut handler = self.#handler.borrow_mut();
+                let handler = handler.as_mut().expect(#unprogrammed_msg);
+                handler(#(#param_names),*)
+            }
+        });
+        if !method.is_pure_virtual {
+            let super_id =
+                SubclassName::get_super_fn_name(&Namespace::new(), &id_str).get_final_ident();
+            let no_super_msg = format!(
+                "{}::{} called, but a mock has no C++ superclass implementation",
+                mock_name, super_id
+            );
+            super_impls.push(quote! {
+                #unsafe_token fn #super_id(#params) #ret_type {
+                    panic!(#no_super_msg)
+                }
+            });
+        }
+    }
+    let mut items: Vec<Item> = vec![
+        parse_quote! {
+            #[allow(non_snake_case)]
+            #[derive(Default)]
+            pub struct #mock_name {
+                calls: ::std::cell::RefCell<Vec<&'static str>>,
+                #(#fields),*
+            }
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            impl #mock_name {
+                /// The names of the methods which have been called on
+                /// this mock so far, in order.
+                pub fn calls(&self) -> Vec<&'static str> {
+                    self.calls.borrow().clone()
+                }
+                #(#expectations)*
+            }
+        },
+        parse_quote! {
+            #[allow(non_snake_case)]
+            impl #methods_trait for #mock_name {
+                #(#method_impls)*
+            }
+        },
+    ];
+    if let Some(supers_trait) = supers_trait {
+        items.push(parse_quote! {
+            #[allow(non_snake_case)]
+            #[allow(unused_variables)]
+            impl #supers_trait for #mock_name {
+                #(#super_impls)*
+            }
+        });
+    }
+    (mock_name, items)
+}
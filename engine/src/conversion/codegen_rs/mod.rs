@@ -6,29 +6,55 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod batch;
+mod channel_observer;
+mod extern_rust_enum;
+mod extern_rust_factory;
+mod field_metadata;
 mod fun_codegen;
 mod function_wrapper_rs;
+mod generic_subclass;
 mod impl_item_creator;
+mod layout_override;
+mod lifecycle;
 mod lifetime;
+mod mirror_type;
+mod mock;
+mod namespace_mapping;
 mod namespace_organizer;
+mod newtype_enum;
 mod non_pod_struct;
+mod qt_signals;
+mod raii_guard;
+mod thin_call;
+mod trait_impl;
 pub(crate) mod unqualify;
+mod vector_push;
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+};
 
-use autocxx_parser::IncludeCppConfig;
+use autocxx_parser::{IncludeCppConfig, Inclusion, RustPath};
 
 use itertools::Itertools;
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::{Literal, Span, TokenStream};
 use syn::{
     parse_quote, punctuated::Punctuated, token::Comma, Attribute, Expr, FnArg, ForeignItem,
-    ForeignItemFn, Ident, ImplItem, Item, ItemForeignMod, ItemMod, TraitItem,
+    ForeignItemFn, Ident, ImplItem, Item, ItemForeignMod, ItemImpl, ItemMod, ItemStruct,
+    PathArguments, ReturnType, Stmt, TraitItem, Type, TypePath,
 };
 
 use crate::{
     conversion::{
         codegen_rs::{
-            non_pod_struct::{make_non_pod, new_non_pod_struct},
+            field_metadata::generate_field_metadata,
+            mirror_type::generate_mirror_type_impls,
+            newtype_enum::enum_as_newtype,
+            non_pod_struct::{make_non_pod, new_non_pod_struct, new_opaque_field_blob},
+            raii_guard::generate_raii_guard,
+            trait_impl::generate_trait_impl,
             unqualify::{unqualify_params, unqualify_ret_type},
         },
         doc_attr::get_doc_attr,
@@ -36,25 +62,40 @@ use crate::{
     known_types::known_types,
     types::{make_ident, Namespace, QualifiedName},
 };
+use extern_rust_enum::generate_extern_rust_enum;
+use extern_rust_factory::generate_boxing_wrapper;
 use impl_item_creator::create_impl_items;
+use layout_override::{apply_layout_override_to_non_pod, apply_layout_override_to_pod};
+use lifecycle::generate_lifecycle_functions;
+use qt_signals::generate_connect_method;
+use vector_push::generate_vector_push;
 
 use self::{
     fun_codegen::gen_function,
+    namespace_mapping::{assign_output_namespaces, OutputItem},
     namespace_organizer::{HasNs, NamespaceEntries},
 };
 
 use super::{
-    analysis::fun::{FnPhase, ReceiverMutability},
+    analysis::{
+        fun::{FnKind, FnPhase, ReceiverMutability},
+        reserved_names::{choose_helper_name, MAKE_STRING},
+    },
     api::{AnalysisPhase, Api, SubclassName, TypeKind, TypedefKind},
 };
 use super::{
-    api::{Layout, Provenance, RustSubclassFnDetails, SuperclassMethod, TraitImplSignature},
+    api::{
+        Layout, Provenance, RustSubclassFnDetails, SourceLocation, SuperclassMethod,
+        TraitImplSignature,
+    },
     apivec::ApiVec,
     codegen_cpp::type_to_cpp::{
         namespaced_name_using_original_name_map, original_name_map_from_apis, CppNameMap,
     },
 };
-use super::{convert_error::ErrorContext, ConvertError};
+use super::{
+    c_header::generate_c_wrapper, convert_error::ErrorContext, size_report::ApiSizes, ConvertError,
+};
 use quote::{quote, ToTokens};
 
 /// An entry which needs to go into an `impl` block for a given type.
@@ -84,7 +125,7 @@ enum Use {
     Custom(Box<Item>),
 }
 
-fn get_string_items() -> Vec<Item> {
+fn get_string_items(make_string: &Ident) -> Vec<Item> {
     [
         Item::Trait(parse_quote! {
             pub trait ToCppString {
@@ -97,21 +138,21 @@ fn get_string_items() -> Vec<Item> {
         Item::Impl(parse_quote! {
             impl ToCppString for &str {
                 fn into_cpp(self) -> cxx::UniquePtr<cxx::CxxString> {
-                    make_string(self)
+                    #make_string(self)
                 }
             }
         }),
         Item::Impl(parse_quote! {
             impl ToCppString for String {
                 fn into_cpp(self) -> cxx::UniquePtr<cxx::CxxString> {
-                    make_string(&self)
+                    #make_string(&self)
                 }
             }
         }),
         Item::Impl(parse_quote! {
             impl ToCppString for &String {
                 fn into_cpp(self) -> cxx::UniquePtr<cxx::CxxString> {
-                    make_string(self)
+                    #make_string(self)
                 }
             }
         }),
@@ -126,25 +167,56 @@ fn get_string_items() -> Vec<Item> {
     .to_vec()
 }
 
+/// Names `make_string`, which is exposed at the root of the output mod,
+/// such that it doesn't clash with any C++ item exposed there.
+fn choose_make_string_name(config: &IncludeCppConfig, apis: &ApiVec<FnPhase>) -> Ident {
+    let root_names: HashSet<String> = apis
+        .iter()
+        .filter(|api| api.name().get_namespace().is_empty())
+        .filter_map(|api| match api {
+            Api::Function { analysis, .. } => {
+                matches!(analysis.kind, FnKind::Function).then(|| analysis.rust_name.clone())
+            }
+            Api::StringConstructor { .. } => None,
+            _ => Some(api.name().get_final_item().to_string()),
+        })
+        .collect();
+    make_ident(choose_helper_name(
+        config,
+        MAKE_STRING,
+        MAKE_STRING,
+        |name| root_names.contains(name),
+    ))
+}
+
 /// Type which handles generation of Rust code.
 /// In practice, much of the "generation" involves connecting together
 /// existing lumps of code within the Api structures.
 pub(crate) struct RsCodeGenerator<'a> {
-    include_list: &'a [String],
+    include_list: &'a [Inclusion],
     bindgen_mod: ItemMod,
     original_name_map: CppNameMap,
     config: &'a IncludeCppConfig,
     header_name: Option<String>,
+    /// Whether we're generating the string utilities, e.g. `ToCppString`.
+    string_utilities_generated: bool,
+    /// The name by which `make_string` is exposed in the output mod.
+    make_string_name: Ident,
+    /// Whether to generate `extern "C"` wrappers for Rust functions, to be
+    /// declared in a C header.
+    c_wrappers: bool,
 }
 
 impl<'a> RsCodeGenerator<'a> {
     /// Generate code for a set of APIs that was discovered during parsing.
     pub(crate) fn generate_rs_code(
         all_apis: ApiVec<FnPhase>,
-        include_list: &'a [String],
+        include_list: &'a [Inclusion],
         bindgen_mod: ItemMod,
         config: &'a IncludeCppConfig,
         header_name: Option<String>,
+        api_sizes: Option<&mut ApiSizes>,
+        c_wrappers: bool,
     ) -> Vec<Item> {
         let c = Self {
             include_list,
@@ -152,11 +224,20 @@ impl<'a> RsCodeGenerator<'a> {
             original_name_map: original_name_map_from_apis(&all_apis),
             config,
             header_name,
+            string_utilities_generated: all_apis
+                .iter()
+                .any(|api| matches!(api, Api::StringConstructor { .. })),
+            make_string_name: choose_make_string_name(config, &all_apis),
+            c_wrappers,
         };
-        c.rs_codegen(all_apis)
+        c.rs_codegen(all_apis, api_sizes)
     }
 
-    fn rs_codegen(mut self, all_apis: ApiVec<FnPhase>) -> Vec<Item> {
+    fn rs_codegen(
+        mut self,
+        all_apis: ApiVec<FnPhase>,
+        api_sizes: Option<&mut ApiSizes>,
+    ) -> Vec<Item> {
         // ... and now let's start to generate the output code.
         // First off, when we generate structs we may need to add some methods
         // if they're superclasses.
@@ -164,26 +245,43 @@ impl<'a> RsCodeGenerator<'a> {
         let subclasses_with_a_single_trivial_constructor =
             find_trivially_constructed_subclasses(&all_apis);
         // Now let's generate the Rust code.
-        let (rs_codegen_results_and_namespaces, additional_cpp_needs): (Vec<_>, Vec<_>) = all_apis
-            .into_iter()
-            .map(|api| {
-                let more_cpp_needed = api.needs_cpp_codegen();
-                let name = api.name().clone();
-                let gen = self.generate_rs_for_api(
-                    api,
-                    &methods_by_superclass,
-                    &subclasses_with_a_single_trivial_constructor,
-                );
-                ((name, gen), more_cpp_needed)
-            })
-            .unzip();
+        let (mut rs_codegen_results_and_namespaces, additional_cpp_needs): (Vec<_>, Vec<_>) =
+            all_apis
+                .into_iter()
+                .map(|api| {
+                    let more_cpp_needed = api.needs_cpp_codegen();
+                    let name = api.name().clone();
+                    let gen = self.generate_rs_for_api(
+                        api,
+                        &methods_by_superclass,
+                        &subclasses_with_a_single_trivial_constructor,
+                    );
+                    ((name, gen), more_cpp_needed)
+                })
+                .unzip();
+        if let Some(api_sizes) = api_sizes {
+            for (name, gen) in &rs_codegen_results_and_namespaces {
+                api_sizes.record_rs(name, gen.items_for_size_report());
+            }
+        }
         // First, the hierarchy of mods containing lots of 'use' statements
         // which is the final API exposed as 'ffi'.
         let mut use_statements =
-            Self::generate_final_use_statements(&rs_codegen_results_and_namespaces);
-        // And work out what we need for the bindgen mod.
-        let bindgen_root_items =
-            self.generate_final_bindgen_mods(&rs_codegen_results_and_namespaces);
+            self.generate_final_use_statements(&rs_codegen_results_and_namespaces);
+        // And work out what we need for the bindgen mod. The items are moved
+        // out of the results rather than cloned, since they're most of the
+        // generated code.
+        let bindgen_mod_entries: Vec<_> = rs_codegen_results_and_namespaces
+            .iter_mut()
+            .map(|(name, gen)| BindgenModEntry {
+                name: name.clone(),
+                items: Cell::new(std::mem::take(&mut gen.bindgen_mod_items)),
+                impl_entry: Cell::new(gen.impl_entry.take()),
+                trait_impl_entry: Cell::new(gen.trait_impl_entry.take()),
+            })
+            .collect();
+        let bindgen_root_items = self.generate_final_bindgen_mods(&bindgen_mod_entries);
+        drop(bindgen_mod_entries);
         // Both of the above ('use' hierarchy and bindgen mod) are organized into
         // sub-mods by namespace. From here on, things are flat.
         let (_, rs_codegen_results): (Vec<_>, Vec<_>) =
@@ -210,12 +308,28 @@ impl<'a> RsCodeGenerator<'a> {
         let mut extern_c_mod_items: Vec<ForeignItem> =
             extern_c_mod_items.into_iter().flatten().collect();
         // The same for extern "Rust"
-        let mut extern_rust_mod_items = extern_rust_mod_items.into_iter().flatten().collect();
+        let mut extern_rust_mod_items: Vec<ForeignItem> =
+            extern_rust_mod_items.into_iter().flatten().collect();
         // And a list of global items to include at the top level.
         let mut all_items: Vec<Item> = all_items.into_iter().flatten().collect();
         // And finally any C++ we need to generate. And by "we" I mean autocxx not cxx.
-        let has_additional_cpp_needs = additional_cpp_needs.into_iter().any(std::convert::identity);
+        let has_additional_cpp_needs = additional_cpp_needs.into_iter().any(std::convert::identity)
+            || !self.config.get_qt_signals().is_empty();
         extern_c_mod_items.extend(self.build_include_foreign_items(has_additional_cpp_needs));
+        // Fieldless Rust enums are shared with C++ rather than being opaque.
+        for e in &self.config.extern_rust_enums {
+            let (bridge_item, mut global_items) =
+                generate_extern_rust_enum(e, self.extern_rust_namespace_attr());
+            bridge_items.push(bridge_item);
+            all_items.append(&mut global_items);
+        }
+        // Methods connecting Qt signals to Rust closures.
+        for signal in self.config.get_qt_signals() {
+            let mut result = generate_connect_method(signal);
+            extern_c_mod_items.append(&mut result.extern_c_mod_items);
+            extern_rust_mod_items.append(&mut result.extern_rust_mod_items);
+            all_items.append(&mut result.global_items);
+        }
         // We will always create an extern "C" mod even if bindgen
         // didn't generate one, e.g. because it only generated types.
         // We still want cxx to know about those types.
@@ -229,31 +343,31 @@ impl<'a> RsCodeGenerator<'a> {
         );
         extern_rust_mod.items.append(&mut extern_rust_mod_items);
         bridge_items.push(Item::ForeignMod(extern_rust_mod));
-        // The extensive use of parse_quote here could end up
-        // being a performance bottleneck. If so, we might want
-        // to set the 'contents' field of the ItemMod
-        // structures directly.
+        // We set the 'contents' field of these ItemMods directly rather than
+        // interpolating the items using parse_quote, which would turn them
+        // all into tokens and back again.
         if !bindgen_root_items.is_empty() {
             self.bindgen_mod.vis = parse_quote! {};
-            self.bindgen_mod.content.as_mut().unwrap().1 = vec![Item::Mod(parse_quote! {
-                pub(super) mod root {
-                    #(#bindgen_root_items)*
-                }
-            })];
+            let mut root_mod: ItemMod = parse_quote! {
+                pub(super) mod root {}
+            };
+            root_mod.content.as_mut().unwrap().1 = bindgen_root_items;
+            self.bindgen_mod.content.as_mut().unwrap().1 = vec![Item::Mod(root_mod)];
             all_items.push(Item::Mod(self.bindgen_mod));
         }
-        all_items.push(Item::Mod(parse_quote! {
+        let mut cxxbridge_mod: ItemMod = parse_quote! {
             #[cxx::bridge]
-            mod cxxbridge {
-                #(#bridge_items)*
-            }
-        }));
+            mod cxxbridge {}
+        };
+        cxxbridge_mod.content.as_mut().unwrap().1 = bridge_items;
+        all_items.push(Item::Mod(cxxbridge_mod));
 
         all_items.push(Item::Use(parse_quote! {
             #[allow(unused_imports)]
             use bindgen::root;
         }));
         all_items.append(&mut use_statements);
+        all_items.extend(generate_lifecycle_functions(self.config));
         all_items
     }
 
@@ -278,6 +392,14 @@ impl<'a> RsCodeGenerator<'a> {
         results
     }
 
+    /// The `#[namespace]` attribute for Rust types and functions exposed to
+    /// C++, if an `extern_rust_namespace!` directive was given.
+    fn extern_rust_namespace_attr(&self) -> Option<Attribute> {
+        self.config
+            .get_extern_rust_namespace()
+            .map(|ns| parse_quote! { #[namespace = #ns] })
+    }
+
     fn make_foreign_mod_unsafe(ifm: ItemForeignMod) -> Item {
         // At the moment syn does not support outputting 'unsafe extern "C"' except in verbatim
         // items. See https://github.com/dtolnay/syn/pull/938
@@ -287,47 +409,75 @@ impl<'a> RsCodeGenerator<'a> {
     }
 
     fn build_include_foreign_items(&self, has_additional_cpp_needs: bool) -> Vec<ForeignItem> {
+        let has_prelude = !self.config.get_prelude().is_empty();
         let extra_inclusion = if has_additional_cpp_needs {
             Some(self.header_name.clone().unwrap())
+        } else if has_prelude {
+            self.header_name.clone()
         } else {
             None
+        }
+        .map(|path| Inclusion {
+            path,
+            system: false,
+        });
+        // Any prelude is in our additional header, so that must then be
+        // included before everything else.
+        let (first_inclusion, last_inclusion) = if has_prelude {
+            (extra_inclusion, None)
+        } else {
+            (None, extra_inclusion)
         };
-        let chained = self.include_list.iter().chain(extra_inclusion.iter());
+        let chained = first_inclusion
+            .iter()
+            .chain(self.include_list.iter())
+            .chain(last_inclusion.iter());
         chained
             .map(|inc| {
+                let path = &inc.path;
+                // cxx takes system includes as tokens within <>.
+                let path = if inc.system {
+                    let path: TokenStream = path.parse().unwrap();
+                    quote! { <#path> }
+                } else {
+                    quote! { #path }
+                };
                 ForeignItem::Macro(parse_quote! {
-                    include!(#inc);
+                    include!(#path);
                 })
             })
             .collect()
     }
 
     /// Generate lots of 'use' statements to pull cxxbridge items into the output
-    /// mod hierarchy according to C++ namespaces.
+    /// mod hierarchy according to C++ namespaces (perhaps as remapped by
+    /// `map_namespace!` or `flatten_namespaces!`).
     fn generate_final_use_statements(
+        &self,
         input_items: &[(QualifiedName, RsCodegenResult)],
     ) -> Vec<Item> {
         let mut output_items = Vec::new();
-        let ns_entries = NamespaceEntries::new(input_items);
+        let items = assign_output_namespaces(self.config, input_items);
+        let ns_entries = NamespaceEntries::new(&items);
         Self::append_child_use_namespace(&ns_entries, &mut output_items);
         output_items
     }
 
     fn append_child_use_namespace(
-        ns_entries: &NamespaceEntries<(QualifiedName, RsCodegenResult)>,
+        ns_entries: &NamespaceEntries<OutputItem>,
         output_items: &mut Vec<Item>,
     ) {
-        for (name, codegen) in ns_entries.entries() {
+        for OutputItem { ns, name, codegen } in ns_entries.entries() {
             output_items.extend(codegen.materializations.iter().map(|materialization| {
                 match materialization {
                     Use::UsedFromCxxBridgeWithAlias(alias) => {
-                        Self::generate_cxx_use_stmt(name, Some(alias))
+                        Self::generate_cxx_use_stmt(name, ns, Some(alias))
                     }
-                    Use::UsedFromCxxBridge => Self::generate_cxx_use_stmt(name, None),
-                    Use::UsedFromBindgen => Self::generate_bindgen_use_stmt(name),
+                    Use::UsedFromCxxBridge => Self::generate_cxx_use_stmt(name, ns, None),
+                    Use::UsedFromBindgen => Self::generate_bindgen_use_stmt(name, ns),
                     Use::SpecificNameFromBindgen(id) => {
                         let name = QualifiedName::new(name.get_namespace(), id.clone());
-                        Self::generate_bindgen_use_stmt(&name)
+                        Self::generate_bindgen_use_stmt(&name, ns)
                     }
                     Use::Custom(item) => *item.clone(),
                 }
@@ -359,7 +509,7 @@ impl<'a> RsCodeGenerator<'a> {
                 #(#supers)::*
             ::cxxbridge;
         }));
-        if !self.config.exclude_utilities() {
+        if self.string_utilities_generated {
             let supers = super_duper.clone().take(ns.depth() + 2);
             items.push(Item::Use(parse_quote! {
                 #[allow(unused_imports)]
@@ -379,33 +529,33 @@ impl<'a> RsCodeGenerator<'a> {
 
     fn append_child_bindgen_namespace(
         &mut self,
-        ns_entries: &NamespaceEntries<(QualifiedName, RsCodegenResult)>,
+        ns_entries: &NamespaceEntries<BindgenModEntry>,
         output_items: &mut Vec<Item>,
         ns: &Namespace,
     ) {
         let mut impl_entries_by_type: HashMap<_, Vec<_>> = HashMap::new();
         let mut trait_impl_entries_by_trait_and_ty: HashMap<_, Vec<_>> = HashMap::new();
-        for item in ns_entries.entries() {
-            output_items.extend(item.1.bindgen_mod_items.iter().cloned());
-            if let Some(impl_entry) = &item.1.impl_entry {
+        for entry in ns_entries.entries() {
+            output_items.append(&mut entry.items.take());
+            if let Some(impl_entry) = entry.impl_entry.take() {
                 impl_entries_by_type
-                    .entry(impl_entry.ty.clone())
+                    .entry(impl_entry.ty)
                     .or_default()
-                    .push(&impl_entry.item);
+                    .push(impl_entry.item);
             }
-            if let Some(trait_impl_entry) = &item.1.trait_impl_entry {
+            if let Some(trait_impl_entry) = entry.trait_impl_entry.take() {
                 trait_impl_entries_by_trait_and_ty
-                    .entry(trait_impl_entry.key.clone())
+                    .entry(trait_impl_entry.key)
                     .or_default()
-                    .push(&trait_impl_entry.item);
+                    .push(trait_impl_entry.item);
             }
         }
         for (ty, entries) in impl_entries_by_type.into_iter() {
-            output_items.push(Item::Impl(parse_quote! {
-                impl #ty {
-                    #(#entries)*
-                }
-            }))
+            let mut impl_block: ItemImpl = parse_quote! {
+                impl #ty {}
+            };
+            impl_block.items = entries;
+            output_items.push(Item::Impl(impl_block))
         }
         for (key, entries) in trait_impl_entries_by_trait_and_ty.into_iter() {
             let unsafety = key.unsafety;
@@ -439,10 +589,7 @@ impl<'a> RsCodeGenerator<'a> {
         parse_quote! { #id }
     }
 
-    fn generate_final_bindgen_mods(
-        &mut self,
-        input_items: &[(QualifiedName, RsCodegenResult)],
-    ) -> Vec<Item> {
+    fn generate_final_bindgen_mods(&mut self, input_items: &[BindgenModEntry]) -> Vec<Item> {
         let mut output_items = Vec::new();
         let ns = Namespace::new();
         let ns_entries = NamespaceEntries::new(input_items);
@@ -460,6 +607,7 @@ impl<'a> RsCodeGenerator<'a> {
         let name = api.name().clone();
         let id = name.get_final_ident();
         let cpp_call_name = api.effective_cpp_name().to_string();
+        let supports_vector_push = api.supports_vector_push() && !self.config.exclude_impls;
         match api {
             Api::StringConstructor { .. } => {
                 let make_string_name = make_ident(self.config.get_makestring_name());
@@ -467,10 +615,10 @@ impl<'a> RsCodeGenerator<'a> {
                     extern_c_mod_items: vec![ForeignItem::Fn(parse_quote!(
                         fn #make_string_name(str_: &str) -> UniquePtr<CxxString>;
                     ))],
-                    global_items: get_string_items(),
-                    materializations: vec![Use::UsedFromCxxBridgeWithAlias(make_ident(
-                        "make_string",
-                    ))],
+                    global_items: get_string_items(&self.make_string_name),
+                    materializations: vec![Use::UsedFromCxxBridgeWithAlias(
+                        self.make_string_name.clone(),
+                    )],
                     ..Default::default()
                 }
             }
@@ -491,11 +639,51 @@ impl<'a> RsCodeGenerator<'a> {
                 ..Default::default()
             },
             Api::Struct {
-                details, analysis, ..
+                mut details,
+                analysis,
+                ..
             } => {
                 let doc_attr = get_doc_attr(&details.item.attrs);
-                let layout = details.layout.clone();
-                self.generate_type(
+                let mut layout = details.layout.clone();
+                if let Some(layout_override) = analysis.pod.layout_override {
+                    if matches!(analysis.pod.kind, TypeKind::Pod) {
+                        apply_layout_override_to_pod(&mut details.item, layout_override);
+                    } else {
+                        layout = layout.map(|layout| {
+                            apply_layout_override_to_non_pod(layout, layout_override)
+                        });
+                    }
+                }
+                let field_metadata = if matches!(analysis.pod.kind, TypeKind::Pod)
+                    && self.config.wants_field_metadata(&name.to_cpp_name())
+                {
+                    generate_field_metadata(&details.item)
+                } else {
+                    None
+                };
+                // A guard is only useful if the type can be destroyed.
+                let raii_guard = if analysis.constructors.destructor
+                    && self.config.is_raii_type(&name.to_cpp_name())
+                {
+                    Some(generate_raii_guard(&id))
+                } else {
+                    None
+                };
+                let vector_push = supports_vector_push.then(|| generate_vector_push(&name, &id));
+                let by_value_new = matches!(analysis.pod.kind, TypeKind::Pod)
+                    .then(|| generate_by_value_new_impl(&details.item));
+                let trait_impls: Vec<_> = analysis
+                    .trait_impls
+                    .iter()
+                    .map(|details| generate_trait_impl(&id, analysis.pod.kind, details))
+                    .collect();
+                let mirror_type_impls = match self.config.get_mirror_type(&name.to_cpp_name()) {
+                    Some(mirror) if matches!(analysis.pod.kind, TypeKind::Pod) => {
+                        generate_mirror_type_impls(&details.item, mirror)
+                    }
+                    _ => Vec::new(),
+                };
+                let mut result = self.generate_type(
                     &name,
                     id,
                     analysis.pod.kind,
@@ -504,20 +692,53 @@ impl<'a> RsCodeGenerator<'a> {
                     || Some((Item::Struct(details.item), doc_attr)),
                     associated_methods,
                     layout,
-                )
+                );
+                result
+                    .bindgen_mod_items
+                    .extend(analysis.pod.opaque_fields.iter().map(|opaque_field| {
+                        Item::Struct(new_opaque_field_blob(
+                            opaque_field.blob_ident(&name),
+                            &opaque_field.layout,
+                        ))
+                    }));
+                result
+                    .bindgen_mod_items
+                    .extend(field_metadata.map(Item::Impl));
+                if let Some((guard_name, guard_items)) = raii_guard {
+                    result.bindgen_mod_items.extend(guard_items);
+                    result
+                        .materializations
+                        .push(Use::SpecificNameFromBindgen(guard_name));
+                }
+                result.bindgen_mod_items.extend(by_value_new);
+                result.bindgen_mod_items.extend(mirror_type_impls);
+                result.bindgen_mod_items.extend(trait_impls);
+                if let Some(mut vector_push) = vector_push {
+                    result
+                        .extern_c_mod_items
+                        .append(&mut vector_push.extern_c_mod_items);
+                    result.global_items.append(&mut vector_push.global_items);
+                }
+                result
             }
             Api::Enum { item, .. } => {
                 let doc_attr = get_doc_attr(&item.attrs);
-                self.generate_type(
+                let (item, extra_items) = match enum_as_newtype(&item) {
+                    Some((newtype, consts)) => (Item::Struct(newtype), vec![Item::Impl(consts)]),
+                    None => (Item::Enum(item), Vec::new()),
+                };
+                let mut result = self.generate_type(
                     &name,
                     id,
                     TypeKind::Pod,
                     true,
                     true,
-                    || Some((Item::Enum(item), doc_attr)),
+                    || Some((item, doc_attr)),
                     associated_methods,
                     None,
-                )
+                );
+                result.bindgen_mod_items.extend(extra_items);
+                result
             }
             Api::ForwardDeclaration { .. } | Api::ConcreteType { .. } => self.generate_type(
                 &name,
@@ -529,30 +750,70 @@ impl<'a> RsCodeGenerator<'a> {
                 associated_methods,
                 None,
             ),
+            Api::ExternCppType {
+                rust_path,
+                layout,
+                rs_shims,
+                ..
+            } => self.generate_extern_cpp_type(&name, id, rust_path, layout, rs_shims),
             Api::CType { .. } => RsCodegenResult {
                 extern_c_mod_items: vec![ForeignItem::Verbatim(quote! {
                     type #id = autocxx::#id;
                 })],
                 ..Default::default()
             },
-            Api::RustType { path, .. } => RsCodegenResult {
-                global_items: vec![parse_quote! {
-                    use super::#path;
-                }],
-                extern_rust_mod_items: vec![parse_quote! {
-                    type #id;
-                }],
-                ..Default::default()
-            },
-            Api::RustFn { sig, path, .. } => RsCodegenResult {
-                global_items: vec![parse_quote! {
-                    use super::#path;
-                }],
-                extern_rust_mod_items: vec![parse_quote! {
-                    #sig;
-                }],
-                ..Default::default()
-            },
+            Api::RustType { path, .. } => {
+                let ns_attr = self.extern_rust_namespace_attr();
+                RsCodegenResult {
+                    global_items: vec![parse_quote! {
+                        use super::#path;
+                    }],
+                    extern_rust_mod_items: vec![parse_quote! {
+                        #ns_attr
+                        type #id;
+                    }],
+                    ..Default::default()
+                }
+            }
+            Api::RustFn {
+                mut sig,
+                path,
+                is_factory,
+                ..
+            } => {
+                let c_wrapper = if self.c_wrappers {
+                    generate_c_wrapper(self.config, &path, &sig)
+                } else {
+                    None
+                };
+                // Within the cxx::bridge, types such as `ffi::Foo` or
+                // `std::vec::Vec<u32>` must be referred to by their final
+                // name, so that cxx can map slices and vectors to their
+                // `rust::Slice` and `rust::Vec` equivalents.
+                sig.inputs = unqualify_params(sig.inputs);
+                sig.output = strip_result_error_type(unqualify_ret_type(sig.output));
+                let mut boxing_wrapper = None;
+                if is_factory {
+                    if let Some((wrapper, wrapper_sig)) = generate_boxing_wrapper(&sig) {
+                        boxing_wrapper = Some(wrapper);
+                        sig = wrapper_sig;
+                    }
+                }
+                let ns_attr = self.extern_rust_namespace_attr();
+                RsCodegenResult {
+                    global_items: std::iter::once(parse_quote! {
+                        use super::#path;
+                    })
+                    .chain(c_wrapper.map(Item::Fn))
+                    .chain(boxing_wrapper)
+                    .collect(),
+                    extern_rust_mod_items: vec![parse_quote! {
+                        #ns_attr
+                        #sig;
+                    }],
+                    ..Default::default()
+                }
+            }
             Api::RustSubclassFn {
                 details, subclass, ..
             } => Self::generate_subclass_fn(id, *details, subclass),
@@ -564,7 +825,11 @@ impl<'a> RsCodeGenerator<'a> {
                     subclasses_with_a_single_trivial_constructor.contains(&name.0.name);
                 self.generate_subclass(name, &superclass, methods, generate_peer_constructor)
             }
-            Api::IgnoredItem { err, ctx, .. } => Self::generate_error_entry(err, ctx),
+            Api::IgnoredItem {
+                name: api_name,
+                err,
+                ctx,
+            } => Self::generate_error_entry(err, ctx, api_name.source_location()),
             Api::SubclassTraitItem { .. } => RsCodegenResult::default(),
         }
     }
@@ -588,13 +853,32 @@ impl<'a> RsCodeGenerator<'a> {
         global_items.push(parse_quote! {
             pub use bindgen::root::#holder;
         });
+        // A generic subclass is generated by us within this mod, whereas
+        // other subclasses are written by the user alongside it.
+        let rust_path: TypePath = if self.config.is_generic_subclass(&id.to_string()) {
+            global_items.extend(generic_subclass::generate_generic_subclass(
+                &sub, superclass, methods,
+            ));
+            if self
+                .config
+                .get_channel_observer_subclass(&superclass.to_cpp_name())
+                == Some(&id)
+            {
+                global_items.extend(channel_observer::generate_channel_observer(
+                    &sub, superclass, methods,
+                ));
+            }
+            parse_quote!(super::super::#id)
+        } else {
+            parse_quote!(super::super::super::#id)
+        };
         let relinquish_ownership_call = sub.cpp_remove_ownership();
         let mut bindgen_mod_items = vec![
             parse_quote! {
                 pub use cxxbridge::#cpp_id;
             },
             parse_quote! {
-                pub struct #holder(pub autocxx::subclass::CppSubclassRustPeerHolder<super::super::super::#id>);
+                pub struct #holder(pub autocxx::subclass::CppSubclassRustPeerHolder<#rust_path>);
             },
             parse_quote! {
                 impl autocxx::subclass::CppSubclassCppPeer for #cpp_id {
@@ -640,7 +924,7 @@ impl<'a> RsCodeGenerator<'a> {
             if !methods_impls.is_empty() {
                 bindgen_mod_items.push(parse_quote! {
                     #[allow(non_snake_case)]
-                    impl #supers for super::super::super::#id {
+                    impl #supers for #rust_path {
                         #(#methods_impls)*
                     }
                 });
@@ -648,7 +932,7 @@ impl<'a> RsCodeGenerator<'a> {
         }
         if generate_peer_constructor {
             bindgen_mod_items.push(parse_quote! {
-                impl autocxx::subclass::CppPeerConstructor<#cpp_id> for super::super::super::#id {
+                impl autocxx::subclass::CppPeerConstructor<#cpp_id> for #rust_path {
                     fn make_peer(&mut self, peer_holder: autocxx::subclass::CppSubclassRustPeerHolder<Self>) -> cxx::UniquePtr<#cpp_path> {
                         #cpp_id :: make_unique(peer_holder)
                     }
@@ -666,7 +950,7 @@ impl<'a> RsCodeGenerator<'a> {
             fn #as_mut_id(self: Pin<&mut #cpp_id>) -> Pin<&mut #super_cxxxbridge_id>;
         });
         bindgen_mod_items.push(parse_quote! {
-            impl AsRef<#super_path> for super::super::super::#id {
+            impl AsRef<#super_path> for #rust_path {
                 fn as_ref(&self) -> &cxxbridge::#super_cxxxbridge_id {
                     use autocxx::subclass::CppSubclass;
                     self.peer().#as_id()
@@ -675,7 +959,7 @@ impl<'a> RsCodeGenerator<'a> {
         });
         // TODO it would be nice to impl AsMut here but pin prevents us
         bindgen_mod_items.push(parse_quote! {
-            impl super::super::super::#id {
+            impl #rust_path {
                 pub fn pin_mut(&mut self) -> ::std::pin::Pin<&mut cxxbridge::#super_cxxxbridge_id> {
                     use autocxx::subclass::CppSubclass;
                     self.peer_mut().#as_mut_id()
@@ -689,6 +973,29 @@ impl<'a> RsCodeGenerator<'a> {
                 Box::new(#holder(me.0.relinquish_ownership()))
             }
         });
+        // If C++ is destroying this object, the Rust side is still alive
+        // (because the C++ side owns it), so we can tell it. If Rust is
+        // destroying the pair, the Rust side has already gone.
+        let destroyed = sub.destroyed();
+        let destroyed_call: Option<Stmt> = if has_destroyed_hook(methods) {
+            let methods_trait = SubclassName::get_methods_trait_name(superclass).to_type_path();
+            Some(parse_quote! {
+                if let Some(rc) = me.0.get() {
+                    if let Ok(mut b) = rc.as_ref().try_borrow_mut() {
+                        #methods_trait::destroyed(std::ops::DerefMut::deref_mut(&mut b));
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        global_items.push(parse_quote! {
+            #[allow(non_snake_case)]
+            #[allow(unused_variables)]
+            pub fn #destroyed(me: &#holder) {
+                #destroyed_call
+            }
+        });
         RsCodegenResult {
             extern_c_mod_items,
             // For now we just assume we can't keep subclasses in vectors, but we can put them in
@@ -707,6 +1014,9 @@ impl<'a> RsCodeGenerator<'a> {
                 parse_quote! {
                     fn #remove_ownership(me: Box<#holder>) -> Box<#holder>;
                 },
+                parse_quote! {
+                    fn #destroyed(me: &#holder);
+                },
             ],
             ..Default::default()
         }
@@ -798,6 +1108,7 @@ impl<'a> RsCodeGenerator<'a> {
             &mut bindgen_mod_items,
             &mut materializations,
             associated_methods.get(name),
+            self.config.is_mocked(&name.to_cpp_name()),
         );
         let orig_item = item_creator();
         // We have a choice here to either:
@@ -858,9 +1169,10 @@ impl<'a> RsCodeGenerator<'a> {
         bindgen_mod_items: &mut Vec<Item>,
         materializations: &mut Vec<Use>,
         methods: Option<&Vec<SuperclassMethod>>,
+        generate_mock: bool,
     ) {
         if let Some(methods) = methods {
-            let (supers, mains): (Vec<_>, Vec<_>) = methods
+            let (supers, mut mains): (Vec<_>, Vec<TraitItem>) = methods
                 .iter()
                 .map(|method| {
                     let id = &method.name;
@@ -884,7 +1196,10 @@ impl<'a> RsCodeGenerator<'a> {
                             ),
                         )
                     } else {
+                        let super_doc =
+                            format!("Calls the C++ superclass implementation of `{}`.", id);
                         let a: Option<TraitItem> = Some(parse_quote!(
+                            #[doc = #super_doc]
                             #unsafe_token fn #super_id(#params) #ret_type;
                         ));
                         let b: TraitItem = parse_quote!(
@@ -896,11 +1211,25 @@ impl<'a> RsCodeGenerator<'a> {
                     }
                 })
                 .unzip();
+            if has_destroyed_hook(Some(methods)) {
+                mains.push(parse_quote!(
+                    /// Called when the C++ peer of this subclass is destroyed by
+                    /// C++, for example when C++ code deletes it through a pointer
+                    /// to its superclass. This is not called if the object pair
+                    /// is being destroyed because Rust dropped it.
+                    fn destroyed(&mut self) {}
+                ));
+            }
             let supers: Vec<_> = supers.into_iter().flatten().collect();
             let supers_name = SubclassName::get_supers_trait_name(name).get_final_ident();
             let methods_name = SubclassName::get_methods_trait_name(name).get_final_ident();
             if !supers.is_empty() {
+                let supers_doc = format!(
+                    "The C++ implementations of the virtual methods of `{}`, for subclasses to call.",
+                    name.get_final_item()
+                );
                 bindgen_mod_items.push(parse_quote! {
+                    #[doc = #supers_doc]
                     #[allow(non_snake_case)]
                     pub trait #supers_name {
                         #(#supers)*
@@ -912,7 +1241,7 @@ impl<'a> RsCodeGenerator<'a> {
                         #(#mains)*
                     }
                 });
-                materializations.push(Use::SpecificNameFromBindgen(supers_name));
+                materializations.push(Use::SpecificNameFromBindgen(supers_name.clone()));
             } else {
                 bindgen_mod_items.push(parse_quote! {
                     #[allow(non_snake_case)]
@@ -921,6 +1250,20 @@ impl<'a> RsCodeGenerator<'a> {
                     }
                 });
             }
+            if generate_mock {
+                let (mock_name, mut mock_items) = mock::generate_mock(
+                    name,
+                    methods,
+                    &methods_name,
+                    if supers.is_empty() {
+                        None
+                    } else {
+                        Some(&supers_name)
+                    },
+                );
+                bindgen_mod_items.append(&mut mock_items);
+                materializations.push(Use::SpecificNameFromBindgen(mock_name));
+            }
             materializations.push(Use::SpecificNameFromBindgen(methods_name));
         }
     }
@@ -928,8 +1271,18 @@ impl<'a> RsCodeGenerator<'a> {
     /// Generates something in the output mod that will carry a docstring
     /// explaining why a given type or function couldn't have bindings
     /// generated.
-    fn generate_error_entry(err: ConvertError, ctx: ErrorContext) -> RsCodegenResult {
-        let err = format!("autocxx bindings couldn't be generated: {}", err);
+    fn generate_error_entry(
+        err: ConvertError,
+        ctx: ErrorContext,
+        source_location: Option<&SourceLocation>,
+    ) -> RsCodegenResult {
+        let err = match source_location {
+            Some(loc) => format!(
+                "autocxx bindings couldn't be generated: {}\n\nDeclared at {}.",
+                err, loc
+            ),
+            None => format!("autocxx bindings couldn't be generated: {}", err),
+        };
         let (impl_entry, materialization) = match ctx {
             ErrorContext::Item(id) => {
                 let id = Self::sanitize_error_ident(&id).unwrap_or(id);
@@ -991,8 +1344,14 @@ impl<'a> RsCodeGenerator<'a> {
         }
     }
 
-    fn generate_cxx_use_stmt(name: &QualifiedName, alias: Option<&Ident>) -> Item {
-        let segs = Self::find_output_mod_root(name.get_namespace())
+    /// Generates a `use` statement, to be placed in the output mod for
+    /// `output_ns`, which exposes an item from the cxx::bridge.
+    fn generate_cxx_use_stmt(
+        name: &QualifiedName,
+        output_ns: &Namespace,
+        alias: Option<&Ident>,
+    ) -> Item {
+        let segs = Self::find_output_mod_root(output_ns)
             .chain(std::iter::once(make_ident("cxxbridge")))
             .chain(std::iter::once(name.get_final_ident()));
         Item::Use(match alias {
@@ -1005,9 +1364,8 @@ impl<'a> RsCodeGenerator<'a> {
         })
     }
 
-    fn generate_bindgen_use_stmt(name: &QualifiedName) -> Item {
-        let segs =
-            Self::find_output_mod_root(name.get_namespace()).chain(name.get_bindgen_path_idents());
+    fn generate_bindgen_use_stmt(name: &QualifiedName, output_ns: &Namespace) -> Item {
+        let segs = Self::find_output_mod_root(output_ns).chain(name.get_bindgen_path_idents());
         Item::Use(parse_quote! {
             pub use #(#segs)::*;
         })
@@ -1029,6 +1387,42 @@ impl<'a> RsCodeGenerator<'a> {
         })]
     }
 
+    /// Generates a type declared by the user, using `extern_cpp_type!`, to
+    /// be ABI-identical to an existing Rust type. That type must implement
+    /// `cxx::ExternType` with a trivial `Kind`, since the orphan rule stops
+    /// us doing that here. We check that its size and alignment match
+    /// those of the C++ type.
+    fn generate_extern_cpp_type(
+        &self,
+        name: &QualifiedName,
+        id: Ident,
+        rust_path: RustPath,
+        layout: Option<Layout>,
+        rs_shims: Vec<Item>,
+    ) -> RsCodegenResult {
+        let mut bindgen_mod_items = vec![Item::Use(parse_quote! {
+            pub use #rust_path as #id;
+        })];
+        if let Some(layout) = layout {
+            let size = Literal::usize_unsuffixed(layout.size);
+            let align = Literal::usize_unsuffixed(layout.align);
+            bindgen_mod_items.push(parse_quote! {
+                const _: () = assert!(
+                    ::std::mem::size_of::<#id>() == #size
+                        && ::std::mem::align_of::<#id>() == #align,
+                    "extern_cpp_type! Rust type has a different size or alignment from the C++ type"
+                );
+            });
+        }
+        RsCodegenResult {
+            extern_c_mod_items: vec![self.generate_cxxbridge_type(name, true, None)],
+            bindgen_mod_items,
+            global_items: rs_shims,
+            materializations: vec![Use::UsedFromCxxBridge],
+            ..Default::default()
+        }
+    }
+
     fn generate_cxxbridge_type(
         &self,
         name: &QualifiedName,
@@ -1089,10 +1483,54 @@ impl<'a> RsCodeGenerator<'a> {
     }
 
     fn find_output_mod_root(ns: &Namespace) -> impl Iterator<Item = Ident> {
-        std::iter::repeat(make_ident("super")).take(ns.depth())
+        std::iter::repeat_n(make_ident("super"), ns.depth())
+    }
+}
+
+/// cxx expects functions which return errors to be declared as returning
+/// `Result<T>`, whatever the actual error type, so long as it implements
+/// `Display`. This converts a `Result<T, E>` return type accordingly.
+fn strip_result_error_type(ret_type: ReturnType) -> ReturnType {
+    match ret_type {
+        ReturnType::Type(arrow, mut ty) => {
+            if let Type::Path(typ) = ty.as_mut() {
+                let last_seg = typ.path.segments.last_mut().unwrap();
+                if last_seg.ident == "Result" {
+                    if let PathArguments::AngleBracketed(ab) = &mut last_seg.arguments {
+                        ab.args = std::mem::take(&mut ab.args).into_iter().take(1).collect();
+                    }
+                }
+            }
+            ReturnType::Type(arrow, ty)
+        }
+        _ => ret_type,
     }
 }
 
+/// Constructors of POD types return the new object by value. So that such
+/// a value can still be used wherever a `moveit` constructor is expected,
+/// e.g. in `moveit!` or `UniquePtr::emplace`, make the type its own `New`.
+fn generate_by_value_new_impl(item: &ItemStruct) -> Item {
+    let id = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    parse_quote! {
+        unsafe impl #impl_generics autocxx::moveit::new::New for #id #ty_generics #where_clause {
+            type Output = Self;
+            unsafe fn new(self, this: ::std::pin::Pin<&mut ::std::mem::MaybeUninit<Self>>) {
+                this.get_unchecked_mut().write(self);
+            }
+        }
+    }
+}
+
+/// Whether the `_methods` trait for a superclass has a `destroyed` hook.
+/// We can't add one if the superclass has its own virtual method of that name.
+fn has_destroyed_hook(methods: Option<&Vec<SuperclassMethod>>) -> bool {
+    methods
+        .map(|methods| !methods.iter().any(|m| m.name == "destroyed"))
+        .unwrap_or_default()
+}
+
 fn find_trivially_constructed_subclasses(apis: &ApiVec<FnPhase>) -> HashSet<QualifiedName> {
     let (simple_constructors, complex_constructors): (Vec<_>, Vec<_>) = apis
         .iter()
@@ -1122,12 +1560,28 @@ impl HasNs for (QualifiedName, RsCodegenResult) {
     }
 }
 
+impl HasNs for BindgenModEntry {
+    fn get_namespace(&self) -> &Namespace {
+        self.name.get_namespace()
+    }
+}
+
 impl<T: AnalysisPhase> HasNs for Api<T> {
     fn get_namespace(&self) -> &Namespace {
         self.name().get_namespace()
     }
 }
 
+/// The parts of an [`RsCodegenResult`] which go into the bindgen mod.
+/// [`NamespaceEntries`] only gives us references, so these are in `Cell`s
+/// to let us move them into the mod as we come across them.
+struct BindgenModEntry {
+    name: QualifiedName,
+    items: Cell<Vec<Item>>,
+    impl_entry: Cell<Option<Box<ImplBlockDetails>>>,
+    trait_impl_entry: Cell<Option<Box<TraitImplBlockDetails>>>,
+}
+
 /// Snippets of code generated from a particular API.
 /// These are then concatenated together into the final generated code.
 #[derive(Default)]
@@ -1141,3 +1595,27 @@ struct RsCodegenResult {
     trait_impl_entry: Option<Box<TraitImplBlockDetails>>,
     materializations: Vec<Use>,
 }
+
+impl RsCodegenResult {
+    /// The Rust generated for this API, for the purposes of a size report.
+    fn items_for_size_report(&self) -> Vec<TokenStream> {
+        self.extern_c_mod_items
+            .iter()
+            .chain(&self.extern_rust_mod_items)
+            .map(ToTokens::to_token_stream)
+            .chain(
+                self.bridge_items
+                    .iter()
+                    .chain(&self.global_items)
+                    .chain(&self.bindgen_mod_items)
+                    .map(ToTokens::to_token_stream),
+            )
+            .chain(self.impl_entry.iter().map(|e| e.item.to_token_stream()))
+            .chain(
+                self.trait_impl_entry
+                    .iter()
+                    .map(|e| e.item.to_token_stream()),
+            )
+            .collect()
+    }
+}
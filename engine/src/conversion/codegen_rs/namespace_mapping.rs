@@ -0,0 +1,102 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+
+use autocxx_parser::IncludeCppConfig;
+use syn::Ident;
+
+use crate::types::{Namespace, QualifiedName};
+
+use super::{namespace_organizer::HasNs, RsCodegenResult, Use};
+
+/// An item to be exposed within the output mod hierarchy. Its mod
+/// corresponds to its C++ namespace unless that's been changed using
+/// `map_namespace!` or `flatten_namespaces!`.
+pub(super) struct OutputItem<'a> {
+    pub(super) ns: Namespace,
+    pub(super) name: &'a QualifiedName,
+    pub(super) codegen: &'a RsCodegenResult,
+}
+
+impl HasNs for OutputItem<'_> {
+    fn get_namespace(&self) -> &Namespace {
+        &self.ns
+    }
+}
+
+/// Works out where in the output mod hierarchy each item should be
+/// exposed. Moving items between mods may make names clash, for example
+/// when flattening `a::foo` and `b::foo`; if so, the items concerned stay
+/// in the mods corresponding to their C++ namespaces.
+pub(super) fn assign_output_namespaces<'a>(
+    config: &IncludeCppConfig,
+    input_items: &'a [(QualifiedName, RsCodegenResult)],
+) -> Vec<OutputItem<'a>> {
+    let mut items: Vec<_> = input_items
+        .iter()
+        .map(|(name, codegen)| OutputItem {
+            ns: map_namespace(config, name.get_namespace()),
+            name,
+            codegen,
+        })
+        .collect();
+    loop {
+        let mut users_of_name: HashMap<(&Namespace, Ident), Vec<usize>> = HashMap::new();
+        for (idx, item) in items.iter().enumerate() {
+            for id in exposed_idents(item) {
+                let users = users_of_name.entry((&item.ns, id)).or_default();
+                if users.last() != Some(&idx) {
+                    users.push(idx);
+                }
+            }
+        }
+        let clashing: HashSet<usize> = users_of_name
+            .into_values()
+            .filter(|users| users.len() > 1)
+            .flatten()
+            .filter(|idx| items[*idx].ns != *items[*idx].name.get_namespace())
+            .collect();
+        if clashing.is_empty() {
+            break;
+        }
+        for idx in clashing {
+            let item = &mut items[idx];
+            log::warn!(
+                "Not moving {} to mod {}, since its name would clash with another item there",
+                item.name,
+                item.ns
+            );
+            item.ns = item.name.get_namespace().clone();
+        }
+    }
+    items
+}
+
+fn map_namespace(config: &IncludeCppConfig, ns: &Namespace) -> Namespace {
+    match config.map_namespace(&ns.to_string()) {
+        None => ns.clone(),
+        Some(mapped) => mapped
+            .split("::")
+            .filter(|segment| !segment.is_empty())
+            .fold(Namespace::new(), |ns, segment| ns.push(segment.to_string())),
+    }
+}
+
+/// The names by which this item will be known in its output mod.
+fn exposed_idents<'a>(item: &'a OutputItem<'a>) -> impl Iterator<Item = Ident> + 'a {
+    item.codegen
+        .materializations
+        .iter()
+        .filter_map(|materialization| match materialization {
+            Use::UsedFromCxxBridgeWithAlias(alias) => Some(alias.clone()),
+            Use::UsedFromCxxBridge | Use::UsedFromBindgen => Some(item.name.get_final_ident()),
+            Use::SpecificNameFromBindgen(id) => Some(id.clone()),
+            Use::Custom(_) => None,
+        })
+}
@@ -32,7 +32,7 @@ impl<'a, T: HasNs> NamespaceEntries<'a, T> {
         &self.entries
     }
 
-    pub(crate) fn children(&self) -> impl Iterator<Item = (&&String, &NamespaceEntries<T>)> {
+    pub(crate) fn children(&self) -> impl Iterator<Item = (&&String, &NamespaceEntries<'_, T>)> {
         self.children.iter()
     }
 
@@ -0,0 +1,62 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashSet;
+
+use quote::{quote, ToTokens};
+use syn::{parse_quote, Ident, ItemEnum, ItemImpl, ItemStruct};
+
+/// Rust enums can't have two variants with the same discriminant, so if any
+/// of the enumerators of this C++ enum share a value, returns a newtype
+/// wrapping the underlying integer type, plus an associated constant for
+/// each enumerator, to be used instead of a Rust enum. This is similar to
+/// bindgen's "newtype" enum style.
+pub(crate) fn enum_as_newtype(e: &ItemEnum) -> Option<(ItemStruct, ItemImpl)> {
+    let discriminants = e
+        .variants
+        .iter()
+        .map(|v| {
+            v.discriminant
+                .as_ref()
+                .map(|(_, expr)| expr.to_token_stream().to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if discriminants.iter().collect::<HashSet<_>>().len() == discriminants.len() {
+        return None;
+    }
+    let repr: Ident = e
+        .attrs
+        .iter()
+        .find(|attr| attr.path.is_ident("repr"))?
+        .parse_args()
+        .ok()?;
+    let id = &e.ident;
+    let vis = &e.vis;
+    let attrs = e.attrs.iter().filter(|attr| !attr.path.is_ident("repr"));
+    let consts = e.variants.iter().map(|v| {
+        let variant_id = &v.ident;
+        let variant_attrs = &v.attrs;
+        let (_, discriminant) = v.discriminant.as_ref().unwrap();
+        quote! {
+            #(#variant_attrs)*
+            pub const #variant_id: #id = #id(#discriminant);
+        }
+    });
+    Some((
+        parse_quote! {
+            #(#attrs)*
+            #[repr(transparent)]
+            #vis struct #id(pub #repr);
+        },
+        parse_quote! {
+            impl #id {
+                #(#consts)*
+            }
+        },
+    ))
+}
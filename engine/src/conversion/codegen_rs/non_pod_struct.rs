@@ -85,7 +85,7 @@ pub(crate) fn make_non_pod(s: &mut ItemStruct, layout: Option<Layout>) {
         .filter_map(|(counter, gp)| match gp {
             GenericParam::Type(gpt) => {
                 let id = &gpt.ident;
-                let field_name = make_ident(&format!("_phantom_{}", counter));
+                let field_name = make_ident(format!("_phantom_{}", counter));
                 let toks = quote! {
                     #field_name: ::std::marker::PhantomData<::std::cell::UnsafeCell< #id >>
                 };
@@ -127,6 +127,19 @@ pub(crate) fn make_non_pod(s: &mut ItemStruct, layout: Option<Layout>) {
     } })
 }
 
+/// Makes a type with the given layout, to represent a field of a POD
+/// struct which can't itself be represented in Rust.
+pub(crate) fn new_opaque_field_blob(id: Ident, layout: &Layout) -> ItemStruct {
+    let size = make_lit_int(layout.size);
+    let align = make_lit_int(layout.align);
+    parse_quote! {
+        #[repr(C, align(#align))]
+        pub struct #id {
+            _data: [u8; #size]
+        }
+    }
+}
+
 fn make_lit_int(val: usize) -> LitInt {
     LitInt::new(&val.to_string(), Span::call_site())
 }
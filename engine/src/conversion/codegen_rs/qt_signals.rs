@@ -0,0 +1,75 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use autocxx_parser::QtSignal;
+use syn::parse_quote;
+
+use crate::types::{make_ident, QualifiedName};
+
+use super::RsCodegenResult;
+
+/// Generates a method on the class declaring a Qt signal, which connects
+/// the signal to a Rust closure. The closure is boxed within a Rust type
+/// exposed to C++ with a `call` method; the C++ side of this is generated
+/// by `codegen_cpp::qt_signals`.
+pub(super) fn generate_connect_method(signal: &QtSignal) -> RsCodegenResult {
+    let helper = make_ident(signal.mangled_name());
+    let handler = make_ident(format!("{}_Handler", signal.mangled_name()));
+    let class = QualifiedName::new_from_cpp_name(&signal.class).get_final_ident();
+    let connect_fn = &signal.connect_fn;
+    let tys = &signal.rust_params;
+    let args: Vec<_> = (0..tys.len())
+        .map(|i| make_ident(format!("arg{}", i)))
+        .collect();
+    let doc = format!(
+        "Connects the Qt signal `{}` to a closure, which is called each time \
+        the signal is emitted until the connection is broken, e.g. because \
+        this object is destroyed. Returns whether the connection was made.",
+        signal.signature()
+    );
+    RsCodegenResult {
+        extern_c_mod_items: vec![parse_quote! {
+            fn #helper(sender: ::std::pin::Pin<&mut #class>, handler: Box<#handler>) -> bool;
+        }],
+        extern_rust_mod_items: vec![
+            parse_quote! {
+                type #handler;
+            },
+            parse_quote! {
+                fn call(self: &mut #handler, #(#args: #tys),*);
+            },
+        ],
+        global_items: vec![
+            parse_quote! {
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                pub struct #handler(Box<dyn FnMut(#(#tys),*)>);
+            },
+            parse_quote! {
+                impl #handler {
+                    fn call(&mut self, #(#args: #tys),*) {
+                        (self.0)(#(#args),*)
+                    }
+                }
+            },
+            parse_quote! {
+                impl cxxbridge::#class {
+                    #[doc = #doc]
+                    #[allow(non_snake_case)]
+                    pub fn #connect_fn(
+                        self: ::std::pin::Pin<&mut Self>,
+                        handler: impl FnMut(#(#tys),*) + 'static,
+                    ) -> bool {
+                        cxxbridge::#helper(self, Box::new(#handler(Box::new(handler))))
+                    }
+                }
+            },
+        ],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use syn::{parse_quote, Ident, Item};
+
+use crate::types::make_ident;
+
+/// Generates a `<type>Guard` struct for a type requested using `raii!`.
+/// The guard constructs the C++ object in place when it's created, and
+/// destroys it when it's dropped, so that scope-based C++ types (such as
+/// lock guards) can be used without dealing with a `UniquePtr`.
+pub(super) fn generate_raii_guard(id: &Ident) -> (Ident, Vec<Item>) {
+    let guard_name = make_ident(format!("{}Guard", id));
+    let doc = format!(
+        "Constructs a `{}` in place when created, and destroys it when dropped.",
+        id
+    );
+    let items = vec![
+        parse_quote! {
+            #[doc = #doc]
+            #[must_use]
+            pub struct #guard_name(cxx::UniquePtr<#id>);
+        },
+        parse_quote! {
+            impl #guard_name {
+                /// Creates the guard using one of the type's constructors,
+                /// for example its `new` function.
+                pub fn new(constructor: impl autocxx::moveit::new::New<Output = #id>) -> Self {
+                    use autocxx::moveit::EmplaceUnpinned;
+                    Self(cxx::UniquePtr::emplace(constructor))
+                }
+
+                /// Returns a pinned mutable reference to the guarded object.
+                pub fn pin_mut(&mut self) -> ::std::pin::Pin<&mut #id> {
+                    self.0.pin_mut()
+                }
+            }
+        },
+        parse_quote! {
+            impl ::std::ops::Deref for #guard_name {
+                type Target = #id;
+                fn deref(&self) -> &#id {
+                    self.0.as_ref().unwrap()
+                }
+            }
+        },
+    ];
+    (guard_name, items)
+}
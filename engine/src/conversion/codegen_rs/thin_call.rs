@@ -0,0 +1,53 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use syn::{parse_quote, Attribute, ImplItem, ReturnType};
+
+use crate::{
+    conversion::{
+        analysis::fun::thin_call::ThinCallDetails, api::UnsafetyNeeded, unwind::extern_abi,
+    },
+    types::{make_ident, QualifiedName},
+};
+
+use super::{ImplBlockDetails, RsCodegenResult};
+
+/// Generates a getter which calls C++ through a plain `extern "C"` function,
+/// generated by `codegen_cpp::thin_call`, instead of through cxx. The
+/// receiver is passed as a `void` pointer since the type isn't FFI-safe
+/// as far as Rust knows.
+pub(super) fn generate_thin_call(
+    thin_call: &ThinCallDetails,
+    impl_for: &QualifiedName,
+    rust_name: &str,
+    ret_type: &ReturnType,
+    unsafety: &UnsafetyNeeded,
+    doc_attr: &Option<Attribute>,
+    cfg: &Option<Attribute>,
+) -> RsCodegenResult {
+    let helper = &thin_call.helper;
+    let rust_name = make_ident(rust_name);
+    let unsafety = unsafety.wrapper_token();
+    let abi = extern_abi(thin_call.exception_policy);
+    RsCodegenResult {
+        impl_entry: Some(Box::new(ImplBlockDetails {
+            item: ImplItem::Method(parse_quote! {
+                #doc_attr
+                #cfg
+                pub #unsafety fn #rust_name(&self) #ret_type {
+                    #abi {
+                        fn #helper(this: *const ::std::ffi::c_void) #ret_type;
+                    }
+                    unsafe { #helper(self as *const Self as *const ::std::ffi::c_void) }
+                }
+            }),
+            ty: impl_for.get_final_ident(),
+        })),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use quote::quote;
+use syn::{parse_quote, Ident, Item};
+
+use crate::conversion::{
+    analysis::trait_impls::{SupportedTrait, TraitImplDetails},
+    api::TypeKind,
+};
+
+/// Generates an implementation of a standard trait which delegates to C++
+/// methods, requested using `impl_trait!`. `std::io` traits need `&mut
+/// self`, which non-POD types can't offer, so for those types they're
+/// implemented for `Pin<&mut T>` instead.
+pub(super) fn generate_trait_impl(id: &Ident, kind: TypeKind, details: &TraitImplDetails) -> Item {
+    let method = |name: &str| &details.methods[name];
+    let pinned = !matches!(kind, TypeKind::Pod);
+    let (ty, this) = if pinned {
+        (
+            quote! { ::std::pin::Pin<&mut #id> },
+            quote! { self.as_mut() },
+        )
+    } else {
+        (quote! { #id }, quote! { ::std::pin::Pin::new(self) })
+    };
+    match details.trt {
+        SupportedTrait::Write => {
+            let write = method("write");
+            let flush = method("flush");
+            parse_quote! {
+                impl ::std::io::Write for #ty {
+                    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+                        #[allow(unused_unsafe)]
+                        let written = unsafe { #this.#write(buf.as_ptr(), buf.len()) };
+                        Ok(written)
+                    }
+                    fn flush(&mut self) -> ::std::io::Result<()> {
+                        #[allow(unused_unsafe)]
+                        unsafe { #this.#flush() };
+                        Ok(())
+                    }
+                }
+            }
+        }
+        SupportedTrait::Read => {
+            let read = method("read");
+            parse_quote! {
+                impl ::std::io::Read for #ty {
+                    fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+                        #[allow(unused_unsafe)]
+                        let read = unsafe { #this.#read(buf.as_mut_ptr(), buf.len()) };
+                        Ok(read)
+                    }
+                }
+            }
+        }
+        SupportedTrait::IntoIterator => {
+            let len = method("len");
+            let get = method("get");
+            let element_type = details.element_type.as_ref().unwrap();
+            parse_quote! {
+                impl<'a> IntoIterator for &'a #id {
+                    type Item = &'a #element_type;
+                    type IntoIter = autocxx::IndexedIter<'a, #id, #element_type>;
+                    #[allow(unused_unsafe)]
+                    fn into_iter(self) -> Self::IntoIter {
+                        let len = unsafe { self.#len() };
+                        autocxx::IndexedIter::new(self, len, |container, index| unsafe {
+                            container.#get(index)
+                        })
+                    }
+                }
+            }
+        }
+        SupportedTrait::Display => {
+            let fmt = method("fmt");
+            parse_quote! {
+                impl ::std::fmt::Display for #id {
+                    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        #[allow(unused_unsafe)]
+                        let s = unsafe { self.#fmt() };
+                        ::std::fmt::Display::fmt(&s, f)
+                    }
+                }
+            }
+        }
+    }
+}
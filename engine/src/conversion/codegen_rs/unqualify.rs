@@ -6,13 +6,13 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+//! Mod to handle stripping paths off the front of types.
+
 use syn::{
     parse_quote, punctuated::Punctuated, FnArg, GenericArgument, PathArguments, PathSegment,
     ReturnType, Token, Type, TypePath,
 };
 
-/// Mod to handle stripping paths off the front of types.
-
 fn unqualify_type_path(typ: TypePath) -> TypePath {
     // If we've still got more than one
     // path segment then this is referring to a type within
@@ -61,6 +61,10 @@ fn unqualify_type(typ: Type) -> Type {
             typeptr.elem = unqualify_boxed_type(typeptr.elem);
             Type::Ptr(typeptr)
         }
+        Type::Slice(mut typeslice) => {
+            typeslice.elem = unqualify_boxed_type(typeslice.elem);
+            Type::Slice(typeslice)
+        }
         _ => typ,
     }
 }
@@ -0,0 +1,39 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use syn::{parse_quote, Ident};
+
+use crate::{
+    conversion::codegen_cpp::vector_push::vector_push_helper_name,
+    types::{make_ident, QualifiedName},
+};
+
+use super::RsCodegenResult;
+
+/// Implements `autocxx::CxxVectorPush` for a movable type, such that it can
+/// be pushed onto a `CxxVector` using `CxxVectorExt::push_moved`. This calls
+/// a helper generated by `codegen_cpp::vector_push`.
+pub(super) fn generate_vector_push(name: &QualifiedName, id: &Ident) -> RsCodegenResult {
+    let helper = make_ident(vector_push_helper_name(name));
+    RsCodegenResult {
+        extern_c_mod_items: vec![parse_quote! {
+            fn #helper(vec: ::std::pin::Pin<&mut CxxVector<#id>>, value: ::std::pin::Pin<&mut #id>);
+        }],
+        global_items: vec![parse_quote! {
+            impl autocxx::CxxVectorPush for cxxbridge::#id {
+                fn push_moved(
+                    vec: ::std::pin::Pin<&mut cxx::CxxVector<Self>>,
+                    value: ::std::pin::Pin<&mut Self>,
+                ) {
+                    cxxbridge::#helper(vec, value)
+                }
+            }
+        }],
+        ..Default::default()
+    }
+}
@@ -11,7 +11,7 @@ use autocxx_parser::UnsafePolicy;
 use syn::parse_quote;
 use syn::ItemMod;
 
-use crate::CppCodegenOptions;
+use crate::{source_locations::SourceLocations, CppCodegenOptions};
 
 use super::BridgeConverter;
 
@@ -29,13 +29,12 @@ fn do_test(input: ItemMod) {
     let tc = parse_quote! {};
     let bc = BridgeConverter::new(&[], &tc);
     let inclusions = "".into();
-    bc.convert(
-        input,
-        UnsafePolicy::AllFunctionsSafe,
-        inclusions,
-        &CppCodegenOptions::default(),
-    )
-    .unwrap();
+    let options = CppCodegenOptions::default();
+    let parsed = bc
+        .parse(input, &options, &SourceLocations::default())
+        .unwrap();
+    bc.convert(parsed, UnsafePolicy::AllFunctionsSafe, inclusions, &options)
+        .unwrap();
 }
 
 // How to add a test here
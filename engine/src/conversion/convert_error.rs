@@ -30,7 +30,9 @@ pub enum ConvertError {
     UnsupportedType(String),
     UnknownType(String),
     StaticData(String),
-    InfinitelyRecursiveTypedef(QualifiedName),
+    /// A typedef which leads back to itself, with the chain of typedefs
+    /// followed, starting and ending with the same name.
+    InfinitelyRecursiveTypedef(Vec<QualifiedName>),
     UnexpectedUseStatement(Option<Ident>),
     TemplatedTypeContainingNonPathArg(QualifiedName),
     InvalidPointee,
@@ -53,11 +55,132 @@ pub enum ConvertError {
     PrivateMethod,
     AssignmentOperator,
     Deleted,
+    BlockedConstructor,
     RValueReferenceField,
     MethodOfNonAllowlistedType,
     MethodOfGenericType,
+    MethodOfExternCppType,
     DuplicateItemsFoundInParsing,
     ConstructorWithOnlyOneParam,
+    FunctionPointer,
+    ExcludedByCfg(String),
+    UnexpectedIgnoredItems(Vec<String>),
+    InternalLinkage(String),
+    InvalidTraitImpl(String, String, String),
+    ReturnsOwnedNonPointer(String),
+    ConsumesNonPointer(String, usize),
+    MethodOfOpaqueOnlyType,
+    Deprecated,
+}
+
+impl ConvertError {
+    /// A stable code for this kind of error, shown in diagnostics, which
+    /// `autocxx-gen --explain` can describe in more detail. Codes are never
+    /// reused or renumbered, so new variants must take the next free code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConvertError::NoContent => "ACX0001",
+            ConvertError::UnsafePodType(..) => "ACX0002",
+            ConvertError::UnexpectedForeignItem => "ACX0003",
+            ConvertError::UnexpectedOuterItem => "ACX0004",
+            ConvertError::UnexpectedItemInMod => "ACX0005",
+            ConvertError::ComplexTypedefTarget(..) => "ACX0006",
+            ConvertError::UnexpectedThisType(..) => "ACX0007",
+            ConvertError::UnsupportedBuiltInType(..) => "ACX0008",
+            ConvertError::ConflictingTemplatedArgsWithTypedef(..) => "ACX0009",
+            ConvertError::UnacceptableParam(..) => "ACX0010",
+            ConvertError::NotOneInputReference(..) => "ACX0011",
+            ConvertError::UnsupportedType(..) => "ACX0012",
+            ConvertError::UnknownType(..) => "ACX0013",
+            ConvertError::StaticData(..) => "ACX0014",
+            ConvertError::InfinitelyRecursiveTypedef(..) => "ACX0015",
+            ConvertError::UnexpectedUseStatement(..) => "ACX0017",
+            ConvertError::TemplatedTypeContainingNonPathArg(..) => "ACX0018",
+            ConvertError::InvalidPointee => "ACX0019",
+            ConvertError::DidNotGenerateAnything(..) => "ACX0020",
+            ConvertError::TypeContainingForwardDeclaration(..) => "ACX0021",
+            ConvertError::Blocked(..) => "ACX0022",
+            ConvertError::UnusedTemplateParam => "ACX0023",
+            ConvertError::TooManyUnderscores => "ACX0024",
+            ConvertError::UnknownDependentType(..) => "ACX0025",
+            ConvertError::IgnoredDependent(..) => "ACX0026",
+            ConvertError::ReservedName(..) => "ACX0027",
+            ConvertError::DuplicateCxxBridgeName => "ACX0028",
+            ConvertError::UnsupportedReceiver => "ACX0029",
+            ConvertError::BoxContainingNonRustType(..) => "ACX0030",
+            ConvertError::RustTypeWithAPath(..) => "ACX0031",
+            ConvertError::AbstractNestedType => "ACX0032",
+            ConvertError::NonPublicNestedType => "ACX0033",
+            ConvertError::RValueParam => "ACX0034",
+            ConvertError::RValueReturn => "ACX0035",
+            ConvertError::PrivateMethod => "ACX0036",
+            ConvertError::AssignmentOperator => "ACX0037",
+            ConvertError::Deleted => "ACX0038",
+            ConvertError::BlockedConstructor => "ACX0039",
+            ConvertError::RValueReferenceField => "ACX0040",
+            ConvertError::MethodOfNonAllowlistedType => "ACX0041",
+            ConvertError::MethodOfGenericType => "ACX0042",
+            ConvertError::MethodOfExternCppType => "ACX0043",
+            ConvertError::DuplicateItemsFoundInParsing => "ACX0044",
+            ConvertError::ConstructorWithOnlyOneParam => "ACX0045",
+            ConvertError::FunctionPointer => "ACX0046",
+            ConvertError::ExcludedByCfg(..) => "ACX0047",
+            ConvertError::UnexpectedIgnoredItems(..) => "ACX0048",
+            ConvertError::InternalLinkage(..) => "ACX0049",
+            ConvertError::InvalidTraitImpl(..) => "ACX0050",
+            ConvertError::ReturnsOwnedNonPointer(..) => "ACX0051",
+            ConvertError::ConsumesNonPointer(..) => "ACX0052",
+            ConvertError::MethodOfOpaqueOnlyType => "ACX0053",
+            ConvertError::Deprecated => "ACX0054",
+        }
+    }
+
+    /// How much this problem matters to the user.
+    pub(crate) fn severity(&self) -> Severity {
+        match self {
+            ConvertError::PrivateMethod
+            | ConvertError::AssignmentOperator
+            | ConvertError::Deleted
+            | ConvertError::BlockedConstructor
+            | ConvertError::MethodOfNonAllowlistedType
+            | ConvertError::MethodOfGenericType
+            | ConvertError::MethodOfOpaqueOnlyType
+            | ConvertError::Deprecated
+            | ConvertError::ExcludedByCfg(..) => Severity::Info,
+            ConvertError::NoContent
+            | ConvertError::UnsafePodType(..)
+            | ConvertError::UnexpectedForeignItem
+            | ConvertError::UnexpectedOuterItem
+            | ConvertError::UnexpectedItemInMod
+            | ConvertError::UnexpectedUseStatement(..)
+            | ConvertError::DidNotGenerateAnything(..)
+            | ConvertError::UnexpectedIgnoredItems(..)
+            | ConvertError::InvalidTraitImpl(..) => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// How much a [`ConvertError`] matters to the user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Severity {
+    /// Expected, and needs no action, e.g. a private method was skipped.
+    Info,
+    /// Something was skipped which the user may have wanted.
+    Warning,
+    /// The directives asked for something impossible, or bindgen produced
+    /// something autocxx doesn't understand.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
 }
 
 fn format_maybe_identifier(id: &Option<Ident>) -> String {
@@ -69,6 +192,7 @@ fn format_maybe_identifier(id: &Option<Ident>) -> String {
 
 impl Display for ConvertError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] ", self.code())?;
         match self {
             ConvertError::NoContent => write!(f, "The initial run of 'bindgen' did not generate any content. This might be because none of the requested items for generation could be converted.")?,
             ConvertError::UnsafePodType(err) => write!(f, "An item was requested using 'generate_pod' which was not safe to hold by value in Rust. {}", err)?,
@@ -84,13 +208,13 @@ impl Display for ConvertError {
             ConvertError::UnsupportedType(ty_desc) => write!(f, "Encountered type not yet supported by autocxx: {}", ty_desc)?,
             ConvertError::UnknownType(ty_desc) => write!(f, "Encountered type not yet known by autocxx: {}", ty_desc)?,
             ConvertError::StaticData(ty_desc) => write!(f, "Encountered mutable static data, not yet supported: {}", ty_desc)?,
-            ConvertError::InfinitelyRecursiveTypedef(tn) => write!(f, "Encountered typedef to itself - this is a known bindgen bug: {}", tn.to_cpp_name())?,
+            ConvertError::InfinitelyRecursiveTypedef(chain) => write!(f, "Encountered a typedef which leads back to itself, so has no underlying type: {}", chain.iter().map(|tn| tn.to_cpp_name()).join(" -> "))?,
             ConvertError::UnexpectedUseStatement(maybe_ident) => write!(f, "Unexpected 'use' statement encountered: {}", format_maybe_identifier(maybe_ident))?,
             ConvertError::TemplatedTypeContainingNonPathArg(tn) => write!(f, "Type {} was parameterized over something complex which we don't yet support", tn)?,
             ConvertError::InvalidPointee => write!(f, "Pointer pointed to something unsupported")?,
             ConvertError::DidNotGenerateAnything(directive) => write!(f, "The 'generate' or 'generate_pod' directive for '{}' did not result in any code being generated. Perhaps this was mis-spelled or you didn't qualify the name with any namespaces? Otherwise please report a bug.", directive)?,
             ConvertError::TypeContainingForwardDeclaration(tn) => write!(f, "Found an attempt at using a forward declaration ({}) inside a templated cxx type such as UniquePtr or CxxVector", tn.to_cpp_name())?,
-            ConvertError::Blocked(tn) => write!(f, "Found an attempt at using a type marked as blocked! ({})", tn.to_cpp_name())?,
+            ConvertError::Blocked(tn) => write!(f, "Found an attempt at using a type marked as blocked, using block! or block_ns! ({})", tn.to_cpp_name())?,
             ConvertError::UnusedTemplateParam => write!(f, "This function or method uses a type where one of the template parameters was incomprehensible to bindgen/autocxx - probably because it uses template specialization.")?,
             ConvertError::TooManyUnderscores => write!(f, "Names containing __ are reserved by C++ so not acceptable to cxx")?,
             ConvertError::UnknownDependentType(qn) => write!(f, "This item relies on a type not known to autocxx ({})", qn.to_cpp_name())?,
@@ -107,11 +231,22 @@ impl Display for ConvertError {
             ConvertError::PrivateMethod => write!(f, "This method is private")?,
             ConvertError::AssignmentOperator => write!(f, "autocxx does not know how to generate bindings to operator=")?,
             ConvertError::Deleted => write!(f, "This function was marked =delete")?,
+            ConvertError::BlockedConstructor => write!(f, "This constructor was blocked using block_constructors!")?,
             ConvertError::RValueReferenceField => write!(f, "This structure has an rvalue reference field (&&) which is not yet supported.")?,
             ConvertError::MethodOfNonAllowlistedType => write!(f, "This type was not on the allowlist, so we are not generating methods for it.")?,
             ConvertError::MethodOfGenericType => write!(f, "This type is templated, so we can't generate bindings. We will instead generate bindings for each instantiation.")?,
+            ConvertError::MethodOfExternCppType => write!(f, "This type was declared using extern_cpp_type! to be an existing Rust type, to which we can't add methods.")?,
             ConvertError::DuplicateItemsFoundInParsing => write!(f, "bindgen generated multiple different APIs (functions/types) with this name. autocxx doesn't know how to diambiguate them, so we won't generate bindings for any of them.")?,
             ConvertError::ConstructorWithOnlyOneParam => write!(f, "bindgen generated a move or copy constructor with an unexpected number of parameters.")?,
+            ConvertError::FunctionPointer => write!(f, "Function pointers are only supported as struct fields, or typedefs used by struct fields")?,
+            ConvertError::ExcludedByCfg(item) => write!(f, "The cfg! directive for {} excludes it on the target being built", item)?,
+            ConvertError::UnexpectedIgnoredItems(items) => write!(f, "strict! was given, but bindings couldn't be generated for these items, which weren't acknowledged using expect_ignored!: {}", items.join(", "))?,
+            ConvertError::InternalLinkage(fn_name) => write!(f, "{} appears to be in an anonymous namespace, so has internal linkage and can't be called from the code generated by cxx. If it's defined in a header, use internal_linkage_shims!() to call it through a shim; otherwise, move it out of the anonymous namespace.", fn_name)?,
+            ConvertError::InvalidTraitImpl(ty, trait_path, problem) => write!(f, "impl_trait! can't implement {} for {}: {}", trait_path, ty, problem)?,
+            ConvertError::ReturnsOwnedNonPointer(fn_name) => write!(f, "returns_owned! was given for {}, but it doesn't return a non-const pointer to a class or struct, so there's nothing to put in a UniquePtr.", fn_name)?,
+            ConvertError::ConsumesNonPointer(fn_name, arg) => write!(f, "consumes! was given for parameter {} of {}, but there's no such parameter which is a non-const pointer to a class or struct, so it can't take a UniquePtr.", arg, fn_name)?,
+            ConvertError::MethodOfOpaqueOnlyType => write!(f, "This type was declared using opaque_only!, so we are not generating methods or constructors for it.")?,
+            ConvertError::Deprecated => write!(f, "This function is deprecated, and deprecation_policy!(skip) asked us not to generate bindings for deprecated functions.")?,
         }
         Ok(())
     }
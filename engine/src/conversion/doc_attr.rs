@@ -6,7 +6,10 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use syn::Attribute;
+use autocxx_parser::IncludeCppConfig;
+use syn::{parse_quote, Attribute, Lit, Meta, MetaNameValue};
+
+use super::api::SourceLocation;
 
 /// Returns the attribute (if any) which contains a doc comment.
 pub(super) fn get_doc_attr(attrs: &[Attribute]) -> Option<Attribute> {
@@ -15,3 +18,99 @@ pub(super) fn get_doc_attr(attrs: &[Attribute]) -> Option<Attribute> {
         .find(|a| a.path.get_ident().iter().any(|p| *p == "doc"))
         .cloned()
 }
+
+/// Combines documentation given in a directive, e.g.
+/// `generate!("Widget", doc = "...")`, with the doc comment (if any)
+/// which bindgen found in the C++ header. The directive's documentation
+/// comes first.
+pub(super) fn merge_doc_attr(doc: &str, cpp_doc_attr: Option<&Attribute>) -> Attribute {
+    let doc = match cpp_doc_attr.and_then(doc_text) {
+        Some(cpp_doc) => format!("{}\n\n{}", doc, cpp_doc),
+        None => doc.to_string(),
+    };
+    parse_quote! {
+        #[doc = #doc]
+    }
+}
+
+/// Adds a paragraph of our own after the doc comment (if any) which
+/// bindgen found in the C++ header.
+pub(super) fn append_doc_attr(cpp_doc_attr: Option<&Attribute>, doc: &str) -> Attribute {
+    let doc = match cpp_doc_attr.and_then(doc_text) {
+        Some(cpp_doc) => format!("{}\n\n{}", cpp_doc, doc),
+        None => doc.to_string(),
+    };
+    parse_quote! {
+        #[doc = #doc]
+    }
+}
+
+fn doc_text(attr: &Attribute) -> Option<String> {
+    match attr.parse_meta() {
+        Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(doc), ..
+        })) => Some(doc.value()),
+        _ => None,
+    }
+}
+
+/// As [`merge_doc_attr`], but replaces the doc comment within a list of
+/// attributes.
+pub(super) fn merge_doc_attrs(doc: &str, attrs: &mut Vec<Attribute>) {
+    let cpp_doc_attr = attrs
+        .iter()
+        .position(|a| a.path.is_ident("doc"))
+        .map(|idx| attrs.remove(idx));
+    attrs.insert(0, merge_doc_attr(doc, cpp_doc_attr.as_ref()));
+}
+
+/// As [`append_doc_attr`], but replaces the doc comment within a list of
+/// attributes.
+pub(super) fn append_doc_attrs(attrs: &mut Vec<Attribute>, doc: &str) {
+    let cpp_doc_attr = attrs
+        .iter()
+        .position(|a| a.path.is_ident("doc"))
+        .map(|idx| attrs.remove(idx));
+    attrs.insert(0, append_doc_attr(cpp_doc_attr.as_ref(), doc));
+}
+
+/// A note of which of the headers named in `include_cpp!` declared an
+/// item, if there are several, for its documentation.
+pub(super) fn header_attribution(
+    config: &IncludeCppConfig,
+    source_location: Option<&SourceLocation>,
+) -> Option<String> {
+    source_location
+        .and_then(|loc| loc.included_header(config))
+        .map(|header| format!("Declared in `{}`.", header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_doc_attrs;
+    use quote::quote;
+    use syn::{parse_quote, Attribute};
+
+    #[test]
+    fn test_merge_doc_attrs() {
+        let mut attrs: Vec<Attribute> = vec![
+            parse_quote! { #[repr(C)] },
+            parse_quote! { #[doc = " From C++"] },
+        ];
+        merge_doc_attrs("From the directive", &mut attrs);
+        assert_eq!(
+            quote! { #(#attrs)* }.to_string(),
+            quote! {
+                #[doc = "From the directive\n\n From C++"]
+                #[repr(C)]
+            }
+            .to_string()
+        );
+        let mut attrs = Vec::new();
+        merge_doc_attrs("From the directive", &mut attrs);
+        assert_eq!(
+            quote! { #(#attrs)* }.to_string(),
+            quote! { #[doc = "From the directive"] }.to_string()
+        );
+    }
+}
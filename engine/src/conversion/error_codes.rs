@@ -0,0 +1,483 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Extended explanations of each [`ConvertError`](super::ConvertError),
+//! keyed by the code returned by `ConvertError::code`, for
+//! `autocxx-gen --explain`.
+
+/// Each error code, and an explanation of what it means and how to
+/// work around it. Codes are never reused or renumbered.
+static EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "ACX0001",
+        "bindgen generated no bindings at all from the headers given in include_cpp!.
+
+This usually means that none of the items named in generate! or generate_pod!
+directives exist in the headers. Check that each #include is found on the
+include path, and that each item is named with its full C++ namespace,
+e.g. generate!(\"base::Widget\").",
+    ),
+    (
+        "ACX0002",
+        "A type was requested using generate_pod!, but isn't safe to hold by value
+in Rust.
+
+Types held by value in Rust must be trivially movable: Rust moves values by
+copying their bytes, so they can't have a user-defined copy or move
+constructor, or a destructor, and can't contain any type which does. The
+message says which part of the type is the problem.
+
+Use generate! instead, and hold the type in a UniquePtr or using moveit. If
+you control the C++, consider removing the non-trivial member.",
+    ),
+    (
+        "ACX0003",
+        "bindgen generated something unexpected within an extern \"C\" block.
+
+This is a limitation of autocxx. Try generating bindings for fewer items to
+narrow down which one causes this, and please report it as an issue, with
+a minimized test case if possible (see tools/reduce).",
+    ),
+    (
+        "ACX0004",
+        "bindgen generated something unexpected at the top level of its output.
+
+This is a limitation of autocxx. Try generating bindings for fewer items to
+narrow down which one causes this, and please report it as an issue, with
+a minimized test case if possible (see tools/reduce).",
+    ),
+    (
+        "ACX0005",
+        "bindgen generated something unexpected within a C++ namespace.
+
+This is a limitation of autocxx. Try generating bindings for fewer items to
+narrow down which one causes this, and please report it as an issue, with
+a minimized test case if possible (see tools/reduce).",
+    ),
+    (
+        "ACX0006",
+        "A typedef points to a type too complex for autocxx to represent, such as
+a templated type with unusual arguments.
+
+Anything using this typedef can't have bindings generated. Consider writing
+a C++ wrapper function which avoids the typedef, or a simpler typedef.",
+    ),
+    (
+        "ACX0007",
+        "A method's 'this' parameter, as reported by bindgen, had an unexpected
+type.
+
+This is usually caused by template specialization which bindgen couldn't
+understand. Write a free C++ function which takes a reference to the object
+and calls the method, and generate bindings for that instead.",
+    ),
+    (
+        "ACX0008",
+        "A built-in C++ type, such as something within std::, was used which
+autocxx doesn't know how to represent.
+
+autocxx supports a fixed set of standard library types (e.g. std::string,
+std::unique_ptr, std::vector). Write C++ wrapper functions which convert to
+or from a supported type, or declare the type using extern_cpp_opaque_type!
+if you only need to pass it around by reference. Please raise an issue if
+you think the type should be supported.",
+    ),
+    (
+        "ACX0009",
+        "A typedef has template arguments, and so does the type it points to.
+
+autocxx can't combine the two. Use the underlying type directly, or a
+typedef without template arguments.",
+    ),
+    (
+        "ACX0010",
+        "A function has a parameter or return type which is either on the
+blocklist, or is only forward declared.
+
+Remove the type from the blocklist, or include the header which fully
+defines it. If it's deliberately incomplete, declare it using
+extern_cpp_opaque_type! and pass it by reference or pointer.",
+    ),
+    (
+        "ACX0011",
+        "A function returns a reference, but takes either no reference parameters,
+or more than one, so Rust can't infer the lifetime of the returned reference.
+
+Write a C++ wrapper function which returns by value, or which takes exactly
+one reference parameter from which the return value is derived. If the
+reference is to a static, consider returning a pointer instead.",
+    ),
+    (
+        "ACX0012",
+        "A type was encountered which autocxx doesn't yet support, for example an
+array parameter or a pointer to a pointer.
+
+Write a C++ wrapper function which uses simpler types, and generate bindings
+for that instead.",
+    ),
+    (
+        "ACX0013",
+        "A type was encountered which autocxx doesn't recognize at all.
+
+This usually means bindgen produced something unusual for a complex C++
+type. Write a C++ wrapper function which uses simpler types. Please raise
+an issue if this type seems straightforward.",
+    ),
+    (
+        "ACX0014",
+        "A mutable static or global variable was encountered.
+
+autocxx can't safely expose mutable static data to Rust. Write C++ getter
+and setter functions, and generate bindings for those.",
+    ),
+    (
+        "ACX0015",
+        "A typedef, or a chain of typedefs, leads back to itself, so has no
+underlying type.
+
+This is usually the result of a bindgen bug, e.g. with typedefs of
+anonymous types. Block one of the typedefs using block!, or give the
+underlying type a name.",
+    ),
+    (
+        "ACX0016",
+        "No longer used. Cycles of typedefs are reported as ACX0015.",
+    ),
+    (
+        "ACX0017",
+        "bindgen generated an unexpected 'use' statement.
+
+This is a limitation of autocxx. Please report it as an issue, with a
+minimized test case if possible (see tools/reduce).",
+    ),
+    (
+        "ACX0018",
+        "A templated type is parameterized by something other than a simple type,
+e.g. a function type or a constant expression.
+
+autocxx can only instantiate templates over plain types. Write a typedef
+or C++ wrapper which hides the complex parameter.",
+    ),
+    (
+        "ACX0019",
+        "A pointer or reference points to something unsupported, such as another
+pointer or a function.
+
+Write a C++ wrapper function with a simpler signature.",
+    ),
+    (
+        "ACX0020",
+        "A generate! or generate_pod! directive didn't result in any bindings.
+
+Perhaps the item is mis-spelled, lacks its namespace qualification (it
+should be e.g. \"base::Widget\"), or isn't declared in any of the included
+headers. Otherwise, look for other errors about the same item, which may
+explain why it couldn't be generated.",
+    ),
+    (
+        "ACX0021",
+        "A forward-declared type was used inside a templated cxx type, such as
+UniquePtr or CxxVector.
+
+Those types need the complete C++ type, e.g. to call its destructor.
+Include the header which fully defines the type, or, if it will be complete
+wherever the C++ compiler needs it to be, declare it using
+extern_cpp_opaque_type!.",
+    ),
+    (
+        "ACX0022",
+        "An item uses a type which has been blocked using block!, or which is
+in a namespace blocked using block_ns!.
+
+Anything mentioning a blocked type is skipped. Remove the block! or
+block_ns! directive if you need this item.",
+    ),
+    (
+        "ACX0023",
+        "A type has a template parameter which bindgen couldn't understand,
+typically because of template specialization.
+
+Use a typedef naming a specific instantiation, or write a C++ wrapper which
+uses a concrete type.",
+    ),
+    (
+        "ACX0024",
+        "A name contains '__', which is reserved in C++ and so rejected by cxx.
+
+Rename the item, or write a C++ wrapper with a different name and generate
+bindings for that.",
+    ),
+    (
+        "ACX0025",
+        "An item relies on a type which autocxx doesn't know about.
+
+The type may be in a header which isn't included, or may itself have been
+skipped. Look for other errors about that type.",
+    ),
+    (
+        "ACX0026",
+        "An item depends on other types for which bindings couldn't be generated.
+
+Look for the errors reported against those types, which are named in the
+message; fixing those will usually fix this too.",
+    ),
+    (
+        "ACX0027",
+        "An item's name is a reserved word in Rust, such as 'type' or 'match'.
+
+Rename it in C++, or write a C++ wrapper with a different name.",
+    ),
+    (
+        "ACX0028",
+        "The same name is used in several C++ namespaces. At present cxx, and so
+autocxx, allows only one item of a given name in certain contexts.
+
+Generate bindings for only one of them, or write a C++ wrapper with a
+distinct name.",
+    ),
+    (
+        "ACX0029",
+        "A method belongs to a type which can't be used as 'self' in Rust,
+usually because the type involves template specialization.
+
+Write a free C++ function which takes the object by reference and calls
+the method, and generate bindings for that instead.",
+    ),
+    (
+        "ACX0030",
+        "A rust::Box<T> was used where T isn't known to be a Rust type.
+
+Declare T using rust_type!, or use extern_rust_type on its Rust definition.",
+    ),
+    (
+        "ACX0031",
+        "A Rust type was named with a path, i.e. containing '::'.
+
+Rust types used from C++ must be simple identifiers. Import the type with a
+'use' statement so it can be named directly.",
+    ),
+    (
+        "ACX0032",
+        "A type is nested within another class, and is abstract (or autocxx can't
+tell whether it's abstract, because it's not on the allowlist).
+
+Nested abstract types aren't yet supported. If the type isn't abstract,
+add it to the allowlist using generate!.",
+    ),
+    (
+        "ACX0033",
+        "A type is nested within another class with protected or private
+visibility, so can't be used from outside that class.
+
+Make it public in C++, if you control the code.",
+    ),
+    (
+        "ACX0034",
+        "A function takes an rvalue reference (&&) parameter, which isn't yet
+supported.
+
+Write a C++ wrapper which takes the parameter by value or by reference,
+and moves from it.",
+    ),
+    (
+        "ACX0035",
+        "A function returns an rvalue reference (&&), which isn't yet supported.
+
+Write a C++ wrapper which returns by value instead.",
+    ),
+    (
+        "ACX0036",
+        "A method is private, so can't be called from outside the class.
+
+This is expected; no action is needed unless you need to call the method,
+in which case make it public or add a public wrapper method.",
+    ),
+    (
+        "ACX0037",
+        "autocxx doesn't generate bindings for operator=.
+
+Use the generated copy or move constructors instead, or write a named C++
+method which performs the assignment.",
+    ),
+    (
+        "ACX0038",
+        "A function was deleted using '= delete', so can't be called.
+
+This is expected and needs no action.",
+    ),
+    (
+        "ACX0039",
+        "A constructor was blocked using block_constructors!.
+
+This is expected and needs no action. Remove the directive if you need the
+constructor.",
+    ),
+    (
+        "ACX0040",
+        "A struct has an rvalue reference (&&) field, which isn't yet supported.
+
+Hold the struct in a UniquePtr and access the field through C++ wrapper
+functions.",
+    ),
+    (
+        "ACX0041",
+        "A method belongs to a type which isn't on the allowlist, so bindings for
+its methods aren't generated.
+
+Add the type using generate! if you need its methods.",
+    ),
+    (
+        "ACX0042",
+        "A method belongs to a templated type, so can't have bindings generated
+directly.
+
+Bindings are instead generated for each instantiation of the template which
+is used by a generated API. Use a typedef naming the instantiation you need,
+and generate! that.",
+    ),
+    (
+        "ACX0043",
+        "A method belongs to a type declared using extern_cpp_type! (or a
+TypeMapper) to be an existing Rust type, to which autocxx can't add methods.
+
+Implement the method in Rust on that type, or write a free C++ function
+which calls it and generate bindings for that.",
+    ),
+    (
+        "ACX0044",
+        "bindgen generated several different items with the same name, and
+autocxx can't tell which is which, so generates none of them.
+
+This can happen with overloads which differ only in ways bindgen doesn't
+report. Write C++ wrappers with distinct names.",
+    ),
+    (
+        "ACX0045",
+        "bindgen reported a copy or move constructor with an unexpected number of
+parameters, e.g. because of default arguments.
+
+Write a C++ wrapper function which makes the copy or move, if you need it.",
+    ),
+    (
+        "ACX0046",
+        "A function pointer was used somewhere other than a struct field, or a
+typedef used by a struct field.
+
+cxx can't pass function pointers between the languages. Consider using a
+Rust closure via a subclass or extern_rust_function, or a C++ std::function
+wrapper.",
+    ),
+    (
+        "ACX0047",
+        "A cfg! directive excludes this item on the target being built.
+
+This is expected and needs no action.",
+    ),
+    (
+        "ACX0048",
+        "strict! was given in include_cpp!, but bindings couldn't be generated
+for some items, and those items weren't acknowledged using expect_ignored!.
+
+The reason for each item is reported separately. Fix the problem, or, if the
+item can't be bound, acknowledge it using e.g.
+expect_ignored!(\"ns::Widget::method\", \"takes a std::variant\"), or
+silence that kind of problem entirely using silence_error!.",
+    ),
+    (
+        "ACX0049",
+        "This function is in an anonymous namespace, so it has internal linkage:
+each translation unit which includes the header gets its own private copy,
+which can't be called from any other translation unit, including the one
+cxx generates.
+
+If the function is defined in the header, give internal_linkage_shims!() in
+include_cpp! and autocxx will call it through a shim compiled alongside the
+rest of its generated C++. Otherwise, move the function out of the anonymous
+namespace.",
+    ),
+    (
+        "ACX0050",
+        "An impl_trait! directive couldn't be implemented. Only std::io::Write,
+std::io::Read, std::fmt::Display and IntoIterator are supported, and each
+method of the trait must be mapped to a method of the type with a compatible
+signature:
+
+  std::io::Write: write = a method taking (const uint8_t*, size_t) and
+                  returning size_t; flush = a method taking no parameters
+                  and returning void.
+  std::io::Read:  read = a method taking (uint8_t*, size_t) and returning
+                  size_t.
+  std::fmt::Display: fmt = a const method taking no parameters and
+                  returning a std::string.
+  IntoIterator:   len = a const method taking no parameters and returning
+                  size_t; get = a const method taking a size_t and
+                  returning a const reference.
+
+The methods must themselves be generated, so check that the type is
+generated and that autocxx didn't report a problem with the method.",
+    ),
+    (
+        "ACX0051",
+        "returns_owned! was given for a function, promising that it returns an
+owning raw pointer which should be wrapped in a UniquePtr, but the function
+doesn't return a non-const pointer to a class or struct, e.g. Widget*.
+
+We don't generate bindings for the function in case the promise reflects a
+misunderstanding of its ownership semantics. Remove the returns_owned!
+directive if it was intended for a different function.",
+    ),
+    (
+        "ACX0052",
+        "consumes! was given for a function parameter, promising that it takes
+ownership of the object to which a raw pointer points, so that it may be
+passed a UniquePtr, but that parameter isn't a non-const pointer to a class
+or struct, e.g. Widget*. Parameters are numbered from 1, not counting the
+receiver of a method, so in void Sink::adopt(Widget* w) w is arg = 1.
+
+We don't generate bindings for the function in case the promise reflects a
+misunderstanding of its ownership semantics.",
+    ),
+    (
+        "ACX0053",
+        "A method or constructor belongs to a type declared using opaque_only!,
+so no bindings are generated for it. The type itself is still generated, so
+that other functions can take or return it.
+
+This is expected and needs no action. Remove the directive if you need the
+method.",
+    ),
+    (
+        "ACX0054",
+        "A function is marked [[deprecated]] in C++, and deprecation_policy!(skip)
+asks that no bindings are generated for deprecated functions.
+
+This is expected and needs no action. Use deprecation_policy!(warn) instead
+to generate bindings marked #[deprecated], so that using them from Rust
+gives a warning.",
+    ),
+];
+
+/// Returns the extended explanation of the given error code, if it's known.
+pub fn explain_error_code(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EXPLANATIONS;
+
+    #[test]
+    fn test_codes_are_sequential() {
+        for (i, (code, _)) in EXPLANATIONS.iter().enumerate() {
+            assert_eq!(*code, format!("ACX{:04}", i + 1));
+        }
+    }
+}
@@ -6,16 +6,114 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use autocxx_parser::IncludeCppConfig;
 use syn::ItemEnum;
 
 use super::{
-    api::{AnalysisPhase, Api, ApiName, FuncToConvert, StructDetails, TypedefKind},
+    api::{AnalysisPhase, Api, ApiName, FuncToConvert, SourceLocation, StructDetails, TypedefKind},
     apivec::ApiVec,
-    convert_error::{ConvertErrorWithContext, ErrorContext},
+    convert_error::{ConvertErrorWithContext, ErrorContext, Severity},
     ConvertError,
 };
 use crate::types::{Namespace, QualifiedName};
 
+/// A problem found while converting some item, to be reported to the
+/// user once conversion is complete.
+struct Problem {
+    /// The C++ name of the item, e.g. `ns::Widget` or `ns::Widget::method`,
+    /// if known.
+    item: Option<String>,
+    source_location: Option<SourceLocation>,
+    err: ConvertError,
+}
+
+thread_local! {
+    /// Problems found so far in the conversion happening on this thread.
+    /// The same problem is often found in several analysis phases, or for
+    /// several instantiations of a template, so these are gathered here
+    /// and deduplicated by [`report_problems`] rather than printed as found.
+    static PROBLEMS: RefCell<Vec<Problem>> = const { RefCell::new(Vec::new()) };
+}
+
+fn note_problem(item: Option<String>, source_location: Option<SourceLocation>, err: &ConvertError) {
+    PROBLEMS.with(|problems| {
+        problems.borrow_mut().push(Problem {
+            item,
+            source_location,
+            err: err.clone(),
+        })
+    });
+}
+
+/// Report each distinct problem found since this was last called, other
+/// than those silenced using `silence_error!` or acknowledged using
+/// `expect_ignored!`. They're logged, at `info` level if they need no
+/// action, or `warn` level otherwise. If `strict!` was given, fails if there were any other problems.
+pub(crate) fn report_problems(config: &IncludeCppConfig) -> Result<(), ConvertError> {
+    let problems = PROBLEMS.with(|problems| std::mem::take(&mut *problems.borrow_mut()));
+    let mut reported = HashSet::new();
+    let mut unexpected = Vec::new();
+    for problem in problems {
+        if config.is_error_silenced(problem.err.code())
+            || problem
+                .item
+                .as_ref()
+                .is_some_and(|item| config.is_ignored_item_expected(item))
+        {
+            continue;
+        }
+        let header = problem
+            .source_location
+            .as_ref()
+            .and_then(|loc| loc.included_header(config));
+        let msg = match (&problem.item, header) {
+            (Some(item), Some(header)) => {
+                format!("Ignored {} from {}: {}", item, header, problem.err)
+            }
+            (Some(item), None) => format!("Ignored {}: {}", item, problem.err),
+            (None, Some(header)) => format!("Ignored item from {}: {}", header, problem.err),
+            (None, None) => format!("Ignored item: {}", problem.err),
+        };
+        let msg = match problem.source_location {
+            Some(loc) => format!("{}: {}", loc, msg),
+            None => msg,
+        };
+        if !reported.insert(msg.clone()) {
+            continue;
+        }
+        match problem.err.severity() {
+            Severity::Info => log::info!("{}", msg),
+            severity => {
+                log::warn!("{}: {}", severity, msg);
+                unexpected.push(problem.item.unwrap_or_else(|| "<unknown>".into()));
+            }
+        }
+    }
+    if config.is_strict() && !unexpected.is_empty() {
+        unexpected.sort();
+        unexpected.dedup();
+        return Err(ConvertError::UnexpectedIgnoredItems(unexpected));
+    }
+    Ok(())
+}
+
+/// The C++ name of the item to which an error relates, e.g. `ns::Widget`
+/// or `ns::Widget::method`.
+fn describe_error_context(ns: &Namespace, ctx: &ErrorContext) -> Option<String> {
+    match ctx {
+        ErrorContext::Item(id) => Some(QualifiedName::new(ns, id.clone()).to_cpp_name()),
+        ErrorContext::Method { self_ty, method } => Some(format!(
+            "{}::{}",
+            QualifiedName::new(ns, self_ty.clone()).to_cpp_name(),
+            method
+        )),
+        ErrorContext::NoCode => None,
+    }
+}
+
 /// Run some code which may generate a ConvertError.
 /// If it does, try to note the problem in our output APIs
 /// such that users will see documentation of the error.
@@ -30,12 +128,12 @@ where
     match fun() {
         Ok(result) => Some(result),
         Err(ConvertErrorWithContext(err, None)) => {
-            eprintln!("Ignored item: {}", err);
+            note_problem(None, None, &err);
             None
         }
         Err(ConvertErrorWithContext(err, Some(ctx))) => {
-            eprintln!("Ignored item {}: {}", ctx, err);
-            if let Some(item) = ignored_item(ns, ctx, err) {
+            note_problem(describe_error_context(ns, &ctx), None, &err);
+            if let Some(item) = ignored_item(ns, ctx, err, None) {
                 apis.push(item);
             }
             None
@@ -46,7 +144,7 @@ where
 /// Run some code which generates an API. Add that API, or if
 /// anything goes wrong, instead add a note of the problem in our
 /// output API such that users will see documentation for the problem.
-pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
+pub(crate) fn convert_apis<FF, SF, EF, TF, A, B>(
     in_apis: ApiVec<A>,
     out_apis: &mut ApiVec<B>,
     mut func_conversion: FF,
@@ -55,7 +153,7 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
     mut typedef_conversion: TF,
 ) where
     A: AnalysisPhase,
-    B: AnalysisPhase,
+    B: AnalysisPhase + 'static,
     FF: FnMut(
         ApiName,
         Box<FuncToConvert>,
@@ -78,8 +176,9 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
         A::TypedefAnalysis,
     ) -> Result<Box<dyn Iterator<Item = Api<B>>>, ConvertErrorWithContext>,
 {
+    out_apis.reserve(in_apis.len());
     out_apis.extend(in_apis.into_iter().flat_map(|api| {
-        let tn = api.name().clone();
+        let tn = api.name_info().clone();
         let result: Result<Box<dyn Iterator<Item = Api<B>>>, ConvertErrorWithContext> = match api {
             // No changes to any of these...
             Api::ConcreteType {
@@ -100,15 +199,36 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
             Api::Const { name, const_item } => {
                 Ok(Box::new(std::iter::once(Api::Const { name, const_item })))
             }
+            Api::ExternCppType {
+                name,
+                rust_path,
+                layout,
+                cpp_shims,
+                rs_shims,
+            } => Ok(Box::new(std::iter::once(Api::ExternCppType {
+                name,
+                rust_path,
+                layout,
+                cpp_shims,
+                rs_shims,
+            }))),
             Api::CType { name, typename } => {
                 Ok(Box::new(std::iter::once(Api::CType { name, typename })))
             }
             Api::RustType { name, path } => {
                 Ok(Box::new(std::iter::once(Api::RustType { name, path })))
             }
-            Api::RustFn { name, sig, path } => {
-                Ok(Box::new(std::iter::once(Api::RustFn { name, sig, path })))
-            }
+            Api::RustFn {
+                name,
+                sig,
+                path,
+                is_factory,
+            } => Ok(Box::new(std::iter::once(Api::RustFn {
+                name,
+                sig,
+                path,
+                is_factory,
+            }))),
             Api::RustSubclassFn {
                 name,
                 subclass,
@@ -160,18 +280,30 @@ pub(crate) fn convert_apis<FF, SF, EF, TF, A, B: 'static>(
 }
 
 fn api_or_error<T: AnalysisPhase + 'static>(
-    name: QualifiedName,
+    name: ApiName,
     api_or_error: Result<Box<dyn Iterator<Item = Api<T>>>, ConvertErrorWithContext>,
 ) -> Box<dyn Iterator<Item = Api<T>>> {
     match api_or_error {
         Ok(opt) => opt,
-        Err(ConvertErrorWithContext(err, None)) => {
-            eprintln!("Ignored {}: {}", name, err);
-            Box::new(std::iter::empty())
-        }
-        Err(ConvertErrorWithContext(err, Some(ctx))) => {
-            eprintln!("Ignored {}: {}", name, err);
-            Box::new(ignored_item(name.get_namespace(), ctx, err).into_iter())
+        Err(ConvertErrorWithContext(err, ctx)) => {
+            let source_location = name.source_location().cloned();
+            // Methods are named after their type, rather than the name
+            // bindgen gave them.
+            let item = match &ctx {
+                Some(ErrorContext::Method { self_ty, .. }) => format!(
+                    "{}::{}",
+                    QualifiedName::new(name.name.get_namespace(), self_ty.clone()).to_cpp_name(),
+                    name.cpp_name()
+                ),
+                _ => name.qualified_cpp_name(),
+            };
+            note_problem(Some(item), source_location.clone(), &err);
+            Box::new(
+                ctx.and_then(|ctx| {
+                    ignored_item(name.name.get_namespace(), ctx, err, source_location)
+                })
+                .into_iter(),
+            )
         }
     }
 }
@@ -180,19 +312,17 @@ fn api_or_error<T: AnalysisPhase + 'static>(
 /// a method). Add that API, or if
 /// anything goes wrong, instead add a note of the problem in our
 /// output API such that users will see documentation for the problem.
-pub(crate) fn convert_item_apis<F, A, B: 'static>(
-    in_apis: ApiVec<A>,
-    out_apis: &mut ApiVec<B>,
-    mut fun: F,
-) where
+pub(crate) fn convert_item_apis<F, A, B>(in_apis: ApiVec<A>, out_apis: &mut ApiVec<B>, mut fun: F)
+where
     F: FnMut(Api<A>) -> Result<Box<dyn Iterator<Item = Api<B>>>, ConvertError>,
     A: AnalysisPhase,
-    B: AnalysisPhase,
+    B: AnalysisPhase + 'static,
 {
+    out_apis.reserve(in_apis.len());
     out_apis.extend(in_apis.into_iter().flat_map(|api| {
-        let tn = api.name().clone();
+        let tn = api.name_info().clone();
         let result = fun(api).map_err(|e| {
-            ConvertErrorWithContext(e, Some(ErrorContext::Item(tn.get_final_ident())))
+            ConvertErrorWithContext(e, Some(ErrorContext::Item(tn.name.get_final_ident())))
         });
         api_or_error(tn, result)
     }))
@@ -202,9 +332,10 @@ fn ignored_item<A: AnalysisPhase>(
     ns: &Namespace,
     ctx: ErrorContext,
     err: ConvertError,
+    source_location: Option<SourceLocation>,
 ) -> Option<Api<A>> {
     ctx.get_id().cloned().map(|id| Api::IgnoredItem {
-        name: ApiName::new(ns, id),
+        name: ApiName::new(ns, id).with_source_location(source_location),
         err,
         ctx,
     })
@@ -0,0 +1,94 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Libraries often version their ABI using inline namespaces, such as
+//! `std::__1` or `mylib::abi_v2`. C++ code needn't mention them, and
+//! bindgen doesn't either: their contents appear within the enclosing
+//! namespace. But users may well mention them in directives, having
+//! copied a name from a compiler error or a debugger, so we work out
+//! which namespaces they've named are inline, and forget about them.
+
+use std::collections::HashSet;
+
+/// Finds the namespaces named within `requested` items which must be
+/// inline: those which don't exist, within an enclosing namespace which
+/// does, and such that the item exists in that enclosing namespace.
+/// `known_items` and `known_namespaces` are as bindgen told us about them,
+/// i.e. without any inline namespaces.
+pub(crate) fn find_inline_namespaces(
+    requested: impl Iterator<Item = String>,
+    known_items: &HashSet<String>,
+    known_namespaces: &HashSet<String>,
+) -> Vec<String> {
+    let mut inline_namespaces = Vec::new();
+    for item in requested {
+        if item.contains('<') || known_items.contains(&item) {
+            continue;
+        }
+        let segments: Vec<&str> = item.split("::").collect();
+        // Never the outermost namespace, nor the item itself.
+        for i in 1..segments.len().saturating_sub(1) {
+            let enclosing = segments[..i].join("::");
+            let candidate = segments[..=i].join("::");
+            if !known_namespaces.contains(&enclosing) || known_namespaces.contains(&candidate) {
+                continue;
+            }
+            let without_candidate = segments[..i]
+                .iter()
+                .chain(&segments[i + 1..])
+                .copied()
+                .collect::<Vec<_>>()
+                .join("::");
+            if known_items.contains(&without_candidate) {
+                if !inline_namespaces.contains(&candidate) {
+                    log::info!(
+                        "Treating {} as an inline namespace, since {} exists",
+                        candidate,
+                        without_candidate
+                    );
+                    inline_namespaces.push(candidate);
+                }
+                break;
+            }
+        }
+    }
+    inline_namespaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_inline_namespaces;
+    use std::collections::HashSet;
+
+    fn set(items: &[&str]) -> HashSet<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_inline_namespaces() {
+        let known_items = set(&["std::string", "lib::Widget", "lib::detail::Gadget", "Goat"]);
+        let known_namespaces = set(&["std", "lib", "lib::detail"]);
+        let requested = [
+            "std::__1::string",
+            "lib::v2::Widget",
+            "lib::v2::detail::Gadget",
+            "lib::detail::Gadget",
+            "lib::v3::Missing",
+            "typo::Goat",
+            "std::__1::vector<int>",
+        ];
+        assert_eq!(
+            find_inline_namespaces(
+                requested.iter().map(|item| item.to_string()),
+                &known_items,
+                &known_namespaces
+            ),
+            vec!["std::__1".to_string(), "lib::v2".to_string()]
+        );
+    }
+}
@@ -9,25 +9,35 @@
 mod analysis;
 mod api;
 mod apivec;
+mod c_header;
 mod codegen_cpp;
 mod codegen_rs;
 #[cfg(test)]
 mod conversion_tests;
 mod convert_error;
 mod doc_attr;
+mod error_codes;
 mod error_reporter;
+mod inline_namespaces;
 mod parse;
+mod size_report;
+mod unwind;
 mod utilities;
 
 use analysis::fun::FnAnalyzer;
-use autocxx_parser::IncludeCppConfig;
+pub use analysis::type_converter::{TypeMapper, TypeMapping};
+use autocxx_parser::{IncludeCppConfig, Inclusion};
 pub(crate) use codegen_cpp::CppCodeGenerator;
 pub(crate) use convert_error::ConvertError;
+pub use error_codes::explain_error_code;
 use itertools::Itertools;
+pub(crate) use parse::{concretes_allowlist, concretes_header};
+pub use size_report::{SizeReport, SizeReportEntry};
 use syn::{Item, ItemMod};
 
 use crate::{
-    conversion::analysis::deps::HasDependencies, CppCodegenOptions, CppFilePair, UnsafePolicy,
+    conversion::analysis::deps::HasDependencies, source_locations::SourceLocations,
+    CppCodegenOptions, CppFilePair, UnsafePolicy,
 };
 
 use self::{
@@ -37,16 +47,22 @@ use self::{
         casts::add_casts,
         check_names,
         constructor_deps::decorate_types_with_constructor_deps,
+        field_accessors::add_field_accessors,
         fun::FnPhase,
         gc::filter_apis_by_following_edges_from_allowlist,
         pod::analyze_pod_apis,
+        refcounting::add_ref_counting,
         remove_ignored::filter_apis_by_ignored_dependents,
         tdef::convert_typedef_targets,
+        trait_impls::analyze_trait_impls,
     },
-    api::AnalysisPhase,
+    api::{AnalysisPhase, Api},
     apivec::ApiVec,
     codegen_rs::RsCodeGenerator,
-    parse::ParseBindgen,
+    error_reporter::report_problems,
+    parse::{ParseBindgen, ParsedBindings},
+    size_report::{ApiGraph, ApiSizes},
+    utilities::remove_unused_utilities,
 };
 
 const LOG_APIS: bool = true;
@@ -64,7 +80,7 @@ const LOG_APIS: bool = true;
 /// if the bindgen output is not as expected. It may be in future that
 /// we need to be a bit more graceful, but for now, that's OK.
 pub(crate) struct BridgeConverter<'a> {
-    include_list: &'a [String],
+    include_list: &'a [Inclusion],
     config: &'a IncludeCppConfig,
 }
 
@@ -72,10 +88,13 @@ pub(crate) struct BridgeConverter<'a> {
 pub(crate) struct CodegenResults {
     pub(crate) rs: Vec<Item>,
     pub(crate) cpp: Option<CppFilePair>,
+    /// A C header for those Rust functions which C can call, if requested.
+    pub(crate) c_header: Option<CppFilePair>,
+    pub(crate) size_report: Option<SizeReport>,
 }
 
 impl<'a> BridgeConverter<'a> {
-    pub fn new(include_list: &'a [String], config: &'a IncludeCppConfig) -> Self {
+    pub fn new(include_list: &'a [Inclusion], config: &'a IncludeCppConfig) -> Self {
         Self {
             include_list,
             config,
@@ -108,6 +127,61 @@ impl<'a> BridgeConverter<'a> {
         }
     }
 
+    /// Finds the inline namespaces, such as `std::__1`, named within
+    /// directives. bindgen tells us about their contents as if they were
+    /// declared in the enclosing namespace, so these need to be stripped
+    /// from the names in directives before conversion.
+    pub(crate) fn find_inline_namespaces(&self, source_locations: &SourceLocations) -> Vec<String> {
+        let known_items = source_locations.items.keys().cloned().collect();
+        inline_namespaces::find_inline_namespaces(
+            self.config.must_generate_list(),
+            &known_items,
+            &source_locations.namespaces,
+        )
+    }
+
+    /// Parses the bindings generated by bindgen, without yet insisting
+    /// that everything named in directives was found.
+    pub(crate) fn parse(
+        &self,
+        bindgen_mod: ItemMod,
+        cpp_codegen_options: &CppCodegenOptions,
+        source_locations: &SourceLocations,
+    ) -> Result<ParsedBindings, ConvertError> {
+        let parser = ParseBindgen::new(
+            self.config,
+            &cpp_codegen_options.type_mappers,
+            source_locations,
+        );
+        parser.parse_items(bindgen_mod).inspect_err(|_| {
+            // Tell the user about anything else which couldn't be parsed.
+            let _ = report_problems(self.config);
+        })
+    }
+
+    /// Finds the items declared in the headers named using
+    /// `generate_from_header!`, by the names which would be given to
+    /// `generate!`. Methods are covered by their type.
+    pub(crate) fn find_items_from_headers(&self, parsed: &ParsedBindings) -> Vec<String> {
+        parsed
+            .apis
+            .iter()
+            .filter(|api| {
+                api.name_info()
+                    .source_location()
+                    .is_some_and(|loc| self.config.is_header_to_generate(&loc.file))
+            })
+            .map(|api| match api {
+                Api::Function { fun, .. } => match &fun.self_ty {
+                    Some(self_ty) => self_ty.to_cpp_name(),
+                    None => api.name_info().qualified_cpp_name(),
+                },
+                _ => api.name().to_cpp_name(),
+            })
+            .unique()
+            .collect()
+    }
+
     /// Convert a TokenStream of bindgen-generated bindings to a form
     /// suitable for cxx.
     ///
@@ -116,89 +190,133 @@ impl<'a> BridgeConverter<'a> {
     /// up by the `syn` crate).
     pub(crate) fn convert(
         &self,
-        mut bindgen_mod: ItemMod,
+        parsed: ParsedBindings,
         unsafe_policy: UnsafePolicy,
         inclusions: String,
         cpp_codegen_options: &CppCodegenOptions,
     ) -> Result<CodegenResults, ConvertError> {
-        match &mut bindgen_mod.content {
-            None => Err(ConvertError::NoContent),
-            Some((_, items)) => {
-                // Parse the bindgen mod.
-                let items_to_process = items.drain(..).collect();
-                let parser = ParseBindgen::new(self.config);
-                let apis = parser.parse_items(items_to_process)?;
-                Self::dump_apis("parsing", &apis);
-                // Inside parse_results, we now have a list of APIs.
-                // We now enter various analysis phases.
-                // Next, convert any typedefs.
-                // "Convert" means replacing bindgen-style type targets
-                // (e.g. root::std::unique_ptr) with cxx-style targets (e.g. UniquePtr).
-                let apis = convert_typedef_targets(self.config, apis);
-                Self::dump_apis("typedefs", &apis);
-                // Now analyze which of them can be POD (i.e. trivial, movable, pass-by-value
-                // versus which need to be opaque).
-                // Specifically, let's confirm that the items requested by the user to be
-                // POD really are POD, and duly mark any dependent types.
-                // This returns a new list of `Api`s, which will be parameterized with
-                // the analysis results. It also returns an object which can be used
-                // by subsequent phases to work out which objects are POD.
-                let analyzed_apis = analyze_pod_apis(apis, self.config)?;
-                Self::dump_apis("pod analysis", &analyzed_apis);
-                let analyzed_apis = add_casts(analyzed_apis);
-                let analyzed_apis = create_alloc_and_frees(analyzed_apis);
-                // Next, figure out how we materialize different functions.
-                // Some will be simple entries in the cxx::bridge module; others will
-                // require C++ wrapper functions. This is probably the most complex
-                // part of `autocxx`. Again, this returns a new set of `Api`s, but
-                // parameterized by a richer set of metadata.
-                Self::dump_apis("adding casts", &analyzed_apis);
-                let analyzed_apis =
-                    FnAnalyzer::analyze_functions(analyzed_apis, unsafe_policy, self.config);
-                // If any of those functions turned out to be pure virtual, don't attempt
-                // to generate UniquePtr implementations for the type, since it can't
-                // be instantiated.
-                Self::dump_apis("analyze fns", &analyzed_apis);
-                let analyzed_apis = mark_types_abstract(analyzed_apis);
-                Self::dump_apis("marking abstract", &analyzed_apis);
-                // Annotate structs with a note of any copy/move constructors which
-                // we may want to retain to avoid garbage collecting them later.
-                let analyzed_apis = decorate_types_with_constructor_deps(analyzed_apis);
-                Self::dump_apis_with_deps("adding constructor deps", &analyzed_apis);
-                let analyzed_apis = discard_ignored_functions(analyzed_apis);
-                Self::dump_apis_with_deps("ignoring ignorable fns", &analyzed_apis);
-                // Remove any APIs whose names are not compatible with cxx.
-                let analyzed_apis = check_names(analyzed_apis);
-                // During parsing or subsequent processing we might have encountered
-                // items which we couldn't process due to as-yet-unsupported features.
-                // There might be other items depending on such things. Let's remove them
-                // too.
-                let analyzed_apis = filter_apis_by_ignored_dependents(analyzed_apis);
-                Self::dump_apis_with_deps("removing ignored dependents", &analyzed_apis);
-
-                // We now garbage collect the ones we don't need...
-                let mut analyzed_apis =
-                    filter_apis_by_following_edges_from_allowlist(analyzed_apis, self.config);
-                // Determine what variably-sized C types (e.g. int) we need to include
-                analysis::ctypes::append_ctype_information(&mut analyzed_apis);
-                Self::dump_apis_with_deps("GC", &analyzed_apis);
-                // And finally pass them to the code gen phases, which outputs
-                // code suitable for cxx to consume.
-                let cpp = CppCodeGenerator::generate_cpp_code(
-                    inclusions,
-                    &analyzed_apis,
-                    self.config,
-                    cpp_codegen_options,
-                )?;
-                let rs = RsCodeGenerator::generate_rs_code(
-                    analyzed_apis,
-                    self.include_list,
-                    bindgen_mod,
-                    self.config,
-                    cpp.as_ref().map(|file_pair| file_pair.header_name.clone()),
-                );
-                Ok(CodegenResults { rs, cpp })
-            }
-        }
+        let results = self.convert_items(parsed, unsafe_policy, inclusions, cpp_codegen_options);
+        // Whether or not that succeeded, tell the user about anything which
+        // couldn't be converted along the way.
+        let problems = report_problems(self.config);
+        results.and_then(|results| problems.map(|_| results))
+    }
+
+    fn convert_items(
+        &self,
+        parsed: ParsedBindings,
+        unsafe_policy: UnsafePolicy,
+        inclusions: String,
+        cpp_codegen_options: &CppCodegenOptions,
+    ) -> Result<CodegenResults, ConvertError> {
+        parsed.confirm_all_generate_directives_obeyed(self.config)?;
+        let ParsedBindings {
+            apis, bindgen_mod, ..
+        } = parsed;
+        Self::dump_apis("parsing", &apis);
+        // Inside parse_results, we now have a list of APIs.
+        // We now enter various analysis phases.
+        // Next, convert any typedefs.
+        // "Convert" means replacing bindgen-style type targets
+        // (e.g. root::std::unique_ptr) with cxx-style targets (e.g. UniquePtr).
+        let apis = convert_typedef_targets(self.config, apis);
+        Self::dump_apis("typedefs", &apis);
+        // Now analyze which of them can be POD (i.e. trivial, movable, pass-by-value
+        // versus which need to be opaque).
+        // Specifically, let's confirm that the items requested by the user to be
+        // POD really are POD, and duly mark any dependent types.
+        // This returns a new list of `Api`s, which will be parameterized with
+        // the analysis results. It also returns an object which can be used
+        // by subsequent phases to work out which objects are POD.
+        let analyzed_apis = analyze_pod_apis(apis, self.config)?;
+        Self::dump_apis("pod analysis", &analyzed_apis);
+        let analyzed_apis = add_casts(analyzed_apis);
+        let analyzed_apis = add_field_accessors(analyzed_apis);
+        let analyzed_apis = create_alloc_and_frees(analyzed_apis);
+        let analyzed_apis = add_ref_counting(analyzed_apis, self.config);
+        // Next, figure out how we materialize different functions.
+        // Some will be simple entries in the cxx::bridge module; others will
+        // require C++ wrapper functions. This is probably the most complex
+        // part of `autocxx`. Again, this returns a new set of `Api`s, but
+        // parameterized by a richer set of metadata.
+        Self::dump_apis("adding casts", &analyzed_apis);
+        let analyzed_apis =
+            FnAnalyzer::analyze_functions(analyzed_apis, unsafe_policy, self.config);
+        // If any of those functions turned out to be pure virtual, don't attempt
+        // to generate UniquePtr implementations for the type, since it can't
+        // be instantiated.
+        Self::dump_apis("analyze fns", &analyzed_apis);
+        let analyzed_apis = mark_types_abstract(analyzed_apis, self.config);
+        Self::dump_apis("marking abstract", &analyzed_apis);
+        // Annotate structs with a note of any copy/move constructors which
+        // we may want to retain to avoid garbage collecting them later.
+        let analyzed_apis = decorate_types_with_constructor_deps(analyzed_apis);
+        Self::dump_apis_with_deps("adding constructor deps", &analyzed_apis);
+        let analyzed_apis = discard_ignored_functions(analyzed_apis);
+        Self::dump_apis_with_deps("ignoring ignorable fns", &analyzed_apis);
+        // Remove any APIs whose names are not compatible with cxx.
+        let analyzed_apis = check_names(analyzed_apis);
+        // During parsing or subsequent processing we might have encountered
+        // items which we couldn't process due to as-yet-unsupported features.
+        // There might be other items depending on such things. Let's remove them
+        // too.
+        let analyzed_apis = filter_apis_by_ignored_dependents(analyzed_apis);
+        Self::dump_apis_with_deps("removing ignored dependents", &analyzed_apis);
+
+        // We now garbage collect the ones we don't need...
+        let analyzed_apis =
+            filter_apis_by_following_edges_from_allowlist(analyzed_apis, self.config);
+        // ... including any utilities which turned out to be unnecessary.
+        let analyzed_apis = remove_unused_utilities(analyzed_apis);
+        // Now we know which methods survived, check any traits the
+        // user asked us to implement using them.
+        let mut analyzed_apis = analyze_trait_impls(analyzed_apis, self.config)?;
+        // Determine what variably-sized C types (e.g. int) we need to include
+        analysis::ctypes::append_ctype_information(&mut analyzed_apis);
+        Self::dump_apis_with_deps("GC", &analyzed_apis);
+        // If asked, note which APIs each requested item needed, so
+        // we can attribute the generated code to them.
+        let api_graph = cpp_codegen_options
+            .size_report
+            .then(|| ApiGraph::new(&analyzed_apis, self.config));
+        let mut api_sizes = api_graph.as_ref().map(|_| ApiSizes::default());
+        // And finally pass them to the code gen phases, which outputs
+        // code suitable for cxx to consume.
+        let cpp = CppCodeGenerator::generate_cpp_code(
+            inclusions,
+            &analyzed_apis,
+            self.config,
+            cpp_codegen_options,
+            api_sizes.as_mut(),
+        )?;
+        let rs = RsCodeGenerator::generate_rs_code(
+            analyzed_apis,
+            self.include_list,
+            bindgen_mod,
+            self.config,
+            cpp.as_ref().map(|file_pair| file_pair.header_name.clone()),
+            api_sizes.as_mut(),
+            cpp_codegen_options.c_header,
+        );
+        let c_header = if cpp_codegen_options.c_header {
+            let cpp_header_name = cpp_codegen_options
+                .header_namer
+                .name_header(self.config.get_mod_name().to_string());
+            c_header::generate_c_header(
+                self.config,
+                format!("{}_c.h", cpp_header_name.trim_end_matches(".h")),
+            )
+        } else {
+            None
+        };
+        let size_report = api_graph
+            .zip(api_sizes)
+            .map(|(graph, sizes)| SizeReport::new(&graph, &sizes));
+        Ok(CodegenResults {
+            rs,
+            cpp,
+            c_header,
+            size_report,
+        })
     }
 }
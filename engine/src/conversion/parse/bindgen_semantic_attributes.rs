@@ -14,7 +14,7 @@ use syn::{
 };
 
 use crate::conversion::{
-    api::{CppVisibility, Layout, References, SpecialMemberKind, Virtualness},
+    api::{CppVisibility, Deprecation, Layout, References, SpecialMemberKind, Virtualness},
     convert_error::{ConvertErrorWithContext, ErrorContext},
     ConvertError,
 };
@@ -110,6 +110,14 @@ impl BindgenSemanticAttributes {
         self.parse_if_present("layout")
     }
 
+    /// Whether the item is marked `[[deprecated]]`, and with what message.
+    pub(super) fn get_deprecation(&self) -> Option<Deprecation> {
+        self.0.iter().find(|a| a.is_ident("deprecated")).map(|a| {
+            let message: Option<LitStr> = a.body.as_ref().and_then(|_| a.parse_args().ok());
+            Deprecation(message.map(|ls| ls.value()))
+        })
+    }
+
     /// The original C++ name, which bindgen may have changed.
     pub(super) fn get_original_name(&self) -> Option<String> {
         self.string_if_present("original_name")
@@ -0,0 +1,136 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `concrete!` asks for an instantiation of a class template, such as
+//! `Factory<Widget>`, together with its static member functions. bindgen
+//! doesn't tell us about the members of templates, so in the header which
+//! only bindgen sees, we declare a typedef for the instantiation, and a
+//! struct with a static member function of the same type as each of the
+//! template's:
+//!
+//! ```cpp
+//! namespace autocxx_concrete {
+//! typedef Factory<Widget> FactoryWidget;
+//! namespace statics {
+//! struct FactoryWidget {
+//!     static decltype(Factory<Widget>::create) create;
+//! };
+//! }
+//! }
+//! ```
+//!
+//! We then generate the typedef as a concrete type in the root namespace,
+//! and the struct's static member functions as static methods of that
+//! type. The generated C++ calls them as `FactoryWidget::create()`, using
+//! the typedef which it declares for any concrete type.
+
+use autocxx_parser::IncludeCppConfig;
+
+use crate::source_locations::SourceLocations;
+
+pub(super) const CONCRETES_NAMESPACE: &str = "autocxx_concrete";
+pub(super) const STATICS_NAMESPACE: &str = "statics";
+
+/// Declarations to be appended to the header passed to bindgen for the
+/// instantiations named using `concrete!`. Overloaded static member
+/// functions are omitted, since `decltype` can't choose between them.
+pub(crate) fn concretes_header(
+    config: &IncludeCppConfig,
+    source_locations: &SourceLocations,
+) -> String {
+    let concretes = config.get_concretes();
+    if concretes.is_empty() {
+        return String::new();
+    }
+    let mut header = format!("namespace {} {{\n", CONCRETES_NAMESPACE);
+    for (cpp_type, rust_id) in concretes {
+        header.push_str(&format!("typedef {} {};\n", cpp_type, rust_id));
+    }
+    header.push_str(&format!("namespace {} {{\n", STATICS_NAMESPACE));
+    for (cpp_type, rust_id) in concretes {
+        header.push_str(&format!("struct {} {{\n", rust_id));
+        for method in source_locations.get_template_static_methods(template_name(cpp_type)) {
+            header.push_str(&format!(
+                "    static decltype({}::{}) {};\n",
+                cpp_type, method, method
+            ));
+        }
+        header.push_str("};\n");
+    }
+    header.push_str("}\n}\n");
+    header
+}
+
+/// Items to be added to the allowlist passed to bindgen for the
+/// declarations in [`concretes_header`].
+pub(crate) fn concretes_allowlist(config: &IncludeCppConfig) -> impl Iterator<Item = String> + '_ {
+    config.get_concretes().iter().flat_map(|(_, rust_id)| {
+        [
+            format!("{}::{}", CONCRETES_NAMESPACE, rust_id),
+            format!(
+                "{}::{}::{}",
+                CONCRETES_NAMESPACE, STATICS_NAMESPACE, rust_id
+            ),
+        ]
+    })
+}
+
+/// The name of the class template, e.g. `ns::Factory` for
+/// `::ns::Factory<Widget>`.
+fn template_name(cpp_type: &str) -> &str {
+    let name = cpp_type.split('<').next().unwrap_or_default().trim();
+    name.strip_prefix("::").unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concretes_header, template_name};
+    use crate::source_locations::{SourceLocation, SourceLocations};
+    use autocxx_parser::IncludeCppConfig;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_template_name() {
+        assert_eq!(template_name("Factory<Widget>"), "Factory");
+        assert_eq!(template_name("::ns::Factory< ns::Widget >"), "ns::Factory");
+    }
+
+    #[test]
+    fn test_concretes_header() {
+        let config: IncludeCppConfig = parse_quote! {
+            concrete!("Factory<Widget>", FactoryWidget)
+        };
+        let location = |line| SourceLocation {
+            file: "input.h".to_string(),
+            line,
+        };
+        let source_locations = SourceLocations {
+            items: [
+                ("Factory::create".to_string(), vec![location(1)]),
+                ("Factory::make".to_string(), vec![location(2), location(3)]),
+            ]
+            .into_iter()
+            .collect(),
+            namespaces: Default::default(),
+            template_static_methods: ["Factory::create".to_string(), "Factory::make".to_string()]
+                .into_iter()
+                .collect(),
+        };
+        assert_eq!(
+            concretes_header(&config, &source_locations),
+            "namespace autocxx_concrete {\n\
+             typedef Factory<Widget> FactoryWidget;\n\
+             namespace statics {\n\
+             struct FactoryWidget {\n    \
+             static decltype(Factory<Widget>::create) create;\n\
+             };\n\
+             }\n\
+             }\n"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::conversion::api::Layout;
+
+/// The layout of one of Eigen's fixed-size vector or matrix typedefs, such
+/// as `Eigen::Vector3f` or `Eigen::Matrix4d`. bindgen can't tell us this,
+/// since these are typedefs of templates with non-type parameters. Eigen
+/// aligns fixed-size types to 16 bytes wherever their size is a multiple
+/// of 16, so that it can vectorize them, which is the default for SSE and
+/// NEON. We check our idea of the layout in the generated C++, in case
+/// Eigen has been configured otherwise.
+pub(super) fn eigen_layout(cpp_name: &str) -> Option<Layout> {
+    let name = cpp_name.strip_prefix("Eigen::")?;
+    let (kind, dim_and_scalar) = if let Some(rest) = name.strip_prefix("Vector") {
+        (Kind::Vector, rest)
+    } else if let Some(rest) = name.strip_prefix("Matrix") {
+        (Kind::Matrix, rest)
+    } else {
+        return None;
+    };
+    let (dim, scalar_size) = match dim_and_scalar.as_bytes() {
+        [dim @ b'2'..=b'4', b'f'] => (dim - b'0', 4),
+        [dim @ b'2'..=b'4', b'd'] => (dim - b'0', 8),
+        _ => return None,
+    };
+    let elements = match kind {
+        Kind::Vector => dim as usize,
+        Kind::Matrix => (dim * dim) as usize,
+    };
+    let size = elements * scalar_size;
+    Some(Layout {
+        size,
+        align: if size % 16 == 0 { 16 } else { scalar_size },
+        packed: false,
+    })
+}
+
+enum Kind {
+    Vector,
+    Matrix,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::eigen_layout;
+
+    #[test]
+    fn test_eigen_layout() {
+        let layout = |name| eigen_layout(name).map(|layout| (layout.size, layout.align));
+        assert_eq!(layout("Eigen::Vector3f"), Some((12, 4)));
+        assert_eq!(layout("Eigen::Vector4f"), Some((16, 16)));
+        assert_eq!(layout("Eigen::Vector3d"), Some((24, 8)));
+        assert_eq!(layout("Eigen::Matrix3f"), Some((36, 4)));
+        assert_eq!(layout("Eigen::Matrix4d"), Some((128, 16)));
+        assert_eq!(layout("Eigen::VectorXf"), None);
+        assert_eq!(layout("Eigen::Vector5f"), None);
+        assert_eq!(layout("Vector3f"), None);
+    }
+}
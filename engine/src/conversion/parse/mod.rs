@@ -7,8 +7,11 @@
 // except according to those terms.
 
 mod bindgen_semantic_attributes;
+mod concretes;
+mod eigen;
 mod parse_bindgen;
 mod parse_foreign_mod;
 
 pub(crate) use bindgen_semantic_attributes::BindgenSemanticAttributes;
-pub(crate) use parse_bindgen::ParseBindgen;
+pub(crate) use concretes::{concretes_allowlist, concretes_header};
+pub(crate) use parse_bindgen::{ParseBindgen, ParsedBindings};
@@ -6,13 +6,13 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     conversion::{
         api::{Api, ApiName, NullPhase, StructDetails, SubclassName, TypedefKind, UnanalyzedApi},
         apivec::ApiVec,
-        ConvertError,
+        ConvertError, TypeMapper, TypeMapping,
     },
     types::Namespace,
     types::QualifiedName,
@@ -20,15 +20,24 @@ use crate::{
 use crate::{
     conversion::{
         convert_error::{ConvertErrorWithContext, ErrorContext},
+        doc_attr::{append_doc_attrs, header_attribution, merge_doc_attrs},
         error_reporter::report_any_error,
     },
-    types::validate_ident_ok_for_cxx,
+    source_locations::SourceLocations,
+    target_cfg::excluded_for_target,
+    types::{unmangle_keyword, validate_ident_ok_for_cxx},
 };
 use autocxx_parser::IncludeCppConfig;
-use syn::{parse_quote, Fields, Ident, Item, TypePath, UseTree};
+use syn::{
+    parse_quote, Attribute, Expr, Fields, Ident, ImplItem, Item, ItemConst, ItemEnum, ItemMod,
+    Type, TypePath, UseTree,
+};
 
 use super::{
-    super::utilities::generate_utilities, bindgen_semantic_attributes::BindgenSemanticAttributes,
+    super::{codegen_cpp::type_to_cpp::type_to_cpp, utilities::generate_utilities},
+    bindgen_semantic_attributes::BindgenSemanticAttributes,
+    concretes::{CONCRETES_NAMESPACE, STATICS_NAMESPACE},
+    eigen::eigen_layout,
 };
 
 use super::parse_foreign_mod::ParseForeignMod;
@@ -36,32 +45,59 @@ use super::parse_foreign_mod::ParseForeignMod;
 /// Parses a bindgen mod in order to understand the APIs within it.
 pub(crate) struct ParseBindgen<'a> {
     config: &'a IncludeCppConfig,
+    type_mappers: &'a [Box<dyn TypeMapper + 'a>],
+    source_locations: &'a SourceLocations,
     apis: ApiVec<NullPhase>,
+    /// Namespaces which contain an anonymous namespace.
+    anonymous_namespaces: HashSet<Namespace>,
+    /// Items brought into a namespace by using-declarations such as
+    /// `using lib::v2::foo;`: the namespace, and the path to the item.
+    reexports: Vec<(Namespace, TypePath)>,
 }
 
-fn api_name(ns: &Namespace, id: Ident, attrs: &BindgenSemanticAttributes) -> ApiName {
-    ApiName::new_with_cpp_name(ns, id, attrs.get_original_name())
+/// An enumerator which aliases another: its attributes, its name, and the
+/// name of the enumerator it aliases.
+type EnumAlias = (Vec<Attribute>, Ident, Ident);
+
+fn api_name(
+    ns: &Namespace,
+    id: Ident,
+    attrs: &BindgenSemanticAttributes,
+    source_locations: &SourceLocations,
+) -> ApiName {
+    let name = ApiName::new_with_cpp_name(ns, id, attrs.get_original_name());
+    let source_location = source_locations.get(&name.qualified_cpp_name(), 0);
+    name.with_source_location(source_location)
 }
 
 pub(crate) fn api_name_qualified(
     ns: &Namespace,
     id: Ident,
     attrs: &BindgenSemanticAttributes,
+    source_locations: &SourceLocations,
 ) -> Result<ApiName, ConvertErrorWithContext> {
     match validate_ident_ok_for_cxx(&id.to_string()) {
         Err(e) => {
             let ctx = ErrorContext::Item(id);
             Err(ConvertErrorWithContext(e, Some(ctx)))
         }
-        Ok(..) => Ok(api_name(ns, id, attrs)),
+        Ok(..) => Ok(api_name(ns, id, attrs, source_locations)),
     }
 }
 
 impl<'a> ParseBindgen<'a> {
-    pub(crate) fn new(config: &'a IncludeCppConfig) -> Self {
+    pub(crate) fn new(
+        config: &'a IncludeCppConfig,
+        type_mappers: &'a [Box<dyn TypeMapper + 'a>],
+        source_locations: &'a SourceLocations,
+    ) -> Self {
         ParseBindgen {
             config,
+            type_mappers,
+            source_locations,
             apis: ApiVec::new(),
+            anonymous_namespaces: HashSet::new(),
+            reexports: Vec::new(),
         }
     }
 
@@ -69,17 +105,85 @@ impl<'a> ParseBindgen<'a> {
     /// `Api`s together with some other data.
     pub(crate) fn parse_items(
         mut self,
-        items: Vec<Item>,
-    ) -> Result<ApiVec<NullPhase>, ConvertError> {
+        mut bindgen_mod: ItemMod,
+    ) -> Result<ParsedBindings, ConvertError> {
+        // Take the items, rather than draining them into a new Vec,
+        // so that bindgen_mod doesn't hold on to their allocation.
+        let items = match &mut bindgen_mod.content {
+            Some((_, items)) => std::mem::take(items),
+            None => return Err(ConvertError::NoContent),
+        };
+        self.parse_all_items(items)?;
+        Ok(ParsedBindings {
+            apis: self.apis,
+            bindgen_mod,
+            anonymous_namespaces: self.anonymous_namespaces,
+        })
+    }
+
+    fn parse_all_items(&mut self, items: Vec<Item>) -> Result<(), ConvertError> {
         let items = Self::find_items_in_root(items)?;
         if !self.config.exclude_utilities() {
             generate_utilities(&mut self.apis, self.config);
         }
         self.add_apis_from_config();
         let root_ns = Namespace::new();
-        self.parse_mod_items(items, root_ns);
-        self.confirm_all_generate_directives_obeyed()?;
-        Ok(self.apis)
+        self.parse_mod_items(items, root_ns, false);
+        self.add_reexports();
+        Ok(())
+    }
+
+    /// Makes the items named by using-declarations available in the
+    /// namespaces into which they're brought. A function (including all
+    /// its overloads) is generated afresh in that namespace, since C++ can
+    /// call it by that name too; anything else becomes an alias of the
+    /// original, like a typedef.
+    fn add_reexports(&mut self) {
+        for (ns, old_path) in std::mem::take(&mut self.reexports) {
+            let old_name = QualifiedName::from_type_path(&old_path);
+            let functions: Vec<_> = self
+                .apis
+                .iter()
+                .filter_map(|api| match api {
+                    Api::Function {
+                        name,
+                        name_for_gc,
+                        fun,
+                        ..
+                    } if fun.self_ty.is_none()
+                        && name.qualified_cpp_name() == old_name.to_cpp_name() =>
+                    {
+                        let mut name = name.clone();
+                        name.name = QualifiedName::new(&ns, name.name.get_final_ident());
+                        let mut fun = fun.clone();
+                        fun.reexported_from = Some(old_name.get_namespace().clone());
+                        Some(Api::Function {
+                            name,
+                            name_for_gc: name_for_gc.clone(),
+                            fun,
+                            analysis: (),
+                        })
+                    }
+                    _ => None,
+                })
+                .collect();
+            if functions.is_empty() {
+                let id = old_name.get_final_ident();
+                self.apis
+                    .push_eliminating_duplicates(UnanalyzedApi::Typedef {
+                        name: ApiName::new(&ns, id.clone()),
+                        item: TypedefKind::Use(parse_quote! {
+                            pub use #old_path as #id;
+                        }),
+                        old_tyname: Some(old_name),
+                        analysis: (),
+                    });
+            } else {
+                for function in functions {
+                    self.apis.push_eliminating_duplicates(function);
+                }
+            }
+        }
     }
 
     /// Some API items are not populated from bindgen output, but instead
@@ -97,6 +201,7 @@ impl<'a> ParseBindgen<'a> {
                     name: ApiName::new_in_root_namespace(id),
                     path: fun.path.clone(),
                     sig: fun.sig.clone(),
+                    is_factory: fun.is_factory,
                 }
             }));
         self.apis.extend(self.config.rust_types.iter().map(|path| {
@@ -128,18 +233,22 @@ impl<'a> ParseBindgen<'a> {
 
     /// Interpret the bindgen-generated .rs for a particular
     /// mod, which corresponds to a C++ namespace.
-    fn parse_mod_items(&mut self, items: Vec<Item>, ns: Namespace) {
+    fn parse_mod_items(&mut self, items: Vec<Item>, ns: Namespace, internal_linkage: bool) {
+        // Namespaces excluded using block_ns! contribute nothing at all.
+        if !ns.is_empty() && self.config.is_in_blocked_namespace(&ns.to_string()) {
+            return;
+        }
         // This object maintains some state specific to this namespace, i.e.
         // this particular mod.
-        let mut mod_converter = ParseForeignMod::new(ns.clone());
+        let mut mod_converter = ParseForeignMod::new(ns.clone(), internal_linkage);
         let mut more_apis = ApiVec::new();
-        for item in items {
+        for item in Self::merge_duplicate_enumerators(items) {
             report_any_error(&ns, &mut more_apis, || {
                 self.parse_item(item, &mut mod_converter, &ns)
             });
         }
         self.apis.append(&mut more_apis);
-        mod_converter.finished(&mut self.apis);
+        mod_converter.finished(&mut self.apis, self.config, self.source_locations);
     }
 
     fn parse_item(
@@ -153,17 +262,40 @@ impl<'a> ParseBindgen<'a> {
                 mod_converter.convert_foreign_mod_items(fm.items);
                 Ok(())
             }
-            Item::Struct(s) => {
+            Item::Struct(mut s) => {
                 if s.ident.to_string().ends_with("__bindgen_vtable") {
                     return Ok(());
                 }
-                let is_forward_declaration = Self::spot_forward_declaration(&s.fields);
+                // As for functions, we name fields which are Rust keywords
+                // using raw identifiers rather than bindgen's `move_`.
+                for field in s.fields.iter_mut() {
+                    if let Some(id) = field.ident.as_ref().and_then(unmangle_keyword) {
+                        field.ident = Some(id);
+                    }
+                }
                 let annotations = BindgenSemanticAttributes::new(&s.attrs);
                 // cxx::bridge can't cope with type aliases to generic
                 // types at the moment.
-                let name = api_name_qualified(ns, s.ident.clone(), &annotations)?;
+                let name =
+                    api_name_qualified(ns, s.ident.clone(), &annotations, self.source_locations)?;
+                if let Some(doc) = self.config.get_doc(&name.name.to_cpp_name()) {
+                    merge_doc_attrs(doc, &mut s.attrs);
+                }
+                if let Some(doc) = header_attribution(self.config, name.source_location()) {
+                    append_doc_attrs(&mut s.attrs, &doc);
+                }
+                let is_forward_declaration = Self::spot_forward_declaration(&s.fields)
+                    || self.config.is_opaque_type(&name.name.to_cpp_name());
                 let api = if ns.is_empty() && self.config.is_rust_type(&s.ident) {
                     None
+                } else if let Some(mapping) = self.find_type_mapping(&name.name.to_cpp_name()) {
+                    Some(UnanalyzedApi::ExternCppType {
+                        name,
+                        rust_path: mapping.rust_type,
+                        layout: annotations.get_layout(),
+                        cpp_shims: mapping.cpp_shims,
+                        rs_shims: mapping.rs_shims,
+                    })
                 } else if is_forward_declaration {
                     Some(UnanalyzedApi::ForwardDeclaration { name })
                 } else {
@@ -182,19 +314,39 @@ impl<'a> ParseBindgen<'a> {
                     })
                 };
                 if let Some(api) = api {
-                    if !self.config.is_on_blocklist(&api.name().to_cpp_name()) {
+                    if !self.is_excluded(&api.name().to_cpp_name()) {
                         self.apis.push_eliminating_duplicates(api);
                     }
                 }
                 Ok(())
             }
-            Item::Enum(e) => {
+            Item::Enum(mut e) => {
+                if let Some(const_items) = Self::anonymous_enum_constants(&e) {
+                    for const_item in const_items {
+                        let annotations = BindgenSemanticAttributes::new(&const_item.attrs);
+                        self.apis.push_eliminating_duplicates(UnanalyzedApi::Const {
+                            name: api_name(
+                                ns,
+                                const_item.ident.clone(),
+                                &annotations,
+                                self.source_locations,
+                            ),
+                            const_item,
+                        });
+                    }
+                    return Ok(());
+                }
                 let annotations = BindgenSemanticAttributes::new(&e.attrs);
-                let api = UnanalyzedApi::Enum {
-                    name: api_name_qualified(ns, e.ident.clone(), &annotations)?,
-                    item: e,
-                };
-                if !self.config.is_on_blocklist(&api.name().to_cpp_name()) {
+                let name =
+                    api_name_qualified(ns, e.ident.clone(), &annotations, self.source_locations)?;
+                if let Some(doc) = self.config.get_doc(&name.name.to_cpp_name()) {
+                    merge_doc_attrs(doc, &mut e.attrs);
+                }
+                if let Some(doc) = header_attribution(self.config, name.source_location()) {
+                    append_doc_attrs(&mut e.attrs, &doc);
+                }
+                let api = UnanalyzedApi::Enum { name, item: e };
+                if !self.is_excluded(&api.name().to_cpp_name()) {
                     self.apis.push_eliminating_duplicates(api);
                 }
                 Ok(())
@@ -212,8 +364,17 @@ impl<'a> ParseBindgen<'a> {
             }
             Item::Mod(itm) => {
                 if let Some((_, items)) = itm.content {
-                    let new_ns = ns.push(itm.ident.to_string());
-                    self.parse_mod_items(items, new_ns);
+                    if itm.ident.to_string().starts_with("_bindgen_mod_") {
+                        // An anonymous namespace. Its contents can be named
+                        // as if they were in the enclosing namespace.
+                        self.anonymous_namespaces.insert(ns.clone());
+                        self.parse_mod_items(items, ns.clone(), true);
+                    } else if ns.is_empty() && itm.ident == CONCRETES_NAMESPACE {
+                        self.parse_concretes(items)?;
+                    } else {
+                        let new_ns = ns.push(itm.ident.to_string());
+                        self.parse_mod_items(items, new_ns, false);
+                    }
                 }
                 Ok(())
             }
@@ -228,6 +389,32 @@ impl<'a> ParseBindgen<'a> {
                         }
                         UseTree::Name(un) if un.ident == "root" => break, // we do not add this to any API since we generate equivalent
                         // use statements in our codegen phase.
+                        UseTree::Name(un) if segs.len() > 2 => {
+                            // A using-declaration such as `using lib::v2::foo;`.
+                            // We don't yet know what it names, so deal with it
+                            // once we've seen everything.
+                            assert!(segs.remove(0) == "self", "Path didn't start with self");
+                            assert!(
+                                segs.remove(0) == "super",
+                                "Path didn't start with self::super"
+                            );
+                            let id = &un.ident;
+                            let old_path: TypePath = parse_quote! {
+                                #(#segs)::* :: #id
+                            };
+                            let tyname = QualifiedName::new(ns, id.clone());
+                            if QualifiedName::from_type_path(&old_path) == tyname {
+                                return Err(ConvertErrorWithContext(
+                                    ConvertError::InfinitelyRecursiveTypedef(vec![
+                                        tyname.clone(),
+                                        tyname,
+                                    ]),
+                                    Some(ErrorContext::Item(id.clone())),
+                                ));
+                            }
+                            self.reexports.push((ns.clone(), old_path));
+                            break;
+                        }
                         UseTree::Rename(urn) => {
                             let old_id = &urn.ident;
                             let new_id = &urn.rename;
@@ -247,14 +434,22 @@ impl<'a> ParseBindgen<'a> {
                             let old_tyname = QualifiedName::from_type_path(&old_path);
                             if new_tyname == old_tyname {
                                 return Err(ConvertErrorWithContext(
-                                    ConvertError::InfinitelyRecursiveTypedef(new_tyname),
+                                    ConvertError::InfinitelyRecursiveTypedef(vec![
+                                        new_tyname.clone(),
+                                        new_tyname,
+                                    ]),
                                     Some(ErrorContext::Item(new_id.clone())),
                                 ));
                             }
                             let annotations = BindgenSemanticAttributes::new(&use_item.attrs);
                             self.apis
                                 .push_eliminating_duplicates(UnanalyzedApi::Typedef {
-                                    name: api_name(ns, new_id.clone(), &annotations),
+                                    name: api_name(
+                                        ns,
+                                        new_id.clone(),
+                                        &annotations,
+                                        self.source_locations,
+                                    ),
                                     item: TypedefKind::Use(parse_quote! {
                                         pub use #old_path as #new_id;
                                     }),
@@ -276,18 +471,38 @@ impl<'a> ParseBindgen<'a> {
             Item::Const(const_item) => {
                 let annotations = BindgenSemanticAttributes::new(&const_item.attrs);
                 self.apis.push_eliminating_duplicates(UnanalyzedApi::Const {
-                    name: api_name(ns, const_item.ident.clone(), &annotations),
+                    name: api_name(
+                        ns,
+                        const_item.ident.clone(),
+                        &annotations,
+                        self.source_locations,
+                    ),
                     const_item,
                 });
                 Ok(())
             }
             Item::Type(ity) => {
                 let annotations = BindgenSemanticAttributes::new(&ity.attrs);
+                let name = api_name(ns, ity.ident.clone(), &annotations, self.source_locations);
+                let cpp_name = name.name.to_cpp_name();
+                if let Some(mapping) = self.find_type_mapping(&cpp_name) {
+                    // bindgen doesn't tell us the layout of typedefs, but we
+                    // know it for Eigen's fixed-size types.
+                    self.apis
+                        .push_eliminating_duplicates(UnanalyzedApi::ExternCppType {
+                            name,
+                            rust_path: mapping.rust_type,
+                            layout: eigen_layout(&cpp_name),
+                            cpp_shims: mapping.cpp_shims,
+                            rs_shims: mapping.rs_shims,
+                        });
+                    return Ok(());
+                }
                 // It's known that sometimes bindgen will give us duplicate typedefs with the
                 // same name - see test_issue_264.
                 self.apis
                     .push_eliminating_duplicates(UnanalyzedApi::Typedef {
-                        name: api_name(ns, ity.ident.clone(), &annotations),
+                        name,
                         item: TypedefKind::Type(ity),
                         old_tyname: None,
                         analysis: (),
@@ -301,21 +516,209 @@ impl<'a> ParseBindgen<'a> {
         }
     }
 
+    /// Parses the declarations made for `concrete!`: see the `concretes`
+    /// module. Each typedef becomes a concrete type, and each struct's
+    /// static member functions become its static methods.
+    fn parse_concretes(&mut self, items: Vec<Item>) -> Result<(), ConvertErrorWithContext> {
+        for item in items {
+            match item {
+                Item::Type(ity) => {
+                    let cpp_definition = type_to_cpp(&ity.ty, &HashMap::new()).map_err(|err| {
+                        ConvertErrorWithContext(err, Some(ErrorContext::Item(ity.ident.clone())))
+                    })?;
+                    self.apis.push(UnanalyzedApi::ConcreteType {
+                        name: ApiName::new_in_root_namespace(ity.ident),
+                        rs_definition: ity.ty,
+                        cpp_definition,
+                    });
+                }
+                Item::Mod(itm) if itm.ident == STATICS_NAMESPACE => {
+                    // Methods found here are attributed to the type of the
+                    // same name in the root namespace, i.e. the concrete
+                    // type.
+                    let mut mod_converter = ParseForeignMod::new(Namespace::new(), false);
+                    for item in itm.content.into_iter().flat_map(|(_, items)| items) {
+                        match item {
+                            Item::ForeignMod(fm) => {
+                                mod_converter.convert_foreign_mod_items(fm.items)
+                            }
+                            Item::Impl(imp) => mod_converter.convert_impl_items(imp),
+                            _ => {}
+                        }
+                    }
+                    mod_converter.finished(&mut self.apis, self.config, self.source_locations);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// bindgen represents any enumerator which shares its value with an
+    /// earlier enumerator as an associated constant, e.g.
+    /// `impl A { pub const B: A = A::C; }`, since a Rust enum can't have
+    /// two variants with the same discriminant. Fold such constants back
+    /// into the enum as variants; we later generate such an enum as a
+    /// newtype instead.
+    fn merge_duplicate_enumerators(items: Vec<Item>) -> Vec<Item> {
+        let enum_ids: HashSet<Ident> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Enum(e) => Some(e.ident.clone()),
+                _ => None,
+            })
+            .collect();
+        let mut aliases: HashMap<Ident, Vec<EnumAlias>> = HashMap::new();
+        let mut items: Vec<Item> = items
+            .into_iter()
+            .filter(|item| match Self::enum_aliases(item, &enum_ids) {
+                Some((enum_id, these_aliases)) => {
+                    aliases.entry(enum_id).or_default().extend(these_aliases);
+                    false
+                }
+                None => true,
+            })
+            .collect();
+        for item in items.iter_mut() {
+            if let Item::Enum(e) = item {
+                for (attrs, alias, target) in aliases.remove(&e.ident).unwrap_or_default() {
+                    let discriminant = e
+                        .variants
+                        .iter()
+                        .find(|v| v.ident == target)
+                        .and_then(|v| v.discriminant.clone());
+                    if let Some((_, discriminant)) = discriminant {
+                        e.variants.push(parse_quote! {
+                            #(#attrs)*
+                            #alias = #discriminant
+                        });
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    /// If this is an impl block containing only aliases of enumerators of
+    /// one of the given enums, returns the enum and the aliases.
+    fn enum_aliases(item: &Item, enum_ids: &HashSet<Ident>) -> Option<(Ident, Vec<EnumAlias>)> {
+        let imp = match item {
+            Item::Impl(imp) if imp.trait_.is_none() => imp,
+            _ => return None,
+        };
+        let enum_id = match imp.self_ty.as_ref() {
+            Type::Path(typ) => typ.path.get_ident().filter(|id| enum_ids.contains(id))?,
+            _ => return None,
+        };
+        let aliases = imp
+            .items
+            .iter()
+            .map(|impl_item| match impl_item {
+                ImplItem::Const(c) => match &c.expr {
+                    Expr::Path(p) if p.path.segments.len() == 2 => Some((
+                        c.attrs.clone(),
+                        c.ident.clone(),
+                        p.path.segments.last().unwrap().ident.clone(),
+                    )),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some((enum_id.clone(), aliases))
+    }
+
+    /// There's no way to refer to an anonymous enum, e.g.
+    /// `enum { FLAG_A = 1, FLAG_B = 2 };`, so instead of generating it
+    /// as a Rust enum we represent each enumerator as a constant of the
+    /// underlying type. Returns `None` if this isn't an anonymous enum.
+    fn anonymous_enum_constants(e: &ItemEnum) -> Option<Vec<ItemConst>> {
+        if !e.ident.to_string().contains("_bindgen_ty_") {
+            return None;
+        }
+        let repr: Ident = e
+            .attrs
+            .iter()
+            .find(|attr| attr.path.is_ident("repr"))?
+            .parse_args()
+            .ok()?;
+        e.variants
+            .iter()
+            .map(|v| {
+                let (_, discriminant) = v.discriminant.as_ref()?;
+                let ident = &v.ident;
+                let doc_attrs = v.attrs.iter().filter(|attr| attr.path.is_ident("doc"));
+                Some(parse_quote! {
+                    #(#doc_attrs)*
+                    pub const #ident: #repr = #discriminant;
+                })
+            })
+            .collect()
+    }
+
+    /// Finds the existing Rust type, if any, which should represent this
+    /// C++ type: either given by the user using `extern_cpp_type!`, or by
+    /// a [`TypeMapper`].
+    fn find_type_mapping(&self, cpp_name: &str) -> Option<TypeMapping> {
+        match self.config.get_extern_cpp_type(cpp_name) {
+            Some(rust_path) => Some(TypeMapping::new(rust_path.clone())),
+            None => self
+                .type_mappers
+                .iter()
+                .find_map(|mapper| mapper.map_type(cpp_name)),
+        }
+    }
+
     fn spot_forward_declaration(s: &Fields) -> bool {
         s.iter()
             .filter_map(|f| f.ident.as_ref())
             .any(|id| id == "_unused")
     }
 
-    fn confirm_all_generate_directives_obeyed(&self) -> Result<(), ConvertError> {
+    /// Whether this type should be skipped because it's on the blocklist,
+    /// or because it's only wanted on other targets.
+    fn is_excluded(&self, cpp_name: &str) -> bool {
+        self.config.is_on_blocklist(cpp_name) || excluded_for_target(self.config, cpp_name)
+    }
+}
+
+/// The `Api`s found in the bindgen output, before any analysis.
+pub(crate) struct ParsedBindings {
+    pub(crate) apis: ApiVec<NullPhase>,
+    /// The bindgen mod, emptied of its items.
+    pub(crate) bindgen_mod: ItemMod,
+    /// Namespaces which contain an anonymous namespace.
+    anonymous_namespaces: HashSet<Namespace>,
+}
+
+impl ParsedBindings {
+    /// Checks that everything which the user asked for was found.
+    pub(crate) fn confirm_all_generate_directives_obeyed(
+        &self,
+        config: &IncludeCppConfig,
+    ) -> Result<(), ConvertError> {
         let api_names: HashSet<_> = self
             .apis
             .iter()
             .map(|api| api.name().to_cpp_name())
             .collect();
-        for generate_directive in self.config.must_generate_list() {
-            if !api_names.contains(&generate_directive) {
-                return Err(ConvertError::DidNotGenerateAnything(generate_directive));
+        for generate_directive in config.must_generate_list() {
+            // Items which only exist on other platforms are likely to
+            // be absent entirely.
+            if !api_names.contains(&generate_directive)
+                && !excluded_for_target(config, &generate_directive)
+            {
+                // bindgen doesn't report functions with internal linkage,
+                // so if there's an anonymous namespace where this would
+                // be, that's the likeliest explanation.
+                let ns = QualifiedName::new_from_cpp_name(&generate_directive)
+                    .get_namespace()
+                    .clone();
+                return Err(if self.anonymous_namespaces.contains(&ns) {
+                    ConvertError::InternalLinkage(generate_directive)
+                } else {
+                    ConvertError::DidNotGenerateAnything(generate_directive)
+                });
             }
         }
         Ok(())
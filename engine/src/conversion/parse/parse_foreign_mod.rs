@@ -6,9 +6,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::conversion::api::{ApiName, NullPhase, Provenance};
+use crate::conversion::api::{ApiName, NullPhase, Provenance, SourceLocation};
 use crate::conversion::apivec::ApiVec;
-use crate::conversion::doc_attr::get_doc_attr;
+use crate::conversion::doc_attr::{
+    append_doc_attr, get_doc_attr, header_attribution, merge_doc_attr,
+};
 use crate::conversion::error_reporter::report_any_error;
 use crate::conversion::{
     api::{FuncToConvert, UnanalyzedApi},
@@ -17,8 +19,11 @@ use crate::conversion::{
 };
 use crate::{
     conversion::ConvertError,
+    source_locations::SourceLocations,
     types::{Namespace, QualifiedName},
 };
+use autocxx_parser::IncludeCppConfig;
+use itertools::Itertools;
 use std::collections::HashMap;
 use syn::{Block, Expr, ExprCall, ForeignItem, Ident, ImplItem, ItemImpl, Stmt, Type};
 
@@ -29,6 +34,9 @@ use super::bindgen_semantic_attributes::BindgenSemanticAttributes;
 /// a specific C++ namespace.
 pub(crate) struct ParseForeignMod {
     ns: Namespace,
+    /// Whether this mod is an anonymous namespace, whose functions have
+    /// internal linkage.
+    internal_linkage: bool,
     // We mostly act upon the functions we see within the 'extern "C"'
     // block of bindgen output, but we can't actually do this until
     // we've seen the (possibly subsequent) 'impl' blocks so we can
@@ -43,9 +51,10 @@ pub(crate) struct ParseForeignMod {
 }
 
 impl ParseForeignMod {
-    pub(crate) fn new(ns: Namespace) -> Self {
+    pub(crate) fn new(ns: Namespace, internal_linkage: bool) -> Self {
         Self {
             ns,
+            internal_linkage,
             funcs_to_convert: Vec::new(),
             method_receivers: HashMap::new(),
             ignored_apis: ApiVec::new(),
@@ -88,6 +97,10 @@ impl ParseForeignMod {
                     add_to_trait: None,
                     is_deleted: annotations.has_attr("deleted"),
                     synthetic_cpp: None,
+                    internal_linkage: false,
+                    source_location: None,
+                    deprecation: annotations.get_deprecation(),
+                    reexported_from: None,
                 });
                 Ok(())
             }
@@ -126,17 +139,57 @@ impl ParseForeignMod {
     /// Indicate that all foreign mods and all impl blocks have been
     /// fed into us, and we should process that information to generate
     /// the resulting APIs.
-    pub(crate) fn finished(mut self, apis: &mut ApiVec<NullPhase>) {
+    pub(crate) fn finished(
+        mut self,
+        apis: &mut ApiVec<NullPhase>,
+        config: &IncludeCppConfig,
+        source_locations: &SourceLocations,
+    ) {
         apis.append(&mut self.ignored_apis);
         while !self.funcs_to_convert.is_empty() {
             let mut fun = self.funcs_to_convert.remove(0);
             fun.self_ty = self.method_receivers.get(&fun.ident).cloned();
+            fun.source_location = self.find_source_location(&fun, source_locations);
+            if self.internal_linkage {
+                if !config.generate_internal_linkage_shims() {
+                    let ctx = match &fun.self_ty {
+                        Some(self_ty) => ErrorContext::Method {
+                            self_ty: self_ty.get_final_ident(),
+                            method: fun.ident.clone(),
+                        },
+                        None => ErrorContext::Item(fun.ident.clone()),
+                    };
+                    let name = QualifiedName::new(&self.ns, fun.ident.clone()).to_cpp_name();
+                    report_any_error(&self.ns, apis, || -> Result<(), _> {
+                        Err(ConvertErrorWithContext(
+                            ConvertError::InternalLinkage(name),
+                            Some(ctx),
+                        ))
+                    });
+                    continue;
+                }
+                fun.internal_linkage = true;
+            }
+            if fun.self_ty.is_none() {
+                let cpp_name = fun
+                    .original_name
+                    .clone()
+                    .unwrap_or_else(|| fun.ident.to_string());
+                let cpp_name = self.ns.iter().chain(std::iter::once(&cpp_name)).join("::");
+                if let Some(doc) = config.get_doc(&cpp_name) {
+                    fun.doc_attr = Some(merge_doc_attr(doc, fun.doc_attr.as_ref()));
+                }
+                if let Some(doc) = header_attribution(config, fun.source_location.as_ref()) {
+                    fun.doc_attr = Some(append_doc_attr(fun.doc_attr.as_ref(), &doc));
+                }
+            }
             apis.push_eliminating_duplicates(UnanalyzedApi::Function {
                 name: ApiName::new_with_cpp_name(
                     &self.ns,
                     fun.ident.clone(),
                     fun.original_name.clone(),
-                ),
+                )
+                .with_source_location(fun.source_location.clone()),
                 fun: Box::new(fun),
                 analysis: (),
                 name_for_gc: None,
@@ -145,6 +198,28 @@ impl ParseForeignMod {
     }
 }
 
+impl ParseForeignMod {
+    /// Where this function was declared. Overloads are told apart by the
+    /// number which bindgen appended to their names, e.g. `A_foo1`.
+    fn find_source_location(
+        &self,
+        fun: &FuncToConvert,
+        source_locations: &SourceLocations,
+    ) -> Option<SourceLocation> {
+        let ident = fun.ident.to_string();
+        let cpp_name = fun.original_name.as_ref().unwrap_or(&ident);
+        let overload = ident
+            .rfind(cpp_name.as_str())
+            .and_then(|pos| ident[pos + cpp_name.len()..].parse().ok())
+            .unwrap_or(0);
+        let qualified_name = match &fun.self_ty {
+            Some(self_ty) => format!("{}::{}", self_ty.to_cpp_name(), cpp_name),
+            None => self.ns.iter().chain(std::iter::once(cpp_name)).join("::"),
+        };
+        source_locations.get(&qualified_name, overload)
+    }
+}
+
 /// bindgen sometimes generates an impl fn called a which calls
 /// a function called a1(), if it's dealing with conflicting names.
 /// We actually care about the name a1, so we have to parse the
@@ -0,0 +1,240 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use autocxx_parser::IncludeCppConfig;
+use proc_macro2::{TokenStream, TokenTree};
+
+use crate::types::QualifiedName;
+
+use super::{
+    analysis::{deps::HasDependencies, fun::FnPhase},
+    apivec::ApiVec,
+};
+
+/// The code generated for a single API, not including anything it
+/// depends upon.
+#[derive(Default, Clone)]
+struct ApiSize {
+    rs_items: usize,
+    rs_tokens: usize,
+    cpp_bytes: usize,
+}
+
+/// Records how much code is generated for each API, for the benefit of
+/// a [`SizeReport`].
+#[derive(Default)]
+pub(crate) struct ApiSizes(HashMap<QualifiedName, ApiSize>);
+
+impl ApiSizes {
+    pub(crate) fn record_rs(&mut self, name: &QualifiedName, items: Vec<TokenStream>) {
+        let size = self.0.entry(name.clone()).or_default();
+        size.rs_items += items.len();
+        size.rs_tokens += items.into_iter().map(count_tokens).sum::<usize>();
+    }
+
+    pub(crate) fn record_cpp(&mut self, name: &QualifiedName, bytes: usize) {
+        self.0.entry(name.clone()).or_default().cpp_bytes += bytes;
+    }
+}
+
+fn count_tokens(ts: TokenStream) -> usize {
+    ts.into_iter()
+        .map(|tt| match tt {
+            TokenTree::Group(group) => 1 + count_tokens(group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// The items requested by the user, and the APIs each one needs, as
+/// followed by garbage collection.
+pub(crate) struct ApiGraph {
+    roots: HashMap<String, Vec<QualifiedName>>,
+    deps: HashMap<QualifiedName, Vec<QualifiedName>>,
+    /// Which of the headers named in `include_cpp!` declared each
+    /// requested item, if there are several.
+    headers: HashMap<String, String>,
+}
+
+impl ApiGraph {
+    pub(crate) fn new(apis: &ApiVec<FnPhase>, config: &IncludeCppConfig) -> Self {
+        let mut roots: HashMap<String, Vec<QualifiedName>> = HashMap::new();
+        let mut headers = HashMap::new();
+        for api in apis.iter() {
+            let requested = api.typename_for_allowlist().to_cpp_name();
+            if config.is_on_allowlist(&requested) {
+                if let Some(header) = api
+                    .name_info()
+                    .source_location()
+                    .and_then(|loc| loc.included_header(config))
+                {
+                    headers.insert(requested.clone(), header.to_string());
+                }
+                roots.entry(requested).or_default().push(api.name().clone());
+            }
+        }
+        let deps = apis
+            .iter()
+            .map(|api| (api.name().clone(), api.deps().cloned().collect()))
+            .collect();
+        Self {
+            roots,
+            deps,
+            headers,
+        }
+    }
+
+    fn reachable_from<'a>(&'a self, root: &'a [QualifiedName]) -> HashSet<&'a QualifiedName> {
+        let mut todos: Vec<&QualifiedName> = root.iter().collect();
+        let mut done = HashSet::new();
+        while let Some(todo) = todos.pop() {
+            if done.insert(todo) {
+                todos.extend(self.deps.get(todo).into_iter().flatten());
+            }
+        }
+        done
+    }
+}
+
+/// How much generated code was attributable to one item requested by the
+/// user (e.g. using `generate!`), including all the APIs it needs.
+pub struct SizeReportEntry {
+    /// The item requested, e.g. `ns::Widget`.
+    pub requested: String,
+    /// Which of the headers named in `include_cpp!` declared the item,
+    /// if there are several.
+    pub header: Option<String>,
+    /// How many APIs (types, functions, etc.) this pulled in.
+    pub apis: usize,
+    /// How many Rust items were generated for those APIs.
+    pub rs_items: usize,
+    /// How many Rust tokens were in those items.
+    pub rs_tokens: usize,
+    /// How many bytes of C++ shims were generated for those APIs. This
+    /// doesn't include the C++ subsequently generated by cxx.
+    pub cpp_bytes: usize,
+}
+
+/// A breakdown of the generated code by the item requested, biggest
+/// first, to identify which requests are responsible for long build times.
+/// An API needed by several requested items counts towards each of them,
+/// but only once towards the total.
+pub struct SizeReport {
+    pub entries: Vec<SizeReportEntry>,
+    pub total: SizeReportEntry,
+}
+
+impl SizeReport {
+    pub(crate) fn new(graph: &ApiGraph, sizes: &ApiSizes) -> Self {
+        let make_entry = |requested: String, apis: &HashSet<&QualifiedName>| {
+            let mut entry = SizeReportEntry {
+                header: graph.headers.get(&requested).cloned(),
+                requested,
+                apis: apis.len(),
+                rs_items: 0,
+                rs_tokens: 0,
+                cpp_bytes: 0,
+            };
+            for size in apis.iter().filter_map(|api| sizes.0.get(*api)) {
+                entry.rs_items += size.rs_items;
+                entry.rs_tokens += size.rs_tokens;
+                entry.cpp_bytes += size.cpp_bytes;
+            }
+            entry
+        };
+        let mut entries: Vec<_> = graph
+            .roots
+            .iter()
+            .map(|(requested, root)| make_entry(requested.clone(), &graph.reachable_from(root)))
+            .collect();
+        entries.sort_by(|a, b| {
+            (b.rs_tokens + b.cpp_bytes, &a.requested)
+                .cmp(&(a.rs_tokens + a.cpp_bytes, &b.requested))
+        });
+        let total = make_entry("(total)".into(), &sizes.0.keys().collect());
+        Self { entries, total }
+    }
+}
+
+impl Display for SizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:>6} {:>10} {:>11} {:>10}  requested item",
+            "APIs", "Rust items", "Rust tokens", "C++ bytes"
+        )?;
+        for entry in self.entries.iter().chain(std::iter::once(&self.total)) {
+            write!(
+                f,
+                "{:>6} {:>10} {:>11} {:>10}  {}",
+                entry.apis, entry.rs_items, entry.rs_tokens, entry.cpp_bytes, entry.requested
+            )?;
+            match &entry.header {
+                Some(header) => writeln!(f, " ({})", header)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use quote::quote;
+
+    use super::{ApiGraph, ApiSizes, SizeReport};
+    use crate::types::QualifiedName;
+
+    #[test]
+    fn test_size_report() {
+        let name = QualifiedName::new_from_cpp_name;
+        let mut sizes = ApiSizes::default();
+        sizes.record_rs(&name("A"), vec![quote! { fn a() {} }]);
+        sizes.record_rs(&name("B"), vec![quote! { struct B; }, quote! { impl B {} }]);
+        sizes.record_cpp(&name("B"), 100);
+        sizes.record_cpp(&name("C"), 10);
+        let graph = ApiGraph {
+            roots: HashMap::from([
+                ("A".to_string(), vec![name("A")]),
+                ("C".to_string(), vec![name("C")]),
+            ]),
+            deps: HashMap::from([(name("A"), vec![name("B")]), (name("B"), vec![])]),
+            headers: HashMap::from([("A".to_string(), "a.h".to_string())]),
+        };
+        let report = SizeReport::new(&graph, &sizes);
+        let summary: Vec<_> = report
+            .entries
+            .iter()
+            .chain(std::iter::once(&report.total))
+            .map(|e| {
+                (
+                    e.requested.as_str(),
+                    e.apis,
+                    e.rs_items,
+                    e.rs_tokens,
+                    e.cpp_bytes,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("A", 2, 3, 10, 100),
+                ("C", 1, 0, 0, 10),
+                ("(total)", 3, 3, 10, 110)
+            ]
+        );
+        assert_eq!(report.entries[0].header.as_deref(), Some("a.h"));
+        assert_eq!(report.entries[1].header, None);
+    }
+}
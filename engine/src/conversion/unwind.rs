@@ -0,0 +1,35 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Decides how the `extern` functions which we generate ourselves, rather
+//! than through cxx, are declared, according to the `exception_policy!`.
+//! Unwinding across an `extern "C"` function is undefined behavior (or,
+//! on newer toolchains, an abort), so if C++ exceptions or Rust panics are
+//! to propagate through these functions they must use the `"C-unwind"`
+//! ABI, and the C++ side must not be `noexcept`. The functions which cxx
+//! generates are cxx's business.
+
+use autocxx_parser::ExceptionPolicy;
+use syn::{parse_quote, Abi};
+
+/// The ABI of a Rust `extern` function or block.
+pub(crate) fn extern_abi(policy: ExceptionPolicy) -> Abi {
+    match policy {
+        ExceptionPolicy::Terminate => parse_quote! { extern "C" },
+        ExceptionPolicy::Propagate => parse_quote! { extern "C-unwind" },
+    }
+}
+
+/// The exception specification to put after the signature of the C++
+/// side of such a function.
+pub(crate) fn cpp_exception_spec(policy: ExceptionPolicy) -> &'static str {
+    match policy {
+        ExceptionPolicy::Terminate => " noexcept",
+        ExceptionPolicy::Propagate => "",
+    }
+}
@@ -9,22 +9,41 @@
 use autocxx_parser::IncludeCppConfig;
 
 use super::{
-    api::{ApiName, NullPhase, UnanalyzedApi},
+    analysis::{deps::HasDependencies, fun::FnPhase},
+    api::{Api, ApiName, NullPhase, UnanalyzedApi},
     apivec::ApiVec,
 };
-use crate::types::{make_ident, Namespace};
+use crate::types::{make_ident, Namespace, QualifiedName};
 
-/// Adds items which we always add, cos they're useful.
+/// Adds items which are useful whenever the bindings involve certain types.
 /// Any APIs or techniques which do not involve actual C++ interop
 /// shouldn't go here, but instead should go into the main autocxx
-/// src/lib.rs.
+/// src/lib.rs. Any which turn out not to be needed are removed later by
+/// [`remove_unused_utilities`].
 pub(crate) fn generate_utilities(apis: &mut ApiVec<NullPhase>, config: &IncludeCppConfig) {
-    // Unless we've been specifically asked not to do so, we always
-    // generate a 'make_string' function. That pretty much *always* means
-    // we run two passes through bindgen. i.e. the next 'if' is always true,
-    // and we always generate an additional C++ file for our bindings additions,
-    // unless the include_cpp macro has specified ExcludeUtilities.
+    // Unless we've been specifically asked not to do so, we
+    // generate a 'make_string' function, which is kept if any of the
+    // APIs we end up generating involve strings. If so, that means we
+    // generate an additional C++ file for our bindings additions.
     apis.push(UnanalyzedApi::StringConstructor {
         name: ApiName::new(&Namespace::new(), make_ident(config.get_makestring_name())),
     });
 }
+
+/// Removes any utilities which aren't needed by the APIs remaining after
+/// garbage collection, so that minimal bindings result in minimal
+/// generated code. The string utilities are needed only if some API
+/// involves `std::string`.
+pub(crate) fn remove_unused_utilities(apis: ApiVec<FnPhase>) -> ApiVec<FnPhase> {
+    let std_string = QualifiedName::new_from_cpp_name("std::string");
+    let strings_used = apis
+        .iter()
+        .any(|api| api.deps().any(|dep| dep == &std_string));
+    if strings_used {
+        apis
+    } else {
+        apis.into_iter()
+            .filter(|api| !matches!(api, Api::StringConstructor { .. }))
+            .collect()
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Checks that the C++ we generate compiles, before it's handed to the
+//! user's own build, by running the compiler over it with
+//! `-fsyntax-only`. Any errors are attributed to the C++ API for which we
+//! generated the offending shim, which is much more helpful than the raw
+//! compiler output about a file the user never wrote.
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// A problem found within the generated C++ by
+/// [`crate::Builder::early_typecheck`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "nightly", doc(cfg(feature = "build")))]
+pub struct GeneratedCppError {
+    /// The C++ API on whose behalf the offending code was generated,
+    /// e.g. `Foo::bar`, if known.
+    pub api: Option<String>,
+    /// Where the compiler found the problem, if it said.
+    pub location: Option<(PathBuf, usize)>,
+    /// The compiler's message.
+    pub message: String,
+}
+
+impl Display for GeneratedCppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.api {
+            Some(api) => write!(f, "in the C++ generated for {}: {}", api, self.message)?,
+            None => write!(f, "in generated C++: {}", self.message)?,
+        }
+        if let Some((file, line)) = &self.location {
+            write!(f, " ({}:{})", file.display(), line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A file of generated C++, and the shims within it, as pairs of the shim's
+/// name and the API for which it was generated.
+pub(crate) struct GeneratedFile {
+    pub(crate) path: PathBuf,
+    pub(crate) content: Vec<u8>,
+    pub(crate) shim_origins: Vec<(String, String)>,
+}
+
+/// Runs the compiler in syntax-only mode over `file_to_check`, and
+/// returns any errors it reports. `generated_files` are used to attribute
+/// errors found within them. Returns `None` if we can't find a compiler,
+/// in which case no check is possible.
+pub(crate) fn typecheck(
+    builder: &cc::Build,
+    extra_args: &[String],
+    file_to_check: &Path,
+    generated_files: &[GeneratedFile],
+) -> std::io::Result<Option<Vec<GeneratedCppError>>> {
+    let compiler = match builder.try_get_compiler() {
+        Ok(compiler) => compiler,
+        Err(e) => {
+            log::warn!("Not checking the generated C++: {}", e);
+            return Ok(None);
+        }
+    };
+    let syntax_only_flag = if compiler.is_like_msvc() {
+        "/Zs"
+    } else {
+        "-fsyntax-only"
+    };
+    let output = compiler
+        .to_command()
+        .args(extra_args)
+        .arg(syntax_only_flag)
+        .arg(file_to_check)
+        .output()?;
+    if output.status.success() {
+        return Ok(Some(Vec::new()));
+    }
+    // Some compilers report errors on stdout.
+    let report = [output.stderr, output.stdout].concat();
+    let report = String::from_utf8_lossy(&report);
+    let mut errors: Vec<_> = report
+        .lines()
+        .filter_map(parse_error_line)
+        .map(|(file, line, message)| GeneratedCppError {
+            api: attribute(&file, line, generated_files),
+            location: Some((file, line)),
+            message,
+        })
+        .collect();
+    if errors.is_empty() {
+        errors.push(GeneratedCppError {
+            api: None,
+            location: Some((file_to_check.to_path_buf(), 0)),
+            message: report.trim().to_string(),
+        });
+    }
+    Ok(Some(errors))
+}
+
+/// Parses an error reported in either of the common formats,
+/// `file:line:column: error: message` (GCC and clang) or
+/// `file(line): error C1234: message` (MSVC).
+fn parse_error_line(line: &str) -> Option<(PathBuf, usize, String)> {
+    if let Some((location, message)) = line
+        .split_once(": error: ")
+        .or_else(|| line.split_once(": fatal error: "))
+    {
+        let mut parts = location.rsplitn(3, ':');
+        let _column = parts.next()?;
+        let line = parts.next()?.parse().ok()?;
+        let file = parts.next()?;
+        return Some((PathBuf::from(file), line, message.to_string()));
+    }
+    let (location, message) = line
+        .split_once("): error ")
+        .or_else(|| line.split_once("): fatal error "))?;
+    let (file, line) = location.rsplit_once('(')?;
+    let line = line.split(',').next()?.parse().ok()?;
+    Some((PathBuf::from(file), line, message.to_string()))
+}
+
+/// Finds the shim within which an error lies: the last one named at or
+/// before the line of the error.
+fn attribute(file: &Path, line: usize, generated_files: &[GeneratedFile]) -> Option<String> {
+    let generated = generated_files
+        .iter()
+        .find(|generated| same_file(&generated.path, file))?;
+    let content = String::from_utf8_lossy(&generated.content);
+    content
+        .lines()
+        .take(line)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .find_map(|text| {
+            generated
+                .shim_origins
+                .iter()
+                .find(|(shim, _)| contains_identifier(text, shim))
+        })
+        .map(|(_, api)| api.clone())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+        || (b.is_relative() && a.ends_with(b))
+}
+
+fn contains_identifier(text: &str, id: &str) -> bool {
+    let is_id_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.match_indices(id).any(|(start, _)| {
+        !text[..start].ends_with(is_id_char) && !text[start + id.len()..].starts_with(is_id_char)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attribute, parse_error_line, GeneratedFile};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_parse_error_line() {
+        assert_eq!(
+            parse_error_line("/gen/autocxxgen_ffi.h:12:5: error: no member named 'baz'"),
+            Some((
+                PathBuf::from("/gen/autocxxgen_ffi.h"),
+                12,
+                "no member named 'baz'".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_error_line(r"C:\gen\gen0.cxx(7): error C2039: 'baz': is not a member"),
+            Some((
+                PathBuf::from(r"C:\gen\gen0.cxx"),
+                7,
+                "C2039: 'baz': is not a member".to_string()
+            ))
+        );
+        assert_eq!(parse_error_line("/gen/gen0.cxx:3:1: warning: unused"), None);
+    }
+
+    #[test]
+    fn test_attribute() {
+        let content = b"#include <memory>\ninline void Foo_bar_autocxx_wrapper(Foo& arg0) {\n  arg0.baz();\n}\ninline void Foo_bar2_autocxx_wrapper(Foo& arg0) {}\n";
+        let shim_origins = vec![
+            (
+                "Foo_bar_autocxx_wrapper".to_string(),
+                "Foo::bar".to_string(),
+            ),
+            (
+                "Foo_bar2_autocxx_wrapper".to_string(),
+                "Foo::bar2".to_string(),
+            ),
+        ];
+        let generated = [GeneratedFile {
+            path: PathBuf::from("/gen/autocxxgen_ffi.h"),
+            content: content.to_vec(),
+            shim_origins,
+        }];
+        let file = Path::new("/gen/autocxxgen_ffi.h");
+        assert_eq!(attribute(file, 3, &generated).as_deref(), Some("Foo::bar"));
+        assert_eq!(attribute(file, 5, &generated).as_deref(), Some("Foo::bar2"));
+        assert_eq!(attribute(file, 1, &generated), None);
+        assert_eq!(attribute(Path::new("/elsewhere.h"), 3, &generated), None);
+    }
+}
@@ -15,7 +15,7 @@ use once_cell::sync::OnceCell;
 use std::collections::HashMap;
 use syn::{parse_quote, Type, TypePath, TypePtr};
 
-//// The behavior of the type.
+/// The behavior of the type.
 #[derive(Debug)]
 enum Behavior {
     CxxContainerByValueSafe,
@@ -101,12 +101,12 @@ impl TypeDetails {
         let mut segs = self.rs_name.split("::").peekable();
         if segs.peek().map(|seg| seg.is_empty()).unwrap_or_default() {
             segs.next();
-            let segs = segs.into_iter().map(make_ident);
+            let segs = segs.map(make_ident);
             parse_quote! {
                 ::#(#segs)::*
             }
         } else {
-            let segs = segs.into_iter().map(make_ident);
+            let segs = segs.map(make_ident);
             parse_quote! {
                 #(#segs)::*
             }
@@ -260,8 +260,8 @@ impl TypeDatabase {
     /// generate code for.
     pub(crate) fn get_initial_blocklist(&self) -> impl Iterator<Item = &str> + '_ {
         self.by_rs_name
-            .iter()
-            .filter_map(|(_, td)| td.get_prelude_entry().map(|_| td.cpp_name.as_str()))
+            .values()
+            .filter_map(|td| td.get_prelude_entry().map(|_| td.cpp_name.as_str()))
     }
 
     /// Whether this is one of the ctypes (mostly variable length integers)
@@ -295,6 +295,19 @@ impl TypeDatabase {
         self.get(ty).is_some()
     }
 
+    /// Whether this is a number or `bool`, which C++ and Rust lay out
+    /// identically.
+    pub(crate) fn is_scalar(&self, ty: &QualifiedName) -> bool {
+        self.get(ty)
+            .map(|td| {
+                matches!(
+                    td.behavior,
+                    Behavior::CByValue | Behavior::CVariableLengthByValue
+                )
+            })
+            .unwrap_or(false)
+    }
+
     pub(crate) fn convertible_from_strs(&self, ty: &QualifiedName) -> bool {
         self.get(ty)
             .map(|x| matches!(x.behavior, Behavior::CxxString))
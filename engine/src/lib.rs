@@ -13,26 +13,45 @@
 // This feature=nightly could be set by build.rs, but since we only care
 // about it for docs, we ask docs.rs to set it in the Cargo.toml.
 #![cfg_attr(feature = "nightly", feature(doc_cfg))]
-#![forbid(unsafe_code)]
+// Only `source_locations`, which calls libclang, may use unsafe code.
+#![deny(unsafe_code)]
+// Conversion errors carry their context, and are only ever created on the
+// unhappy path, so their size isn't worth boxing them for.
+#![allow(clippy::result_large_err)]
 
 mod ast_discoverer;
+mod bindings_cache;
 mod conversion;
 mod cxxbridge;
 mod known_types;
 mod parse_callbacks;
 mod parse_file;
 mod rust_pretty_printer;
+mod source_locations;
+mod target_cfg;
 mod types;
 
+#[cfg(any(test, feature = "build"))]
+mod api_lock;
 #[cfg(any(test, feature = "build"))]
 mod builder;
+#[cfg(any(test, feature = "build"))]
+mod early_typecheck;
 
-use autocxx_parser::{IncludeCppConfig, UnsafePolicy};
-use conversion::BridgeConverter;
+pub use autocxx_parser::RustPath;
+use autocxx_parser::{BindgenDerive, IncludeCppConfig, UnsafePolicy};
+use bindings_cache::{BindgenAllowlist, CacheLookup};
+use conversion::{concretes_allowlist, concretes_header, BridgeConverter};
+pub use conversion::{explain_error_code, SizeReport, SizeReportEntry, TypeMapper, TypeMapping};
 use parse_callbacks::AutocxxParseCallbacks;
 use parse_file::CppBuildable;
 use proc_macro2::TokenStream as TokenStream2;
-use std::{fmt::Display, path::PathBuf};
+use source_locations::SourceLocations;
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 use std::{
     fs::File,
     io::prelude::*,
@@ -48,6 +67,7 @@ use syn::{
     parse_quote, ItemMod, Macro,
 };
 
+use indoc::indoc;
 use itertools::{join, Itertools};
 use known_types::known_types;
 use log::info;
@@ -56,10 +76,14 @@ use log::info;
 /// We hope to unfork.
 use autocxx_bindgen as bindgen;
 
+#[cfg(any(test, feature = "build"))]
+pub use api_lock::ApiLockMismatch;
 #[cfg(any(test, feature = "build"))]
 pub use builder::{
     Builder, BuilderBuild, BuilderContext, BuilderError, BuilderResult, BuilderSuccess,
 };
+#[cfg(any(test, feature = "build"))]
+pub use early_typecheck::GeneratedCppError;
 pub use parse_file::{parse_file, ParseError, ParsedFile};
 
 pub use cxx_gen::HEADER;
@@ -72,15 +96,23 @@ pub use cxx_gen::HEADER;
 pub use cxx;
 
 #[derive(Clone)]
+#[non_exhaustive]
 /// Some C++ content which should be written to disk and built.
 pub struct CppFilePair {
     /// Declarations to go into a header file.
     pub header: Vec<u8>,
     /// Implementations to go into a .cpp file.
     pub implementation: Option<Vec<u8>>,
+    /// Further implementations, each to go into its own .cpp file, if the
+    /// implementation has been sharded. See [`CppOutputLayout`].
+    pub additional_implementations: Vec<Vec<u8>>,
     /// The name which should be used for the header file
     /// (important as it may be `#include`d elsewhere)
     pub header_name: String,
+    /// The name of each shim function defined in these files, along with
+    /// the C++ API for which it was generated, such that problems compiling
+    /// the shims can be attributed to the API concerned.
+    pub shim_origins: Vec<(String, String)>,
 }
 
 /// All generated C++ content which should be written to disk.
@@ -93,6 +125,9 @@ pub enum Error {
     /// Any error reported by bindgen, generating the C++ bindings.
     /// Any C++ parsing errors, etc. would be reported this way.
     Bindgen(()),
+    /// A header named in `include_cpp!` couldn't be found in any of
+    /// these include directories.
+    HeaderNotFound(String, Vec<PathBuf>),
     /// Any problem parsing the Rust file.
     Parsing(syn::Error),
     /// No `include_cpp!` macro could be found.
@@ -106,9 +141,10 @@ impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Bindgen(_) => write!(f, "Bindgen was unable to generate the initial .rs bindings for this file. This may indicate a parsing problem with the C++ headers.")?,
+            Error::HeaderNotFound(header, inc_dirs) => write!(f, "The header {} could not be found. The include directories searched were: {}. Pass the directory containing it to autocxx_build::Builder::new, or add it to the AUTOCXX_INC environment variable.", header, inc_dirs.iter().map(|dir| dir.display().to_string()).join(", "))?,
             Error::Parsing(err) => write!(f, "The Rust file could not be parsed: {}", err)?,
             Error::NoAutoCxxInc => write!(f, "No C++ include directory was provided.")?,
-            Error::Conversion(err) => write!(f, "autocxx could not generate the requested bindings. {}", err)?,
+            Error::Conversion(err) => write!(f, "autocxx could not generate the requested bindings. {} For more information, run `autocxx-gen --explain {}`.", err, err.code())?,
         }
         Ok(())
     }
@@ -120,7 +156,9 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 struct GenerationResults {
     item_mod: ItemMod,
     cpp: Option<CppFilePair>,
+    c_header: Option<CppFilePair>,
     inc_dirs: Vec<PathBuf>,
+    size_report: Option<SizeReport>,
 }
 enum State {
     NotGenerated,
@@ -130,6 +168,24 @@ enum State {
 
 const AUTOCXX_CLANG_ARGS: &[&str; 4] = &["-x", "c++", "-std=c++14", "-DBINDGEN"];
 
+/// Surrounds each `#include <...>` to suppress warnings from it.
+const SYSTEM_INCLUDE_PROLOGUE: &str = indoc! {"
+    #if defined(__GNUC__)
+    #pragma GCC diagnostic push
+    #pragma GCC diagnostic ignored \"-Wall\"
+    #pragma GCC diagnostic ignored \"-Wextra\"
+    #elif defined(_MSC_VER)
+    #pragma warning(push, 0)
+    #endif
+"};
+const SYSTEM_INCLUDE_EPILOGUE: &str = indoc! {"
+    #if defined(__GNUC__)
+    #pragma GCC diagnostic pop
+    #elif defined(_MSC_VER)
+    #pragma warning(pop)
+    #endif
+"};
+
 /// Implement to learn of header files which get included
 /// by this build process, such that your build system can choose
 /// to rerun the build process if any such file changes in future.
@@ -269,10 +325,18 @@ impl IncludeCppEngine {
 
     fn build_header(&self) -> String {
         join(
-            self.config
-                .inclusions
-                .iter()
-                .map(|path| format!("#include \"{}\"\n", path)),
+            self.config.get_ordered_inclusions().iter().map(|inc| {
+                if inc.system {
+                    // Third-party headers aren't ours to fix, so don't
+                    // report warnings in them.
+                    format!(
+                        "{}#include {}\n{}",
+                        SYSTEM_INCLUDE_PROLOGUE, inc, SYSTEM_INCLUDE_EPILOGUE
+                    )
+                } else {
+                    format!("#include {}\n", inc)
+                }
+            }),
             "",
         )
     }
@@ -281,6 +345,7 @@ impl IncludeCppEngine {
         &self,
         inc_dirs: &[PathBuf],
         extra_clang_args: &[&str],
+        allowlist: &BindgenAllowlist,
     ) -> bindgen::Builder {
         let mut builder = bindgen::builder()
             .clang_args(make_clang_args(inc_dirs, extra_clang_args))
@@ -299,16 +364,33 @@ impl IncludeCppEngine {
         for item in known_types().get_initial_blocklist() {
             builder = builder.blocklist_item(item);
         }
+        for pattern in self.config.get_bindgen_opaque_types() {
+            builder = builder.opaque_type(pattern);
+        }
+        for pattern in self.config.get_bindgen_blocklisted_files() {
+            builder = builder.blocklist_file(pattern);
+        }
+        for derive in self.config.get_bindgen_derives() {
+            builder = match derive {
+                BindgenDerive::Debug => builder.derive_debug(true),
+                BindgenDerive::Default => builder.derive_default(true),
+                BindgenDerive::Hash => builder.derive_hash(true),
+                BindgenDerive::PartialEq => builder.derive_partialeq(true),
+                BindgenDerive::Eq => builder.derive_eq(true),
+                BindgenDerive::PartialOrd => builder.derive_partialord(true),
+                BindgenDerive::Ord => builder.derive_ord(true),
+            };
+        }
 
         // 3. Passes allowlist and other options to the bindgen::Builder equivalent
         //    to --output-style=cxx --allowlist=<as passed in>
-        if let Some(allowlist) = self.config.bindgen_allowlist() {
+        if let Some(allowlist) = allowlist {
             for a in allowlist {
                 // TODO - allowlist type/functions/separately
                 builder = builder
-                    .allowlist_type(&a)
-                    .allowlist_function(&a)
-                    .allowlist_var(&a);
+                    .allowlist_type(a)
+                    .allowlist_function(a)
+                    .allowlist_var(a);
             }
         }
 
@@ -323,6 +405,35 @@ impl IncludeCppEngine {
         builder
     }
 
+    /// Describes the bindgen options chosen by the user's directives, such
+    /// that cached bindings generated with different options aren't reused.
+    fn bindgen_option_flags(&self) -> impl Iterator<Item = String> + '_ {
+        let opaque_types = self
+            .config
+            .get_bindgen_opaque_types()
+            .iter()
+            .map(|pattern| format!("--opaque-type={}", pattern));
+        let blocklisted_files = self
+            .config
+            .get_bindgen_blocklisted_files()
+            .iter()
+            .map(|pattern| format!("--blocklist-file={}", pattern));
+        let derives = self
+            .config
+            .get_bindgen_derives()
+            .iter()
+            .map(|derive| format!("--with-derive-{}", derive.name()));
+        let concretes = self
+            .config
+            .get_concretes()
+            .iter()
+            .map(|(cpp_type, rust_id)| format!("--autocxx-concrete={}={}", cpp_type, rust_id));
+        opaque_types
+            .chain(blocklisted_files)
+            .chain(derives)
+            .chain(concretes)
+    }
+
     pub fn get_rs_filename(&self) -> String {
         self.config.get_rs_filename()
     }
@@ -336,28 +447,45 @@ impl IncludeCppEngine {
         }
     }
 
+    /// Returns a breakdown of the generated code by the item requested.
+    /// Only available if [`CppCodegenOptions::size_report`] was set when
+    /// calling `generate`.
+    pub fn get_size_report(&self) -> Option<&SizeReport> {
+        match &self.state {
+            State::Generated(gen_results) => gen_results.size_report.as_ref(),
+            _ => None,
+        }
+    }
+
     /// Returns the name of the mod which this `include_cpp!` will generate.
     /// Can and should be used to ensure multiple mods in a file don't conflict.
     pub fn get_mod_name(&self) -> String {
         self.config.get_mod_name().to_string()
     }
 
-    fn parse_bindings(&self, bindings: bindgen::Bindings) -> Result<ItemMod> {
-        // This bindings object is actually a TokenStream internally and we're wasting
-        // effort converting to and from string. We could enhance the bindgen API
-        // in future.
-        let bindings = bindings.to_string();
-        // Manually add the mod ffi {} so that we can ask syn to parse
-        // into a single construct.
-        let bindings = format!("mod bindgen {{ {} }}", bindings);
+    fn parse_bindings(&self, bindings: &str) -> Result<ItemMod> {
         info!("Bindings: {}", bindings);
-        syn::parse_str::<ItemMod>(&bindings).map_err(Error::Parsing)
+        // Parse the items and then put them in a mod bindgen {}, rather
+        // than wrapping the string in a mod before parsing it, since the
+        // bindings may be large enough that we'd rather not copy them.
+        let file = syn::parse_str::<syn::File>(bindings).map_err(Error::Parsing)?;
+        let mut bindgen_mod: ItemMod = parse_quote! {
+            mod bindgen {}
+        };
+        bindgen_mod.attrs = file.attrs;
+        bindgen_mod.content.as_mut().unwrap().1 = file.items;
+        Ok(bindgen_mod)
     }
 
     /// Actually examine the headers to find out what needs generating.
     /// Most errors occur at this stage as we fail to interpret the C++
     /// headers properly.
     ///
+    /// The output of bindgen is cached for the lifetime of this process,
+    /// and optionally persisted (see
+    /// [`CppCodegenOptions::bindings_cache_dir`]), so generating for the
+    /// same headers but different directives may not need to run libclang.
+    ///
     /// See documentation for this type for flow diagrams and more details.
     pub fn generate(
         &mut self,
@@ -375,25 +503,126 @@ impl IncludeCppEngine {
             State::Generated(_) => panic!("Only call generate once"),
         }
 
+        // Later arguments win, so a standard chosen for just this
+        // include_cpp! overrides any chosen for the whole build.
+        let std_flag = self
+            .config
+            .get_cpp_standard()
+            .map(|standard| format!("-std={}", standard));
+        let extra_clang_args: Vec<&str> = extra_clang_args
+            .iter()
+            .copied()
+            .chain(std_flag.as_deref())
+            .collect();
+        let extra_clang_args = &extra_clang_args[..];
+
         let mod_name = self.config.get_mod_name();
-        let mut builder = self.make_bindgen_builder(&inc_dirs, extra_clang_args);
-        if let Some(dep_recorder) = dep_recorder {
-            builder = builder.parse_callbacks(Box::new(AutocxxParseCallbacks(dep_recorder)));
-        }
         let header_contents = self.build_header();
-        self.dump_header_if_so_configured(&header_contents, &inc_dirs, extra_clang_args);
-        let header_and_prelude = format!("{}\n\n{}", known_types().get_prelude(), header_contents);
+        let user_prelude = self.config.get_prelude();
+        self.dump_header_if_so_configured(
+            &format!("{}{}", user_prelude, header_contents),
+            &inc_dirs,
+            extra_clang_args,
+        );
+        let header_and_prelude = format!(
+            "{}{}\n\n{}",
+            user_prelude,
+            known_types().get_prelude(),
+            header_contents
+        );
         log::info!("Header and prelude for bindgen:\n{}", header_and_prelude);
-        builder = builder.header_contents("example.hpp", &header_and_prelude);
 
-        let bindings = builder.generate().map_err(Error::Bindgen)?;
-        let bindings = self.parse_bindings(bindings)?;
+        let cache_key = bindings_cache::cache_key(
+            &header_and_prelude,
+            make_clang_args(&inc_dirs, extra_clang_args).chain(self.bindgen_option_flags()),
+        );
+        let requested_allowlist = self
+            .config
+            .bindgen_allowlist()
+            .map(|allowlist| allowlist.chain(concretes_allowlist(&self.config)).collect());
+        let (bindings, source_locations) = match bindings_cache::lookup(
+            cache_key,
+            requested_allowlist,
+            cpp_codegen_options.bindings_cache_dir.as_deref(),
+        ) {
+            CacheLookup::Hit {
+                bindings,
+                source_locations,
+                dependencies,
+            } => {
+                info!("Reusing cached bindings");
+                if let Some(dep_recorder) = dep_recorder {
+                    for filename in dependencies {
+                        dep_recorder.record_header_file_dependency(&filename);
+                    }
+                }
+                (bindings, source_locations)
+            }
+            CacheLookup::Miss { allowlist } => {
+                // We need to know the static member functions of any
+                // templates named using concrete! before running bindgen.
+                let source_locations = Arc::new(SourceLocations::find(
+                    "example.hpp",
+                    &header_and_prelude,
+                    &make_clang_args(&inc_dirs, extra_clang_args).collect::<Vec<_>>(),
+                ));
+                let bindgen_header = format!(
+                    "{}{}",
+                    header_and_prelude,
+                    concretes_header(&self.config, &source_locations)
+                );
+                let included_files = Arc::new(Mutex::new(Vec::new()));
+                let builder = self
+                    .make_bindgen_builder(&inc_dirs, extra_clang_args, &allowlist)
+                    .parse_callbacks(Box::new(AutocxxParseCallbacks {
+                        dep_recorder,
+                        included_files: included_files.clone(),
+                    }))
+                    .header_contents("example.hpp", &bindgen_header);
+                // This bindings object is actually a TokenStream internally and we're wasting
+                // effort converting to and from string. We could enhance the bindgen API
+                // in future.
+                let bindings: Arc<str> = builder
+                    .generate()
+                    .map_err(|err| match self.find_missing_header(&inc_dirs) {
+                        Some(header) => Error::HeaderNotFound(header, inc_dirs.clone()),
+                        None => Error::Bindgen(err),
+                    })?
+                    .to_string()
+                    .into();
+                let included_files = included_files.lock().unwrap().clone();
+                bindings_cache::store(
+                    cache_key,
+                    allowlist,
+                    included_files,
+                    bindings.clone(),
+                    source_locations.clone(),
+                    cpp_codegen_options.bindings_cache_dir.as_deref(),
+                );
+                (bindings, source_locations)
+            }
+        };
+        let bindings = self.parse_bindings(&bindings)?;
 
-        let converter = BridgeConverter::new(&self.config.inclusions, &self.config);
+        let inclusions = self.config.get_ordered_inclusions();
+        let inline_namespaces = BridgeConverter::new(&inclusions, &self.config)
+            .find_inline_namespaces(&source_locations);
+        if !inline_namespaces.is_empty() {
+            self.config.strip_inline_namespaces(&inline_namespaces);
+        }
+        let parsed = BridgeConverter::new(&inclusions, &self.config)
+            .parse(bindings, cpp_codegen_options, &source_locations)
+            .map_err(Error::Conversion)?;
+        if self.config.has_headers_to_generate() {
+            let items_from_headers =
+                BridgeConverter::new(&inclusions, &self.config).find_items_from_headers(&parsed);
+            self.config.add_items_from_headers(items_from_headers);
+        }
+        let converter = BridgeConverter::new(&inclusions, &self.config);
 
         let conversion = converter
             .convert(
-                bindings,
+                parsed,
                 self.config.unsafe_policy.clone(),
                 header_contents,
                 cpp_codegen_options,
@@ -416,11 +645,31 @@ impl IncludeCppEngine {
         self.state = State::Generated(Box::new(GenerationResults {
             item_mod: new_bindings,
             cpp: conversion.cpp,
+            c_header: conversion.c_header,
             inc_dirs,
+            size_report: conversion.size_report,
         }));
         Ok(())
     }
 
+    /// Find the first header named in `include_cpp!` which isn't in any of
+    /// these include directories. bindgen only tells us that it failed, and
+    /// clang's complaint is easily lost amongst other output, so this lets
+    /// us say what went wrong more clearly. System headers are skipped,
+    /// since they may be found in the compiler's own include directories.
+    fn find_missing_header(&self, inc_dirs: &[PathBuf]) -> Option<String> {
+        self.config
+            .inclusions
+            .iter()
+            .filter(|inc| !inc.system)
+            .map(|inc| &inc.path)
+            .find(|header| {
+                let header = Path::new(header);
+                !header.is_file() && !inc_dirs.iter().any(|dir| dir.join(header).is_file())
+            })
+            .cloned()
+    }
+
     /// Return the include directories used for this include_cpp invocation.
     fn include_dirs(&self) -> impl Iterator<Item = &PathBuf> {
         match &self.state {
@@ -536,6 +785,8 @@ pub fn do_cxx_cpp_generation(
             cxx_generated.implementation,
             cpp_codegen_options.suppress_system_headers,
         )),
+        additional_implementations: Vec::new(),
+        shim_origins: Vec::new(),
     })
 }
 
@@ -571,10 +822,17 @@ impl CppBuildable for IncludeCppEngine {
                 if let Some(cpp_file_pair) = &gen_results.cpp {
                     files.push(cpp_file_pair.clone());
                 }
+                if let Some(c_header) = &gen_results.c_header {
+                    files.push(c_header.clone());
+                }
             }
         };
         Ok(GeneratedCpp(files))
     }
+
+    fn cpp_standard(&self) -> Option<&str> {
+        self.config.get_cpp_standard()
+    }
 }
 
 /// Get clang args as if we were operating clang the same way as we operate
@@ -640,6 +898,61 @@ impl HeaderNamer<'_> {
     }
 }
 
+/// How the C++ function definitions generated by autocxx for each
+/// `include_cpp!` are laid out into files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CppOutputLayout {
+    /// A single .cpp file, which is fastest for a build from scratch.
+    #[default]
+    Unity,
+    /// This many .cpp files, which can be compiled in parallel, and fewer of
+    /// which need to be recompiled after a change. Each definition is
+    /// assigned to a file according to a stable hash of its content, so the
+    /// assignment is deterministic. All the files are generated, even if
+    /// some are empty.
+    Sharded(usize),
+}
+
+/// Which compiler warnings to suppress within the C++ functions generated by
+/// autocxx for each `include_cpp!`. Only the generated code is affected,
+/// not the headers which it includes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum WarningSuppression {
+    /// Suppress those warnings which the generated code is known to trigger:
+    /// unused parameters, and calls to deprecated functions which were
+    /// nevertheless requested using `generate!`. This uses pragmas
+    /// understood by clang, gcc and MSVC.
+    #[default]
+    Targeted,
+    /// Suppress no warnings.
+    Off,
+    /// Surround the generated code with this C++, for instance `#pragma`s
+    /// suitable for some other compiler. Each should end with a newline.
+    Custom { prologue: String, epilogue: String },
+}
+
+/// The visibility of the C++ shim functions generated by autocxx, which
+/// are otherwise exported from any shared library containing them. They're
+/// only ever called by the C++ generated by cxx, so need not be exported
+/// unless that's in a different library. This doesn't affect the functions
+/// which cxx generates (see [`CppCodegenOptions::cxx_impl_annotations`]),
+/// nor those which Rust calls directly (those requested using `thin_call!`),
+/// nor those intended to be called from other C++ (those generated for
+/// `extern_rust_function`s).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ShimVisibility {
+    /// Leave the compiler to decide, which typically means exporting them.
+    #[default]
+    Default,
+    /// Mark them as hidden, using an attribute understood by clang and gcc.
+    /// With MSVC, functions aren't exported unless asked, so this does
+    /// nothing.
+    Hidden,
+    /// Annotate them with this, for instance an export macro defined by
+    /// your library.
+    Annotation(String),
+}
+
 /// Options for C++ codegen
 #[derive(Default)]
 pub struct CppCodegenOptions<'a> {
@@ -664,4 +977,35 @@ pub struct CppCodegenOptions<'a> {
     /// Whether to skip using [`cxx_gen`] to generate the C++ code,
     /// so that some other process can handle that.
     pub skip_cxx_gen: bool,
+    /// Whether to emit `#line` directives in the generated C++, so that
+    /// compiler errors within generated code refer to the original header
+    /// where the relevant C++ API was declared, if known.
+    pub emit_line_directives: bool,
+    /// How to lay out the generated C++ function definitions into files.
+    pub output_layout: CppOutputLayout,
+    /// Which compiler warnings to suppress within the generated C++, so
+    /// that it can be built using `-Werror`.
+    pub warning_suppression: WarningSuppression,
+    /// The visibility of the shim functions within the generated C++.
+    pub shim_visibility: ShimVisibility,
+    /// A directory in which to persist the output of bindgen, so that
+    /// later runs for unchanged headers needn't run libclang at all.
+    /// Cached bindings are reused even if the directives within
+    /// `include_cpp!` have changed, so long as they require no further
+    /// C++ APIs. The directory may be shared between `include_cpp!`
+    /// sections and between builds.
+    pub bindings_cache_dir: Option<PathBuf>,
+    /// Whether to record how much code is generated on behalf of each
+    /// item requested in `include_cpp!`, available afterwards from
+    /// [`IncludeCppEngine::get_size_report`].
+    pub size_report: bool,
+    /// Whether to additionally generate a plain C header declaring those
+    /// Rust functions exposed using `extern_rust_function` whose
+    /// signatures can be represented in C, so that C code (or other
+    /// binding generators) can call them. Each is named after the
+    /// `include_cpp!` mod, e.g. `ffi_do_thing`.
+    pub c_header: bool,
+    /// Custom conversions from C++ types to existing Rust types, consulted
+    /// in order. See [`TypeMapper`].
+    pub type_mappers: Vec<Box<dyn TypeMapper + 'a>>,
 }
@@ -6,18 +6,33 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::panic::UnwindSafe;
+use std::{
+    panic::UnwindSafe,
+    sync::{Arc, Mutex},
+};
 
 use crate::RebuildDependencyRecorder;
 use autocxx_bindgen::callbacks::ParseCallbacks;
 
+/// Passes each header file included by bindgen on to any
+/// [`RebuildDependencyRecorder`], and also keeps a list of them,
+/// since cached bindings remain valid only while they're unchanged.
 #[derive(Debug)]
-pub(crate) struct AutocxxParseCallbacks(pub(crate) Box<dyn RebuildDependencyRecorder>);
+pub(crate) struct AutocxxParseCallbacks {
+    pub(crate) dep_recorder: Option<Box<dyn RebuildDependencyRecorder>>,
+    pub(crate) included_files: Arc<Mutex<Vec<String>>>,
+}
 
 impl UnwindSafe for AutocxxParseCallbacks {}
 
 impl ParseCallbacks for AutocxxParseCallbacks {
     fn include_file(&self, filename: &str) {
-        self.0.record_header_file_dependency(filename);
+        if let Some(dep_recorder) = &self.dep_recorder {
+            dep_recorder.record_header_file_dependency(filename);
+        }
+        self.included_files
+            .lock()
+            .unwrap()
+            .push(filename.to_string());
     }
 }
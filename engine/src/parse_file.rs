@@ -161,6 +161,7 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                                 self.extra_superclasses.push(Subclass {
                                     superclass,
                                     subclass,
+                                    generic: false,
                                 })
                             }
                         }
@@ -191,7 +192,9 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
         mut discoveries,
     } = state;
     if !auto_allowlist
-        && (!discoveries.extern_rust_types.is_empty() || !discoveries.extern_rust_funs.is_empty())
+        && (!discoveries.extern_rust_types.is_empty()
+            || !discoveries.extern_rust_funs.is_empty()
+            || !discoveries.extern_rust_enums.is_empty())
     {
         return Err(ParseError::DiscoveredRustItemsWhenNotInAutoDiscover);
     }
@@ -209,7 +212,14 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                     .subclasses
                     .append(&mut extra_superclasses);
                 if auto_allowlist {
-                    for cpp in discoveries.cpp_list {
+                    // References to the C++ side of shared Rust enums
+                    // (e.g. `ffi::Status`) aren't C++ items for bindgen to find.
+                    for cpp in discoveries.cpp_list.into_iter().filter(|cpp| {
+                        !discoveries
+                            .extern_rust_enums
+                            .iter()
+                            .any(|e| e.definition.ident == cpp)
+                    }) {
                         engine
                             .config_mut()
                             .allowlist
@@ -225,6 +235,10 @@ fn parse_file_contents(source: syn::File, auto_allowlist: bool) -> Result<Parsed
                     .config_mut()
                     .rust_types
                     .append(&mut discoveries.extern_rust_types);
+                engine
+                    .config_mut()
+                    .extern_rust_enums
+                    .append(&mut discoveries.extern_rust_enums);
             }
         }
         if autocxx_seg_iterator.next().is_some() {
@@ -262,6 +276,12 @@ pub trait CppBuildable {
         &self,
         cpp_codegen_options: &CppCodegenOptions,
     ) -> Result<GeneratedCpp, cxx_gen::Error>;
+
+    /// The C++ standard with which this code must be built, if it differs
+    /// from that of the rest of the build.
+    fn cpp_standard(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl ParsedFile {
@@ -11,10 +11,10 @@ use std::io::Write;
 use std::process::{Command, Stdio};
 
 enum Error {
-    Run(std::io::Error),
-    Write(std::io::Error),
-    Utf8(std::string::FromUtf8Error),
-    Wait(std::io::Error),
+    Run,
+    Write,
+    Utf8,
+    Wait,
 }
 
 pub(crate) fn pretty_print(ts: &TokenStream) -> String {
@@ -33,8 +33,8 @@ fn reformat(text: impl std::fmt::Display) -> Result<String, Error> {
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
-        .map_err(Error::Run)?;
-    write!(rustfmt.stdin.take().unwrap(), "{}", text).map_err(Error::Write)?;
-    let output = rustfmt.wait_with_output().map_err(Error::Wait)?;
-    String::from_utf8(output.stdout).map_err(Error::Utf8)
+        .map_err(|_| Error::Run)?;
+    write!(rustfmt.stdin.take().unwrap(), "{}", text).map_err(|_| Error::Write)?;
+    let output = rustfmt.wait_with_output().map_err(|_| Error::Wait)?;
+    String::from_utf8(output.stdout).map_err(|_| Error::Utf8)
 }
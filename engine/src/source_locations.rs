@@ -0,0 +1,384 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! bindgen doesn't tell us where it found each item, but we'd like to know:
+//! for diagnostics, for `#line` directives, and to find the items declared
+//! in the headers named using `generate_from_header!`. So, before running
+//! bindgen, we ask libclang directly, using the same arguments. If libclang
+//! is loaded at runtime, we load it on this thread, and bindgen then uses
+//! the same library.
+//!
+//! bindgen doesn't tell us about the members of class templates either, so
+//! we also find their static member functions here, for `concrete!`.
+//!
+//! Only declarations are examined, not function bodies, so this is much
+//! quicker than bindgen's own parse.
+
+#![allow(unsafe_code)]
+// libclang's constants are named as in C.
+#![allow(non_upper_case_globals)]
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_uint, c_ulong},
+    ptr,
+};
+
+use autocxx_parser::IncludeCppConfig;
+use clang_sys::*;
+
+/// Where an item was found in the original C++ headers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SourceLocation {
+    pub(crate) file: String,
+    pub(crate) line: usize,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+impl SourceLocation {
+    /// Which of the headers named in `include_cpp!` this is, if there are
+    /// several, such that it's worth saying which an item came from.
+    pub(crate) fn included_header<'a>(&self, config: &'a IncludeCppConfig) -> Option<&'a str> {
+        if config.inclusions.len() > 1 {
+            config.included_header_for_file(&self.file)
+        } else {
+            None
+        }
+    }
+}
+
+/// The items and namespaces declared in the headers, named as bindgen
+/// names them: that is, omitting inline and anonymous namespaces.
+#[derive(Default, Debug, PartialEq, Eq)]
+pub(crate) struct SourceLocations {
+    /// The declarations of each item, by qualified C++ name. Overloaded
+    /// functions have several, in the order in which bindgen numbers them.
+    pub(crate) items: HashMap<String, Vec<SourceLocation>>,
+    pub(crate) namespaces: HashSet<String>,
+    /// The public static member functions of class templates, by
+    /// qualified C++ name, e.g. `Factory::create`.
+    pub(crate) template_static_methods: HashSet<String>,
+}
+
+impl SourceLocations {
+    /// Finds the declarations within `header`, which is passed to libclang
+    /// as an unsaved file called `header_name`.
+    pub(crate) fn find(header_name: &str, header: &str, clang_args: &[String]) -> Self {
+        // bindgen loads libclang only when it runs, and only on its own
+        // thread, so we may need to load it ourselves.
+        if !clang_sys::is_loaded() {
+            if let Err(err) = clang_sys::load() {
+                log::warn!("Unable to load libclang to find source locations: {}", err);
+                return Self::default();
+            }
+        }
+        let mut args = clang_args.to_vec();
+        // As bindgen does, use the compiler's own include directories, and
+        // the target for which we're building.
+        if let Some(search_paths) =
+            clang_sys::support::Clang::find(None, &args).and_then(|clang| clang.cpp_search_paths)
+        {
+            for path in search_paths {
+                args.push("-isystem".to_string());
+                args.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if !args.iter().any(|arg| arg.starts_with("--target")) {
+            if let Ok(target) = std::env::var("TARGET") {
+                args.push(format!("--target={}", target));
+            }
+        }
+        let args: Vec<CString> = args
+            .into_iter()
+            .filter_map(|arg| CString::new(arg).ok())
+            .collect();
+        let arg_ptrs: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        let (header_name, header) = match (CString::new(header_name), CString::new(header)) {
+            (Ok(header_name), Ok(header)) => (header_name, header),
+            _ => return Self::default(),
+        };
+        let mut unsaved = CXUnsavedFile {
+            Filename: header_name.as_ptr(),
+            Contents: header.as_ptr(),
+            Length: header.as_bytes().len() as c_ulong,
+        };
+        unsafe {
+            let index = clang_createIndex(0, 0);
+            let tu = clang_parseTranslationUnit(
+                index,
+                header_name.as_ptr(),
+                arg_ptrs.as_ptr(),
+                arg_ptrs.len() as _,
+                &mut unsaved,
+                1,
+                CXTranslationUnit_SkipFunctionBodies,
+            );
+            let mut visitor = Visitor {
+                tu,
+                scope: Vec::new(),
+                locations: Self::default(),
+            };
+            if tu.is_null() {
+                log::warn!("libclang couldn't parse the headers to find source locations");
+            } else {
+                visitor.visit_children(clang_getTranslationUnitCursor(tu));
+                clang_disposeTranslationUnit(tu);
+            }
+            clang_disposeIndex(index);
+            visitor.locations
+        }
+    }
+
+    /// Where the given item was declared. `overload` is the number which
+    /// bindgen appended to its name, if it's one of several overloads.
+    pub(crate) fn get(&self, qualified_name: &str, overload: usize) -> Option<SourceLocation> {
+        let declarations = self.items.get(qualified_name)?;
+        declarations
+            .get(overload)
+            .or_else(|| declarations.first())
+            .cloned()
+    }
+
+    /// The names of the public static member functions of the given class
+    /// template, in order, omitting any which are overloaded.
+    pub(crate) fn get_template_static_methods(&self, template: &str) -> Vec<&str> {
+        let mut methods: Vec<_> = self
+            .template_static_methods
+            .iter()
+            .filter(|name| self.items.get(*name).map_or(0, Vec::len) == 1)
+            .filter_map(|name| name.strip_prefix(template)?.strip_prefix("::"))
+            .filter(|method| !method.contains("::"))
+            .collect();
+        methods.sort_unstable();
+        methods
+    }
+}
+
+struct Visitor {
+    tu: CXTranslationUnit,
+    /// The names of the namespaces and types enclosing the declarations
+    /// being visited.
+    scope: Vec<String>,
+    locations: SourceLocations,
+}
+
+extern "C" fn visit(cursor: CXCursor, _parent: CXCursor, data: CXClientData) -> CXChildVisitResult {
+    let visitor = unsafe { &mut *(data as *mut Visitor) };
+    visitor.visit(cursor);
+    CXChildVisit_Continue
+}
+
+impl Visitor {
+    fn visit_children(&mut self, cursor: CXCursor) {
+        unsafe {
+            clang_visitChildren(cursor, visit, self as *mut Self as CXClientData);
+        }
+    }
+
+    fn visit_children_within(&mut self, cursor: CXCursor, name: String) {
+        self.scope.push(name);
+        self.visit_children(cursor);
+        self.scope.pop();
+    }
+
+    fn visit(&mut self, cursor: CXCursor) {
+        let kind = unsafe { clang_getCursorKind(cursor) };
+        match kind {
+            CXCursor_LinkageSpec => self.visit_children(cursor),
+            CXCursor_Namespace => {
+                let name = spelling(cursor);
+                if name.is_empty() || self.is_inline_namespace(cursor) {
+                    self.visit_children(cursor)
+                } else {
+                    self.locations.namespaces.insert(self.qualified_name(&name));
+                    self.visit_children_within(cursor, name)
+                }
+            }
+            _ if unsafe { clang_isDeclaration(kind) } != 0 => {
+                let name = spelling(cursor);
+                if name.is_empty() {
+                    return;
+                }
+                let is_type = matches!(
+                    kind,
+                    CXCursor_StructDecl
+                        | CXCursor_ClassDecl
+                        | CXCursor_UnionDecl
+                        | CXCursor_ClassTemplate
+                        | CXCursor_EnumDecl
+                );
+                // Record each item once, however many times it's declared,
+                // but for types prefer the definition to any forward
+                // declaration.
+                let is_first_declaration =
+                    unsafe { clang_equalCursors(cursor, clang_getCanonicalCursor(cursor)) } != 0;
+                if is_first_declaration {
+                    if kind == CXCursor_CXXMethod && self.is_template_static_method(cursor) {
+                        self.locations
+                            .template_static_methods
+                            .insert(self.qualified_name(&name));
+                    }
+                    let definition = unsafe { clang_getCursorDefinition(cursor) };
+                    let declaration = if is_type && unsafe { clang_Cursor_isNull(definition) } == 0
+                    {
+                        definition
+                    } else {
+                        cursor
+                    };
+                    if let Some(location) = location(declaration) {
+                        self.locations
+                            .items
+                            .entry(self.qualified_name(&name))
+                            .or_default()
+                            .push(location);
+                    }
+                }
+                if is_type && kind != CXCursor_EnumDecl {
+                    self.visit_children_within(cursor, name)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn qualified_name(&self, name: &str) -> String {
+        self.scope
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(name))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    fn is_template_static_method(&self, cursor: CXCursor) -> bool {
+        unsafe {
+            clang_CXXMethod_isStatic(cursor) != 0
+                && clang_getCXXAccessSpecifier(cursor) == CX_CXXPublic
+                && clang_getCursorKind(clang_getCursorSemanticParent(cursor))
+                    == CXCursor_ClassTemplate
+        }
+    }
+
+    /// Whether this is an inline namespace, i.e. `inline namespace foo`.
+    /// libclang can only tell us directly from version 9.
+    fn is_inline_namespace(&self, cursor: CXCursor) -> bool {
+        unsafe {
+            let start = clang_getRangeStart(clang_getCursorExtent(cursor));
+            let range = clang_getRange(start, clang_getCursorLocation(cursor));
+            let mut tokens = ptr::null_mut();
+            let mut token_count: c_uint = 0;
+            clang_tokenize(self.tu, range, &mut tokens, &mut token_count);
+            let is_inline = token_count > 0
+                && into_string(clang_getTokenSpelling(self.tu, *tokens)) == "inline";
+            clang_disposeTokens(self.tu, tokens, token_count);
+            is_inline
+        }
+    }
+}
+
+fn spelling(cursor: CXCursor) -> String {
+    unsafe { into_string(clang_getCursorSpelling(cursor)) }
+}
+
+fn location(cursor: CXCursor) -> Option<SourceLocation> {
+    let mut file = ptr::null_mut();
+    let mut line: c_uint = 0;
+    unsafe {
+        clang_getExpansionLocation(
+            clang_getCursorLocation(cursor),
+            &mut file,
+            &mut line,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        );
+    }
+    if file.is_null() {
+        None
+    } else {
+        Some(SourceLocation {
+            file: unsafe { into_string(clang_getFileName(file)) },
+            line: line as usize,
+        })
+    }
+}
+
+/// Takes ownership of a libclang string.
+unsafe fn into_string(s: CXString) -> String {
+    let c_str = clang_getCString(s);
+    let result = if c_str.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(c_str).to_string_lossy().into_owned()
+    };
+    clang_disposeString(s);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceLocations;
+    use crate::{CppCodegenOptions, IncludeCppEngine};
+    use proc_macro2::Span;
+    use syn::{parse_quote, Token};
+
+    /// Whether libclang can be loaded at all. libclang is loaded per
+    /// thread, so this is checked on a thread of its own, leaving each test
+    /// to start with it unloaded, as a build script or proc macro would.
+    fn libclang_available() -> bool {
+        std::thread::spawn(|| clang_sys::load().is_ok())
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_find_loads_libclang() {
+        let available = libclang_available();
+        let locations = std::thread::spawn(|| {
+            SourceLocations::find("input.h", "namespace a {\nstruct B {};\n}\n", &[])
+        })
+        .join()
+        .expect("finding source locations shouldn't panic");
+        if available {
+            assert_eq!(locations.get("a::B", 0).unwrap().line, 2);
+        } else {
+            assert_eq!(locations, SourceLocations::default());
+        }
+    }
+
+    #[test]
+    fn test_generate_with_default_features() {
+        if !libclang_available() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("input.h"), "struct A { int a; };\n").unwrap();
+        let inc_dir = dir.path().to_path_buf();
+        std::thread::spawn(move || {
+            let hexathorpe = Token![#](Span::call_site());
+            let mut engine = IncludeCppEngine::new_from_syn(parse_quote! {
+                include_cpp! {
+                    #hexathorpe include "input.h"
+                    safety!(unsafe_ffi)
+                    generate!("A")
+                }
+            })
+            .unwrap();
+            engine
+                .generate(vec![inc_dir], &[], None, &CppCodegenOptions::default())
+                .unwrap();
+        })
+        .join()
+        .expect("generating bindings shouldn't panic");
+    }
+}
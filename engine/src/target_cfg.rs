@@ -0,0 +1,107 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Evaluation of the predicates given in `cfg!` and `generate_if!`
+//! directives.
+//!
+//! Bindings are generated for a specific target, so rather than asking
+//! cxx to cope with `#[cfg]` attributes, we decide at generation time
+//! whether each such item should exist at all. Within a build script we
+//! know the target from cargo's `CARGO_CFG_*` environment variables, and
+//! the enabled features from `CARGO_FEATURE_*`; elsewhere (e.g.
+//! `autocxx-gen` run by some other build system) we assume the target is
+//! the host, and that no features are enabled.
+
+use autocxx_parser::IncludeCppConfig;
+use syn::{parse_quote, Attribute, Lit, Meta, NestedMeta};
+
+/// Whether the user has asked for this item to be generated only on
+/// targets satisfying some predicate, which this target doesn't.
+pub(crate) fn excluded_for_target(config: &IncludeCppConfig, cpp_name: &str) -> bool {
+    config
+        .get_cfg(cpp_name)
+        .is_some_and(|predicate| !cfg_holds(&predicate))
+}
+
+/// A `#[cfg]` attribute to put on the Rust items generated for this item,
+/// if the user gave one using `cfg!`.
+pub(crate) fn cfg_attr(config: &IncludeCppConfig, cpp_name: &str) -> Option<Attribute> {
+    config.get_cfg(cpp_name).map(|predicate| {
+        let predicate = parse_predicate(&predicate);
+        parse_quote! {
+            #[cfg(#predicate)]
+        }
+    })
+}
+
+fn parse_predicate(predicate: &str) -> NestedMeta {
+    syn::parse_str(predicate).expect("cfg predicates are validated when parsing directives")
+}
+
+fn cfg_holds(predicate: &str) -> bool {
+    evaluate(&parse_predicate(predicate))
+}
+
+fn evaluate(predicate: &NestedMeta) -> bool {
+    match predicate {
+        NestedMeta::Meta(Meta::Path(path)) => path
+            .get_ident()
+            .is_some_and(|key| target_values(&key.to_string()).is_some()),
+        NestedMeta::Meta(Meta::NameValue(nv)) => match (nv.path.get_ident(), &nv.lit) {
+            (Some(key), Lit::Str(feature)) if key == "feature" => feature_enabled(&feature.value()),
+            (Some(key), Lit::Str(value)) => target_values(&key.to_string())
+                .is_some_and(|values| values.contains(&value.value())),
+            _ => false,
+        },
+        NestedMeta::Meta(Meta::List(list)) => {
+            let mut args = list.nested.iter();
+            if list.path.is_ident("any") {
+                args.any(evaluate)
+            } else if list.path.is_ident("all") {
+                args.all(evaluate)
+            } else if list.path.is_ident("not") && list.nested.len() == 1 {
+                !evaluate(&list.nested[0])
+            } else {
+                false
+            }
+        }
+        NestedMeta::Lit(_) => false,
+    }
+}
+
+/// The values of a `cfg` key for the target, or `None` if it's not set.
+/// Flags such as `unix` have no values, but are still `Some`.
+fn target_values(key: &str) -> Option<Vec<String>> {
+    if std::env::var_os("CARGO_CFG_TARGET_OS").is_some() {
+        std::env::var(format!("CARGO_CFG_{}", key.to_uppercase()))
+            .ok()
+            .map(|values| values.split(',').map(str::to_string).collect())
+    } else {
+        host_values(key)
+    }
+}
+
+fn feature_enabled(feature: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+    std::env::var_os(var).is_some()
+}
+
+fn host_values(key: &str) -> Option<Vec<String>> {
+    let family = std::env::consts::FAMILY;
+    let value = match key {
+        "unix" | "windows" if key == family => return Some(Vec::new()),
+        "target_family" => family.to_string(),
+        "target_os" => std::env::consts::OS.to_string(),
+        "target_arch" => std::env::consts::ARCH.to_string(),
+        "target_pointer_width" => usize::BITS.to_string(),
+        "target_endian" if cfg!(target_endian = "little") => "little".to_string(),
+        "target_endian" => "big".to_string(),
+        _ => return None,
+    };
+    Some(vec![value])
+}
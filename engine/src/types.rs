@@ -8,15 +8,36 @@
 
 use itertools::Itertools;
 use proc_macro2::Span;
-use quote::ToTokens;
+use quote::format_ident;
 use std::iter::Peekable;
 use std::{fmt::Display, sync::Arc};
 use syn::{parse_quote, Ident, PathSegment, TypePath};
 
+pub(crate) use autocxx_parser::is_rust_keyword;
+
 use crate::{conversion::ConvertError, known_types::known_types};
 
+/// Make an identifier. Names which are Rust keywords become raw identifiers,
+/// e.g. `r#type`. The string form of a raw identifier (with its `r#` prefix)
+/// is also accepted.
 pub(crate) fn make_ident<S: AsRef<str>>(id: S) -> Ident {
-    Ident::new(id.as_ref(), Span::call_site())
+    let id = id.as_ref();
+    let id = id.strip_prefix("r#").unwrap_or(id);
+    if is_rust_keyword(id) {
+        format_ident!("r#{}", id)
+    } else {
+        Ident::new(id, Span::call_site())
+    }
+}
+
+/// bindgen appends an underscore to names which are Rust keywords, e.g.
+/// `move_`. If this is such a name, returns the raw identifier which we
+/// use instead, e.g. `r#move`.
+pub(crate) fn unmangle_keyword(id: &Ident) -> Option<Ident> {
+    id.to_string()
+        .strip_suffix('_')
+        .filter(|id| is_rust_keyword(id))
+        .map(make_ident)
 }
 
 /// Newtype wrapper for a C++ namespace.
@@ -240,16 +261,18 @@ pub fn validate_ident_ok_for_cxx(id: &str) -> Result<(), ConvertError> {
     }
 }
 
+/// Whether this name can be used as-is as a Rust identifier, i.e.
+/// without being turned into a raw identifier.
 pub fn validate_ident_ok_for_rust(label: &str) -> Result<(), ConvertError> {
-    let id = make_ident(label);
-    syn::parse2::<syn::Ident>(id.into_token_stream())
+    syn::parse_str::<syn::Ident>(label)
         .map_err(|_| ConvertError::ReservedName(label.to_string()))
         .map(|_| ())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::QualifiedName;
+    use super::{make_ident, unmangle_keyword, validate_ident_ok_for_rust, QualifiedName};
+    use syn::parse_quote;
 
     #[test]
     fn test_ints() {
@@ -262,4 +285,28 @@ mod tests {
             "uint64_t"
         );
     }
+
+    #[test]
+    fn test_raw_idents() {
+        assert_eq!(make_ident("type").to_string(), "r#type");
+        assert_eq!(make_ident("r#type").to_string(), "r#type");
+        assert_eq!(make_ident("typed").to_string(), "typed");
+        assert_eq!(make_ident("self").to_string(), "self");
+        assert!(validate_ident_ok_for_rust("type").is_err());
+        assert!(validate_ident_ok_for_rust("typed").is_ok());
+    }
+
+    #[test]
+    fn test_unmangle_keyword() {
+        assert_eq!(
+            unmangle_keyword(&parse_quote! { move_ })
+                .unwrap()
+                .to_string(),
+            "r#move"
+        );
+        assert!(unmangle_keyword(&parse_quote! { moved_ }).is_none());
+        assert!(unmangle_keyword(&make_ident("move")).is_none());
+        // These can't be raw identifiers, so bindgen's name is kept.
+        assert!(unmangle_keyword(&parse_quote! { self_ }).is_none());
+    }
 }
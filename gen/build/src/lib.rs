@@ -14,6 +14,8 @@ use std::{ffi::OsStr, path::Path};
 
 pub type Builder = autocxx_engine::Builder<'static, CargoBuilderContext>;
 
+pub use autocxx_engine::{RustPath, TypeMapper, TypeMapping};
+
 #[deprecated]
 /// Use [`Builder::new`] instead
 pub fn build<P1, I, T>(
@@ -53,9 +55,13 @@ pub struct CargoBuilderContext;
 
 impl BuilderContext for CargoBuilderContext {
     fn setup() {
-        env_logger::builder()
+        // Show warnings, such as problems generating bindings, unless
+        // asked otherwise using RUST_LOG.
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn"))
             .format(|buf, record| writeln!(buf, "cargo:warning=MESSAGE:{}", record.args()))
             .init();
+        println!("cargo:rerun-if-env-changed=AUTOCXX_INC");
+        println!("cargo:rerun-if-env-changed=AUTOCXX_UPDATE_LOCK");
     }
     fn get_dependency_recorder() -> Option<Box<dyn RebuildDependencyRecorder>> {
         Some(Box::new(CargoRebuildDependencyRecorder::new()))
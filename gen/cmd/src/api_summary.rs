@@ -0,0 +1,166 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A stable textual summary of the Rust API generated by autocxx, with one
+//! line per public item (or field, variant or method thereof), so that
+//! binding crates can spot accidental API changes, e.g. when their headers
+//! change or when upgrading autocxx. Each line is prefixed by the path of
+//! the mod containing the item.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{Attribute, Fields, ForeignItem, ImplItem, Item, ItemMod, Meta, NestedMeta, Visibility};
+
+/// Summarizes the public items within the given bindings.
+pub(crate) fn summarize_api(bindings: TokenStream) -> Vec<String> {
+    let bindings: ItemMod = match syn::parse2(bindings) {
+        Ok(bindings) => bindings,
+        Err(_) => return Vec::new(), // e.g. parse-only mode
+    };
+    let mut lines = Vec::new();
+    summarize_mod(&bindings, &bindings.ident.to_string(), &mut lines);
+    lines
+}
+
+fn summarize_mod(m: &ItemMod, path: &str, lines: &mut Vec<String>) {
+    for item in m.content.iter().flat_map(|(_, items)| items) {
+        if let Item::Mod(inner) = item {
+            summarize_mod(inner, &format!("{}::{}", path, inner.ident), lines);
+            continue;
+        }
+        let mut add = |desc: String| lines.push(format!("{}: {}", path, desc));
+        match item {
+            Item::ForeignMod(fm) => {
+                for fi in &fm.items {
+                    match fi {
+                        ForeignItem::Fn(f) if !is_hidden(&f.attrs) => add(tokens(&f.sig)),
+                        ForeignItem::Type(t) if !is_hidden(&t.attrs) => {
+                            add(format!("type {}", t.ident))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ if !is_public(item) => {}
+            Item::Fn(f) => add(tokens(&f.sig)),
+            Item::Struct(s) => {
+                add(format!("struct {}{}", s.ident, tokens(&s.generics)));
+                if let Fields::Named(fields) = &s.fields {
+                    for field in &fields.named {
+                        if matches!(field.vis, Visibility::Public(_)) {
+                            add(format!(
+                                "field {}::{}: {}",
+                                s.ident,
+                                field.ident.as_ref().unwrap(),
+                                tokens(&field.ty)
+                            ));
+                        }
+                    }
+                }
+            }
+            Item::Enum(e) => {
+                add(format!("enum {}{}", e.ident, tokens(&e.generics)));
+                for variant in &e.variants {
+                    add(format!("variant {}::{}", e.ident, tokens(variant)));
+                }
+            }
+            Item::Type(t) => add(format!(
+                "type {}{} = {}",
+                t.ident,
+                tokens(&t.generics),
+                tokens(&t.ty)
+            )),
+            Item::Const(c) => add(format!("const {}: {}", c.ident, tokens(&c.ty))),
+            Item::Static(s) => add(format!("static {}: {}", s.ident, tokens(&s.ty))),
+            Item::Use(u) => add(format!("use {}", tokens(&u.tree))),
+            Item::Trait(t) => {
+                add(format!("trait {}{}", t.ident, tokens(&t.generics)));
+                for trait_item in &t.items {
+                    if let syn::TraitItem::Method(m) = trait_item {
+                        add(format!("method {}::{}", t.ident, tokens(&m.sig)));
+                    }
+                }
+            }
+            Item::Impl(i) => match &i.trait_ {
+                Some((_, trait_path, _)) => add(format!(
+                    "impl {} for {}",
+                    tokens(trait_path),
+                    tokens(&i.self_ty)
+                )),
+                None => {
+                    for impl_item in &i.items {
+                        if let ImplItem::Method(m) = impl_item {
+                            if matches!(m.vis, Visibility::Public(_)) && !is_hidden(&m.attrs) {
+                                add(format!("method {}::{}", tokens(&i.self_ty), tokens(&m.sig)));
+                            }
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Whether this item is visible outside its mod and not hidden from the
+/// docs. Impls and macros have no visibility of their own.
+fn is_public(item: &Item) -> bool {
+    let (vis, attrs) = match item {
+        Item::Fn(i) => (Some(&i.vis), &i.attrs),
+        Item::Struct(i) => (Some(&i.vis), &i.attrs),
+        Item::Enum(i) => (Some(&i.vis), &i.attrs),
+        Item::Type(i) => (Some(&i.vis), &i.attrs),
+        Item::Const(i) => (Some(&i.vis), &i.attrs),
+        Item::Static(i) => (Some(&i.vis), &i.attrs),
+        Item::Use(i) => (Some(&i.vis), &i.attrs),
+        Item::Trait(i) => (Some(&i.vis), &i.attrs),
+        Item::Impl(i) => (None, &i.attrs),
+        _ => return false,
+    };
+    !is_hidden(attrs) && vis.is_none_or(|vis| matches!(vis, Visibility::Public(_)))
+}
+
+fn is_hidden(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(list)) if list.path.is_ident("doc") => list.nested.iter().any(
+            |nested| matches!(nested, NestedMeta::Meta(Meta::Path(p)) if p.is_ident("hidden")),
+        ),
+        _ => false,
+    })
+}
+
+fn tokens(t: &impl ToTokens) -> String {
+    t.to_token_stream().to_string()
+}
+
+/// Writes the given summary to `path`, sorted so that it's stable.
+pub(crate) fn write_api_summary(path: &Path, lines: Vec<String>) -> std::io::Result<()> {
+    let lines: BTreeSet<String> = lines.into_iter().collect();
+    let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    std::fs::write(path, content)
+}
+
+/// Compares the given summary against one previously written to `path`,
+/// reporting each line removed or added to stderr. Returns whether any
+/// lines were removed, since that's a change which may break users of the
+/// bindings; a changed signature counts as a removal and an addition.
+pub(crate) fn diff_api_summary(path: &Path, lines: Vec<String>) -> std::io::Result<bool> {
+    let lines: BTreeSet<String> = lines.into_iter().collect();
+    let previous = std::fs::read_to_string(path)?;
+    let previous: BTreeSet<String> = previous.lines().map(str::to_string).collect();
+    for removed in previous.difference(&lines) {
+        eprintln!("- {}", removed);
+    }
+    for added in lines.difference(&previous) {
+        eprintln!("+ {}", added);
+    }
+    Ok(!previous.is_subset(&lines))
+}
@@ -0,0 +1,74 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Formatting of the generated C++ and Rust using clang-format and
+//! rustfmt, so that output which is checked in can be reviewed sensibly.
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Formats some generated C++ using clang-format, or whatever the
+/// `CLANG_FORMAT` environment variable names. `path` is where the C++ will
+/// be written: it determines whether the content is treated as a header,
+/// and which `.clang-format` file, if any, applies.
+pub(crate) fn clang_format(path: &Path, content: &[u8]) -> io::Result<Vec<u8>> {
+    let mut assume_filename = OsString::from("--assume-filename=");
+    assume_filename.push(path);
+    run_formatter(
+        "CLANG_FORMAT",
+        "clang-format",
+        &[assume_filename.as_os_str()],
+        path,
+        content,
+    )
+}
+
+/// Formats some generated Rust using rustfmt, or whatever the `RUSTFMT`
+/// environment variable names. `path` is where the Rust will be written,
+/// which is used only in error messages: rustfmt looks for a `rustfmt.toml`
+/// starting from the current directory.
+pub(crate) fn rustfmt(path: &Path, content: &[u8]) -> io::Result<Vec<u8>> {
+    run_formatter(
+        "RUSTFMT",
+        "rustfmt",
+        &[OsStr::new("--edition"), OsStr::new("2021")],
+        path,
+        content,
+    )
+}
+
+fn run_formatter(
+    env_var: &str,
+    default_program: &str,
+    args: &[&OsStr],
+    path: &Path,
+    content: &[u8],
+) -> io::Result<Vec<u8>> {
+    let program = std::env::var_os(env_var).unwrap_or_else(|| OsString::from(default_program));
+    let mut child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // Formatters read all their input before writing anything, so they
+    // can't block on a full stdout pipe while we're writing.
+    child.stdin.take().unwrap().write_all(content)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} failed on {}: {}",
+            program.to_string_lossy(),
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(output.stdout)
+}
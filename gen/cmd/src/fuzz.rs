@@ -0,0 +1,176 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generation of `cargo fuzz` harnesses for functions exposed by an
+//! `include_cpp!` block. We work from the generated Rust bindings rather
+//! than from the engine's internal API representation, because that's
+//! exactly the layer we wish to exercise: each harness calls the function
+//! by the same path that a user of the bindings would.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ForeignItem, Ident, Item, ItemMod, Signature, Type, UseTree};
+
+/// Primitive types for which `arbitrary` can conjure up values, and which
+/// we know can be passed straight through the generated bindings.
+static FUZZABLE_PRIMITIVES: &[&str] = &[
+    "i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "isize", "usize", "f32", "f64", "bool",
+];
+
+/// A single cargo-fuzz target.
+pub(crate) struct FuzzTarget {
+    pub(crate) filename: String,
+    pub(crate) content: String,
+}
+
+/// Works out which functions within the generated bindings `mod` can be
+/// fuzzed (because all their parameters are primitives) and returns a
+/// harness for each one. If `selected` is non-empty, only functions with
+/// those names are considered.
+pub(crate) fn generate_fuzz_targets(
+    crate_name: &str,
+    bindings: TokenStream,
+    selected: &[&str],
+) -> Vec<FuzzTarget> {
+    let bindings: ItemMod = match syn::parse2(bindings) {
+        Ok(bindings) => bindings,
+        Err(_) => return Vec::new(), // e.g. parse-only mode
+    };
+    let mut signatures = HashMap::new();
+    find_signatures(&bindings, &mut signatures);
+    let mut exports = Vec::new();
+    find_exported_functions(&bindings, Vec::new(), &mut exports);
+    let crate_name = format_ident!("{}", crate_name.replace('-', "_"));
+    exports
+        .into_iter()
+        .filter(|(_, exported, _)| {
+            selected.is_empty() || selected.iter().any(|sel| exported == sel)
+        })
+        .filter_map(|(path, exported, original)| {
+            let sig = signatures.get(&original)?;
+            let arg_types = fuzzable_arg_types(sig)?;
+            Some(generate_harness(&crate_name, &path, &exported, &arg_types))
+        })
+        .collect()
+}
+
+/// Records the signature of every function in the `cxxbridge` mod, and of
+/// every Rust wrapper function in the `bindgen` mod, keyed by name.
+fn find_signatures(m: &ItemMod, signatures: &mut HashMap<Ident, Signature>) {
+    for item in m.content.iter().flat_map(|(_, items)| items) {
+        match item {
+            Item::Mod(inner) => find_signatures(inner, signatures),
+            Item::ForeignMod(fm) => {
+                for fi in &fm.items {
+                    if let ForeignItem::Fn(f) = fi {
+                        signatures.insert(f.sig.ident.clone(), f.sig.clone());
+                    }
+                }
+            }
+            Item::Fn(f) => {
+                signatures.insert(f.sig.ident.clone(), f.sig.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds the `use` statements by which functions are exposed to users of
+/// the bindings, returning the path of the enclosing mod, the exported name
+/// and the name of the original function.
+fn find_exported_functions(
+    m: &ItemMod,
+    mut path: Vec<Ident>,
+    exports: &mut Vec<(Vec<Ident>, Ident, Ident)>,
+) {
+    if m.ident == "cxxbridge" || m.ident == "bindgen" {
+        return;
+    }
+    path.push(m.ident.clone());
+    for item in m.content.iter().flat_map(|(_, items)| items) {
+        match item {
+            Item::Mod(inner) => find_exported_functions(inner, path.clone(), exports),
+            Item::Use(u) => {
+                let mut tree = &u.tree;
+                while let UseTree::Path(up) = tree {
+                    tree = &up.tree;
+                }
+                match tree {
+                    UseTree::Name(un) => {
+                        exports.push((path.clone(), un.ident.clone(), un.ident.clone()))
+                    }
+                    UseTree::Rename(urn) => {
+                        exports.push((path.clone(), urn.rename.clone(), urn.ident.clone()))
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the argument types if every one of them is something we know
+/// how to generate arbitrarily.
+fn fuzzable_arg_types(sig: &Signature) -> Option<Vec<Type>> {
+    if !sig.generics.params.is_empty() {
+        return None;
+    }
+    sig.inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pt) => match pt.ty.as_ref() {
+                Type::Path(typ)
+                    if typ.qself.is_none()
+                        && typ
+                            .path
+                            .get_ident()
+                            .map(|id| FUZZABLE_PRIMITIVES.iter().any(|p| id == p))
+                            .unwrap_or(false) =>
+                {
+                    Some(Type::Path(typ.clone()))
+                }
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+fn generate_harness(
+    crate_name: &Ident,
+    path: &[Ident],
+    exported: &Ident,
+    arg_types: &[Type],
+) -> FuzzTarget {
+    let arg_names: Vec<_> = (0..arg_types.len())
+        .map(|n| format_ident!("arg{}", n))
+        .collect();
+    let ts = quote! {
+        #![no_main]
+        use libfuzzer_sys::fuzz_target;
+
+        fuzz_target!(|input: ( #(#arg_types,)* )| {
+            let ( #(#arg_names,)* ) = input;
+            #[allow(unused_unsafe)]
+            let _ = unsafe { #crate_name :: #(#path::)* #exported ( #(#arg_names),* ) };
+        });
+    };
+    let filename = path
+        .iter()
+        .chain(std::iter::once(exported))
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join("_");
+    FuzzTarget {
+        filename: format!("{}.rs", filename),
+        content: ts.to_string(),
+    }
+}
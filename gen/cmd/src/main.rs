@@ -8,7 +8,16 @@
 
 #![forbid(unsafe_code)]
 
-use autocxx_engine::{parse_file, HeaderNamer};
+mod api_summary;
+mod formatter;
+mod fuzz;
+mod pretty_printers;
+mod symbols;
+
+use autocxx_engine::{
+    explain_error_code, parse_file, CppOutputLayout, HeaderNamer, ShimVisibility,
+    WarningSuppression,
+};
 use clap::{crate_authors, crate_version, App, Arg, ArgGroup};
 use proc_macro2::TokenStream;
 use quote::ToTokens;
@@ -70,6 +79,95 @@ which `.include.rs` file to use, so the only way to get fixed output paths is
 with `--gen-rs-complete`. There are always multiple `.cc` files (even with just
 a single `include_cpp!` section), and we always generate the same number of each
 type of file.
+
+If you pass `--emit-fuzz-targets <CRATE>`, a `fuzz_targets` directory will
+additionally be created in the output directory, containing a `cargo fuzz`
+harness for each generated function whose parameters are all primitive
+types. `<CRATE>` is the name of the crate containing the `include_cpp!`
+macro, which the harnesses call into. Use `--fuzz-function` to restrict
+this to specific functions.
+
+If you pass `--gen-pretty-printers <CRATE>`, debugger pretty-printers will be
+written to the output directory for each opaque C++ type, and any `UniquePtr`
+or `CxxVector` containing such a type: `autocxx_printers.py` for GDB,
+`autocxx_lldb.py` for LLDB and `autocxx.natvis` for Visual Studio. These show
+the C++ contents of such types, instead of raw bytes. `<CRATE>` is the name of
+the crate containing the `include_cpp!` macro, which must be at the crate root.
+
+If you pass `--symbols-file <PATH>` along with `--gen-cpp`, the symbols in the
+final generated C++ will be recorded in that file: those generated by cxx,
+and the names of all autocxx's shims, including any which are emitted as
+references to identical shims. Any changes since the previous run will be
+reported. Pass `--fail-on-symbol-changes` as well to fail if there are any
+such changes, for instance if you ship a library whose exported symbols must
+remain stable.
+See also the `mangling_version!` directive.
+
+If you pass `--api-summary <PATH>`, a sorted list of the public items in the
+generated Rust API, with their signatures, will be written to that file. Check
+it in, and pass it to a later run using `--diff <PATH>` to see what's been
+removed (`-`) or added (`+`) since, for instance when your headers or autocxx
+itself have changed. With `--diff`, the run fails if anything was removed or
+changed, since that may break users of the bindings. Additions are fine.
+
+By default, the C++ functions generated by autocxx for each `include_cpp!`
+section are written into a single `.cc` file, which is fastest to build from
+scratch. Pass `--cpp-shards <N>` to spread them across `N` files instead, which
+can be built in parallel and need less rebuilding after small changes. The
+assignment of functions to files is deterministic. Bear in mind that this
+increases the number of `.cc` files, so with `--generate-exact`, `N` must allow
+for the extra files.
+
+Pass `--clang-format` to format the generated C++ using `clang-format`, which
+makes it reviewable if you check it in. Any `.clang-format` file applying to
+the output directory is respected. Set the `CLANG_FORMAT` environment variable
+to use a particular `clang-format` binary. Different versions of `clang-format`
+may format the code differently.
+
+Similarly, pass `--rustfmt` to format the generated Rust using `rustfmt` (or
+whatever the `RUSTFMT` environment variable names). Each C++ namespace is
+already a separate `mod` within the output, so once formatted, changes to one
+namespace produce a diff confined to that `mod`.
+
+The generated C++ is surrounded by pragmas suppressing the compiler warnings
+it's known to trigger, such as unused parameters, so that it can be built with
+`-Werror`. Pass `--no-warning-suppression` to omit them.
+
+The C++ shim functions generated by autocxx are only called by the C++ which
+cxx generates, so if you build a shared library, you may not want to export
+them. Pass `--hidden-shims` to give them hidden visibility, or
+`--shim-annotation <ANNOTATION>` to annotate them with your own export macro.
+
+If you pass `--bindings-cache-dir <PATH>`, the output of bindgen will be saved
+in that directory, keyed by the headers and clang arguments. Later runs whose
+headers (including anything they include) haven't changed will reuse it rather
+than parsing the C++ using libclang, so long as their `include_cpp!` directives
+require no C++ APIs beyond those already generated. If your CI restores this
+directory from a cache, such runs needn't load libclang at all. Cached
+bindings are keyed by absolute paths, so they won't be reused if the checkout
+moves.
+
+If your builds are slow, pass `--size-report` to find out which requests in
+your `include_cpp!` sections are responsible for the most generated code. For
+each item requested (e.g. by `generate!`) this prints the number of APIs it
+pulled in, including anything those APIs depend upon, and how many Rust items,
+Rust tokens and bytes of C++ shims were generated for them. The biggest come
+first. An API needed by several requested items counts towards each of them.
+The C++ subsequently generated by cxx isn't included.
+
+If you pass `--gen-c-header`, then alongside the C++ header for each
+`include_cpp!` section, a plain C header (named like it, but ending `_c.h`) is
+generated, declaring those Rust functions given using `extern_rust_function`
+whose parameters and return types are all primitives or pointers to
+primitives. Each is prefixed with the name of the `include_cpp!` mod, e.g.
+`ffi_do_thing`, and is implemented by a `#[no_mangle] extern \"C\"` wrapper in the
+generated Rust. This lets C code, or other binding generators, call the same
+Rust code. The Rust must be generated with the same option.
+
+Each error reported by autocxx, whether as a build failure or in the
+documentation of a placeholder item where bindings couldn't be generated, has a
+code such as `ACX0042`. Run `autocxx-gen --explain ACX0042` to find out more
+about it, including typical workarounds. No other arguments are needed.
 ";
 
 fn main() {
@@ -81,7 +179,7 @@ fn main() {
         .arg(
             Arg::with_name("INPUT")
                 .help("Sets the input .rs file to use")
-                .required(true)
+                .required_unless("explain")
                 .index(1),
         )
         .arg(
@@ -91,7 +189,7 @@ fn main() {
                 .value_name("PATH")
                 .help("output directory path")
                 .takes_value(true)
-                .required(true),
+                .required_unless("explain"),
         )
         .arg(
             Arg::with_name("inc")
@@ -127,7 +225,6 @@ fn main() {
                 .help("whether to generate Rust files for inclusion using autocxx_macro (suffix will be .include.rs)")
         )
         .group(ArgGroup::with_name("mode")
-            .required(true)
             .multiple(true)
             .arg("gen-cpp")
             .arg("gen-rs-complete")
@@ -139,6 +236,48 @@ fn main() {
                 .help("Skip performing C++ codegen for #[cxx::bridge] blocks. Only applies for --gen-cpp")
                 .requires("gen-cpp")
         )
+        .arg(
+            Arg::with_name("line-directives")
+                .long("line-directives")
+                .help("Emit #line directives in generated C++ pointing back to the original headers, where known")
+        )
+        .arg(
+            Arg::with_name("clang-format")
+                .long("clang-format")
+                .help("Format the generated C++ using clang-format, or the program named by the CLANG_FORMAT environment variable")
+                .requires("gen-cpp")
+        )
+        .arg(
+            Arg::with_name("rustfmt")
+                .long("rustfmt")
+                .help("Format the generated Rust using rustfmt, or the program named by the RUSTFMT environment variable")
+        )
+        .arg(
+            Arg::with_name("no-warning-suppression")
+                .long("no-warning-suppression")
+                .help("Don't suppress the compiler warnings which generated C++ is known to trigger")
+        )
+        .arg(
+            Arg::with_name("hidden-shims")
+                .long("hidden-shims")
+                .help("Give the C++ shim functions generated by autocxx hidden visibility")
+        )
+        .arg(
+            Arg::with_name("shim-annotation")
+                .long("shim-annotation")
+                .value_name("ANNOTATION")
+                .help("annotation for the C++ shim functions generated by autocxx, e.g. an export macro")
+                .takes_value(true)
+                .conflicts_with("hidden-shims"),
+        )
+        .arg(
+            Arg::with_name("cpp-shards")
+                .long("cpp-shards")
+                .value_name("N")
+                .help("Spread the C++ functions generated for each include_cpp! across N .cc files")
+                .takes_value(true)
+                .requires("gen-cpp")
+        )
         .arg(
             Arg::with_name("generate-exact")
                 .long("generate-exact")
@@ -183,6 +322,82 @@ fn main() {
                 .help("prefix for path to cxxgen.h (which we generate into the output directory) within #include statements. Must end in /")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("emit-fuzz-targets")
+                .long("emit-fuzz-targets")
+                .value_name("CRATE")
+                .help("emit cargo-fuzz harnesses calling into the bindings within crate CRATE, for functions taking only primitive arguments")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fuzz-function")
+                .long("fuzz-function")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FUNCTION")
+                .help("only emit fuzz harnesses for this function. May be repeated. Only applies for --emit-fuzz-targets")
+                .takes_value(true)
+                .requires("emit-fuzz-targets"),
+        )
+        .arg(
+            Arg::with_name("gen-pretty-printers")
+                .long("gen-pretty-printers")
+                .value_name("CRATE")
+                .help("emit GDB, LLDB and natvis pretty-printers for opaque types within the bindings in crate CRATE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("symbols-file")
+                .long("symbols-file")
+                .value_name("PATH")
+                .help("record the symbols in the generated C++ in this file, reporting any changes since the previous run. Only applies for --gen-cpp")
+                .takes_value(true)
+                .requires("gen-cpp"),
+        )
+        .arg(
+            Arg::with_name("fail-on-symbol-changes")
+                .long("fail-on-symbol-changes")
+                .help("fail if the generated symbols have changed since the previous run. Only applies for --symbols-file")
+                .requires("symbols-file"),
+        )
+        .arg(
+            Arg::with_name("api-summary")
+                .long("api-summary")
+                .value_name("PATH")
+                .help("write a sorted list of the signatures of the generated Rust API to this file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("diff")
+                .long("diff")
+                .value_name("PATH")
+                .help("compare the generated Rust API against a list previously written using --api-summary, failing if anything was removed or changed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bindings-cache-dir")
+                .long("bindings-cache-dir")
+                .value_name("PATH")
+                .help("save the output of bindgen in this directory, and reuse it on later runs if the headers are unchanged")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("size-report")
+                .long("size-report")
+                .help("print how much code was generated on behalf of each item requested in include_cpp!"),
+        )
+        .arg(
+            Arg::with_name("gen-c-header")
+                .long("gen-c-header")
+                .help("additionally generate a C header for Rust functions with C-compatible signatures"),
+        )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .value_name("CODE")
+                .help("print a detailed explanation of an error code, e.g. ACX0042, and exit")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("clang-args")
                 .last(true)
@@ -191,7 +406,27 @@ fn main() {
         )
         .get_matches();
 
-    env_logger::builder().init();
+    if let Some(code) = matches.value_of("explain") {
+        match explain_error_code(code) {
+            Some(explanation) => println!("{}", explanation),
+            None => {
+                eprintln!("{} is not an autocxx error code.", code);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if !matches.is_present("mode") {
+        clap::Error::with_description(
+            "One of --gen-cpp, --gen-rs-complete or --gen-rs-include must be specified",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+
+    // Show warnings, such as problems generating bindings, unless asked
+    // otherwise using RUST_LOG.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     let mut parsed_file = parse_file(
         matches.value_of("INPUT").unwrap(),
         matches.is_present("auto-allowlist"),
@@ -226,7 +461,30 @@ fn main() {
         path_to_cxx_h: get_option_string("cxx-h-path", &matches),
         path_to_cxxgen_h: get_option_string("cxxgen-h-path", &matches),
         skip_cxx_gen: matches.is_present("skip-cxx-gen"),
+        emit_line_directives: matches.is_present("line-directives"),
+        output_layout: match matches.value_of("cpp-shards") {
+            Some(shards) => CppOutputLayout::Sharded(
+                shards
+                    .parse()
+                    .expect("--cpp-shards must be a number of files"),
+            ),
+            None => CppOutputLayout::Unity,
+        },
+        warning_suppression: if matches.is_present("no-warning-suppression") {
+            WarningSuppression::Off
+        } else {
+            WarningSuppression::Targeted
+        },
+        shim_visibility: match get_option_string("shim-annotation", &matches) {
+            Some(annotation) => ShimVisibility::Annotation(annotation),
+            None if matches.is_present("hidden-shims") => ShimVisibility::Hidden,
+            None => ShimVisibility::Default,
+        },
+        bindings_cache_dir: matches.value_of_os("bindings-cache-dir").map(PathBuf::from),
+        size_report: matches.is_present("size-report"),
+        c_header: matches.is_present("gen-c-header"),
         header_namer,
+        type_mappers: Vec::new(),
     };
     // In future, we should provide an option to write a .d file here
     // by passing a callback into the dep_recorder parameter here.
@@ -235,32 +493,74 @@ fn main() {
         .resolve_all(incs, &extra_clang_args, None, &cpp_codegen_options)
         .expect("Unable to resolve macro");
     let outdir: PathBuf = matches.value_of_os("outdir").unwrap().into();
+    if matches.is_present("size-report") {
+        for include_cxx in parsed_file.get_rs_buildables() {
+            if let Some(report) = include_cxx.get_size_report() {
+                println!("Generated code for mod {}:", include_cxx.get_mod_name());
+                print!("{}", report);
+            }
+        }
+    }
     if matches.is_present("gen-cpp") {
         let cpp = matches.value_of("cpp-extension").unwrap();
         let mut counter = 0usize;
+        let mut symbols = Vec::new();
         for include_cxx in parsed_file.get_cpp_buildables() {
             let generations = include_cxx
                 .generate_h_and_cxx(&cpp_codegen_options)
                 .expect("Unable to generate header and C++ code");
+            if matches.is_present("symbols-file") {
+                symbols.extend(symbols::find_symbols(&generations));
+            }
+            let maybe_format = |fname: &str, content: Vec<u8>| {
+                if matches.is_present("clang-format") {
+                    formatter::clang_format(&outdir.join(fname), &content)
+                        .expect("Unable to format generated C++")
+                } else {
+                    content
+                }
+            };
             for pair in generations.0 {
                 let cppname = format!("gen{}.{}", counter, cpp);
-                write_to_file(&outdir, cppname, &pair.implementation.unwrap_or_default());
-                write_to_file(&outdir, pair.header_name, &pair.header);
+                let implementation =
+                    maybe_format(&cppname, pair.implementation.unwrap_or_default());
+                write_to_file(&outdir, cppname, &implementation);
+                let header = maybe_format(&pair.header_name, pair.header);
+                write_to_file(&outdir, pair.header_name, &header);
                 counter += 1;
+                for implementation in pair.additional_implementations {
+                    let cppname = format!("gen{}.{}", counter, cpp);
+                    let implementation = maybe_format(&cppname, implementation);
+                    write_to_file(&outdir, cppname, &implementation);
+                    counter += 1;
+                }
             }
         }
         write_placeholders(&outdir, counter, desired_number, cpp);
+        if let Some(symbols_file) = matches.value_of_os("symbols-file") {
+            let changed = symbols::update_symbols_file(Path::new(symbols_file), symbols)
+                .expect("Unable to update symbols file");
+            if changed && matches.is_present("fail-on-symbol-changes") {
+                eprintln!("Generated symbols have changed since the previous run.");
+                std::process::exit(1);
+            }
+        }
     }
     drop(cpp_codegen_options);
     write_placeholders(&outdir, header_counter.into_inner(), desired_number, "h");
+    let maybe_rustfmt = |fname: &str, content: String| {
+        if matches.is_present("rustfmt") {
+            formatter::rustfmt(&outdir.join(fname), content.as_bytes())
+                .expect("Unable to format generated Rust")
+        } else {
+            content.into_bytes()
+        }
+    };
     if matches.is_present("gen-rs-complete") {
         let mut ts = TokenStream::new();
         parsed_file.to_tokens(&mut ts);
-        write_to_file(
-            &outdir,
-            "gen.complete.rs".to_string(),
-            ts.to_string().as_bytes(),
-        );
+        let content = maybe_rustfmt("gen.complete.rs", ts.to_string());
+        write_to_file(&outdir, "gen.complete.rs".to_string(), &content);
     }
     if matches.is_present("gen-rs-include") {
         let autocxxes = parsed_file.get_rs_buildables();
@@ -272,13 +572,70 @@ fn main() {
             } else {
                 include_cxx.get_rs_filename()
             };
-            write_to_file(&outdir, fname, ts.to_string().as_bytes());
+            let content = maybe_rustfmt(&fname, ts.to_string());
+            write_to_file(&outdir, fname, &content);
             counter += 1;
         }
         if matches.is_present("fix-rs-include-name") {
             write_placeholders(&outdir, counter, desired_number, "include.rs");
         }
     }
+    if let Some(crate_name) = matches.value_of("emit-fuzz-targets") {
+        let selected: Vec<_> = matches
+            .values_of("fuzz-function")
+            .unwrap_or_default()
+            .collect();
+        let fuzz_dir = outdir.join("fuzz_targets");
+        std::fs::create_dir_all(&fuzz_dir).expect("Unable to create fuzz_targets directory");
+        for include_cxx in parsed_file.get_rs_buildables() {
+            for target in
+                fuzz::generate_fuzz_targets(crate_name, include_cxx.generate_rs(), &selected)
+            {
+                write_to_file(&fuzz_dir, target.filename, target.content.as_bytes());
+            }
+        }
+    }
+    if let Some(crate_name) = matches.value_of("gen-pretty-printers") {
+        let (gdb, lldb, natvis) = pretty_printers::generate_pretty_printers(
+            crate_name,
+            parsed_file
+                .get_rs_buildables()
+                .map(|include_cxx| include_cxx.generate_rs()),
+        );
+        write_to_file(
+            &outdir,
+            pretty_printers::GDB_PRINTERS_FILENAME.to_string(),
+            gdb.as_bytes(),
+        );
+        write_to_file(
+            &outdir,
+            pretty_printers::LLDB_PRINTERS_FILENAME.to_string(),
+            lldb.as_bytes(),
+        );
+        write_to_file(
+            &outdir,
+            pretty_printers::NATVIS_FILENAME.to_string(),
+            natvis.as_bytes(),
+        );
+    }
+    if matches.is_present("api-summary") || matches.is_present("diff") {
+        let summary: Vec<_> = parsed_file
+            .get_rs_buildables()
+            .flat_map(|include_cxx| api_summary::summarize_api(include_cxx.generate_rs()))
+            .collect();
+        if let Some(old_summary) = matches.value_of_os("diff") {
+            let removed = api_summary::diff_api_summary(Path::new(old_summary), summary.clone())
+                .expect("Unable to read previous API summary");
+            if removed {
+                eprintln!("Items have been removed from, or changed in, the generated API.");
+                std::process::exit(1);
+            }
+        }
+        if let Some(summary_file) = matches.value_of_os("api-summary") {
+            api_summary::write_api_summary(Path::new(summary_file), summary)
+                .expect("Unable to write API summary");
+        }
+    }
 }
 
 fn get_option_string(option: &str, matches: &clap::ArgMatches) -> Option<String> {
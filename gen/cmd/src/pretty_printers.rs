@@ -0,0 +1,385 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Generation of debugger pretty-printers for the opaque types exposed by an
+//! `include_cpp!` block. From the Rust side, such types (and any
+//! `UniquePtr` or `CxxVector` containing them) appear to a debugger as
+//! byte blobs; these printers instead reinterpret them as the C++ type,
+//! whose layout the debugger knows from the C++ debug info.
+
+use proc_macro2::TokenStream;
+use syn::{ImplItem, Item, ItemImpl, ItemMod, Macro, Type};
+
+/// Filename for the GDB Python pretty-printers.
+pub(crate) const GDB_PRINTERS_FILENAME: &str = "autocxx_printers.py";
+
+/// Filename for the LLDB Python formatters.
+pub(crate) const LLDB_PRINTERS_FILENAME: &str = "autocxx_lldb.py";
+
+/// Filename for the Visual Studio natvis visualizers.
+pub(crate) const NATVIS_FILENAME: &str = "autocxx.natvis";
+
+/// An opaque type exposed to Rust: its Rust path and its C++ name.
+struct OpaqueType {
+    rust_path: String,
+    cpp_name: String,
+}
+
+/// Finds all the opaque types within the generated bindings `mod`, by
+/// looking for the `cxx::ExternType` impls which autocxx generates.
+fn find_opaque_types(crate_name: &str, bindings: TokenStream) -> Vec<OpaqueType> {
+    let bindings: ItemMod = match syn::parse2(bindings) {
+        Ok(bindings) => bindings,
+        Err(_) => return Vec::new(), // e.g. parse-only mode
+    };
+    let prefix = format!("{}::{}", crate_name.replace('-', "_"), bindings.ident);
+    bindings
+        .content
+        .iter()
+        .flat_map(|(_, items)| items)
+        .filter_map(|item| match item {
+            Item::Impl(imp) => extern_type_details(imp),
+            _ => None,
+        })
+        .map(|(path, cpp_name)| OpaqueType {
+            rust_path: format!("{}::{}", prefix, path),
+            cpp_name,
+        })
+        .collect()
+}
+
+/// If this is an `impl cxx::ExternType` for an opaque type, returns the
+/// Rust path relative to the bindings mod, and the C++ type name.
+fn extern_type_details(imp: &ItemImpl) -> Option<(String, String)> {
+    let (_, trait_path, _) = imp.trait_.as_ref()?;
+    if trait_path.segments.last()?.ident != "ExternType" {
+        return None;
+    }
+    let self_path = match imp.self_ty.as_ref() {
+        Type::Path(typ) => typ
+            .path
+            .segments
+            .iter()
+            .map(|seg| seg.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::"),
+        _ => return None,
+    };
+    let mut cpp_name = None;
+    let mut opaque = false;
+    for item in &imp.items {
+        if let ImplItem::Type(ty) = item {
+            match (ty.ident.to_string().as_str(), &ty.ty) {
+                ("Id", Type::Macro(m)) => cpp_name = type_id_name(&m.mac),
+                ("Kind", Type::Path(typ)) => {
+                    opaque = typ
+                        .path
+                        .segments
+                        .last()
+                        .map(|seg| seg.ident == "Opaque")
+                        .unwrap_or(false)
+                }
+                _ => {}
+            }
+        }
+    }
+    if opaque {
+        cpp_name.map(|cpp_name| (self_path, cpp_name))
+    } else {
+        None
+    }
+}
+
+/// Extracts the name from `cxx::type_id!("a::B")`.
+fn type_id_name(mac: &Macro) -> Option<String> {
+    mac.parse_body::<syn::LitStr>().ok().map(|s| s.value())
+}
+
+/// Generates the GDB Python pretty-printers, LLDB Python formatters and
+/// Visual Studio natvis for all the opaque types within the given bindings.
+/// `crate_name` is the name of the crate containing the `include_cpp!` macro.
+pub(crate) fn generate_pretty_printers(
+    crate_name: &str,
+    bindings: impl Iterator<Item = TokenStream>,
+) -> (String, String, String) {
+    let types: Vec<_> = bindings
+        .flat_map(|bindings| find_opaque_types(crate_name, bindings))
+        .collect();
+    (
+        generate_gdb_printers(&types),
+        generate_lldb_printers(&types),
+        generate_natvis(&types),
+    )
+}
+
+fn generate_gdb_printers(types: &[OpaqueType]) -> String {
+    let type_table = types
+        .iter()
+        .map(|t| format!("    \"{}\": \"{}\",\n", t.rust_path, t.cpp_name))
+        .collect::<String>();
+    format!(
+        r#"# Generated by autocxx-gen. Do not edit.
+# Load with `source autocxx_printers.py` in GDB.
+
+import re
+
+import gdb
+
+# Map from Rust type path to C++ type name for each opaque type.
+AUTOCXX_TYPES = {{
+{}}}
+
+UNIQUE_PTR_RE = re.compile(r"^cxx::unique_ptr::UniquePtr<(.*)>$")
+CXX_VECTOR_RE = re.compile(r"^cxx::cxx_vector::CxxVector<(.*)>$")
+
+
+def _cpp_type(cpp_name):
+    try:
+        return gdb.lookup_type(cpp_name)
+    except gdb.error:
+        return None
+
+
+def _reinterpret(val, cpp_name):
+    cpp_type = _cpp_type(cpp_name)
+    if cpp_type is None or val.address is None:
+        return None
+    return val.address.cast(cpp_type.pointer()).dereference()
+
+
+class OpaqueTypePrinter:
+    def __init__(self, val, cpp_name):
+        self.val = val
+        self.cpp_name = cpp_name
+
+    def to_string(self):
+        cpp_val = _reinterpret(self.val, self.cpp_name)
+        if cpp_val is None:
+            return "<opaque C++ {{}}>".format(self.cpp_name)
+        return cpp_val
+
+
+class UniquePtrPrinter:
+    def __init__(self, val, cpp_name):
+        self.val = val
+        self.cpp_name = cpp_name
+
+    def to_string(self):
+        if self.val.address is None:
+            return "<UniquePtr<{{}}>>".format(self.cpp_name)
+        void_ptr = gdb.lookup_type("void").pointer()
+        ptr = self.val.address.cast(void_ptr.pointer()).dereference()
+        if int(ptr) == 0:
+            return "UniquePtr(null)"
+        cpp_type = _cpp_type(self.cpp_name)
+        if cpp_type is None:
+            return "UniquePtr<{{}}>({{}})".format(self.cpp_name, ptr)
+        return ptr.cast(cpp_type.pointer()).dereference()
+
+
+class CxxVectorPrinter:
+    def __init__(self, val, cpp_name):
+        self.val = val
+        self.cpp_name = cpp_name
+
+    def to_string(self):
+        cpp_val = _reinterpret(self.val, "std::vector<{{}}>".format(self.cpp_name))
+        if cpp_val is None:
+            return "<opaque std::vector<{{}}>>".format(self.cpp_name)
+        return cpp_val
+
+
+def autocxx_lookup(val):
+    name = val.type.strip_typedefs().tag
+    if name is None:
+        return None
+    if name in AUTOCXX_TYPES:
+        return OpaqueTypePrinter(val, AUTOCXX_TYPES[name])
+    m = UNIQUE_PTR_RE.match(name)
+    if m and m.group(1) in AUTOCXX_TYPES:
+        return UniquePtrPrinter(val, AUTOCXX_TYPES[m.group(1)])
+    m = CXX_VECTOR_RE.match(name)
+    if m and m.group(1) in AUTOCXX_TYPES:
+        return CxxVectorPrinter(val, AUTOCXX_TYPES[m.group(1)])
+    return None
+
+
+gdb.pretty_printers.append(autocxx_lookup)
+"#,
+        type_table
+    )
+}
+
+fn generate_lldb_printers(types: &[OpaqueType]) -> String {
+    let type_table = types
+        .iter()
+        .map(|t| format!("    \"{}\": \"{}\",\n", t.rust_path, t.cpp_name))
+        .collect::<String>();
+    format!(
+        r#"# Generated by autocxx-gen. Do not edit.
+# Load with `command script import autocxx_lldb.py` in LLDB.
+
+import re
+
+import lldb
+
+# Map from Rust type path to C++ type name for each opaque type.
+AUTOCXX_TYPES = {{
+{}}}
+
+UNIQUE_PTR_RE = re.compile(r"^cxx::unique_ptr::UniquePtr<(.*)>$")
+CXX_VECTOR_RE = re.compile(r"^cxx::cxx_vector::CxxVector<(.*)>$")
+
+
+def _cpp_type(valobj, cpp_name):
+    cpp_type = valobj.GetTarget().FindFirstType(cpp_name)
+    if not cpp_type.IsValid():
+        return None
+    return cpp_type
+
+
+def _reinterpret(valobj, cpp_name):
+    cpp_type = _cpp_type(valobj, cpp_name)
+    address = valobj.GetLoadAddress()
+    if cpp_type is None or address == lldb.LLDB_INVALID_ADDRESS:
+        return None
+    return valobj.CreateValueFromAddress(valobj.GetName(), address, cpp_type)
+
+
+def _unique_ptr_target(valobj, cpp_name):
+    # Returns the C++ object owned by a UniquePtr, None if the UniquePtr is
+    # null, or False if we can't tell.
+    address = valobj.GetLoadAddress()
+    void_ptr = valobj.GetTarget().GetBasicType(lldb.eBasicTypeVoid).GetPointerType()
+    if address == lldb.LLDB_INVALID_ADDRESS:
+        return False
+    ptr = valobj.CreateValueFromAddress("ptr", address, void_ptr).GetValueAsUnsigned()
+    if ptr == 0:
+        return None
+    cpp_type = _cpp_type(valobj, cpp_name)
+    if cpp_type is None:
+        return False
+    return valobj.CreateValueFromAddress("*ptr", ptr, cpp_type)
+
+
+def _cpp_value(valobj):
+    # Returns the C++ value which the given Rust value represents, None if
+    # it's a null UniquePtr, or False if it can't be found.
+    name = valobj.GetType().GetCanonicalType().GetName()
+    if name in AUTOCXX_TYPES:
+        return _reinterpret(valobj, AUTOCXX_TYPES[name]) or False
+    m = UNIQUE_PTR_RE.match(name)
+    if m and m.group(1) in AUTOCXX_TYPES:
+        return _unique_ptr_target(valobj, AUTOCXX_TYPES[m.group(1)])
+    m = CXX_VECTOR_RE.match(name)
+    if m and m.group(1) in AUTOCXX_TYPES:
+        vector_name = "std::vector<{{}}>".format(AUTOCXX_TYPES[m.group(1)])
+        return _reinterpret(valobj, vector_name) or False
+    return False
+
+
+class AutocxxProvider:
+    def __init__(self, valobj, internal_dict):
+        self.valobj = valobj
+        self.cpp_val = None
+
+    def update(self):
+        self.cpp_val = _cpp_value(self.valobj)
+        return False
+
+    def num_children(self):
+        return self.cpp_val.GetNumChildren() if self.cpp_val else 0
+
+    def get_child_index(self, name):
+        return self.cpp_val.GetIndexOfChildWithName(name) if self.cpp_val else -1
+
+    def get_child_at_index(self, index):
+        return self.cpp_val.GetChildAtIndex(index) if self.cpp_val else None
+
+    def has_children(self):
+        return self.num_children() > 0
+
+
+def autocxx_summary(valobj, internal_dict):
+    valobj = valobj.GetNonSyntheticValue()
+    cpp_val = _cpp_value(valobj)
+    if cpp_val is None:
+        return "UniquePtr(null)"
+    if cpp_val is False:
+        return "<opaque C++ {{}}>".format(valobj.GetType().GetName())
+    return cpp_val.GetSummary() or cpp_val.GetValue() or cpp_val.GetTypeName()
+
+
+def __lldb_init_module(debugger, internal_dict):
+    if not AUTOCXX_TYPES:
+        return
+    names = "|".join(re.escape(name) for name in AUTOCXX_TYPES)
+    for regex in (
+        "^({{}})$".format(names),
+        "^cxx::unique_ptr::UniquePtr<({{}})>$".format(names),
+        "^cxx::cxx_vector::CxxVector<({{}})>$".format(names),
+    ):
+        debugger.HandleCommand(
+            "type synthetic add -x '{{}}' -l {{}}.AutocxxProvider".format(regex, __name__)
+        )
+        debugger.HandleCommand(
+            "type summary add -x '{{}}' -F {{}}.autocxx_summary".format(regex, __name__)
+        )
+"#,
+        type_table
+    )
+}
+
+fn generate_natvis(types: &[OpaqueType]) -> String {
+    let entries = types
+        .iter()
+        .map(|t| {
+            let rust_path = xml_escape(&t.rust_path);
+            let cpp_name = xml_escape(&t.cpp_name);
+            format!(
+                r#"  <Type Name="{rust}">
+    <DisplayString>{{*({cpp}*)this}}</DisplayString>
+    <Expand>
+      <ExpandedItem>*({cpp}*)this</ExpandedItem>
+    </Expand>
+  </Type>
+  <Type Name="cxx::unique_ptr::UniquePtr&lt;{rust}&gt;">
+    <DisplayString Condition="*(void**)this == 0">UniquePtr(null)</DisplayString>
+    <DisplayString>{{**({cpp}**)this}}</DisplayString>
+    <Expand>
+      <ExpandedItem Condition="*(void**)this != 0">**({cpp}**)this</ExpandedItem>
+    </Expand>
+  </Type>
+  <Type Name="cxx::cxx_vector::CxxVector&lt;{rust}&gt;">
+    <DisplayString>{{*(std::vector&lt;{cpp}&gt;*)this}}</DisplayString>
+    <Expand>
+      <ExpandedItem>*(std::vector&lt;{cpp}&gt;*)this</ExpandedItem>
+    </Expand>
+  </Type>
+"#,
+                rust = rust_path,
+                cpp = cpp_name
+            )
+        })
+        .collect::<String>();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- Generated by autocxx-gen. Do not edit. -->
+<AutoVisualizer xmlns="http://schemas.microsoft.com/vstudio/debugger/natvis/2010">
+{}</AutoVisualizer>
+"#,
+        entries
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
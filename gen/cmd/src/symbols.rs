@@ -0,0 +1,95 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Tracking of the symbols in the C++ generated for the bindings: both
+//! those by which cxx's generated code and the Rust side call one another,
+//! and the names of autocxx's shims. Libraries which export such symbols
+//! can use this to spot changes between runs, e.g. when upgrading autocxx.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use autocxx_engine::GeneratedCpp;
+
+/// Prefix of a shim which is emitted only as a reference to an identical
+/// shim with another name.
+const SHIM_ALIAS_PREFIX: &str = "constexpr auto& ";
+
+/// Finds the symbols within the final C++ generated for some bindings.
+/// These are every cxx symbol (e.g. `cxxbridge1$DoMath`) mentioned
+/// anywhere in that C++, and every shim generated by autocxx. A shim
+/// which has been emitted as a reference to another, identical, shim is
+/// recorded as `name = other_name`, since it exports no symbol of its own.
+pub(crate) fn find_symbols(generated: &GeneratedCpp) -> Vec<String> {
+    let mut symbols = Vec::new();
+    for pair in &generated.0 {
+        let header = String::from_utf8_lossy(&pair.header);
+        let files = std::iter::once(&pair.header)
+            .chain(pair.implementation.iter())
+            .chain(pair.additional_implementations.iter());
+        for file in files {
+            find_cxx_symbols(&String::from_utf8_lossy(file), &mut symbols);
+        }
+        for (shim, _) in &pair.shim_origins {
+            symbols.push(match find_shim_alias(&header, shim) {
+                Some(target) => format!("{} = {}", shim, target),
+                None => shim.clone(),
+            });
+        }
+    }
+    symbols
+}
+
+/// Finds the identifiers in some C++ which are symbols mangled by cxx.
+/// These may be preceded by namespace segments, e.g. `ns$cxxbridge1$Foo`.
+fn find_cxx_symbols(cpp: &str, symbols: &mut Vec<String>) {
+    symbols.extend(
+        cpp.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .filter(|word| word.contains("cxxbridge1$"))
+            .map(str::to_string),
+    );
+}
+
+/// If the given shim was emitted as a reference to another shim, returns
+/// the name of that other shim.
+fn find_shim_alias<'a>(header: &'a str, shim: &str) -> Option<&'a str> {
+    header.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix(SHIM_ALIAS_PREFIX)?
+            .strip_prefix(shim)?
+            .strip_prefix(" = ")?
+            .strip_suffix(';')
+    })
+}
+
+/// Compares the given symbols against those recorded in `path` by a
+/// previous run (if any), reports any differences to stderr, and then
+/// records the new symbols. Returns whether anything changed.
+pub(crate) fn update_symbols_file(path: &Path, symbols: Vec<String>) -> std::io::Result<bool> {
+    let symbols: BTreeSet<String> = symbols.into_iter().collect();
+    let changed = match std::fs::read_to_string(path) {
+        Ok(previous) => {
+            let previous: BTreeSet<String> = previous.lines().map(str::to_string).collect();
+            for removed in previous.difference(&symbols) {
+                eprintln!("Symbol removed since previous run: {}", removed);
+            }
+            for added in symbols.difference(&previous) {
+                eprintln!("Symbol added since previous run: {}", added);
+            }
+            previous != symbols
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => return Err(e),
+    };
+    let content: String = symbols
+        .iter()
+        .map(|symbol| format!("{}\n", symbol))
+        .collect();
+    std::fs::write(path, content)?;
+    Ok(changed)
+}
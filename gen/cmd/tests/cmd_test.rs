@@ -22,6 +22,19 @@ fn test_help() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_explain() -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::cargo_bin("autocxx-gen")?
+        .arg("--explain")
+        .arg("ACX0021")
+        .output()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("extern_cpp_opaque_type!"));
+    let mut cmd = Command::cargo_bin("autocxx-gen")?;
+    cmd.arg("--explain").arg("ACX9999").assert().failure();
+    Ok(())
+}
+
 fn base_test<F>(tmp_dir: &TempDir, arg_modifier: F) -> Result<(), Box<dyn std::error::Error>>
 where
     F: FnOnce(&mut Command),
@@ -160,6 +173,210 @@ fn test_skip_cxx_gen() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_emit_fuzz_targets() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--emit-fuzz-targets").arg("demo");
+    })?;
+    assert_contains(&tmp_dir, "fuzz_targets/ffi_DoMath.rs", "fuzz_target");
+    assert_contains(&tmp_dir, "fuzz_targets/ffi_DoMath.rs", "u32");
+    Ok(())
+}
+
+#[test]
+fn test_line_directives() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--line-directives");
+    })?;
+    // The shim for Goat::describe, which returns a std::string, should
+    // point back at its declaration in demo/src/input.h.
+    let input_h = tmp_dir.path().join("demo").join("input.h");
+    assert_contains(
+        &tmp_dir,
+        "autocxxgen_ffi.h",
+        &format!("#line 21 \"{}\"", input_h.to_str().unwrap()),
+    );
+    // ... and the following generated code should point back at the
+    // generated header itself.
+    assert_not_contains(&tmp_dir, "autocxxgen_ffi.h", "autocxx: restore #line");
+    Ok(())
+}
+
+#[test]
+fn test_size_report() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--size-report");
+    })
+}
+
+#[test]
+fn test_cpp_shards() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--cpp-shards").arg("3");
+    })?;
+    // gen0.cc is generated by cxx, and gen1.cc to gen3.cc are the shards
+    // of the code generated by autocxx.
+    assert_contains(&tmp_dir, "gen3.cc", "#include \"autocxxgen_ffi.h\"");
+    Ok(())
+}
+
+#[test]
+fn test_warning_suppression() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |_| {})?;
+    assert_contains(&tmp_dir, "gen1.cc", "#pragma GCC diagnostic push");
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--no-warning-suppression");
+    })?;
+    let content = std::fs::read_to_string(tmp_dir.path().join("gen1.cc"))?;
+    assert!(!content.contains("#pragma"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clang_format() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let tmp_dir = TempDir::new("example")?;
+    // Stands in for clang-format, which may not be installed.
+    let formatter = tmp_dir.path().join("fake-clang-format");
+    std::fs::write(&formatter, "#!/bin/sh\necho '// formatted'\ncat\n")?;
+    std::fs::set_permissions(&formatter, std::fs::Permissions::from_mode(0o755))?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--clang-format").env("CLANG_FORMAT", &formatter);
+    })?;
+    assert_contains(&tmp_dir, "gen1.cc", "// formatted");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rustfmt() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let tmp_dir = TempDir::new("example")?;
+    // Stands in for rustfmt, so that we needn't depend on its exact output.
+    let formatter = tmp_dir.path().join("fake-rustfmt");
+    std::fs::write(&formatter, "#!/bin/sh\necho '// formatted'\ncat\n")?;
+    std::fs::set_permissions(&formatter, std::fs::Permissions::from_mode(0o755))?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--rustfmt")
+            .arg("--fix-rs-include-name")
+            .env("RUSTFMT", &formatter);
+    })?;
+    assert_contains(&tmp_dir, "gen0.include.rs", "// formatted");
+    Ok(())
+}
+
+#[test]
+fn test_gen_pretty_printers() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--gen-pretty-printers").arg("demo");
+    })?;
+    assert_contains(
+        &tmp_dir,
+        "autocxx_printers.py",
+        "demo::ffi::bindgen::root::Goat",
+    );
+    assert_contains(
+        &tmp_dir,
+        "autocxx_lldb.py",
+        "\"demo::ffi::bindgen::root::Goat\": \"Goat\"",
+    );
+    assert_contains(
+        &tmp_dir,
+        "autocxx.natvis",
+        "UniquePtr&lt;demo::ffi::bindgen::root::Goat&gt;",
+    );
+    Ok(())
+}
+
+#[test]
+fn test_symbols_file() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let symbols_path = tmp_dir.path().join("symbols.txt");
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--symbols-file").arg(&symbols_path);
+    })?;
+    assert_contains(&tmp_dir, "symbols.txt", "cxxbridge1$DoMath");
+    // A second run with the same input should report no changes.
+    std::fs::remove_dir_all(tmp_dir.path().join("demo"))?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--symbols-file")
+            .arg(&symbols_path)
+            .arg("--fail-on-symbol-changes");
+    })?;
+    // But if the recorded names differ, we should fail.
+    std::fs::write(&symbols_path, "SomethingElse\n")?;
+    std::fs::remove_dir_all(tmp_dir.path().join("demo"))?;
+    let result = std::panic::catch_unwind(|| {
+        base_test(&tmp_dir, |cmd| {
+            cmd.arg("--symbols-file")
+                .arg(&symbols_path)
+                .arg("--fail-on-symbol-changes");
+        })
+    });
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_api_summary() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let summary_path = tmp_dir.path().join("api.txt");
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--api-summary").arg(&summary_path);
+    })?;
+    assert_contains(&tmp_dir, "api.txt", "DoMath");
+    // Diffing against an identical summary should succeed...
+    std::fs::remove_dir_all(tmp_dir.path().join("demo"))?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--diff").arg(&summary_path);
+    })?;
+    // ... as should diffing against one lacking some items...
+    std::fs::write(&summary_path, "")?;
+    std::fs::remove_dir_all(tmp_dir.path().join("demo"))?;
+    base_test(&tmp_dir, |cmd| {
+        cmd.arg("--diff").arg(&summary_path);
+    })?;
+    // ... but not against one with items we no longer generate.
+    std::fs::write(&summary_path, "ffi: fn SomethingElse ()\n")?;
+    std::fs::remove_dir_all(tmp_dir.path().join("demo"))?;
+    let result = std::panic::catch_unwind(|| {
+        base_test(&tmp_dir, |cmd| {
+            cmd.arg("--diff").arg(&summary_path);
+        })
+    });
+    assert!(result.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_missing_header() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = TempDir::new("example")?;
+    let demo_code_dir = tmp_dir.path().join("demo");
+    std::fs::create_dir(&demo_code_dir)?;
+    write_to_file(&demo_code_dir, "main.rs", MAIN_RS.as_bytes());
+    let output = Command::cargo_bin("autocxx-gen")?
+        .arg("--inc")
+        .arg(tmp_dir.path())
+        .arg(demo_code_dir.join("main.rs"))
+        .arg("--outdir")
+        .arg(tmp_dir.path())
+        .arg("--gen-cpp")
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("HeaderNotFound(\"input.h\""));
+    assert!(stderr.contains(tmp_dir.path().to_str().unwrap()));
+    Ok(())
+}
+
 fn write_to_file(dir: &Path, filename: &str, content: &[u8]) {
     let path = dir.join(filename);
     let mut f = File::create(&path).expect("Unable to create file");
@@ -203,3 +420,9 @@ fn assert_contains(outdir: &TempDir, fname: &str, pattern: &str) {
     eprintln!("content = {}", content);
     assert!(content.contains(pattern));
 }
+
+fn assert_not_contains(outdir: &TempDir, fname: &str, pattern: &str) {
+    let p = outdir.path().join(fname);
+    let content = std::fs::read_to_string(&p).expect(fname);
+    assert!(!content.contains(pattern));
+}
@@ -0,0 +1,19 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Records the target for which the tests are being built, so that the
+// C++ and bindings they generate can be built for the same target. Run
+// e.g. `cargo test --target i686-unknown-linux-gnu` to test a 32-bit
+// target.
+fn main() {
+    println!(
+        "cargo:rustc-env=AUTOCXX_TEST_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -6,6 +6,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod snapshot;
+
 use std::{
     ffi::OsStr,
     fs::File,
@@ -15,7 +17,9 @@ use std::{
     sync::Mutex,
 };
 
-use autocxx_engine::{Builder, BuilderContext, BuilderError, RebuildDependencyRecorder, HEADER};
+use autocxx_engine::{
+    Builder, BuilderContext, BuilderError, CppOutputLayout, RebuildDependencyRecorder, HEADER,
+};
 use log::info;
 use once_cell::sync::OnceCell;
 use proc_macro2::{Span, TokenStream};
@@ -23,8 +27,16 @@ use quote::{quote, TokenStreamExt};
 use syn::Token;
 use tempfile::{tempdir, TempDir};
 
+pub use snapshot::{make_snapshot_checker, UPDATE_SNAPSHOTS_ENV_VAR};
+
 const KEEP_TEMPDIRS: bool = false;
 
+/// The target for which these tests were built. We generate bindings and
+/// build the C++ for the same target, so that testing with e.g.
+/// `cargo test --target i686-unknown-linux-gnu` checks that layouts are
+/// right for that target rather than the host.
+const TEST_TARGET: &str = env!("AUTOCXX_TEST_TARGET");
+
 /// API to run a documentation test. Panics if the test fails.
 /// Guarantees not to emit anything to stdout and so can be run in an mdbook context.
 pub fn doctest(
@@ -229,6 +241,134 @@ pub fn run_test_expect_fail_ex(
     .expect_err("Unexpected success");
 }
 
+/// Dependencies recorded during [`do_run_header_only_test`]. A
+/// [`BuilderContext`] can't carry any state of its own.
+static HEADER_ONLY_DEPENDENCIES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The [`BuilderContext`] used for header-only integration tests, which
+/// records the files upon which the build depends.
+struct HeaderOnlyTestBuilderContext;
+
+#[derive(Debug)]
+struct HeaderOnlyDependencyRecorder;
+
+impl RebuildDependencyRecorder for HeaderOnlyDependencyRecorder {
+    fn record_header_file_dependency(&self, filename: &str) {
+        HEADER_ONLY_DEPENDENCIES
+            .lock()
+            .unwrap()
+            .push(filename.to_string());
+    }
+}
+
+impl BuilderContext for HeaderOnlyTestBuilderContext {
+    fn get_dependency_recorder() -> Option<Box<dyn RebuildDependencyRecorder>> {
+        Some(Box::new(HeaderOnlyDependencyRecorder))
+    }
+}
+
+/// What a header-only build produced, besides a library which the test
+/// has already linked against.
+pub struct HeaderOnlyTestResults {
+    /// The files which cargo was told should cause a rebuild.
+    pub dependencies: Vec<String>,
+    /// The number of object files compiled from the generated C++.
+    pub object_count: usize,
+}
+
+/// A positive test of [`Builder::build_header_only`], with no C++ of the
+/// test's own. The code checker is given the generated .cxx files.
+pub fn do_run_header_only_test(
+    header_code: &str,
+    rust_code: TokenStream,
+    directives: TokenStream,
+    cpp_output_layout: CppOutputLayout,
+    code_checker: Option<CodeChecker>,
+) -> Result<HeaderOnlyTestResults, TestError> {
+    const HEADER_NAME: &str = "input.h";
+    let hexathorpe = Token![#](Span::call_site());
+    let rust_code = quote! {
+        use autocxx::prelude::*;
+
+        include_cpp!(
+            #hexathorpe include "input.h"
+            safety!(unsafe_ffi)
+            #directives
+        );
+
+        fn main() {
+            #rust_code
+        }
+
+        #[link(name="autocxx_header_only")]
+        extern {}
+    };
+    let tdir = tempdir().unwrap();
+    write_to_file(
+        &tdir,
+        HEADER_NAME,
+        &format!("#pragma once\n{}", header_code),
+    );
+    write_to_file(&tdir, "cxx.h", HEADER);
+    let rs_path = write_to_file(&tdir, "input.rs", &rust_code.to_string());
+    let target_dir = tdir.path().join("target");
+    std::fs::create_dir(&target_dir).unwrap();
+
+    // build_header_only has no cc::Build for us to configure, so it's
+    // configured as it would be within a build script.
+    std::env::set_var("OUT_DIR", &target_dir);
+    std::env::set_var("TARGET", TEST_TARGET);
+    std::env::set_var("HOST", rust_info::get().target_triple.unwrap());
+    std::env::set_var("OPT_LEVEL", "1");
+    HEADER_ONLY_DEPENDENCIES.lock().unwrap().clear();
+    Builder::<HeaderOnlyTestBuilderContext>::new(&rs_path, [tdir.path()])
+        .custom_gendir(target_dir.clone())
+        .clang_target(TEST_TARGET)
+        .cpp_output_layout(cpp_output_layout)
+        .build_header_only()
+        .map_err(TestError::AutoCxx)?;
+    let dependencies = std::mem::take(&mut *HEADER_ONLY_DEPENDENCIES.lock().unwrap());
+
+    let cxx_dir = target_dir.join("cxx");
+    let generated_cpp = files_with_extension(&cxx_dir, "cxx");
+    if let Some(code_checker) = &code_checker {
+        code_checker.check_cpp(&generated_cpp)?;
+    }
+    let object_count = files_with_extension(&target_dir, "o").len();
+
+    let rs_dir = target_dir.join("rs");
+    let generated_rs_files = files_with_extension(&rs_dir, "rs");
+    let r = get_builder().lock().unwrap().build(
+        &target_dir,
+        "autocxx_header_only",
+        &tdir.path(),
+        &[HEADER_NAME, "cxx.h"],
+        &rs_path,
+        generated_rs_files,
+    );
+    if r.is_err() {
+        return Err(TestError::RsBuild);
+    }
+    Ok(HeaderOnlyTestResults {
+        dependencies,
+        object_count,
+    })
+}
+
+/// Finds all the files beneath `dir` with the given extension.
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(files_with_extension(&path, extension));
+        } else if path.extension() == Some(OsStr::new(extension)) {
+            files.push(path);
+        }
+    }
+    files
+}
+
 /// In the future maybe the tests will distinguish the exact type of failure expected.
 #[derive(Debug)]
 pub enum TestError {
@@ -241,6 +381,9 @@ pub enum TestError {
     RsFileParse(syn::Error),
     RsCodeExaminationFail,
     CppCodeExaminationFail,
+    CppFileRead(std::io::Error),
+    SnapshotMismatch(PathBuf),
+    SnapshotWrite(std::io::Error),
 }
 
 pub fn directives_from_lists(
@@ -345,8 +488,9 @@ pub fn do_run_test_manual(
     let rs_path = write_rust_to_file(&rust_code);
 
     info!("Path is {:?}", tdir.path());
-    let builder = Builder::<TestBuilderContext>::new(&rs_path, &[tdir.path()])
-        .custom_gendir(target_dir.clone());
+    let builder = Builder::<TestBuilderContext>::new(&rs_path, [tdir.path()])
+        .custom_gendir(target_dir.clone())
+        .clang_target(TEST_TARGET);
     let builder = if let Some(builder_modifier) = &builder_modifier {
         builder_modifier.modify_autocxx_builder(builder)
     } else {
@@ -357,7 +501,7 @@ pub fn do_run_test_manual(
     let generated_rs_files = build_results.1;
 
     if let Some(code_checker) = &rust_code_checker {
-        let mut file = File::open(generated_rs_files.get(0).ok_or(TestError::NoRs)?)
+        let mut file = File::open(generated_rs_files.first().ok_or(TestError::NoRs)?)
             .map_err(TestError::RsFileOpen)?;
         let mut content = String::new();
         file.read_to_string(&mut content)
@@ -371,7 +515,7 @@ pub fn do_run_test_manual(
         }
     }
 
-    let target = rust_info::get().target_triple.unwrap();
+    let host = rust_info::get().target_triple.unwrap();
 
     if !cxx_code.is_empty() {
         // Step 4: Write the C++ code snippet to a .cc file, along with a #include
@@ -383,8 +527,8 @@ pub fn do_run_test_manual(
 
     let b = b
         .out_dir(&target_dir)
-        .host(&target)
-        .target(&target)
+        .host(&host)
+        .target(TEST_TARGET)
         .opt_level(1)
         .flag("-std=c++14") // For clang
         .flag_if_supported("/GX"); // Enable C++ exceptions for msvc
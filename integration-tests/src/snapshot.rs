@@ -0,0 +1,90 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+
+use crate::{CodeChecker, CodeCheckerFns, TestError};
+
+/// Set this environment variable to rewrite any snapshots which don't
+/// match, rather than failing the test.
+pub const UPDATE_SNAPSHOTS_ENV_VAR: &str = "AUTOCXX_UPDATE_SNAPSHOTS";
+
+/// Returns a code checker which compares the generated Rust and C++
+/// against snapshots stored as `<name>.rs.snap` and `<name>.cpp.snap`
+/// within `snapshot_dir`. If a snapshot doesn't yet exist, or if
+/// `AUTOCXX_UPDATE_SNAPSHOTS` is set, the snapshot is (re)written and the
+/// check passes. This allows binding crates to spot any change in the
+/// generated code when they upgrade autocxx.
+pub fn make_snapshot_checker(snapshot_dir: impl Into<PathBuf>, name: &str) -> CodeChecker {
+    Box::new(SnapshotChecker {
+        snapshot_dir: snapshot_dir.into(),
+        name: name.to_string(),
+    })
+}
+
+struct SnapshotChecker {
+    snapshot_dir: PathBuf,
+    name: String,
+}
+
+impl SnapshotChecker {
+    fn check_snapshot(&self, extension: &str, actual: &str) -> Result<(), TestError> {
+        let path = self
+            .snapshot_dir
+            .join(format!("{}.{}.snap", self.name, extension));
+        match std::fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => Ok(()),
+            Ok(_) if std::env::var_os(UPDATE_SNAPSHOTS_ENV_VAR).is_none() => {
+                eprintln!(
+                    "Generated code does not match snapshot {}. Set {} to update it.",
+                    path.display(),
+                    UPDATE_SNAPSHOTS_ENV_VAR
+                );
+                Err(TestError::SnapshotMismatch(path))
+            }
+            _ => write_snapshot(&path, actual),
+        }
+    }
+}
+
+fn write_snapshot(path: &Path, content: &str) -> Result<(), TestError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(TestError::SnapshotWrite)?;
+    }
+    std::fs::write(path, content).map_err(TestError::SnapshotWrite)
+}
+
+impl CodeCheckerFns for SnapshotChecker {
+    fn check_rust(&self, rs: syn::File) -> Result<(), TestError> {
+        let mut ts = TokenStream::new();
+        rs.to_tokens(&mut ts);
+        self.check_snapshot("rs", &ts.to_string())
+    }
+
+    fn check_cpp(&self, cpp: &[PathBuf]) -> Result<(), TestError> {
+        // Use only the file name, since the directory is a temporary one.
+        let content = cpp
+            .iter()
+            .sorted_by_key(|p| p.file_name().map(|f| f.to_os_string()))
+            .map(|p| {
+                let name = p
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let body = std::fs::read_to_string(p).map_err(TestError::CppFileRead)?;
+                Ok(format!("// ---- {} ----\n{}", name, body))
+            })
+            .collect::<Result<Vec<_>, TestError>>()?
+            .join("\n");
+        self.check_snapshot("cpp", &content)
+    }
+}
@@ -6,7 +6,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use autocxx_engine::Builder;
+use autocxx_engine::{Builder, ShimVisibility, TypeMapper, TypeMapping};
+use syn::parse_quote;
 
 use autocxx_integration_tests::{BuilderModifier, BuilderModifierFns, TestBuilderContext};
 
@@ -66,3 +67,95 @@ impl BuilderModifierFns for SkipCxxGen {
         builder.skip_cxx_gen(true)
     }
 }
+
+pub(crate) struct UsePrecompiledHeader;
+
+impl BuilderModifierFns for UsePrecompiledHeader {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder
+            .extra_clang_args(&["-std=c++14"])
+            .use_precompiled_header("input.h")
+    }
+}
+
+pub(crate) struct EnableEarlyTypecheck;
+
+impl BuilderModifierFns for EnableEarlyTypecheck {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder.early_typecheck(true)
+    }
+}
+
+pub(crate) struct HideShims;
+
+impl BuilderModifierFns for HideShims {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder.shim_visibility(ShimVisibility::Hidden)
+    }
+}
+
+/// Generates a C header for Rust functions, which requires autodiscovery
+/// of those functions.
+pub(crate) struct EnableCHeader;
+
+impl BuilderModifierFns for EnableCHeader {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder.auto_allowlist(true).c_header(true)
+    }
+}
+
+/// Maps `fixed::Q16` to `crate::Q16` using a [`TypeMapper`], with a shim
+/// on each side.
+pub(crate) struct MapQ16;
+
+struct Q16Mapper;
+
+impl TypeMapper for Q16Mapper {
+    fn map_type(&self, cpp_name: &str) -> Option<TypeMapping> {
+        (cpp_name == "fixed::Q16").then(|| TypeMapping {
+            rust_type: parse_quote! { crate::Q16 },
+            cpp_shims: Some(
+                "static_assert(sizeof(fixed::Q16) == 4, \"Q16 should be 32 bits\");".into(),
+            ),
+            rs_shims: vec![parse_quote! {
+                impl crate::Q16 {
+                    pub fn to_int(self) -> i32 {
+                        self.raw >> 16
+                    }
+                }
+            }],
+        })
+    }
+}
+
+impl BuilderModifierFns for MapQ16 {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder.type_mapper(Q16Mapper)
+    }
+}
+
+pub(crate) struct UseRsModuleTree;
+
+impl BuilderModifierFns for UseRsModuleTree {
+    fn modify_autocxx_builder<'a>(
+        &self,
+        builder: Builder<'a, TestBuilderContext>,
+    ) -> Builder<'a, TestBuilderContext> {
+        builder.rs_module_tree(true)
+    }
+}
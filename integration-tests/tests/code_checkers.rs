@@ -130,7 +130,7 @@ impl<'a> CodeCheckerFns for CppMatcher<'a> {
         for filename in cpp {
             let file = File::open(filename).unwrap();
             let lines = BufReader::new(file).lines();
-            for l in lines.filter_map(|l| l.ok()) {
+            for l in lines.map_while(Result::ok) {
                 if self.negative_matches.iter().any(|neg| l.contains(neg)) {
                     return Err(TestError::CppCodeExaminationFail);
                 }
@@ -145,6 +145,43 @@ impl<'a> CodeCheckerFns for CppMatcher<'a> {
     }
 }
 
+/// Ensures that each given string appears in exactly the given number of
+/// lines of generated C++.
+pub(crate) struct CppLineCounter<'a> {
+    expected_counts: &'a [(&'a str, usize)],
+}
+
+impl<'a> CppLineCounter<'a> {
+    pub(crate) fn new(expected_counts: &'a [(&'a str, usize)]) -> Self {
+        Self { expected_counts }
+    }
+}
+
+impl<'a> CodeCheckerFns for CppLineCounter<'a> {
+    fn check_cpp(&self, cpp: &[PathBuf]) -> Result<(), TestError> {
+        let mut counts = vec![0; self.expected_counts.len()];
+        for filename in cpp {
+            let file = File::open(filename).unwrap();
+            for l in BufReader::new(file).lines().map_while(Result::ok) {
+                for (count, (needle, _)) in counts.iter_mut().zip(self.expected_counts) {
+                    if l.contains(needle) {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+        if counts
+            .iter()
+            .zip(self.expected_counts)
+            .all(|(count, (_, expected))| count == expected)
+        {
+            Ok(())
+        } else {
+            Err(TestError::CppCodeExaminationFail)
+        }
+    }
+}
+
 pub(crate) struct NoSystemHeadersChecker;
 
 impl CodeCheckerFns for NoSystemHeadersChecker {
@@ -8,15 +8,19 @@
 
 use crate::{
     builder_modifiers::{
-        make_clang_arg_adder, EnableAutodiscover, SetSuppressSystemHeaders, SkipCxxGen,
+        make_clang_arg_adder, EnableAutodiscover, EnableCHeader, EnableEarlyTypecheck, HideShims,
+        MapQ16, SetSuppressSystemHeaders, SkipCxxGen, UsePrecompiledHeader, UseRsModuleTree,
     },
     code_checkers::{
-        make_error_finder, make_string_finder, CppCounter, CppMatcher, NoSystemHeadersChecker,
+        make_error_finder, make_string_finder, CppCounter, CppLineCounter, CppMatcher,
+        NoSystemHeadersChecker,
     },
 };
+use autocxx_engine::CppOutputLayout;
 use autocxx_integration_tests::{
-    directives_from_lists, do_run_test, do_run_test_manual, run_test, run_test_ex,
-    run_test_expect_fail, run_test_expect_fail_ex, TestError,
+    directives_from_lists, do_run_header_only_test, do_run_test, do_run_test_manual,
+    make_snapshot_checker, run_test, run_test_ex, run_test_expect_fail, run_test_expect_fail_ex,
+    TestError,
 };
 use indoc::indoc;
 use itertools::Itertools;
@@ -1083,6 +1087,31 @@ fn test_enum_no_funcs() {
     run_test(cxx, hdr, rs, &["Bob"], &[]);
 }
 
+#[test]
+fn test_enum_with_duplicate_values() {
+    let cxx = indoc! {"
+        Bob give_bob() {
+            return Bob::BOB_VALUE_3;
+        }
+    "};
+    let hdr = indoc! {"
+        #include <cstdint>
+        enum Bob {
+            BOB_VALUE_1 = 1,
+            BOB_VALUE_2 = 2,
+            BOB_VALUE_3 = 1,
+        };
+        Bob give_bob();
+    "};
+    let rs = quote! {
+        let a = ffi::Bob::BOB_VALUE_1;
+        let b = ffi::give_bob();
+        assert!(a == b);
+        assert_eq!(ffi::Bob::BOB_VALUE_2.0, 2);
+    };
+    run_test(cxx, hdr, rs, &["Bob", "give_bob"], &[]);
+}
+
 #[test]
 fn test_enum_with_funcs_as_pod() {
     let cxx = indoc! {"
@@ -1175,6 +1204,342 @@ fn test_constructors_for_specialized_types() {
     run_test("", hdr, rs, &["C"], &[]);
 }
 
+#[test]
+fn test_pod_with_function_pointers() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        typedef uint32_t (*binary_op)(uint32_t, uint32_t);
+        struct Plugin {
+            uint32_t (*unary)(uint32_t);
+            binary_op binary;
+        };
+        inline uint32_t call_plugin(const Plugin& p) {
+            return p.unary(p.binary(2, 3));
+        }
+    "};
+    let rs = quote! {
+        unsafe extern "C" fn double(a: u32) -> u32 {
+            a * 2
+        }
+        unsafe extern "C" fn add(a: u32, b: u32) -> u32 {
+            a + b
+        }
+        let p = ffi::Plugin {
+            unary: Some(double),
+            binary: Some(add),
+        };
+        assert_eq!(ffi::call_plugin(&p), 10);
+    };
+    run_test("", hdr, rs, &["call_plugin"], &["Plugin"]);
+}
+
+#[test]
+fn test_pod_with_array_of_structs() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Point {
+            uint32_t x;
+            uint32_t y;
+        };
+        struct Polygon {
+            Point points[3];
+            uint32_t weights[2][3];
+        };
+        inline uint32_t sum_x(const Polygon& p) {
+            return p.points[0].x + p.points[1].x + p.points[2].x + p.weights[1][2];
+        }
+    "};
+    let rs = quote! {
+        let p = ffi::Polygon {
+            points: [
+                ffi::Point { x: 1, y: 2 },
+                ffi::Point { x: 1, y: 2 },
+                ffi::Point { x: 1, y: 2 },
+            ],
+            weights: [[0, 0, 0], [0, 0, 4]],
+        };
+        assert_eq!(ffi::sum_x(&p), 7);
+        assert_eq!(p.points[2].y, 2);
+    };
+    run_test("", hdr, rs, &["sum_x"], &["Polygon"]);
+}
+
+#[test]
+fn test_mirror_type() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Point {
+            uint32_t x;
+            uint32_t y;
+        };
+        inline Point make_point() { return Point { 3, 4 }; }
+    "};
+    let rs = quote! {
+        let p: crate::Point = ffi::make_point().into();
+        assert_eq!(p, crate::Point { x: 3, y: 4 });
+        assert!(ffi::make_point() == p);
+        assert!(p == ffi::make_point());
+        let q: ffi::Point = crate::Point { x: 5, y: 6 }.into();
+        assert_eq!(q.y, 6);
+        assert!(q != p);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["make_point"],
+            &["Point"],
+            Some(quote! {
+                mirror_type!("Point", crate::Point)
+            }),
+        ),
+        None,
+        None,
+        Some(quote! {
+            #[derive(Debug, PartialEq)]
+            pub struct Point {
+                pub x: u32,
+                pub y: u32,
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_refcounted() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t& document_count() {
+            static uint32_t count = 0;
+            return count;
+        }
+        class RefCountedBase {
+        public:
+            void Ref() const { count++; }
+            void Unref() const {
+                if (--count == 0) {
+                    delete this;
+                }
+            }
+        protected:
+            RefCountedBase() : count(1) {}
+            virtual ~RefCountedBase() {}
+        private:
+            mutable uint32_t count;
+        };
+        class Document : public RefCountedBase {
+        public:
+            Document() { document_count()++; }
+            ~Document() { document_count()--; }
+            uint32_t pages() const { return 3; }
+        };
+        inline Document* CreateDocument() { return new Document(); }
+        inline uint32_t LiveDocuments() { return document_count(); }
+    "};
+    let rs = quote! {
+        let doc = unsafe { autocxx::Handle::adopt(ffi::CreateDocument()) }.unwrap();
+        assert_eq!(doc.pages(), 3);
+        let another = doc.clone();
+        drop(doc);
+        assert_eq!(another.pages(), 3);
+        assert_eq!(ffi::LiveDocuments(), 1);
+        drop(another);
+        assert_eq!(ffi::LiveDocuments(), 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Document", "CreateDocument", "LiveDocuments"],
+            &[],
+            Some(quote! {
+                refcounted!("RefCountedBase", addref = "Ref", release = "Unref")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_impl_trait() {
+    let hdr = indoc! {"
+        #include <algorithm>
+        #include <cstdint>
+        #include <cstddef>
+        #include <cstring>
+        #include <string>
+        class Buffer {
+        public:
+            Buffer() : flushes(0) {}
+            size_t write_bytes(const uint8_t* data, size_t len) {
+                contents.append(reinterpret_cast<const char*>(data), len);
+                return len;
+            }
+            void flush() { flushes++; }
+            size_t read_bytes(uint8_t* data, size_t len) {
+                size_t n = std::min(len, contents.size());
+                memcpy(data, contents.data(), n);
+                contents.erase(0, n);
+                return n;
+            }
+            std::string describe() const { return contents; }
+            uint32_t get_flushes() const { return flushes; }
+        private:
+            std::string contents;
+            uint32_t flushes;
+        };
+    "};
+    let rs = quote! {
+        use std::io::{Read, Write};
+        let mut buffer = ffi::Buffer::make_unique();
+        buffer.pin_mut().write_all(b"hello").unwrap();
+        Write::flush(&mut buffer.pin_mut()).unwrap();
+        assert_eq!(buffer.get_flushes(), 1);
+        assert_eq!(format!("{}", buffer.as_ref().unwrap()), "hello");
+        let mut out = [0u8; 3];
+        buffer.pin_mut().read_exact(&mut out).unwrap();
+        assert_eq!(&out, b"hel");
+        assert_eq!(format!("{}", buffer.as_ref().unwrap()), "lo");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Buffer"],
+            &[],
+            Some(quote! {
+                impl_trait!("Buffer", "std::io::Write", write = "Buffer::write_bytes", flush = "Buffer::flush")
+                impl_trait!("Buffer", "std::io::Read", read = "Buffer::read_bytes")
+                impl_trait!("Buffer", "std::fmt::Display", fmt = "Buffer::describe")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_impl_trait_into_iterator() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <cstddef>
+        #include <vector>
+        class IntList {
+        public:
+            IntList() : values({1, 2, 3}) {}
+            size_t size() const { return values.size(); }
+            const uint32_t& at(size_t index) const { return values.at(index); }
+            const std::vector<uint32_t>& as_vector() const { return values; }
+        private:
+            std::vector<uint32_t> values;
+        };
+    "};
+    let rs = quote! {
+        let list = ffi::IntList::make_unique();
+        let mut total = 0;
+        for value in list.as_ref().unwrap() {
+            total += *value;
+        }
+        assert_eq!(total, 6);
+        let reversed: Vec<u32> = list.as_ref().unwrap().into_iter().rev().copied().collect();
+        assert_eq!(reversed, [3, 2, 1]);
+        let mut total = 0;
+        for value in list.as_vector() {
+            total += *value;
+        }
+        assert_eq!(total, 6);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["IntList"],
+            &[],
+            Some(quote! {
+                impl_trait!("IntList", "IntoIterator", len = "IntList::size", get = "IntList::at")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_impl_trait_incompatible() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Sink {
+        public:
+            uint32_t write_int(uint32_t value) { return value; }
+            void flush() {}
+        };
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        directives_from_lists(
+            &["Sink"],
+            &[],
+            Some(quote! {
+                impl_trait!("Sink", "std::io::Write", write = "Sink::write_int", flush = "Sink::flush")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_pod_field_metadata() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <cstddef>
+        struct Record {
+            uint8_t tag;
+            uint32_t value;
+            uint16_t extras[3];
+        };
+        inline uint32_t value_offset() { return offsetof(Record, value); }
+        inline uint32_t extras_offset() { return offsetof(Record, extras); }
+    "};
+    let rs = quote! {
+        use autocxx::FieldMetadataProvider;
+        let metadata = ffi::Record::field_metadata();
+        let names: Vec<_> = metadata.iter().map(|f| f.name).collect();
+        assert_eq!(names, ["tag", "value", "extras"]);
+        assert_eq!(metadata[0].offset, 0);
+        assert_eq!(metadata[1].offset, ffi::value_offset() as usize);
+        assert_eq!(metadata[2].offset, ffi::extras_offset() as usize);
+        assert_eq!(metadata[1].size, 4);
+        assert_eq!(metadata[2].size, 6);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["value_offset", "extras_offset"],
+            &["Record"],
+            Some(quote! {
+                field_metadata!("Record")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
 #[test]
 fn test_pod_mut_method() {
     let cxx = indoc! {"
@@ -1211,6 +1576,21 @@ fn test_define_int() {
     run_test(cxx, hdr, rs, &["BOB"], &[]);
 }
 
+#[test]
+fn test_anonymous_enum() {
+    let hdr = indoc! {"
+        enum { FLAG_A = 1, FLAG_B = 2 };
+        namespace a {
+            enum { FLAG_C = 4 };
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::FLAG_A | ffi::FLAG_B, 3);
+        assert_eq!(ffi::a::FLAG_C, 4);
+    };
+    run_test("", hdr, rs, &["FLAG_A", "FLAG_B", "a::FLAG_C"], &[]);
+}
+
 #[test]
 fn test_define_str() {
     let cxx = indoc! {"
@@ -2762,53 +3142,1867 @@ fn test_conflicting_ns_structs() {
 fn test_make_string() {
     let hdr = indoc! {"
         #include <cstdint>
+        #include <string>
         struct Bob {
             uint32_t a;
         };
+        inline uint32_t take_str(std::string a) { return a.size(); }
     "};
     let rs = quote! {
         use ffi::ToCppString;
         let a = "hello".into_cpp();
         assert_eq!(a.to_str().unwrap(), "hello");
+        assert_eq!(ffi::take_str("hi"), 2);
     };
-    run_test("", hdr, rs, &["Bob"], &[]);
+    run_test("", hdr, rs, &["Bob", "take_str"], &[]);
 }
 
 #[test]
-fn test_string_make_unique() {
+fn test_no_make_string_unless_needed() {
     let hdr = indoc! {"
-        #include <string>
-        inline void take_string(const std::string*) {};
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
     "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["Bob"], &[], None),
+        None,
+        Some(Box::new(CppMatcher::new(&[], &["make_string"]))),
+        None,
+    );
+}
+
+fn run_long_name_test(mangling_version: Option<u32>, cpp_matcher: CppMatcher<'static>) {
+    let name = format!("get_{}", "x".repeat(200));
+    let hdr = format!(
+        indoc! {"
+        #include <cstdint>
+        inline uint32_t {}() {{ return 42; }}
+    "},
+        name
+    );
+    let id = proc_macro2::Ident::new(&name, Span::call_site());
     let rs = quote! {
-        let s = ffi::make_string("");
-        unsafe { ffi::take_string(s.as_ref().unwrap()) };
+        assert_eq!(ffi::#id(), 42);
     };
-    run_test("", hdr, rs, &["take_string"], &[]);
+    let mangling_version = mangling_version.map(|version| {
+        let version = proc_macro2::Literal::u32_unsuffixed(version);
+        quote! { mangling_version!(#version) }
+    });
+    run_test_ex(
+        "",
+        &hdr,
+        rs,
+        quote! {
+            generate!(#name)
+            #mangling_version
+        },
+        None,
+        Some(Box::new(cpp_matcher)),
+        None,
+    );
 }
 
 #[test]
-fn test_string_constant() {
+fn test_long_name_shortened() {
+    run_long_name_test(None, CppMatcher::new(&["_autocxx_hash"], &[]));
+}
+
+#[test]
+fn test_long_name_unshortened_in_mangling_version_1() {
+    run_long_name_test(Some(1), CppMatcher::new(&[], &["_autocxx_hash"]));
+}
+
+#[test]
+fn test_prelude() {
     let hdr = indoc! {"
         #include <cstdint>
-        const char* STRING = \"Foo\";
+        #ifndef AUTOCXX_TEST_PRELUDE
+        #error Prelude not applied
+        #endif
+        inline uint32_t give_int() { return AUTOCXX_TEST_PRELUDE; }
     "};
     let rs = quote! {
-        let a = std::str::from_utf8(ffi::STRING).unwrap().trim_end_matches(char::from(0));
-        assert_eq!(a, "Foo");
+        assert_eq!(ffi::give_int(), 5);
     };
-    run_test("", hdr, rs, &["STRING"], &[]);
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["give_int"],
+            &[],
+            Some(quote! {
+                prelude!("#define AUTOCXX_TEST_PRELUDE 5")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
-fn test_pod_constant_harmless_inside_type() {
-    // Check that the presence of this constant doesn't break anything.
+fn test_init_and_shutdown_functions() {
     let hdr = indoc! {"
         #include <cstdint>
-        struct Bob {
-            uint32_t a;
-        };
-        struct Anna {
+        extern uint32_t state;
+        inline void init_a() { state = state * 10 + 1; }
+        inline void init_b() { state = state * 10 + 2; }
+        inline void shutdown_a() { state = state * 10 + 3; }
+        inline void shutdown_b() { state = state * 10 + 4; }
+        inline uint32_t get_state() { return state; }
+    "};
+    let cpp = indoc! {"
+        uint32_t state = 0;
+    "};
+    let rs = quote! {
+        ffi::initialize();
+        ffi::initialize();
+        assert_eq!(ffi::get_state(), 12);
+        ffi::shutdown();
+        assert_eq!(ffi::get_state(), 1243);
+    };
+    run_test_ex(
+        cpp,
+        hdr,
+        rs,
+        directives_from_lists(
+            &["get_state"],
+            &[],
+            Some(quote! {
+                init_function!("init_a")
+                init_function!("init_b")
+                shutdown_function!("shutdown_a")
+                shutdown_function!("shutdown_b")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_raii_guard() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        extern uint32_t depth;
+        class ScopeCounter {
+        public:
+            ScopeCounter(uint32_t amount) : amount(amount) { depth += amount; }
+            ScopeCounter(const ScopeCounter&) = delete;
+            ScopeCounter(ScopeCounter&&) = delete;
+            ~ScopeCounter() { depth -= amount; }
+            uint32_t get_amount() const { return amount; }
+        private:
+            uint32_t amount;
+        };
+        inline uint32_t get_depth() { return depth; }
+    "};
+    let cpp = indoc! {"
+        uint32_t depth = 0;
+    "};
+    let rs = quote! {
+        {
+            let outer = ffi::ScopeCounterGuard::new(ffi::ScopeCounter::new(2));
+            assert_eq!(ffi::get_depth(), 2);
+            let inner = ffi::ScopeCounterGuard::new(ffi::ScopeCounter::new(3));
+            assert_eq!(ffi::get_depth(), 5);
+            assert_eq!(inner.get_amount(), 3);
+            drop(inner);
+            assert_eq!(ffi::get_depth(), 2);
+            assert_eq!(outer.get_amount(), 2);
+        }
+        assert_eq!(ffi::get_depth(), 0);
+    };
+    run_test_ex(
+        cpp,
+        hdr,
+        rs,
+        directives_from_lists(
+            &["get_depth"],
+            &[],
+            Some(quote! {
+                raii!("ScopeCounter")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_batch() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        extern uint32_t total;
+        class Canvas {
+        public:
+            Canvas() : count(0), sum(0.0f) {}
+            void draw_point(float x, float y) { count++; sum += x * y; }
+            uint32_t get_count() const { return count; }
+            float get_sum() const { return sum; }
+        private:
+            uint32_t count;
+            float sum;
+        };
+        inline void add(uint32_t a, bool double_it) { total += double_it ? a * 2 : a; }
+        inline uint32_t get_total() { return total; }
+    "};
+    let cpp = indoc! {"
+        uint32_t total = 0;
+    "};
+    let rs = quote! {
+        let mut canvas = ffi::Canvas::make_unique();
+        let points = [
+            ffi::Canvas_draw_point_args { x: 1.0, y: 2.0 },
+            ffi::Canvas_draw_point_args { x: 3.0, y: 4.0 },
+        ];
+        canvas.pin_mut().draw_point_batch(&points);
+        assert_eq!(canvas.get_count(), 2);
+        assert_eq!(canvas.get_sum(), 14.0);
+        canvas.pin_mut().draw_point(1.0, 1.0);
+        assert_eq!(canvas.get_count(), 3);
+        ffi::add_batch(&[
+            ffi::add_args { a: 1, double_it: false },
+            ffi::add_args { a: 2, double_it: true },
+        ]);
+        assert_eq!(ffi::get_total(), 5);
+    };
+    run_test_ex(
+        cpp,
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Canvas", "add", "get_total"],
+            &[],
+            Some(quote! {
+                batch!("Canvas::draw_point")
+                batch!("add")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_treat_as_const() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Widget {
+        public:
+            Widget() : id(3) {}
+            uint32_t get_id() { return id; }
+            void set_id(uint32_t new_id) { id = new_id; }
+        private:
+            uint32_t id;
+        };
+    "};
+    let rs = quote! {
+        let mut widget = ffi::Widget::make_unique();
+        let widget_ref: &ffi::Widget = widget.as_ref().unwrap();
+        assert_eq!(widget_ref.get_id(), 3);
+        widget.pin_mut().set_id(4);
+        assert_eq!(widget.get_id(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Widget"],
+            &[],
+            Some(quote! {
+                treat_as_const!("Widget::get_id")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_shared_ref_variant() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Shape {
+        public:
+            Shape() : sides(4) {}
+            uint32_t sides;
+        };
+        class Canvas {
+        public:
+            Canvas() : drawn(0) {}
+            void draw(Shape& shape) { drawn += shape.sides; }
+            uint32_t drawn;
+        };
+        inline uint32_t count_sides(Shape& shape) { return shape.sides; }
+    "};
+    let rs = quote! {
+        let shape = ffi::Shape::make_unique();
+        let shape_ref: &ffi::Shape = shape.as_ref().unwrap();
+        assert_eq!(ffi::count_sides_shared(shape_ref), 4);
+        let mut canvas = ffi::Canvas::make_unique();
+        canvas.pin_mut().draw_shared(shape_ref);
+        assert_eq!(canvas.drawn, 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["count_sides"],
+            &["Canvas", "Shape"],
+            Some(quote! {
+                shared_ref_variant!("Canvas::draw")
+                shared_ref_variant!("count_sides")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_returns_owned() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t& widget_count() {
+            static uint32_t count = 0;
+            return count;
+        }
+        class Widget {
+        public:
+            Widget() { widget_count()++; }
+            ~Widget() { widget_count()--; }
+            uint32_t get_id() const { return 3; }
+        };
+        class Factory {
+        public:
+            Widget* make() const { return new Widget(); }
+        };
+        inline Widget* CreateWidget() { return new Widget(); }
+        inline uint32_t LiveWidgets() { return widget_count(); }
+    "};
+    let rs = quote! {
+        let factory = ffi::Factory::make_unique();
+        let made: cxx::UniquePtr<ffi::Widget> = factory.make();
+        let created: cxx::UniquePtr<ffi::Widget> = ffi::CreateWidget();
+        assert_eq!(made.get_id(), 3);
+        assert_eq!(ffi::LiveWidgets(), 2);
+        drop(made);
+        drop(created);
+        assert_eq!(ffi::LiveWidgets(), 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Factory", "Widget", "CreateWidget", "LiveWidgets"],
+            &[],
+            Some(quote! {
+                returns_owned!("Factory::make")
+                returns_owned!("CreateWidget")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_returns_owned_non_pointer() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t CountWidgets() { return 3; }
+    "};
+    let rs = quote! {
+        ffi::CountWidgets();
+    };
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["CountWidgets"],
+            &[],
+            Some(quote! {
+                returns_owned!("CountWidgets")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_consumes() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        #include <vector>
+        inline uint32_t& widget_count() {
+            static uint32_t count = 0;
+            return count;
+        }
+        class Widget {
+        public:
+            Widget() { widget_count()++; }
+            ~Widget() { widget_count()--; }
+        };
+        class Sink {
+        public:
+            void adopt(Widget* widget) { widgets.emplace_back(widget); }
+            void clear() { widgets.clear(); }
+        private:
+            std::vector<std::unique_ptr<Widget>> widgets;
+        };
+        inline uint32_t LiveWidgets() { return widget_count(); }
+    "};
+    let rs = quote! {
+        let mut sink = ffi::Sink::make_unique();
+        sink.pin_mut().adopt(ffi::Widget::make_unique());
+        sink.pin_mut().adopt(ffi::Widget::make_unique());
+        assert_eq!(ffi::LiveWidgets(), 2);
+        sink.pin_mut().clear();
+        assert_eq!(ffi::LiveWidgets(), 0);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Sink", "Widget", "LiveWidgets"],
+            &[],
+            Some(quote! {
+                consumes!("Sink::adopt", arg = 1)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_thin_call() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace geo {
+        class Point {
+        public:
+            Point() : x(3), y(4.5f), visible(true) {}
+            int32_t get_x() const { return x; }
+            float get_y() const { return y; }
+            bool is_visible() const { return visible; }
+            void hide() { visible = false; }
+        private:
+            int32_t x;
+            float y;
+            bool visible;
+        };
+        }
+    "};
+    let rs = quote! {
+        let mut point = ffi::geo::Point::make_unique();
+        assert_eq!(point.get_x(), 3);
+        assert_eq!(point.get_y(), 4.5);
+        assert!(point.is_visible());
+        point.pin_mut().hide();
+        assert!(!point.is_visible());
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["geo::Point"],
+            &[],
+            Some(quote! {
+                thin_call!("geo::Point::get_x")
+                thin_call!("geo::Point::get_y")
+                thin_call!("geo::Point::is_visible")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_thin_call_propagating_exceptions() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Point {
+        public:
+            Point() : x(3) {}
+            int32_t get_x() const { return x; }
+        private:
+            int32_t x;
+        };
+    "};
+    let rs = quote! {
+        let point = ffi::Point::make_unique();
+        assert_eq!(point.get_x(), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Point"],
+            &[],
+            Some(quote! {
+                thin_call!("Point::get_x")
+                exception_policy!(propagate)
+            }),
+        ),
+        None,
+        Some(Box::new(CppMatcher::new(
+            &["_autocxx_thin(const void* self) {"],
+            &["_autocxx_thin(const void* self) noexcept"],
+        ))),
+        None,
+    );
+}
+
+#[test]
+fn test_generate_from_header() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        struct Goat {
+            uint32_t horns;
+            uint32_t get_horns() const { return horns; }
+        };
+        inline Goat make_goat() { Goat g; g.horns = 2; return g; }
+        inline uint32_t bleat(const std::string& s) { return s.size(); }
+    "};
+    let rs = quote! {
+        let goat = ffi::make_goat();
+        assert_eq!(goat.get_horns(), 2);
+        assert_eq!(ffi::bleat(&ffi::make_string("baa")), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_from_header!("input.h")
+            generate_pod!("Goat")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_inline_namespace_in_directives() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace lib {
+            inline namespace v2 {
+                struct Widget {
+                    uint32_t size;
+                };
+                inline uint32_t measure(const Widget& w) { return w.size; }
+            }
+        }
+    "};
+    let rs = quote! {
+        let w = ffi::lib::Widget { size: 4 };
+        assert_eq!(ffi::lib::measure(&w), 4);
+    };
+    run_test("", hdr, rs, &["lib::v2::measure"], &["lib::v2::Widget"]);
+}
+
+#[test]
+fn test_using_declaration_of_function() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace detail {
+            inline uint32_t frobnicate() { return 3; }
+            inline uint32_t frobnicate(uint32_t a) { return a + 1; }
+        }
+        namespace api {
+            using detail::frobnicate;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::api::frobnicate(), 3);
+        assert_eq!(ffi::api::frobnicate1(4), 5);
+    };
+    run_test("", hdr, rs, &["api::frobnicate"], &[]);
+}
+
+#[test]
+fn test_using_declaration_shim_deduplicated() {
+    let hdr = indoc! {"
+        #include <string>
+        namespace detail {
+            inline std::string greeting() { return \"hello\"; }
+        }
+        namespace api {
+            using detail::greeting;
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::detail::greeting().as_ref().unwrap().to_str().unwrap(), "hello");
+        assert_eq!(ffi::api::greeting().as_ref().unwrap().to_str().unwrap(), "hello");
+    };
+    // Both shims would call detail::greeting, so we should define only one
+    // of them, and refer to it under the other name.
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["detail::greeting", "api::greeting"], &[], None),
+        None,
+        Some(Box::new(CppLineCounter::new(&[
+            ("constexpr auto& ", 1),
+            ("detail::greeting()", 1),
+        ]))),
+        None,
+    );
+}
+
+#[test]
+fn test_build_header_only() {
+    let hdr = indoc! {"
+        #include <string>
+        inline std::string red() { return \"red\"; }
+        inline std::string green() { return \"green\"; }
+        inline std::string blue() { return \"blue\"; }
+        inline std::string cyan() { return \"cyan\"; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::red().as_ref().unwrap().to_str().unwrap(), "red");
+        assert_eq!(ffi::green().as_ref().unwrap().to_str().unwrap(), "green");
+        assert_eq!(ffi::blue().as_ref().unwrap().to_str().unwrap(), "blue");
+        assert_eq!(ffi::cyan().as_ref().unwrap().to_str().unwrap(), "cyan");
+    };
+    // All the shards should be included into a single translation unit.
+    let results = do_run_header_only_test(
+        hdr,
+        rs,
+        directives_from_lists(&["red", "green", "blue", "cyan"], &[], None),
+        CppOutputLayout::Sharded(3),
+        Some(Box::new(CppMatcher::new(&["_1.cxx\"", "_2.cxx\""], &[]))),
+    )
+    .unwrap();
+    assert_eq!(results.object_count, 1);
+    for dependency in ["input.rs", "input.h"] {
+        assert!(
+            results
+                .dependencies
+                .iter()
+                .any(|recorded| recorded.ends_with(dependency)),
+            "{} should cause a rebuild",
+            dependency
+        );
+    }
+}
+
+#[test]
+fn test_deprecation_policy_skip() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        [[deprecated(\"use new_fn\")]] inline uint32_t old_fn() { return 3; }
+        inline uint32_t new_fn() { return 4; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::new_fn(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["old_fn", "new_fn"],
+            &[],
+            Some(quote! {
+                deprecation_policy!(skip)
+            }),
+        ),
+        None,
+        Some(make_error_finder("old_fn")),
+        None,
+    );
+}
+
+#[test]
+fn test_deprecation_policy_warn() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        [[deprecated(\"use new_fn\")]] inline uint32_t old_fn() { return 3; }
+        inline uint32_t new_fn() { return 4; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::new_fn(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["old_fn", "new_fn"],
+            &[],
+            Some(quote! {
+                deprecation_policy!(warn)
+            }),
+        ),
+        None,
+        Some(make_string_finder(vec![
+            "deprecated (note = \"use new_fn\")",
+        ])),
+        None,
+    );
+}
+
+#[test]
+fn test_associate_fn() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        namespace ui {
+        class Widget {
+        public:
+            Widget(uint32_t id) : id(id) {}
+            uint32_t get_id() const { return id; }
+        private:
+            uint32_t id;
+        };
+        }
+        inline std::unique_ptr<ui::Widget> CreateWidget(uint32_t id) {
+            return std::make_unique<ui::Widget>(id);
+        }
+    "};
+    let rs = quote! {
+        let widget = ffi::ui::Widget::create(3);
+        assert_eq!(widget.get_id(), 3);
+        assert_eq!(ffi::CreateWidget(4).get_id(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["ui::Widget", "CreateWidget"],
+            &[],
+            Some(quote! {
+                associate_fn!("CreateWidget", "ui::Widget::create")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_anonymous_namespace_function() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace {
+        inline uint32_t give_int() { return 5; }
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::give_int(), 5);
+    };
+    run_test_expect_fail("", hdr, rs, &["give_int"], &[]);
+}
+
+#[test]
+fn test_block_and_canonical_constructors() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        class A {
+        public:
+            A(uint32_t a) : a(a) {}
+            A(const std::string& s) : a(s.size()) {}
+            A(uint32_t a, uint32_t b) : a(a + b) {}
+            uint32_t get() const { return a; }
+        private:
+            uint32_t a;
+        };
+    "};
+    let rs = quote! {
+        let a = ffi::A::make_unique(&ffi::make_string("hello"));
+        assert_eq!(a.get(), 5);
+        let a = ffi::A::make_unique1(3);
+        assert_eq!(a.get(), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["A"],
+            &[],
+            Some(quote! {
+                canonical_constructor!("A(const std::string&)")
+                block_constructors!("A(uint32_t, uint32_t)")
+            }),
+        ),
+        None,
+        Some(make_string_finder(
+            ["blocked using block_constructors"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_opaque_only() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        class HugeMatrix {
+        public:
+            HugeMatrix() : rank(3) {}
+            void transpose() {}
+            uint32_t rank;
+        };
+        inline std::unique_ptr<HugeMatrix> make_matrix() {
+            return std::make_unique<HugeMatrix>();
+        }
+        inline uint32_t get_rank(const HugeMatrix& m) { return m.rank; }
+    "};
+    let rs = quote! {
+        let m = ffi::make_matrix();
+        assert_eq!(ffi::get_rank(&m), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("make_matrix")
+            generate!("get_rank")
+            opaque_only!("HugeMatrix")
+        },
+        None,
+        Some(Box::new(CppMatcher::new(&[], &["transpose"]))),
+        None,
+    );
+}
+
+#[test]
+fn test_instantiable_override() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Base {
+        public:
+            virtual uint32_t foo() const = 0;
+            virtual ~Base() {}
+        };
+        class Derived : public Base {
+        public:
+            uint32_t foo() const override;
+        };
+    "};
+    let cpp = indoc! {"
+        uint32_t Derived::foo() const { return 3; }
+    "};
+    let rs = quote! {
+        let d = ffi::Derived::make_unique();
+        assert_eq!(d.foo(), 3);
+    };
+    run_test_ex(
+        cpp,
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Derived"],
+            &[],
+            Some(quote! {
+                instantiable!("Derived")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_instantiable_but_abstract() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Base {
+        public:
+            virtual uint32_t foo() const = 0;
+            virtual ~Base() {}
+        };
+    "};
+    let rs = quote! {};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["Base"],
+            &[],
+            Some(quote! {
+                instantiable!("Base")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_cfg_directive() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #ifdef _WIN32
+        inline uint32_t win_only_fn() { return 1; }
+        #endif
+        inline uint32_t everywhere_fn() { return 2; }
+        class Widget {
+        public:
+            uint32_t get() const { return 3; }
+            uint32_t seldom_used() const { return 4; }
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::everywhere_fn(), 2);
+        assert_eq!(ffi::Widget::make_unique().get(), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &["win_only_fn", "everywhere_fn", "Widget"],
+            &[],
+            Some(quote! {
+                cfg!("win_only_fn", "windows")
+                cfg!("Widget::seldom_used", "target_os = \"none\"")
+            }),
+        ),
+        None,
+        Some(Box::new(CppMatcher::new(&[], &["seldom_used"]))),
+        None,
+    );
+}
+
+#[test]
+fn test_generate_if_feature_disabled() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t stable_fn() { return 2; }
+        inline uint32_t experimental_fn() { return 3; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::stable_fn(), 2);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("stable_fn")
+            generate_if!("autocxx-test-experimental", "experimental_fn")
+        },
+        None,
+        Some(Box::new(CppMatcher::new(&[], &["experimental_fn"]))),
+        None,
+    );
+}
+
+#[test]
+fn test_map_namespace() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace a { namespace b { namespace c {
+            struct Widget {
+                uint32_t a;
+            };
+            inline uint32_t get_a(const Widget& w) { return w.a; }
+        } } }
+        namespace x {
+            inline uint32_t give_two() { return 2; }
+        }
+    "};
+    let rs = quote! {
+        let w = ffi::abc::Widget { a: 3 };
+        assert_eq!(ffi::abc::get_a(&w), 3);
+        assert_eq!(ffi::x::give_two(), 2);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_pod!("a::b::c::Widget")
+            generate!("a::b::c::get_a")
+            generate!("x::give_two")
+            map_namespace!("a::b::c", "abc")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_flatten_namespaces() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace a {
+            inline uint32_t give_one() { return 1; }
+            inline uint32_t give() { return 2; }
+        }
+        namespace b {
+            inline uint32_t give() { return 3; }
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::give_one(), 1);
+        assert_eq!(ffi::a::give(), 2);
+        assert_eq!(ffi::b::give(), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("a::give_one")
+            generate!("a::give")
+            generate!("b::give")
+            flatten_namespaces!()
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_snake_case_methods() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Widget {
+        public:
+            uint32_t GetValue() const { return 1; }
+            uint32_t GetHTTPStatus() const { return 200; }
+            uint32_t GetSize() const { return 2; }
+            uint32_t get_size() const { return 3; }
+            static uint32_t MakeId() { return 4; }
+            uint32_t pad;
+        };
+    "};
+    let rs = quote! {
+        let w = ffi::Widget { pad: 0 };
+        assert_eq!(w.get_value(), 1);
+        assert_eq!(w.get_http_status(), 200);
+        assert_eq!(w.GetSize(), 2);
+        assert_eq!(w.get_size(), 3);
+        assert_eq!(ffi::Widget::make_id(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_pod!("Widget")
+            snake_case_methods!()
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_property_accessors() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Widget {
+        public:
+            uint32_t getWidth() const { return width; }
+            void setWidth(uint32_t w) { width = w; }
+            uint32_t get_height() const { return height; }
+            void set_height(uint32_t h) { height = h; }
+            uint32_t GetDepth() const { return 3; }
+            uint32_t depth() const { return 4; }
+            void SetDepth(uint32_t) {}
+            uint32_t width;
+            uint32_t height;
+        };
+    "};
+    let rs = quote! {
+        let mut w = ffi::Widget { width: 1, height: 2 };
+        assert_eq!(w.width(), 1);
+        w.set_width(10);
+        assert_eq!(w.width(), 10);
+        assert_eq!(w.height(), 2);
+        w.set_height(20);
+        assert_eq!(w.height(), 20);
+        assert_eq!(w.GetDepth(), 3);
+        assert_eq!(w.depth(), 4);
+        w.SetDepth(5);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_pod!("Widget")
+            property_accessors!()
+        },
+        None,
+        Some(make_string_finder(
+            ["See also [`set_width`](Self::set_width)"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_helper_name_clashes() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        #include <string>
+        inline std::string make_string(uint32_t count) { return std::string(count, 'a'); }
+        class Widget {
+        public:
+            Widget() : v(1) {}
+            static std::unique_ptr<Widget> make_unique() {
+                auto w = std::make_unique<Widget>();
+                w->v = 2;
+                return w;
+            }
+            uint32_t value() const { return v; }
+        private:
+            uint32_t v;
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::make_string(3).to_str().unwrap(), "aaa");
+        assert_eq!(ffi::make_string_autocxx("hello").to_str().unwrap(), "hello");
+        assert_eq!(ffi::Widget::make_unique().value(), 2);
+        assert_eq!(ffi::Widget::make_unique_autocxx().value(), 1);
+    };
+    run_test("", hdr, rs, &["make_string", "Widget"], &[]);
+}
+
+#[test]
+fn test_generate_with_doc() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        /// The native widget.
+        struct Widget {
+            uint32_t a;
+        };
+        inline uint32_t get_a(const Widget& w) { return w.a; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::get_a(&ffi::Widget { a: 3 }), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_pod!("Widget", doc = "Handle to the native widget")
+            generate!("get_a", doc = "Reads the widget's value")
+        },
+        None,
+        Some(make_string_finder(
+            ["Handle to the native widget", "Reads the widget's value"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_helper_name_directive() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        inline uint32_t take_str(std::string a) { return a.size(); }
+        class Widget {
+        public:
+            Widget() : v(1) {}
+            uint32_t value() const { return v; }
+        private:
+            uint32_t v;
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::take_str(ffi::make_cpp_string("hello")), 5);
+        assert_eq!(ffi::Widget::create().value(), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("take_str")
+            generate!("Widget")
+            helper_name!("make_string", "make_cpp_string")
+            helper_name!("Widget::make_unique", "create")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_pod_with_opaque_field() {
+    // Inner can't be POD because it contains a blocked type, but Outer
+    // can still be POD with the inner field as an opaque blob.
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Blocked {
+            uint32_t a;
+        };
+        struct Inner {
+            Blocked blocked;
+            uint32_t c;
+        };
+        struct Outer {
+            uint32_t a;
+            Inner inner;
+            uint32_t b;
+        };
+        inline Outer make_outer() {
+            Outer o;
+            o.a = 1;
+            o.inner.c = 2;
+            o.b = 3;
+            return o;
+        }
+        inline uint32_t get_c(const Inner& inner) { return inner.c; }
+    "};
+    let rs = quote! {
+        let outer = ffi::make_outer();
+        assert_eq!(outer.a, 1);
+        assert_eq!(outer.b, 3);
+        assert_eq!(ffi::get_c(outer.inner()), 2);
+        assert_eq!(std::mem::size_of::<ffi::Outer>(), 16);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("make_outer")
+            generate!("get_c")
+            generate_pod!("Outer")
+            block!("Blocked")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_generate_pod_recursive() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Limits {
+            uint32_t min;
+            uint32_t max;
+        };
+        struct Config {
+            Limits limits;
+            uint32_t flags;
+        };
+        inline uint32_t range(Config c) { return c.limits.max - c.limits.min; }
+    "};
+    let rs = quote! {
+        let c = ffi::Config {
+            limits: ffi::Limits { min: 2, max: 5 },
+            flags: 0,
+        };
+        assert_eq!(ffi::range(c), 3);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("range")
+            generate_pod_recursive!("Config")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_generate_pod_recursive_reports_all_problems() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Blocked {
+            uint32_t a;
+        };
+        struct Inner {
+            Blocked blocked;
+            uint32_t b;
+        };
+        struct Config {
+            Inner inner;
+            uint32_t flags;
+        };
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate_pod_recursive!("Config")
+            block!("Blocked")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_force_pod_unsafe() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t& drop_count() {
+            static uint32_t count = 0;
+            return count;
+        }
+        struct Logged {
+            uint32_t a;
+            ~Logged() { drop_count()++; }
+        };
+        inline uint32_t get_a(const Logged& l) { return l.a; }
+        inline uint32_t get_drop_count() { return drop_count(); }
+    "};
+    let rs = quote! {
+        let l = ffi::Logged { a: 3 };
+        assert_eq!(ffi::get_a(&l), 3);
+        assert_eq!(ffi::get_drop_count(), 0);
+        drop(l);
+        assert_eq!(ffi::get_drop_count(), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("get_a")
+            generate!("get_drop_count")
+            force_pod_unsafe!("Logged")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_layout_packed_pod() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #pragma pack(push, 1)
+        struct Packet {
+            uint8_t kind;
+            uint32_t len;
+        };
+        #pragma pack(pop)
+        inline uint32_t packet_size() { return sizeof(Packet); }
+        inline uint32_t get_len(const Packet& p) { return p.len; }
+    "};
+    let rs = quote! {
+        assert_eq!(std::mem::size_of::<ffi::Packet>(), 5);
+        assert_eq!(ffi::packet_size(), 5);
+        let p = ffi::Packet { kind: 1, len: 7 };
+        assert_eq!(ffi::get_len(&p), 7);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("packet_size")
+            generate!("get_len")
+            generate_pod!("Packet")
+            layout!("Packet", packed = 1)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_layout_aligned_non_pod() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct alignas(16) Vector {
+            float x;
+            float y;
+            float z;
+        };
+    "};
+    let rs = quote! {
+        assert_eq!(std::mem::align_of::<ffi::Vector>(), 16);
+        assert_eq!(std::mem::size_of::<ffi::Vector>(), 16);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("Vector")
+            layout!("Vector", align = 16)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_layout_mismatch() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct alignas(16) Vector {
+            float x;
+            float y;
+            float z;
+        };
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate!("Vector")
+            layout!("Vector", align = 8)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_pod_layout_matches_target() {
+    // Run with e.g. --target i686-unknown-linux-gnu to check that POD
+    // layouts are computed for the target rather than the host.
+    let hdr = indoc! {"
+        #include <cstddef>
+        #include <cstdint>
+        struct Record {
+            uint8_t tag;
+            const char* name;
+            long count;
+            size_t len;
+        };
+        inline size_t record_size() { return sizeof(Record); }
+        inline size_t count_offset() { return offsetof(Record, count); }
+        inline size_t len_offset() { return offsetof(Record, len); }
+    "};
+    let rs = quote! {
+        let r = ffi::Record {
+            tag: 0,
+            name: std::ptr::null(),
+            count: autocxx::c_long(0),
+            len: 0,
+        };
+        let base = &r as *const ffi::Record as usize;
+        assert_eq!(std::mem::size_of::<ffi::Record>(), ffi::record_size());
+        assert_eq!(&r.count as *const _ as usize - base, ffi::count_offset());
+        assert_eq!(&r.len as *const _ as usize - base, ffi::len_offset());
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("record_size")
+            generate!("count_offset")
+            generate!("len_offset")
+            generate_pod!("Record")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_c_header() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        uint64_t add_via_c();
+    "};
+    let cxx = indoc! {"
+        #include \"autocxxgen_ffi_c.h\"
+        uint64_t add_via_c() {
+            uint32_t b = 5;
+            return ffi_add(2, &b);
+        }
+    "};
+    run_test_ex(
+        cxx,
+        hdr,
+        quote! {
+            assert_eq!(ffi::add_via_c(), 7);
+        },
+        quote! {
+            generate!("add_via_c")
+        },
+        Some(Box::new(EnableCHeader)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_function]
+            pub unsafe fn add(a: u32, b: *const u32) -> u64 {
+                (a + *b) as u64
+            }
+
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn describe(data: &[u8]) -> u32 {
+                data.len() as u32
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_cpp_type() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace fixed {
+        struct Q16 {
+            int32_t raw;
+            int32_t to_int() const { return raw >> 16; }
+        };
+        }
+        inline fixed::Q16 half() { return fixed::Q16 { 1 << 15 }; }
+        inline int32_t raw_of(fixed::Q16 q) { return q.raw; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::half(), crate::Q16 { raw: 1 << 15 });
+        assert_eq!(ffi::raw_of(crate::Q16 { raw: 7 }), 7);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("half")
+            generate!("raw_of")
+            extern_cpp_type!("fixed::Q16", crate::Q16)
+        },
+        None,
+        None,
+        Some(quote! {
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            pub struct Q16 {
+                raw: i32,
+            }
+
+            unsafe impl cxx::ExternType for Q16 {
+                type Id = cxx::type_id!("fixed::Q16");
+                type Kind = cxx::kind::Trivial;
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_cpp_type_size_mismatch() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace fixed {
+        struct Q16 {
+            int32_t raw;
+        };
+        }
+        inline int32_t raw_of(fixed::Q16 q) { return q.raw; }
+    "};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        quote! {},
+        quote! {
+            generate!("raw_of")
+            extern_cpp_type!("fixed::Q16", crate::Q16)
+        },
+        None,
+        None,
+        Some(quote! {
+            #[repr(C)]
+            #[derive(Clone, Copy)]
+            pub struct Q16 {
+                raw: i64,
+            }
+
+            unsafe impl cxx::ExternType for Q16 {
+                type Id = cxx::type_id!("fixed::Q16");
+                type Kind = cxx::kind::Trivial;
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_type_mapper() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace fixed {
+        struct Q16 {
+            int32_t raw;
+        };
+        }
+        inline fixed::Q16 one_and_a_half() { return fixed::Q16 { 3 << 15 }; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::one_and_a_half().to_int(), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate!("one_and_a_half") },
+        Some(Box::new(MapQ16)),
+        None,
+        Some(quote! {
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            pub struct Q16 {
+                raw: i32,
+            }
+
+            unsafe impl cxx::ExternType for Q16 {
+                type Id = cxx::type_id!("fixed::Q16");
+                type Kind = cxx::kind::Trivial;
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_cpp_type_eigen() {
+    let hdr = indoc! {"
+        namespace Eigen {
+        template <typename Scalar, int Rows, int Cols> class Matrix {
+        public:
+            Scalar m_data[Rows * Cols];
+        };
+        typedef Matrix<float, 3, 1> Vector3f;
+        }
+        inline Eigen::Vector3f scale(const Eigen::Vector3f& v, float f) {
+            return Eigen::Vector3f { { v.m_data[0] * f, v.m_data[1] * f, v.m_data[2] * f } };
+        }
+    "};
+    let rs = quote! {
+        let v = autocxx::eigen::glam::Vector3f::from(glam::Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(*ffi::scale(&v, 2.0), glam::Vec3::new(2.0, 4.0, 6.0));
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("scale")
+            extern_cpp_type!("Eigen::Vector3f", autocxx::eigen::glam::Vector3f)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_qt_signal() {
+    let hdr = indoc! {"
+        #include <functional>
+        #include <vector>
+        // Just enough of Qt to connect a signal to a functor.
+        class QObject {
+        public:
+            template <typename Sender, typename Functor>
+            static bool connect(Sender* sender, void (Sender::*)(int), Functor functor) {
+                sender->receivers.push_back(functor);
+                return true;
+            }
+        };
+        class Slider : public QObject {
+        public:
+            Slider() {}
+            // As generated by moc for a signal.
+            void valueChanged(int value) {
+                for (auto& receiver : receivers) {
+                    receiver(value);
+                }
+            }
+            std::vector<std::function<void(int)>> receivers;
+        };
+    "};
+    let rs = quote! {
+        use std::{cell::Cell, rc::Rc};
+        let total = Rc::new(Cell::new(0));
+        let total_in_closure = total.clone();
+        let mut slider = ffi::Slider::make_unique();
+        assert!(slider.pin_mut().connect_valueChanged(move |value| {
+            total_in_closure.set(total_in_closure.get() + value)
+        }));
+        slider.pin_mut().valueChanged(autocxx::c_int(2));
+        slider.pin_mut().valueChanged(autocxx::c_int(3));
+        assert_eq!(total.get(), 5);
+        drop(slider);
+        assert_eq!(Rc::strong_count(&total), 1);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("Slider")
+            qt_signal!("Slider::valueChanged(int)")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_precompiled_header() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        inline uint32_t take_str(std::string a) { return a.size(); }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::take_str("hello"), 5);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["take_str"], &[], None),
+        Some(Box::new(UsePrecompiledHeader)),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_early_typecheck() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        class Goat {
+        public:
+            Goat() : horns(2) {}
+            uint32_t describe(std::string name) const { return name.size() + horns; }
+        private:
+            uint32_t horns;
+        };
+    "};
+    let rs = quote! {
+        let goat = ffi::Goat::make_unique();
+        assert_eq!(goat.describe("Billy"), 7);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["Goat"], &[], None),
+        Some(Box::new(EnableEarlyTypecheck)),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_hidden_shims() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <string>
+        inline uint32_t take_str(std::string a) { return a.size(); }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::take_str("hello"), 5);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["take_str"], &[], None),
+        Some(Box::new(HideShims)),
+        Some(Box::new(CppMatcher::new(
+            &["#define AUTOCXX_HIDDEN", "AUTOCXX_HIDDEN uint32_t take_str"],
+            &[],
+        ))),
+        None,
+    );
+}
+
+#[test]
+fn test_string_make_unique() {
+    let hdr = indoc! {"
+        #include <string>
+        inline void take_string(const std::string*) {};
+    "};
+    let rs = quote! {
+        let s = ffi::make_string("");
+        unsafe { ffi::take_string(s.as_ref().unwrap()) };
+    };
+    run_test("", hdr, rs, &["take_string"], &[]);
+}
+
+#[test]
+fn test_string_constant() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        const char* STRING = \"Foo\";
+    "};
+    let rs = quote! {
+        let a = std::str::from_utf8(ffi::STRING).unwrap().trim_end_matches(char::from(0));
+        assert_eq!(a, "Foo");
+    };
+    run_test("", hdr, rs, &["STRING"], &[]);
+}
+
+#[test]
+fn test_pod_constant_harmless_inside_type() {
+    // Check that the presence of this constant doesn't break anything.
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct Bob {
+            uint32_t a;
+        };
+        struct Anna {
             uint32_t a;
             const Bob BOB = Bob { 10 };
         };
@@ -3573,6 +5767,49 @@ fn test_forward_declaration() {
     run_test(cpp, hdr, rs, &["B", "get_a", "delete_a"], &[]);
 }
 
+#[test]
+fn test_extern_cpp_opaque_type() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        struct A;
+        std::unique_ptr<A> make_a();
+        uint32_t get_a(const A&);
+        struct A {
+            uint32_t a;
+        };
+    "};
+    let cpp = indoc! {"
+        std::unique_ptr<A> make_a() {
+            auto a = std::make_unique<A>();
+            a->a = 3;
+            return a;
+        }
+        uint32_t get_a(const A& a) {
+            return a.a;
+        }
+    "};
+    let rs = quote! {
+        let a = ffi::make_a();
+        assert_eq!(ffi::get_a(&a), 3);
+    };
+    run_test_ex(
+        cpp,
+        hdr,
+        rs,
+        directives_from_lists(
+            &["make_a", "get_a"],
+            &[],
+            Some(quote! {
+                extern_cpp_opaque_type!("A")
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
+}
+
 #[test]
 fn test_ulong() {
     let hdr = indoc! {"
@@ -3655,11 +5892,41 @@ fn test_reserved_name() {
         inline uint32_t async(uint32_t a) { return a; }
     "};
     let rs = quote! {
-        assert_eq!(ffi::async_(34), 34);
+        assert_eq!(ffi::r#async(34), 34);
     };
     run_test("", hdr, rs, &["async_"], &[]);
 }
 
+#[test]
+fn test_reserved_name_generated_by_cpp_name() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        inline uint32_t async(uint32_t a) { return a; }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::r#async(34), 34);
+    };
+    run_test("", hdr, rs, &["async"], &[]);
+}
+
+#[test]
+fn test_keyword_field() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct A {
+            uint32_t async;
+            uint32_t move;
+        };
+        inline uint32_t sum(const A& a) { return a.async + a.move; }
+    "};
+    let rs = quote! {
+        let a = ffi::A { r#async: 1, r#move: 2 };
+        assert_eq!(ffi::sum(&a), 3);
+        assert_eq!(a.r#async, 1);
+    };
+    run_test("", hdr, rs, &["sum"], &["A"]);
+}
+
 #[cfg_attr(skip_windows_gnu_failing_tests, ignore)]
 #[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
 #[test]
@@ -3955,34 +6222,131 @@ fn test_vector_cycle_bare() {
 #[test]
 fn test_typedef_to_std() {
     let hdr = indoc! {"
-        #include <string>
-        typedef std::string my_string;
-        inline uint32_t take_str(my_string a) {
-            return a.size();
+        #include <string>
+        typedef std::string my_string;
+        inline uint32_t take_str(my_string a) {
+            return a.size();
+        }
+    "};
+    let rs = quote! {
+        use ffi::ToCppString;
+        assert_eq!(ffi::take_str("hello".into_cpp()), 5);
+    };
+    run_test("", hdr, rs, &["take_str"], &[]);
+}
+
+#[test]
+fn test_typedef_to_up_in_fn_call() {
+    let hdr = indoc! {"
+        #include <string>
+        #include <memory>
+        typedef std::unique_ptr<std::string> my_string;
+        inline uint32_t take_str(my_string a) {
+            return a->size();
+        }
+    "};
+    let rs = quote! {
+        use ffi::ToCppString;
+        assert_eq!(ffi::take_str("hello".into_cpp()), 5);
+    };
+    run_test("", hdr, rs, &["take_str"], &[]);
+}
+
+#[test]
+fn test_alias_template_to_up() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        template <typename T> using Ptr = std::unique_ptr<T>;
+        struct Goat {
+            uint32_t horns;
+        };
+        using GoatPtr = Ptr<Goat>;
+        inline Ptr<Goat> make_goat() {
+            auto g = std::make_unique<Goat>();
+            g->horns = 2;
+            return g;
+        }
+        inline uint32_t count_horns(const GoatPtr& g) {
+            return g->horns;
+        }
+    "};
+    let rs = quote! {
+        let goat = ffi::make_goat();
+        assert_eq!(ffi::count_horns(&goat), 2);
+    };
+    run_test("", hdr, rs, &["make_goat", "count_horns"], &["Goat"]);
+}
+
+#[test]
+fn test_alias_template_to_unsupported_template() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        #include <vector>
+        template <typename T> using Vec = std::vector<T, std::allocator<T>>;
+        inline std::unique_ptr<Vec<uint32_t>> make_vec() {
+            return std::make_unique<Vec<uint32_t>>(3);
+        }
+        inline uint32_t vec_len(const Vec<uint32_t>& v) {
+            return v.size();
         }
     "};
     let rs = quote! {
-        use ffi::ToCppString;
-        assert_eq!(ffi::take_str("hello".into_cpp()), 5);
+        let v = ffi::make_vec();
+        assert_eq!(ffi::vec_len(&v), 3);
     };
-    run_test("", hdr, rs, &["take_str"], &[]);
+    run_test("", hdr, rs, &["make_vec", "vec_len"], &[]);
 }
 
 #[test]
-fn test_typedef_to_up_in_fn_call() {
+fn test_concrete_static_methods() {
     let hdr = indoc! {"
-        #include <string>
+        #include <cstdint>
         #include <memory>
-        typedef std::unique_ptr<std::string> my_string;
-        inline uint32_t take_str(my_string a) {
-            return a->size();
-        }
+        struct Widget {
+            uint32_t size;
+        };
+        template <typename T> class Factory {
+        public:
+            static std::unique_ptr<Factory> create() {
+                auto factory = std::make_unique<Factory>();
+                factory->made = 1;
+                return factory;
+            }
+            static T make(uint32_t size) {
+                T t;
+                t.size = size;
+                return t;
+            }
+            static uint32_t count(const Factory& factory) {
+                return factory.made;
+            }
+        private:
+            T* last = nullptr;
+            uint32_t made = 0;
+        };
     "};
     let rs = quote! {
-        use ffi::ToCppString;
-        assert_eq!(ffi::take_str("hello".into_cpp()), 5);
+        let factory = ffi::FactoryWidget::create();
+        assert_eq!(ffi::FactoryWidget::count(&factory), 1);
+        assert_eq!(ffi::FactoryWidget::make(3).size, 3);
     };
-    run_test("", hdr, rs, &["take_str"], &[]);
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(
+            &[],
+            &["Widget"],
+            Some(quote! {
+                concrete!("Factory<Widget>", FactoryWidget)
+            }),
+        ),
+        None,
+        None,
+        None,
+    );
 }
 
 #[test]
@@ -4518,6 +6882,27 @@ fn test_double_underscore_typedef_ignored() {
     run_test("", hdr, rs, &["B"], &[]);
 }
 
+#[test]
+fn test_double_underscore_typedef_opaque() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    typedef int __int32_t;
+    typedef __int32_t __darwin_pid_t;
+    typedef __darwin_pid_t pid_t;
+    struct B {
+        uint32_t take_foo(const pid_t&) const {
+            return 3;
+        }
+    };
+    "};
+    let rs = quote! {
+        // pid_t can't be represented as an int, but functions which take
+        // it by reference should still be generated with it as an opaque type.
+        let _: fn(&ffi::B, &ffi::pid_t) -> u32 = ffi::B::take_foo;
+    };
+    run_test("", hdr, rs, &["B"], &[]);
+}
+
 #[test]
 fn test_double_underscores_fn_namespace() {
     let hdr = indoc! {"
@@ -5060,6 +7445,38 @@ fn test_immovable_object() {
     run_test("", hdr, rs, &["A", "B"], &[]);
 }
 
+#[test]
+fn test_immovable_object_construction() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        class Guard {
+        public:
+            Guard(uint32_t a) : a(a) {}
+            Guard(const Guard&) = delete;
+            Guard(Guard&&) = delete;
+            uint32_t get() const { return a; }
+        private:
+            uint32_t a;
+        };
+        inline Guard make_guard() { return Guard(4); }
+    "};
+    let rs = quote! {
+        let a = ffi::Guard::make_unique(3);
+        assert_eq!(a.get(), 3);
+        let b = ffi::make_guard();
+        assert_eq!(b.get(), 4);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        directives_from_lists(&["Guard", "make_guard"], &[], None),
+        make_clang_arg_adder(&["-std=c++17"]),
+        None,
+        None,
+    );
+}
+
 #[test]
 fn test_struct_with_reference() {
     let hdr = indoc! {"
@@ -5209,69 +7626,403 @@ fn test_private_inheritance() {
 }
 
 #[test]
-fn test_error_generated_for_static_data() {
+fn test_error_generated_for_static_data() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct A {
+            A() {}
+            uint32_t a;
+        };
+        static A FOO = A();
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate!("FOO")},
+        None,
+        Some(make_error_finder("FOO")),
+        None,
+    );
+}
+
+#[test]
+fn test_error_generated_for_array_dependent_function() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <functional>
+        inline void take_func(std::function<bool(const uint32_t number)>) {
+        }
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate! ("take_func")},
+        None,
+        Some(make_error_finder("take_func")),
+        None,
+    );
+}
+
+#[test]
+#[cfg_attr(skip_windows_gnu_failing_tests, ignore)]
+#[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
+fn test_error_generated_for_array_dependent_method() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <functional>
+        struct A {
+            void take_func(std::function<bool(const uint32_t number)>) {
+            }
+        };
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate! ("A")},
+        None,
+        Some(make_string_finder(
+            ["take_func", "couldn't be generated"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_error_includes_source_location() {
+    let hdr = indoc! {"
+        struct A {
+            void take_rvalue(A&&) {}
+        };
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate! ("A")},
+        None,
+        Some(make_string_finder(
+            // input.h begins with #pragma once.
+            ["take_rvalue", "Declared at", "input.h:3"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_ignored_function_includes_source_location() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        struct A {};
+
+        inline void take_rvalue(A&&) {}
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! { generate! ("take_rvalue")},
+        None,
+        Some(make_string_finder(
+            ["take_rvalue", "Declared at", "input.h:5"].to_vec(),
+        )),
+        None,
+    );
+}
+
+#[test]
+fn test_silence_error() {
+    // Silencing an error only stops it being reported during the build:
+    // the docs still explain why the method is missing.
+    let hdr = indoc! {"
+        struct A {
+            void take_rvalue(A&&) {}
+        };
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("A")
+            silence_error!("ACX0034")
+        },
+        None,
+        Some(make_string_finder(["take_rvalue", "ACX0034"].to_vec())),
+        None,
+    );
+}
+
+#[test]
+fn test_strict_with_expected_ignored_item() {
+    let hdr = indoc! {"
+        struct A {
+            void take_rvalue(A&&) {}
+        };
+    "};
+    let rs = quote! {};
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("A")
+            strict!()
+            expect_ignored!("A::take_rvalue", "takes an rvalue reference")
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_strict_with_unexpected_ignored_item() {
+    let hdr = indoc! {"
+        struct A {
+            void take_rvalue(A&&) {}
+        };
+    "};
+    let rs = quote! {};
+    run_test_expect_fail_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("A")
+            strict!()
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_vector_ext() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        #include <memory>
+        #include <string>
+        #include <vector>
+        struct Point {
+            uint32_t x;
+            uint32_t y;
+        };
+        class Label {
+        public:
+            Label(const std::string& text) : text(text) {}
+            const std::string& get_text() const { return text; }
+        private:
+            std::string text;
+        };
+        inline std::unique_ptr<std::vector<Point>> make_points() {
+            return std::make_unique<std::vector<Point>>();
+        }
+        inline std::unique_ptr<std::vector<Label>> make_labels() {
+            return std::make_unique<std::vector<Label>>();
+        }
+        inline std::unique_ptr<std::vector<uint32_t>> make_ints() {
+            auto ints = std::make_unique<std::vector<uint32_t>>();
+            ints->push_back(3);
+            return ints;
+        }
+    "};
+    let rs = quote! {
+        let mut points = ffi::make_points();
+        let mut point = ffi::Point { x: 1, y: 2 };
+        points.pin_mut().push_moved(std::pin::Pin::new(&mut point));
+        let points = points.to_vec();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].y, 2);
+        moveit! {
+            let mut label = ffi::Label::new("hello");
+        }
+        let mut labels = ffi::make_labels();
+        labels.pin_mut().push_moved(label.as_mut());
+        assert_eq!(labels.get(0).unwrap().get_text().to_str().unwrap(), "hello");
+        let ints = ffi::make_ints();
+        assert!(ints.contains(&3));
+        assert!(!ints.contains(&4));
+    };
+    run_test(
+        "",
+        hdr,
+        rs,
+        &["Label", "make_points", "make_labels", "make_ints"],
+        &["Point"],
+    );
+}
+
+#[test]
+fn test_rs_module_tree() {
     let hdr = indoc! {"
         #include <cstdint>
-        struct A {
-            A() {}
-            uint32_t a;
-        };
-        static A FOO = A();
+        namespace a {
+            namespace b {
+                struct Point {
+                    uint32_t x;
+                };
+                inline uint32_t get_x(const Point& p) {
+                    return p.x;
+                }
+            }
+        }
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        let p = ffi::a::b::Point { x: 3 };
+        assert_eq!(ffi::a::b::get_x(&p), 3);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
-        quote! { generate!("FOO")},
+        quote! {
+            generate_pod!("a::b::Point")
+            generate!("a::b::get_x")
+        },
+        Some(Box::new(UseRsModuleTree)),
         None,
-        Some(make_error_finder("FOO")),
         None,
     );
 }
 
 #[test]
-fn test_error_generated_for_array_dependent_function() {
+fn test_system_include() {
+    // The header relies on cstdint having been included before it.
     let hdr = indoc! {"
-        #include <cstdint>
-        #include <functional>
-        inline void take_func(std::function<bool(const uint32_t number)>) {
+        inline uint32_t take_u32(uint32_t a) {
+            return a;
         }
     "};
-    let rs = quote! {};
+    let hexathorpe = Token![#](Span::call_site());
+    let rs = quote! {
+        assert_eq!(ffi::take_u32(7), 7);
+    };
     run_test_ex(
         "",
         hdr,
         rs,
-        quote! { generate! ("take_func")},
+        quote! {
+            #hexathorpe include <cstdint>
+            include_priority!("cstdint", -1)
+            generate!("take_u32")
+        },
+        None,
         None,
-        Some(make_error_finder("take_func")),
         None,
     );
 }
 
 #[test]
-#[cfg_attr(skip_windows_gnu_failing_tests, ignore)]
-#[cfg_attr(skip_windows_msvc_failing_tests, ignore)]
-fn test_error_generated_for_array_dependent_method() {
+fn test_pod_constructor_by_value() {
     let hdr = indoc! {"
         #include <cstdint>
-        #include <functional>
-        struct A {
-            void take_func(std::function<bool(const uint32_t number)>) {
-            }
+        struct Point {
+            Point(uint32_t x, uint32_t y) : x(x), y(y) {}
+            uint32_t sum() const { return x + y; }
+            uint32_t x;
+            uint32_t y;
         };
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        let p: ffi::Point = ffi::Point::new(1, 2);
+        assert_eq!(p.x, 1);
+        assert_eq!(p.sum(), 3);
+        moveit! {
+            let q = ffi::Point::new(3, 4);
+        }
+        assert_eq!(q.sum(), 7);
+        use autocxx::moveit::Emplace;
+        let boxed = Box::emplace(ffi::Point::new(5, 6));
+        assert_eq!(boxed.y, 6);
+    };
+    run_test("", hdr, rs, &[], &["Point"]);
+}
+
+#[test]
+fn test_string_returns_str() {
+    let hdr = indoc! {"
+        #include <string>
+        #include <memory>
+        class Widget {
+        public:
+            Widget() : name(\"widget\") {}
+            const std::string& get_name() const { return name; }
+            const std::string& rename() { name = \"gadget\"; return name; }
+            std::string describe() const { return \"a \" + name; }
+        private:
+            std::string name;
+        };
+        inline std::string greeting() { return \"hello\"; }
+        inline std::unique_ptr<std::string> maybe_greeting() { return nullptr; }
+    "};
+    let rs = quote! {
+        let mut w = ffi::Widget::make_unique();
+        let name: &str = w.get_name();
+        assert_eq!(name, "widget");
+        let described: String = w.describe();
+        assert_eq!(described, "a widget");
+        assert_eq!(w.pin_mut().rename(), "gadget");
+        let greeting: String = ffi::greeting();
+        assert_eq!(greeting, "hello");
+        let maybe_greeting: cxx::UniquePtr<cxx::CxxString> = ffi::maybe_greeting();
+        assert!(maybe_greeting.is_null());
+    };
     run_test_ex(
         "",
         hdr,
         rs,
-        quote! { generate! ("A")},
+        quote! {
+            generate!("Widget")
+            generate!("greeting")
+            generate!("maybe_greeting")
+            string_returns!(str)
+        },
+        None,
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_string_returns_cow() {
+    let hdr = indoc! {"
+        #include <string>
+        class Widget {
+        public:
+            Widget() : name(\"widget\\xff\") {}
+            const std::string& get_name() const { return name; }
+            std::string describe() const { return \"a \" + name; }
+        private:
+            std::string name;
+        };
+    "};
+    let rs = quote! {
+        let w = ffi::Widget::make_unique();
+        let name: std::borrow::Cow<str> = w.get_name();
+        assert_eq!(name, "widget\u{fffd}");
+        let described: String = w.describe();
+        assert_eq!(described, "a widget\u{fffd}");
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate!("Widget")
+            string_returns!(cow)
+        },
+        None,
         None,
-        Some(make_string_finder(
-            ["take_func", "couldn't be generated"].to_vec(),
-        )),
         None,
     );
 }
@@ -5361,7 +8112,9 @@ fn test_keyword_function() {
     let hdr = indoc! {"
         inline void move(int a) {};
     "};
-    let rs = quote! {};
+    let rs = quote! {
+        ffi::r#move(3);
+    };
     run_test("", hdr, rs, &["move_"], &[]);
 }
 
@@ -5371,10 +8124,14 @@ fn test_keyword_method() {
         struct A {
             int a;
             inline void move() {};
+            inline int type() const { return a; };
         };
     "};
-    let rs = quote! {};
-    run_test("", hdr, rs, &["A"], &[]);
+    let rs = quote! {
+        let a = ffi::A { a: 3 };
+        assert_eq!(a.r#type(), 3);
+    };
+    run_test("", hdr, rs, &[], &["A"]);
 }
 
 #[test]
@@ -5652,6 +8409,41 @@ fn test_bitset() {
     );
 }
 
+#[test]
+fn test_block_ns() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        namespace mylib {
+        namespace internal {
+        struct Impl {
+            uint32_t secret;
+        };
+        inline uint32_t reveal() { return 1; }
+        namespace detail {
+        inline uint32_t reveal_more() { return 2; }
+        }
+        }
+        inline uint32_t answer() { return 42; }
+        inline uint32_t peek(const internal::Impl& i) { return i.secret; }
+        }
+    "};
+    let rs = quote! {
+        assert_eq!(ffi::mylib::answer(), 42);
+    };
+    run_test_ex(
+        "",
+        hdr,
+        rs,
+        quote! {
+            generate_all!()
+            block_ns!("mylib::internal")
+        },
+        None,
+        Some(Box::new(CppMatcher::new(&["answer"], &["reveal", "peek"]))),
+        None,
+    );
+}
+
 #[test]
 fn test_cint_vector() {
     let hdr = indoc! {"
@@ -6311,6 +9103,40 @@ fn test_box_via_extern_rust_in_mod() {
     );
 }
 
+#[test]
+fn test_extern_rust_enum() {
+    let hdr = indoc! {"
+        #include <cxx.h>
+        inline void do_thing() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let cpp_status: ffi::Status = Status::Busy.into();
+            assert!(cpp_status == ffi::Status::Busy);
+            assert_eq!(cpp_status.repr, 4);
+            let status: Status = std::convert::TryFrom::try_from(cpp_status).unwrap();
+            assert!(status == Status::Busy);
+            let unknown = ffi::Status { repr: 2 };
+            assert!(<Status as std::convert::TryFrom<_>>::try_from(unknown).is_err());
+        },
+        quote! {
+            generate!("do_thing")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            #[derive(PartialEq, Debug)]
+            pub enum Status {
+                Ready,
+                Busy = 4,
+            }
+        }),
+    );
+}
+
 #[test]
 fn test_extern_rust_fn() {
     let hdr = indoc! {"
@@ -6328,8 +9154,208 @@ fn test_extern_rust_fn() {
         None,
         Some(quote! {
             #[autocxx::extern_rust::extern_rust_function]
-            fn my_rust_fun() {
+            fn my_rust_fun() {
+
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_slice_and_vec() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        uint32_t sum_via_rust();
+        uint32_t len_via_rust();
+    "};
+    let cxx = indoc! {"
+        uint32_t sum_via_rust() {
+            uint8_t data[] = {1, 2, 3};
+            return sum_bytes(rust::Slice<const uint8_t>(data, 3));
+        }
+        uint32_t len_via_rust() {
+            rust::Vec<uint32_t> v = make_vec(4);
+            return v.size();
+        }
+    "};
+    run_test_ex(
+        cxx,
+        hdr,
+        quote! {
+            assert_eq!(ffi::sum_via_rust(), 6);
+            assert_eq!(ffi::len_via_rust(), 4);
+        },
+        quote! {
+            generate!("sum_via_rust")
+            generate!("len_via_rust")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn sum_bytes(data: &[u8]) -> u32 {
+                data.iter().map(|b| *b as u32).sum()
+            }
+
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn make_vec(n: u32) -> std::vec::Vec<u32> {
+                (0..n).collect()
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_fn_result() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        uint32_t parse_via_exception(bool valid);
+        uint32_t parse_via_expected(bool valid);
+    "};
+    let cxx = indoc! {"
+        uint32_t parse_via_exception(bool valid) {
+            try {
+                return parse_number(valid ? \"12\" : \"twelve\");
+            } catch (const rust::Error&) {
+                return 0;
+            }
+        }
+        uint32_t parse_via_expected(bool valid) {
+            autocxx::Expected<uint32_t> result = parse_number_expected(valid ? \"34\" : \"thirty-four\");
+            if (!result) {
+                return result.error().empty() ? 1 : 0;
+            }
+            return result.value();
+        }
+    "};
+    run_test_ex(
+        cxx,
+        hdr,
+        quote! {
+            assert_eq!(ffi::parse_via_exception(true), 12);
+            assert_eq!(ffi::parse_via_exception(false), 0);
+            assert_eq!(ffi::parse_via_expected(true), 34);
+            assert_eq!(ffi::parse_via_expected(false), 0);
+        },
+        quote! {
+            generate!("parse_via_exception")
+            generate!("parse_via_expected")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_function(expected)]
+            pub fn parse_number(s: &str) -> Result<u32, std::num::ParseIntError> {
+                s.parse()
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_namespace_and_class() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        uint32_t via_namespace();
+        uint32_t via_class();
+    "};
+    let cxx = indoc! {"
+        uint32_t via_namespace() {
+            rust::Box<mylib::api::Counter> c = mylib::api::make_counter(3);
+            return mylib::api::counter_value(*c);
+        }
+        uint32_t via_class() {
+            rust::Box<mylib::api::RustApi::Counter> c = mylib::api::RustApi::make_counter(4);
+            uint32_t parsed = mylib::api::RustApi::parse_number_expected(\"5\").value();
+            return mylib::api::RustApi::counter_value(*c) + parsed;
+        }
+    "};
+    run_test_ex(
+        cxx,
+        hdr,
+        quote! {
+            assert_eq!(ffi::via_namespace(), 3);
+            assert_eq!(ffi::via_class(), 9);
+        },
+        quote! {
+            generate!("via_namespace")
+            generate!("via_class")
+            extern_rust_namespace!("mylib::api")
+            extern_rust_class!("RustApi")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct Counter(u32);
+
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn make_counter(n: u32) -> Box<Counter> {
+                Box::new(Counter(n))
+            }
+
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn counter_value(c: &Counter) -> u32 {
+                c.0
+            }
+
+            #[autocxx::extern_rust::extern_rust_function(expected)]
+            pub fn parse_number(s: &str) -> Result<u32, std::num::ParseIntError> {
+                s.parse()
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_extern_rust_factory() {
+    let hdr = indoc! {"
+        #include <cstdint>
+        uint32_t total_horns();
+    "};
+    let cxx = indoc! {"
+        uint32_t total_horns() {
+            rust::Box<Goat> billy = make_Goat(2);
+            rust::Box<Sheep> dolly = make_Sheep(1);
+            return goat_horns(*billy) + sheep_horns(*dolly);
+        }
+    "};
+    run_test_ex(
+        cxx,
+        hdr,
+        quote! {
+            assert_eq!(ffi::total_horns(), 3);
+        },
+        quote! {
+            generate!("total_horns")
+        },
+        Some(Box::new(EnableAutodiscover)),
+        None,
+        Some(quote! {
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct Goat(u32);
+
+            #[autocxx::extern_rust::extern_rust_type]
+            pub struct Sheep(u32);
+
+            #[autocxx::extern_rust::extern_rust_function(factory)]
+            pub fn new_goat(horns: u32) -> Goat {
+                Goat(horns)
+            }
+
+            #[autocxx::extern_rust::extern_rust_function(factory)]
+            pub fn new_sheep(horns: u32) -> Box<Sheep> {
+                Box::new(Sheep(horns))
+            }
+
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn goat_horns(goat: &Goat) -> u32 {
+                goat.0
+            }
 
+            #[autocxx::extern_rust::extern_rust_function]
+            pub fn sheep_horns(sheep: &Sheep) -> u32 {
+                sheep.0
             }
         }),
     );
@@ -6650,6 +9676,54 @@ fn test_pv_subclass_passed_to_fn() {
     );
 }
 
+#[test]
+fn test_pv_subclass_mock() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo(uint32_t a) const = 0;
+        virtual ~Observer() {}
+    };
+    inline void bar() {}
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let mut mock = ffi::MockObserver::default();
+            mock.expect_foo(|a| a + 1);
+            assert_eq!(call_foo(&mock), 4);
+            assert_eq!(mock.calls(), vec!["foo"]);
+        },
+        quote! {
+            generate!("bar")
+            subclass!("Observer",MyObserver)
+            mock!("Observer")
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+                a: u32
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self, a: u32) -> u32 {
+                    a
+                }
+            }
+            fn call_foo(o: &impl Observer_methods) -> u32 {
+                o.foo(3)
+            }
+        }),
+    );
+}
+
 #[test]
 fn test_pv_subclass_derive_defaults() {
     let hdr = indoc! {"
@@ -6923,6 +9997,54 @@ fn test_pv_protected_method() {
     );
 }
 
+#[test]
+fn test_pv_subclass_calls_protected_method() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    class Observer {
+    public:
+        Observer() : count(0) {}
+        virtual uint32_t foo() { return 0; }
+        virtual ~Observer() {}
+    protected:
+        uint32_t get_count() const { return count; }
+        void increment(uint32_t by) { count += by; }
+    private:
+        uint32_t count;
+    };
+    inline uint32_t call_foo(Observer& obs) { return obs.foo(); }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let obs = MyObserver::new_rust_owned(MyObserver { cpp_peer: Default::default() });
+            assert_eq!(obs.borrow_mut().foo(), 3);
+            assert_eq!(obs.borrow_mut().foo(), 6);
+        },
+        quote! {
+            generate!("call_foo")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&mut self) -> u32 {
+                    self.peer_mut().increment(3);
+                    self.peer().get_count()
+                }
+            }
+        }),
+    );
+}
+
 #[test]
 fn test_pv_subclass_allocation_not_self_owned() {
     let hdr = indoc! {"
@@ -7517,6 +10639,85 @@ fn test_pv_subclass_types() {
     );
 }
 
+#[test]
+fn test_pv_subclass_reference_params() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    #include <memory>
+
+    class Counter {
+    public:
+        Counter() : count(0) {}
+        void increment() { count++; }
+        uint32_t get() const { return count; }
+    private:
+        uint32_t count;
+    };
+    class CounterObserver {
+    public:
+        CounterObserver() {}
+        virtual void touch(Counter& c) = 0;
+        virtual uint32_t peek(const Counter& c) const = 0;
+        virtual uint32_t take(std::unique_ptr<Counter> c) const = 0;
+        virtual ~CounterObserver() {}
+    };
+
+    inline uint32_t touch_and_peek(CounterObserver& obs, Counter& c) {
+        obs.touch(c);
+        obs.touch(c);
+        return obs.peek(c);
+    }
+    inline uint32_t hand_over(const CounterObserver& obs) {
+        auto c = std::make_unique<Counter>();
+        c->increment();
+        return obs.take(std::move(c));
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let obs = MyCounterObserver::new_rust_owned(MyCounterObserver::default());
+            let mut c = ffi::Counter::make_unique();
+            assert_eq!(
+                ffi::touch_and_peek(obs.as_ref().borrow_mut().pin_mut(), c.pin_mut()),
+                2
+            );
+            assert_eq!(c.get(), 2);
+            assert_eq!(ffi::hand_over(obs.as_ref().borrow().as_ref()), 1);
+        },
+        quote! {
+            generate!("Counter")
+            generate!("touch_and_peek")
+            generate!("hand_over")
+            subclass!("CounterObserver",MyCounterObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::CounterObserver_methods;
+            #[autocxx::subclass::subclass]
+            #[derive(Default)]
+            pub struct MyCounterObserver {
+            }
+            impl CounterObserver_methods for MyCounterObserver {
+                fn touch(&mut self, c: std::pin::Pin<&mut ffi::Counter>) {
+                    c.increment()
+                }
+
+                fn peek(&self, c: &ffi::Counter) -> u32 {
+                    c.get()
+                }
+
+                fn take(&self, c: cxx::UniquePtr<ffi::Counter>) -> u32 {
+                    c.get()
+                }
+            }
+        }),
+    );
+}
+
 #[test]
 fn test_pv_subclass_constructors() {
     // Also tests a Rust-side subclass type which is an empty struct
@@ -7775,6 +10976,203 @@ fn test_pv_subclass_namespaced_superclass() {
     );
 }
 
+#[test]
+fn test_pv_subclass_nested_namespaced_superclass() {
+    let hdr = indoc! {"
+    #include <cstdint>
+
+    namespace ui {
+    namespace widgets {
+    class Widget {
+    public:
+        Widget() {}
+        virtual uint32_t width() const { return 10; }
+        virtual uint32_t height() const = 0;
+        virtual ~Widget() {}
+    protected:
+        uint32_t margin() const { return 1; }
+    };
+    }
+    }
+    inline uint32_t area(const ui::widgets::Widget& w) { return w.width() * w.height(); }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let w = MyWidget::new_rust_owned(MyWidget { cpp_peer: Default::default() });
+            assert_eq!(ffi::area(w.borrow().as_ref()), 132);
+        },
+        quote! {
+            generate!("area")
+            subclass!("ui::widgets::Widget",MyWidget)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::ui::widgets::Widget_methods;
+            use ffi::ui::widgets::Widget_supers;
+            #[autocxx::subclass::subclass]
+            pub struct MyWidget {
+            }
+            impl Widget_methods for MyWidget {
+                fn width(&self) -> u32 {
+                    self.width_super() + 2 * self.peer().margin()
+                }
+                fn height(&self) -> u32 {
+                    11
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_destroyed_hook() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual ~Observer() {}
+    };
+    inline void delete_observer(Observer* obs) { delete obs; }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            // Destroyed by Rust: the hook isn't called.
+            let obs = MyObserver::new_rust_owned(MyObserver { cpp_peer: Default::default() });
+            std::mem::drop(obs);
+            assert_eq!(DESTROYED.load(Ordering::SeqCst), 0);
+            // Destroyed by C++ through a superclass pointer: the hook is called.
+            let obs = MyObserver::new_cpp_owned(MyObserver { cpp_peer: Default::default() });
+            let obs_ptr = obs.into_raw() as *mut ffi::Observer;
+            unsafe { ffi::delete_observer(obs_ptr) };
+            assert_eq!(DESTROYED.load(Ordering::SeqCst), 1);
+        },
+        quote! {
+            generate!("delete_observer")
+            subclass!("Observer",MyObserver)
+        },
+        None,
+        None,
+        Some(quote! {
+            use std::sync::atomic::{AtomicU32, Ordering};
+            static DESTROYED: AtomicU32 = AtomicU32::new(0);
+            use autocxx::subclass::CppSubclass;
+            use ffi::Observer_methods;
+            #[autocxx::subclass::subclass]
+            pub struct MyObserver {
+            }
+            impl Observer_methods for MyObserver {
+                fn foo(&self) -> u32 {
+                    4
+                }
+                fn destroyed(&mut self) {
+                    DESTROYED.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_generic() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    class Observer {
+    public:
+        Observer() {}
+        virtual uint32_t foo() const = 0;
+        virtual uint32_t bar() const { return 3; }
+        virtual ~Observer() {}
+    };
+    inline uint32_t call_observer(const Observer& obs) { return obs.foo() + obs.bar(); }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let a = ffi::AnyObserver::new_rust_owned(ffi::AnyObserver::new(Four));
+            assert_eq!(ffi::call_observer(a.borrow().as_ref()), 7);
+            let b = ffi::AnyObserver::new_rust_owned(ffi::AnyObserver::new(Doubler(5)));
+            assert_eq!(ffi::call_observer(b.borrow().as_ref()), 15);
+        },
+        quote! {
+            generate!("call_observer")
+            subclass!("Observer",AnyObserver,generic)
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+            use ffi::AnyObserver_methods;
+            struct Four;
+            impl AnyObserver_methods for Four {
+                fn foo(&self, _peer: &ffi::AnyObserverCpp) -> u32 {
+                    4
+                }
+            }
+            struct Doubler(u32);
+            impl AnyObserver_methods for Doubler {
+                fn foo(&self, _peer: &ffi::AnyObserverCpp) -> u32 {
+                    self.0
+                }
+                fn bar(&self, peer: &ffi::AnyObserverCpp) -> u32 {
+                    self.0 * 2 + peer.bar_super() - 3
+                }
+            }
+        }),
+    );
+}
+
+#[test]
+fn test_pv_subclass_channel_observer() {
+    let hdr = indoc! {"
+    #include <cstdint>
+    class GoatObserver {
+    public:
+        GoatObserver() {}
+        virtual void goat_fed(uint32_t grams) const = 0;
+        virtual void goat_burped() = 0;
+        virtual uint32_t appetite() const { return 3; }
+        virtual ~GoatObserver() {}
+    };
+    inline uint32_t feed_goat(GoatObserver& obs) {
+        uint32_t grams = obs.appetite() * 100;
+        obs.goat_fed(grams);
+        obs.goat_burped();
+        return grams;
+    }
+    "};
+    run_test_ex(
+        "",
+        hdr,
+        quote! {
+            let (obs, events) = ffi::AnyGoatObserver::channel();
+            let obs = ffi::AnyGoatObserver::new_rust_owned(obs);
+            assert_eq!(ffi::feed_goat(obs.borrow_mut().pin_mut()), 300);
+            assert!(matches!(events.try_recv(), Ok(ffi::GoatObserverEvent::GoatFed(300))));
+            assert!(matches!(events.try_recv(), Ok(ffi::GoatObserverEvent::GoatBurped)));
+            assert!(events.try_recv().is_err());
+        },
+        quote! {
+            generate!("feed_goat")
+            subclass!("GoatObserver",AnyGoatObserver,generic)
+            channel_observer!("GoatObserver")
+        },
+        None,
+        None,
+        Some(quote! {
+            use autocxx::subclass::CppSubclass;
+        }),
+    );
+}
+
 #[test]
 fn test_no_constructor_make_unique() {
     let hdr = indoc! {"
@@ -8583,6 +11981,42 @@ fn test_skip_cxx_gen() {
     );
 }
 
+#[test]
+fn test_snapshot() {
+    let cxx = indoc! {"
+        void do_nothing() {
+        }
+    "};
+    let hdr = indoc! {"
+        void do_nothing();
+    "};
+    let snapshot_dir = tempfile::tempdir().unwrap();
+    // First run records the snapshots; second compares against them.
+    for _ in 0..2 {
+        run_test_ex(
+            cxx,
+            hdr,
+            quote! {},
+            directives_from_lists(&["do_nothing"], &[], None),
+            None,
+            Some(make_snapshot_checker(snapshot_dir.path(), "do_nothing")),
+            None,
+        );
+    }
+    assert!(snapshot_dir.path().join("do_nothing.rs.snap").exists());
+    assert!(snapshot_dir.path().join("do_nothing.cpp.snap").exists());
+    std::fs::write(snapshot_dir.path().join("do_nothing.rs.snap"), "mismatch").unwrap();
+    run_test_expect_fail_ex(
+        cxx,
+        hdr,
+        quote! {},
+        directives_from_lists(&["do_nothing"], &[], None),
+        None,
+        Some(make_snapshot_checker(snapshot_dir.path(), "do_nothing")),
+        None,
+    );
+}
+
 #[test]
 /// Tests types with various forms of copy, move, and default constructors. Calls the things which
 /// should be generated, and will produce C++ compile failures if other wrappers are generated.
@@ -104,7 +104,18 @@ pub fn extern_rust_type(attr: TokenStream, input: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn extern_rust_function(attr: TokenStream, input: TokenStream) -> TokenStream {
     if !attr.is_empty() {
-        abort!(Span::call_site(), "Expected no attributes");
+        let attr: Ident = syn::parse(attr).unwrap_or_else(|_| {
+            abort!(
+                Span::call_site(),
+                "Expected no attributes, 'expected' or 'factory'"
+            )
+        });
+        if attr != "expected" && attr != "factory" {
+            abort!(
+                attr.span(),
+                "Expected no attributes, 'expected' or 'factory'"
+            );
+        }
     }
     let i: Item =
         syn::parse(input.clone()).unwrap_or_else(|_| abort!(Span::call_site(), "Expected an item"));
@@ -0,0 +1,320 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which choose the items to generate, or not: `generate!`
+//! and its variants, `pod!`, `concrete!` and `block!` and its variants.
+
+use quote::ToTokens;
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{expect_no_args, Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "generate",
+        parse: parse_generate,
+    },
+    Directive {
+        name: "generate_if",
+        parse: parse_generate_if,
+    },
+    Directive {
+        name: "generate_pod",
+        parse: parse_generate_pod,
+    },
+    Directive {
+        name: "generate_pod_recursive",
+        parse: parse_generate_pod_recursive,
+    },
+    Directive {
+        name: "force_pod_unsafe",
+        parse: parse_force_pod_unsafe,
+    },
+    Directive {
+        name: "pod",
+        parse: parse_pod,
+    },
+    Directive {
+        name: "concrete",
+        parse: parse_concrete,
+    },
+    Directive {
+        name: "generate_from_header",
+        parse: parse_generate_from_header,
+    },
+    Directive {
+        name: "generate_all",
+        parse: parse_generate_all,
+    },
+    Directive {
+        name: "block",
+        parse: parse_block,
+    },
+    Directive {
+        name: "block_ns",
+        parse: parse_block_ns,
+    },
+];
+
+/// Allowlist configuration.
+#[derive(Hash, Debug)]
+pub enum Allowlist {
+    Unspecified(Vec<String>),
+    All,
+    Specific(Vec<String>),
+}
+
+impl Allowlist {
+    pub fn push(&mut self, item: LitStr) -> ParseResult<()> {
+        match self {
+            Allowlist::Unspecified(ref mut uncommitted_list) => {
+                let new_list = uncommitted_list
+                    .drain(..)
+                    .chain(std::iter::once(item.value()))
+                    .collect();
+                *self = Allowlist::Specific(new_list);
+            }
+            Allowlist::All => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "use either generate!/generate_pod! or generate_all!, not both.",
+                ))
+            }
+            Allowlist::Specific(list) => list.push(item.value()),
+        };
+        Ok(())
+    }
+
+    pub(crate) fn set_all(&mut self, ident: &Ident) -> ParseResult<()> {
+        if matches!(self, Allowlist::Specific(..)) {
+            return Err(syn::Error::new(
+                ident.span(),
+                "use either generate!/generate_pod! or generate_all!, not both.",
+            ));
+        }
+        *self = Allowlist::All;
+        Ok(())
+    }
+
+    /// Commits to generating specific items, without naming any yet, as
+    /// for `generate_from_header!`.
+    pub(crate) fn set_specific(&mut self, ident: &Ident) -> ParseResult<()> {
+        match self {
+            Allowlist::Unspecified(ref mut uncommitted_list) => {
+                *self = Allowlist::Specific(std::mem::take(uncommitted_list));
+            }
+            Allowlist::All => {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "use either generate_from_header! or generate_all!, not both.",
+                ))
+            }
+            Allowlist::Specific(_) => {}
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::derivable_impls)] // nightly-only
+impl Default for Allowlist {
+    fn default() -> Self {
+        Allowlist::Unspecified(Vec::new())
+    }
+}
+
+/// Parses the item to generate, and any `doc = "..."` which follows it,
+/// for `generate!` and its variants.
+fn parse_generate_args(state: &mut ParseState, args: ParseStream) -> ParseResult<LitStr> {
+    let generate: LitStr = args.parse()?;
+    if let Some(doc) = parse_optional_doc(args)? {
+        state.config.docs.push((generate.value(), doc));
+    }
+    Ok(generate)
+}
+
+fn parse_generate(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let generate = parse_generate_args(state, args)?;
+    state.config.allowlist.push(generate)
+}
+
+fn parse_generate_if(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let feature: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let generate: LitStr = args.parse()?;
+    state.config.cfgs.push((
+        generate.value(),
+        format!("feature = {}", feature.to_token_stream()),
+    ));
+    state.config.allowlist.push(generate)
+}
+
+fn parse_generate_pod(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let generate_pod = parse_generate_args(state, args)?;
+    state.config.pod_requests.push(generate_pod.value());
+    state.config.allowlist.push(generate_pod)
+}
+
+fn parse_generate_pod_recursive(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let generate_pod = parse_generate_args(state, args)?;
+    state.config.pod_requests.push(generate_pod.value());
+    state
+        .config
+        .pod_recursive_requests
+        .push(generate_pod.value());
+    state.config.allowlist.push(generate_pod)
+}
+
+fn parse_force_pod_unsafe(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let generate_pod = parse_generate_args(state, args)?;
+    state.config.pod_requests.push(generate_pod.value());
+    state
+        .config
+        .force_pod_unsafe_requests
+        .push(generate_pod.value());
+    state.config.allowlist.push(generate_pod)
+}
+
+fn parse_pod(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let pod: LitStr = args.parse()?;
+    state.config.pod_requests.push(pod.value());
+    Ok(())
+}
+
+fn parse_concrete(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let cpp_type: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let rust_id: Ident = args.parse()?;
+    state
+        .config
+        .allowlist
+        .push(LitStr::new(&rust_id.to_string(), rust_id.span()))?;
+    state.config.concretes.push((cpp_type.value(), rust_id));
+    Ok(())
+}
+
+fn parse_generate_from_header(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    let header: LitStr = args.parse()?;
+    if header.value().is_empty() {
+        return Err(syn::Error::new(header.span(), "expected a header name"));
+    }
+    state.config.allowlist.set_specific(ident)?;
+    state.config.headers_to_generate.push(header.value());
+    Ok(())
+}
+
+fn parse_generate_all(state: &mut ParseState, args: ParseStream, ident: &Ident) -> ParseResult<()> {
+    state.config.allowlist.set_all(ident)?;
+    expect_no_args(args, ident)
+}
+
+fn parse_block(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let generate: LitStr = args.parse()?;
+    state.config.blocklist.push(generate.value());
+    Ok(())
+}
+
+fn parse_block_ns(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let ns: LitStr = args.parse()?;
+    let ns_value = ns.value();
+    let is_identifier = |seg: &str| {
+        !seg.is_empty()
+            && !seg.starts_with(|c: char| c.is_ascii_digit())
+            && seg.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    if !ns_value.split("::").all(is_identifier) {
+        return Err(syn::Error::new(
+            ns.span(),
+            "expected a C++ namespace such as \"mylib::internal\"",
+        ));
+    }
+    state.config.blocked_namespaces.push(ns_value);
+    Ok(())
+}
+
+/// Parses the `doc = "..."` which may follow the item name in
+/// `generate!`, `generate_pod!` and `generate_pod_recursive!`.
+fn parse_optional_doc(args: ParseStream) -> ParseResult<Option<String>> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    args.parse::<syn::token::Comma>()?;
+    let key: Ident = args.parse()?;
+    if key != "doc" {
+        return Err(syn::Error::new(key.span(), "expected doc = \"...\""));
+    }
+    args.parse::<syn::token::Eq>()?;
+    let doc: LitStr = args.parse()?;
+    Ok(Some(doc.value()))
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for i in &config.pod_requests {
+        if !config.pod_recursive_requests.contains(i)
+            && !config.force_pod_unsafe_requests.contains(i)
+        {
+            tokens.extend(quote! { pod!(#i) });
+        }
+    }
+    for i in &config.blocklist {
+        tokens.extend(quote! { block!(#i) });
+    }
+    for i in &config.blocked_namespaces {
+        tokens.extend(quote! { block_ns!(#i) });
+    }
+    for i in &config.headers_to_generate {
+        tokens.extend(quote! { generate_from_header!(#i) });
+    }
+    match &config.allowlist {
+        Allowlist::All => tokens.extend(quote! { generate_all!() }),
+        Allowlist::Specific(items) => {
+            for i in items {
+                if let Some((cpp_type, rust_id)) =
+                    config.concretes.iter().find(|(_, rust_id)| rust_id == i)
+                {
+                    tokens.extend(quote! { concrete!(#cpp_type, #rust_id) });
+                    continue;
+                }
+                if config.pod_recursive_requests.contains(i) {
+                    match config.get_doc(i) {
+                        Some(doc) => {
+                            tokens.extend(quote! { generate_pod_recursive!(#i, doc = #doc) })
+                        }
+                        None => tokens.extend(quote! { generate_pod_recursive!(#i) }),
+                    }
+                    continue;
+                }
+                if config.force_pod_unsafe_requests.contains(i) {
+                    match config.get_doc(i) {
+                        Some(doc) => tokens.extend(quote! { force_pod_unsafe!(#i, doc = #doc) }),
+                        None => tokens.extend(quote! { force_pod_unsafe!(#i) }),
+                    }
+                    continue;
+                }
+                match config.get_doc(i) {
+                    Some(doc) => tokens.extend(quote! { generate!(#i, doc = #doc) }),
+                    None => tokens.extend(quote! { generate!(#i) }),
+                }
+            }
+        }
+        Allowlist::Unspecified(_) => panic!("Allowlist mode not yet determined"),
+    }
+}
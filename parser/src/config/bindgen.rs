@@ -0,0 +1,136 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which are passed more or less directly to bindgen:
+//! `bindgen_opaque_type!`, `bindgen_blocklist_file!` and `bindgen_derive!`.
+
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "bindgen_opaque_type",
+        parse: parse_bindgen_opaque_type,
+    },
+    Directive {
+        name: "bindgen_blocklist_file",
+        parse: parse_bindgen_blocklist_file,
+    },
+    Directive {
+        name: "bindgen_derive",
+        parse: parse_bindgen_derive,
+    },
+];
+
+/// A trait which bindgen may derive for POD types, requested using
+/// `bindgen_derive!`. `Copy` (and therefore `Clone`) is deliberately absent:
+/// autocxx must respect C++ copy constructors, so never lets bindgen
+/// derive those.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum BindgenDerive {
+    Debug,
+    Default,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+}
+
+impl BindgenDerive {
+    const ALL: [BindgenDerive; 7] = [
+        BindgenDerive::Debug,
+        BindgenDerive::Default,
+        BindgenDerive::Hash,
+        BindgenDerive::PartialEq,
+        BindgenDerive::Eq,
+        BindgenDerive::PartialOrd,
+        BindgenDerive::Ord,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BindgenDerive::Debug => "Debug",
+            BindgenDerive::Default => "Default",
+            BindgenDerive::Hash => "Hash",
+            BindgenDerive::PartialEq => "PartialEq",
+            BindgenDerive::Eq => "Eq",
+            BindgenDerive::PartialOrd => "PartialOrd",
+            BindgenDerive::Ord => "Ord",
+        }
+    }
+
+    fn from_lit(lit: &LitStr) -> ParseResult<Self> {
+        let name = lit.value();
+        if let Some(derive) = Self::ALL.iter().find(|derive| derive.name() == name) {
+            return Ok(*derive);
+        }
+        let msg = if name == "Copy" || name == "Clone" {
+            "bindgen can't be asked to derive Copy or Clone, since autocxx must call C++ copy constructors".to_string()
+        } else {
+            format!(
+                "expected one of {}",
+                Self::ALL
+                    .iter()
+                    .map(BindgenDerive::name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        };
+        Err(syn::Error::new(lit.span(), msg))
+    }
+}
+
+fn parse_bindgen_opaque_type(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    // Checked against the POD requests once all directives are parsed.
+    state.bindgen_opaque_types.push(args.parse()?);
+    Ok(())
+}
+
+fn parse_bindgen_blocklist_file(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let pattern: LitStr = args.parse()?;
+    state.config.bindgen_blocklisted_files.push(pattern.value());
+    Ok(())
+}
+
+fn parse_bindgen_derive(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let derive: LitStr = args.parse()?;
+    state
+        .config
+        .bindgen_derives
+        .push(BindgenDerive::from_lit(&derive)?);
+    Ok(())
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for i in &config.bindgen_opaque_types {
+        tokens.extend(quote! { bindgen_opaque_type!(#i) });
+    }
+    for i in &config.bindgen_blocklisted_files {
+        tokens.extend(quote! { bindgen_blocklist_file!(#i) });
+    }
+    for i in &config.bindgen_derives {
+        let name = i.name();
+        tokens.extend(quote! { bindgen_derive!(#name) });
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives concerning the problems reported while generating bindings:
+//! `silence_error!`, `expect_ignored!` and `strict!`.
+
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{expect_no_args, Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "silence_error",
+        parse: parse_silence_error,
+    },
+    Directive {
+        name: "expect_ignored",
+        parse: parse_expect_ignored,
+    },
+    Directive {
+        name: "strict",
+        parse: parse_strict,
+    },
+];
+
+fn parse_silence_error(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let code: LitStr = args.parse()?;
+    if !is_valid_error_code(&code.value()) {
+        return Err(syn::Error::new(
+            code.span(),
+            "expected an autocxx error code such as \"ACX0036\"",
+        ));
+    }
+    state
+        .config
+        .silenced_errors
+        .push(code.value().to_ascii_uppercase());
+    Ok(())
+}
+
+fn parse_expect_ignored(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let item: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let reason: LitStr = args.parse()?;
+    state
+        .config
+        .expected_ignored
+        .push((item.value(), reason.value()));
+    Ok(())
+}
+
+fn parse_strict(state: &mut ParseState, args: ParseStream, ident: &Ident) -> ParseResult<()> {
+    state.config.strict = true;
+    expect_no_args(args, ident)
+}
+
+/// Whether this looks like the code of a `ConvertError` in autocxx-engine,
+/// e.g. `ACX0036`.
+fn is_valid_error_code(code: &str) -> bool {
+    code.len() == 7
+        && code.is_ascii()
+        && code[..3].eq_ignore_ascii_case("ACX")
+        && code[3..].bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for code in &config.silenced_errors {
+        tokens.extend(quote! { silence_error!(#code) });
+    }
+    for (item, reason) in &config.expected_ignored {
+        tokens.extend(quote! { expect_ignored!(#item, #reason) });
+    }
+    if config.strict {
+        tokens.extend(quote! { strict!() });
+    }
+}
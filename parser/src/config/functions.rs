@@ -0,0 +1,410 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which change how particular C++ functions are bound, or how
+//! functions are bound in general: `block_constructors!`, `batch!`,
+//! `string_returns!`, `exception_policy!` and so on.
+
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use proc_macro2::Span;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "block_constructors",
+        parse: parse_block_constructors,
+    },
+    Directive {
+        name: "canonical_constructor",
+        parse: parse_canonical_constructor,
+    },
+    Directive {
+        name: "init_function",
+        parse: parse_init_function,
+    },
+    Directive {
+        name: "shutdown_function",
+        parse: parse_shutdown_function,
+    },
+    Directive {
+        name: "batch",
+        parse: parse_batch,
+    },
+    Directive {
+        name: "thin_call",
+        parse: parse_thin_call,
+    },
+    Directive {
+        name: "treat_as_const",
+        parse: parse_treat_as_const,
+    },
+    Directive {
+        name: "shared_ref_variant",
+        parse: parse_shared_ref_variant,
+    },
+    Directive {
+        name: "returns_owned",
+        parse: parse_returns_owned,
+    },
+    Directive {
+        name: "consumes",
+        parse: parse_consumes,
+    },
+    Directive {
+        name: "associate_fn",
+        parse: parse_associate_fn,
+    },
+    Directive {
+        name: "string_returns",
+        parse: parse_string_returns,
+    },
+    Directive {
+        name: "exception_policy",
+        parse: parse_exception_policy,
+    },
+    Directive {
+        name: "deprecation_policy",
+        parse: parse_deprecation_policy,
+    },
+];
+
+/// How returned C++ strings should be presented to Rust, chosen using
+/// `string_returns!`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StringReturns {
+    /// `&CxxString` becomes `&str`, and a `std::string` returned by value
+    /// becomes `String`. Panics if the string isn't valid UTF-8.
+    Str,
+    /// `&CxxString` becomes `Cow<str>`, and a `std::string` returned by
+    /// value becomes `String`. Invalid UTF-8 is replaced.
+    Cow,
+}
+
+impl StringReturns {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        let key: Ident = args.parse()?;
+        if key == "str" {
+            Ok(Self::Str)
+        } else if key == "cow" {
+            Ok(Self::Cow)
+        } else {
+            Err(syn::Error::new(key.span(), "expected str or cow"))
+        }
+    }
+
+    #[cfg(feature = "reproduction_case")]
+    fn key(&self) -> Ident {
+        match self {
+            Self::Str => Ident::new("str", Span::call_site()),
+            Self::Cow => Ident::new("cow", Span::call_site()),
+        }
+    }
+}
+
+/// What happens when a C++ exception or a Rust panic reaches one of the
+/// `extern` functions which autocxx generates itself, rather than through
+/// cxx, chosen using `exception_policy!`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum ExceptionPolicy {
+    /// The functions are `extern "C"` on the Rust side and `noexcept` on
+    /// the C++ side, so the program is terminated.
+    #[default]
+    Terminate,
+    /// The functions are `extern "C-unwind"` on the Rust side, and not
+    /// `noexcept` on the C++ side, so the exception or panic unwinds
+    /// through them to the caller.
+    Propagate,
+}
+
+impl ExceptionPolicy {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        let key: Ident = args.parse()?;
+        if key == "terminate" {
+            Ok(Self::Terminate)
+        } else if key == "propagate" {
+            Ok(Self::Propagate)
+        } else {
+            Err(syn::Error::new(
+                key.span(),
+                "expected terminate or propagate",
+            ))
+        }
+    }
+
+    #[cfg(feature = "reproduction_case")]
+    fn key(&self) -> Ident {
+        match self {
+            Self::Terminate => Ident::new("terminate", Span::call_site()),
+            Self::Propagate => Ident::new("propagate", Span::call_site()),
+        }
+    }
+}
+
+/// What happens to C++ functions marked `[[deprecated]]`, chosen using
+/// `deprecation_policy!`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum DeprecationPolicy {
+    /// No bindings are generated for them.
+    Skip,
+    /// Bindings are generated, marked `#[deprecated]`, so that Rust code
+    /// which uses them gets a warning.
+    Warn,
+    /// Bindings are generated as for any other function.
+    #[default]
+    Generate,
+}
+
+impl DeprecationPolicy {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        let key: Ident = args.parse()?;
+        if key == "skip" {
+            Ok(Self::Skip)
+        } else if key == "warn" {
+            Ok(Self::Warn)
+        } else if key == "generate" {
+            Ok(Self::Generate)
+        } else {
+            Err(syn::Error::new(
+                key.span(),
+                "expected skip, warn or generate",
+            ))
+        }
+    }
+
+    #[cfg(feature = "reproduction_case")]
+    fn key(&self) -> Ident {
+        match self {
+            Self::Skip => Ident::new("skip", Span::call_site()),
+            Self::Warn => Ident::new("warn", Span::call_site()),
+            Self::Generate => Ident::new("generate", Span::call_site()),
+        }
+    }
+}
+
+fn parse_block_constructors(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let generate: LitStr = args.parse()?;
+    if generate.value().contains('(') && split_constructor_signature(&generate.value()).is_none() {
+        return Err(syn::Error::new(
+            generate.span(),
+            "expected a type such as \"Foo\", or a constructor signature such as \"Foo(int, const std::string&)\"",
+        ));
+    }
+    state.config.constructor_blocklist.push(generate.value());
+    Ok(())
+}
+
+fn parse_canonical_constructor(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let signature: LitStr = args.parse()?;
+    if split_constructor_signature(&signature.value()).is_none() {
+        return Err(syn::Error::new(
+            signature.span(),
+            "expected a constructor signature such as \"Foo(int, const std::string&)\"",
+        ));
+    }
+    state.config.canonical_constructors.push(signature.value());
+    Ok(())
+}
+
+fn parse_init_function(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let init_function: LitStr = args.parse()?;
+    state.config.init_functions.push(init_function.value());
+    Ok(())
+}
+
+fn parse_shutdown_function(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let shutdown_function: LitStr = args.parse()?;
+    state
+        .config
+        .shutdown_functions
+        .push(shutdown_function.value());
+    Ok(())
+}
+
+fn parse_batch(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let function: LitStr = args.parse()?;
+    state.config.batched_functions.push(function.value());
+    Ok(())
+}
+
+fn parse_thin_call(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let method: LitStr = args.parse()?;
+    state.config.thin_calls.push(method.value());
+    Ok(())
+}
+
+fn parse_treat_as_const(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let method: LitStr = args.parse()?;
+    state.config.treated_as_const.push(method.value());
+    Ok(())
+}
+
+fn parse_shared_ref_variant(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let function: LitStr = args.parse()?;
+    state.config.shared_ref_variants.push(function.value());
+    Ok(())
+}
+
+fn parse_returns_owned(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let function: LitStr = args.parse()?;
+    state.config.owned_returns.push(function.value());
+    Ok(())
+}
+
+fn parse_consumes(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let function: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let key: Ident = args.parse()?;
+    if key != "arg" {
+        return Err(syn::Error::new(key.span(), "expected arg = <number>"));
+    }
+    args.parse::<syn::token::Eq>()?;
+    let arg: syn::LitInt = args.parse()?;
+    let arg: usize = arg.base10_parse()?;
+    if arg == 0 {
+        return Err(syn::Error::new(
+            key.span(),
+            "parameters are numbered from 1",
+        ));
+    }
+    state.config.consumed_args.push((function.value(), arg));
+    Ok(())
+}
+
+fn parse_associate_fn(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let function: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let target: LitStr = args.parse()?;
+    if split_associated_fn_target(&target.value()).is_none() {
+        return Err(syn::Error::new(
+            target.span(),
+            "expected a type and function name such as \"Widget::create\"",
+        ));
+    }
+    state
+        .config
+        .associated_fns
+        .push((function.value(), target.value()));
+    Ok(())
+}
+
+fn parse_string_returns(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    state.config.string_returns = Some(StringReturns::parse_args(args)?);
+    Ok(())
+}
+
+fn parse_exception_policy(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    state.config.exception_policy = ExceptionPolicy::parse_args(args)?;
+    Ok(())
+}
+
+fn parse_deprecation_policy(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    state.config.deprecation_policy = DeprecationPolicy::parse_args(args)?;
+    Ok(())
+}
+
+/// Splits a constructor signature such as `Foo(int, const std::string&)`
+/// into the type name and the normalized parameter list.
+pub(super) fn split_constructor_signature(signature: &str) -> Option<(&str, String)> {
+    let (ty, params) = signature.trim().split_once('(')?;
+    let params = params.strip_suffix(')')?;
+    Some((ty.trim(), normalize_signature(params)))
+}
+
+/// Splits the target of an `associate_fn!`, such as `ns::Widget::create`,
+/// into the type and the name of the function to generate within it.
+pub(super) fn split_associated_fn_target(target: &str) -> Option<(&str, &str)> {
+    let (ty, function) = target.trim().rsplit_once("::")?;
+    if ty.is_empty() || syn::parse_str::<Ident>(function).is_err() {
+        None
+    } else {
+        Some((ty, function))
+    }
+}
+
+/// Removes all whitespace, so that signatures can be compared
+/// irrespective of formatting.
+pub(super) fn normalize_signature(params: &str) -> String {
+    params.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for i in &config.constructor_blocklist {
+        tokens.extend(quote! { block_constructors!(#i) });
+    }
+    for i in &config.canonical_constructors {
+        tokens.extend(quote! { canonical_constructor!(#i) });
+    }
+    for i in &config.init_functions {
+        tokens.extend(quote! { init_function!(#i) });
+    }
+    for i in &config.shutdown_functions {
+        tokens.extend(quote! { shutdown_function!(#i) });
+    }
+    for i in &config.batched_functions {
+        tokens.extend(quote! { batch!(#i) });
+    }
+    for i in &config.thin_calls {
+        tokens.extend(quote! { thin_call!(#i) });
+    }
+    for i in &config.treated_as_const {
+        tokens.extend(quote! { treat_as_const!(#i) });
+    }
+    for i in &config.shared_ref_variants {
+        tokens.extend(quote! { shared_ref_variant!(#i) });
+    }
+    for i in &config.owned_returns {
+        tokens.extend(quote! { returns_owned!(#i) });
+    }
+    for (function, arg) in &config.consumed_args {
+        let arg = syn::LitInt::new(&arg.to_string(), Span::call_site());
+        tokens.extend(quote! { consumes!(#function, arg = #arg) });
+    }
+    for (function, target) in &config.associated_fns {
+        tokens.extend(quote! { associate_fn!(#function, #target) });
+    }
+    if let Some(string_returns) = &config.string_returns {
+        let key = string_returns.key();
+        tokens.extend(quote! { string_returns!(#key) });
+    }
+    if config.exception_policy != ExceptionPolicy::default() {
+        let key = config.exception_policy.key();
+        tokens.extend(quote! { exception_policy!(#key) });
+    }
+    if config.deprecation_policy != DeprecationPolicy::default() {
+        let key = config.deprecation_policy.key();
+        tokens.extend(quote! { deprecation_policy!(#key) });
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which affect the generated code as a whole: `safety!`,
+//! `prelude!`, `cpp_standard!`, `cfg!` and so on.
+
+use proc_macro2::Span;
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitStr, Result as ParseResult, Token,
+};
+
+use super::{expect_no_args, Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::{quote, ToTokens};
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "safety",
+        parse: parse_safety,
+    },
+    Directive {
+        name: "parse_only",
+        parse: parse_parse_only,
+    },
+    Directive {
+        name: "exclude_impls",
+        parse: parse_exclude_impls,
+    },
+    Directive {
+        name: "exclude_utilities",
+        parse: parse_exclude_utilities,
+    },
+    Directive {
+        name: "prelude",
+        parse: parse_prelude,
+    },
+    Directive {
+        name: "include_priority",
+        parse: parse_include_priority,
+    },
+    Directive {
+        name: "cpp_standard",
+        parse: parse_cpp_standard,
+    },
+    Directive {
+        name: "internal_linkage_shims",
+        parse: parse_internal_linkage_shims,
+    },
+    Directive {
+        name: "cfg",
+        parse: parse_cfg,
+    },
+];
+
+#[derive(PartialEq, Clone, Debug, Hash, Default)]
+pub enum UnsafePolicy {
+    AllFunctionsSafe,
+    #[default]
+    AllFunctionsUnsafe,
+}
+
+impl Parse for UnsafePolicy {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        if input.parse::<Option<Token![unsafe]>>()?.is_some() {
+            return Ok(UnsafePolicy::AllFunctionsSafe);
+        }
+        let r = match input.parse::<Option<syn::Ident>>()? {
+            Some(id) => {
+                if id == "unsafe_ffi" {
+                    Ok(UnsafePolicy::AllFunctionsSafe)
+                } else {
+                    Err(syn::Error::new(id.span(), "expected unsafe_ffi"))
+                }
+            }
+            None => Ok(UnsafePolicy::AllFunctionsUnsafe),
+        };
+        if !input.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "unexpected tokens within safety directive",
+            ));
+        }
+        r
+    }
+}
+
+#[cfg(feature = "reproduction_case")]
+impl ToTokens for UnsafePolicy {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        if *self == UnsafePolicy::AllFunctionsSafe {
+            tokens.extend(quote! { unsafe })
+        }
+    }
+}
+
+fn parse_safety(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    state.config.unsafe_policy = args.parse()?;
+    Ok(())
+}
+
+fn parse_parse_only(state: &mut ParseState, args: ParseStream, ident: &Ident) -> ParseResult<()> {
+    state.config.parse_only = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_exclude_impls(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.exclude_impls = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_exclude_utilities(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.exclude_utilities = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_prelude(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let prelude: LitStr = args.parse()?;
+    state.config.preludes.push(prelude.value());
+    Ok(())
+}
+
+fn parse_include_priority(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let hdr: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let priority: syn::LitInt = args.parse()?;
+    state
+        .config
+        .include_priorities
+        .push((hdr.value(), priority.base10_parse()?));
+    Ok(())
+}
+
+fn parse_cpp_standard(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let standard: LitStr = args.parse()?;
+    if !is_supported_cpp_standard(&standard.value()) {
+        return Err(syn::Error::new(
+            standard.span(),
+            "expected a C++ standard supported by autocxx (C++14 or later), e.g. \"c++17\" or \"gnu++20\"",
+        ));
+    }
+    state.config.cpp_standard = Some(standard.value());
+    Ok(())
+}
+
+fn parse_internal_linkage_shims(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.internal_linkage_shims = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_cfg(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let item: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let predicate: LitStr = args.parse()?;
+    if syn::parse_str::<syn::NestedMeta>(&predicate.value()).is_err() {
+        return Err(syn::Error::new(
+            predicate.span(),
+            "expected a cfg predicate, e.g. \"windows\"",
+        ));
+    }
+    state.config.cfgs.push((item.value(), predicate.value()));
+    Ok(())
+}
+
+/// Whether this is a value for `-std` which selects C++14 or later.
+pub fn is_supported_cpp_standard(standard: &str) -> bool {
+    let version = standard
+        .strip_prefix("c++")
+        .or_else(|| standard.strip_prefix("gnu++"));
+    matches!(
+        version,
+        Some("14" | "1y" | "17" | "1z" | "20" | "2a" | "23" | "2b")
+    )
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    let unsafety = &config.unsafe_policy;
+    tokens.extend(quote! {
+        safety!(#unsafety)
+    });
+    if config.exclude_impls {
+        tokens.extend(quote! { exclude_impls!() });
+    }
+    if config.parse_only {
+        tokens.extend(quote! { parse_only!() });
+    }
+    if config.exclude_utilities {
+        tokens.extend(quote! { exclude_utilities!() });
+    }
+    for i in &config.preludes {
+        tokens.extend(quote! { prelude!(#i) });
+    }
+    for (hdr, priority) in &config.include_priorities {
+        let priority = proc_macro2::Literal::i32_unsuffixed(*priority);
+        tokens.extend(quote! { include_priority!(#hdr, #priority) });
+    }
+    if let Some(standard) = &config.cpp_standard {
+        tokens.extend(quote! { cpp_standard!(#standard) });
+    }
+    if config.internal_linkage_shims {
+        tokens.extend(quote! { internal_linkage_shims!() });
+    }
+    for (item, predicate) in &config.cfgs {
+        tokens.extend(quote! { cfg!(#item, #predicate) });
+    }
+}
@@ -0,0 +1,1992 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The configuration given within `include_cpp!`. Each directive is parsed
+//! by a function found in the table of directives for its family, in one of
+//! the submodules.
+
+mod allowlist;
+mod bindgen;
+mod diagnostics;
+mod functions;
+mod general;
+mod naming;
+mod rust;
+mod types;
+
+use std::{borrow::Cow, collections::HashSet, fmt::Display};
+
+use proc_macro2::{Span, TokenTree};
+use syn::{
+    parse::{Parse, ParseStream},
+    LitStr, Token,
+};
+use syn::{Ident, Result as ParseResult};
+
+use crate::RustPath;
+
+use self::functions::{
+    normalize_signature, split_associated_fn_target, split_constructor_signature,
+};
+pub use self::{
+    allowlist::Allowlist,
+    bindgen::BindgenDerive,
+    functions::{DeprecationPolicy, ExceptionPolicy, StringReturns},
+    general::{is_supported_cpp_standard, UnsafePolicy},
+    naming::CURRENT_MANGLING_VERSION,
+    rust::{RustEnum, RustFun, Subclass},
+    types::{LayoutOverride, QtSignal, RefCounting, TraitImpl},
+};
+
+#[cfg(feature = "reproduction_case")]
+use quote::{quote, ToTokens};
+
+/// A header to be included, given using `#include` within `include_cpp!`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inclusion {
+    pub path: String,
+    /// Whether this was given as `#include <path>`, rather than
+    /// `#include "path"`, so is a system or third-party header.
+    pub system: bool,
+}
+
+impl Inclusion {
+    fn parse_path(input: ParseStream) -> ParseResult<Self> {
+        if input.parse::<Option<Token![<]>>()?.is_none() {
+            let path: LitStr = input.parse()?;
+            return Ok(Self {
+                path: path.value(),
+                system: false,
+            });
+        }
+        // The path is made of several tokens, e.g. opencv2, /, opencv, ., hpp
+        let mut path = String::new();
+        while input.parse::<Option<Token![>]>>()?.is_none() {
+            path.push_str(&input.parse::<TokenTree>()?.to_string());
+        }
+        Ok(Self { path, system: true })
+    }
+}
+
+impl Display for Inclusion {
+    /// The path as it would appear after `#include`, with its quotes or
+    /// angle brackets.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.system {
+            write!(f, "<{}>", self.path)
+        } else {
+            write!(f, "\"{}\"", self.path)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IncludeCppConfig {
+    pub inclusions: Vec<Inclusion>,
+    pub unsafe_policy: UnsafePolicy,
+    pub parse_only: bool,
+    pub exclude_impls: bool,
+    pod_requests: Vec<String>,
+    pod_recursive_requests: Vec<String>,
+    force_pod_unsafe_requests: Vec<String>,
+    pub allowlist: Allowlist,
+    blocklist: Vec<String>,
+    blocked_namespaces: Vec<String>,
+    headers_to_generate: Vec<String>,
+    /// Items found to be declared in `headers_to_generate`, once bindgen
+    /// has told us where each item was declared.
+    items_from_headers: Vec<String>,
+    constructor_blocklist: Vec<String>,
+    exclude_utilities: bool,
+    mod_name: Option<Ident>,
+    pub rust_types: Vec<RustPath>,
+    pub subclasses: Vec<Subclass>,
+    pub extern_rust_funs: Vec<RustFun>,
+    pub extern_rust_enums: Vec<RustEnum>,
+    extern_rust_namespace: Option<String>,
+    extern_rust_class: Option<String>,
+    mocks: Vec<String>,
+    channel_observers: Vec<String>,
+    mangling_version: Option<u32>,
+    preludes: Vec<String>,
+    include_priorities: Vec<(String, i32)>,
+    opaque_types: Vec<String>,
+    extern_cpp_types: Vec<(String, RustPath)>,
+    mirror_types: Vec<(String, RustPath)>,
+    concretes: Vec<(String, Ident)>,
+    field_metadata: Vec<String>,
+    init_functions: Vec<String>,
+    shutdown_functions: Vec<String>,
+    raii_types: Vec<String>,
+    batched_functions: Vec<String>,
+    thin_calls: Vec<String>,
+    treated_as_const: Vec<String>,
+    shared_ref_variants: Vec<String>,
+    owned_returns: Vec<String>,
+    consumed_args: Vec<(String, usize)>,
+    associated_fns: Vec<(String, String)>,
+    canonical_constructors: Vec<String>,
+    instantiable_types: Vec<String>,
+    opaque_only_types: Vec<String>,
+    bindgen_opaque_types: Vec<String>,
+    bindgen_blocklisted_files: Vec<String>,
+    bindgen_derives: Vec<BindgenDerive>,
+    cfgs: Vec<(String, String)>,
+    namespace_mappings: Vec<(String, String)>,
+    flatten_namespaces: bool,
+    snake_case_methods: bool,
+    property_accessors: bool,
+    helper_names: Vec<(String, String)>,
+    docs: Vec<(String, String)>,
+    layout_overrides: Vec<(String, LayoutOverride)>,
+    qt_signals: Vec<QtSignal>,
+    trait_impls: Vec<TraitImpl>,
+    ref_countings: Vec<RefCounting>,
+    silenced_errors: Vec<String>,
+    expected_ignored: Vec<(String, String)>,
+    strict: bool,
+    internal_linkage_shims: bool,
+    cpp_standard: Option<String>,
+    string_returns: Option<StringReturns>,
+    exception_policy: ExceptionPolicy,
+    deprecation_policy: DeprecationPolicy,
+}
+
+/// A directive which may be given within `include_cpp!`, such as
+/// `generate!`, and the function which parses the arguments within its
+/// parentheses into the config. The identifier naming the directive is
+/// passed too, for use in error messages.
+struct Directive {
+    name: &'static str,
+    parse: fn(&mut ParseState, ParseStream, &Ident) -> ParseResult<()>,
+}
+
+/// The tables of directives, one for each family.
+static DIRECTIVE_FAMILIES: &[&[Directive]] = &[
+    general::DIRECTIVES,
+    allowlist::DIRECTIVES,
+    functions::DIRECTIVES,
+    types::DIRECTIVES,
+    rust::DIRECTIVES,
+    naming::DIRECTIVES,
+    bindgen::DIRECTIVES,
+    diagnostics::DIRECTIVES,
+];
+
+fn find_directive(ident: &Ident) -> Option<&'static Directive> {
+    DIRECTIVE_FAMILIES
+        .iter()
+        .flat_map(|directives| directives.iter())
+        .find(|directive| ident == directive.name)
+}
+
+/// The config as it's being parsed, along with those directives which can
+/// only be checked once all have been parsed, kept as given so that any
+/// problems can be reported against them.
+#[derive(Default)]
+struct ParseState {
+    config: IncludeCppConfig,
+    /// Given using `bindgen_opaque_type!`.
+    bindgen_opaque_types: Vec<LitStr>,
+    /// Given using `channel_observer!`.
+    channel_observers: Vec<LitStr>,
+}
+
+impl ParseState {
+    fn finish(self) -> ParseResult<IncludeCppConfig> {
+        let mut config = self.config;
+        // bindgen turns opaque types into blobs of bytes, which we can't
+        // then represent as POD types.
+        if let Some(pattern) = self
+            .bindgen_opaque_types
+            .iter()
+            .find(|pattern| config.pod_requests.contains(&pattern.value()))
+        {
+            return Err(syn::Error::new(
+                pattern.span(),
+                "this type was also requested using generate_pod!, but types which bindgen makes opaque can't be POD",
+            ));
+        }
+        // The events are delivered by a generated generic subclass.
+        if let Some(superclass) = self.channel_observers.iter().find(|superclass| {
+            !config
+                .subclasses
+                .iter()
+                .any(|sc| sc.generic && sc.superclass == superclass.value())
+        }) {
+            return Err(syn::Error::new(
+                superclass.span(),
+                "channel_observer! requires this class to also be used in subclass!(..., generic)",
+            ));
+        }
+        config.channel_observers = self
+            .channel_observers
+            .into_iter()
+            .map(|superclass| superclass.value())
+            .collect();
+        config.bindgen_opaque_types = self
+            .bindgen_opaque_types
+            .into_iter()
+            .map(|pattern| pattern.value())
+            .collect();
+        Ok(config)
+    }
+}
+
+impl Parse for IncludeCppConfig {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        // Takes as inputs:
+        // 1. List of headers to include
+        // 2. List of #defines to include
+        // 3. Allowlist
+
+        let mut state = ParseState::default();
+        while !input.is_empty() {
+            let has_hexathorpe = input.parse::<Option<syn::token::Pound>>()?.is_some();
+            let ident: syn::Ident = input.parse()?;
+            if has_hexathorpe {
+                if ident != "include" {
+                    return Err(syn::Error::new(ident.span(), "expected include"));
+                }
+                state.config.inclusions.push(Inclusion::parse_path(input)?);
+            } else {
+                input.parse::<Option<syn::token::Bang>>()?;
+                let directive = find_directive(&ident).ok_or_else(|| {
+                    syn::Error::new(
+                        ident.span(),
+                        "expected generate, generate_pod, nested_type, safety or exclude_utilities",
+                    )
+                })?;
+                let args;
+                syn::parenthesized!(args in input);
+                (directive.parse)(&mut state, &args, &ident)?;
+            }
+            if input.is_empty() {
+                break;
+            }
+        }
+        state.finish()
+    }
+}
+
+/// Rust keywords which may be used as raw identifiers, e.g. `r#type`.
+/// (`self`, `Self`, `super` and `crate` can't be.)
+static RAW_IDENT_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Whether this name is a Rust keyword, such that it needs to be
+/// represented as a raw identifier in Rust.
+pub fn is_rust_keyword(id: &str) -> bool {
+    RAW_IDENT_KEYWORDS.contains(&id)
+}
+
+/// Spells a name as it is in C++. Items named after Rust keywords may be
+/// given as bindgen names them (`move_`), or as we do (`r#move`).
+fn cpp_spelling(name: &str) -> Cow<'_, str> {
+    let (prefix, last) = match name.rsplit_once("::") {
+        Some((prefix, last)) => (Some(prefix), last),
+        None => (None, name),
+    };
+    match last
+        .strip_prefix("r#")
+        .or_else(|| last.strip_suffix('_'))
+        .filter(|id| is_rust_keyword(id))
+    {
+        None => Cow::Borrowed(name),
+        Some(last) => Cow::Owned(match prefix {
+            Some(prefix) => format!("{}::{}", prefix, last),
+            None => last.to_string(),
+        }),
+    }
+}
+
+/// bindgen's name for an item named after a Rust keyword, e.g. `move_`.
+fn bindgen_keyword_name(name: &str) -> Option<String> {
+    let last = name.rsplit("::").next().unwrap_or(name);
+    is_rust_keyword(last).then(|| format!("{}_", name))
+}
+
+/// Turns a name such as `std::__1::string` into a bindgen allowlist
+/// pattern which also matches `std::string`, in case `__1` is inline.
+fn allow_inline_namespaces(item: String) -> String {
+    if item.contains('<') {
+        return item;
+    }
+    let segments: Vec<&str> = item.split("::").collect();
+    match segments.as_slice() {
+        [outermost, namespaces @ .., last] if !namespaces.is_empty() => {
+            let namespaces: String = namespaces
+                .iter()
+                .map(|ns| format!("(?:{}::)?", ns))
+                .collect();
+            format!("{}::{}{}", outermost, namespaces, last)
+        }
+        _ => item,
+    }
+}
+
+/// Strips any of the `inline_namespaces` from a qualified C++ name.
+fn strip_inline_namespaces(name: &mut String, inline_namespaces: &[String]) {
+    for ns in inline_namespaces {
+        if let Some(rest) = name.strip_prefix(ns.as_str()) {
+            if let Some(item) = rest.strip_prefix("::") {
+                *name = match ns.rsplit_once("::") {
+                    Some((enclosing, _)) => format!("{}::{}", enclosing, item),
+                    None => item.to_string(),
+                };
+            }
+        }
+    }
+}
+
+/// Whether `file`, as reported by clang, is `header` as named in an
+/// `#include` directive, i.e. whether it's that or ends with it as a
+/// whole path component.
+fn is_same_header(file: &str, header: &str) -> bool {
+    file == header
+        || file
+            .strip_suffix(header)
+            .is_some_and(|dir| dir.ends_with('/') || dir.ends_with('\\'))
+}
+
+/// For directives which take no arguments, e.g. `generate_all!()`.
+fn expect_no_args(args: ParseStream, latest_ident: &Ident) -> ParseResult<()> {
+    if args.is_empty() {
+        Ok(())
+    } else {
+        Err(syn::Error::new(
+            latest_ident.span(),
+            "expected no arguments to directive",
+        ))
+    }
+}
+
+impl IncludeCppConfig {
+    pub fn get_pod_requests(&self) -> &[String] {
+        &self.pod_requests
+    }
+
+    /// Types requested using `generate_pod_recursive!`. These are also
+    /// included in [`Self::get_pod_requests`].
+    pub fn get_pod_recursive_requests(&self) -> &[String] {
+        &self.pod_recursive_requests
+    }
+
+    /// Whether this type was requested using `force_pod_unsafe!`, such
+    /// that it's POD even though C++ doesn't think it trivially relocatable.
+    pub fn is_force_pod_unsafe(&self, cpp_name: &str) -> bool {
+        self.force_pod_unsafe_requests
+            .iter()
+            .any(|ty| ty == cpp_name)
+    }
+
+    pub fn get_mod_name(&self) -> Ident {
+        self.mod_name
+            .as_ref()
+            .cloned()
+            .unwrap_or_else(|| Ident::new("ffi", Span::call_site()))
+    }
+
+    /// Whether to avoid generating the standard helpful utility
+    /// functions which we normally include in every mod.
+    pub fn exclude_utilities(&self) -> bool {
+        self.exclude_utilities
+    }
+
+    /// C++ code to be placed before any `#include`s, both when
+    /// running bindgen and in the generated C++. See the `prelude!`
+    /// directive.
+    pub fn get_prelude(&self) -> String {
+        self.preludes
+            .iter()
+            .map(|prelude| format!("{}\n", prelude))
+            .collect()
+    }
+
+    /// The headers to `#include`, in the order they should be included:
+    /// in ascending order of any priority given using `include_priority!`
+    /// (headers without a priority have priority 0), and otherwise in the
+    /// order they were listed.
+    pub fn get_ordered_inclusions(&self) -> Vec<Inclusion> {
+        let mut inclusions = self.inclusions.clone();
+        inclusions.sort_by_key(|inc| {
+            self.include_priorities
+                .iter()
+                .rev()
+                .find(|(hdr, _)| *hdr == inc.path)
+                .map(|(_, priority)| *priority)
+                .unwrap_or_default()
+        });
+        inclusions
+    }
+
+    /// C++ functions to be called by the generated `initialize` function.
+    /// See the `init_function!` directive.
+    pub fn get_init_functions(&self) -> &[String] {
+        &self.init_functions
+    }
+
+    /// C++ functions to be called by the generated `shutdown` function.
+    /// See the `shutdown_function!` directive.
+    pub fn get_shutdown_functions(&self) -> &[String] {
+        &self.shutdown_functions
+    }
+
+    fn lifecycle_functions(&self) -> impl Iterator<Item = &String> {
+        self.init_functions
+            .iter()
+            .chain(self.shutdown_functions.iter())
+    }
+
+    /// Items which the user has explicitly asked us to generate;
+    /// we should raise an error if we weren't able to do so.
+    pub fn must_generate_list(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        if let Allowlist::Specific(items) = &self.allowlist {
+            Box::new(
+                items
+                    .iter()
+                    .chain(self.pod_requests.iter())
+                    .chain(self.lifecycle_functions())
+                    .cloned(),
+            )
+        } else {
+            Box::new(
+                self.pod_requests
+                    .iter()
+                    .chain(self.lifecycle_functions())
+                    .cloned(),
+            )
+        }
+    }
+
+    /// The allowlist of items to be passed into bindgen, if any. If
+    /// `generate_from_header!` was used, bindgen must tell us about
+    /// everything, since we only know which items are declared in those
+    /// headers once it has. Any namespace named, other than the
+    /// outermost, might be inline, and bindgen knows items within inline
+    /// namespaces by names which don't mention them, so those namespaces
+    /// are optional as far as bindgen is concerned.
+    pub fn bindgen_allowlist(&self) -> Option<Box<dyn Iterator<Item = String> + '_>> {
+        if self.has_headers_to_generate() {
+            None
+        } else {
+            self.requested_items().map(|items| {
+                Box::new(items.flat_map(|item| {
+                    let item = cpp_spelling(&item).into_owned();
+                    let bindgen_name = bindgen_keyword_name(&item);
+                    std::iter::once(item)
+                        .chain(bindgen_name)
+                        .map(allow_inline_namespaces)
+                })) as Box<dyn Iterator<Item = String>>
+            })
+        }
+    }
+
+    /// All the items requested, if not everything.
+    fn requested_items(&self) -> Option<Box<dyn Iterator<Item = String> + '_>> {
+        match &self.allowlist {
+            Allowlist::All => None,
+            Allowlist::Specific(items) => Some(Box::new(
+                items
+                    .iter()
+                    .chain(self.items_from_headers.iter())
+                    .chain(self.pod_requests.iter())
+                    .chain(self.opaque_types.iter())
+                    .chain(self.opaque_only_types.iter())
+                    .chain(self.extern_cpp_types.iter().map(|(ty, _)| ty))
+                    .chain(self.lifecycle_functions())
+                    .chain(self.raii_types.iter())
+                    .cloned()
+                    .chain(self.active_utilities())
+                    .chain(self.subclasses.iter().flat_map(|sc| {
+                        [
+                            format!("{}Cpp", sc.subclass),
+                            sc.subclass.to_string(), // TODO may not be necessary
+                            sc.superclass.clone(),
+                        ]
+                    })),
+            )),
+            Allowlist::Unspecified(_) => unreachable!(),
+        }
+    }
+
+    fn active_utilities(&self) -> Vec<String> {
+        if self.exclude_utilities {
+            Vec::new()
+        } else {
+            vec![self.get_makestring_name().to_string()]
+        }
+    }
+
+    /// Whether this type is on the allowlist specified by the user.
+    ///
+    /// A note on the allowlist handling in general. It's used in two places:
+    /// 1) As directives to bindgen
+    /// 2) After bindgen has generated code, to filter the APIs which
+    ///    we pass to cxx.
+    ///
+    /// This second pass may seem redundant. But sometimes bindgen generates
+    /// unnecessary stuff.
+    pub fn is_on_allowlist(&self, cpp_name: &str) -> bool {
+        match self.requested_items() {
+            None => true,
+            Some(mut items) => {
+                let cpp_spelling_of_name = cpp_spelling(cpp_name);
+                items.any(|item| cpp_spelling(&item) == cpp_spelling_of_name)
+                    || self.active_utilities().iter().any(|item| *item == cpp_name)
+                    || self.is_subclass_holder(cpp_name)
+                    || self.is_subclass_cpp(cpp_name)
+                    || self.is_rust_fun(cpp_name)
+            }
+        }
+    }
+
+    /// Whether the user has declared this type using
+    /// `extern_cpp_opaque_type!`, in which case we should represent it
+    /// as an opaque type even if it's only forward declared.
+    pub fn is_opaque_type(&self, cpp_name: &str) -> bool {
+        self.opaque_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// The existing Rust type which the user has declared, using
+    /// `extern_cpp_type!`, to be ABI-identical to this C++ type.
+    pub fn get_extern_cpp_type(&self, cpp_name: &str) -> Option<&RustPath> {
+        self.extern_cpp_types
+            .iter()
+            .find(|(item, _)| item == cpp_name)
+            .map(|(_, rust_path)| rust_path)
+    }
+
+    /// The Rust type which the user has declared, using `mirror_type!`, to
+    /// have the same fields as this POD type, such that conversions and
+    /// comparisons between the two should be generated.
+    pub fn get_mirror_type(&self, cpp_name: &str) -> Option<&RustPath> {
+        self.mirror_types
+            .iter()
+            .find(|(item, _)| item == cpp_name)
+            .map(|(_, rust_path)| rust_path)
+    }
+
+    /// The instantiations of class templates which the user has named
+    /// using `concrete!`, each with the name of the Rust type to represent
+    /// it.
+    pub fn get_concretes(&self) -> &[(String, Ident)] {
+        &self.concretes
+    }
+
+    /// The instantiation of a class template which the user has asked,
+    /// using `concrete!`, to be represented by this Rust type.
+    pub fn get_concrete(&self, rust_name: &str) -> Option<&str> {
+        self.concretes
+            .iter()
+            .find(|(_, rust_id)| rust_id == rust_name)
+            .map(|(cpp_type, _)| cpp_type.as_str())
+    }
+
+    /// Whether the user has asked for metadata describing the fields
+    /// of this type, using `field_metadata!`.
+    pub fn wants_field_metadata(&self, cpp_name: &str) -> bool {
+        self.field_metadata.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asked for an RAII guard to be generated
+    /// for this type, using `raii!`.
+    pub fn is_raii_type(&self, cpp_name: &str) -> bool {
+        self.raii_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asked for a batched form of this function or
+    /// method, e.g. `Canvas::draw_point`, using `batch!`.
+    pub fn is_batched(&self, cpp_name: &str) -> bool {
+        self.batched_functions.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asked for this method, e.g. `Point::get_x`, to
+    /// be called through a thin `extern "C"` wrapper using `thin_call!`.
+    pub fn is_thin_call(&self, cpp_name: &str) -> bool {
+        self.thin_calls.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has promised, using `treat_as_const!`, that this
+    /// non-const method, e.g. `Widget::name`, doesn't in fact modify its
+    /// receiver, so it may be given a `&self` receiver.
+    pub fn is_treated_as_const(&self, cpp_name: &str) -> bool {
+        self.treated_as_const.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has asked, using `shared_ref_variant!`, for an
+    /// additional variant of this function or method, e.g. `measure` or
+    /// `Canvas::draw`, which accepts shared references in place of its
+    /// non-const reference parameters.
+    pub fn wants_shared_ref_variant(&self, cpp_name: &str) -> bool {
+        self.shared_ref_variants.iter().any(|item| item == cpp_name)
+    }
+
+    /// Whether the user has promised, using `returns_owned!`, that the raw
+    /// pointer returned by this function or method, e.g. `Factory::make`,
+    /// owns its object, which should therefore be returned in a `UniquePtr`.
+    pub fn returns_owned(&self, cpp_name: &str) -> bool {
+        self.owned_returns.iter().any(|item| item == cpp_name)
+    }
+
+    /// The parameters of this function or method, e.g. `Sink::adopt`,
+    /// which the user has promised, using `consumes!`, take ownership of
+    /// the objects to which they point. They're numbered from 1, not
+    /// counting the receiver of a method.
+    pub fn get_consumed_args<'a>(&'a self, cpp_name: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.consumed_args
+            .iter()
+            .filter(move |(function, _)| function == cpp_name)
+            .map(|(_, arg)| *arg)
+    }
+
+    /// The type, and the name of the associated function within it, to
+    /// which the user has asked for this free function to be attached
+    /// using `associate_fn!`. For example, `associate_fn!("CreateWidget",
+    /// "Widget::create")` gives `("Widget", "create")`.
+    pub fn get_associated_fn(&self, cpp_name: &str) -> Option<(&str, &str)> {
+        self.associated_fns
+            .iter()
+            .find(|(function, _)| function == cpp_name)
+            .and_then(|(_, target)| split_associated_fn_target(target))
+    }
+
+    /// Whether the user has blocked the constructor of this type which
+    /// takes the given parameters, using `block_constructors!` with a
+    /// signature. The parameters are a comma-separated list of C++ types.
+    pub fn is_constructor_blocked(&self, cpp_name: &str, params: &str) -> bool {
+        let params = normalize_signature(params);
+        self.constructor_blocklist
+            .iter()
+            .filter_map(|sig| split_constructor_signature(sig))
+            .any(|(ty, blocked_params)| ty == cpp_name && blocked_params == params)
+    }
+
+    /// The normalized parameters of the constructor which the user has
+    /// asked to be called `new`, using `canonical_constructor!`, if any.
+    fn get_canonical_constructor(&self, cpp_name: &str) -> Option<String> {
+        self.canonical_constructors
+            .iter()
+            .filter_map(|sig| split_constructor_signature(sig))
+            .find(|(ty, _)| *ty == cpp_name)
+            .map(|(_, params)| params)
+    }
+
+    /// Whether the user has asked for a canonical constructor for this type.
+    pub fn has_canonical_constructor(&self, cpp_name: &str) -> bool {
+        self.get_canonical_constructor(cpp_name).is_some()
+    }
+
+    /// Whether the constructor of this type which takes the given
+    /// parameters was named as canonical using `canonical_constructor!`.
+    pub fn is_canonical_constructor(&self, cpp_name: &str, params: &str) -> bool {
+        self.get_canonical_constructor(cpp_name).as_deref() == Some(&normalize_signature(params))
+    }
+
+    /// Whether the user has asserted that this type is not abstract,
+    /// using `instantiable!`.
+    pub fn is_instantiable(&self, cpp_name: &str) -> bool {
+        self.instantiable_types.iter().any(|item| item == cpp_name)
+    }
+
+    pub fn get_instantiable_types(&self) -> &[String] {
+        &self.instantiable_types
+    }
+
+    /// Whether the user has asked, using `opaque_only!`, that this type
+    /// be generated without any methods or constructors.
+    pub fn is_opaque_only(&self, cpp_name: &str) -> bool {
+        self.opaque_only_types.iter().any(|item| item == cpp_name)
+    }
+
+    /// The C++ namespace in which Rust types and functions should be
+    /// declared, if not the global namespace.
+    pub fn get_extern_rust_namespace(&self) -> Option<&str> {
+        self.extern_rust_namespace.as_deref()
+    }
+
+    /// The name of a C++ class which should group Rust functions
+    /// (as static member functions) and types (as member type aliases).
+    pub fn get_extern_rust_class(&self) -> Option<&str> {
+        self.extern_rust_class.as_deref()
+    }
+
+    /// Patterns for types which bindgen should treat as opaque, requested
+    /// using `bindgen_opaque_type!`.
+    pub fn get_bindgen_opaque_types(&self) -> &[String] {
+        &self.bindgen_opaque_types
+    }
+
+    /// Patterns for header files whose contents bindgen should skip,
+    /// requested using `bindgen_blocklist_file!`.
+    pub fn get_bindgen_blocklisted_files(&self) -> &[String] {
+        &self.bindgen_blocklisted_files
+    }
+
+    /// Extra traits which bindgen should derive where it can, requested
+    /// using `bindgen_derive!`.
+    pub fn get_bindgen_derives(&self) -> &[BindgenDerive] {
+        &self.bindgen_derives
+    }
+
+    /// The `cfg` predicate, e.g. `windows`, under which this item should be
+    /// generated, if one was given using `cfg!` or `generate_if!`. For
+    /// methods, the name is of the form `Type::method`.
+    pub fn get_cfg(&self, cpp_name: &str) -> Option<String> {
+        let predicates: Vec<_> = self
+            .cfgs
+            .iter()
+            .filter(|(item, _)| item == cpp_name)
+            .map(|(_, predicate)| predicate.as_str())
+            .collect();
+        match predicates.as_slice() {
+            [] => None,
+            [predicate] => Some(predicate.to_string()),
+            _ => Some(format!("all({})", predicates.join(", "))),
+        }
+    }
+
+    /// The mod path, relative to the output mod, at which items in this
+    /// C++ namespace (e.g. `a::b::c`) should be exposed, if that's been
+    /// changed using `map_namespace!` or `flatten_namespaces!`. The
+    /// longest matching `map_namespace!` wins; namespaces not matched by
+    /// any are moved to the root if `flatten_namespaces!` was given.
+    pub fn map_namespace(&self, ns: &str) -> Option<String> {
+        let best_mapping = self
+            .namespace_mappings
+            .iter()
+            .filter_map(|(from, to)| {
+                let rest = ns.strip_prefix(from.as_str())?;
+                if rest.is_empty() {
+                    Some((from.len(), to.clone()))
+                } else {
+                    let rest = rest.strip_prefix("::")?;
+                    Some((
+                        from.len(),
+                        if to.is_empty() {
+                            rest.to_string()
+                        } else {
+                            format!("{}::{}", to, rest)
+                        },
+                    ))
+                }
+            })
+            .max_by_key(|(len, _)| *len);
+        match best_mapping {
+            Some((_, mapped)) => Some(mapped),
+            None if self.flatten_namespaces && !ns.is_empty() => Some(String::new()),
+            None => None,
+        }
+    }
+
+    /// Whether C++ method names such as `GetValue` should be exposed to
+    /// Rust in `snake_case`, e.g. `get_value`.
+    pub fn snake_case_methods(&self) -> bool {
+        self.snake_case_methods
+    }
+
+    /// Whether getter/setter pairs such as `getWidth`/`setWidth` should
+    /// be exposed to Rust as `width`/`set_width`.
+    pub fn property_accessors(&self) -> bool {
+        self.property_accessors
+    }
+
+    /// The Rust name chosen using `helper_name!` for a generated helper,
+    /// e.g. `make_string` or `ns::Widget::make_unique`.
+    pub fn get_helper_name(&self, helper: &str) -> Option<&str> {
+        self.helper_names
+            .iter()
+            .rev()
+            .find(|(this_helper, _)| this_helper == helper)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Documentation given for this item using e.g.
+    /// `generate!("Widget", doc = "...")`.
+    pub fn get_doc(&self, cpp_name: &str) -> Option<&str> {
+        self.docs
+            .iter()
+            .find(|(item, _)| item == cpp_name)
+            .map(|(_, doc)| doc.as_str())
+    }
+
+    /// The packing or alignment asserted for this type using `layout!`.
+    pub fn get_layout_override(&self, cpp_name: &str) -> Option<LayoutOverride> {
+        self.layout_overrides
+            .iter()
+            .find(|(item, _)| item == cpp_name)
+            .map(|(_, layout)| *layout)
+    }
+
+    /// Qt signals given using `qt_signal!`.
+    pub fn get_qt_signals(&self) -> &[QtSignal] {
+        &self.qt_signals
+    }
+
+    /// Traits to be implemented by delegating to C++ methods, given using
+    /// `impl_trait!`.
+    pub fn get_trait_impls(&self) -> &[TraitImpl] {
+        &self.trait_impls
+    }
+
+    /// Reference counted base classes given using `refcounted!`.
+    pub fn get_ref_countings(&self) -> &[RefCounting] {
+        &self.ref_countings
+    }
+
+    /// Whether problems with this error code, e.g. `ACX0036`, were
+    /// silenced using `silence_error!`, so shouldn't be reported.
+    pub fn is_error_silenced(&self, code: &str) -> bool {
+        self.silenced_errors
+            .iter()
+            .any(|silenced| silenced.eq_ignore_ascii_case(code))
+    }
+
+    /// Whether this item, e.g. `ns::Widget` or `ns::Widget::method`, was
+    /// acknowledged using `expect_ignored!` to be something for which
+    /// bindings can't be generated.
+    pub fn is_ignored_item_expected(&self, cpp_name: &str) -> bool {
+        self.expected_ignored
+            .iter()
+            .any(|(item, _)| item == cpp_name)
+    }
+
+    /// Whether `strict!` was given, so that any item for which bindings
+    /// can't be generated should fail the build, unless acknowledged.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether `internal_linkage_shims!` was given, so that functions in
+    /// anonymous namespaces should be called through shims compiled
+    /// alongside the header which defines them.
+    pub fn generate_internal_linkage_shims(&self) -> bool {
+        self.internal_linkage_shims
+    }
+
+    /// The C++ standard chosen using `cpp_standard!` for just this
+    /// `include_cpp!`, e.g. `c++20`, overriding any chosen for the build.
+    pub fn get_cpp_standard(&self) -> Option<&str> {
+        self.cpp_standard.as_deref()
+    }
+
+    /// How returned `std::string`s should be presented to Rust, if
+    /// chosen using `string_returns!`.
+    pub fn get_string_returns(&self) -> Option<StringReturns> {
+        self.string_returns
+    }
+
+    /// What happens when an exception or panic reaches the `extern`
+    /// functions which we generate ourselves, as chosen using
+    /// `exception_policy!`.
+    pub fn exception_policy(&self) -> ExceptionPolicy {
+        self.exception_policy
+    }
+
+    /// What happens to functions marked `[[deprecated]]`, as chosen using
+    /// `deprecation_policy!`.
+    pub fn deprecation_policy(&self) -> DeprecationPolicy {
+        self.deprecation_policy
+    }
+
+    pub fn is_on_blocklist(&self, cpp_name: &str) -> bool {
+        self.blocklist.contains(&cpp_name.to_string())
+    }
+
+    /// Whether this namespace, or the type or function with this
+    /// qualified name, is within a namespace excluded using `block_ns!`.
+    pub fn is_in_blocked_namespace(&self, cpp_name: &str) -> bool {
+        self.blocked_namespaces.iter().any(|ns| {
+            cpp_name
+                .strip_prefix(ns.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+    }
+
+    /// Whether any headers were named using `generate_from_header!`.
+    pub fn has_headers_to_generate(&self) -> bool {
+        !self.headers_to_generate.is_empty()
+    }
+
+    /// Whether this file, as reported by clang, is a header named using
+    /// `generate_from_header!`.
+    pub fn is_header_to_generate(&self, file: &str) -> bool {
+        self.headers_to_generate
+            .iter()
+            .any(|header| is_same_header(file, header))
+    }
+
+    /// Generate these items, found to be declared in the headers named
+    /// using `generate_from_header!`, as if each had been named using
+    /// `generate!`, except that it's not an error if they can't be.
+    pub fn add_items_from_headers(&mut self, items: impl IntoIterator<Item = String>) {
+        self.items_from_headers.extend(items);
+    }
+
+    /// Forget about these inline namespaces, such as `std::__1`, wherever
+    /// they're mentioned in the names of items in directives, since
+    /// bindgen knows those items by names which don't mention them.
+    pub fn strip_inline_namespaces(&mut self, inline_namespaces: &[String]) {
+        let strip = |name: &mut String| strip_inline_namespaces(name, inline_namespaces);
+        if let Allowlist::Specific(items) = &mut self.allowlist {
+            items.iter_mut().for_each(strip);
+        }
+        for names in [
+            &mut self.pod_requests,
+            &mut self.pod_recursive_requests,
+            &mut self.force_pod_unsafe_requests,
+            &mut self.blocklist,
+            &mut self.constructor_blocklist,
+            &mut self.mocks,
+            &mut self.channel_observers,
+            &mut self.opaque_types,
+            &mut self.field_metadata,
+            &mut self.init_functions,
+            &mut self.shutdown_functions,
+            &mut self.raii_types,
+            &mut self.batched_functions,
+            &mut self.thin_calls,
+            &mut self.treated_as_const,
+            &mut self.shared_ref_variants,
+            &mut self.owned_returns,
+            &mut self.canonical_constructors,
+            &mut self.instantiable_types,
+            &mut self.opaque_only_types,
+        ] {
+            names.iter_mut().for_each(strip);
+        }
+        self.extern_cpp_types
+            .iter_mut()
+            .for_each(|(name, _)| strip(name));
+        self.mirror_types
+            .iter_mut()
+            .for_each(|(name, _)| strip(name));
+        self.consumed_args
+            .iter_mut()
+            .for_each(|(name, _)| strip(name));
+        self.associated_fns
+            .iter_mut()
+            .for_each(|(function, target)| {
+                strip(function);
+                strip(target);
+            });
+        self.cfgs.iter_mut().for_each(|(name, _)| strip(name));
+        self.docs.iter_mut().for_each(|(name, _)| strip(name));
+        self.layout_overrides
+            .iter_mut()
+            .for_each(|(name, _)| strip(name));
+        self.expected_ignored
+            .iter_mut()
+            .for_each(|(name, _)| strip(name));
+        self.subclasses
+            .iter_mut()
+            .for_each(|sc| strip(&mut sc.superclass));
+    }
+
+    /// Which of the headers named in `#include` directives this file,
+    /// as reported by clang, is, if any.
+    pub fn included_header_for_file(&self, file: &str) -> Option<&str> {
+        self.inclusions
+            .iter()
+            .map(|inc| inc.path.as_str())
+            .find(|header| is_same_header(file, header))
+    }
+
+    pub fn is_on_constructor_blocklist(&self, cpp_name: &str) -> bool {
+        self.constructor_blocklist.contains(&cpp_name.to_string())
+    }
+
+    pub fn get_blocklist(&self) -> impl Iterator<Item = &String> {
+        self.blocklist.iter()
+    }
+
+    /// In case there are multiple sets of ffi mods in a single binary,
+    /// endeavor to return a name which can be used to make symbols
+    /// unique.
+    pub fn uniquify_name_per_mod<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self.mod_name.as_ref() {
+            None => Cow::Borrowed(name),
+            Some(md) => Cow::Owned(format!("{}_{}", name, md)),
+        }
+    }
+
+    pub fn get_makestring_name(&self) -> Cow<'_, str> {
+        self.uniquify_name_per_mod("autocxx_make_string")
+    }
+
+    pub fn is_rust_type(&self, id: &Ident) -> bool {
+        self.rust_types
+            .iter()
+            .any(|rt| rt.get_final_ident() == &id.to_string())
+            || self.is_subclass_holder(&id.to_string())
+    }
+
+    fn is_rust_fun(&self, possible_fun: &str) -> bool {
+        self.extern_rust_funs
+            .iter()
+            .map(|fun| &fun.sig.ident)
+            .any(|id| id == possible_fun)
+    }
+
+    pub fn superclasses(&self) -> impl Iterator<Item = &String> {
+        let mut uniquified = HashSet::new();
+        uniquified.extend(self.subclasses.iter().map(|sc| &sc.superclass));
+        uniquified.into_iter()
+    }
+
+    /// Whether we should generate a Rust mock implementation of the
+    /// `_methods` trait for this superclass.
+    pub fn is_mocked(&self, cpp_name: &str) -> bool {
+        self.mocks.iter().any(|m| m == cpp_name)
+    }
+
+    /// If events from this superclass should be forwarded into a channel,
+    /// the generic subclass which should offer that channel.
+    pub fn get_channel_observer_subclass(&self, superclass: &str) -> Option<&Ident> {
+        if !self.channel_observers.iter().any(|c| c == superclass) {
+            return None;
+        }
+        self.subclasses
+            .iter()
+            .find(|sc| sc.generic && sc.superclass == superclass)
+            .map(|sc| &sc.subclass)
+    }
+
+    /// The version of the scheme used to name generated shims. Unless
+    /// pinned using `mangling_version!`, this is the latest version.
+    pub fn get_mangling_version(&self) -> u32 {
+        self.mangling_version.unwrap_or(CURRENT_MANGLING_VERSION)
+    }
+
+    pub fn is_subclass_holder(&self, id: &str) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| format!("{}Holder", sc.subclass) == id)
+    }
+
+    /// Whether this is a subclass which was requested using
+    /// `subclass!(..., generic)`, such that autocxx generates the Rust
+    /// struct itself.
+    pub fn is_generic_subclass(&self, id: &str) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| sc.generic && sc.subclass == id)
+    }
+
+    fn is_subclass_cpp(&self, id: &str) -> bool {
+        self.subclasses
+            .iter()
+            .any(|sc| format!("{}Cpp", sc.subclass) == id)
+    }
+
+    /// Return the filename to which generated .rs should be written.
+    pub fn get_rs_filename(&self) -> String {
+        format!(
+            "autocxx-{}-gen.rs",
+            self.mod_name
+                .as_ref()
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| "ffi-default".into())
+        )
+    }
+
+    pub fn confirm_complete(&mut self, auto_allowlist: bool) -> ParseResult<()> {
+        if matches!(self.allowlist, Allowlist::Unspecified(_)) {
+            if auto_allowlist {
+                self.allowlist = Allowlist::Specific(Vec::new());
+                Ok(())
+            } else {
+                Err(syn::Error::new(
+                    Span::call_site(),
+                    "expected either generate! or generate_all!",
+                ))
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Used in reduction to substitute all included headers with a single
+    /// preprocessed replacement.
+    pub fn replace_included_headers(&mut self, replacement: &str) {
+        self.inclusions.clear();
+        self.inclusions.push(Inclusion {
+            path: replacement.to_string(),
+            system: false,
+        });
+    }
+}
+
+#[cfg(feature = "reproduction_case")]
+impl ToTokens for IncludeCppConfig {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        for inc in &self.inclusions {
+            let hexathorpe = syn::token::Pound(Span::call_site());
+            let path = &inc.path;
+            if inc.system {
+                let path: proc_macro2::TokenStream = path.parse().unwrap();
+                tokens.extend(quote! {
+                    #hexathorpe include < #path >
+                })
+            } else {
+                tokens.extend(quote! {
+                    #hexathorpe include #path
+                })
+            }
+        }
+        general::to_tokens(self, tokens);
+        allowlist::to_tokens(self, tokens);
+        functions::to_tokens(self, tokens);
+        types::to_tokens(self, tokens);
+        rust::to_tokens(self, tokens);
+        naming::to_tokens(self, tokens);
+        bindgen::to_tokens(self, tokens);
+        diagnostics::to_tokens(self, tokens);
+    }
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use std::collections::HashSet;
+
+    use crate::config::{
+        BindgenDerive, DeprecationPolicy, ExceptionPolicy, IncludeCppConfig, LayoutOverride,
+        StringReturns, UnsafePolicy, CURRENT_MANGLING_VERSION, DIRECTIVE_FAMILIES,
+    };
+    use quote::ToTokens;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_directive_names_unique() {
+        let mut names = HashSet::new();
+        for directive in DIRECTIVE_FAMILIES
+            .iter()
+            .flat_map(|directives| directives.iter())
+        {
+            assert!(
+                names.insert(directive.name),
+                "{} is given twice",
+                directive.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_directive_errors() {
+        assert!(syn::parse_str::<IncludeCppConfig>("generate_everything!()").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("flatten_namespaces!(\"a\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("flatten_namespaces").is_err());
+    }
+
+    #[test]
+    fn test_name() {
+        let config: IncludeCppConfig = parse_quote! {
+            name!(bindings)
+            generate!("A")
+        };
+        assert_eq!(config.get_mod_name(), "bindings");
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_mod_name(), "bindings");
+    }
+
+    #[test]
+    fn test_safety_unsafe() {
+        let us: UnsafePolicy = parse_quote! {
+            unsafe
+        };
+        assert_eq!(us, UnsafePolicy::AllFunctionsSafe)
+    }
+
+    #[test]
+    fn test_safety_unsafe_ffi() {
+        let us: UnsafePolicy = parse_quote! {
+            unsafe_ffi
+        };
+        assert_eq!(us, UnsafePolicy::AllFunctionsSafe)
+    }
+
+    #[test]
+    fn test_safety_safe() {
+        let us: UnsafePolicy = parse_quote! {};
+        assert_eq!(us, UnsafePolicy::AllFunctionsUnsafe)
+    }
+
+    #[test]
+    fn test_mangling_version() {
+        let config: IncludeCppConfig = parse_quote! {
+            mangling_version!(1)
+        };
+        assert_eq!(config.get_mangling_version(), 1);
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.get_mangling_version(), CURRENT_MANGLING_VERSION);
+        assert!(syn::parse_str::<IncludeCppConfig>("mangling_version!(2)").is_ok());
+        assert!(syn::parse_str::<IncludeCppConfig>("mangling_version!(0)").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("mangling_version!(999)").is_err());
+    }
+
+    #[test]
+    fn test_include_ordering() {
+        let config: IncludeCppConfig = syn::parse_str(
+            r##"
+            #include "a.h"
+            #include "b.h"
+            #include "windows.h"
+            #include "c.h"
+            prelude!("#define NOMINMAX")
+            include_priority!("windows.h", -10)
+            include_priority!("a.h", 5)
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            config
+                .get_ordered_inclusions()
+                .iter()
+                .map(|inc| inc.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["windows.h", "b.h", "c.h", "a.h"]
+        );
+        assert_eq!(config.get_prelude(), "#define NOMINMAX\n");
+    }
+
+    #[test]
+    fn test_constructor_signatures() {
+        let config: IncludeCppConfig = parse_quote! {
+            block_constructors!("A(int, const std::string &)")
+            block_constructors!("C")
+            canonical_constructor!("A( uint32_t )")
+        };
+        assert!(config.is_constructor_blocked("A", "int,const std::string&"));
+        assert!(!config.is_constructor_blocked("A", "int"));
+        assert!(!config.is_constructor_blocked("B", "int,const std::string&"));
+        assert!(config.has_canonical_constructor("A"));
+        assert!(!config.has_canonical_constructor("B"));
+        assert!(config.is_canonical_constructor("A", "uint32_t"));
+        assert!(!config.is_canonical_constructor("A", ""));
+        assert!(!config.is_on_constructor_blocklist("A"));
+        assert!(config.is_on_constructor_blocklist("C"));
+        assert!(!config.is_constructor_blocked("C", ""));
+        assert!(syn::parse_str::<IncludeCppConfig>("block_constructors!(\"A(int\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("canonical_constructor!(\"A\")").is_err());
+    }
+
+    #[test]
+    fn test_generic_subclass() {
+        let config: IncludeCppConfig = parse_quote! {
+            subclass!("Observer", AnyObserver, generic)
+            subclass!("Observer", MyObserver)
+        };
+        assert!(config.is_generic_subclass("AnyObserver"));
+        assert!(!config.is_generic_subclass("MyObserver"));
+        assert!(syn::parse_str::<IncludeCppConfig>("subclass!(\"Observer\", A, other)").is_err());
+    }
+
+    #[test]
+    fn test_extern_rust_namespace() {
+        let config: IncludeCppConfig = parse_quote! {
+            extern_rust_namespace!("mylib::api")
+            extern_rust_class!("RustApi")
+        };
+        assert_eq!(config.get_extern_rust_namespace(), Some("mylib::api"));
+        assert_eq!(config.get_extern_rust_class(), Some("RustApi"));
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.get_extern_rust_namespace(), None);
+    }
+
+    #[test]
+    fn test_bindgen_options() {
+        let config: IncludeCppConfig = parse_quote! {
+            bindgen_opaque_type!("std::.*")
+            bindgen_blocklist_file!(".*/internal/.*")
+            bindgen_derive!("Debug")
+            bindgen_derive!("PartialEq")
+        };
+        assert_eq!(config.get_bindgen_opaque_types(), ["std::.*"]);
+        assert_eq!(config.get_bindgen_blocklisted_files(), [".*/internal/.*"]);
+        assert_eq!(
+            config.get_bindgen_derives(),
+            [BindgenDerive::Debug, BindgenDerive::PartialEq]
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>("bindgen_derive!(\"Copy\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("bindgen_derive!(\"Serialize\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            "generate_pod!(\"A\") bindgen_opaque_type!(\"A\")"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_cfg() {
+        let config: IncludeCppConfig = parse_quote! {
+            cfg!("Widget::win_only_fn", "windows")
+            cfg!("linux_only_fn", "target_os = \"linux\"")
+        };
+        assert_eq!(
+            config.get_cfg("Widget::win_only_fn").as_deref(),
+            Some("windows")
+        );
+        assert_eq!(
+            config.get_cfg("linux_only_fn").as_deref(),
+            Some("target_os = \"linux\"")
+        );
+        assert_eq!(config.get_cfg("Widget"), None);
+        assert!(syn::parse_str::<IncludeCppConfig>("cfg!(\"A\", \"not a predicate\")").is_err());
+    }
+
+    #[test]
+    fn test_generate_if() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_if!("experimental", "mylib::NewThing")
+            cfg!("mylib::NewThing", "unix")
+        };
+        assert!(config.is_on_allowlist("mylib::NewThing"));
+        assert_eq!(
+            config.get_cfg("mylib::NewThing").as_deref(),
+            Some("all(feature = \"experimental\", unix)")
+        );
+    }
+
+    #[test]
+    fn test_map_namespace() {
+        let config: IncludeCppConfig = parse_quote! {
+            map_namespace!("a::b::c", "abc")
+            map_namespace!("a::b", "")
+        };
+        assert_eq!(config.map_namespace("a::b::c").as_deref(), Some("abc"));
+        assert_eq!(
+            config.map_namespace("a::b::c::d").as_deref(),
+            Some("abc::d")
+        );
+        assert_eq!(config.map_namespace("a::b::e").as_deref(), Some("e"));
+        assert_eq!(config.map_namespace("a::bb"), None);
+        assert_eq!(config.map_namespace("x"), None);
+        let config: IncludeCppConfig = parse_quote! {
+            map_namespace!("a", "renamed")
+            flatten_namespaces!()
+        };
+        assert_eq!(config.map_namespace("a::b").as_deref(), Some("renamed::b"));
+        assert_eq!(config.map_namespace("x::y").as_deref(), Some(""));
+        assert_eq!(config.map_namespace(""), None);
+        assert!(syn::parse_str::<IncludeCppConfig>("map_namespace!(\"a\", \"b-c\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("map_namespace!(\"\", \"b\")").is_err());
+    }
+
+    #[test]
+    fn test_snake_case_methods() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+        };
+        assert!(!config.snake_case_methods());
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            snake_case_methods!()
+        };
+        assert!(config.snake_case_methods());
+    }
+
+    #[test]
+    fn test_block_ns() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            block_ns!("mylib::internal")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_in_blocked_namespace("mylib::internal"));
+        assert!(reparsed.is_in_blocked_namespace("mylib::internal::detail::Widget"));
+        assert!(!reparsed.is_in_blocked_namespace("mylib::internals::Widget"));
+        assert!(!reparsed.is_in_blocked_namespace("mylib::Widget"));
+        assert!(syn::parse_str::<IncludeCppConfig>("block_ns!(\"\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("block_ns!(\"mylib::\")").is_err());
+    }
+
+    #[test]
+    fn test_generate_from_header() {
+        let mut config: IncludeCppConfig = syn::parse_str(
+            r##"
+            #include "widget.h"
+            #include "gadget.h"
+            generate_from_header!("widget.h")
+            "##,
+        )
+        .unwrap();
+        assert!(config.bindgen_allowlist().is_none());
+        assert!(!config.is_on_allowlist("Widget"));
+        config.add_items_from_headers(vec!["Widget".to_string()]);
+        assert!(config.is_on_allowlist("Widget"));
+        assert!(config.is_header_to_generate("/src/include/widget.h"));
+        assert!(!config.is_header_to_generate("/src/include/subwidget.h"));
+        assert_eq!(
+            config.included_header_for_file("/src/include/gadget.h"),
+            Some("gadget.h")
+        );
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_header_to_generate("widget.h"));
+        assert!(!reparsed.is_on_allowlist("Widget"));
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            "generate_all!() generate_from_header!(\"widget.h\")"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_keyword_names() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("async")
+            generate!("ns::move_")
+            generate!("asynchronous")
+        };
+        let allowlist: Vec<_> = config.bindgen_allowlist().unwrap().collect();
+        assert!(allowlist.contains(&"async".to_string()));
+        assert!(allowlist.contains(&"async_".to_string()));
+        assert!(allowlist.contains(&"ns::move".to_string()));
+        assert!(allowlist.contains(&"ns::move_".to_string()));
+        assert!(!allowlist.contains(&"asynchronous_".to_string()));
+        assert!(config.is_on_allowlist("async"));
+        assert!(config.is_on_allowlist("async_"));
+        assert!(config.is_on_allowlist("r#async"));
+        assert!(config.is_on_allowlist("ns::r#move"));
+        assert!(!config.is_on_allowlist("asynchronous_"));
+    }
+
+    #[test]
+    fn test_inline_namespaces() {
+        let mut config: IncludeCppConfig = parse_quote! {
+            generate!("lib::v2::Widget")
+            generate_pod!("std::__1::string_view")
+            block!("lib::v2::detail::Gadget")
+            generate!("Goat")
+        };
+        let allowlist: Vec<_> = config.bindgen_allowlist().unwrap().collect();
+        assert!(allowlist.contains(&"lib::(?:v2::)?Widget".to_string()));
+        assert!(allowlist.contains(&"std::(?:__1::)?string_view".to_string()));
+        assert!(allowlist.contains(&"Goat".to_string()));
+        config.strip_inline_namespaces(&["lib::v2".to_string(), "std::__1".to_string()]);
+        assert!(config.is_on_allowlist("lib::Widget"));
+        assert!(!config.is_on_allowlist("lib::v2::Widget"));
+        assert_eq!(config.get_pod_requests(), ["std::string_view"]);
+        assert!(config.is_on_blocklist("lib::detail::Gadget"));
+        assert!(config.is_on_allowlist("Goat"));
+    }
+
+    #[test]
+    fn test_opaque_only() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Solver")
+            opaque_only!("HugeMatrix")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_opaque_only("HugeMatrix"));
+        assert!(!reparsed.is_opaque_only("Solver"));
+        assert!(reparsed.is_on_allowlist("HugeMatrix"));
+    }
+
+    #[test]
+    fn test_property_accessors() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+        };
+        assert!(!config.property_accessors());
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            property_accessors!()
+        };
+        assert!(config.property_accessors());
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.property_accessors());
+    }
+
+    #[test]
+    fn test_helper_name() {
+        let config: IncludeCppConfig = parse_quote! {
+            helper_name!("make_string", "make_cpp_string")
+            helper_name!("ns::Widget::make_unique", "create")
+        };
+        assert_eq!(
+            config.get_helper_name("make_string"),
+            Some("make_cpp_string")
+        );
+        assert_eq!(
+            config.get_helper_name("ns::Widget::make_unique"),
+            Some("create")
+        );
+        assert_eq!(config.get_helper_name("ns::Widget::new"), None);
+        assert!(
+            syn::parse_str::<IncludeCppConfig>("helper_name!(\"make_string\", \"a-b\")").is_err()
+        );
+    }
+
+    #[test]
+    fn test_generate_with_doc() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget", doc = "Handle to the native widget")
+            generate_pod!("Point", doc = "A point")
+            generate!("make_widget")
+        };
+        assert!(config.is_on_allowlist("Widget"));
+        assert_eq!(
+            config.get_doc("Widget"),
+            Some("Handle to the native widget")
+        );
+        assert_eq!(config.get_doc("Point"), Some("A point"));
+        assert_eq!(config.get_doc("make_widget"), None);
+        assert!(syn::parse_str::<IncludeCppConfig>("generate!(\"Widget\", docs = \"x\")").is_err());
+    }
+
+    #[test]
+    fn test_generate_pod_recursive() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_pod!("Point")
+            generate_pod_recursive!("Config")
+        };
+        assert!(config.is_on_allowlist("Config"));
+        assert_eq!(config.get_pod_requests(), ["Point", "Config"]);
+        assert_eq!(config.get_pod_recursive_requests(), ["Config"]);
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_pod_requests(), ["Point", "Config"]);
+        assert_eq!(reparsed.get_pod_recursive_requests(), ["Config"]);
+    }
+
+    #[test]
+    fn test_force_pod_unsafe() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_pod!("Point")
+            force_pod_unsafe!("LoggingPoint")
+        };
+        assert!(config.is_on_allowlist("LoggingPoint"));
+        assert_eq!(config.get_pod_requests(), ["Point", "LoggingPoint"]);
+        assert!(config.is_force_pod_unsafe("LoggingPoint"));
+        assert!(!config.is_force_pod_unsafe("Point"));
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_pod_requests(), ["Point", "LoggingPoint"]);
+        assert!(reparsed.is_force_pod_unsafe("LoggingPoint"));
+    }
+
+    #[test]
+    fn test_layout() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            layout!("Packet", packed = 1)
+            layout!("ns::Vector", align = 16)
+        };
+        assert_eq!(
+            config.get_layout_override("Packet"),
+            Some(LayoutOverride::Packed(1))
+        );
+        assert_eq!(
+            config.get_layout_override("ns::Vector"),
+            Some(LayoutOverride::Align(16))
+        );
+        assert_eq!(config.get_layout_override("Other"), None);
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed.get_layout_override("ns::Vector"),
+            Some(LayoutOverride::Align(16))
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>("layout!(\"A\", align = 3)").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("layout!(\"A\", size = 4)").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            "layout!(\"A\", packed = 1) layout!(\"A\", align = 8)"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_extern_cpp_type() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            extern_cpp_type!("fixed::Q16", fixed_point::Q16)
+        };
+        let rust_path = |config: &IncludeCppConfig| {
+            config
+                .get_extern_cpp_type("fixed::Q16")
+                .map(|path| path.to_token_stream().to_string())
+        };
+        assert_eq!(rust_path(&config).as_deref(), Some("fixed_point :: Q16"));
+        assert!(config.get_extern_cpp_type("Q16").is_none());
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(rust_path(&reparsed).as_deref(), Some("fixed_point :: Q16"));
+    }
+
+    #[test]
+    fn test_mirror_type() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_pod!("geo::Point")
+            mirror_type!("geo::Point", crate::Point)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed
+                .get_mirror_type("geo::Point")
+                .map(|path| path.to_token_stream().to_string())
+                .as_deref(),
+            Some("crate :: Point")
+        );
+        assert!(reparsed.get_mirror_type("Point").is_none());
+    }
+
+    #[test]
+    fn test_concrete() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            concrete!("Factory<Widget>", FactoryWidget)
+        };
+        assert!(config.is_on_allowlist("FactoryWidget"));
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_concretes().len(), 1);
+        assert_eq!(
+            reparsed.get_concrete("FactoryWidget"),
+            Some("Factory<Widget>")
+        );
+        assert!(reparsed.get_concrete("Widget").is_none());
+        assert_eq!(
+            reparsed.must_generate_list().collect::<Vec<_>>(),
+            ["Widget", "FactoryWidget"]
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            r#"generate_all!() concrete!("Factory<Widget>", FactoryWidget)"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_impl_trait() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            impl_trait!("Widget", "std::io::Write", write = "Widget::write_bytes", flush = "Widget::flush",)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        let trait_impls = reparsed.get_trait_impls();
+        assert_eq!(trait_impls.len(), 1);
+        assert_eq!(trait_impls[0].ty, "Widget");
+        assert_eq!(trait_impls[0].trait_path, "std::io::Write");
+        assert_eq!(trait_impls[0].methods[0].0, "write");
+        assert_eq!(trait_impls[0].methods[1].1, "Widget::flush");
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            r#"impl_trait!("Widget", "std::io::Write", flush = "Widget::a", flush = "Widget::b")"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_refcounted() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Document")
+            refcounted!("Base", addref = "Ref", release = "Unref")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        let ref_countings = reparsed.get_ref_countings();
+        assert_eq!(ref_countings.len(), 1);
+        assert_eq!(ref_countings[0].base, "Base");
+        assert_eq!(ref_countings[0].addref, "Ref");
+        assert_eq!(ref_countings[0].release, "Unref");
+        assert!(
+            syn::parse_str::<IncludeCppConfig>(r#"refcounted!("Base", addref = "Ref")"#).is_err()
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            r#"refcounted!("Base", addref = "Ref", release = "Unref", delete = "Free")"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_qt_signal() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            qt_signal!("ns::Slider::valueChanged(int)")
+            qt_signal!("ns::Slider::valueChanged(unsigned   int, qreal)")
+            qt_signal!("Button::pressed()")
+        };
+        let signals = config.get_qt_signals();
+        assert_eq!(signals.len(), 3);
+        assert_eq!(signals[0].class, "ns::Slider");
+        assert_eq!(signals[0].connect_fn, "connect_valueChanged");
+        assert_eq!(signals[1].connect_fn, "connect_valueChanged1");
+        assert_eq!(signals[1].cpp_params, vec!["unsigned int", "qreal"]);
+        assert_eq!(signals[1].rust_params, vec!["u32", "f64"]);
+        assert_eq!(signals[1].mangled_name(), "ns_Slider_connect_valueChanged1");
+        assert!(signals[2].cpp_params.is_empty());
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed.get_qt_signals()[1].signature(),
+            "ns::Slider::valueChanged(unsigned int, qreal)"
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>("qt_signal!(\"pressed()\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("qt_signal!(\"A::changed(QString)\")").is_err());
+    }
+
+    #[test]
+    fn test_silence_error() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            silence_error!("ACX0036")
+            silence_error!("acx0041")
+        };
+        assert!(config.is_error_silenced("ACX0036"));
+        assert!(config.is_error_silenced("ACX0041"));
+        assert!(!config.is_error_silenced("ACX0034"));
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_error_silenced("ACX0041"));
+        assert!(syn::parse_str::<IncludeCppConfig>("silence_error!(\"PrivateMethod\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("silence_error!(\"ACX36\")").is_err());
+    }
+
+    #[test]
+    fn test_expect_ignored() {
+        let config: IncludeCppConfig = parse_quote! {
+            expect_ignored!("ns::Widget::weird_fn", "uses std::variant")
+        };
+        assert!(!config.is_strict());
+        assert!(config.is_ignored_item_expected("ns::Widget::weird_fn"));
+        assert!(!config.is_ignored_item_expected("ns::Widget"));
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            strict!()
+            expect_ignored!("Widget", "abstract nested type")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_strict());
+        assert!(reparsed.is_ignored_item_expected("Widget"));
+        assert!(syn::parse_str::<IncludeCppConfig>("expect_ignored!(\"Widget\")").is_err());
+    }
+
+    #[test]
+    fn test_internal_linkage_shims() {
+        let config: IncludeCppConfig = parse_quote! {};
+        assert!(!config.generate_internal_linkage_shims());
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            internal_linkage_shims!()
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.generate_internal_linkage_shims());
+    }
+
+    #[test]
+    fn test_cpp_standard() {
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.get_cpp_standard(), None);
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            cpp_standard!("c++20")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_cpp_standard(), Some("c++20"));
+        assert!(syn::parse_str::<IncludeCppConfig>("cpp_standard!(\"c++11\")").is_err());
+        assert!(syn::parse_str::<IncludeCppConfig>("cpp_standard!(\"20\")").is_err());
+    }
+
+    #[test]
+    fn test_batch() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Canvas")
+            batch!("Canvas::draw_point")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_batched("Canvas::draw_point"));
+        assert!(!reparsed.is_batched("Canvas::draw_line"));
+    }
+
+    #[test]
+    fn test_associate_fn() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            generate!("ui::CreateWidget")
+            associate_fn!("ui::CreateWidget", "Widget::create")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed.get_associated_fn("ui::CreateWidget"),
+            Some(("Widget", "create"))
+        );
+        assert!(reparsed.get_associated_fn("CreateWidget").is_none());
+        assert!(
+            syn::parse_str::<IncludeCppConfig>(r#"associate_fn!("CreateWidget", "create")"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_treat_as_const() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Widget")
+            treat_as_const!("Widget::name")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_treated_as_const("Widget::name"));
+        assert!(!reparsed.is_treated_as_const("Widget::rename"));
+    }
+
+    #[test]
+    fn test_shared_ref_variant() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Canvas")
+            shared_ref_variant!("Canvas::draw")
+            shared_ref_variant!("measure")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.wants_shared_ref_variant("Canvas::draw"));
+        assert!(reparsed.wants_shared_ref_variant("measure"));
+        assert!(!reparsed.wants_shared_ref_variant("Canvas::clear"));
+    }
+
+    #[test]
+    fn test_returns_owned() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Factory")
+            returns_owned!("Factory::make")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.returns_owned("Factory::make"));
+        assert!(!reparsed.returns_owned("Factory::get"));
+    }
+
+    #[test]
+    fn test_consumes() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Sink")
+            consumes!("Sink::adopt", arg = 1)
+            consumes!("Sink::adopt", arg = 3)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed
+                .get_consumed_args("Sink::adopt")
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(reparsed.get_consumed_args("Sink::peek").count(), 0);
+        assert!(
+            syn::parse_str::<IncludeCppConfig>(r#"consumes!("Sink::adopt", arg = 0)"#).is_err()
+        );
+        assert!(syn::parse_str::<IncludeCppConfig>(r#"consumes!("Sink::adopt", 1)"#).is_err());
+    }
+
+    #[test]
+    fn test_channel_observer() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            subclass!("GoatObserver", AnyGoatObserver, generic)
+            channel_observer!("GoatObserver")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(
+            reparsed
+                .get_channel_observer_subclass("GoatObserver")
+                .map(|id| id.to_string()),
+            Some("AnyGoatObserver".to_string())
+        );
+        assert!(reparsed
+            .get_channel_observer_subclass("SheepObserver")
+            .is_none());
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            r#"subclass!("GoatObserver", MyGoatObserver) channel_observer!("GoatObserver")"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_thin_call() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate!("Point")
+            thin_call!("Point::get_x")
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.is_thin_call("Point::get_x"));
+        assert!(!reparsed.is_thin_call("Point::get_y"));
+    }
+
+    #[test]
+    fn test_string_returns() {
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.get_string_returns(), None);
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            string_returns!(cow)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.get_string_returns(), Some(StringReturns::Cow));
+        let config: IncludeCppConfig = parse_quote! {
+            string_returns!(str)
+        };
+        assert_eq!(config.get_string_returns(), Some(StringReturns::Str));
+        assert!(syn::parse_str::<IncludeCppConfig>("string_returns!(string)").is_err());
+    }
+
+    #[test]
+    fn test_exception_policy() {
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.exception_policy(), ExceptionPolicy::Terminate);
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            exception_policy!(propagate)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.exception_policy(), ExceptionPolicy::Propagate);
+        let config: IncludeCppConfig = parse_quote! {
+            exception_policy!(terminate)
+        };
+        assert_eq!(config.exception_policy(), ExceptionPolicy::Terminate);
+        assert!(syn::parse_str::<IncludeCppConfig>("exception_policy!(catch)").is_err());
+    }
+
+    #[test]
+    fn test_deprecation_policy() {
+        let config: IncludeCppConfig = parse_quote! {};
+        assert_eq!(config.deprecation_policy(), DeprecationPolicy::Generate);
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            deprecation_policy!(skip)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.deprecation_policy(), DeprecationPolicy::Skip);
+        let config: IncludeCppConfig = parse_quote! {
+            deprecation_policy!(warn)
+        };
+        assert_eq!(config.deprecation_policy(), DeprecationPolicy::Warn);
+        assert!(syn::parse_str::<IncludeCppConfig>("deprecation_policy!(error)").is_err());
+    }
+
+    #[test]
+    fn test_extern_rust_fun_factory() {
+        let config: IncludeCppConfig = parse_quote! {
+            generate_all!()
+            extern_rust_fun!(crate::new_goat, fn new_goat(horns: u32) -> Goat, factory)
+        };
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert!(reparsed.extern_rust_funs[0].is_factory);
+        assert!(!reparsed.extern_rust_funs[0].returns_expected);
+        assert!(syn::parse_str::<IncludeCppConfig>(
+            "extern_rust_fun!(crate::new_goat, fn new_goat() -> Goat, builder)"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_system_include() {
+        let config: IncludeCppConfig = syn::parse_str(
+            r##"
+            #include <opencv2/opencv.hpp>
+            #include "widget.h"
+            generate_all!()
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.inclusions[0].path, "opencv2/opencv.hpp");
+        assert!(config.inclusions[0].system);
+        assert_eq!(config.inclusions[0].to_string(), "<opencv2/opencv.hpp>");
+        assert_eq!(config.inclusions[1].to_string(), "\"widget.h\"");
+        let reparsed: IncludeCppConfig = syn::parse2(config.to_token_stream()).unwrap();
+        assert_eq!(reparsed.inclusions, config.inclusions);
+        assert!(syn::parse_str::<IncludeCppConfig>("#include <vector").is_err());
+    }
+}
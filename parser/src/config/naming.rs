@@ -0,0 +1,180 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which affect the names of the generated Rust items, and of
+//! the shims behind them: `name!`, `map_namespace!`, `snake_case_methods!`,
+//! `mangling_version!` and so on.
+
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{expect_no_args, Directive, ParseState};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use proc_macro2::Span;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "name",
+        parse: parse_name,
+    },
+    Directive {
+        name: "map_namespace",
+        parse: parse_map_namespace,
+    },
+    Directive {
+        name: "flatten_namespaces",
+        parse: parse_flatten_namespaces,
+    },
+    Directive {
+        name: "snake_case_methods",
+        parse: parse_snake_case_methods,
+    },
+    Directive {
+        name: "property_accessors",
+        parse: parse_property_accessors,
+    },
+    Directive {
+        name: "helper_name",
+        parse: parse_helper_name,
+    },
+    Directive {
+        name: "mangling_version",
+        parse: parse_mangling_version,
+    },
+];
+
+/// The version of the scheme used to name generated shims, which determines
+/// the names of the symbols exported by them. See the `mangling_version!`
+/// directive. Every version from 1 up to this one is supported.
+pub const CURRENT_MANGLING_VERSION: u32 = 2;
+
+fn parse_name(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let ident: Ident = args.parse()?;
+    state.config.mod_name = Some(ident);
+    Ok(())
+}
+
+fn parse_map_namespace(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let from: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let to: LitStr = args.parse()?;
+    if from.value().is_empty() || !is_valid_mod_path(&from.value()) {
+        return Err(syn::Error::new(
+            from.span(),
+            "expected a C++ namespace such as \"a::b::c\"",
+        ));
+    }
+    if !to.value().is_empty() && !is_valid_mod_path(&to.value()) {
+        return Err(syn::Error::new(
+            to.span(),
+            "expected a Rust mod path such as \"abc\", or \"\" for the root",
+        ));
+    }
+    state
+        .config
+        .namespace_mappings
+        .push((from.value(), to.value()));
+    Ok(())
+}
+
+fn parse_flatten_namespaces(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.flatten_namespaces = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_snake_case_methods(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.snake_case_methods = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_property_accessors(
+    state: &mut ParseState,
+    args: ParseStream,
+    ident: &Ident,
+) -> ParseResult<()> {
+    state.config.property_accessors = true;
+    expect_no_args(args, ident)
+}
+
+fn parse_helper_name(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let helper: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let name: LitStr = args.parse()?;
+    if syn::parse_str::<Ident>(&name.value()).is_err() {
+        return Err(syn::Error::new(
+            name.span(),
+            "expected a Rust identifier such as \"make_cpp_string\"",
+        ));
+    }
+    state
+        .config
+        .helper_names
+        .push((helper.value(), name.value()));
+    Ok(())
+}
+
+fn parse_mangling_version(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let version: syn::LitInt = args.parse()?;
+    let version_num: u32 = version.base10_parse()?;
+    if !(1..=CURRENT_MANGLING_VERSION).contains(&version_num) {
+        return Err(syn::Error::new(
+            version.span(),
+            format!(
+                "unsupported mangling version; this version of autocxx supports versions 1 to {}",
+                CURRENT_MANGLING_VERSION
+            ),
+        ));
+    }
+    state.config.mangling_version = Some(version_num);
+    Ok(())
+}
+
+/// Whether this is a `::`-separated list of identifiers which can be used
+/// as mod names.
+fn is_valid_mod_path(path: &str) -> bool {
+    path.split("::")
+        .all(|segment| syn::parse_str::<Ident>(segment).is_ok())
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    if let Some(mod_name) = &config.mod_name {
+        tokens.extend(quote! { name!(#mod_name) });
+    }
+    for (from, to) in &config.namespace_mappings {
+        tokens.extend(quote! { map_namespace!(#from, #to) });
+    }
+    if config.flatten_namespaces {
+        tokens.extend(quote! { flatten_namespaces!() });
+    }
+    if config.snake_case_methods {
+        tokens.extend(quote! { snake_case_methods!() });
+    }
+    if config.property_accessors {
+        tokens.extend(quote! { property_accessors!() });
+    }
+    for (helper, name) in &config.helper_names {
+        tokens.extend(quote! { helper_name!(#helper, #name) });
+    }
+    if let Some(version) = &config.mangling_version {
+        let version = syn::LitInt::new(&version.to_string(), Span::call_site());
+        tokens.extend(quote! { mangling_version!(#version) });
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives concerning Rust items exposed to C++, and Rust subclasses of
+//! C++ classes: `extern_rust_type!`, `extern_rust_fun!`, `subclass!` and
+//! so on.
+
+use quote::ToTokens;
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult, Signature};
+
+use super::{Directive, ParseState};
+use crate::{
+    directives::{EXTERN_RUST_TYPE, SUBCLASS},
+    RustPath,
+};
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "rust_type",
+        parse: parse_extern_rust_type,
+    },
+    Directive {
+        name: EXTERN_RUST_TYPE,
+        parse: parse_extern_rust_type,
+    },
+    Directive {
+        name: "extern_rust_fun",
+        parse: parse_extern_rust_fun,
+    },
+    Directive {
+        name: "extern_rust_enum",
+        parse: parse_extern_rust_enum,
+    },
+    Directive {
+        name: "extern_rust_namespace",
+        parse: parse_extern_rust_namespace,
+    },
+    Directive {
+        name: "extern_rust_class",
+        parse: parse_extern_rust_class,
+    },
+    Directive {
+        name: SUBCLASS,
+        parse: parse_subclass,
+    },
+    Directive {
+        name: "mock",
+        parse: parse_mock,
+    },
+    Directive {
+        name: "channel_observer",
+        parse: parse_channel_observer,
+    },
+];
+
+#[derive(Debug)]
+pub struct Subclass {
+    pub superclass: String,
+    pub subclass: Ident,
+    /// Whether autocxx should generate the subclass itself, such that
+    /// any number of Rust types can be plugged into it.
+    pub generic: bool,
+}
+
+pub struct RustFun {
+    pub path: RustPath,
+    pub sig: Signature,
+    /// Whether C++ should additionally be able to call this function
+    /// via a wrapper returning `autocxx::Expected`, rather than only
+    /// via a function which throws an exception if it returns an error.
+    pub returns_expected: bool,
+    /// Whether this function creates instances of a Rust type exposed to
+    /// C++, such that C++ should be able to call it via a `make_<type>`
+    /// factory function returning a `rust::Box`.
+    pub is_factory: bool,
+}
+
+impl std::fmt::Debug for RustFun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RustFun")
+            .field("path", &self.path)
+            .field("sig", &self.sig.to_token_stream().to_string())
+            .field("returns_expected", &self.returns_expected)
+            .field("is_factory", &self.is_factory)
+            .finish()
+    }
+}
+
+/// A fieldless Rust enum which is to be made available to C++.
+pub struct RustEnum {
+    pub path: RustPath,
+    pub definition: syn::ItemEnum,
+}
+
+impl std::fmt::Debug for RustEnum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RustEnum")
+            .field("path", &self.path)
+            .field("definition", &self.definition.to_token_stream().to_string())
+            .finish()
+    }
+}
+
+fn parse_extern_rust_type(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let id: Ident = args.parse()?;
+    state.config.rust_types.push(RustPath::new_from_ident(id));
+    Ok(())
+}
+
+fn parse_extern_rust_fun(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let path: RustPath = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let sig: Signature = args.parse()?;
+    let mut returns_expected = false;
+    let mut is_factory = false;
+    if args.parse::<Option<syn::token::Comma>>()?.is_some() {
+        let modifier: Ident = args.parse()?;
+        if modifier == "expected" {
+            returns_expected = true;
+        } else if modifier == "factory" {
+            is_factory = true;
+        } else {
+            return Err(syn::Error::new(
+                modifier.span(),
+                "the only supported modifiers are 'expected' and 'factory'",
+            ));
+        }
+    }
+    state.config.extern_rust_funs.push(RustFun {
+        path,
+        sig,
+        returns_expected,
+        is_factory,
+    });
+    Ok(())
+}
+
+fn parse_extern_rust_enum(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let path: RustPath = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let definition: syn::ItemEnum = args.parse()?;
+    state
+        .config
+        .extern_rust_enums
+        .push(RustEnum { path, definition });
+    Ok(())
+}
+
+fn parse_extern_rust_namespace(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let namespace: LitStr = args.parse()?;
+    state.config.extern_rust_namespace = Some(namespace.value());
+    Ok(())
+}
+
+fn parse_extern_rust_class(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let class: LitStr = args.parse()?;
+    state.config.extern_rust_class = Some(class.value());
+    Ok(())
+}
+
+fn parse_subclass(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let superclass: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let subclass: Ident = args.parse()?;
+    let mut generic = false;
+    if args.parse::<Option<syn::token::Comma>>()?.is_some() {
+        let modifier: Ident = args.parse()?;
+        if modifier != "generic" {
+            return Err(syn::Error::new(modifier.span(), "expected 'generic'"));
+        }
+        generic = true;
+    }
+    state.config.subclasses.push(Subclass {
+        superclass: superclass.value(),
+        subclass,
+        generic,
+    });
+    Ok(())
+}
+
+fn parse_mock(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let mock: LitStr = args.parse()?;
+    state.config.mocks.push(mock.value());
+    Ok(())
+}
+
+fn parse_channel_observer(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    // Checked against the subclass! directives once all are parsed.
+    state.channel_observers.push(args.parse()?);
+    Ok(())
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for path in &config.rust_types {
+        tokens.extend(quote! { rust_type!(#path) });
+    }
+    for i in &config.extern_rust_funs {
+        let p = &i.path;
+        let s = &i.sig;
+        if i.returns_expected {
+            tokens.extend(quote! { extern_rust_fun!(#p,#s,expected) });
+        } else if i.is_factory {
+            tokens.extend(quote! { extern_rust_fun!(#p,#s,factory) });
+        } else {
+            tokens.extend(quote! { extern_rust_fun!(#p,#s) });
+        }
+    }
+    for i in &config.extern_rust_enums {
+        let p = &i.path;
+        let d = &i.definition;
+        tokens.extend(quote! { extern_rust_enum!(#p,#d) });
+    }
+    if let Some(namespace) = &config.extern_rust_namespace {
+        tokens.extend(quote! { extern_rust_namespace!(#namespace) });
+    }
+    if let Some(class) = &config.extern_rust_class {
+        tokens.extend(quote! { extern_rust_class!(#class) });
+    }
+    for i in &config.subclasses {
+        let superclass = &i.superclass;
+        let subclass = &i.subclass;
+        if i.generic {
+            tokens.extend(quote! { subclass!(#superclass,#subclass,generic) });
+        } else {
+            tokens.extend(quote! { subclass!(#superclass,#subclass) });
+        }
+    }
+    for i in &config.mocks {
+        tokens.extend(quote! { mock!(#i) });
+    }
+    for i in &config.channel_observers {
+        tokens.extend(quote! { channel_observer!(#i) });
+    }
+}
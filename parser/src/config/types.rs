@@ -0,0 +1,468 @@
+// Copyright 2020 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Directives which describe C++ types, or how they should be represented
+//! in Rust: `extern_cpp_type!`, `layout!`, `impl_trait!`, `qt_signal!`
+//! and so on.
+
+use proc_macro2::Span;
+use syn::{parse::ParseStream, Ident, LitStr, Result as ParseResult};
+
+use super::{Directive, ParseState};
+use crate::RustPath;
+
+#[cfg(feature = "reproduction_case")]
+use super::IncludeCppConfig;
+#[cfg(feature = "reproduction_case")]
+use quote::quote;
+
+pub(super) static DIRECTIVES: &[Directive] = &[
+    Directive {
+        name: "extern_cpp_opaque_type",
+        parse: parse_extern_cpp_opaque_type,
+    },
+    Directive {
+        name: "extern_cpp_type",
+        parse: parse_extern_cpp_type,
+    },
+    Directive {
+        name: "mirror_type",
+        parse: parse_mirror_type,
+    },
+    Directive {
+        name: "field_metadata",
+        parse: parse_field_metadata,
+    },
+    Directive {
+        name: "raii",
+        parse: parse_raii,
+    },
+    Directive {
+        name: "instantiable",
+        parse: parse_instantiable,
+    },
+    Directive {
+        name: "opaque_only",
+        parse: parse_opaque_only,
+    },
+    Directive {
+        name: "layout",
+        parse: parse_layout,
+    },
+    Directive {
+        name: "impl_trait",
+        parse: parse_impl_trait,
+    },
+    Directive {
+        name: "refcounted",
+        parse: parse_refcounted,
+    },
+    Directive {
+        name: "qt_signal",
+        parse: parse_qt_signal,
+    },
+];
+
+/// A layout asserted by the user for a type, using `layout!`, which
+/// overrides whatever bindgen came up with.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LayoutOverride {
+    /// As `#pragma pack(N)`: fields are aligned to at most N bytes.
+    Packed(usize),
+    /// As `alignas(N)`: the type is aligned to N bytes.
+    Align(usize),
+}
+
+impl LayoutOverride {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        args.parse::<syn::token::Comma>()?;
+        let key: Ident = args.parse()?;
+        args.parse::<syn::token::Eq>()?;
+        let value: syn::LitInt = args.parse()?;
+        let n: usize = value.base10_parse()?;
+        if !n.is_power_of_two() {
+            return Err(syn::Error::new(
+                value.span(),
+                "packing and alignment must be a power of two",
+            ));
+        }
+        if key == "packed" {
+            Ok(Self::Packed(n))
+        } else if key == "align" {
+            Ok(Self::Align(n))
+        } else {
+            Err(syn::Error::new(
+                key.span(),
+                "expected packed = N or align = N",
+            ))
+        }
+    }
+
+    #[cfg(feature = "reproduction_case")]
+    fn key_and_value(&self) -> (Ident, usize) {
+        match self {
+            Self::Packed(n) => (Ident::new("packed", Span::call_site()), *n),
+            Self::Align(n) => (Ident::new("align", Span::call_site()), *n),
+        }
+    }
+}
+
+/// A Qt signal given using `qt_signal!`, which can be connected to a Rust
+/// closure. moc's `signals:` sections are invisible to bindgen (they're
+/// just `public:`), so the user tells us which methods are signals, and
+/// their parameter types so that we can pick the right overload.
+#[derive(Debug, Clone)]
+pub struct QtSignal {
+    /// The class declaring the signal, e.g. `ns::Widget`.
+    pub class: String,
+    pub name: String,
+    /// The C++ types of the signal's parameters, as given.
+    pub cpp_params: Vec<String>,
+    /// The equivalent Rust types.
+    pub rust_params: Vec<Ident>,
+    /// The name of the generated method, e.g. `connect_clicked`. Overloads
+    /// are numbered in the order given, as for other overloaded methods.
+    pub connect_fn: Ident,
+}
+
+impl QtSignal {
+    fn new(signature: &LitStr, previous: &[QtSignal]) -> ParseResult<Self> {
+        let err = |msg: &str| syn::Error::new(signature.span(), msg);
+        let value = signature.value();
+        let (qualified_name, params) = value
+            .trim()
+            .split_once('(')
+            .and_then(|(name, params)| Some((name.trim(), params.strip_suffix(')')?.trim())))
+            .ok_or_else(|| {
+                err("expected a signal signature such as \"Widget::valueChanged(int)\"")
+            })?;
+        let (class, name) = qualified_name
+            .rsplit_once("::")
+            .filter(|(class, name)| !class.is_empty() && syn::parse_str::<Ident>(name).is_ok())
+            .ok_or_else(|| err("expected the signal to be qualified by its class"))?;
+        let cpp_params: Vec<String> = if params.is_empty() || params == "void" {
+            Vec::new()
+        } else {
+            params
+                .split(',')
+                .map(|param| param.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect()
+        };
+        let rust_params = cpp_params
+            .iter()
+            .map(|param| {
+                qt_signal_param_type(param)
+                    .map(|ty| Ident::new(ty, Span::call_site()))
+                    .ok_or_else(|| {
+                        err(&format!(
+                            "unsupported signal parameter type {}; only integers, floating point numbers and bool are supported",
+                            param
+                        ))
+                    })
+            })
+            .collect::<ParseResult<_>>()?;
+        let overloads = previous
+            .iter()
+            .filter(|other| other.class == class && other.name == name)
+            .count();
+        let connect_fn = match overloads {
+            0 => format!("connect_{}", name),
+            n => format!("connect_{}{}", name, n),
+        };
+        Ok(Self {
+            class: class.to_string(),
+            name: name.to_string(),
+            cpp_params,
+            rust_params,
+            connect_fn: Ident::new(&connect_fn, Span::call_site()),
+        })
+    }
+
+    /// The signature as given in `qt_signal!`, modulo whitespace.
+    pub fn signature(&self) -> String {
+        format!(
+            "{}::{}({})",
+            self.class,
+            self.name,
+            self.cpp_params.join(", ")
+        )
+    }
+
+    /// A name which is unique to this signal and may be used in both C++
+    /// and Rust, from which we derive the names of generated helpers.
+    pub fn mangled_name(&self) -> String {
+        format!("{}_{}", self.class.replace("::", "_"), self.connect_fn)
+    }
+}
+
+/// A standard Rust trait to be implemented for a generated type by
+/// delegating to C++ methods, given using `impl_trait!`.
+#[derive(Debug, Clone)]
+pub struct TraitImpl {
+    /// The C++ type, e.g. `ns::Widget`.
+    pub ty: String,
+    /// The trait, as given, e.g. `std::io::Write`.
+    pub trait_path: String,
+    /// Each method of the trait, and the C++ method which implements it,
+    /// e.g. `write` and `ns::Widget::write_bytes`.
+    pub methods: Vec<(Ident, String)>,
+}
+
+impl TraitImpl {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        let ty: LitStr = args.parse()?;
+        args.parse::<syn::token::Comma>()?;
+        let trait_path: LitStr = args.parse()?;
+        let mut methods: Vec<(Ident, String)> = Vec::new();
+        while !args.is_empty() {
+            args.parse::<syn::token::Comma>()?;
+            if args.is_empty() {
+                break;
+            }
+            let method: Ident = args.parse()?;
+            args.parse::<syn::token::Eq>()?;
+            let cpp_method: LitStr = args.parse()?;
+            if methods.iter().any(|(other, _)| *other == method) {
+                return Err(syn::Error::new(
+                    method.span(),
+                    "each method of the trait may only be given once",
+                ));
+            }
+            methods.push((method, cpp_method.value()));
+        }
+        Ok(Self {
+            ty: ty.value(),
+            trait_path: trait_path.value(),
+            methods,
+        })
+    }
+}
+
+/// A reference counted base class, given using `refcounted!`, and the
+/// methods which add and release references.
+#[derive(Debug, Clone)]
+pub struct RefCounting {
+    /// The C++ base class, e.g. `ns::RefCounted`.
+    pub base: String,
+    /// The method which adds a reference, e.g. `AddRef`.
+    pub addref: String,
+    /// The method which releases a reference, e.g. `Release`.
+    pub release: String,
+}
+
+impl RefCounting {
+    fn parse_args(args: ParseStream) -> ParseResult<Self> {
+        let base: LitStr = args.parse()?;
+        let mut addref = None;
+        let mut release = None;
+        while !args.is_empty() {
+            args.parse::<syn::token::Comma>()?;
+            if args.is_empty() {
+                break;
+            }
+            let key: Ident = args.parse()?;
+            args.parse::<syn::token::Eq>()?;
+            let method: LitStr = args.parse()?;
+            let slot = if key == "addref" {
+                &mut addref
+            } else if key == "release" {
+                &mut release
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "expected addref = \"...\" or release = \"...\"",
+                ));
+            };
+            if slot.replace(method.value()).is_some() {
+                return Err(syn::Error::new(key.span(), "given more than once"));
+            }
+        }
+        match (addref, release) {
+            (Some(addref), Some(release)) => Ok(Self {
+                base: base.value(),
+                addref,
+                release,
+            }),
+            _ => Err(syn::Error::new(
+                base.span(),
+                "refcounted! needs both addref = \"...\" and release = \"...\"",
+            )),
+        }
+    }
+}
+
+/// The Rust type which we use for a given C++ type of signal parameter.
+/// These are passed by value, so must be primitives.
+fn qt_signal_param_type(cpp_type: &str) -> Option<&'static str> {
+    Some(match cpp_type {
+        "bool" => "bool",
+        "qint8" | "int8_t" | "signed char" => "i8",
+        "quint8" | "uint8_t" | "uchar" | "unsigned char" => "u8",
+        "qint16" | "int16_t" | "short" => "i16",
+        "quint16" | "uint16_t" | "ushort" | "unsigned short" => "u16",
+        "qint32" | "int32_t" | "int" => "i32",
+        "quint32" | "uint32_t" | "uint" | "unsigned" | "unsigned int" => "u32",
+        "qint64" | "int64_t" | "qlonglong" | "long long" => "i64",
+        "quint64" | "uint64_t" | "qulonglong" | "unsigned long long" => "u64",
+        "float" => "f32",
+        "double" | "qreal" => "f64",
+        _ => return None,
+    })
+}
+
+fn parse_extern_cpp_opaque_type(
+    state: &mut ParseState,
+    args: ParseStream,
+    _: &Ident,
+) -> ParseResult<()> {
+    let opaque_type: LitStr = args.parse()?;
+    state.config.opaque_types.push(opaque_type.value());
+    Ok(())
+}
+
+fn parse_extern_cpp_type(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let cpp_type: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let rust_path: RustPath = args.parse()?;
+    state
+        .config
+        .extern_cpp_types
+        .push((cpp_type.value(), rust_path));
+    Ok(())
+}
+
+fn parse_mirror_type(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let cpp_type: LitStr = args.parse()?;
+    args.parse::<syn::token::Comma>()?;
+    let rust_path: RustPath = args.parse()?;
+    state
+        .config
+        .mirror_types
+        .push((cpp_type.value(), rust_path));
+    Ok(())
+}
+
+fn parse_field_metadata(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let ty: LitStr = args.parse()?;
+    state.config.field_metadata.push(ty.value());
+    Ok(())
+}
+
+fn parse_raii(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let raii_type: LitStr = args.parse()?;
+    state.config.raii_types.push(raii_type.value());
+    Ok(())
+}
+
+fn parse_instantiable(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let instantiable_type: LitStr = args.parse()?;
+    state
+        .config
+        .instantiable_types
+        .push(instantiable_type.value());
+    Ok(())
+}
+
+fn parse_opaque_only(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let opaque_only_type: LitStr = args.parse()?;
+    state
+        .config
+        .opaque_only_types
+        .push(opaque_only_type.value());
+    Ok(())
+}
+
+fn parse_layout(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let ty: LitStr = args.parse()?;
+    let layout = LayoutOverride::parse_args(args)?;
+    if state
+        .config
+        .layout_overrides
+        .iter()
+        .any(|(other, _)| *other == ty.value())
+    {
+        return Err(syn::Error::new(
+            ty.span(),
+            "only one layout! may be given for each type, since Rust can't combine packed and align",
+        ));
+    }
+    state.config.layout_overrides.push((ty.value(), layout));
+    Ok(())
+}
+
+fn parse_impl_trait(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    state.config.trait_impls.push(TraitImpl::parse_args(args)?);
+    Ok(())
+}
+
+fn parse_refcounted(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    state
+        .config
+        .ref_countings
+        .push(RefCounting::parse_args(args)?);
+    Ok(())
+}
+
+fn parse_qt_signal(state: &mut ParseState, args: ParseStream, _: &Ident) -> ParseResult<()> {
+    let signature: LitStr = args.parse()?;
+    let signal = QtSignal::new(&signature, &state.config.qt_signals)?;
+    state.config.qt_signals.push(signal);
+    Ok(())
+}
+
+#[cfg(feature = "reproduction_case")]
+pub(super) fn to_tokens(config: &IncludeCppConfig, tokens: &mut proc_macro2::TokenStream) {
+    for i in &config.opaque_types {
+        tokens.extend(quote! { extern_cpp_opaque_type!(#i) });
+    }
+    for (ty, rust_path) in &config.extern_cpp_types {
+        tokens.extend(quote! { extern_cpp_type!(#ty, #rust_path) });
+    }
+    for (ty, rust_path) in &config.mirror_types {
+        tokens.extend(quote! { mirror_type!(#ty, #rust_path) });
+    }
+    for i in &config.field_metadata {
+        tokens.extend(quote! { field_metadata!(#i) });
+    }
+    for i in &config.raii_types {
+        tokens.extend(quote! { raii!(#i) });
+    }
+    for i in &config.instantiable_types {
+        tokens.extend(quote! { instantiable!(#i) });
+    }
+    for i in &config.opaque_only_types {
+        tokens.extend(quote! { opaque_only!(#i) });
+    }
+    for (ty, layout) in &config.layout_overrides {
+        let (key, value) = layout.key_and_value();
+        let value = proc_macro2::Literal::usize_unsuffixed(value);
+        tokens.extend(quote! { layout!(#ty, #key = #value) });
+    }
+    for trait_impl in &config.trait_impls {
+        let ty = &trait_impl.ty;
+        let trait_path = &trait_impl.trait_path;
+        let (methods, cpp_methods): (Vec<_>, Vec<_>) = trait_impl.methods.iter().cloned().unzip();
+        tokens.extend(quote! {
+            impl_trait!(#ty, #trait_path #(, #methods = #cpp_methods)*)
+        });
+    }
+    for ref_counting in &config.ref_countings {
+        let RefCounting {
+            base,
+            addref,
+            release,
+        } = ref_counting;
+        tokens.extend(quote! {
+            refcounted!(#base, addref = #addref, release = #release)
+        });
+    }
+    for signal in &config.qt_signals {
+        let signature = signal.signature();
+        tokens.extend(quote! { qt_signal!(#signature) });
+    }
+}
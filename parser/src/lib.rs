@@ -13,7 +13,11 @@ pub mod file_locations;
 mod path;
 mod subclass_attrs;
 
-pub use config::{IncludeCppConfig, RustFun, Subclass, UnsafePolicy};
+pub use config::{
+    is_rust_keyword, is_supported_cpp_standard, BindgenDerive, DeprecationPolicy, ExceptionPolicy,
+    IncludeCppConfig, Inclusion, LayoutOverride, QtSignal, RefCounting, RustEnum, RustFun,
+    StringReturns, Subclass, TraitImpl, UnsafePolicy, CURRENT_MANGLING_VERSION,
+};
 use file_locations::FileLocationStrategy;
 pub use path::RustPath;
 use proc_macro2::TokenStream as TokenStream2;
@@ -29,7 +33,7 @@ use syn::{
 /// and the standalone macro discoverer
 pub mod directives {
     pub static EXTERN_RUST_TYPE: &str = "extern_rust_type";
-    pub static EXTERN_RUST_FUN: &str = "extern_rust_fun";
+    pub static EXTERN_RUST_FUN: &str = "extern_rust_function";
     pub static SUBCLASS: &str = "subclass";
 }
 
@@ -9,7 +9,9 @@
 use crate::ParseResult;
 use proc_macro2::Ident;
 use quote::{quote, ToTokens, TokenStreamExt};
+use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream};
+use syn::Token;
 
 /// A little like [`syn::Path`] but simpler - contains only identifiers,
 /// no path arguments. Guaranteed to always have at least one identifier.
@@ -48,7 +50,12 @@ impl ToTokens for RustPath {
 
 impl Parse for RustPath {
     fn parse(input: ParseStream) -> ParseResult<Self> {
-        let id: Ident = input.parse()?;
+        // Paths may be relative to the crate root, but `crate` is a keyword.
+        let id: Ident = if input.peek(Token![crate]) {
+            input.call(Ident::parse_any)?
+        } else {
+            input.parse()?
+        };
         let mut p = RustPath::new_from_ident(id);
         while input.parse::<Option<syn::token::Colon2>>()?.is_some() {
             let id: Ident = input.parse()?;
@@ -0,0 +1,110 @@
+//! Rust types for Eigen's fixed-size vectors and matrices, such as
+//! `Eigen::Vector3f` or `Eigen::Matrix4d`, backed by the equivalent
+//! [nalgebra](https://nalgebra.org) or [glam](https://docs.rs/glam) types.
+//! Each is ABI-compatible with the Eigen type, so can be passed by value
+//! between Rust and C++. To use them, enable the `nalgebra` or `glam`
+//! feature of this crate, then name them using `extern_cpp_type!`:
+//!
+//! ```ignore
+//! include_cpp! {
+//!     #include "geometry.h"
+//!     extern_cpp_type!("Eigen::Vector3f", autocxx::eigen::glam::Vector3f)
+//!     generate!("transform")
+//! }
+//! ```
+//!
+//! Each type derefs to the nalgebra or glam type, and converts to and from
+//! it using [`From`]. Like Eigen, all these types are column-major. They
+//! assume Eigen's default alignment, which is 16 bytes for any type whose
+//! size is a multiple of 16 bytes; this is checked at C++ compile time.
+
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+macro_rules! eigen_types {
+    ($($name:ident($cpp_name:literal, $inner:ty, $size:literal, $align:literal);)*) => {
+        $(
+            #[doc = concat!("Eigen's `", $cpp_name, "`.")]
+            #[repr(C, align($align))]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            pub struct $name(pub $inner);
+
+            unsafe impl cxx::ExternType for $name {
+                type Id = cxx::type_id!($cpp_name);
+                type Kind = cxx::kind::Trivial;
+            }
+
+            const _: () = assert!(
+                std::mem::size_of::<$name>() == $size && std::mem::align_of::<$name>() == $align
+            );
+
+            impl From<$inner> for $name {
+                fn from(inner: $inner) -> Self {
+                    Self(inner)
+                }
+            }
+
+            impl From<$name> for $inner {
+                fn from(outer: $name) -> Self {
+                    outer.0
+                }
+            }
+
+            impl std::ops::Deref for $name {
+                type Target = $inner;
+                fn deref(&self) -> &$inner {
+                    &self.0
+                }
+            }
+
+            impl std::ops::DerefMut for $name {
+                fn deref_mut(&mut self) -> &mut $inner {
+                    &mut self.0
+                }
+            }
+        )*
+    };
+}
+
+/// Eigen types backed by [nalgebra](https://nalgebra.org) types.
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra {
+    eigen_types! {
+        Vector2f("Eigen::Vector2f", ::nalgebra::Vector2<f32>, 8, 4);
+        Vector3f("Eigen::Vector3f", ::nalgebra::Vector3<f32>, 12, 4);
+        Vector4f("Eigen::Vector4f", ::nalgebra::Vector4<f32>, 16, 16);
+        Vector2d("Eigen::Vector2d", ::nalgebra::Vector2<f64>, 16, 16);
+        Vector3d("Eigen::Vector3d", ::nalgebra::Vector3<f64>, 24, 8);
+        Vector4d("Eigen::Vector4d", ::nalgebra::Vector4<f64>, 32, 16);
+        Matrix2f("Eigen::Matrix2f", ::nalgebra::Matrix2<f32>, 16, 16);
+        Matrix3f("Eigen::Matrix3f", ::nalgebra::Matrix3<f32>, 36, 4);
+        Matrix4f("Eigen::Matrix4f", ::nalgebra::Matrix4<f32>, 64, 16);
+        Matrix2d("Eigen::Matrix2d", ::nalgebra::Matrix2<f64>, 32, 16);
+        Matrix3d("Eigen::Matrix3d", ::nalgebra::Matrix3<f64>, 72, 8);
+        Matrix4d("Eigen::Matrix4d", ::nalgebra::Matrix4<f64>, 128, 16);
+    }
+}
+
+/// Eigen types backed by [glam](https://docs.rs/glam) types.
+#[cfg(feature = "glam")]
+pub mod glam {
+    eigen_types! {
+        Vector2f("Eigen::Vector2f", ::glam::Vec2, 8, 4);
+        Vector3f("Eigen::Vector3f", ::glam::Vec3, 12, 4);
+        Vector4f("Eigen::Vector4f", ::glam::Vec4, 16, 16);
+        Vector2d("Eigen::Vector2d", ::glam::DVec2, 16, 16);
+        Vector3d("Eigen::Vector3d", ::glam::DVec3, 24, 8);
+        Vector4d("Eigen::Vector4d", ::glam::DVec4, 32, 16);
+        Matrix2f("Eigen::Matrix2f", ::glam::Mat2, 16, 16);
+        Matrix3f("Eigen::Matrix3f", ::glam::Mat3, 36, 4);
+        Matrix4f("Eigen::Matrix4f", ::glam::Mat4, 64, 16);
+        Matrix2d("Eigen::Matrix2d", ::glam::DMat2, 32, 16);
+        Matrix3d("Eigen::Matrix3d", ::glam::DMat3, 72, 8);
+        Matrix4d("Eigen::Matrix4d", ::glam::DMat4, 128, 16);
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// An iterator over the elements of a C++ container which offers indexed
+/// access, such as a class with `size()` and `at(size_t)` methods. autocxx
+/// generates `IntoIterator` implementations which return this when asked
+/// to using `impl_trait!`, so that such containers may be used in `for`
+/// loops.
+pub struct IndexedIter<'a, C, T: ?Sized> {
+    container: &'a C,
+    get: fn(&'a C, usize) -> &'a T,
+    index: usize,
+    len: usize,
+}
+
+impl<'a, C, T: ?Sized> IndexedIter<'a, C, T> {
+    /// Creates an iterator over the first `len` elements of `container`,
+    /// each of which is retrieved using `get`.
+    pub fn new(container: &'a C, len: usize, get: fn(&'a C, usize) -> &'a T) -> Self {
+        Self {
+            container,
+            get,
+            index: 0,
+            len,
+        }
+    }
+}
+
+impl<'a, C, T: ?Sized> Iterator for IndexedIter<'a, C, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+        let item = (self.get)(self.container, self.index);
+        self.index += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, C, T: ?Sized> DoubleEndedIterator for IndexedIter<'a, C, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+        self.len -= 1;
+        Some((self.get)(self.container, self.len))
+    }
+}
+
+impl<'a, C, T: ?Sized> ExactSizeIterator for IndexedIter<'a, C, T> {}
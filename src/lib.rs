@@ -14,8 +14,13 @@
 // do anything - all the magic is handled entirely by
 // autocxx_macro::include_cpp_impl.
 
+#[cfg(any(feature = "nalgebra", feature = "glam"))]
+pub mod eigen;
+mod iter;
+mod refcounted;
 pub mod subclass;
 mod value_param;
+mod vector;
 
 #[allow(unused_imports)] // doc cross-reference only
 use autocxx_engine::IncludeCppEngine;
@@ -63,7 +68,9 @@ use autocxx_engine::IncludeCppEngine;
 /// Within the braces of the `include_cpp!{...}` macro, you should provide
 /// a list of at least the following:
 ///
-/// * `#include "cpp_header.h"`: a header filename to parse and include
+/// * `#include "cpp_header.h"`: a header filename to parse and include.
+///   Third-party headers can be given as `#include <lib/header.h>`, in which
+///   case warnings from them are suppressed when compiling the generated C++.
 /// * `generate!("type_or_function_name")`: a type or function name whose declaration
 ///   should be made available to C++. (See the section on Allowlisting, below).
 /// * Optionally, `safety!(unsafe)` - see discussion of [`safety`].
@@ -98,20 +105,38 @@ use autocxx_engine::IncludeCppEngine;
 /// [IncludeCppEngine].
 #[macro_export]
 macro_rules! include_cpp {
-    (
-        $(#$include:ident $lit:literal)*
-        $($mac:ident!($($arg:tt)*))*
-    ) => {
-        $($crate::$include!{__docs})*
-        $($crate::$mac!{__docs})*
-        $crate::include_cpp_impl! {
-            $(#include $lit)*
-            $($mac!($($arg)*))*
-        }
+    ($($tt:tt)*) => {
+        $crate::include_cpp_docs! { $($tt)* }
+        $crate::include_cpp_impl! { $($tt)* }
+    };
+}
+
+/// Refers to the macro for each directive within [include_cpp], so that
+/// IDEs can show its documentation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! include_cpp_docs {
+    (@system > $($rest:tt)*) => {
+        $crate::include_cpp_docs! { $($rest)* }
+    };
+    (@system $skip:tt $($rest:tt)*) => {
+        $crate::include_cpp_docs! { @system $($rest)* }
+    };
+    (#$include:ident < $($rest:tt)*) => {
+        $crate::$include! { __docs }
+        $crate::include_cpp_docs! { @system $($rest)* }
+    };
+    (#$include:ident $lit:literal $($rest:tt)*) => {
+        $crate::$include! { __docs }
+        $crate::include_cpp_docs! { $($rest)* }
+    };
+    ($($mac:ident!($($arg:tt)*))*) => {
+        $($crate::$mac! { __docs })*
     };
 }
 
-/// Include a C++ header. A directive to be included inside
+/// Include a C++ header, using either `#include "header.h"` or
+/// `#include <header.h>`. A directive to be included inside
 /// [include_cpp] - see [include_cpp] for details
 #[macro_export]
 macro_rules! include {
@@ -122,11 +147,31 @@ macro_rules! include {
 /// A directive to be included inside
 /// [include_cpp] - see [include_cpp] for general information.
 /// See also [generate_pod].
+///
+/// Rustdoc for the generated item may be given using e.g.
+/// `generate!("Widget", doc = "Handle to the native widget")`. Any
+/// comment on the item in the C++ header follows it.
 #[macro_export]
 macro_rules! generate {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Generate Rust bindings for the given C++ type or function, but only
+/// if a cargo feature of the crate is enabled, e.g.
+/// `generate_if!("experimental", "mylib::NewThing")`. This allows one
+/// [include_cpp] block to serve both minimal and full builds. The feature
+/// is checked when bindings are generated, which is by a `build.rs`; tools
+/// such as `autocxx-gen` run outside cargo treat all features as disabled.
+/// Rust wrapper functions are marked with the corresponding
+/// `#[cfg(feature = "...")]` attribute. See also the `cfg!` directive.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! generate_if {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// Generate as "plain old data" and add to allowlist.
 /// Generate Rust bindings for the given C++ type such that
 /// it can be passed and owned by value in Rust. This only works
@@ -134,6 +179,9 @@ macro_rules! generate {
 /// destructor - you'll encounter a compile error otherwise.
 /// If your type doesn't match that description, use [generate]
 /// instead, and own the type using [UniquePtr][autocxx_engine::cxx::UniquePtr].
+/// Fields whose types can't themselves be POD are represented as opaque
+/// blobs, with accessor methods, so long as their layout is known.
+/// As with [generate], rustdoc may be given using `doc = "..."`.
 /// A directive to be included inside
 /// [include_cpp] - see [include_cpp] for general information.
 #[macro_export]
@@ -141,6 +189,171 @@ macro_rules! generate_pod {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// As [generate_pod], but insists that the types of all fields, and of
+/// their fields in turn, are also POD - no field may be represented as
+/// an opaque blob. If that's not possible, the error lists every such
+/// field, and why it can't be POD, rather than just the first.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! generate_pod_recursive {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// As [generate_pod], but for types which C++ doesn't consider trivially
+/// relocatable - typically because they have a destructor which does
+/// nothing of consequence, such as logging. You are promising that it's
+/// safe to move such a type around using `memcpy`; if that's not true,
+/// the results are undefined. The generated Rust type's `Drop`
+/// implementation calls the C++ destructor.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! force_pod_unsafe {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Assert the packing or alignment of a type, for types using
+/// `#pragma pack` or `alignas` where the layout otherwise generated is
+/// wrong. Use `layout!("Foo", packed = N)` to represent the type
+/// using `#[repr(C, packed(N))]`, or `layout!("Foo", align = N)` for
+/// `#[repr(C, align(N))]`. Rust can't combine the two. The generated C++
+/// checks that its own idea of the type's alignment agrees.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! layout {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Connect a Qt signal to Rust closures, e.g.
+/// `qt_signal!("Slider::valueChanged(int)")`. This generates a method
+/// `connect_valueChanged` on `Slider`, which must itself be generated,
+/// taking a closure with the signal's parameters. Only signals with
+/// integer, floating point or `bool` parameters are supported.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! qt_signal {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Stop reporting a kind of problem during code generation, given its
+/// code, e.g. `silence_error!("ACX0036")` to stop reporting private
+/// methods. The affected items are still skipped, and their
+/// documentation still explains why.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! silence_error {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose the C++ standard, e.g. `cpp_standard!("c++20")`, for just this
+/// [include_cpp], for when some headers need a later standard than the
+/// rest of the build. It's used both when parsing the headers, and when
+/// `autocxx_build` compiles the C++ generated for this [include_cpp].
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! cpp_standard {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Present strings returned from C++ as native Rust strings.
+/// With `string_returns!(str)`, functions returning `const std::string&`
+/// return `&str` instead of `&CxxString`, panicking if the string isn't
+/// UTF-8. With `string_returns!(cow)` they return `Cow<str>`, replacing
+/// any invalid UTF-8. Either way, functions returning `std::string` by
+/// value return `String` instead of `UniquePtr<CxxString>`.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! string_returns {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose what happens when a C++ exception or Rust panic reaches one of
+/// the `extern` functions which autocxx generates itself rather than
+/// through cxx, such as those for [thin_call] and the C wrappers for
+/// `extern_rust_function`s. With `exception_policy!(terminate)`, the
+/// default, they're `extern "C"` and `noexcept`, so the program is
+/// terminated. With `exception_policy!(propagate)`, they're
+/// `extern "C-unwind"` and not `noexcept`, so the exception or panic
+/// unwinds through to the caller. That needs Rust 1.71 or later.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! exception_policy {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// What to do about C++ functions marked `[[deprecated]]`.
+/// With `deprecation_policy!(generate)`, the default, they're generated
+/// like any other function. With `deprecation_policy!(warn)`, they're
+/// marked `#[deprecated]`, with any message given in C++, so that
+/// calling them from Rust gives a warning. With
+/// `deprecation_policy!(skip)`, no bindings are generated for them.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! deprecation_policy {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Acknowledge that bindings can't be generated for an item, giving
+/// the reason, e.g.
+/// `expect_ignored!("ns::Widget::weird_fn", "uses std::variant")`.
+/// The item is then no longer reported during the build, and doesn't
+/// cause [strict] mode to fail. Methods are named after their type.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! expect_ignored {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Fail the build if bindings can't be generated for any item, unless
+/// that item was acknowledged using [expect_ignored], or that kind of
+/// problem was silenced using [silence_error]. This guards against
+/// bindings silently disappearing, e.g. when headers change. Items
+/// which are expected not to have bindings, such as private methods,
+/// don't count.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! strict {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate bindings for functions in anonymous namespaces, which have
+/// internal linkage and so can't normally be called from other
+/// translation units. Without this, such functions are reported as
+/// ignored. With it, each is called through a shim in the C++ which
+/// autocxx generates, which includes the header and therefore gets its
+/// own copy of the function. This only works for functions defined in
+/// the header itself, and each translation unit's copy has its own
+/// `static` variables, if any.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! internal_linkage_shims {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate Rust bindings for all the C++ types and functions declared
+/// in one of the headers named by an `#include`, e.g.
+/// `generate_from_header!("widget.h")`, as if each had been named using
+/// [generate]. Items declared in other headers which it includes aren't
+/// generated unless they're needed. May be combined with [generate],
+/// and used more than once.
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! generate_from_header {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// Generate Rust bindings for all C++ types and functions
 /// found. Highly experimental and not recommended.
 /// A directive to be included inside
@@ -160,6 +373,8 @@ macro_rules! pod {
 
 /// Skip the normal generation of a `make_string` function
 /// and other utilities which we might generate normally.
+/// (These are in any case only generated if some generated API
+/// involves `std::string`.)
 /// A directive to be included inside
 /// [include_cpp] - see [include_cpp] for general information.
 #[macro_export]
@@ -181,10 +396,30 @@ macro_rules! block {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Entirely exclude a C++ namespace, and all the namespaces nested
+/// within it, e.g. `block_ns!("mylib::internal")`. Nothing within the
+/// namespace is generated, reported or even analyzed, which is useful
+/// for private implementation namespaces, especially alongside
+/// [generate_all]. As with [block], any functions or methods elsewhere
+/// which take or return a type from the namespace are also blocked.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! block_ns {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 /// Avoid generating implicit constructors for this type.
 /// The rules for when to generate C++ implicit constructors
 /// are complex, and if autocxx gets it wrong, you can block
-/// such constructors using this.
+/// such constructors using this, e.g. `block_constructors!("Foo")`.
+///
+/// Alternatively, give a signature to avoid generating one specific
+/// constructor, whether implicit or not, e.g.
+/// `block_constructors!("Foo(int, const std::string&)")`.
+/// Parameter types must be spelled as autocxx would spell them in C++,
+/// though whitespace is ignored.
 ///
 /// A directive to be included inside
 /// [include_cpp] - see [include_cpp] for general information.
@@ -272,6 +507,568 @@ macro_rules! subclass {
     ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
 }
 
+/// Generate a Rust mock implementation of the `_methods` trait for a
+/// C++ superclass, e.g. `mock!("Observer")` generates `MockObserver`
+/// which implements `Observer_methods`. The mock records the name of
+/// each method called, and each method's behavior can be programmed
+/// using `expect_<method>`. This allows Rust code which is generic over
+/// the `_methods` trait to be unit tested without calling into the real
+/// C++ implementation.
+///
+/// The mock exists only on the Rust side: no C++ implementation of the
+/// superclass is generated, so a mock can't be passed to C++ code, and
+/// code which calls the C++ methods directly (rather than through the
+/// trait) can't use it. The superclass must also be used in a
+/// [`subclass`] directive, since the mock implements the trait which that
+/// generates.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! mock {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Forwards the callbacks made to a C++ observer interface into a
+/// `std::sync::mpsc` channel, e.g. `channel_observer!("GoatObserver")`.
+/// The superclass must also be used in a generic [`subclass`] directive,
+/// such as `subclass!("GoatObserver", AnyGoatObserver, generic)`.
+/// autocxx then generates a `GoatObserverEvent` enum with a variant for
+/// each virtual method, and `AnyGoatObserver::channel()`, which returns
+/// a subclass instance along with the `Receiver` of those events.
+///
+/// Only methods which return nothing and whose arguments borrow nothing
+/// can be forwarded. Other methods call the superclass implementation,
+/// or panic if they're pure virtual.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! channel_observer {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Pins the scheme used to name the shims which autocxx generates, which
+/// determines the names of the symbols exported from them, e.g.
+/// `mangling_version!(1)`. If you ship a library whose exported symbols
+/// must remain stable across autocxx upgrades, use this: future versions of
+/// autocxx will continue to generate names using the pinned scheme, or fail
+/// to build if they no longer support it. The versions are:
+/// * 1: the original scheme.
+/// * 2 (the latest): as 1, except that names too long for some toolchains
+///   are shortened to a prefix followed by a stable hash.
+///
+/// `autocxx-gen --symbols-file` can be used to report on any changes in the
+/// symbols within the final generated C++ between runs, whether caused by
+/// the naming scheme or otherwise.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! mangling_version {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Inject some C++ before any of the headers are included, e.g.
+/// `prelude!("#define NOMINMAX")`. Some SDK headers require certain
+/// macros to be defined, or other headers to have been included, first.
+/// The prelude is placed before the `#include`s both when parsing the
+/// headers and in the C++ which autocxx generates. May be used
+/// multiple times.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! prelude {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Control the order in which a header is included, e.g.
+/// `include_priority!("windows.h", -10)`. Headers are included in
+/// ascending order of priority; headers without a priority have priority
+/// 0, and headers with the same priority are included in the order listed.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! include_priority {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare a C++ type which may only be forward declared in the
+/// included headers, e.g. `extern_cpp_opaque_type!("Foo")`. The type
+/// is always represented in Rust as an opaque type, so functions which
+/// take or return it by pointer, by reference, or in a `UniquePtr` can
+/// still be generated. The C++ compiler checks that the type is complete
+/// only where that's actually required, for example where a `UniquePtr`
+/// of it is dropped.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! extern_cpp_opaque_type {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Declare that a C++ type is ABI-identical to an existing Rust type, e.g.
+/// `extern_cpp_type!("fixed::Q16", fixed_point::Q16)`, such that it can be
+/// shared between crates without copying. Wherever the C++ type is used,
+/// the generated bindings use the Rust type instead. That type must
+/// implement `cxx::ExternType` with the C++ name as its `Id` and
+/// `cxx::kind::Trivial` as its `Kind`. The generated code checks that the
+/// two types have the same size and alignment. Methods of the C++ type
+/// aren't generated, since we can't add them to a Rust type from another
+/// crate.
+///
+/// The C++ type may also be a typedef. For Eigen's fixed-size vectors and
+/// matrices, such as `Eigen::Vector3f`, the `nalgebra` and `glam` features
+/// of this crate provide suitable Rust types in the `autocxx::eigen` module.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! extern_cpp_type {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate metadata describing the fields of a POD type, e.g.
+/// `field_metadata!("Point")`. The type will implement
+/// [`FieldMetadataProvider`], which returns the name, offset and size of
+/// each field. This is useful for interoperating with C++ reflection
+/// or serialization frameworks which expect tables of field offsets.
+/// The type must also be requested using `generate_pod!`; no metadata is
+/// generated for non-POD types.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! field_metadata {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate conversions and comparisons between a POD type and an
+/// existing Rust type with the same fields, e.g.
+/// `mirror_type!("geo::Point", crate::Point)`. This implements `From`
+/// in both directions, converting each field using `Into`, and
+/// `PartialEq` in both directions, so that tests can compare values
+/// across the boundary. The Rust type must have a field of the same name
+/// for each field of the C++ type. The C++ type must also be requested
+/// using `generate_pod!`. If the C++ type has base classes or padding,
+/// it can't be built from the Rust type, so only conversion into the Rust
+/// type is generated.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! mirror_type {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate a concrete Rust type for an instantiation of a C++ class
+/// template, e.g. `concrete!("Factory<Widget>", FactoryWidget)`, along
+/// with bindings for the public static member functions of the template,
+/// such that `ffi::FactoryWidget::create()` calls
+/// `Factory<Widget>::create()`. Like other instantiations of templates,
+/// the type is opaque. Overloaded static member functions, and those
+/// which are themselves templates, aren't generated.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! concrete {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Implement a standard Rust trait for a generated type by delegating to
+/// its C++ methods, e.g.
+/// `impl_trait!("Widget", "std::io::Write", write = "Widget::write_bytes", flush = "Widget::flush")`.
+/// Each method of the trait must be given, and the C++ methods must have
+/// compatible signatures, which is checked when generating bindings.
+/// Supported traits are:
+/// * `std::io::Write`: `write` takes `(const uint8_t*, size_t)` and returns
+///   `size_t`, and `flush` takes nothing and returns `void`.
+/// * `std::io::Read`: `read` takes `(uint8_t*, size_t)` and returns `size_t`.
+/// * `std::fmt::Display`: `fmt` is a const method taking nothing and
+///   returning a `std::string`.
+/// * `IntoIterator`: implemented for `&Widget`, so that a container type can
+///   be used in `for` loops. `len` is a const method taking nothing and
+///   returning `size_t`, and `get` is a const method taking a `size_t` index
+///   and returning a const reference, e.g.
+///   `impl_trait!("IntList", "IntoIterator", len = "IntList::size", get = "IntList::at")`.
+///   [`cxx::CxxVector`] already implements `IntoIterator` for references.
+///
+/// For non-POD types, the `std::io` traits are implemented for
+/// `Pin<&mut Widget>`, since you can't have a `&mut Widget`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! impl_trait {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Manage the lifetime of classes deriving from a reference counted base
+/// class using its reference counting methods, e.g.
+/// `refcounted!("RefCountedBase", addref = "AddRef", release = "Release")`.
+/// Such classes, and the base class itself, then implement [RefCounted],
+/// so they may be held in a [Handle] whose `Clone` adds a reference and whose
+/// `Drop` releases one, matching the library's own ownership model rather
+/// than that of [UniquePtr][autocxx_engine::cxx::UniquePtr]. Handles are
+/// created from raw pointers, e.g. those returned by C++ factory functions,
+/// using [Handle::adopt] or [Handle::retain]. The methods may be `const`
+/// and must take no parameters.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! refcounted {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Register a C++ function which must be called to initialize a library
+/// before any other calls are made into it, e.g. `init_function!("lib_init")`.
+/// The function must take no parameters. If any are registered, the
+/// generated mod will contain an `initialize` function which calls each of
+/// them once, in the order listed. May be used multiple times.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! init_function {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Register a C++ function which must be called to shut down a library,
+/// e.g. `shutdown_function!("lib_shutdown")`. The function must take no
+/// parameters. If any are registered, the generated mod will contain a
+/// `shutdown` function which calls each of them once, in the reverse of the
+/// order listed, in the same way that C++ destroys static objects in the
+/// reverse order of their construction. May be used multiple times.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! shutdown_function {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate an RAII guard for a C++ type whose whole purpose is
+/// scope-based, such as a lock guard or a profiling scope, e.g.
+/// `raii!("ScopedTimer")`. This generates a `ScopedTimerGuard` type,
+/// created using any of the type's constructors, for example
+/// `ScopedTimerGuard::new(ScopedTimer::new("name"))`. The C++ object
+/// is constructed in place, and destroyed when the guard is dropped.
+/// Such types needn't be copyable or movable. Template instantiations
+/// aren't currently supported, since we don't generate their
+/// constructors; instead, name a subclass or a wrapper type.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! raii {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate a batched form of a hot function or method, e.g.
+/// `batch!("Canvas::draw_point")`, so that a tight Rust loop needn't
+/// cross into C++ once per call. This generates a `Canvas_draw_point_args`
+/// struct with a field for each parameter, and a `draw_point_batch` method
+/// taking a slice of them, which calls `draw_point` for each in turn from
+/// C++. Only functions returning nothing, and whose parameters are all
+/// numbers or `bool`s, can be batched. The function or its type must also
+/// be generated.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! batch {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Call a simple getter through a plain `extern "C"` function instead of
+/// the usual cxx thunk, e.g. `thin_call!("Point::get_x")`. The C++ getter
+/// is inlined into that function, so this can noticeably reduce overhead
+/// in getter-heavy code. Only const methods which take no parameters and
+/// return a number or `bool` can be called this way; the method's type
+/// must also be generated.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! thin_call {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Give a method which isn't marked `const`, but doesn't in fact modify its
+/// object, a `&self` receiver instead of `self: Pin<&mut Self>`, e.g.
+/// `treat_as_const!("Widget::name")`. The generated C++ calls the method
+/// through a `const_cast`. autocxx has no way to check this: if the method
+/// does modify the object, the behavior is undefined, since Rust assumes
+/// that nothing changes behind a shared reference.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! treat_as_const {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Additionally generate a variant of a function or method which takes
+/// shared references where the C++ takes non-const references, e.g.
+/// `shared_ref_variant!("Canvas::draw")` generates `draw_shared(&self, shape: &Shape)`
+/// alongside `draw(self: Pin<&mut Self>, shape: Pin<&mut Shape>)`. Free functions
+/// are named with their namespace, e.g. `shared_ref_variant!("geo::measure")`.
+/// The variant passes its arguments to C++ through a `const_cast`, so,
+/// as with [treat_as_const], only use this for functions which are known
+/// not to modify those arguments; otherwise the behavior is undefined.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! shared_ref_variant {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Promise that a function or method returning a raw pointer, e.g.
+/// `Widget* Factory::make()`, transfers ownership of the object to the
+/// caller, e.g. `returns_owned!("Factory::make")`. It will then return a
+/// [UniquePtr][autocxx_engine::cxx::UniquePtr] which deletes the object
+/// when dropped, rather than a raw pointer which is assumed to be
+/// borrowed. Free functions are named with their namespace, e.g.
+/// `returns_owned!("geo::CreateShape")`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! returns_owned {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Promise that a raw pointer parameter of a function or method takes
+/// ownership of the object to which it points, e.g.
+/// `consumes!("Sink::adopt", arg = 1)` for `void Sink::adopt(Widget* w)`.
+/// Parameters are numbered from 1, not counting the receiver of a method.
+/// The function will then take a [UniquePtr][autocxx_engine::cxx::UniquePtr],
+/// which is released into the call, so that ownership visibly passes to
+/// C++. The opposite of [returns_owned].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! consumes {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Also make a free function available as an associated function of a
+/// type, e.g. `associate_fn!("CreateWidget", "Widget::create")` allows
+/// `ffi::Widget::create()` as well as `ffi::CreateWidget()`. This is useful
+/// for factory functions, which are easier to find alongside the type they
+/// create. Both the function and the type must also be generated.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! associate_fn {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose which constructor of a type should be called `new` (and
+/// `make_unique`), e.g. `canonical_constructor!("Foo(const std::string&)")`.
+/// Normally, overloaded constructors are called `new`, `new1`, `new2` etc.
+/// in the order in which they're declared; with this directive, the other
+/// constructors are numbered instead. The signature is specified as for
+/// [block_constructors].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! canonical_constructor {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Assert that a type is not abstract, e.g. `instantiable!("Foo")`.
+/// autocxx doesn't generate constructors for types which it believes
+/// to be abstract, because they have (or inherit) pure virtual functions.
+/// It can occasionally get this wrong, for instance if it can't see
+/// that such a function is overridden. This directive forces constructors
+/// to be generated. If the type is in fact abstract, the generated C++
+/// will fail to compile with an explanatory message.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! instantiable {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate a type without any of its methods or constructors, e.g.
+/// `opaque_only!("HugeMatrix")`, for types which are only needed so that
+/// other functions can take or return them. The type is generated as an
+/// opaque type, as if it had been named in a [generate] directive, but
+/// none of its member functions are, even when using [generate_all].
+/// This can substantially reduce the amount of generated code for big
+/// classes.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! opaque_only {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose the C++ namespace in which Rust types and functions exposed
+/// using [`extern_rust::extern_rust_type`] and
+/// [`extern_rust::extern_rust_function`] are declared, e.g.
+/// `extern_rust_namespace!("mylib::api")`. By default they're declared
+/// in the global namespace.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! extern_rust_namespace {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Additionally group the Rust functions and types exposed to C++ into a
+/// C++ class, e.g. `extern_rust_class!("RustApi")`, for C++ codebases
+/// whose convention is to call `RustApi::do_thing()`. Each function
+/// becomes a static member function, and each type (other than enums)
+/// becomes a member type alias. The class is declared in the namespace
+/// chosen by [extern_rust_namespace], if any.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! extern_rust_class {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Ask bindgen to treat types matching this regular expression as opaque,
+/// e.g. `bindgen_opaque_type!("std::.*")`, which can avoid bindgen
+/// failing on types it doesn't understand. Such types are never POD, so
+/// can't also be named in [generate_pod].
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! bindgen_opaque_type {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Ask bindgen to skip the contents of any header file whose path matches
+/// this regular expression, e.g. `bindgen_blocklist_file!(".*/detail/.*")`.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! bindgen_blocklist_file {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Ask bindgen to derive a trait for POD types where it can, e.g.
+/// `bindgen_derive!("Debug")`. Any of `Debug`, `Default`, `Hash`,
+/// `PartialEq`, `Eq`, `PartialOrd` and `Ord` may be requested. `Copy` and
+/// `Clone` may not, since C++ copy constructors must be respected.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! bindgen_derive {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Generate bindings for a function, method or type only when building for
+/// targets matching a `cfg` predicate, for APIs which the C++ declares only
+/// on some platforms (e.g. within `#ifdef _WIN32`). For example,
+/// `cfg!("Widget::win_only_fn", "windows")`. On other targets, the item
+/// isn't generated in either Rust or C++, and it's not an error for it to
+/// be missing from the headers. Rust wrapper functions are also marked
+/// with the corresponding `#[cfg]` attribute.
+///
+/// The predicate is evaluated when bindings are generated. In a `build.rs`
+/// that's for the target being built; elsewhere it's for the host.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! cfg {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Expose the items in a C++ namespace (and those nested within it) in a
+/// differently named Rust mod, e.g. `map_namespace!("a::b::c", "abc")`
+/// makes `a::b::c::Widget` available as `ffi::abc::Widget` rather than
+/// `ffi::a::b::c::Widget`. Use `""` to expose the items directly in the
+/// output mod. Where several directives match, the longest wins.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! map_namespace {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Expose all items directly in the output mod, irrespective of their C++
+/// namespaces, except those moved elsewhere by [map_namespace]. If this
+/// would cause two items to have the same name, those items instead
+/// remain in the mods matching their C++ namespaces.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! flatten_namespaces {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Expose C++ methods with `snake_case` names, so that `Widget::GetValue`
+/// can be called from Rust as `get_value`. The C++ method is still the
+/// one called. If two methods of a type would end up with the same name
+/// (e.g. `GetValue` and `get_value`), or the new name would be a Rust
+/// keyword, those methods keep their original names and a warning is
+/// logged. Free functions, constructors and virtual methods are not
+/// renamed.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! snake_case_methods {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Expose pairs of C++ getters and setters, such as `getWidth() const`
+/// and `setWidth(int)`, or `get_width` and `set_width`, as `width` and
+/// `set_width` in Rust. A getter must take no parameters and return
+/// something; a setter must take one parameter and return nothing. The
+/// documentation of each links to the other. Overloaded and virtual
+/// methods are left alone, as are pairs whose new names would clash with
+/// another method, with a warning. Each pair found is logged at `info`
+/// level, so set `RUST_LOG=autocxx_engine=info` to see a report of what's
+/// been renamed.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! property_accessors {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
+/// Choose the Rust name of a helper which autocxx generates, e.g.
+/// `helper_name!("make_string", "make_cpp_string")`, or
+/// `helper_name!("ns::Widget::make_unique", "create")` for the helpers
+/// generated for each type (`new` and `make_unique`).
+///
+/// If a C++ item already has the name of a helper, the C++ item keeps
+/// its name, and the helper is given a suffixed name such as
+/// `make_unique_autocxx` instead, with a warning. Use this directive
+/// to pick something nicer.
+///
+/// A directive to be included inside
+/// [include_cpp] - see [include_cpp] for general information.
+#[macro_export]
+macro_rules! helper_name {
+    ($($tt:tt)*) => { $crate::usage!{$($tt)*} };
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! usage {
@@ -395,6 +1192,13 @@ pub mod extern_rust {
     /// for instance. This will contribute to an `extern "Rust"` section of the
     /// generated `cxx` bindings, and this type will appear in the C++ header
     /// generated for use in C++.
+    ///
+    /// If the attribute macro is used on an enum whose variants have no fields,
+    /// the C++ header instead gets a matching `enum class`, with the same
+    /// variant names and discriminants. That's represented in Rust by a type of
+    /// the same name within the `ffi` mod, and you can convert to it from your
+    /// enum using `From`, and back again using `TryFrom`. (The latter can fail
+    /// because C++ may use any value of the underlying integer type.)
     pub use autocxx_macro::extern_rust_type;
 
     /// Declare that a given function is a Rust function which is to be exported
@@ -404,9 +1208,63 @@ pub mod extern_rust {
     /// #[extern_rust_function]
     /// pub fn call_me_from_cpp() { }
     /// ```
+    /// Parameters and return types are mapped to C++ in the same way as `cxx`
+    /// maps them, so for example a `&[u8]` parameter becomes a
+    /// `rust::Slice<const uint8_t>` and a `Vec<u32>` return type becomes a
+    /// `rust::Vec<uint32_t>`. C++ types may be referred to via the `ffi` mod,
+    /// for example `&ffi::Point`.
+    ///
+    /// A function may return `Result<T, E>` for any `E` which implements
+    /// `Display`. If it returns an error, C++ sees a `rust::Error` exception
+    /// whose `what()` is the error's message. Alternatively, for C++ code
+    /// which avoids exceptions, use `#[extern_rust_function(expected)]`:
+    /// C++ may then also call `<function>_expected`, which returns an
+    /// `autocxx::Expected<T>` holding either the value or the error message.
+    /// ```
+    /// # use autocxx_macro::extern_rust_function as extern_rust_function;
+    /// #[extern_rust_function(expected)]
+    /// pub fn parse_number(s: &str) -> Result<u32, std::num::ParseIntError> {
+    ///     s.parse()
+    /// }
+    /// ```
+    ///
+    /// To let C++ create instances of a type exposed using
+    /// [`extern_rust_type`], use `#[extern_rust_function(factory)]` on a
+    /// function which returns one, either by value or in a `Box`. C++ may
+    /// then call `make_<type>`, which returns a `rust::Box<type>`.
+    /// ```
+    /// # use autocxx_macro::extern_rust_function as extern_rust_function;
+    /// # use autocxx_macro::extern_rust_type as extern_rust_type;
+    /// #[extern_rust_type]
+    /// pub struct Goat(u32);
+    ///
+    /// #[extern_rust_function(factory)]
+    /// pub fn new_goat(horns: u32) -> Goat {
+    ///     Goat(horns)
+    /// }
+    /// ```
     pub use autocxx_macro::extern_rust_function;
 }
 
+/// The name, offset and size of a field of a C++ type. See
+/// [`field_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldMetadata {
+    /// The name of the field.
+    pub name: &'static str,
+    /// The offset of the field from the start of the type, in bytes,
+    /// equivalent to C++ `offsetof`.
+    pub offset: usize,
+    /// The size of the field in bytes.
+    pub size: usize,
+}
+
+/// Implemented by types for which [`field_metadata`] was requested.
+pub trait FieldMetadataProvider {
+    /// Describes each field of this type, in declaration order.
+    fn field_metadata() -> Vec<FieldMetadata>;
+}
+
 /// Equivalent to [`std::convert::AsMut`], but returns a pinned mutable reference
 /// such that cxx methods can be called on it.
 pub trait PinMut<T>: AsRef<T> {
@@ -414,11 +1272,16 @@ pub trait PinMut<T>: AsRef<T> {
     fn pin_mut(&mut self) -> std::pin::Pin<&mut T>;
 }
 
+pub use iter::IndexedIter;
+pub use refcounted::Handle;
+pub use refcounted::RefCounted;
 pub use value_param::as_copy;
 pub use value_param::as_mov;
 pub use value_param::as_new;
 pub use value_param::ValueParam;
 pub use value_param::ValueParamHandler;
+pub use vector::CxxVectorExt;
+pub use vector::CxxVectorPush;
 
 /// Imports which you're likely to want to use.
 pub mod prelude {
@@ -437,6 +1300,7 @@ pub mod prelude {
     pub use crate::c_void;
     pub use crate::cpp_semantics;
     pub use crate::include_cpp;
+    pub use crate::CxxVectorExt;
     pub use crate::PinMut;
     pub use crate::ValueParam;
     pub use moveit::moveit;
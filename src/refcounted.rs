@@ -0,0 +1,109 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// A C++ type whose lifetime is managed by an intrusive reference count.
+/// autocxx implements this for every type deriving from a base class
+/// given using `refcounted!`, by calling that base class's methods.
+///
+/// # Safety
+///
+/// Implementations must add or release a reference to the object, and
+/// must destroy it only once its last reference is released.
+pub unsafe trait RefCounted {
+    /// Adds a reference to the object.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to a live object.
+    unsafe fn add_ref(obj: *mut Self);
+
+    /// Releases a reference to the object, which may destroy it.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to a live object, and the caller must own one of
+    /// its references.
+    unsafe fn release(obj: *mut Self);
+}
+
+/// An owned reference to a reference counted C++ object. Cloning the
+/// handle adds a reference and dropping it releases one, so the object
+/// lives as long as any handle, or any C++ reference, to it.
+///
+/// Since other handles may refer to the same object, this only offers
+/// shared access to it.
+pub struct Handle<T: RefCounted> {
+    obj: NonNull<T>,
+}
+
+impl<T: RefCounted> Handle<T> {
+    /// Takes ownership of an existing reference to an object, such as
+    /// one returned by a C++ factory function. Returns `None` if `obj` is
+    /// null.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to a live object, and the caller must own one of
+    /// its references, which is transferred to the handle.
+    pub unsafe fn adopt(obj: *mut T) -> Option<Self> {
+        NonNull::new(obj).map(|obj| Self { obj })
+    }
+
+    /// Adds a new reference to an object. Returns `None` if `obj` is null.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must point to a live, reference counted object.
+    pub unsafe fn retain(obj: *mut T) -> Option<Self> {
+        let handle = Self::adopt(obj);
+        if handle.is_some() {
+            T::add_ref(obj);
+        }
+        handle
+    }
+
+    /// Returns a pointer to the object, without affecting its references.
+    pub fn as_ptr(&self) -> *mut T {
+        self.obj.as_ptr()
+    }
+
+    /// Consumes the handle without releasing its reference, which the
+    /// caller becomes responsible for, e.g. by passing it to C++.
+    pub fn into_raw(self) -> *mut T {
+        let obj = self.obj.as_ptr();
+        std::mem::forget(self);
+        obj
+    }
+}
+
+impl<T: RefCounted> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        // SAFETY: our reference keeps the object alive.
+        unsafe { T::add_ref(self.obj.as_ptr()) };
+        Self { obj: self.obj }
+    }
+}
+
+impl<T: RefCounted> Drop for Handle<T> {
+    fn drop(&mut self) {
+        // SAFETY: we own one of the object's references.
+        unsafe { T::release(self.obj.as_ptr()) };
+    }
+}
+
+impl<T: RefCounted> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: our reference keeps the object alive.
+        unsafe { self.obj.as_ref() }
+    }
+}
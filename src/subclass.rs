@@ -50,6 +50,13 @@ pub use autocxx_macro::subclass as is_subclass;
 ///   [`CppSubclass`] for the struct, so it's
 ///   generally easier to use the former option.
 ///
+///   Alternatively, add a third argument of `generic` - for instance,
+///   `subclass!("MyCppSuperclass",AnyBar,generic)`. In this case autocxx
+///   generates the `AnyBar` struct itself, which forwards each virtual
+///   method to any Rust type implementing the generated `AnyBar_methods`
+///   trait. This allows several Rust types to implement the same
+///   C++ class using a single generated C++ subclass.
+///
 /// See [`CppSubclass`] for information about the
 /// multiple steps you need to take to be able to make Rust
 /// subclasses of a C++ class.
@@ -102,20 +109,16 @@ impl<T> CppSubclassRustPeerHolder<T> {
 /// A type showing how the Rust side of a Rust/C++ subclass pair refers to
 /// the C++ side.
 #[doc(hidden)]
+#[derive(Default)]
 pub enum CppSubclassCppPeerHolder<CppPeer: CppSubclassCppPeer> {
+    #[default]
     Empty,
     Owned(Box<UniquePtr<CppPeer>>),
     Unowned(*mut CppPeer),
 }
 
-impl<CppPeer: CppSubclassCppPeer> Default for CppSubclassCppPeerHolder<CppPeer> {
-    fn default() -> Self {
-        CppSubclassCppPeerHolder::Empty
-    }
-}
-
 impl<CppPeer: CppSubclassCppPeer> CppSubclassCppPeerHolder<CppPeer> {
-    fn pin_mut(&mut self) -> Pin<&mut CppPeer> {
+    pub fn pin_mut(&mut self) -> Pin<&mut CppPeer> {
         match self {
             CppSubclassCppPeerHolder::Empty => panic!("Peer not set up"),
             CppSubclassCppPeerHolder::Owned(peer) => peer.pin_mut(),
@@ -126,7 +129,7 @@ impl<CppPeer: CppSubclassCppPeer> CppSubclassCppPeerHolder<CppPeer> {
             },
         }
     }
-    fn get(&self) -> &CppPeer {
+    pub fn get(&self) -> &CppPeer {
         match self {
             CppSubclassCppPeerHolder::Empty => panic!("Peer not set up"),
             CppSubclassCppPeerHolder::Owned(peer) => peer.as_ref(),
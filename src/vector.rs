@@ -0,0 +1,63 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use cxx::{kind::Trivial, vector::VectorElement, CxxVector, ExternType};
+use std::pin::Pin;
+
+/// Convenience methods for a [`CxxVector`], in addition to those such as
+/// `get`, `len` and `iter` which `cxx` provides.
+pub trait CxxVectorExt<T: VectorElement> {
+    /// Whether the vector contains an element equal to `x`.
+    fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq;
+
+    /// Copies the elements into a Rust [`Vec`]. This is available for
+    /// trivial types, such as those given to `generate_pod!`.
+    fn to_vec(&self) -> Vec<T>
+    where
+        T: ExternType<Kind = Trivial> + Clone;
+
+    /// Appends `value` to the vector using its C++ move constructor, which
+    /// leaves `value` in its moved-from state. This is available for any
+    /// C++ type which autocxx finds to be movable and destructible.
+    fn push_moved(self: Pin<&mut Self>, value: Pin<&mut T>)
+    where
+        T: CxxVectorPush;
+}
+
+impl<T: VectorElement> CxxVectorExt<T> for CxxVector<T> {
+    fn contains(&self, x: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|element| element == x)
+    }
+
+    fn to_vec(&self) -> Vec<T>
+    where
+        T: ExternType<Kind = Trivial> + Clone,
+    {
+        self.as_slice().to_vec()
+    }
+
+    fn push_moved(self: Pin<&mut Self>, value: Pin<&mut T>)
+    where
+        T: CxxVectorPush,
+    {
+        T::push_moved(self, value)
+    }
+}
+
+/// Implemented by autocxx for C++ types which can be moved onto the end of
+/// a [`CxxVector`]. Use [`CxxVectorExt::push_moved`] rather than calling
+/// this directly.
+pub trait CxxVectorPush: VectorElement {
+    /// Moves `value` onto the end of `vec`.
+    fn push_moved(vec: Pin<&mut CxxVector<Self>>, value: Pin<&mut Self>);
+}
@@ -0,0 +1,22 @@
+// Copyright 2022 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for testing crates which use autocxx. These are the same
+//! helpers used by autocxx's own integration tests: each test builds
+//! a snippet of C++ and Rust using autocxx and confirms that it compiles
+//! and runs. Pass a checker made by [`make_snapshot_checker`] in order to
+//! also compare the generated Rust and C++ against snapshots kept in your
+//! source tree, so that you can spot any changes to your bindings when
+//! you upgrade autocxx.
+
+pub use autocxx_integration_tests::{
+    directives_from_lists, do_run_test, do_run_test_manual, make_snapshot_checker, run_test,
+    run_test_ex, run_test_expect_fail, run_test_expect_fail_ex, BuilderModifier,
+    BuilderModifierFns, CodeChecker, CodeCheckerFns, TestBuilderContext, TestError,
+    UPDATE_SNAPSHOTS_ENV_VAR,
+};
@@ -287,7 +287,7 @@ fn do_run(matches: ArgMatches, tmp_dir: &TempDir) -> Result<(), std::io::Error>
     match output_path {
         None => print_minimized_case(&concat_path)?,
         Some(output_path) => {
-            std::fs::copy(&concat_path, &PathBuf::from(output_path))?;
+            std::fs::copy(&concat_path, PathBuf::from(output_path))?;
         }
     };
     Ok(())
@@ -426,9 +426,9 @@ fn create_interestingness_test(
         file.write_all(content.as_bytes())?;
     }
 
-    let mut perms = std::fs::metadata(&test_path)?.permissions();
+    let mut perms = std::fs::metadata(test_path)?.permissions();
     perms.set_mode(0o700);
-    std::fs::set_permissions(&test_path, perms)?;
+    std::fs::set_permissions(test_path, perms)?;
     Ok(())
 }
 